@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP INDEX dispatch_queue_created_by_idx;
+DROP INDEX rmbpost_queue_created_by_idx;
+
+ALTER TABLE dispatch_queue
+    DROP COLUMN created_by;
+
+ALTER TABLE rmbpost_queue
+    DROP COLUMN created_by;
\ No newline at end of file
@@ -0,0 +1,9 @@
+-- Add up migration script here
+ALTER TABLE dispatch_queue
+    ADD COLUMN created_by VARCHAR(255);
+
+ALTER TABLE rmbpost_queue
+    ADD COLUMN created_by VARCHAR(255);
+
+CREATE INDEX dispatch_queue_created_by_idx ON dispatch_queue (created_by, created_at);
+CREATE INDEX rmbpost_queue_created_by_idx ON rmbpost_queue (created_by, created_at);
\ No newline at end of file
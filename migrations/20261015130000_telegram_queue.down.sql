@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP TABLE telegram_queue;
\ No newline at end of file
@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE telegram_queue
+(
+    id          SERIAL PRIMARY KEY,
+    sender      VARCHAR(255) NOT NULL,
+    recipient   VARCHAR(255) NOT NULL,
+    telegram_id VARCHAR(255) NOT NULL,
+    tg_type     VARCHAR(255) NOT NULL,
+    status      VARCHAR(255) NOT NULL,
+    error       TEXT,
+    created_at  TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified_at TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
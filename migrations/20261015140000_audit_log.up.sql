@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE TABLE audit_log
+(
+    id          SERIAL PRIMARY KEY,
+    created_at  TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    user_id     INTEGER,
+    action      VARCHAR(255) NOT NULL,
+    target_type VARCHAR(255) NOT NULL,
+    target_id   VARCHAR(255),
+    summary     JSONB        NOT NULL DEFAULT '{}'::jsonb,
+    outcome     TEXT         NOT NULL
+);
+
+CREATE INDEX audit_log_created_at_idx ON audit_log (created_at);
+CREATE INDEX audit_log_user_id_idx ON audit_log (user_id, created_at);
@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE idempotency_keys
+(
+    user_id    INTEGER      NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    scope      VARCHAR(255) NOT NULL,
+    key        VARCHAR(255) NOT NULL,
+    job_id     INTEGER      NOT NULL,
+    created_at TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, scope, key)
+);
+
+CREATE INDEX idempotency_keys_created_at_idx ON idempotency_keys (created_at);
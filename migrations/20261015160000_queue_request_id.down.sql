@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE rmbpost_queue
+    DROP COLUMN request_id;
+
+ALTER TABLE dispatch_queue
+    DROP COLUMN request_id;
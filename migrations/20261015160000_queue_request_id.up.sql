@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE dispatch_queue
+    ADD COLUMN request_id VARCHAR(128);
+
+ALTER TABLE rmbpost_queue
+    ADD COLUMN request_id VARCHAR(128);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+ALTER TABLE dispatch_queue
+    DROP COLUMN warnings;
+
+ALTER TABLE dispatch_content
+    DROP COLUMN source,
+    DROP COLUMN source_format;
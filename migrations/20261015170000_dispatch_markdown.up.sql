@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE dispatch_content
+    ADD COLUMN source_format VARCHAR(16) NOT NULL DEFAULT 'bbcode',
+    ADD COLUMN source TEXT;
+
+ALTER TABLE dispatch_queue
+    ADD COLUMN warnings TEXT[] NOT NULL DEFAULT '{}';
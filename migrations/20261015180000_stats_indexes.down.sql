@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX telegram_queue_created_at_idx;
+DROP INDEX dispatch_content_dispatch_id_idx;
+DROP INDEX dispatches_dispatch_id_idx;
+DROP INDEX dispatch_queue_created_at_idx;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+CREATE INDEX dispatch_queue_created_at_idx ON dispatch_queue (created_at);
+CREATE INDEX dispatches_dispatch_id_idx ON dispatches (dispatch_id);
+CREATE INDEX dispatch_content_dispatch_id_idx ON dispatch_content (dispatch_id, id);
+CREATE INDEX telegram_queue_created_at_idx ON telegram_queue (created_at);
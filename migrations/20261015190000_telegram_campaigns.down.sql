@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE telegram_campaign_recipients;
+DROP TABLE telegram_campaigns;
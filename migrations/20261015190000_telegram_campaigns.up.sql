@@ -0,0 +1,26 @@
+-- Add up migration script here
+CREATE TABLE telegram_campaigns
+(
+    id          SERIAL PRIMARY KEY,
+    name        VARCHAR(255) NOT NULL,
+    sender      VARCHAR(255) NOT NULL,
+    telegram_id VARCHAR(255) NOT NULL,
+    secret_key  VARCHAR(255) NOT NULL,
+    tg_type     VARCHAR(255) NOT NULL,
+    status      VARCHAR(255) NOT NULL,
+    created_by  VARCHAR(255) NOT NULL,
+    created_at  TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified_at TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE telegram_campaign_recipients
+(
+    campaign_id INTEGER      NOT NULL REFERENCES telegram_campaigns (id) ON DELETE CASCADE,
+    position    INTEGER      NOT NULL,
+    recipient   VARCHAR(255) NOT NULL,
+    job_id      INTEGER REFERENCES telegram_queue (id) ON DELETE SET NULL,
+    PRIMARY KEY (campaign_id, position),
+    UNIQUE (campaign_id, recipient)
+);
+
+CREATE INDEX telegram_campaign_recipients_job_id_idx ON telegram_campaign_recipients (job_id);
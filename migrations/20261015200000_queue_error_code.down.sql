@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE rmbpost_queue
+    DROP COLUMN error_code;
+
+ALTER TABLE dispatch_queue
+    DROP COLUMN error_code;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE dispatch_queue
+    ADD COLUMN error_code VARCHAR(255);
+
+ALTER TABLE rmbpost_queue
+    ADD COLUMN error_code VARCHAR(255);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+-- the original spellings aren't kept, so there is nothing to restore
+SELECT 1;
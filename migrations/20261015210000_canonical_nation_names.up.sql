@@ -0,0 +1,37 @@
+-- Add up migration script here
+-- matches utils::name::canonicalize: lowercase, whitespace and underscore runs become one
+-- underscore, none at either end
+CREATE FUNCTION pg_temp.canonicalize(name TEXT) RETURNS TEXT AS $$
+    SELECT btrim(regexp_replace(lower(name), '[[:space:]_]+', '_', 'g'), '_')
+$$ LANGUAGE SQL IMMUTABLE;
+
+UPDATE dispatches
+SET nation = pg_temp.canonicalize(nation)
+WHERE nation <> pg_temp.canonicalize(nation);
+
+UPDATE dispatch_queue
+SET payload = jsonb_set(payload, '{add,nation}', to_jsonb(pg_temp.canonicalize(payload #>> '{add,nation}')))
+WHERE payload #>> '{add,nation}' <> pg_temp.canonicalize(payload #>> '{add,nation}');
+
+UPDATE rmbpost_queue
+SET nation = pg_temp.canonicalize(nation)
+WHERE nation <> pg_temp.canonicalize(nation);
+
+UPDATE telegram_queue
+SET sender = pg_temp.canonicalize(sender), recipient = pg_temp.canonicalize(recipient)
+WHERE sender <> pg_temp.canonicalize(sender) OR recipient <> pg_temp.canonicalize(recipient);
+
+UPDATE telegram_campaigns
+SET sender = pg_temp.canonicalize(sender)
+WHERE sender <> pg_temp.canonicalize(sender);
+
+-- only the first spelling of a recipient on a list is rewritten, so the list stays unique
+UPDATE telegram_campaign_recipients recipients
+SET recipient = pg_temp.canonicalize(recipient)
+WHERE recipient <> pg_temp.canonicalize(recipient)
+    AND NOT EXISTS (
+        SELECT 1 FROM telegram_campaign_recipients earlier
+        WHERE earlier.campaign_id = recipients.campaign_id
+            AND pg_temp.canonicalize(earlier.recipient) = pg_temp.canonicalize(recipients.recipient)
+            AND (earlier.position < recipients.position OR earlier.recipient = pg_temp.canonicalize(recipients.recipient))
+    );
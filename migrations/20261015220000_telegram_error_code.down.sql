@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE telegram_queue
+    DROP COLUMN error_code;
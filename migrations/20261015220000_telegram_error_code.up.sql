@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE telegram_queue
+    ADD COLUMN error_code VARCHAR(255);
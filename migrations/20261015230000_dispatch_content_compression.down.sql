@@ -0,0 +1,13 @@
+-- Add down migration script here
+-- Postgres can't decompress the rows itself, so refuse rather than lose their text.
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM dispatch_content WHERE text IS NULL) THEN
+        RAISE EXCEPTION 'dispatch_content has compressed rows, decompress them before reverting';
+    END IF;
+END $$;
+
+ALTER TABLE dispatch_content
+    DROP CONSTRAINT dispatch_content_text_present,
+    DROP COLUMN text_compressed,
+    ALTER COLUMN text SET NOT NULL;
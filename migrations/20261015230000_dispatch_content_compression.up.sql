@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- Existing rows keep their plain text; new long texts are only written to text_compressed.
+ALTER TABLE dispatch_content
+    ALTER COLUMN text DROP NOT NULL,
+    ADD COLUMN text_compressed BYTEA,
+    ADD CONSTRAINT dispatch_content_text_present
+        CHECK (text IS NOT NULL OR text_compressed IS NOT NULL);
@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS api_keys_user_id_idx;
+DROP INDEX IF EXISTS api_keys_key_idx;
+
+ALTER TABLE api_keys
+    DROP COLUMN revoked_at,
+    DROP COLUMN last_used_at,
+    DROP COLUMN expires_at,
+    DROP COLUMN claims,
+    DROP COLUMN name,
+    ALTER COLUMN created_at TYPE timestamp;
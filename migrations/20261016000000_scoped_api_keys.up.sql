@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- Keys already in the table were stored in plain text and aren't scoped, so they're retired.
+ALTER TABLE api_keys
+    ALTER COLUMN created_at TYPE timestamptz,
+    ADD COLUMN name VARCHAR(100) NOT NULL DEFAULT '',
+    ADD COLUMN claims TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN expires_at TIMESTAMPTZ,
+    ADD COLUMN last_used_at TIMESTAMPTZ,
+    ADD COLUMN revoked_at TIMESTAMPTZ;
+
+UPDATE api_keys SET revoked_at = now();
+
+CREATE UNIQUE INDEX api_keys_key_idx ON api_keys (key);
+CREATE INDEX api_keys_user_id_idx ON api_keys (user_id);
@@ -0,0 +1,10 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS dispatch_queue_group_id_idx;
+
+ALTER TABLE dispatches
+    DROP COLUMN group_id;
+
+ALTER TABLE dispatch_queue
+    DROP COLUMN group_id;
+
+DROP TABLE IF EXISTS dispatch_groups;
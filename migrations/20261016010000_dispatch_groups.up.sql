@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS dispatch_groups (
+    id SERIAL PRIMARY KEY,
+    created_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+ALTER TABLE dispatch_queue
+    ADD COLUMN group_id INTEGER REFERENCES dispatch_groups (id);
+
+ALTER TABLE dispatches
+    ADD COLUMN group_id INTEGER REFERENCES dispatch_groups (id);
+
+CREATE INDEX dispatch_queue_group_id_idx ON dispatch_queue (group_id) WHERE group_id IS NOT NULL;
@@ -0,0 +1,29 @@
+-- Add down migration script here
+-- jobs in a status the old code didn't know about are folded into the nearest one it did
+ALTER TABLE rmbpost_queue
+    DROP COLUMN finished_at,
+    DROP COLUMN started_at,
+    DROP COLUMN claimed_at,
+    ALTER COLUMN status TYPE VARCHAR(255) USING (
+        CASE status
+            WHEN 'succeeded' THEN 'success'
+            WHEN 'failed' THEN 'error'
+            WHEN 'cancelled' THEN 'error'
+            ELSE 'queued'
+        END
+    );
+
+ALTER TABLE dispatch_queue
+    DROP COLUMN finished_at,
+    DROP COLUMN started_at,
+    DROP COLUMN claimed_at,
+    ALTER COLUMN status TYPE VARCHAR(255) USING (
+        CASE status
+            WHEN 'succeeded' THEN 'success'
+            WHEN 'failed' THEN 'failure'
+            WHEN 'cancelled' THEN 'failure'
+            ELSE 'queued'
+        END
+    );
+
+DROP TYPE job_status;
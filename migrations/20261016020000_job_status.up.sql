@@ -0,0 +1,39 @@
+-- Add up migration script here
+CREATE TYPE job_status AS ENUM (
+    'scheduled',
+    'queued',
+    'claimed',
+    'posting',
+    'succeeded',
+    'failed',
+    'cancelled'
+);
+
+ALTER TABLE dispatch_queue
+    ALTER COLUMN status TYPE job_status USING (
+        CASE status
+            WHEN 'success' THEN 'succeeded'
+            WHEN 'failure' THEN 'failed'
+            ELSE status
+        END
+    )::job_status,
+    ADD COLUMN claimed_at TIMESTAMPTZ,
+    ADD COLUMN started_at TIMESTAMPTZ,
+    ADD COLUMN finished_at TIMESTAMPTZ;
+
+-- rmbpost jobs used to fail as 'error'
+ALTER TABLE rmbpost_queue
+    ALTER COLUMN status TYPE job_status USING (
+        CASE status
+            WHEN 'success' THEN 'succeeded'
+            WHEN 'error' THEN 'failed'
+            ELSE status
+        END
+    )::job_status,
+    ADD COLUMN claimed_at TIMESTAMPTZ,
+    ADD COLUMN started_at TIMESTAMPTZ,
+    ADD COLUMN finished_at TIMESTAMPTZ;
+
+UPDATE dispatch_queue SET finished_at = modified_at WHERE status IN ('succeeded', 'failed');
+
+UPDATE rmbpost_queue SET finished_at = modified_at WHERE status IN ('succeeded', 'failed');
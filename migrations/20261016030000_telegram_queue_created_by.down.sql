@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX telegram_queue_created_by_idx;
+
+ALTER TABLE telegram_queue
+    DROP COLUMN created_by;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE telegram_queue
+    ADD COLUMN created_by VARCHAR(255);
+
+CREATE INDEX telegram_queue_created_by_idx ON telegram_queue (created_by, created_at);
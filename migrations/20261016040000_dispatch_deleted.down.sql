@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE dispatches
+    DROP COLUMN deleted_at,
+    DROP COLUMN deleted_by;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE dispatches
+    ADD COLUMN deleted_at TIMESTAMPTZ,
+    ADD COLUMN deleted_by VARCHAR(255);
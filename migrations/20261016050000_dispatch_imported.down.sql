@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE dispatches
+    DROP COLUMN is_imported;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE dispatches
+    ADD COLUMN is_imported BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE telegram_campaigns
+    DROP COLUMN expanded_recipients,
+    DROP COLUMN recipients_from;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE telegram_campaigns
+    ADD COLUMN recipients_from JSONB,
+    ADD COLUMN expanded_recipients INTEGER;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE webhooks
+(
+    id                SERIAL PRIMARY KEY,
+    url               TEXT         NOT NULL,
+    -- kept in the clear, deliveries are signed with it
+    secret            VARCHAR(255) NOT NULL,
+    created_by        VARCHAR(255) NOT NULL,
+    created_at        TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    secret_rotated_at TIMESTAMPTZ
+);
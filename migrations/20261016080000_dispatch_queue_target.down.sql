@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS dispatch_queue_pending_target_idx;
+
+ALTER TABLE dispatch_queue
+    DROP COLUMN target_dispatch_id;
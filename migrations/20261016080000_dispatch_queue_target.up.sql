@@ -0,0 +1,15 @@
+-- Add up migration script here
+ALTER TABLE dispatch_queue
+    ADD COLUMN target_dispatch_id INTEGER GENERATED ALWAYS AS (
+        CASE
+            WHEN type = 'add' THEN NULL
+            ELSE COALESCE(
+                (payload->'edit'->>'id')::INTEGER,
+                (payload->'remove'->>'id')::INTEGER,
+                dispatch_id
+            )
+        END
+    ) STORED;
+
+CREATE INDEX dispatch_queue_pending_target_idx ON dispatch_queue (target_dispatch_id)
+    WHERE status IN ('queued', 'claimed', 'posting');
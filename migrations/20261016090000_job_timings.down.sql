@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS rmbpost_queue_finished_at_idx;
+DROP INDEX IF EXISTS dispatch_queue_finished_at_idx;
+
+ALTER TABLE rmbpost_queue
+    DROP COLUMN executing_at,
+    DROP COLUMN prepared_at;
+
+ALTER TABLE dispatch_queue
+    DROP COLUMN executing_at,
+    DROP COLUMN prepared_at;
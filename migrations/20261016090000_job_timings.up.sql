@@ -0,0 +1,11 @@
+-- Add up migration script here
+ALTER TABLE dispatch_queue
+    ADD COLUMN prepared_at TIMESTAMPTZ,
+    ADD COLUMN executing_at TIMESTAMPTZ;
+
+ALTER TABLE rmbpost_queue
+    ADD COLUMN prepared_at TIMESTAMPTZ,
+    ADD COLUMN executing_at TIMESTAMPTZ;
+
+CREATE INDEX dispatch_queue_finished_at_idx ON dispatch_queue (finished_at);
+CREATE INDEX rmbpost_queue_finished_at_idx ON rmbpost_queue (finished_at);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS rmbpost_queue_group_id_idx;
+
+ALTER TABLE rmbpost_queue
+    DROP COLUMN group_id;
+
+DROP TABLE IF EXISTS rmbpost_groups;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS rmbpost_groups (
+    id SERIAL PRIMARY KEY,
+    created_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+ALTER TABLE rmbpost_queue
+    ADD COLUMN group_id INTEGER REFERENCES rmbpost_groups (id);
+
+CREATE INDEX rmbpost_queue_group_id_idx ON rmbpost_queue (group_id) WHERE group_id IS NOT NULL;
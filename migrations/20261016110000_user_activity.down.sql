@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE users
+    DROP COLUMN disabled_at,
+    DROP COLUMN last_login_at;
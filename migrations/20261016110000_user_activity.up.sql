@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- users.created_at has been there from the start
+ALTER TABLE users
+    ADD COLUMN last_login_at TIMESTAMPTZ,
+    ADD COLUMN disabled_at TIMESTAMPTZ;
@@ -0,0 +1,32 @@
+-- Add down migration script here
+UPDATE dispatch_queue SET status = 'cancelled' WHERE status = 'superseded';
+
+ALTER TABLE dispatch_queue
+    DROP COLUMN superseded_by;
+
+-- enum values can't be dropped, so the type is rebuilt without it; the index filtering on the
+-- status would otherwise be rebuilt against the old type
+DROP INDEX IF EXISTS dispatch_queue_pending_target_idx;
+
+ALTER TYPE job_status RENAME TO job_status_old;
+
+CREATE TYPE job_status AS ENUM (
+    'scheduled',
+    'queued',
+    'claimed',
+    'posting',
+    'succeeded',
+    'failed',
+    'cancelled'
+);
+
+ALTER TABLE dispatch_queue
+    ALTER COLUMN status TYPE job_status USING status::TEXT::job_status;
+
+ALTER TABLE rmbpost_queue
+    ALTER COLUMN status TYPE job_status USING status::TEXT::job_status;
+
+DROP TYPE job_status_old;
+
+CREATE INDEX dispatch_queue_pending_target_idx ON dispatch_queue (target_dispatch_id)
+    WHERE status IN ('queued', 'claimed', 'posting');
@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TYPE job_status ADD VALUE IF NOT EXISTS 'superseded';
+
+-- the job whose edit replaced this one before it was posted
+ALTER TABLE dispatch_queue
+    ADD COLUMN superseded_by INTEGER REFERENCES dispatch_queue (id) ON DELETE SET NULL;
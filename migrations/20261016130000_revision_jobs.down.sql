@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE dispatch_content
+    DROP COLUMN job_id;
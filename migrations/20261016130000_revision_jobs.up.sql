@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- the job that wrote this revision, NULL for imports and revisions from before it was recorded
+ALTER TABLE dispatch_content
+    ADD COLUMN job_id INTEGER REFERENCES dispatch_queue (id) ON DELETE SET NULL;
+
+CREATE INDEX dispatch_content_job_id_idx ON dispatch_content (job_id) WHERE job_id IS NOT NULL;
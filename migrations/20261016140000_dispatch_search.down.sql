@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP TRIGGER dispatch_content_search ON dispatch_content;
+
+DROP FUNCTION dispatch_content_search;
+
+ALTER TABLE dispatch_content
+    DROP COLUMN search;
+
+DROP FUNCTION dispatch_search_vector;
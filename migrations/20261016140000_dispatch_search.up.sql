@@ -0,0 +1,31 @@
+-- Add up migration script here
+-- Title words weigh more than text words. Writers of compressed texts pass the plain text to
+-- dispatch_search_vector themselves; the trigger covers the rest from the text column.
+CREATE FUNCTION dispatch_search_vector(title TEXT, text TEXT) RETURNS TSVECTOR
+    LANGUAGE SQL IMMUTABLE AS $$
+        SELECT setweight(to_tsvector('english', COALESCE(title, '')), 'A')
+            || setweight(to_tsvector('english', COALESCE(text, '')), 'B');
+    $$;
+
+ALTER TABLE dispatch_content
+    ADD COLUMN search TSVECTOR;
+
+CREATE FUNCTION dispatch_content_search() RETURNS TRIGGER
+    LANGUAGE plpgsql AS $$
+        BEGIN
+            IF NEW.search IS NULL THEN
+                NEW.search := dispatch_search_vector(NEW.title, NEW.text);
+            END IF;
+
+            RETURN NEW;
+        END;
+    $$;
+
+CREATE TRIGGER dispatch_content_search
+    BEFORE INSERT ON dispatch_content
+    FOR EACH ROW EXECUTE FUNCTION dispatch_content_search();
+
+-- compressed revisions from before this are only found by their title
+UPDATE dispatch_content SET search = dispatch_search_vector(title, text);
+
+CREATE INDEX dispatch_content_search_idx ON dispatch_content USING GIN (search);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE dispatches
+    DROP COLUMN is_protected,
+    DROP COLUMN protected_at,
+    DROP COLUMN protected_by;
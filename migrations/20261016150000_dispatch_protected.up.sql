@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE dispatches
+    ADD COLUMN is_protected BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN protected_at TIMESTAMPTZ,
+    ADD COLUMN protected_by VARCHAR(255);
@@ -0,0 +1,7 @@
+-- Add up migration script here
+CREATE TABLE telegram_types
+(
+    telegram_id VARCHAR(255) PRIMARY KEY,
+    tg_type     VARCHAR(255) NOT NULL,
+    recorded_at TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
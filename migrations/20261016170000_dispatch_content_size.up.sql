@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE dispatch_content
+    ADD COLUMN size INTEGER;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP INDEX dispatch_content_lower_title_idx;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- new dispatches are checked against the titles of their nation's active dispatches
+CREATE INDEX dispatch_content_lower_title_idx ON dispatch_content (LOWER(title));
@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE nation_presets
+(
+    nation              VARCHAR(255) PRIMARY KEY,
+    default_category    SMALLINT,
+    default_subcategory SMALLINT,
+    title_prefix        TEXT,
+    signature_text      TEXT,
+    updated_by          VARCHAR(255) NOT NULL,
+    updated_at          TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- a subcategory only means something within its category
+    CHECK ((default_category IS NULL) = (default_subcategory IS NULL))
+);
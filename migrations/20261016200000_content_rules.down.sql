@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE rmbpost_queue
+    DROP COLUMN warnings;
+
+DROP TABLE content_rules;
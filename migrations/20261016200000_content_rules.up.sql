@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE TABLE content_rules
+(
+    id          SERIAL PRIMARY KEY,
+    -- what a refusal or warning names, the pattern itself isn't shown to posters
+    name        VARCHAR(255) NOT NULL UNIQUE,
+    pattern     TEXT         NOT NULL,
+    action      VARCHAR(255) NOT NULL,
+    created_by  VARCHAR(255) NOT NULL,
+    created_at  TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified_at TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE rmbpost_queue
+    ADD COLUMN warnings TEXT[] NOT NULL DEFAULT '{}';
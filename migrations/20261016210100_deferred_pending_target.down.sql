@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS dispatch_queue_pending_target_idx;
+
+CREATE INDEX dispatch_queue_pending_target_idx ON dispatch_queue (target_dispatch_id)
+    WHERE status IN ('queued', 'claimed', 'posting');
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- a separate migration, a new enum value can't be used in the transaction that added it
+DROP INDEX IF EXISTS dispatch_queue_pending_target_idx;
+
+CREATE INDEX dispatch_queue_pending_target_idx ON dispatch_queue (target_dispatch_id)
+    WHERE status IN ('queued', 'deferred', 'claimed', 'posting');
@@ -0,0 +1,12 @@
+-- Add up migration script here
+-- views and score of a dispatch as NS reported them when it was sampled
+CREATE TABLE dispatch_metrics
+(
+    id          SERIAL PRIMARY KEY,
+    dispatch_id INTEGER     NOT NULL REFERENCES dispatches (id) ON DELETE CASCADE,
+    views       INTEGER     NOT NULL,
+    score       INTEGER     NOT NULL,
+    sampled_at  TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX dispatch_metrics_dispatch_id_sampled_at_idx ON dispatch_metrics (dispatch_id, sampled_at);
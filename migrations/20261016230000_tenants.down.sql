@@ -0,0 +1,13 @@
+-- Add down migration script here
+ALTER TABLE dispatch_queue
+    DROP COLUMN tenant_id;
+
+ALTER TABLE dispatches
+    DROP COLUMN tenant_id;
+
+ALTER TABLE users
+    DROP COLUMN tenant_id;
+
+DROP TABLE tenant_nations;
+
+DROP TABLE tenants;
@@ -0,0 +1,32 @@
+-- Add up migration script here
+CREATE TABLE tenants
+(
+    id         SERIAL PRIMARY KEY,
+    name       VARCHAR(255) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- everything from before tenants belongs to the first one, which also manages the others
+INSERT INTO tenants (id, name)
+VALUES (1, 'default');
+
+SELECT setval('tenants_id_seq', 1);
+
+-- nations not listed here belong to the default tenant
+CREATE TABLE tenant_nations
+(
+    nation    VARCHAR(255) PRIMARY KEY,
+    tenant_id INTEGER      NOT NULL REFERENCES tenants (id) ON DELETE CASCADE
+);
+
+ALTER TABLE users
+    ADD COLUMN tenant_id INTEGER NOT NULL DEFAULT 1 REFERENCES tenants (id);
+
+ALTER TABLE dispatches
+    ADD COLUMN tenant_id INTEGER NOT NULL DEFAULT 1 REFERENCES tenants (id);
+
+ALTER TABLE dispatch_queue
+    ADD COLUMN tenant_id INTEGER NOT NULL DEFAULT 1 REFERENCES tenants (id);
+
+CREATE INDEX dispatches_tenant_id_idx ON dispatches (tenant_id);
+CREATE INDEX dispatch_queue_tenant_id_idx ON dispatch_queue (tenant_id);
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TYPE job_status ADD VALUE IF NOT EXISTS 'unknown' AFTER 'posting';
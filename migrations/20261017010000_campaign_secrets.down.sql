@@ -0,0 +1,14 @@
+-- Add down migration script here
+ALTER TABLE telegram_campaigns
+    ADD COLUMN secret_key VARCHAR(255);
+
+UPDATE telegram_campaigns SET secret_key = telegram_campaign_secrets.secret_key
+FROM telegram_campaign_secrets
+WHERE telegram_campaign_secrets.campaign_id = telegram_campaigns.id;
+
+UPDATE telegram_campaigns SET secret_key = '' WHERE secret_key IS NULL;
+
+ALTER TABLE telegram_campaigns
+    ALTER COLUMN secret_key SET NOT NULL;
+
+DROP TABLE telegram_campaign_secrets;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- telegram secret keys are kept apart from the campaigns, so nothing reading campaigns to list,
+-- export or report on them can pick one up; only the telegram worker joins them back
+CREATE TABLE telegram_campaign_secrets
+(
+    campaign_id INTEGER PRIMARY KEY REFERENCES telegram_campaigns (id) ON DELETE CASCADE,
+    secret_key  VARCHAR(255) NOT NULL
+);
+
+INSERT INTO telegram_campaign_secrets (campaign_id, secret_key)
+SELECT id, secret_key FROM telegram_campaigns;
+
+ALTER TABLE telegram_campaigns
+    DROP COLUMN secret_key;
@@ -0,0 +1,21 @@
+-- Add down migration script here
+ALTER TABLE audit_log
+    DROP COLUMN tenant_id;
+
+ALTER TABLE webhooks
+    DROP COLUMN tenant_id;
+
+ALTER TABLE telegram_campaigns
+    DROP COLUMN tenant_id;
+
+ALTER TABLE telegram_queue
+    DROP COLUMN tenant_id;
+
+ALTER TABLE dispatch_groups
+    DROP COLUMN tenant_id;
+
+ALTER TABLE rmbpost_groups
+    DROP COLUMN tenant_id;
+
+ALTER TABLE rmbpost_queue
+    DROP COLUMN tenant_id;
@@ -0,0 +1,32 @@
+-- Add up migration script here
+-- everything from before belongs to the default tenant, like the dispatches did
+ALTER TABLE rmbpost_queue
+    ADD COLUMN tenant_id INTEGER NOT NULL DEFAULT 1 REFERENCES tenants (id);
+
+ALTER TABLE rmbpost_groups
+    ADD COLUMN tenant_id INTEGER NOT NULL DEFAULT 1 REFERENCES tenants (id);
+
+ALTER TABLE dispatch_groups
+    ADD COLUMN tenant_id INTEGER NOT NULL DEFAULT 1 REFERENCES tenants (id);
+
+ALTER TABLE telegram_queue
+    ADD COLUMN tenant_id INTEGER NOT NULL DEFAULT 1 REFERENCES tenants (id);
+
+ALTER TABLE telegram_campaigns
+    ADD COLUMN tenant_id INTEGER NOT NULL DEFAULT 1 REFERENCES tenants (id);
+
+ALTER TABLE webhooks
+    ADD COLUMN tenant_id INTEGER NOT NULL DEFAULT 1 REFERENCES tenants (id);
+
+-- the tenant of whoever acted, none for events no user is attributed to, such as lockouts
+ALTER TABLE audit_log
+    ADD COLUMN tenant_id INTEGER REFERENCES tenants (id);
+
+UPDATE audit_log
+SET tenant_id = users.tenant_id
+FROM users
+WHERE users.id = audit_log.user_id;
+
+CREATE INDEX rmbpost_queue_tenant_id_idx ON rmbpost_queue (tenant_id);
+CREATE INDEX telegram_queue_tenant_id_idx ON telegram_queue (tenant_id);
+CREATE INDEX audit_log_tenant_id_idx ON audit_log (tenant_id);
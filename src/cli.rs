@@ -0,0 +1,288 @@
+//! The command line. Without a command, or with `serve`, the app configured by the environment
+//! is served. The other commands are one-off operations for admins on the same configuration,
+//! built from just the pieces they need, without the server or the workers. They print their
+//! result as JSON on stdout, and an error as JSON on stderr with a nonzero exit code, so they
+//! can be scripted.
+
+use serde_json::{Value, json};
+use std::io::BufRead;
+use std::process::ExitCode;
+
+use crate::core::authorization::Claim;
+use crate::core::error::{ConfigError, Error};
+use crate::ns;
+use crate::sync::nations;
+use crate::types::response::{NationVerification, User};
+use crate::workers;
+use crate::{Args, connect};
+
+const USAGE: &str = "usage: eurocore [command]
+
+commands:
+    serve                               serve the app, the default
+    migrate                             run the migrations the database hasn't seen yet
+    user create <username>              create an account, its password read from stdin
+    user grant <username> <claim>...    give an account claims
+    nation verify <nation>              check the stored password of a nation with NS
+    queue requeue-stuck [--older-than-minutes <minutes>]
+                                        hand jobs left queued back to the workers";
+
+/// Minutes a job has to be left queued before `queue requeue-stuck` takes it, unless given.
+const DEFAULT_STUCK_MINUTES: u32 = 30;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Serve,
+    Help,
+    Migrate,
+    UserCreate {
+        username: String,
+    },
+    UserGrant {
+        username: String,
+        claims: Vec<Claim>,
+    },
+    NationVerify {
+        nation: String,
+    },
+    RequeueStuck {
+        older_than_minutes: u32,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Failure {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    App(#[from] Error),
+    #[error("can't read the password: {0}")]
+    Stdin(std::io::Error),
+}
+
+/// What a command prints, and whether it did what was asked, e.g. a nation whose password NS
+/// rejected is printed but fails the command.
+struct Output {
+    result: Value,
+    success: bool,
+}
+
+impl From<Value> for Output {
+    fn from(result: Value) -> Self {
+        Self {
+            result,
+            success: true,
+        }
+    }
+}
+
+/// Runs the command in `args`, the program's arguments after its name.
+pub async fn run(args: impl IntoIterator<Item = String>) -> ExitCode {
+    let args: Vec<String> = args.into_iter().collect();
+
+    let command = match parse(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match command {
+        Command::Serve => match crate::run().await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        },
+        Command::Help => {
+            println!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        command => match execute(command).await {
+            Ok(output) => {
+                println!("{}", output.result);
+
+                if output.success {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", json!({ "error": e.to_string() }));
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+fn parse(args: &[String]) -> Result<Command, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        [] | ["serve"] => Ok(Command::Serve),
+        ["help" | "-h" | "--help"] => Ok(Command::Help),
+        ["migrate"] => Ok(Command::Migrate),
+        ["user", "create", username] => Ok(Command::UserCreate {
+            username: username.to_string(),
+        }),
+        ["user", "grant", username, claims @ ..] if !claims.is_empty() => Ok(Command::UserGrant {
+            username: username.to_string(),
+            claims: claims
+                .iter()
+                .map(|name| {
+                    Claim::ALL
+                        .iter()
+                        .find(|claim| claim.as_str() == *name)
+                        .copied()
+                        .ok_or_else(|| format!("unknown claim `{name}`"))
+                })
+                .collect::<Result<_, _>>()?,
+        }),
+        ["nation", "verify", nation] => Ok(Command::NationVerify {
+            nation: nation.to_string(),
+        }),
+        ["queue", "requeue-stuck"] => Ok(Command::RequeueStuck {
+            older_than_minutes: DEFAULT_STUCK_MINUTES,
+        }),
+        ["queue", "requeue-stuck", "--older-than-minutes", minutes] => Ok(Command::RequeueStuck {
+            older_than_minutes: minutes
+                .parse()
+                .map_err(|_| format!("`{minutes}` isn't a number of minutes"))?,
+        }),
+        _ => Err(format!("unknown command `{}`", args.join(" "))),
+    }
+}
+
+async fn execute(command: Command) -> Result<Output, Failure> {
+    let config = Args::from_env()?;
+
+    match command {
+        Command::Serve | Command::Help => unreachable!("handled by run"),
+        Command::Migrate => {
+            let pool = connect(&config, &config.database_host, config.database_port).await?;
+
+            Ok(json!({ "applied": crate::migrate(&pool).await? }).into())
+        }
+        Command::UserCreate { username } => {
+            let mut password = String::new();
+
+            std::io::stdin()
+                .lock()
+                .read_line(&mut password)
+                .map_err(Failure::Stdin)?;
+
+            let pool = connect(&config, &config.database_host, config.database_port).await?;
+            let users = crate::user_controller(&config, pool)?;
+
+            let (user, _) = users
+                .register(&username, password.trim_end_matches(['\r', '\n']))
+                .await?;
+
+            Ok(json!(User::new(user.id, &user.username)).into())
+        }
+        Command::UserGrant { username, claims } => {
+            let pool = connect(&config, &config.database_host, config.database_port).await?;
+            let users = crate::user_controller(&config, pool)?;
+
+            Ok(json!(users.grant(&username, &claims).await?).into())
+        }
+        Command::NationVerify { nation } => {
+            let client = reqwest::Client::builder()
+                .user_agent(ns::user_agent(&config.user, config.contact.as_deref()))
+                .build()
+                .map_err(ConfigError::from)?;
+            let limiter = crate::ratelimiter(&config);
+            let nation = crate::utils::name::canonicalize(&nation);
+
+            let mut checks = Vec::new();
+
+            for nations in [&config.dispatch_nations, &config.rmbpost_nations] {
+                let nations = nations::new(nations)?;
+
+                checks.push(
+                    match ns::verify(&client, &config.ns_api_url, &limiter, &nations, &nation).await
+                    {
+                        Ok(check) => Some(check),
+                        Err(Error::InvalidNation) => None,
+                        Err(e) => return Err(e.into()),
+                    },
+                );
+            }
+
+            let rmbpost = checks.pop().flatten();
+            let dispatch = checks.pop().flatten();
+
+            let verification =
+                NationVerification::new(&nation, dispatch, rmbpost).ok_or(Error::InvalidNation)?;
+
+            Ok(Output {
+                success: verification.verified,
+                result: json!(verification),
+            })
+        }
+        Command::RequeueStuck { older_than_minutes } => {
+            let pool = connect(&config, &config.database_host, config.database_port).await?;
+            let cutoff = chrono::Utc::now() - chrono::Duration::minutes(older_than_minutes.into());
+
+            let mut requeued = serde_json::Map::new();
+
+            for (pipeline, queue) in workers::QUEUES {
+                requeued.insert(
+                    pipeline.to_string(),
+                    json!(workers::requeue_stuck(&pool, queue, cutoff).await?),
+                );
+            }
+
+            Ok(Value::Object(requeued).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(&args("")), Ok(Command::Serve));
+        assert_eq!(parse(&args("serve")), Ok(Command::Serve));
+        assert_eq!(parse(&args("migrate")), Ok(Command::Migrate));
+        assert_eq!(
+            parse(&args("user create alice")),
+            Ok(Command::UserCreate {
+                username: "alice".to_string()
+            })
+        );
+        assert_eq!(
+            parse(&args("user grant alice admin dispatches.create")),
+            Ok(Command::UserGrant {
+                username: "alice".to_string(),
+                claims: vec![Claim::Admin, Claim::DispatchesCreate],
+            })
+        );
+        assert_eq!(
+            parse(&args("queue requeue-stuck")),
+            Ok(Command::RequeueStuck {
+                older_than_minutes: DEFAULT_STUCK_MINUTES
+            })
+        );
+        assert_eq!(
+            parse(&args("queue requeue-stuck --older-than-minutes 5")),
+            Ok(Command::RequeueStuck {
+                older_than_minutes: 5
+            })
+        );
+
+        assert!(parse(&args("user grant alice")).is_err());
+        assert!(parse(&args("user grant alice superuser")).is_err());
+        assert!(parse(&args("queue requeue-stuck --older-than-minutes soon")).is_err());
+        assert!(parse(&args("serve now")).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! Typed HTTP client for the eurocore API, enabled with the `client` feature.
+//!
+//! The request and response types are the same ones the server uses, so the JSON shapes can't
+//! drift between the two.
+
+pub use crate::ns::dispatch::{EditDispatch, NewDispatch, QueuedDispatchPayload, TextFormat};
+pub use crate::ns::rmbpost::NewRmbPost;
+pub use crate::ns::telegram::{Header, NewCampaign, Params, RecipientSource, TgType};
+pub use crate::types::job::JobStatus;
+pub use crate::types::response::{
+    Dispatch, DispatchStatus, DroppedTelegram, FailedEdit, Login, QueuedTelegram, QueuedTelegrams,
+    RmbPostStatus, TelegramCampaign, TelegramStatus, UnappliedEdits, User,
+};
+pub use crate::types::secret::Secret;
+/// For receivers of webhook deliveries.
+pub use crate::utils::signature::{
+    HEADER as SIGNATURE_HEADER, SignatureError, TOLERANCE_SECS, sign, verify as verify_signature,
+};
+
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClientError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("eurocore returned {status}: {message}")]
+    Api {
+        status: StatusCode,
+        /// stable error code, e.g. `dispatch_not_found`, absent when the body wasn't JSON
+        code: Option<String>,
+        message: String,
+    },
+    #[error("Not logged in")]
+    NoToken,
+}
+
+#[derive(Clone, Debug)]
+pub struct EurocoreClient {
+    base_url: String,
+    client: reqwest::Client,
+    token: Option<String>,
+}
+
+impl EurocoreClient {
+    /// `base_url` is the root of the eurocore instance, e.g. `https://eurocore.example.com`.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+            token: None,
+        }
+    }
+
+    /// Uses an existing JWT instead of logging in.
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Logs in and uses the issued token for all subsequent requests.
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<Login, ClientError> {
+        let login: Login = send(
+            self.request(Method::POST, "/login")
+                .json(&json!({ "username": username, "password": password })),
+        )
+        .await?;
+
+        self.token = Some(login.token.clone());
+
+        Ok(login)
+    }
+
+    pub async fn get_dispatch(&self, id: i32) -> Result<Dispatch, ClientError> {
+        send(self.request(Method::GET, &format!("/dispatches/{id}"))).await
+    }
+
+    pub async fn get_dispatches(&self) -> Result<Vec<Dispatch>, ClientError> {
+        send(self.request(Method::GET, "/dispatches")).await
+    }
+
+    pub async fn create_dispatch(
+        &self,
+        dispatch: &NewDispatch,
+    ) -> Result<DispatchStatus, ClientError> {
+        send(self.authorized(Method::POST, "/dispatches")?.json(dispatch)).await
+    }
+
+    pub async fn edit_dispatch(
+        &self,
+        id: i32,
+        dispatch: &EditDispatch,
+    ) -> Result<DispatchStatus, ClientError> {
+        send(
+            self.authorized(Method::PUT, &format!("/dispatches/{id}"))?
+                .json(dispatch),
+        )
+        .await
+    }
+
+    /// Queues the content of failed edit `job_id` of dispatch `id` again.
+    pub async fn reapply_edit(&self, id: i32, job_id: i32) -> Result<DispatchStatus, ClientError> {
+        send(self.authorized(Method::POST, &format!("/dispatches/{id}/reapply/{job_id}"))?).await
+    }
+
+    pub async fn delete_dispatch(&self, id: i32) -> Result<DispatchStatus, ClientError> {
+        send(self.authorized(Method::DELETE, &format!("/dispatches/{id}"))?).await
+    }
+
+    /// Status of a queued dispatch job.
+    pub async fn get_queue_status(&self, id: i32) -> Result<DispatchStatus, ClientError> {
+        send(self.request(Method::GET, &format!("/queue/dispatches/{id}"))).await
+    }
+
+    pub async fn create_rmbpost(&self, post: &NewRmbPost) -> Result<RmbPostStatus, ClientError> {
+        send(self.authorized(Method::POST, "/rmbposts")?.json(post)).await
+    }
+
+    pub async fn get_rmbpost_status(&self, id: i32) -> Result<RmbPostStatus, ClientError> {
+        send(self.request(Method::GET, &format!("/queue/rmbposts/{id}"))).await
+    }
+
+    pub async fn queue_telegrams(
+        &self,
+        telegrams: Vec<Params>,
+    ) -> Result<QueuedTelegrams, ClientError> {
+        send(
+            self.authorized(Method::POST, "/telegrams")?
+                .json(&telegrams),
+        )
+        .await
+    }
+
+    pub async fn get_telegram_status(&self, id: i32) -> Result<TelegramStatus, ClientError> {
+        send(self.authorized(Method::GET, &format!("/queue/telegrams/{id}"))?).await
+    }
+
+    pub async fn delete_telegram(&self, id: i32) -> Result<(), ClientError> {
+        send_empty(self.authorized(Method::DELETE, &format!("/telegrams/{id}"))?).await
+    }
+
+    pub async fn create_telegram_campaign(
+        &self,
+        campaign: &NewCampaign,
+    ) -> Result<TelegramCampaign, ClientError> {
+        send(
+            self.authorized(Method::POST, "/telegrams/campaigns")?
+                .json(campaign),
+        )
+        .await
+    }
+
+    pub async fn get_telegram_campaign(&self, id: i32) -> Result<TelegramCampaign, ClientError> {
+        send(self.authorized(Method::GET, &format!("/telegrams/campaigns/{id}"))?).await
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, path));
+
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    fn authorized(&self, method: Method, path: &str) -> Result<RequestBuilder, ClientError> {
+        match self.token {
+            Some(_) => Ok(self.request(method, path)),
+            None => Err(ClientError::NoToken),
+        }
+    }
+}
+
+async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, ClientError> {
+    let response = check(request.send().await?).await?;
+
+    Ok(response.json().await?)
+}
+
+async fn send_empty(request: RequestBuilder) -> Result<(), ClientError> {
+    check(request.send().await?).await?;
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: String,
+    message: String,
+}
+
+async fn check(response: reqwest::Response) -> Result<reqwest::Response, ClientError> {
+    let status = response.status();
+
+    if status.is_success() {
+        Ok(response)
+    } else {
+        let text = response.text().await.unwrap_or_default();
+
+        match serde_json::from_str::<ErrorBody>(&text) {
+            Ok(body) => Err(ClientError::Api {
+                status,
+                code: Some(body.code),
+                message: body.message,
+            }),
+            Err(_) => Err(ClientError::Api {
+                status,
+                code: None,
+                message: text,
+            }),
+        }
+    }
+}
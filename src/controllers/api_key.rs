@@ -0,0 +1,260 @@
+use crate::core::error::Error;
+use crate::types::AuthorizedUser;
+use crate::types::request::NewApiKey;
+use crate::types::response::ApiKey;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::fmt::Write;
+
+/// Lets keys be recognised in logs and by secret scanners.
+const PREFIX: &str = "eck_";
+
+/// Random bytes in a key; enough that a plain SHA-256 of the key is a safe thing to store.
+const KEY_BYTES: usize = 32;
+
+const MAX_NAME_LENGTH: usize = 100;
+
+/// The scope of a key that passed every check, see [`Controller::authenticate`].
+#[derive(Debug)]
+pub(crate) struct Grant {
+    pub(crate) id: i32,
+    pub(crate) username: String,
+    pub(crate) claims: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Controller {
+    pool: PgPool,
+}
+
+impl Controller {
+    pub(crate) fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Creates a key carrying `claims`, which must all be claims `user` has. The key itself is
+    /// only returned here; we keep its hash.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn create(
+        &self,
+        user: &AuthorizedUser,
+        new_key: NewApiKey,
+    ) -> Result<ApiKey, Error> {
+        let name = new_key.name.trim();
+
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            return Err(Error::InvalidApiKeyName);
+        }
+
+        if let Some(claim) = ungranted(&user.claims, &new_key.claims) {
+            return Err(Error::ClaimNotGranted(claim.to_string()));
+        }
+
+        if new_key
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return Err(Error::InvalidApiKeyExpiry);
+        }
+
+        let mut claims = new_key.claims;
+        claims.sort();
+        claims.dedup();
+
+        let key = generate();
+
+        let mut api_key = sqlx::query(
+            "INSERT INTO api_keys (key, user_id, name, claims, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, name, claims, expires_at, last_used_at, revoked_at, created_at;",
+        )
+        .bind(hash(&key))
+        .bind(user.id)
+        .bind(name)
+        .bind(&claims)
+        .bind(new_key.expires_at)
+        .map(map_api_key)
+        .fetch_one(&self.pool)
+        .await?;
+
+        api_key.key = Some(key);
+
+        Ok(api_key)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn list(&self, user_id: i32) -> Result<Vec<ApiKey>, Error> {
+        Ok(sqlx::query(
+            "SELECT id, name, claims, expires_at, last_used_at, revoked_at, created_at
+            FROM api_keys
+            WHERE user_id = $1
+            ORDER BY id;",
+        )
+        .bind(user_id)
+        .map(map_api_key)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Revoking is permanent; revoked keys stay listed for audits.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn revoke(&self, user_id: i32, id: i32) -> Result<ApiKey, Error> {
+        match sqlx::query(
+            "UPDATE api_keys SET revoked_at = COALESCE(revoked_at, now())
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, name, claims, expires_at, last_used_at, revoked_at, created_at;",
+        )
+        .bind(id)
+        .bind(user_id)
+        .map(map_api_key)
+        .fetch_optional(&self.pool)
+        .await?
+        {
+            Some(api_key) => Ok(api_key),
+            None => Err(Error::ApiKeyNotFound),
+        }
+    }
+
+    /// Looks `key` up and records the use. Unknown, revoked and expired keys are all rejected.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn authenticate(&self, key: &str) -> Result<Grant, Error> {
+        let row = sqlx::query(
+            "SELECT api_keys.id, api_keys.claims, api_keys.expires_at, api_keys.revoked_at, users.username
+            FROM api_keys
+            JOIN users ON users.id = api_keys.user_id
+            WHERE api_keys.key = $1;",
+        )
+        .bind(hash(key))
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::InvalidApiKey)?;
+
+        if row
+            .get::<Option<chrono::DateTime<Utc>>, _>("revoked_at")
+            .is_some()
+        {
+            return Err(Error::ApiKeyRevoked);
+        }
+
+        if row
+            .get::<Option<chrono::DateTime<Utc>>, _>("expires_at")
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return Err(Error::ApiKeyExpired);
+        }
+
+        let grant = Grant {
+            id: row.get("id"),
+            username: row.get("username"),
+            claims: row.get("claims"),
+        };
+
+        sqlx::query("UPDATE api_keys SET last_used_at = now() WHERE id = $1;")
+            .bind(grant.id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(grant)
+    }
+}
+
+/// The claims a key-authenticated request acts with: the key's scope, minus anything the owner
+/// has lost since the key was created.
+pub(crate) fn scope(grant: &Grant, owner: &[String]) -> Vec<String> {
+    grant
+        .claims
+        .iter()
+        .filter(|claim| owner.contains(claim))
+        .cloned()
+        .collect()
+}
+
+/// The first of `requested` that isn't in `held`.
+fn ungranted<'a>(held: &[String], requested: &'a [String]) -> Option<&'a str> {
+    requested
+        .iter()
+        .find(|claim| !held.contains(claim))
+        .map(String::as_str)
+}
+
+fn generate() -> String {
+    let bytes: [u8; KEY_BYTES] = rand::random();
+
+    format!("{}{}", PREFIX, hex(&bytes))
+}
+
+fn hash(key: &str) -> String {
+    hex(&Sha256::digest(key.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut output, byte| {
+        let _ = write!(output, "{:02x}", byte);
+        output
+    })
+}
+
+fn map_api_key(row: PgRow) -> ApiKey {
+    ApiKey {
+        id: row.get("id"),
+        name: row.get("name"),
+        key: None,
+        claims: row.get("claims"),
+        expires_at: row.get("expires_at"),
+        last_used_at: row.get("last_used_at"),
+        revoked_at: row.get("revoked_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(claims: &[&str]) -> Vec<String> {
+        claims.iter().map(|claim| claim.to_string()).collect()
+    }
+
+    #[test]
+    fn test_ungranted() {
+        let held = claims(&["dispatches.create", "rmbposts.create"]);
+
+        assert_eq!(ungranted(&held, &claims(&["rmbposts.create"])), None);
+        assert_eq!(ungranted(&held, &[]), None);
+        assert_eq!(
+            ungranted(&held, &claims(&["rmbposts.create", "admin"])),
+            Some("admin")
+        );
+    }
+
+    #[test]
+    fn test_scope_drops_claims_the_owner_lost() {
+        let grant = Grant {
+            id: 1,
+            username: "alice".to_string(),
+            claims: claims(&["dispatches.create", "rmbposts.create"]),
+        };
+
+        assert_eq!(
+            scope(&grant, &claims(&["rmbposts.create", "admin"])),
+            claims(&["rmbposts.create"])
+        );
+    }
+
+    #[test]
+    fn test_generated_keys_are_unique_and_hashed() {
+        let (a, b) = (generate(), generate());
+
+        assert!(a.starts_with(PREFIX));
+        assert_eq!(a.len(), PREFIX.len() + KEY_BYTES * 2);
+        assert_ne!(a, b);
+        assert_eq!(hash(&a), hash(&a));
+        assert_ne!(hash(&a), hash(&b));
+        assert_eq!(
+            hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
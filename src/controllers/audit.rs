@@ -0,0 +1,193 @@
+use crate::core::error::Error;
+use crate::types::AuthorizedUser;
+use crate::types::request::AuditQuery;
+use crate::types::response::AuditEntry;
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::{PgPool, Row};
+use tokio::sync::mpsc;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// A state-changing API call, recorded after authorization succeeded.
+#[derive(Debug)]
+pub(crate) struct Event {
+    user_id: Option<i32>,
+    tenant_id: Option<i32>,
+    action: &'static str,
+    target_type: &'static str,
+    target_id: Option<String>,
+    summary: serde_json::Value,
+    outcome: String,
+}
+
+impl Event {
+    pub(crate) fn new(
+        user: &AuthorizedUser,
+        action: &'static str,
+        target_type: &'static str,
+    ) -> Self {
+        Self {
+            user_id: Some(user.id),
+            tenant_id: Some(user.tenant_id),
+            action,
+            target_type,
+            target_id: None,
+            summary: serde_json::Value::Object(Default::default()),
+            outcome: String::new(),
+        }
+    }
+
+    /// An event no authorized user can be attributed to, such as a login lockout.
+    pub(crate) fn anonymous(action: &'static str, target_type: &'static str) -> Self {
+        Self {
+            user_id: None,
+            tenant_id: None,
+            action,
+            target_type,
+            target_id: None,
+            summary: serde_json::Value::Object(Default::default()),
+            outcome: String::new(),
+        }
+    }
+
+    pub(crate) fn target(mut self, target_id: impl ToString) -> Self {
+        self.target_id = Some(target_id.to_string());
+        self
+    }
+
+    /// Short description of the request. Must never contain secrets or full message bodies.
+    pub(crate) fn summary(mut self, summary: serde_json::Value) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    /// Adds the warnings a job was queued with, such as the content rules it matched, to the
+    /// summary.
+    pub(crate) fn warnings(mut self, warnings: &[String]) -> Self {
+        if let Some(summary) = self
+            .summary
+            .as_object_mut()
+            .filter(|_| !warnings.is_empty())
+        {
+            summary.insert("warnings".to_string(), warnings.into());
+        }
+
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Controller {
+    pool: PgPool,
+    tx: mpsc::Sender<Event>,
+}
+
+impl Controller {
+    pub(crate) fn new(pool: PgPool) -> Self {
+        let (tx, rx) = mpsc::channel(256);
+
+        tracing::info!("starting audit writer");
+        tokio::spawn(write_events(pool.clone(), rx));
+
+        Self { pool, tx }
+    }
+
+    /// Records `event` with the outcome of `result`. Persisting happens in the background, so
+    /// this never waits on the database; events are dropped (and logged) if the writer is backed up.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn record<T>(&self, mut event: Event, result: &Result<T, Error>) {
+        event.outcome = match result {
+            Ok(_) => "success".to_string(),
+            Err(e) => e.to_string(),
+        };
+
+        if let Err(e) = self.tx.try_send(event) {
+            tracing::error!("unable to queue audit event: {}", e);
+        }
+    }
+
+    /// Events of users of `tenant`, or every event, anonymous ones included, if none is given.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn list(
+        &self,
+        query: &AuditQuery,
+        tenant: Option<i32>,
+    ) -> Result<Vec<AuditEntry>, Error> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+
+        Ok(sqlx::query(
+            "SELECT
+                audit_log.id,
+                audit_log.created_at,
+                audit_log.user_id,
+                users.username,
+                audit_log.action,
+                audit_log.target_type,
+                audit_log.target_id,
+                audit_log.summary,
+                audit_log.outcome
+            FROM audit_log
+            LEFT JOIN users ON users.id = audit_log.user_id
+            WHERE ($1::TIMESTAMPTZ IS NULL OR audit_log.created_at >= $1)
+            AND ($2::TIMESTAMPTZ IS NULL OR audit_log.created_at < $2)
+            AND ($3::INTEGER IS NULL OR audit_log.user_id = $3)
+            AND ($6::INTEGER IS NULL OR audit_log.tenant_id = $6)
+            ORDER BY audit_log.id DESC
+            LIMIT $4
+            OFFSET $5;",
+        )
+        .bind(query.from)
+        .bind(query.to)
+        .bind(query.user_id)
+        .bind(limit)
+        .bind(query.offset.unwrap_or(0).max(0))
+        .bind(tenant)
+        .map(map_audit_entry)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn write_events(pool: PgPool, mut rx: mpsc::Receiver<Event>) {
+    while let Some(event) = rx.recv().await {
+        if let Err(e) = sqlx::query(
+            "INSERT INTO audit_log
+                (user_id, action, target_type, target_id, summary, outcome, tenant_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7);",
+        )
+        .bind(event.user_id)
+        .bind(event.action)
+        .bind(event.target_type)
+        .bind(&event.target_id)
+        .bind(Json(&event.summary))
+        .bind(&event.outcome)
+        .bind(event.tenant_id)
+        .execute(&pool)
+        .await
+        {
+            tracing::error!("unable to write audit event {:?}: {}", event, e);
+        }
+    }
+}
+
+fn map_audit_entry(row: PgRow) -> AuditEntry {
+    let Json(summary) = row.get("summary");
+
+    AuditEntry {
+        id: row.get("id"),
+        created_at: row.get("created_at"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        action: row.get("action"),
+        target_type: row.get("target_type"),
+        target_id: row.get("target_id"),
+        summary,
+        outcome: row.get("outcome"),
+    }
+}
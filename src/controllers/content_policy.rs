@@ -0,0 +1,329 @@
+//! Rules on what dispatches and RMB posts may say, kept in `content_rules` and managed under
+//! `/admin/content-rules`. Each rule is a pattern matched against the title and text before a
+//! job is queued; `ban` rules refuse the job, `warn` rules let it through with a warning. Posters
+//! only ever see a rule's name, so the pattern doesn't tell them how to get around it.
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::core::error::Error;
+use crate::types::{request, response};
+
+/// Bytes a compiled pattern may take, and its lazy DFA may grow to. The regex crate matches in
+/// linear time, so the size is what a hostile or careless pattern could blow up.
+const SIZE_LIMIT: usize = 1 << 20;
+const DFA_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RuleAction {
+    Ban,
+    Warn,
+}
+
+impl RuleAction {
+    /// Value of the `content_rules.action` column.
+    fn as_str(self) -> &'static str {
+        match self {
+            RuleAction::Ban => "ban",
+            RuleAction::Warn => "warn",
+        }
+    }
+
+    fn from_column(value: &str) -> Self {
+        match value {
+            "warn" => RuleAction::Warn,
+            _ => RuleAction::Ban,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    action: RuleAction,
+    regex: Regex,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ContentPolicy {
+    pool: PgPool,
+    /// the stored rules that compile, swapped out whenever the rules change
+    rules: Arc<RwLock<Vec<Rule>>>,
+}
+
+impl ContentPolicy {
+    /// Loads the stored rules. One that no longer compiles, e.g. after the size limits were
+    /// lowered, is logged and left out until it's fixed.
+    pub(crate) async fn load(pool: PgPool) -> Result<Self, sqlx::Error> {
+        let policy = Self {
+            pool,
+            rules: Arc::new(RwLock::new(Vec::new())),
+        };
+
+        policy.reload().await?;
+
+        Ok(policy)
+    }
+
+    async fn reload(&self) -> Result<(), sqlx::Error> {
+        let mut rules = Vec::new();
+
+        for rule in self.stored().await? {
+            match compile(&rule.pattern) {
+                Ok(regex) => rules.push(Rule {
+                    name: rule.name,
+                    action: rule.action,
+                    regex,
+                }),
+                Err(e) => tracing::error!("content rule {} doesn't compile: {}", rule.name, e),
+            }
+        }
+
+        *self.rules.write().unwrap_or_else(PoisonError::into_inner) = rules;
+
+        Ok(())
+    }
+
+    /// Checks `fields`, e.g. a title and a text, against the rules. Fails naming every `ban`
+    /// rule that matched, otherwise returns a warning for each `warn` rule that did.
+    pub(crate) fn check(&self, fields: &[&str]) -> Result<Vec<String>, Error> {
+        let rules = self.rules.read().unwrap_or_else(PoisonError::into_inner);
+
+        let matched: Vec<&Rule> = rules
+            .iter()
+            .filter(|rule| fields.iter().any(|field| rule.regex.is_match(field)))
+            .collect();
+
+        let banned: Vec<String> = matched
+            .iter()
+            .filter(|rule| rule.action == RuleAction::Ban)
+            .map(|rule| rule.name.clone())
+            .collect();
+
+        if !banned.is_empty() {
+            return Err(Error::ContentPolicyViolation(banned));
+        }
+
+        Ok(matched
+            .iter()
+            .map(|rule| format!("matches content rule `{}`", rule.name))
+            .collect())
+    }
+
+    /// Every stored rule, with why it doesn't compile for those left out of the policy.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn list(&self) -> Result<Vec<response::ContentRule>, Error> {
+        Ok(self
+            .stored()
+            .await?
+            .into_iter()
+            .map(|mut rule| {
+                rule.error = compile(&rule.pattern).err();
+                rule
+            })
+            .collect())
+    }
+
+    async fn stored(&self) -> Result<Vec<response::ContentRule>, sqlx::Error> {
+        sqlx::query(
+            "SELECT id, name, pattern, action, created_by, created_at, modified_at
+            FROM content_rules
+            ORDER BY id;",
+        )
+        .map(map_rule)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn create(
+        &self,
+        username: &str,
+        rule: request::ContentRule,
+    ) -> Result<response::ContentRule, Error> {
+        validate(&rule)?;
+
+        let created = sqlx::query(
+            "INSERT INTO content_rules (name, pattern, action, created_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (name) DO NOTHING
+            RETURNING id, name, pattern, action, created_by, created_at, modified_at;",
+        )
+        .bind(&rule.name)
+        .bind(&rule.pattern)
+        .bind(rule.action.as_str())
+        .bind(username)
+        .map(map_rule)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::ContentRuleExists(rule.name))?;
+
+        self.reload().await?;
+
+        Ok(created)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn update(
+        &self,
+        id: i32,
+        rule: request::ContentRule,
+    ) -> Result<response::ContentRule, Error> {
+        validate(&rule)?;
+
+        let updated = match sqlx::query(
+            "UPDATE content_rules
+            SET name = $2, pattern = $3, action = $4, modified_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            RETURNING id, name, pattern, action, created_by, created_at, modified_at;",
+        )
+        .bind(id)
+        .bind(&rule.name)
+        .bind(&rule.pattern)
+        .bind(rule.action.as_str())
+        .map(map_rule)
+        .fetch_optional(&self.pool)
+        .await
+        {
+            Ok(updated) => updated.ok_or(Error::ContentRuleNotFound)?,
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                return Err(Error::ContentRuleExists(rule.name));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        self.reload().await?;
+
+        Ok(updated)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn delete(&self, id: i32) -> Result<(), Error> {
+        let result = sqlx::query("DELETE FROM content_rules WHERE id = $1;")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::ContentRuleNotFound);
+        }
+
+        Ok(self.reload().await?)
+    }
+}
+
+fn validate(rule: &request::ContentRule) -> Result<(), Error> {
+    if rule.name.trim().is_empty() {
+        return Err(Error::InvalidContentRule("a rule needs a name".to_string()));
+    }
+
+    compile(&rule.pattern).map_err(Error::InvalidContentRule)?;
+
+    Ok(())
+}
+
+/// Patterns ignore case; `(?-i)` turns that off within one.
+fn compile(pattern: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(SIZE_LIMIT)
+        .dfa_size_limit(DFA_SIZE_LIMIT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+fn map_rule(row: PgRow) -> response::ContentRule {
+    response::ContentRule {
+        id: row.get("id"),
+        name: row.get("name"),
+        pattern: row.get("pattern"),
+        action: RuleAction::from_column(row.get("action")),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        modified_at: row.get("modified_at"),
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn policy(rules: &[(&str, &str, RuleAction)]) -> ContentPolicy {
+        ContentPolicy {
+            pool: PgPool::connect_lazy("postgres://localhost/unused").unwrap(),
+            rules: Arc::new(RwLock::new(
+                rules
+                    .iter()
+                    .map(|(name, pattern, action)| Rule {
+                        name: name.to_string(),
+                        action: *action,
+                        regex: compile(pattern).unwrap(),
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let policy = policy(&[
+            ("invite-links", r"discord\.gg/\w+", RuleAction::Ban),
+            ("shouting", r"(?-i)\b[A-Z]{10,}\b", RuleAction::Warn),
+            ("other-regions", r"\bthe north pacific\b", RuleAction::Warn),
+        ]);
+
+        assert_eq!(
+            policy.check(&["News", "all quiet"]).unwrap(),
+            Vec::<String>::new()
+        );
+
+        assert_eq!(
+            policy
+                .check(&["News", "join The North Pacific, NOWNOWNOWNOW"])
+                .unwrap(),
+            vec![
+                "matches content rule `shouting`",
+                "matches content rule `other-regions`"
+            ]
+        );
+
+        // a ban wins over any warnings, and only its name is given
+        match policy.check(&["Join us at DISCORD.GG/abc", "the north pacific"]) {
+            Err(Error::ContentPolicyViolation(rules)) => assert_eq!(rules, vec!["invite-links"]),
+            other => panic!("expected a violation, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_compile_limits() {
+        assert!(compile(r"discord\.gg").is_ok());
+        assert!(compile(r"(unclosed").is_err());
+
+        // compiles to far more than the size limit
+        assert!(compile(r"(\w{500}){500}").is_err());
+
+        let invalid = validate(&request::ContentRule {
+            name: "huge".to_string(),
+            pattern: r"((a{100}){100}){100}".to_string(),
+            action: RuleAction::Ban,
+        });
+        assert!(matches!(invalid, Err(Error::InvalidContentRule(_))));
+    }
+
+    #[test]
+    fn test_no_catastrophic_backtracking() {
+        // exponential for a backtracking engine, linear here
+        let regex = compile(r"^(a+)+$").unwrap();
+        let text = format!("{}b", "a".repeat(100_000));
+
+        let started = Instant::now();
+        assert!(!regex.is_match(&text));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
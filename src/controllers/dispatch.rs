@@ -1,65 +1,488 @@
+use crate::controllers::content_policy::ContentPolicy;
+use crate::controllers::idempotency::{self, Submitted};
+use crate::controllers::quota::Quota;
+use crate::controllers::{tenant, timings};
+use crate::core::authorization::Claim;
+use crate::core::db::{DbPools, Pool};
 use crate::core::error::{ConfigError, Error};
-use crate::ns::dispatch::{Command, EditDispatch, IntermediateDispatch, NewDispatch};
-use crate::sync::{nations, ratelimiter};
+use crate::ns;
+use crate::ns::dispatch::{
+    self, Command, EditDispatch, FactbookCategory, IntermediateDispatch, NewDispatch,
+    NewDispatchGroup, Operation, PublicDispatchResponse, QueueSummary, QueuedDispatchPayload,
+    TextFormat,
+};
+use crate::sync::ratelimiter::{RestrictedAction, Target};
+use crate::sync::{events, nations, ratelimiter};
+use crate::types::job::JobStatus;
+use crate::types::request::{
+    self, DispatchMetricsQuery, DispatchSearchQuery, DispatchStatsGroup, DispatchStatsQuery,
+    ImportDispatch, JobKind, LargestDispatchesQuery, RequestId, TimingsQuery, TopDispatchesQuery,
+};
 use crate::types::response::DispatchStatus;
+use crate::types::response::{NationStatus, QueueEstimate};
 use crate::types::{AuthorizedUser, response};
+use crate::utils::{compress, name};
 use crate::workers;
-use serde::Serialize;
-use sqlx::PgPool;
+use futures_util::StreamExt;
+use quick_xml::de;
+use reqwest::StatusCode;
+use sqlx::PgConnection;
 use sqlx::Row;
 use sqlx::postgres::PgRow;
 use sqlx::types::Json;
 use tokio::sync::{mpsc, oneshot};
 
+/// Most nations one `POST /dispatches/multi` may post as.
+const MAX_GROUP_SIZE: usize = 10;
+
+const DEFAULT_SEARCH_PAGE_SIZE: i64 = 20;
+const MAX_SEARCH_PAGE_SIZE: i64 = 100;
+
+const DEFAULT_LARGEST_LIMIT: i64 = 10;
+const MAX_LARGEST_LIMIT: i64 = 100;
+
+const DEFAULT_TOP_HOURS: i32 = 24;
+const DEFAULT_TOP_LIMIT: i64 = 10;
+const MAX_TOP_LIMIT: i64 = 100;
+
+/// `ts_headline` options for search results: the whole title, and a few passages of the text.
+const TITLE_HIGHLIGHT: &str = "StartSel=<mark>, StopSel=</mark>, HighlightAll=true";
+const TEXT_HIGHLIGHT: &str = "StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=30, \
+     MinWords=10, FragmentDelimiter=\" ... \"";
+
+/// Revisions read ahead of an export's receiver.
+const EXPORT_BUFFER: usize = 64;
+
+/// Ownership information needed before a dispatch can be modified.
+struct DispatchMeta {
+    nation: String,
+    is_active: bool,
+    /// author of the first revision of the dispatch
+    owner: Option<String>,
+    /// id of the latest `dispatch_content` row, and who wrote it when
+    revision: i32,
+    revised_by: String,
+    revised_at: chrono::DateTime<chrono::Utc>,
+    is_protected: bool,
+    /// the admin who protected the dispatch, and when
+    protected_by: Option<String>,
+    protected_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct Controller {
-    pool: PgPool,
-    tx: mpsc::Sender<Command>,
+    url: String,
+    client: reqwest::Client,
+    pools: DbPools,
+    tx: workers::Handle<Command>,
+    limiter: ratelimiter::Sender,
+    nations: nations::Sender,
+    quota: Quota,
+    policy: ContentPolicy,
+    /// refuse edits of protected dispatches, not only their deletion
+    protect_edits: bool,
+    /// share of NS's text length limit a revision may use before its job warns
+    size_warn_utilization: f64,
+    /// refuse new dispatches titled like an active one of the same nation
+    unique_titles: bool,
 }
 
 impl Controller {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
-        user: &str,
+        user_agent: &str,
         url: &str,
-        pool: PgPool,
+        pools: DbPools,
         limiter: ratelimiter::Sender,
         nations: nations::Sender,
+        events: events::Sender,
+        quota: Quota,
+        policy: ContentPolicy,
+        recovery: workers::Recovery,
+        channel: workers::Channel,
+        concurrency: usize,
+        protect_edits: bool,
+        size_warn_utilization: f64,
+        unique_titles: bool,
     ) -> Result<Self, ConfigError> {
-        let (tx, mut client) = workers::dispatch::new(user, url, pool.clone(), limiter, nations)?;
+        let tx = workers::supervise("dispatch", channel.send_timeout, {
+            let (user_agent, url) = (user_agent.to_string(), url.to_string());
+            let pool = pools.get(Pool::Primary).clone();
+            let (limiter, nations) = (limiter.clone(), nations.clone());
+
+            move |activity| {
+                let (tx, mut client) = workers::dispatch::new(
+                    &user_agent,
+                    &url,
+                    pool.clone(),
+                    limiter.clone(),
+                    nations.clone(),
+                    events.clone(),
+                    recovery,
+                    concurrency,
+                    channel.capacity,
+                    activity,
+                )?;
+
+                Ok((tx, async move { client.run().await }))
+            }
+        })?;
+
+        Ok(Self {
+            url: url.to_string(),
+            client: reqwest::Client::builder().user_agent(user_agent).build()?,
+            pools,
+            tx,
+            limiter,
+            nations,
+            quota,
+            policy,
+            protect_edits,
+            size_warn_utilization,
+            unique_titles,
+        })
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn nations(&self) -> Result<Vec<String>, Error> {
+        let mut nations = self.nations.list_nations().await?;
+
+        nations.sort();
+
+        Ok(nations)
+    }
+
+    pub(crate) fn limits(&self) -> response::DispatchLimits {
+        response::DispatchLimits {
+            max_text_length: dispatch::MAX_TEXT_LENGTH,
+            max_group_size: MAX_GROUP_SIZE,
+            text_formats: TextFormat::ALL,
+            scheduled_posts: false,
+        }
+    }
+
+    /// Commands waiting for the worker, and how many its channel holds.
+    pub(crate) fn channel_occupancy(&self) -> (usize, usize) {
+        self.tx.occupancy()
+    }
+
+    pub(crate) fn quota(&self, user: &AuthorizedUser) -> response::QuotaLimits {
+        self.quota.limits(user)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn inspect(&self, nation: &str) -> Result<QueueSummary, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        let command = Command::new(
+            Operation::Inspect {
+                nation: nation.to_string(),
+            },
+            tx,
+        );
+
+        self.tx.send(command).await?;
+
+        match rx.await {
+            Ok(dispatch::Response::Inspect(summary)) => Ok(summary),
+            Ok(_) => Err(Error::Internal),
+            Err(e) => {
+                tracing::error!("received error: {}", e);
+
+                Err(Error::Internal)
+            }
+        }
+    }
+
+    /// When job `job_id` is likely to be done, going by the worker's queue. `None` once the job
+    /// isn't waiting in it. It's only an estimate, so a worker that can't be asked leaves it out
+    /// instead of failing the request.
+    #[tracing::instrument(skip_all)]
+    async fn estimated_completion(&self, job_id: i32) -> Option<chrono::DateTime<chrono::Utc>> {
+        let (tx, rx) = oneshot::channel();
+
+        if let Err(e) = self
+            .tx
+            .send(Command::new(Operation::Estimate { job_id }, tx))
+            .await
+        {
+            tracing::warn!(job_id, "couldn't ask for an estimate: {}", e);
+
+            return None;
+        }
+
+        match rx.await {
+            Ok(dispatch::Response::Estimate(wait)) => {
+                Some(chrono::Utc::now() + chrono::Duration::from_std(wait?).ok()?)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                tracing::error!("received error: {}", e);
+
+                None
+            }
+        }
+    }
 
-        tracing::info!("starting dispatch client");
-        tokio::spawn(async move { client.run().await });
+    /// What the dispatch worker is doing, answered even while it's busy posting.
+    pub(crate) fn worker(&self) -> response::WorkerStatus {
+        self.tx.status()
+    }
 
-        Ok(Self { pool, tx })
+    /// Whether an admin paused the dispatch pipeline, which holds back the metrics collector too.
+    pub(crate) fn is_paused(&self) -> bool {
+        self.tx.is_paused()
     }
 
-    // TODO: refactor this to take a more specific type than anything that implements Serialize
+    /// Pauses, resumes or reports on the dispatch worker.
     #[tracing::instrument(skip_all)]
-    async fn queue<T: Serialize>(
+    pub(crate) async fn control(
         &self,
-        action: &str,
-        payload: Json<T>,
-    ) -> Result<DispatchStatus, Error> {
-        Ok(sqlx::query(
-            "INSERT INTO dispatch_queue (type, payload, status) VALUES ($1, $2, 'queued')
+        control: workers::Control,
+    ) -> Result<response::PipelineStatus, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .send(Command::new(Operation::Control(control), tx))
+            .await?;
+
+        match rx.await {
+            Ok(dispatch::Response::Pipeline(status)) => Ok(status),
+            Ok(_) => Err(Error::Internal),
+            Err(e) => {
+                tracing::error!("received error: {}", e);
+
+                Err(Error::Internal)
+            }
+        }
+    }
+
+    /// Checks the stored password of `nation` with NS, see [`ns::verify`]. Returns `None` if
+    /// `nation` isn't a dispatch nation.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn verify(
+        &self,
+        nation: &str,
+    ) -> Result<Option<response::CredentialCheck>, Error> {
+        let nation = name::canonicalize(nation);
+
+        match ns::verify(
+            &self.client,
+            &self.url,
+            &self.limiter,
+            &self.nations,
+            &nation,
+        )
+        .await
+        {
+            Ok(check) => Ok(Some(check)),
+            Err(Error::InvalidNation) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Re-checks the password of `nation` with NS and clears its unhealthy flag if it works.
+    /// With `ping` false the flag is cleared without asking NS. Returns false if `nation` isn't
+    /// a dispatch nation.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn revalidate(&self, nation: &str, ping: bool) -> Result<bool, Error> {
+        let Some(nation) = self
+            .nations()
+            .await?
+            .into_iter()
+            .find(|configured| *configured == name::canonicalize(nation))
+        else {
+            return Ok(false);
+        };
+
+        if !ping {
+            self.nations.mark_healthy(&nation).await?;
+
+            return Ok(true);
+        }
+
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .send(Command::new(Operation::Revalidate { nation }, tx))
+            .await?;
+
+        match rx.await {
+            Ok(dispatch::Response::Success) => Ok(true),
+            Ok(dispatch::Response::Error(e)) => Err(e),
+            Ok(_) => Err(Error::Internal),
+            Err(e) => {
+                tracing::error!("received error: {}", e);
+
+                Err(Error::Internal)
+            }
+        }
+    }
+
+    /// Refuses jobs for a nation whose password NS has rejected, rather than queueing them to fail.
+    async fn check_credentials(&self, nation: &str) -> Result<(), Error> {
+        if self.nations.is_healthy(nation).await? {
+            Ok(())
+        } else {
+            Err(Error::CredentialUnhealthy(nation.to_string()))
+        }
+    }
+
+    /// Restricted action availability and queued dispatches for every dispatch nation.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn nation_status(&self) -> Result<Vec<NationStatus>, Error> {
+        let mut statuses = Vec::new();
+
+        for nation in self.nations().await? {
+            let summary = self.inspect(&nation).await?;
+
+            statuses.push(NationStatus {
+                restricted_ready_in: self
+                    .limiter
+                    .peek_queued(Target::restricted(&nation, RestrictedAction::Dispatch))
+                    .await
+                    .as_secs_f64()
+                    .ceil() as u64,
+                healthy: self.nations.is_healthy(&nation).await?,
+                queued_dispatches: Some(summary.for_nation),
+                queued_rmbposts: None,
+                nation,
+            });
+        }
+
+        Ok(statuses)
+    }
+
+    /// Rough estimate of when a job submitted now would start. Adds are bound by the nation's
+    /// restricted action cooldown, behind the adds the worker holds reservations for; edits and
+    /// removals by the worker working through its queue.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn estimate(
+        &self,
+        nation: &str,
+        restricted: bool,
+    ) -> Result<QueueEstimate, Error> {
+        let nation = &name::canonicalize(nation);
+
+        if !self.nations().await?.iter().any(|name| name == nation) {
+            return Err(Error::InvalidNation);
+        }
+
+        let summary = self.inspect(nation).await?;
+
+        let wait = if restricted {
+            self.limiter
+                .peek_queued(Target::restricted(nation, RestrictedAction::Dispatch))
+                .await
+        } else {
+            self.limiter.peek(Target::Standard).await + workers::PERIOD * summary.total as u32
+        };
+
+        Ok(QueueEstimate {
+            nation: nation.to_string(),
+            queue_position: summary.total + 1,
+            pending_for_nation: summary.for_nation,
+            estimated_start_at: chrono::Utc::now()
+                + chrono::Duration::from_std(wait).map_err(|_| Error::Internal)?,
+        })
+    }
+
+    /// Stores the job. With an idempotency key that was already used, nothing is stored and the
+    /// original job is returned instead.
+    #[tracing::instrument(skip_all)]
+    async fn queue(
+        &self,
+        user: &AuthorizedUser,
+        payload: QueuedDispatchPayload,
+        warnings: &[String],
+        key: Option<&idempotency::Key>,
+        request_id: &RequestId,
+    ) -> Result<Submitted<DispatchStatus>, Error> {
+        if let Some(job_id) = idempotency::previous_job(key, self.pools.get(Pool::Primary)).await? {
+            return Ok(Submitted::Replayed(
+                self.fetch_status(job_id, Pool::Primary).await?,
+            ));
+        }
+
+        let mut transaction = self.pools.get(Pool::Primary).begin().await?;
+
+        self.quota
+            .check(&mut transaction, "dispatch_queue", user, 1)
+            .await?;
+
+        if let Some((id, actions)) = payload.blocked_by() {
+            check_pending(&mut transaction, id, actions).await?;
+        }
+
+        let status = sqlx::query(
+            "INSERT INTO dispatch_queue (type, payload, status, created_by, request_id, warnings, tenant_id) VALUES ($1, $2, 'queued', $3, $4, $5, $6)
             RETURNING
                 id,
                 type AS action,
                 status,
                 dispatch_id,
                 error,
+                error_code,
+                warnings,
                 created_at,
-                modified_at;",
+                claimed_at,
+                started_at,
+                prepared_at,
+                executing_at,
+                finished_at,
+                modified_at,
+                superseded_by,
+                NULL::INTEGER AS revision_id,
+                NULL::INTEGER AS revision_size;",
         )
-        .bind(action)
-        .bind(payload)
-        .map(map_dispatch_status)
-        .fetch_one(&self.pool)
-        .await?)
+        .bind(payload.action())
+        .bind(Json(payload))
+        .bind(&user.username)
+        .bind(&request_id.0)
+        .bind(warnings)
+        .bind(user.tenant_id)
+        .map(|row: PgRow| map_dispatch_status(row, self.size_warn_utilization))
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        if let Some(job_id) =
+            idempotency::commit(key, transaction, self.pools.get(Pool::Primary), status.id).await?
+        {
+            return Ok(Submitted::Replayed(
+                self.fetch_status(job_id, Pool::Primary).await?,
+            ));
+        }
+
+        Ok(Submitted::Created(status))
     }
 
+    /// Read from the replica, so a job queued a moment ago may not be found yet. Jobs of tenants
+    /// other than `tenant` aren't found.
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn get_status(&self, id: i32) -> Result<response::DispatchStatus, Error> {
+    pub(crate) async fn get_status(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+    ) -> Result<response::DispatchStatus, Error> {
+        let mut status = self.fetch_scoped_status(id, tenant, Pool::Read).await?;
+
+        if !status.status.is_finished() {
+            status.estimated_completion_at = self.estimated_completion(id).await;
+        }
+
+        Ok(status)
+    }
+
+    async fn fetch_status(&self, id: i32, pool: Pool) -> Result<response::DispatchStatus, Error> {
+        self.fetch_scoped_status(id, None, pool).await
+    }
+
+    async fn fetch_scoped_status(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+        pool: Pool,
+    ) -> Result<response::DispatchStatus, Error> {
         match sqlx::query(
             "SELECT
                 id,
@@ -67,14 +490,26 @@ impl Controller {
                 status,
                 dispatch_id,
                 error,
+                error_code,
+                warnings,
                 created_at,
-                modified_at
+                claimed_at,
+                started_at,
+                prepared_at,
+                executing_at,
+                finished_at,
+                modified_at,
+                superseded_by,
+                (SELECT id FROM dispatch_content WHERE job_id = dispatch_queue.id) AS revision_id,
+                (SELECT size FROM dispatch_content WHERE job_id = dispatch_queue.id) AS revision_size
             FROM dispatch_queue
-            WHERE id = $1;",
+            WHERE id = $1
+            AND ($2::INTEGER IS NULL OR tenant_id = $2);",
         )
         .bind(id)
-        .map(map_dispatch_status)
-        .fetch_one(&self.pool)
+        .bind(tenant)
+        .map(|row: PgRow| map_dispatch_status(row, self.size_warn_utilization))
+        .fetch_one(self.pools.get(pool))
         .await
         {
             Ok(status) => Ok(status),
@@ -83,81 +518,266 @@ impl Controller {
         }
     }
 
+    /// Reads back what was submitted for a job. `None` if the stored payload can't be mapped to a
+    /// `QueuedDispatchPayload`, which is only the case for some jobs queued by older versions.
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn get_nation(&self, dispatch_id: i32) -> Result<String, Error> {
+    pub(crate) async fn get_payload(
+        &self,
+        job_id: i32,
+    ) -> Result<Option<QueuedDispatchPayload>, Error> {
         match sqlx::query(
-            "SELECT nation FROM dispatches WHERE dispatch_id = $1 AND is_active = TRUE;",
+            "SELECT type AS action, dispatch_id, payload FROM dispatch_queue WHERE id = $1;",
         )
-        .bind(dispatch_id)
-        .map(|row: PgRow| row.get("nation"))
-        .fetch_one(&self.pool)
+        .bind(job_id)
+        .map(|row: PgRow| {
+            let action: String = row.get("action");
+            let Json(payload): Json<serde_json::Value> = row.get("payload");
+
+            QueuedDispatchPayload::from_stored(&action, row.get("dispatch_id"), payload)
+        })
+        .fetch_one(self.pools.get(Pool::Primary))
         .await
         {
-            Ok(nation) => Ok(nation),
-            Err(sqlx::Error::RowNotFound) => Err(Error::DispatchNotFound),
+            Ok(payload) => Ok(payload),
+            Err(sqlx::Error::RowNotFound) => Err(Error::JobNotFound),
             Err(e) => Err(Error::Sql(e)),
         }
     }
 
+    /// Dispatches of tenants other than `tenant` aren't found.
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn get_one(self, dispatch_id: i32) -> Result<response::Dispatch, Error> {
+    async fn get_dispatch_meta(
+        &self,
+        dispatch_id: i32,
+        tenant: i32,
+    ) -> Result<DispatchMeta, Error> {
         match sqlx::query(
             "SELECT
-                dispatches.dispatch_id,
                 dispatches.nation,
-                dispatch_content.category,
-                dispatch_content.subcategory,
-                dispatch_content.title,
-                dispatch_content.text,
-                dispatch_content.created_by,
-                dispatch_content.created_at as created_at
+                (
+                    SELECT created_by FROM dispatch_content
+                    WHERE dispatch_content.dispatch_id = dispatches.id
+                    ORDER BY dispatch_content.id ASC
+                    LIMIT 1
+                ) AS owner,
+                dispatches.is_active,
+                dispatches.is_protected,
+                dispatches.protected_by,
+                dispatches.protected_at,
+                latest.id AS revision,
+                latest.created_by AS revised_by,
+                latest.created_at AS revised_at
             FROM dispatches
-            JOIN
-                dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+            JOIN LATERAL (
+                SELECT id, created_by, created_at FROM dispatch_content
+                WHERE dispatch_content.dispatch_id = dispatches.id
+                ORDER BY dispatch_content.id DESC
+                LIMIT 1
+            ) latest ON TRUE
             WHERE dispatches.dispatch_id = $1
-            AND dispatches.is_active = TRUE;",
+            AND dispatches.tenant_id = $2;",
         )
         .bind(dispatch_id)
-        .map(map_dispatch)
-        .fetch_one(&self.pool)
+        .bind(tenant)
+        .map(|row: PgRow| DispatchMeta {
+            nation: row.get("nation"),
+            is_active: row.get("is_active"),
+            owner: row.get("owner"),
+            revision: row.get("revision"),
+            revised_by: row.get("revised_by"),
+            revised_at: row.get("revised_at"),
+            is_protected: row.get("is_protected"),
+            protected_by: row.get("protected_by"),
+            protected_at: row.get("protected_at"),
+        })
+        .fetch_one(self.pools.get(Pool::Primary))
         .await
         {
-            Ok(dispatch) => Ok(dispatch),
+            Ok(meta) if !meta.is_active => Err(Error::DispatchInactive),
+            Ok(meta) => Ok(meta),
             Err(sqlx::Error::RowNotFound) => Err(Error::DispatchNotFound),
             Err(e) => Err(Error::Sql(e)),
         }
     }
 
+    /// Cheap validator for the stored dispatches, or a single dispatch if `dispatch_id` is given.
+    /// Job counts per nation, author or category. Edits and removals don't carry the nation or
+    /// category in their payload, so those are taken from the dispatch they target.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn stats(
+        &self,
+        query: &DispatchStatsQuery,
+        tenant_id: i32,
+    ) -> Result<Vec<response::DispatchStats>, Error> {
+        let key = match query.group_by {
+            DispatchStatsGroup::Nation => {
+                "COALESCE(dispatch_queue.payload->'add'->>'nation', dispatch_queue.payload->>'nation', dispatches.nation)"
+            }
+            DispatchStatsGroup::User => "dispatch_queue.created_by",
+            DispatchStatsGroup::Category => {
+                "COALESCE(dispatch_queue.payload->'add'->>'category', dispatch_queue.payload->'edit'->'params'->>'category', dispatch_queue.payload->>'category', latest.category::TEXT)"
+            }
+        };
+
+        Ok(sqlx::query(&format!(
+            "SELECT
+                {key} AS key,
+                COUNT(*) FILTER (WHERE dispatch_queue.type = 'add' AND dispatch_queue.status = 'succeeded') AS adds,
+                COUNT(*) FILTER (WHERE dispatch_queue.type = 'edit' AND dispatch_queue.status = 'succeeded') AS edits,
+                COUNT(*) FILTER (WHERE dispatch_queue.type = 'delete' AND dispatch_queue.status = 'succeeded') AS deletes,
+                COUNT(*) FILTER (WHERE dispatch_queue.status = 'failed') AS failures
+            FROM dispatch_queue
+            LEFT JOIN dispatches ON dispatches.dispatch_id = COALESCE(
+                (dispatch_queue.payload->'edit'->>'id')::INTEGER,
+                (dispatch_queue.payload->'remove'->>'id')::INTEGER,
+                dispatch_queue.dispatch_id
+            )
+            LEFT JOIN LATERAL (
+                SELECT category FROM dispatch_content
+                WHERE dispatch_content.dispatch_id = dispatches.id
+                ORDER BY dispatch_content.id DESC
+                LIMIT 1
+            ) latest ON TRUE
+            WHERE ($1::TIMESTAMPTZ IS NULL OR dispatch_queue.created_at >= $1)
+            AND ($2::TIMESTAMPTZ IS NULL OR dispatch_queue.created_at < $2)
+            AND dispatch_queue.tenant_id = $3
+            GROUP BY 1
+            ORDER BY 1 NULLS LAST;"
+        ))
+        .bind(query.from)
+        .bind(query.to)
+        .bind(tenant_id)
+        .map(|row: PgRow| response::DispatchStats {
+            key: row.get("key"),
+            adds: row.get("adds"),
+            edits: row.get("edits"),
+            deletes: row.get("deletes"),
+            failures: row.get("failures"),
+        })
+        .fetch_all(self.pools.get(Pool::Read))
+        .await?)
+    }
+
+    /// Active dispatches whose latest revision is longest, as NS counts it. Revisions recorded
+    /// before sizes were aren't ranked.
     #[tracing::instrument(skip_all)]
-    async fn get_all(&self) -> Result<Vec<response::Dispatch>, Error> {
+    pub(crate) async fn largest(
+        &self,
+        query: &LargestDispatchesQuery,
+        tenant_id: i32,
+    ) -> Result<Vec<response::DispatchSize>, Error> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_LARGEST_LIMIT)
+            .clamp(1, MAX_LARGEST_LIMIT);
+
         Ok(sqlx::query(
             "SELECT
                 dispatches.dispatch_id,
                 dispatches.nation,
+                dispatch_content.id AS revision,
                 dispatch_content.category,
                 dispatch_content.subcategory,
                 dispatch_content.title,
-                dispatch_content.text,
+                dispatch_content.size,
                 dispatch_content.created_by,
-                dispatch_content.created_at as created_at
+                dispatch_content.created_at as created_at,
+                dispatches.is_active,
+                dispatches.is_protected
             FROM dispatches
-            JOIN
-                dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+            JOIN dispatch_content ON dispatch_content.dispatch_id = dispatches.id
             WHERE dispatch_content.id = (
                 SELECT id FROM dispatch_content
-              WHERE dispatch_content.dispatch_id = dispatches.id
-              ORDER BY dispatch_content.id DESC
-              LIMIT 1
+                WHERE dispatch_content.dispatch_id = dispatches.id
+                ORDER BY dispatch_content.id DESC
+                LIMIT 1
             )
-            AND dispatches.is_active = TRUE;",
+            AND dispatches.is_active = TRUE
+            AND dispatch_content.size IS NOT NULL
+            AND dispatches.tenant_id = $2
+            ORDER BY dispatch_content.size DESC, dispatches.dispatch_id DESC
+            LIMIT $1;",
         )
-        .map(map_dispatch)
-        .fetch_all(&self.pool)
+        .bind(limit)
+        .bind(tenant_id)
+        .map(|row: PgRow| {
+            let (revision, size) = (row.get("revision"), row.get::<i32, _>("size"));
+
+            response::DispatchSize {
+                dispatch: map_dispatch_summary(row),
+                revision,
+                size,
+                utilization: size as f64 / dispatch::MAX_TEXT_LENGTH as f64,
+            }
+        })
+        .fetch_all(self.pools.get(Pool::Read))
         .await?)
     }
 
+    /// Samples of a dispatch's views and score taken within the window, oldest first.
+    /// Dispatches of tenants other than `tenant` aren't found.
     #[tracing::instrument(skip_all)]
-    async fn get_by_nation(&self, nation: String) -> Result<Vec<response::Dispatch>, Error> {
+    pub(crate) async fn metrics(
+        &self,
+        id: i32,
+        query: &DispatchMetricsQuery,
+        tenant: i32,
+    ) -> Result<response::DispatchMetrics, Error> {
+        let from = query
+            .from
+            .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::weeks(1));
+
+        let pool = self.pools.get(Pool::Read);
+
+        if sqlx::query("SELECT id FROM dispatches WHERE dispatch_id = $1 AND tenant_id = $2;")
+            .bind(id)
+            .bind(tenant)
+            .fetch_optional(pool)
+            .await?
+            .is_none()
+        {
+            return Err(Error::DispatchNotFound);
+        }
+
+        let samples = sqlx::query(
+            "SELECT views, score, sampled_at
+            FROM dispatch_metrics
+            JOIN dispatches ON dispatches.id = dispatch_metrics.dispatch_id
+            WHERE dispatches.dispatch_id = $1
+            AND dispatch_metrics.sampled_at >= $2
+            AND ($3::TIMESTAMPTZ IS NULL OR dispatch_metrics.sampled_at <= $3)
+            AND dispatches.tenant_id = $4
+            ORDER BY dispatch_metrics.sampled_at, dispatch_metrics.id;",
+        )
+        .bind(id)
+        .bind(from)
+        .bind(query.to)
+        .bind(tenant)
+        .map(|row: PgRow| response::DispatchMetricSample {
+            views: row.get("views"),
+            score: row.get("score"),
+            sampled_at: row.get("sampled_at"),
+        })
+        .fetch_all(pool)
+        .await?;
+
+        Ok(response::DispatchMetrics { id, samples })
+    }
+
+    /// Active dispatches whose score rose most between the first and the latest sample within
+    /// the last `hours`. Dispatches sampled fewer than twice in the window have no delta to rank.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn top(
+        &self,
+        query: &TopDispatchesQuery,
+        tenant_id: i32,
+    ) -> Result<Vec<response::TopDispatch>, Error> {
+        let hours = query.hours.unwrap_or(DEFAULT_TOP_HOURS).max(1);
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_TOP_LIMIT)
+            .clamp(1, MAX_TOP_LIMIT);
+
         Ok(sqlx::query(
             "SELECT
                 dispatches.dispatch_id,
@@ -165,95 +785,1141 @@ impl Controller {
                 dispatch_content.category,
                 dispatch_content.subcategory,
                 dispatch_content.title,
-                dispatch_content.text,
                 dispatch_content.created_by,
-                dispatch_content.created_at as created_at
+                dispatch_content.created_at as created_at,
+                dispatches.is_active,
+                dispatches.is_protected,
+                latest.views,
+                latest.score,
+                latest.views - earliest.views AS views_delta,
+                latest.score - earliest.score AS score_delta
             FROM dispatches
-            JOIN
-                dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+            JOIN dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+            JOIN LATERAL (
+                SELECT id, views, score FROM dispatch_metrics
+                WHERE dispatch_metrics.dispatch_id = dispatches.id
+                AND sampled_at >= NOW() - make_interval(hours => $1)
+                ORDER BY sampled_at, id
+                LIMIT 1
+            ) earliest ON TRUE
+            JOIN LATERAL (
+                SELECT id, views, score FROM dispatch_metrics
+                WHERE dispatch_metrics.dispatch_id = dispatches.id
+                AND sampled_at >= NOW() - make_interval(hours => $1)
+                ORDER BY sampled_at DESC, id DESC
+                LIMIT 1
+            ) latest ON latest.id <> earliest.id
             WHERE dispatch_content.id = (
                 SELECT id FROM dispatch_content
-              WHERE dispatch_content.dispatch_id = dispatches.id
-              ORDER BY dispatch_content.id DESC
-              LIMIT 1
+                WHERE dispatch_content.dispatch_id = dispatches.id
+                ORDER BY dispatch_content.id DESC
+                LIMIT 1
             )
             AND dispatches.is_active = TRUE
-            AND dispatches.nation = $1;",
+            AND dispatches.tenant_id = $3
+            ORDER BY score_delta DESC, views_delta DESC, dispatches.dispatch_id DESC
+            LIMIT $2;",
         )
-        .bind(nation)
-        .map(map_dispatch)
-        .fetch_all(&self.pool)
+        .bind(hours)
+        .bind(limit)
+        .bind(tenant_id)
+        .map(|row: PgRow| {
+            let (views, score) = (row.get("views"), row.get("score"));
+            let (views_delta, score_delta) = (row.get("views_delta"), row.get("score_delta"));
+
+            response::TopDispatch {
+                dispatch: map_dispatch_summary(row),
+                views,
+                score,
+                views_delta,
+                score_delta,
+            }
+        })
+        .fetch_all(self.pools.get(Pool::Read))
         .await?)
     }
 
-    #[tracing::instrument(skip_all)]
-    pub(crate) async fn get(
-        &self,
-        nation: Option<String>,
-    ) -> Result<Vec<response::Dispatch>, Error> {
-        match nation {
-            Some(nation) => Ok(self.get_by_nation(nation).await?),
-            None => Ok(self.get_all().await?),
-        }
+    /// NS ids of the active dispatches, the ones the metrics collector samples.
+    pub(crate) async fn active_ids(&self) -> Result<Vec<i32>, Error> {
+        Ok(sqlx::query(
+            "SELECT dispatch_id FROM dispatches WHERE is_active = TRUE ORDER BY dispatch_id;",
+        )
+        .map(|row: PgRow| row.get("dispatch_id"))
+        .fetch_all(self.pools.get(Pool::Read))
+        .await?)
     }
 
-    #[tracing::instrument(skip_all)]
-    pub(crate) async fn post(
+    /// Stores a sample of a dispatch's views and score. A dispatch deleted since it was listed
+    /// has nothing to store it on and is passed over.
+    pub(crate) async fn record_metrics(
         &self,
-        user: AuthorizedUser,
-        new_dispatch: NewDispatch,
-    ) -> Result<DispatchStatus, Error> {
-        let job = self.queue("add", Json(new_dispatch.clone())).await?;
+        id: i32,
+        views: i32,
+        score: i32,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO dispatch_metrics (dispatch_id, views, score)
+            SELECT id, $2, $3 FROM dispatches WHERE dispatch_id = $1;",
+        )
+        .bind(id)
+        .bind(views)
+        .bind(score)
+        .execute(self.pools.get(Pool::Primary))
+        .await?;
 
-        let dispatch = IntermediateDispatch::add(job.id, user.username, new_dispatch)?;
+        Ok(())
+    }
 
-        let (tx, rx) = oneshot::channel();
+    /// Percentiles of the time dispatch jobs spent in each phase.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn timings(
+        &self,
+        query: &TimingsQuery,
+        tenant_id: i32,
+    ) -> Result<response::JobTimings, Error> {
+        timings::summarize(
+            self.pools.get(Pool::Read),
+            "dispatch_queue",
+            JobKind::Dispatch,
+            query,
+            tenant_id,
+        )
+        .await
+    }
 
-        if let Err(e) = self.tx.send(Command::new(dispatch, tx)).await {
-            tracing::error!("unable to send dispatch to actor: {}", e);
+    /// Every revision of every dispatch of `tenant_id`, deleted ones included, oldest first.
+    /// Rows are sent as they're read, and reading waits while the receiver is behind, so the
+    /// whole history is never held in memory.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn export(
+        &self,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+        tenant_id: i32,
+    ) -> mpsc::Receiver<Result<response::Dispatch, Error>> {
+        let (tx, rx) = mpsc::channel(EXPORT_BUFFER);
+        let pool = self.pools.get(Pool::Read).clone();
 
-            return Err(Error::Internal);
-        }
+        tokio::spawn(async move {
+            let mut rows = sqlx::query(
+                "SELECT
+                    dispatches.dispatch_id,
+                    dispatches.nation,
+                    dispatch_content.category,
+                    dispatch_content.subcategory,
+                    dispatch_content.title,
+                    dispatch_content.text,
+                    dispatch_content.text_compressed,
+                    dispatch_content.source_format,
+                    dispatch_content.source,
+                    dispatch_content.id AS revision,
+                    dispatch_content.job_id,
+                    dispatch_content.size,
+                    dispatch_content.created_by,
+                    dispatch_content.created_at as created_at,
+                    dispatches.is_active,
+                    dispatches.is_protected
+                FROM dispatches
+                JOIN
+                    dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+                WHERE ($1::TIMESTAMPTZ IS NULL OR dispatch_content.created_at >= $1)
+                AND dispatches.tenant_id = $2
+                ORDER BY dispatch_content.id;",
+            )
+            .bind(since)
+            .bind(tenant_id)
+            .try_map(map_dispatch)
+            .fetch(&pool);
 
-        match rx.await {
-            Ok(_) => Ok(job),
-            Err(e) => {
-                tracing::error!("received error: {}", e);
+            while let Some(row) = rows.next().await {
+                let failed = row.is_err();
 
-                Err(Error::Internal)
+                // the client went away
+                if tx.send(row.map_err(Error::Sql)).await.is_err() || failed {
+                    break;
+                }
             }
-        }
+        });
+
+        rx
     }
 
+    /// Every edit adds a `dispatch_content` row, every removal changes the active count and
+    /// every protection change the protected count or the latest protection, so any mutation
+    /// changes the validator. For a single dispatch, so does every change to an edit job of it,
+    /// which its unapplied edits are counted from.
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn put(
+    pub(crate) async fn validator(
         &self,
-        user: AuthorizedUser,
-        id: i32,
-        dispatch: EditDispatch,
-    ) -> Result<DispatchStatus, Error> {
-        let job = self.queue("edit", Json(dispatch.clone())).await?;
-
-        let nation = self.get_nation(id).await?;
+        dispatch_id: Option<i32>,
+        tenant: Option<i32>,
+    ) -> Result<String, Error> {
+        Ok(sqlx::query(
+            "SELECT
+                COALESCE(MAX(dispatch_content.id), 0) AS latest,
+                COUNT(DISTINCT dispatches.id) FILTER (WHERE dispatches.is_active) AS active,
+                COUNT(DISTINCT dispatches.id) FILTER (WHERE dispatches.is_protected) AS protected,
+                MAX(dispatches.protected_at) AS protected_at,
+                (
+                    SELECT MAX(COALESCE(modified_at, created_at)) FROM dispatch_queue
+                    WHERE target_dispatch_id = $1 AND type = 'edit'
+                ) AS edited_at
+            FROM dispatches
+            JOIN
+                dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+            WHERE ($1::INTEGER IS NULL OR dispatches.dispatch_id = $1)
+            AND ($2::INTEGER IS NULL OR dispatches.tenant_id = $2);",
+        )
+        .bind(dispatch_id)
+        .bind(tenant)
+        .map(|row: PgRow| {
+            let micros = |column| {
+                row.get::<Option<chrono::DateTime<chrono::Utc>>, _>(column)
+                    .map_or(0, |at| at.timestamp_micros())
+            };
 
-        let dispatch = IntermediateDispatch::edit(job.id, user.username, id, nation, dispatch)?;
+            format!(
+                "{}-{}-{}-{}-{}",
+                row.get::<i32, _>("latest"),
+                row.get::<i64, _>("active"),
+                row.get::<i64, _>("protected"),
+                micros("protected_at"),
+                micros("edited_at"),
+            )
+        })
+        .fetch_one(self.pools.get(Pool::Read))
+        .await?)
+    }
 
-        let (tx, rx) = oneshot::channel();
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get_one(
+        self,
+        dispatch_id: i32,
+        include_inactive: bool,
+        tenant: Option<i32>,
+    ) -> Result<response::Dispatch, Error> {
+        let mut dispatch = self
+            .fetch_dispatch(dispatch_id, include_inactive, tenant, Pool::Read)
+            .await?;
 
-        if let Err(e) = self.tx.send(Command::new(dispatch, tx)).await {
-            tracing::error!("unable to send dispatch to actor: {}", e);
+        dispatch.pending_or_failed_edits = Some(self.unapplied_edits(dispatch_id).await?);
 
-            return Err(Error::Internal);
-        }
+        Ok(dispatch)
+    }
 
-        match rx.await {
-            Ok(_) => Ok(job),
-            Err(e) => {
-                tracing::error!("received error: {}", e);
+    /// Edits of `dispatch_id` still pending, and those that failed after its latest revision was
+    /// written; one that failed before was overwritten by that revision anyway.
+    async fn unapplied_edits(&self, dispatch_id: i32) -> Result<response::UnappliedEdits, Error> {
+        let edits = sqlx::query(
+            "SELECT
+                id,
+                status,
+                created_by,
+                created_at,
+                COALESCE(finished_at, modified_at, created_at) AS finished_at,
+                error_code
+            FROM dispatch_queue
+            WHERE target_dispatch_id = $1
+            AND type = 'edit'
+            AND (
+                status = 'scheduled'
+                OR status = ANY($2)
+                OR status = 'failed' AND COALESCE(finished_at, modified_at) > (
+                    SELECT dispatch_content.created_at FROM dispatch_content
+                    JOIN dispatches ON dispatches.id = dispatch_content.dispatch_id
+                    WHERE dispatches.dispatch_id = $1
+                    ORDER BY dispatch_content.id DESC
+                    LIMIT 1
+                )
+            )
+            ORDER BY id;",
+        )
+        .bind(dispatch_id)
+        .bind(JobStatus::PENDING)
+        .map(|row: PgRow| {
+            (
+                row.get::<JobStatus, _>("status"),
+                response::FailedEdit {
+                    job_id: row.get("id"),
+                    created_by: row
+                        .get::<Option<String>, _>("created_by")
+                        .unwrap_or_default(),
+                    created_at: row.get("created_at"),
+                    failed_at: row.get("finished_at"),
+                    error_code: row.get("error_code"),
+                },
+            )
+        })
+        .fetch_all(self.pools.get(Pool::Read))
+        .await?;
+
+        let mut unapplied = response::UnappliedEdits::default();
+
+        for (status, edit) in edits {
+            if status == JobStatus::Failed {
+                unapplied.failed += 1;
+                unapplied.latest_failed = Some(edit);
+            } else {
+                unapplied.pending += 1;
+            }
+        }
+
+        Ok(unapplied)
+    }
+
+    /// The latest revision of `dispatch_id`, on the primary when it has to reflect a write.
+    async fn fetch_dispatch(
+        &self,
+        dispatch_id: i32,
+        include_inactive: bool,
+        tenant: Option<i32>,
+        pool: Pool,
+    ) -> Result<response::Dispatch, Error> {
+        match sqlx::query(
+            "SELECT
+                dispatches.dispatch_id,
+                dispatches.nation,
+                dispatch_content.category,
+                dispatch_content.subcategory,
+                dispatch_content.title,
+                dispatch_content.text,
+                dispatch_content.text_compressed,
+                dispatch_content.source_format,
+                dispatch_content.source,
+                dispatch_content.id AS revision,
+                dispatch_content.job_id,
+                dispatch_content.size,
+                dispatch_content.created_by,
+                dispatch_content.created_at as created_at,
+                dispatches.is_active,
+                dispatches.is_protected
+            FROM dispatches
+            JOIN
+                dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+            WHERE dispatch_content.id = (
+                SELECT id FROM dispatch_content
+              WHERE dispatch_content.dispatch_id = dispatches.id
+              ORDER BY dispatch_content.id DESC
+              LIMIT 1
+            )
+            AND dispatches.dispatch_id = $1
+            AND (dispatches.is_active = TRUE OR $2)
+            AND ($3::INTEGER IS NULL OR dispatches.tenant_id = $3);",
+        )
+        .bind(dispatch_id)
+        .bind(include_inactive)
+        .bind(tenant)
+        .try_map(map_dispatch)
+        .fetch_one(self.pools.get(pool))
+        .await
+        {
+            Ok(dispatch) => Ok(dispatch),
+            Err(sqlx::Error::RowNotFound) => Err(self.missing(dispatch_id, tenant, pool).await?),
+            Err(e) => Err(Error::Sql(e)),
+        }
+    }
+
+    /// Why `dispatch_id` wasn't found: it was deleted, or it never existed.
+    #[tracing::instrument(skip_all)]
+    async fn missing(
+        &self,
+        dispatch_id: i32,
+        tenant: Option<i32>,
+        pool: Pool,
+    ) -> Result<Error, Error> {
+        Ok(sqlx::query(
+            "SELECT deleted_at, deleted_by FROM dispatches
+            WHERE dispatch_id = $1 AND is_active = FALSE
+            AND ($2::INTEGER IS NULL OR tenant_id = $2)
+            ORDER BY id DESC
+            LIMIT 1;",
+        )
+        .bind(dispatch_id)
+        .bind(tenant)
+        .map(|row: PgRow| {
+            Error::DispatchDeleted(Box::new(response::DeletedDispatch {
+                id: dispatch_id,
+                deleted_at: row.get("deleted_at"),
+                deleted_by: row.get("deleted_by"),
+            }))
+        })
+        .fetch_optional(self.pools.get(pool))
+        .await?
+        .unwrap_or(Error::DispatchNotFound))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_all(
+        &self,
+        include_inactive: bool,
+        tenant: Option<i32>,
+    ) -> Result<Vec<response::Dispatch>, Error> {
+        Ok(sqlx::query(
+            "SELECT
+                dispatches.dispatch_id,
+                dispatches.nation,
+                dispatch_content.category,
+                dispatch_content.subcategory,
+                dispatch_content.title,
+                dispatch_content.text,
+                dispatch_content.text_compressed,
+                dispatch_content.source_format,
+                dispatch_content.source,
+                dispatch_content.id AS revision,
+                dispatch_content.job_id,
+                dispatch_content.size,
+                dispatch_content.created_by,
+                dispatch_content.created_at as created_at,
+                dispatches.is_active,
+                dispatches.is_protected
+            FROM dispatches
+            JOIN
+                dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+            WHERE dispatch_content.id = (
+                SELECT id FROM dispatch_content
+              WHERE dispatch_content.dispatch_id = dispatches.id
+              ORDER BY dispatch_content.id DESC
+              LIMIT 1
+            )
+            AND (dispatches.is_active = TRUE OR $1)
+            AND ($2::INTEGER IS NULL OR dispatches.tenant_id = $2);",
+        )
+        .bind(include_inactive)
+        .bind(tenant)
+        .try_map(map_dispatch)
+        .fetch_all(self.pools.get(Pool::Read))
+        .await?)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_by_nation(
+        &self,
+        nation: String,
+        include_inactive: bool,
+        tenant: Option<i32>,
+    ) -> Result<Vec<response::Dispatch>, Error> {
+        Ok(sqlx::query(
+            "SELECT
+                dispatches.dispatch_id,
+                dispatches.nation,
+                dispatch_content.category,
+                dispatch_content.subcategory,
+                dispatch_content.title,
+                dispatch_content.text,
+                dispatch_content.text_compressed,
+                dispatch_content.source_format,
+                dispatch_content.source,
+                dispatch_content.id AS revision,
+                dispatch_content.job_id,
+                dispatch_content.size,
+                dispatch_content.created_by,
+                dispatch_content.created_at as created_at,
+                dispatches.is_active,
+                dispatches.is_protected
+            FROM dispatches
+            JOIN
+                dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+            WHERE dispatch_content.id = (
+                SELECT id FROM dispatch_content
+              WHERE dispatch_content.dispatch_id = dispatches.id
+              ORDER BY dispatch_content.id DESC
+              LIMIT 1
+            )
+            AND (dispatches.is_active = TRUE OR $2)
+            AND dispatches.nation = $1
+            AND ($3::INTEGER IS NULL OR dispatches.tenant_id = $3);",
+        )
+        .bind(nation)
+        .bind(include_inactive)
+        .bind(tenant)
+        .try_map(map_dispatch)
+        .fetch_all(self.pools.get(Pool::Read))
+        .await?)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get(
+        &self,
+        nation: Option<String>,
+        include_inactive: bool,
+        tenant: Option<i32>,
+    ) -> Result<Vec<response::Dispatch>, Error> {
+        match nation {
+            Some(nation) => Ok(self
+                .get_by_nation(name::canonicalize(&nation), include_inactive, tenant)
+                .await?),
+            None => Ok(self.get_all(include_inactive, tenant).await?),
+        }
+    }
+
+    /// Latest revisions without their text, which is never read from the table.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get_summaries(
+        &self,
+        nation: Option<String>,
+        include_inactive: bool,
+        tenant: Option<i32>,
+    ) -> Result<Vec<response::DispatchSummary>, Error> {
+        Ok(sqlx::query(
+            "SELECT
+                dispatches.dispatch_id,
+                dispatches.nation,
+                dispatch_content.category,
+                dispatch_content.subcategory,
+                dispatch_content.title,
+                dispatch_content.created_by,
+                dispatch_content.created_at as created_at,
+                dispatches.is_active,
+                dispatches.is_protected
+            FROM dispatches
+            JOIN
+                dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+            WHERE dispatch_content.id = (
+                SELECT id FROM dispatch_content
+              WHERE dispatch_content.dispatch_id = dispatches.id
+              ORDER BY dispatch_content.id DESC
+              LIMIT 1
+            )
+            AND (dispatches.is_active = TRUE OR $1)
+            AND ($2::VARCHAR IS NULL OR dispatches.nation = $2)
+            AND ($3::INTEGER IS NULL OR dispatches.tenant_id = $3);",
+        )
+        .bind(include_inactive)
+        .bind(nation.as_deref().map(name::canonicalize))
+        .bind(tenant)
+        .map(map_dispatch_summary)
+        .fetch_all(self.pools.get(Pool::Read))
+        .await?)
+    }
+
+    /// Latest revisions matching `query.q`, best matches first. Compressed texts can't be
+    /// highlighted in the database, so the snippets are made from the decoded texts of the page.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn search(
+        &self,
+        query: &DispatchSearchQuery,
+        tenant: Option<i32>,
+    ) -> Result<Vec<response::DispatchSearchResult>, Error> {
+        if query.q.trim().is_empty() {
+            return Err(Error::InvalidQuery("q must not be empty".to_string()));
+        }
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_PAGE_SIZE)
+            .clamp(1, MAX_SEARCH_PAGE_SIZE);
+
+        let matches = sqlx::query(
+            "SELECT
+                dispatches.dispatch_id,
+                dispatches.nation,
+                dispatch_content.category,
+                dispatch_content.subcategory,
+                dispatch_content.title,
+                dispatch_content.text,
+                dispatch_content.text_compressed,
+                dispatch_content.created_by,
+                dispatch_content.created_at as created_at,
+                dispatches.is_active,
+                dispatches.is_protected,
+                ts_rank(dispatch_content.search, query) AS rank,
+                ts_headline('english', dispatch_content.title, query, $2) AS title_highlight
+            FROM dispatches
+            JOIN
+                dispatch_content ON dispatch_content.dispatch_id = dispatches.id,
+                websearch_to_tsquery('english', $1) query
+            WHERE dispatch_content.id = (
+                SELECT id FROM dispatch_content
+              WHERE dispatch_content.dispatch_id = dispatches.id
+              ORDER BY dispatch_content.id DESC
+              LIMIT 1
+            )
+            AND dispatch_content.search @@ query
+            AND (dispatches.is_active = TRUE OR $3)
+            AND ($4::VARCHAR IS NULL OR dispatches.nation = $4)
+            AND ($5::SMALLINT IS NULL OR dispatch_content.category = $5)
+            AND ($6::SMALLINT IS NULL OR dispatch_content.subcategory = $6)
+            AND ($9::INTEGER IS NULL OR dispatches.tenant_id = $9)
+            ORDER BY rank DESC, dispatches.dispatch_id DESC
+            LIMIT $7
+            OFFSET $8;",
+        )
+        .bind(&query.q)
+        .bind(TITLE_HIGHLIGHT)
+        .bind(query.include_inactive)
+        .bind(query.nation.as_deref().map(name::canonicalize))
+        .bind(query.category)
+        .bind(query.subcategory)
+        .bind(limit)
+        .bind(query.offset.unwrap_or(0).max(0))
+        .bind(tenant)
+        .try_map(|row: PgRow| {
+            let text = compress::decode(row.get("text"), row.get("text_compressed"))
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+            Ok((
+                text,
+                row.get::<f32, _>("rank"),
+                row.get::<String, _>("title_highlight"),
+                map_dispatch_summary(row),
+            ))
+        })
+        .fetch_all(self.pools.get(Pool::Read))
+        .await?;
+
+        let texts = matches
+            .iter()
+            .map(|(text, ..)| text.as_str())
+            .collect::<Vec<_>>();
+
+        let snippets: Vec<String> = sqlx::query_scalar(
+            "SELECT ts_headline('english', texts.text, websearch_to_tsquery('english', $2), $3)
+            FROM UNNEST($1::TEXT[]) WITH ORDINALITY AS texts (text, position)
+            ORDER BY texts.position;",
+        )
+        .bind(&texts)
+        .bind(&query.q)
+        .bind(TEXT_HIGHLIGHT)
+        .fetch_all(self.pools.get(Pool::Read))
+        .await?;
+
+        Ok(matches
+            .into_iter()
+            .zip(snippets)
+            .map(
+                |((_, rank, title_highlight, dispatch), snippet)| response::DispatchSearchResult {
+                    dispatch,
+                    rank,
+                    title_highlight,
+                    snippet,
+                },
+            )
+            .collect())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn post(
+        &self,
+        user: AuthorizedUser,
+        mut new_dispatch: NewDispatch,
+        allow_duplicate_title: bool,
+        key: Option<idempotency::Key>,
+        request_id: &RequestId,
+    ) -> Result<Submitted<DispatchStatus>, Error> {
+        new_dispatch.nation = name::canonicalize(&new_dispatch.nation);
+
+        if let Some(preset) = self.find_preset(&new_dispatch.nation).await? {
+            apply_preset(&preset, &mut new_dispatch);
+        }
+
+        new_dispatch.factbook_category()?;
+
+        let policy_warnings = self
+            .policy
+            .check(&[&new_dispatch.title, &new_dispatch.text])?;
+
+        tenant::check_nation(
+            self.pools.get(Pool::Primary),
+            user.tenant_id,
+            &new_dispatch.nation,
+        )
+        .await?;
+
+        self.check_credentials(&new_dispatch.nation).await?;
+
+        if self.unique_titles && !allow_duplicate_title {
+            self.check_title(&new_dispatch.nation, &new_dispatch.title)
+                .await?;
+        }
+
+        let converted = new_dispatch.format.to_bbcode(&new_dispatch.text);
+
+        let job = match self
+            .queue(
+                &user,
+                QueuedDispatchPayload::Add(new_dispatch.clone()),
+                &[converted.warnings, policy_warnings].concat(),
+                key.as_ref(),
+                request_id,
+            )
+            .await?
+        {
+            Submitted::Created(job) => job,
+            replayed => return Ok(replayed),
+        };
+
+        let dispatch =
+            IntermediateDispatch::add(job.id, user.username, new_dispatch, converted.bbcode)?
+                .with_request_id(request_id);
+
+        self.submit(vec![(job.id, Operation::Queue(dispatch))])
+            .await?;
+
+        let job = DispatchStatus {
+            estimated_completion_at: self.estimated_completion(job.id).await,
+            ..job
+        };
+
+        Ok(Submitted::Created(job))
+    }
+
+    /// Refuses a new dispatch titled like one of `nation`'s active dispatches, which is more
+    /// likely a repost of it than a dispatch of its own.
+    async fn check_title(&self, nation: &str, title: &str) -> Result<(), Error> {
+        let existing: Option<i32> = sqlx::query_scalar(
+            "SELECT dispatches.dispatch_id
+            FROM dispatches
+            JOIN
+                dispatch_content ON dispatch_content.dispatch_id = dispatches.id
+            WHERE LOWER(dispatch_content.title) = LOWER($2)
+            AND dispatch_content.id = (
+                SELECT id FROM dispatch_content
+                WHERE dispatch_content.dispatch_id = dispatches.id
+                ORDER BY dispatch_content.id DESC
+                LIMIT 1
+            )
+            AND dispatches.nation = $1
+            AND dispatches.is_active = TRUE
+            LIMIT 1;",
+        )
+        .bind(nation)
+        .bind(title.trim())
+        .fetch_optional(self.pools.get(Pool::Primary))
+        .await?;
+
+        match existing {
+            Some(id) => Err(Error::DuplicateDispatchTitle(id)),
+            None => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn presets(&self) -> Result<Vec<response::NationPreset>, Error> {
+        Ok(sqlx::query(
+            "SELECT nation, default_category, default_subcategory, title_prefix, signature_text,
+                updated_by, updated_at
+            FROM nation_presets
+            ORDER BY nation;",
+        )
+        .map(map_preset)
+        .fetch_all(self.pools.get(Pool::Read))
+        .await?)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn preset(&self, nation: &str) -> Result<response::NationPreset, Error> {
+        self.find_preset(&name::canonicalize(nation))
+            .await?
+            .ok_or(Error::PresetNotFound)
+    }
+
+    /// Read from the primary, as it's applied to what's about to be queued.
+    async fn find_preset(&self, nation: &str) -> Result<Option<response::NationPreset>, Error> {
+        Ok(sqlx::query(
+            "SELECT nation, default_category, default_subcategory, title_prefix, signature_text,
+                updated_by, updated_at
+            FROM nation_presets
+            WHERE nation = $1;",
+        )
+        .bind(nation)
+        .map(map_preset)
+        .fetch_optional(self.pools.get(Pool::Primary))
+        .await?)
+    }
+
+    /// Replaces the preset of `nation`, which has to be one the worker posts as. Empty prefixes
+    /// and signatures are stored as none.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn set_preset(
+        &self,
+        username: &str,
+        nation: &str,
+        preset: request::NationPreset,
+    ) -> Result<response::NationPreset, Error> {
+        let nation = name::canonicalize(nation);
+
+        if !self.nations().await?.contains(&nation) {
+            return Err(Error::InvalidNation);
+        }
+
+        match (preset.default_category, preset.default_subcategory) {
+            (Some(category), Some(subcategory)) => {
+                FactbookCategory::try_from((category, subcategory))?;
+            }
+            (None, None) => {}
+            _ => return Err(Error::InvalidFactbookCategory),
+        }
+
+        Ok(sqlx::query(
+            "INSERT INTO nation_presets
+                (nation, default_category, default_subcategory, title_prefix, signature_text, updated_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (nation) DO UPDATE
+            SET default_category = EXCLUDED.default_category,
+                default_subcategory = EXCLUDED.default_subcategory,
+                title_prefix = EXCLUDED.title_prefix,
+                signature_text = EXCLUDED.signature_text,
+                updated_by = EXCLUDED.updated_by,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING nation, default_category, default_subcategory, title_prefix, signature_text,
+                updated_by, updated_at;",
+        )
+        .bind(&nation)
+        .bind(preset.default_category)
+        .bind(preset.default_subcategory)
+        .bind(preset.title_prefix.filter(|prefix| !prefix.is_empty()))
+        .bind(preset.signature_text.filter(|signature| !signature.is_empty()))
+        .bind(username)
+        .map(map_preset)
+        .fetch_one(self.pools.get(Pool::Primary))
+        .await?)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn delete_preset(&self, nation: &str) -> Result<(), Error> {
+        let result = sqlx::query("DELETE FROM nation_presets WHERE nation = $1;")
+            .bind(name::canonicalize(nation))
+            .execute(self.pools.get(Pool::Primary))
+            .await?;
+
+        match result.rows_affected() {
+            0 => Err(Error::PresetNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    /// Queues `group` for each of its nations in one transaction, so either every job is queued
+    /// or none is.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn post_group(
+        &self,
+        user: AuthorizedUser,
+        group: NewDispatchGroup,
+        key: Option<idempotency::Key>,
+        request_id: &RequestId,
+    ) -> Result<Submitted<response::DispatchGroup>, Error> {
+        FactbookCategory::try_from((group.category, group.subcategory))?;
+
+        let policy_warnings = self.policy.check(&[&group.title, &group.text])?;
+
+        let configured = self.nations().await?;
+
+        let mut nations: Vec<String> = Vec::new();
+
+        for nation in group
+            .nations
+            .iter()
+            .map(|nation| name::canonicalize(nation))
+        {
+            if !configured.contains(&nation) {
+                return Err(Error::InvalidNation);
+            }
+
+            if !nations.contains(&nation) {
+                nations.push(nation);
+            }
+        }
+
+        if nations.is_empty() || nations.len() > MAX_GROUP_SIZE {
+            return Err(Error::InvalidDispatchGroup(MAX_GROUP_SIZE));
+        }
+
+        for nation in &nations {
+            tenant::check_nation(self.pools.get(Pool::Primary), user.tenant_id, nation).await?;
+            self.check_credentials(nation).await?;
+        }
+
+        if let Some(job_id) =
+            idempotency::previous_job(key.as_ref(), self.pools.get(Pool::Primary)).await?
+        {
+            return Ok(Submitted::Replayed(
+                self.get_group_of_job(job_id, user.tenant_id).await?,
+            ));
+        }
+
+        let converted = group.format.to_bbcode(&group.text);
+        let warnings = [converted.warnings, policy_warnings].concat();
+
+        let mut transaction = self.pools.get(Pool::Primary).begin().await?;
+
+        self.quota
+            .check(
+                &mut transaction,
+                "dispatch_queue",
+                &user,
+                nations.len() as i64,
+            )
+            .await?;
+
+        let group_id: i32 = sqlx::query(
+            "INSERT INTO dispatch_groups (created_by, tenant_id) VALUES ($1, $2) RETURNING id;",
+        )
+        .bind(&user.username)
+        .bind(user.tenant_id)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        let mut jobs = Vec::new();
+
+        for nation in &nations {
+            let new_dispatch = group.for_nation(nation);
+            let payload = QueuedDispatchPayload::Add(new_dispatch.clone());
+
+            let job_id: i32 = sqlx::query(
+                "INSERT INTO dispatch_queue (type, payload, status, created_by, request_id, warnings, group_id, tenant_id)
+                VALUES ($1, $2, 'queued', $3, $4, $5, $6, $7)
+                RETURNING id;",
+            )
+            .bind(payload.action())
+            .bind(Json(payload))
+            .bind(&user.username)
+            .bind(&request_id.0)
+            .bind(&warnings)
+            .bind(group_id)
+            .bind(user.tenant_id)
+            .map(|row: PgRow| row.get("id"))
+            .fetch_one(&mut *transaction)
+            .await?;
 
-                Err(Error::Internal)
-            }
+            jobs.push((job_id, new_dispatch));
+        }
+
+        if let Some(job_id) = idempotency::commit(
+            key.as_ref(),
+            transaction,
+            self.pools.get(Pool::Primary),
+            jobs.first().map(|(job_id, _)| *job_id).unwrap_or_default(),
+        )
+        .await?
+        {
+            return Ok(Submitted::Replayed(
+                self.get_group_of_job(job_id, user.tenant_id).await?,
+            ));
+        }
+
+        let mut operations = Vec::with_capacity(jobs.len());
+
+        for (job_id, new_dispatch) in jobs {
+            let dispatch = IntermediateDispatch::add(
+                job_id,
+                user.username.clone(),
+                new_dispatch,
+                converted.bbcode.clone(),
+            )?
+            .with_request_id(request_id);
+
+            operations.push((job_id, Operation::Queue(dispatch)));
+        }
+
+        self.submit(operations).await?;
+
+        Ok(Submitted::Created(
+            self.get_group(group_id, Some(user.tenant_id)).await?,
+        ))
+    }
+
+    /// Groups of tenants other than `tenant` aren't found.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get_group(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+    ) -> Result<response::DispatchGroup, Error> {
+        let (created_by, created_at) = sqlx::query(
+            "SELECT created_by, created_at FROM dispatch_groups
+            WHERE id = $1
+            AND ($2::INTEGER IS NULL OR tenant_id = $2);",
+        )
+        .bind(id)
+        .bind(tenant)
+        .map(|row: PgRow| (row.get("created_by"), row.get("created_at")))
+        .fetch_optional(self.pools.get(Pool::Primary))
+        .await?
+        .ok_or(Error::GroupNotFound)?;
+
+        let jobs = sqlx::query(
+            "SELECT
+                id,
+                type AS action,
+                status,
+                dispatch_id,
+                error,
+                error_code,
+                warnings,
+                created_at,
+                claimed_at,
+                started_at,
+                prepared_at,
+                executing_at,
+                finished_at,
+                modified_at,
+                superseded_by,
+                (SELECT id FROM dispatch_content WHERE job_id = dispatch_queue.id) AS revision_id,
+                (SELECT size FROM dispatch_content WHERE job_id = dispatch_queue.id) AS revision_size
+            FROM dispatch_queue
+            WHERE group_id = $1
+            ORDER BY id;",
+        )
+        .bind(id)
+        .map(|row: PgRow| map_dispatch_status(row, self.size_warn_utilization))
+        .fetch_all(self.pools.get(Pool::Primary))
+        .await?;
+
+        Ok(response::DispatchGroup {
+            id,
+            status: response::DispatchGroup::status(&jobs).to_string(),
+            self_url: response::DispatchGroup::self_url(id),
+            created_by,
+            created_at,
+            jobs,
+        })
+    }
+
+    /// The group a job was queued in, for replaying an idempotent group submission.
+    async fn get_group_of_job(
+        &self,
+        job_id: i32,
+        tenant: i32,
+    ) -> Result<response::DispatchGroup, Error> {
+        let group_id: Option<i32> =
+            sqlx::query("SELECT group_id FROM dispatch_queue WHERE id = $1;")
+                .bind(job_id)
+                .map(|row: PgRow| row.get("group_id"))
+                .fetch_optional(self.pools.get(Pool::Primary))
+                .await?
+                .flatten();
+
+        self.get_group(group_id.ok_or(Error::GroupNotFound)?, Some(tenant))
+            .await
+    }
+
+    /// Queues an edit of dispatch `id`. With `coalesce`, the worker may post it in place of the
+    /// user's own edit still waiting on the dispatch, see [`Operation::Coalesce`].
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn put(
+        &self,
+        user: AuthorizedUser,
+        id: i32,
+        dispatch: EditDispatch,
+        coalesce: bool,
+        key: Option<idempotency::Key>,
+        request_id: &RequestId,
+    ) -> Result<Submitted<DispatchStatus>, Error> {
+        FactbookCategory::try_from((dispatch.category, dispatch.subcategory))?;
+
+        let policy_warnings = self.policy.check(&[&dispatch.title, &dispatch.text])?;
+
+        let meta = self.get_dispatch_meta(id, user.tenant_id).await?;
+
+        check_ownership(&user, &meta, Claim::DispatchesEditAny)?;
+
+        if self.protect_edits {
+            check_protection(id, &meta)?;
         }
+
+        check_revision(dispatch.base_revision, &meta)?;
+
+        self.check_credentials(&meta.nation).await?;
+
+        let converted = dispatch.format.to_bbcode(&dispatch.text);
+
+        let job = match self
+            .queue(
+                &user,
+                QueuedDispatchPayload::Edit {
+                    id,
+                    params: dispatch.clone(),
+                },
+                &[converted.warnings, policy_warnings].concat(),
+                key.as_ref(),
+                request_id,
+            )
+            .await?
+        {
+            Submitted::Created(job) => job,
+            replayed => return Ok(replayed),
+        };
+
+        let dispatch = IntermediateDispatch::edit(
+            job.id,
+            user.username,
+            id,
+            meta.nation,
+            dispatch,
+            converted.bbcode,
+        )?
+        .with_request_id(request_id);
+
+        let operation = match coalesce {
+            true => Operation::Coalesce(dispatch),
+            false => Operation::Queue(dispatch),
+        };
+
+        self.submit(vec![(job.id, operation)]).await?;
+
+        let job = DispatchStatus {
+            estimated_completion_at: self.estimated_completion(job.id).await,
+            ..job
+        };
+
+        Ok(Submitted::Created(job))
+    }
+
+    /// Queues the content of failed edit `job_id` of dispatch `id` again, as a new edit. Unless
+    /// `base_revision` is given, it's based on the revision the failed edit was, or, without one,
+    /// the revision that was latest when the failed edit was queued, so it's refused if the
+    /// dispatch has been edited since.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn reapply(
+        &self,
+        user: AuthorizedUser,
+        id: i32,
+        job_id: i32,
+        base_revision: Option<i32>,
+        request_id: &RequestId,
+    ) -> Result<Submitted<DispatchStatus>, Error> {
+        let (action, status, payload, queued_revision) = sqlx::query(
+            "SELECT
+                type AS action,
+                status,
+                dispatch_id,
+                payload,
+                (
+                    SELECT MAX(dispatch_content.id) FROM dispatch_content
+                    JOIN dispatches ON dispatches.id = dispatch_content.dispatch_id
+                    WHERE dispatches.dispatch_id = dispatch_queue.target_dispatch_id
+                    AND dispatch_content.created_at <= dispatch_queue.created_at
+                ) AS queued_revision
+            FROM dispatch_queue
+            WHERE id = $1 AND target_dispatch_id = $2;",
+        )
+        .bind(job_id)
+        .bind(id)
+        .map(|row: PgRow| {
+            let action: String = row.get("action");
+            let Json(payload): Json<serde_json::Value> = row.get("payload");
+
+            (
+                action.clone(),
+                row.get::<JobStatus, _>("status"),
+                QueuedDispatchPayload::from_stored(&action, row.get("dispatch_id"), payload),
+                row.get::<Option<i32>, _>("queued_revision"),
+            )
+        })
+        .fetch_optional(self.pools.get(Pool::Primary))
+        .await?
+        .ok_or(Error::JobNotFound)?;
+
+        let mut params = match payload {
+            Some(QueuedDispatchPayload::Edit { params, .. }) if status == JobStatus::Failed => {
+                params
+            }
+            _ => return Err(Error::NotReapplicable(job_id, action, status)),
+        };
+
+        params.base_revision = base_revision.or(params.base_revision).or(queued_revision);
+
+        self.put(user, id, params, false, None, request_id).await
     }
 
     #[tracing::instrument(skip_all)]
@@ -261,53 +1927,645 @@ impl Controller {
         &self,
         user: AuthorizedUser,
         id: i32,
+        request_id: &RequestId,
     ) -> Result<DispatchStatus, Error> {
-        let job = self.queue("delete", Json(id)).await?;
+        let meta = self.get_dispatch_meta(id, user.tenant_id).await?;
 
-        let nation = self.get_nation(id).await?;
+        check_ownership(&user, &meta, Claim::DispatchesDeleteAny)?;
 
-        let dispatch = IntermediateDispatch::delete(job.id, user.username, id, nation);
+        check_protection(id, &meta)?;
 
-        let (tx, rx) = oneshot::channel();
+        self.check_credentials(&meta.nation).await?;
+
+        let job = self
+            .queue(
+                &user,
+                QueuedDispatchPayload::Remove { id },
+                &[],
+                None,
+                request_id,
+            )
+            .await?
+            .into_inner();
 
-        if let Err(e) = self.tx.send(Command::new(dispatch, tx)).await {
-            tracing::error!("unable to send dispatch to actor: {}", e);
+        let dispatch = IntermediateDispatch::delete(job.id, user.username, id, meta.nation)
+            .with_request_id(request_id);
 
-            return Err(Error::Internal);
-        }
+        self.submit(vec![(job.id, Operation::Queue(dispatch))])
+            .await?;
 
-        match rx.await {
-            Ok(_) => Ok(job),
-            Err(e) => {
+        Ok(job)
+    }
+
+    /// Hands the operations of the jobs, already stored as `queued`, to the worker in order. If
+    /// its channel stays full, the job that didn't fit and those after it are marked `deferred`
+    /// for the worker to pick up once it has room, and the request is refused naming them.
+    async fn submit(&self, operations: Vec<(i32, Operation)>) -> Result<(), Error> {
+        let job_ids: Vec<i32> = operations.iter().map(|(job_id, _)| *job_id).collect();
+
+        for (i, (_, operation)) in operations.into_iter().enumerate() {
+            let (tx, rx) = oneshot::channel();
+
+            match self.tx.send(Command::new(operation, tx)).await {
+                Err(Error::PipelineBackedUp(pipeline, _)) => {
+                    let deferred = &job_ids[i..];
+
+                    sqlx::query(
+                        "UPDATE dispatch_queue SET status = 'deferred', modified_at = $2
+                        WHERE id = ANY($1) AND status = 'queued';",
+                    )
+                    .bind(deferred)
+                    .bind(chrono::Utc::now())
+                    .execute(self.pools.get(Pool::Primary))
+                    .await?;
+
+                    return Err(Error::PipelineBackedUp(pipeline, deferred.to_vec()));
+                }
+                result => result?,
+            }
+
+            if let Err(e) = rx.await {
                 tracing::error!("received error: {}", e);
 
-                Err(Error::Internal)
+                return Err(Error::Internal);
             }
         }
+
+        Ok(())
+    }
+
+    /// Protects dispatch `id` from deletion through the API, and from edits with
+    /// `protect_dispatch_edits`, until it's unprotected. Protecting it again keeps who protected
+    /// it first.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn protect(
+        &self,
+        user: &AuthorizedUser,
+        id: i32,
+    ) -> Result<response::Dispatch, Error> {
+        self.get_dispatch_meta(id, user.tenant_id).await?;
+
+        sqlx::query(
+            "UPDATE dispatches
+            SET is_protected = TRUE, protected_at = NOW(), protected_by = $2
+            WHERE dispatch_id = $1 AND is_active AND NOT is_protected;",
+        )
+        .bind(id)
+        .bind(&user.username)
+        .execute(self.pools.get(Pool::Primary))
+        .await?;
+
+        self.fetch_dispatch(id, false, Some(user.tenant_id), Pool::Primary)
+            .await
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn unprotect(
+        &self,
+        user: &AuthorizedUser,
+        id: i32,
+    ) -> Result<response::Dispatch, Error> {
+        self.get_dispatch_meta(id, user.tenant_id).await?;
+
+        sqlx::query(
+            "UPDATE dispatches
+            SET is_protected = FALSE, protected_at = NULL, protected_by = NULL
+            WHERE dispatch_id = $1 AND is_active;",
+        )
+        .bind(id)
+        .execute(self.pools.get(Pool::Primary))
+        .await?;
+
+        self.fetch_dispatch(id, false, Some(user.tenant_id), Pool::Primary)
+            .await
+    }
+
+    /// Starts tracking a dispatch posted outside of eurocore, as read from NS's public API. The
+    /// importing user becomes its owner, so it can be edited and deleted like any other.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn import(
+        &self,
+        user: &AuthorizedUser,
+        params: ImportDispatch,
+    ) -> Result<response::Dispatch, Error> {
+        let nation = name::canonicalize(&params.nation);
+
+        if !self.nations().await?.contains(&nation) {
+            return Err(Error::InvalidNation);
+        }
+
+        tenant::check_nation(self.pools.get(Pool::Primary), user.tenant_id, &nation).await?;
+
+        if self.is_tracked(params.dispatch_id).await? {
+            return Err(Error::DispatchAlreadyTracked(params.dispatch_id));
+        }
+
+        let dispatch = self.fetch_public(params.dispatch_id).await?;
+
+        if name::canonicalize(&dispatch.author) != nation {
+            return Err(Error::DispatchAuthorMismatch(dispatch.author));
+        }
+
+        let (category, subcategory) =
+            FactbookCategory::from_names(&dispatch.category, &dispatch.subcategory)?.to_tuple();
+        let text = compress::encode(&dispatch.text);
+
+        let mut tx = self.pools.get(Pool::Primary).begin().await?;
+
+        // checked again here, an import racing this one may have got in since
+        let id: i32 = sqlx::query(
+            "INSERT INTO dispatches (dispatch_id, nation, is_imported, tenant_id)
+            SELECT $1, $2, TRUE, $3
+            WHERE NOT EXISTS (SELECT 1 FROM dispatches WHERE dispatch_id = $1)
+            RETURNING id;",
+        )
+        .bind(params.dispatch_id)
+        .bind(&nation)
+        .bind(user.tenant_id)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::DispatchAlreadyTracked(params.dispatch_id))?;
+
+        sqlx::query(
+            "INSERT INTO dispatch_content
+                (dispatch_id, category, subcategory, title, text, text_compressed, created_by, search, size)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, dispatch_search_vector($4, $8), $9);",
+        )
+        .bind(id)
+        .bind(category)
+        .bind(subcategory)
+        .bind(&dispatch.title)
+        .bind(text.plain())
+        .bind(text.compressed())
+        .bind(&user.username)
+        .bind(&dispatch.text)
+        .bind(dispatch::encoded_length(&dispatch.text) as i32)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        self.fetch_dispatch(
+            params.dispatch_id,
+            false,
+            Some(user.tenant_id),
+            Pool::Primary,
+        )
+        .await
+    }
+
+    /// Whether `dispatch_id` is in the dispatches table, deleted or not.
+    async fn is_tracked(&self, dispatch_id: i32) -> Result<bool, Error> {
+        Ok(sqlx::query(
+            "SELECT EXISTS (SELECT 1 FROM dispatches WHERE dispatch_id = $1) AS tracked;",
+        )
+        .bind(dispatch_id)
+        .map(|row: PgRow| row.get("tracked"))
+        .fetch_one(self.pools.get(Pool::Primary))
+        .await?)
+    }
+
+    /// Reads a dispatch from the public API, like any other request within the standard limit.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn fetch_public(
+        &self,
+        dispatch_id: i32,
+    ) -> Result<dispatch::PublicDispatch, Error> {
+        if let Err(duration) = self.limiter.acquire(Target::Standard).await {
+            tokio::time::sleep(duration).await;
+        }
+
+        let resp = self
+            .client
+            .get(&self.url)
+            .query(&[
+                ("q", "dispatch".to_string()),
+                ("dispatchid", dispatch_id.to_string()),
+            ])
+            .send()
+            .await?;
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Err(Error::DispatchNotFound);
+        }
+
+        let body = resp.error_for_status()?.text().await?;
+
+        Ok(de::from_str::<PublicDispatchResponse>(&body)?.dispatch)
+    }
+}
+
+/// Users may only modify dispatches they originally authored, unless they hold `any_claim`.
+fn check_ownership(
+    user: &AuthorizedUser,
+    meta: &DispatchMeta,
+    any_claim: Claim,
+) -> Result<(), Error> {
+    if user.holds(any_claim) {
+        return Ok(());
+    }
+
+    match &meta.owner {
+        Some(owner) if *owner == user.username => Ok(()),
+        owner => Err(Error::NotDispatchOwner(owner.clone().unwrap_or_default())),
+    }
+}
+
+/// Protected dispatches are locked until an admin unprotects them, whoever asks.
+fn check_protection(id: i32, meta: &DispatchMeta) -> Result<(), Error> {
+    if !meta.is_protected {
+        return Ok(());
+    }
+
+    Err(Error::DispatchProtected(Box::new(
+        response::ProtectedDispatch {
+            id,
+            protected_by: meta.protected_by.clone(),
+            protected_at: meta.protected_at,
+        },
+    )))
+}
+
+/// Fails if jobs of `actions` are pending on NS dispatch `id`. The dispatch stays locked until
+/// the transaction ends, so a conflicting job queued at the same time waits for this one and
+/// then sees it.
+async fn check_pending(
+    transaction: &mut PgConnection,
+    id: i32,
+    actions: &[&str],
+) -> Result<(), Error> {
+    sqlx::query("SELECT id FROM dispatches WHERE dispatch_id = $1 FOR UPDATE;")
+        .bind(id)
+        .execute(&mut *transaction)
+        .await?;
+
+    let jobs = sqlx::query(
+        "SELECT id, type AS action, status FROM dispatch_queue
+        WHERE target_dispatch_id = $1
+        AND status = ANY($3)
+        AND type = ANY($2)
+        ORDER BY id;",
+    )
+    .bind(id)
+    .bind(actions)
+    .bind(JobStatus::PENDING)
+    .map(|row: PgRow| response::PendingJob {
+        id: row.get("id"),
+        action: row.get("action"),
+        status: row.get("status"),
+    })
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    if jobs.is_empty() {
+        return Ok(());
+    }
+
+    Err(Error::PendingJobs(Box::new(response::PendingJobs {
+        dispatch_id: id,
+        jobs,
+    })))
+}
+
+/// Fills in what `dispatch` left out from `preset`; a category given explicitly wins over the
+/// preset's. The title prefix and signature are added unless the dispatch already has them, so
+/// a dispatch copied from an earlier one doesn't get them twice.
+fn apply_preset(preset: &response::NationPreset, dispatch: &mut NewDispatch) {
+    if dispatch.category.is_none() && dispatch.subcategory.is_none() {
+        dispatch.category = preset.default_category;
+        dispatch.subcategory = preset.default_subcategory;
+    }
+
+    if let Some(prefix) = preset
+        .title_prefix
+        .as_deref()
+        .filter(|prefix| !dispatch.title.starts_with(prefix))
+    {
+        dispatch.title.insert_str(0, prefix);
+    }
+
+    if let Some(signature) = &preset.signature_text {
+        let text = dispatch.text.trim_end();
+
+        if !text.ends_with(signature.trim_end()) {
+            dispatch.text = format!("{}\n\n{}", text, signature);
+        }
+    }
+}
+
+/// Edits based on an older revision than the latest are rejected, so concurrent editors don't
+/// silently overwrite each other. Edits without a base revision always go through.
+fn check_revision(base_revision: Option<i32>, meta: &DispatchMeta) -> Result<(), Error> {
+    match base_revision {
+        Some(base_revision) if base_revision != meta.revision => {
+            Err(Error::EditConflict(Box::new(response::EditConflict::new(
+                base_revision,
+                meta.revision,
+                &meta.revised_by,
+                meta.revised_at,
+            ))))
+        }
+        _ => Ok(()),
     }
 }
 
-fn map_dispatch(row: PgRow) -> response::Dispatch {
-    response::Dispatch {
+fn map_dispatch(row: PgRow) -> Result<response::Dispatch, sqlx::Error> {
+    let text = compress::decode(row.get("text"), row.get("text_compressed"))
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
+    Ok(response::Dispatch {
+        id: row.get("dispatch_id"),
+        nation: row.get("nation"),
+        category: row.get("category"),
+        subcategory: row.get("subcategory"),
+        title: row.get("title"),
+        text,
+        revision: row.get("revision"),
+        job_id: row.get("job_id"),
+        size: row.get("size"),
+        source_format: TextFormat::from_column(row.get("source_format")),
+        source: row.get("source"),
+        created_by: row.get("created_by"),
+        modified_at: row.get("created_at"),
+        is_active: row.get("is_active"),
+        is_protected: row.get("is_protected"),
+        rendered: None,
+        pending_or_failed_edits: None,
+    })
+}
+
+fn map_dispatch_summary(row: PgRow) -> response::DispatchSummary {
+    response::DispatchSummary {
         id: row.get("dispatch_id"),
         nation: row.get("nation"),
         category: row.get("category"),
         subcategory: row.get("subcategory"),
         title: row.get("title"),
-        text: row.get("text"),
         created_by: row.get("created_by"),
         modified_at: row.get("created_at"),
+        is_active: row.get("is_active"),
+        is_protected: row.get("is_protected"),
+    }
+}
+
+fn map_preset(row: PgRow) -> response::NationPreset {
+    response::NationPreset {
+        nation: row.get("nation"),
+        default_category: row.get("default_category"),
+        default_subcategory: row.get("default_subcategory"),
+        title_prefix: row.get("title_prefix"),
+        signature_text: row.get("signature_text"),
+        updated_by: row.get("updated_by"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+/// The warnings stored when the job was queued, and one about the size of the revision it wrote
+/// if that's close to NS's limit, see [`dispatch::size_warning`].
+fn status_warnings(row: &PgRow, size_warn_utilization: f64) -> Vec<String> {
+    let mut warnings: Vec<String> = row.get("warnings");
+
+    if let Some(warning) = row
+        .get::<Option<i32>, _>("revision_size")
+        .and_then(|size| dispatch::size_warning(size as usize, size_warn_utilization))
+    {
+        warnings.push(warning);
     }
+
+    warnings
 }
 
-fn map_dispatch_status(row: PgRow) -> DispatchStatus {
+fn map_dispatch_status(row: PgRow, size_warn_utilization: f64) -> DispatchStatus {
+    let (id, action, status, dispatch_id) = (
+        row.get("id"),
+        row.get::<String, _>("action"),
+        row.get::<JobStatus, _>("status"),
+        row.get("dispatch_id"),
+    );
+
     DispatchStatus {
-        id: row.get("id"),
-        action: row.get("action"),
-        status: row.get("status"),
-        dispatch_id: row.get("dispatch_id"),
+        self_url: DispatchStatus::self_url(id),
+        resource: DispatchStatus::resource_url(&action, status, dispatch_id),
+        id,
+        action,
+        status,
+        dispatch_id,
         error: row.get("error"),
+        error_code: row.get("error_code"),
+        warnings: status_warnings(&row, size_warn_utilization),
         created_at: row.get("created_at"),
+        claimed_at: row.get("claimed_at"),
+        started_at: row.get("started_at"),
+        prepared_at: row.get("prepared_at"),
+        executing_at: row.get("executing_at"),
+        finished_at: row.get("finished_at"),
         modified_at: row.get("modified_at"),
+        durations: response::JobDurations::new(
+            row.get("created_at"),
+            row.get("claimed_at"),
+            row.get("started_at"),
+            row.get("prepared_at"),
+            row.get("executing_at"),
+            row.get("finished_at"),
+        ),
+        superseded_by: row.get("superseded_by"),
+        revision_id: row.get("revision_id"),
+        estimated_completion_at: None,
+        payload: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(username: &str, claims: &[&str]) -> AuthorizedUser {
+        AuthorizedUser {
+            id: 1,
+            username: username.to_string(),
+            password_hash: String::new(),
+            claims: claims.iter().map(|claim| claim.to_string()).collect(),
+            created_at: chrono::Utc::now(),
+            tenant_id: crate::types::DEFAULT_TENANT,
+            api_key_id: None,
+        }
+    }
+
+    fn meta(owner: Option<&str>) -> DispatchMeta {
+        DispatchMeta {
+            nation: "testlandia".to_string(),
+            is_active: true,
+            owner: owner.map(str::to_string),
+            revision: 42,
+            revised_by: "bob".to_string(),
+            revised_at: chrono::Utc::now(),
+            is_protected: false,
+            protected_by: None,
+            protected_at: None,
+        }
+    }
+
+    fn preset() -> response::NationPreset {
+        response::NationPreset {
+            nation: "testlandia".to_string(),
+            default_category: Some(3),
+            default_subcategory: Some(315),
+            title_prefix: Some("News: ".to_string()),
+            signature_text: Some("[i]The Herald[/i]".to_string()),
+            updated_by: "admin".to_string(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    fn new_dispatch(category: Option<(i16, i16)>) -> NewDispatch {
+        NewDispatch {
+            nation: "testlandia".to_string(),
+            title: "Headline".to_string(),
+            text: "story\n".to_string(),
+            category: category.map(|(category, _)| category),
+            subcategory: category.map(|(_, subcategory)| subcategory),
+            format: TextFormat::Bbcode,
+        }
+    }
+
+    #[test]
+    fn test_apply_preset() {
+        // explicit values win over the preset's
+        let mut dispatch = new_dispatch(Some((1, 100)));
+        apply_preset(&preset(), &mut dispatch);
+        assert_eq!(
+            (dispatch.category, dispatch.subcategory),
+            (Some(1), Some(100))
+        );
+        assert_eq!(dispatch.title, "News: Headline");
+        assert_eq!(dispatch.text, "story\n\n[i]The Herald[/i]");
+
+        // the preset fills in what was left out
+        let mut dispatch = new_dispatch(None);
+        apply_preset(&preset(), &mut dispatch);
+        assert_eq!(
+            (dispatch.category, dispatch.subcategory),
+            (Some(3), Some(315))
+        );
+        assert!(dispatch.factbook_category().is_ok());
+
+        // applying it again changes nothing
+        let applied = dispatch.clone();
+        apply_preset(&preset(), &mut dispatch);
+        assert_eq!(dispatch.title, applied.title);
+        assert_eq!(dispatch.text, applied.text);
+
+        // neither has a category
+        let mut dispatch = new_dispatch(None);
+        apply_preset(
+            &response::NationPreset {
+                default_category: None,
+                default_subcategory: None,
+                ..preset()
+            },
+            &mut dispatch,
+        );
+        assert!(matches!(
+            dispatch.factbook_category(),
+            Err(Error::MissingDispatchCategory)
+        ));
+    }
+
+    #[test]
+    fn test_owner_can_modify() {
+        let alice = user("alice", &["dispatches.edit", "dispatches.delete"]);
+
+        assert!(check_ownership(&alice, &meta(Some("alice")), Claim::DispatchesEditAny).is_ok());
+        assert!(check_ownership(&alice, &meta(Some("alice")), Claim::DispatchesDeleteAny).is_ok());
+    }
+
+    #[test]
+    fn test_non_owner_is_rejected() {
+        let bob = user("bob", &["dispatches.edit", "dispatches.delete"]);
+
+        for claim in [Claim::DispatchesEditAny, Claim::DispatchesDeleteAny] {
+            match check_ownership(&bob, &meta(Some("alice")), claim) {
+                Err(Error::NotDispatchOwner(owner)) => assert_eq!(owner, "alice"),
+                other => panic!("expected NotDispatchOwner, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_any_claim_overrides_ownership() {
+        let editor = user("bob", &["dispatches.edit", "dispatches.edit.any"]);
+
+        assert!(check_ownership(&editor, &meta(Some("alice")), Claim::DispatchesEditAny).is_ok());
+        assert!(
+            check_ownership(&editor, &meta(Some("alice")), Claim::DispatchesDeleteAny).is_err()
+        );
+
+        let deleter = user("bob", &["dispatches.delete", "dispatches.delete.any"]);
+
+        assert!(
+            check_ownership(&deleter, &meta(Some("alice")), Claim::DispatchesDeleteAny).is_ok()
+        );
+        assert!(check_ownership(&deleter, &meta(Some("alice")), Claim::DispatchesEditAny).is_err());
+    }
+
+    #[test]
+    fn test_unowned_dispatch_requires_any_claim() {
+        let alice = user("alice", &["dispatches.edit"]);
+        let editor = user("bob", &["dispatches.edit.any"]);
+
+        assert!(check_ownership(&alice, &meta(None), Claim::DispatchesEditAny).is_err());
+        assert!(check_ownership(&editor, &meta(None), Claim::DispatchesEditAny).is_ok());
+    }
+
+    #[test]
+    fn test_edit_on_latest_revision() {
+        assert!(check_revision(Some(42), &meta(Some("alice"))).is_ok());
+        assert!(check_revision(None, &meta(Some("alice"))).is_ok());
+    }
+
+    #[test]
+    fn test_edit_on_stale_revision_conflicts() {
+        let meta = meta(Some("alice"));
+
+        match check_revision(Some(41), &meta) {
+            Err(Error::EditConflict(conflict)) => {
+                let body = serde_json::to_value(&conflict).unwrap();
+
+                assert_eq!(body["base_revision"], 41);
+                assert_eq!(body["revision"], 42);
+                assert_eq!(body["revised_by"], "bob");
+                assert_eq!(
+                    body["revised_at"],
+                    serde_json::to_value(meta.revised_at).unwrap()
+                );
+            }
+            other => panic!("expected EditConflict, got {:?}", other),
+        }
+
+        // a revision that never existed is just as stale
+        assert!(check_revision(Some(43), &meta).is_err());
+    }
+
+    #[test]
+    fn test_protected_dispatch_is_locked() {
+        assert!(check_protection(7, &meta(Some("alice"))).is_ok());
+
+        let protected_at = chrono::Utc::now();
+        let meta = DispatchMeta {
+            is_protected: true,
+            protected_by: Some("admin".to_string()),
+            protected_at: Some(protected_at),
+            ..meta(Some("alice"))
+        };
+
+        match check_protection(7, &meta) {
+            Err(Error::DispatchProtected(protected)) => {
+                assert_eq!(protected.id, 7);
+                assert_eq!(protected.protected_by.as_deref(), Some("admin"));
+                assert_eq!(protected.protected_at, Some(protected_at));
+            }
+            other => panic!("expected DispatchProtected, got {:?}", other),
+        }
     }
 }
@@ -0,0 +1,68 @@
+//! Samples the views and score NS reports for each active dispatch into `dispatch_metrics`, for
+//! `GET /dispatches/{id}/metrics` and `GET /stats/dispatches/top`. Each round reads every active
+//! dispatch once, its requests spaced evenly over the interval so they share the standard limit
+//! with everything else instead of arriving at once.
+
+use std::time::Duration;
+
+use crate::controllers::dispatch;
+use crate::core::error::Error;
+
+/// Starts collecting every `interval`, unless it's zero.
+pub(crate) fn start(controller: dispatch::Controller, interval: Duration) {
+    if interval.is_zero() {
+        return;
+    }
+
+    tracing::info!("starting dispatch metrics collector");
+    tokio::spawn(run(controller, interval));
+}
+
+async fn run(controller: dispatch::Controller, interval: Duration) {
+    loop {
+        let ids = match controller.active_ids().await {
+            Ok(ids) => ids,
+            Err(e) => {
+                tracing::error!("unable to list dispatches for metrics: {}", e);
+                Vec::new()
+            }
+        };
+
+        if ids.is_empty() {
+            tokio::time::sleep(interval).await;
+            continue;
+        }
+
+        let spacing = interval / ids.len() as u32;
+
+        for id in ids {
+            tokio::time::sleep(spacing).await;
+
+            // a paused pipeline means NS shouldn't hear from us about dispatches
+            if controller.is_paused() {
+                continue;
+            }
+
+            if let Err(e) = sample(&controller, id).await {
+                match e {
+                    Error::DispatchNotFound => {
+                        tracing::warn!("dispatch {} is gone from NS, no metrics sampled", id)
+                    }
+                    e => tracing::error!("unable to sample metrics of dispatch {}: {}", id, e),
+                }
+            }
+        }
+    }
+}
+
+async fn sample(controller: &dispatch::Controller, id: i32) -> Result<(), Error> {
+    let dispatch = controller.fetch_public(id).await?;
+
+    match (dispatch.views, dispatch.score) {
+        (Some(views), Some(score)) => controller.record_metrics(id, views, score).await,
+        _ => {
+            tracing::debug!("NS reported no metrics for dispatch {}", id);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::core::error::Error;
+use crate::types::AuthorizedUser;
+use axum::http::{HeaderMap, StatusCode};
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, PgPool, Postgres, Row, Transaction};
+
+const HEADER: &str = "idempotency-key";
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Keys older than this are forgotten, and may be reused.
+const TTL_HOURS: i32 = 24;
+
+/// Outcome of a job submission that may have carried an `Idempotency-Key`.
+#[derive(Debug)]
+pub(crate) enum Submitted<T> {
+    Created(T),
+    /// The key was already used, `T` is the current status of the original job.
+    Replayed(T),
+}
+
+impl<T> Submitted<T> {
+    pub(crate) fn inner(&self) -> &T {
+        match self {
+            Submitted::Created(inner) | Submitted::Replayed(inner) => inner,
+        }
+    }
+
+    pub(crate) fn status_code(&self) -> StatusCode {
+        match self {
+            Submitted::Created(_) => StatusCode::ACCEPTED,
+            Submitted::Replayed(_) => StatusCode::OK,
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> T {
+        match self {
+            Submitted::Created(inner) | Submitted::Replayed(inner) => inner,
+        }
+    }
+}
+
+/// An `Idempotency-Key` as sent by `user` for one kind of submission.
+#[derive(Debug)]
+pub(crate) struct Key {
+    user_id: i32,
+    scope: &'static str,
+    key: String,
+}
+
+impl Key {
+    /// Reads the `Idempotency-Key` header, if present.
+    pub(crate) fn from_headers(
+        headers: &HeaderMap,
+        user: &AuthorizedUser,
+        scope: &'static str,
+    ) -> Result<Option<Self>, Error> {
+        let value = match headers.get(HEADER) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        match value.to_str() {
+            Ok(key) if is_valid(key) => Ok(Some(Self {
+                user_id: user.id,
+                scope,
+                key: key.to_string(),
+            })),
+            _ => Err(Error::InvalidIdempotencyKey),
+        }
+    }
+
+    /// The job previously submitted with this key, if it hasn't expired.
+    #[tracing::instrument(skip_all)]
+    async fn find(&self, pool: &PgPool) -> Result<Option<i32>, Error> {
+        Ok(sqlx::query(
+            "SELECT job_id FROM idempotency_keys
+            WHERE user_id = $1 AND scope = $2 AND key = $3
+            AND created_at > NOW() - make_interval(hours => $4);",
+        )
+        .bind(self.user_id)
+        .bind(self.scope)
+        .bind(&self.key)
+        .bind(TTL_HOURS)
+        .map(|row: PgRow| row.get("job_id"))
+        .fetch_optional(pool)
+        .await?)
+    }
+
+    /// Associates the key with `job_id`. Returns `false` if a concurrent request already claimed
+    /// the key; the unique index makes this wait for that request's transaction to finish.
+    #[tracing::instrument(skip_all)]
+    async fn store(&self, conn: &mut PgConnection, job_id: i32) -> Result<bool, Error> {
+        sqlx::query(
+            "DELETE FROM idempotency_keys WHERE created_at < NOW() - make_interval(hours => $1);",
+        )
+        .bind(TTL_HOURS)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(sqlx::query(
+            "INSERT INTO idempotency_keys (user_id, scope, key, job_id) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, scope, key) DO NOTHING;",
+        )
+        .bind(self.user_id)
+        .bind(self.scope)
+        .bind(&self.key)
+        .bind(job_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected()
+            == 1)
+    }
+}
+
+/// The job previously submitted with `key`, if there is a key and it was used before.
+pub(crate) async fn previous_job(key: Option<&Key>, pool: &PgPool) -> Result<Option<i32>, Error> {
+    match key {
+        Some(key) => key.find(pool).await,
+        None => Ok(None),
+    }
+}
+
+/// Records `key` for `job_id` and commits the transaction that created the job. If a concurrent
+/// request claimed the key first, rolls back instead and returns that request's job.
+pub(crate) async fn commit(
+    key: Option<&Key>,
+    mut transaction: Transaction<'_, Postgres>,
+    pool: &PgPool,
+    job_id: i32,
+) -> Result<Option<i32>, Error> {
+    let stored = match key {
+        Some(key) => key.store(&mut transaction, job_id).await?,
+        None => true,
+    };
+
+    if stored {
+        transaction.commit().await?;
+
+        return Ok(None);
+    }
+
+    transaction.rollback().await?;
+
+    match previous_job(key, pool).await? {
+        Some(job_id) => Ok(Some(job_id)),
+        None => Err(Error::Internal),
+    }
+}
+
+fn is_valid(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_KEY_LENGTH
+        && key.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use chrono::Utc;
+
+    fn user() -> AuthorizedUser {
+        AuthorizedUser {
+            id: 7,
+            username: "alice".to_string(),
+            password_hash: String::new(),
+            claims: vec![],
+            created_at: Utc::now(),
+            tenant_id: crate::types::DEFAULT_TENANT,
+            api_key_id: None,
+        }
+    }
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(HEADER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_key_from_headers() {
+        assert!(
+            Key::from_headers(&HeaderMap::new(), &user(), "dispatch.create")
+                .unwrap()
+                .is_none()
+        );
+
+        let key = Key::from_headers(&headers("3f2a-retry"), &user(), "dispatch.create")
+            .unwrap()
+            .unwrap();
+        assert_eq!(key.user_id, 7);
+        assert_eq!(key.scope, "dispatch.create");
+        assert_eq!(key.key, "3f2a-retry");
+
+        for value in ["", "has space", &"k".repeat(MAX_KEY_LENGTH + 1)] {
+            assert!(matches!(
+                Key::from_headers(&headers(value), &user(), "dispatch.create"),
+                Err(Error::InvalidIdempotencyKey)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_submitted() {
+        let created = Submitted::Created(1);
+        assert_eq!(created.status_code(), StatusCode::ACCEPTED);
+        assert_eq!(*created.inner(), 1);
+
+        let replayed = Submitted::Replayed(2);
+        assert_eq!(replayed.status_code(), StatusCode::OK);
+        assert_eq!(replayed.into_inner(), 2);
+    }
+}
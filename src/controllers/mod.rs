@@ -1,5 +1,16 @@
+pub(crate) mod api_key;
+pub(crate) mod audit;
+pub(crate) mod content_policy;
 pub(crate) mod dispatch;
+pub(crate) mod dispatch_metrics;
+pub(crate) mod idempotency;
+mod preflight;
+pub(crate) mod quota;
+pub(crate) mod retention;
 pub(crate) mod rmbpost;
 pub(crate) mod telegram;
-mod token;
+pub(crate) mod tenant;
+pub(crate) mod throttle;
+mod timings;
 pub(crate) mod user;
+pub(crate) mod webhook;
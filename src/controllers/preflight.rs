@@ -0,0 +1,149 @@
+//! Optional check, before an RMB post is queued, that NS would let the nation post on the target
+//! region's RMB. Anything the public API can't tell us is let through, NS has the final word.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::core::error::Error;
+use crate::ns::region::{self, RmbPolicy};
+use crate::sync::ratelimiter;
+
+/// How long a looked up region or residency is trusted.
+const CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, Debug)]
+struct Cache<V> {
+    entries: Arc<Mutex<HashMap<String, (Instant, V)>>>,
+}
+
+impl<V: Clone> Cache<V> {
+    fn new() -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn get(&self, key: &str, now: Instant) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match entries.get(key) {
+            Some((fetched_at, value)) if now.duration_since(*fetched_at) < CACHE_TTL => {
+                Some(value.clone())
+            }
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, key: &str, value: V, now: Instant) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key.to_string(), (now, value));
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Preflight {
+    client: reqwest::Client,
+    url: String,
+    limiter: ratelimiter::Sender,
+    homes: Cache<String>,
+    policies: Cache<RmbPolicy>,
+}
+
+impl Preflight {
+    pub(crate) fn new(client: reqwest::Client, url: &str, limiter: ratelimiter::Sender) -> Self {
+        Self {
+            client,
+            url: url.to_string(),
+            limiter,
+            homes: Cache::new(),
+            policies: Cache::new(),
+        }
+    }
+
+    /// Fails with [`Error::RmbPostRefused`] if `nation` clearly can't post on `region`'s RMB, or
+    /// with [`Error::RegionNotFound`] if NS doesn't know the region. Both names must be
+    /// canonicalized.
+    #[tracing::instrument(skip(self))]
+    pub(crate) async fn check(&self, nation: &str, region: &str) -> Result<(), Error> {
+        let home = match self.home_region(nation).await {
+            Ok(home) => home,
+            Err(e) => {
+                tracing::warn!("unable to look up the region of {}: {}", nation, e);
+                return Ok(());
+            }
+        };
+
+        // residents may always post
+        if home == region {
+            return Ok(());
+        }
+
+        let policy = match self.policy(region).await {
+            Ok(policy) => policy,
+            Err(Error::RegionNotFound) => return Err(Error::RegionNotFound),
+            Err(e) => {
+                tracing::warn!("unable to look up the RMB policy of {}: {}", region, e);
+                return Ok(());
+            }
+        };
+
+        match policy.refusal(&home) {
+            Some(refusal) => Err(Error::RmbPostRefused(refusal)),
+            None => Ok(()),
+        }
+    }
+
+    async fn home_region(&self, nation: &str) -> Result<String, Error> {
+        if let Some(home) = self.homes.get(nation, Instant::now()) {
+            return Ok(home);
+        }
+
+        let home = region::home_region(&self.client, &self.url, &self.limiter, nation).await?;
+        self.homes.insert(nation, home.clone(), Instant::now());
+
+        Ok(home)
+    }
+
+    async fn policy(&self, region: &str) -> Result<RmbPolicy, Error> {
+        if let Some(policy) = self.policies.get(region, Instant::now()) {
+            return Ok(policy);
+        }
+
+        let policy = region::rmb_policy(&self.client, &self.url, &self.limiter, region).await?;
+        self.policies.insert(region, policy.clone(), Instant::now());
+
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_expires() {
+        let cache = Cache::new();
+        let now = Instant::now();
+
+        cache.insert("the_north_pacific", 1, now);
+
+        assert_eq!(cache.get("the_north_pacific", now), Some(1));
+        assert_eq!(
+            cache.get(
+                "the_north_pacific",
+                now + CACHE_TTL - Duration::from_secs(1)
+            ),
+            Some(1)
+        );
+        assert_eq!(cache.get("the_north_pacific", now + CACHE_TTL), None);
+        assert_eq!(cache.get("the_north_pacific", now), None);
+        assert_eq!(cache.get("the_south_pacific", now), None);
+    }
+}
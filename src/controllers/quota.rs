@@ -0,0 +1,111 @@
+use crate::core::authorization::Claim;
+use crate::core::error::Error;
+use crate::types::AuthorizedUser;
+use crate::types::job::JobStatus;
+use crate::types::response::{QuotaExceeded, QuotaLimits};
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, Row};
+
+/// Per-user limits on queued jobs. Users holding the `admin` claim are exempt.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Quota {
+    max_pending: i64,
+    max_daily: Option<i64>,
+}
+
+struct Usage {
+    pending: i64,
+    oldest_pending: Option<DateTime<Utc>>,
+    daily: i64,
+    oldest_daily: Option<DateTime<Utc>>,
+}
+
+impl Quota {
+    pub(crate) fn new(max_pending: i64, max_daily: Option<i64>) -> Self {
+        Self {
+            max_pending,
+            max_daily,
+        }
+    }
+
+    /// The limits `user` is held to.
+    pub(crate) fn limits(&self, user: &AuthorizedUser) -> QuotaLimits {
+        if user.holds(Claim::Admin) {
+            return QuotaLimits {
+                max_pending: None,
+                max_daily: None,
+            };
+        }
+
+        QuotaLimits {
+            max_pending: Some(self.max_pending),
+            max_daily: self.max_daily,
+        }
+    }
+
+    /// Whether `user` may queue `jobs` more jobs. `table` must be one of the job queue tables, it
+    /// is interpolated into the query as-is. Locks the user's row until `transaction` ends, so
+    /// the jobs have to be inserted in the same transaction; a concurrent request of the same
+    /// user waits and then counts them.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn check(
+        &self,
+        transaction: &mut PgConnection,
+        table: &str,
+        user: &AuthorizedUser,
+        jobs: i64,
+    ) -> Result<(), Error> {
+        if user.holds(Claim::Admin) {
+            return Ok(());
+        }
+
+        sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE;")
+            .bind(user.id)
+            .execute(&mut *transaction)
+            .await?;
+
+        // scheduled jobs aren't due yet, but they still hold a place in the user's quota
+        let usage = sqlx::query(&format!(
+            "SELECT
+                COUNT(*) FILTER (WHERE status = 'scheduled' OR status = ANY($2)) AS pending,
+                MIN(created_at) FILTER (WHERE status = 'scheduled' OR status = ANY($2)) AS oldest_pending,
+                COUNT(*) FILTER (WHERE created_at > NOW() - INTERVAL '1 day') AS daily,
+                MIN(created_at) FILTER (WHERE created_at > NOW() - INTERVAL '1 day') AS oldest_daily
+            FROM {table}
+            WHERE created_by = $1;"
+        ))
+        .bind(&user.username)
+        .bind(JobStatus::PENDING)
+        .map(|row: PgRow| Usage {
+            pending: row.get("pending"),
+            oldest_pending: row.get("oldest_pending"),
+            daily: row.get("daily"),
+            oldest_daily: row.get("oldest_daily"),
+        })
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        if usage.pending + jobs > self.max_pending {
+            return Err(Error::QuotaExceeded(QuotaExceeded::new(
+                "pending",
+                self.max_pending,
+                usage.oldest_pending,
+                None,
+            )));
+        }
+
+        if let Some(max_daily) = self.max_daily.filter(|max| usage.daily + jobs > *max) {
+            return Err(Error::QuotaExceeded(QuotaExceeded::new(
+                "daily",
+                max_daily,
+                usage.oldest_daily,
+                usage
+                    .oldest_daily
+                    .map(|oldest| oldest + chrono::Duration::days(1)),
+            )));
+        }
+
+        Ok(())
+    }
+}
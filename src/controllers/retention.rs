@@ -0,0 +1,270 @@
+use crate::core::config::Args;
+use crate::core::error::Error;
+use crate::types::response::{PrunedTable, RetentionStatus};
+use chrono::Utc;
+use sqlx::PgPool;
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Time between sweeps.
+const PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Rows deleted per statement, small enough that no statement holds its locks for long.
+const BATCH_SIZE: i64 = 500;
+
+/// Pause between batches, leaving the workers room to get at the tables.
+const BATCH_PAUSE: Duration = Duration::from_millis(200);
+
+/// What to prune. A window or cap of zero leaves its table alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Policy {
+    /// days finished dispatch jobs are kept
+    pub(crate) dispatch_queue_days: u32,
+    /// days finished RMB post jobs are kept
+    pub(crate) rmbpost_queue_days: u32,
+    /// days sent, failed and skipped telegrams are kept
+    pub(crate) telegram_queue_days: u32,
+    /// revisions kept per dispatch, counting the current one
+    pub(crate) dispatch_revisions: u32,
+    /// count what would be pruned without deleting anything
+    pub(crate) dry_run: bool,
+}
+
+impl Policy {
+    pub(crate) fn from_config(config: &Args) -> Self {
+        Self {
+            dispatch_queue_days: config.retention_dispatch_queue_days,
+            rmbpost_queue_days: config.retention_rmbpost_queue_days,
+            telegram_queue_days: config.retention_telegram_queue_days,
+            dispatch_revisions: config.retention_dispatch_revisions,
+            dry_run: config.retention_dry_run,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.rules().iter().any(|(_, limit)| *limit > 0)
+    }
+
+    fn rules(&self) -> [(Rule, u32); 4] {
+        [
+            (Rule::DispatchQueue, self.dispatch_queue_days),
+            (Rule::RmbpostQueue, self.rmbpost_queue_days),
+            (Rule::TelegramQueue, self.telegram_queue_days),
+            (Rule::DispatchRevisions, self.dispatch_revisions),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Rule {
+    DispatchQueue,
+    RmbpostQueue,
+    TelegramQueue,
+    DispatchRevisions,
+}
+
+impl Rule {
+    fn table(&self) -> &'static str {
+        match self {
+            Rule::DispatchQueue => "dispatch_queue",
+            Rule::RmbpostQueue => "rmbpost_queue",
+            Rule::TelegramQueue => "telegram_queue",
+            Rule::DispatchRevisions => "dispatch_content",
+        }
+    }
+
+    /// Selects the ids of prunable rows, `$1` being the window or cap and `$2` the batch size.
+    /// Telegrams of a campaign still in progress are kept, its progress counts them.
+    fn candidates(&self) -> &'static str {
+        match self {
+            Rule::DispatchQueue => {
+                "SELECT id FROM dispatch_queue
+                WHERE status IN ('succeeded', 'failed', 'cancelled', 'superseded')
+                AND COALESCE(finished_at, modified_at) < NOW() - make_interval(days => $1)
+                ORDER BY id
+                LIMIT $2"
+            }
+            Rule::RmbpostQueue => {
+                "SELECT id FROM rmbpost_queue
+                WHERE status IN ('succeeded', 'failed', 'cancelled', 'superseded')
+                AND COALESCE(finished_at, modified_at) < NOW() - make_interval(days => $1)
+                ORDER BY id
+                LIMIT $2"
+            }
+            Rule::TelegramQueue => {
+                "SELECT id FROM telegram_queue
+                WHERE status IN ('sent', 'failed', 'skipped')
+                AND modified_at < NOW() - make_interval(days => $1)
+                AND NOT EXISTS (
+                    SELECT 1 FROM telegram_campaign_recipients
+                    JOIN telegram_campaigns
+                        ON telegram_campaigns.id = telegram_campaign_recipients.campaign_id
+                    WHERE telegram_campaign_recipients.job_id = telegram_queue.id
+                    AND telegram_campaigns.status <> 'completed'
+                )
+                ORDER BY id
+                LIMIT $2"
+            }
+            Rule::DispatchRevisions => {
+                "SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY dispatch_id ORDER BY id DESC) AS rank
+                    FROM dispatch_content
+                ) revisions
+                WHERE rank > $1
+                ORDER BY id
+                LIMIT $2"
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(pool))]
+    async fn count(&self, pool: &PgPool, limit: u32) -> Result<i64, Error> {
+        Ok(sqlx::query(&format!(
+            "SELECT COUNT(*) AS count FROM ({}) candidates;",
+            self.candidates()
+        ))
+        .bind(limit as i32)
+        .bind(i64::MAX)
+        .map(|row: PgRow| row.get("count"))
+        .fetch_one(pool)
+        .await?)
+    }
+
+    /// Deletes in batches until nothing is left to prune.
+    #[tracing::instrument(skip(pool))]
+    async fn prune(&self, pool: &PgPool, limit: u32) -> Result<i64, Error> {
+        let statement = format!(
+            "DELETE FROM {} WHERE id IN ({});",
+            self.table(),
+            self.candidates()
+        );
+
+        let mut pruned = 0;
+
+        loop {
+            let deleted = sqlx::query(&statement)
+                .bind(limit as i32)
+                .bind(BATCH_SIZE)
+                .execute(pool)
+                .await?
+                .rows_affected() as i64;
+
+            pruned += deleted;
+
+            if deleted < BATCH_SIZE {
+                return Ok(pruned);
+            }
+
+            tokio::time::sleep(BATCH_PAUSE).await;
+        }
+    }
+}
+
+/// Applies `policy` once, reporting per table how many rows were pruned, or would have been.
+#[tracing::instrument(skip(pool))]
+pub(crate) async fn sweep(pool: &PgPool, policy: &Policy) -> Result<Vec<PrunedTable>, Error> {
+    let mut tables = Vec::new();
+
+    for (rule, limit) in policy.rules() {
+        if limit == 0 {
+            continue;
+        }
+
+        let rows = if policy.dry_run {
+            rule.count(pool, limit).await?
+        } else {
+            rule.prune(pool, limit).await?
+        };
+
+        if rows > 0 {
+            tracing::info!(
+                "{} {} rows from {}",
+                if policy.dry_run {
+                    "would prune"
+                } else {
+                    "pruned"
+                },
+                rows,
+                rule.table()
+            );
+        }
+
+        tables.push(PrunedTable {
+            table: rule.table().to_string(),
+            rows,
+        });
+    }
+
+    Ok(tables)
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Controller {
+    rx: watch::Receiver<RetentionStatus>,
+}
+
+impl Controller {
+    /// Starts sweeping with `policy` unless it's disabled.
+    pub(crate) fn new(pool: PgPool, policy: Policy) -> Self {
+        let (tx, rx) = watch::channel(RetentionStatus {
+            enabled: policy.is_enabled(),
+            dry_run: policy.dry_run,
+            last_sweep_at: None,
+            tables: Vec::new(),
+        });
+
+        if policy.is_enabled() {
+            tracing::info!("starting retention sweeper");
+            tokio::spawn(run(pool, policy, tx));
+        }
+
+        Self { rx }
+    }
+
+    /// Outcome of the last completed sweep.
+    pub(crate) fn status(&self) -> RetentionStatus {
+        self.rx.borrow().clone()
+    }
+}
+
+async fn run(pool: PgPool, policy: Policy, tx: watch::Sender<RetentionStatus>) {
+    let mut interval = tokio::time::interval(PERIOD);
+
+    loop {
+        interval.tick().await;
+
+        match sweep(&pool, &policy).await {
+            Ok(tables) => tx.send_modify(|status| {
+                status.last_sweep_at = Some(Utc::now());
+                status.tables = tables;
+            }),
+            Err(e) => tracing::error!("retention sweep failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_by_default() {
+        assert!(!Policy::default().is_enabled());
+        assert!(
+            !Policy {
+                dry_run: true,
+                ..Policy::default()
+            }
+            .is_enabled()
+        );
+        assert!(
+            Policy {
+                dispatch_revisions: 5,
+                ..Policy::default()
+            }
+            .is_enabled()
+        );
+    }
+}
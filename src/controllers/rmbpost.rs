@@ -1,90 +1,771 @@
+use crate::controllers::content_policy::ContentPolicy;
+use crate::controllers::idempotency::{self, Submitted};
+use crate::controllers::preflight::Preflight;
+use crate::controllers::quota::Quota;
+use crate::controllers::{tenant, timings};
+use crate::core::db::{DbPools, Pool};
 use crate::core::error::{ConfigError, Error};
+use crate::ns;
 use crate::ns::rmbpost;
-use crate::ns::rmbpost::{Action, IntermediateRmbPost, NewRmbPost};
-use crate::sync::{nations, ratelimiter};
-use crate::types::response;
+use crate::ns::rmbpost::{Action, IntermediateRmbPost, NewRmbPost, NewRmbPostBatch};
+use crate::sync::ratelimiter::{RestrictedAction, Target};
+use crate::sync::{events, nations, ratelimiter};
+use crate::types::job::JobStatus;
+use crate::types::request::{JobKind, RequestId, TimingsQuery};
+use crate::types::response::NationStatus;
+use crate::types::{AuthorizedUser, response};
+use crate::utils::name;
 use crate::workers;
-use sqlx::PgPool;
+use reqwest::StatusCode;
 use sqlx::Row;
 use sqlx::postgres::PgRow;
-use tokio::sync::{mpsc, oneshot};
+use std::collections::HashMap;
+use tokio::sync::oneshot;
+
+/// Most regions one `POST /rmbposts/batch` may post to.
+const MAX_BATCH_SIZE: usize = 20;
 
 #[derive(Clone, Debug)]
 pub(crate) struct Controller {
-    pool: PgPool,
-    tx: mpsc::Sender<rmbpost::Command>,
+    url: String,
+    client: reqwest::Client,
+    pools: DbPools,
+    tx: workers::Handle<rmbpost::Command>,
+    limiter: ratelimiter::Sender,
+    nations: nations::Sender,
+    quota: Quota,
+    policy: ContentPolicy,
+    verify_region: bool,
+    preflight: Option<Preflight>,
 }
 
 impl Controller {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         user_agent: &str,
         url: &str,
-        pool: PgPool,
+        pools: DbPools,
         limiter: ratelimiter::Sender,
         nations: nations::Sender,
+        events: events::Sender,
+        quota: Quota,
+        policy: ContentPolicy,
+        verify_region: bool,
+        preflight: bool,
+        recovery: workers::Recovery,
+        channel: workers::Channel,
     ) -> Result<Self, ConfigError> {
-        let (tx, mut client) =
-            workers::rmbpost::new(user_agent, url, pool.clone(), limiter, nations)?;
+        let tx = workers::supervise("rmbpost", channel.send_timeout, {
+            let (user_agent, url) = (user_agent.to_string(), url.to_string());
+            let pool = pools.get(Pool::Primary).clone();
+            let (limiter, nations) = (limiter.clone(), nations.clone());
+
+            move |activity| {
+                let (tx, mut client) = workers::rmbpost::new(
+                    &user_agent,
+                    &url,
+                    pool.clone(),
+                    limiter.clone(),
+                    nations.clone(),
+                    events.clone(),
+                    recovery,
+                    channel.capacity,
+                    activity,
+                )?;
+
+                Ok((tx, async move { client.run().await }))
+            }
+        })?;
+
+        let client = reqwest::Client::builder().user_agent(user_agent).build()?;
+
+        Ok(Self {
+            url: url.to_string(),
+            preflight: preflight.then(|| Preflight::new(client.clone(), url, limiter.clone())),
+            client,
+            pools,
+            tx,
+            limiter,
+            nations,
+            quota,
+            policy,
+            verify_region,
+        })
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn nations(&self) -> Result<Vec<String>, Error> {
+        let mut nations = self.nations.list_nations().await?;
+
+        nations.sort();
+
+        Ok(nations)
+    }
+
+    /// Commands waiting for the worker, and how many its channel holds.
+    pub(crate) fn channel_occupancy(&self) -> (usize, usize) {
+        self.tx.occupancy()
+    }
 
-        tokio::spawn(async move { client.run().await });
+    pub(crate) fn limits(&self) -> response::RmbpostLimits {
+        response::RmbpostLimits {
+            max_batch_size: MAX_BATCH_SIZE,
+        }
+    }
+
+    /// Returns the configured rmbpost nation matching `nation` once both are canonicalized.
+    #[tracing::instrument(skip_all)]
+    async fn configured_nation(&self, nation: &str) -> Result<String, Error> {
+        let nation = name::canonicalize(nation);
 
-        Ok(Self { pool, tx })
+        self.nations
+            .list_nations()
+            .await?
+            .into_iter()
+            .find(|configured| name::canonicalize(configured) == nation)
+            .ok_or(Error::InvalidNation)
+    }
+
+    /// Checks the stored password of `nation` with NS, see [`ns::verify`]. Returns `None` if
+    /// `nation` isn't an RMB post nation.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn verify(
+        &self,
+        nation: &str,
+    ) -> Result<Option<response::CredentialCheck>, Error> {
+        let nation = name::canonicalize(nation);
+
+        match ns::verify(
+            &self.client,
+            &self.url,
+            &self.limiter,
+            &self.nations,
+            &nation,
+        )
+        .await
+        {
+            Ok(check) => Ok(Some(check)),
+            Err(Error::InvalidNation) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Re-checks the password of `nation` with NS and clears its unhealthy flag if it works.
+    /// With `ping` false the flag is cleared without asking NS. Returns false if `nation` isn't
+    /// an RMB post nation.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn revalidate(&self, nation: &str, ping: bool) -> Result<bool, Error> {
+        let nation = match self.configured_nation(nation).await {
+            Ok(nation) => nation,
+            Err(Error::InvalidNation) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        if !ping {
+            self.nations.mark_healthy(&nation).await?;
+
+            return Ok(true);
+        }
+
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .send(rmbpost::Command::new(Action::Revalidate { nation }, tx))
+            .await?;
+
+        match rx.await {
+            Ok(rmbpost::Response::Success) => Ok(true),
+            Ok(rmbpost::Response::Error(e)) => Err(e),
+            Ok(_) => Err(Error::Internal),
+            Err(e) => {
+                tracing::error!("received error: {}", e);
+
+                Err(Error::Internal)
+            }
+        }
+    }
+
+    /// Asks the public API whether `region` exists.
+    #[tracing::instrument(skip_all)]
+    async fn check_region(&self, region: &str) -> Result<(), Error> {
+        if let Err(duration) = self.limiter.acquire(Target::Standard).await {
+            tokio::time::sleep(duration).await;
+        }
+
+        let resp = self
+            .client
+            .get(&self.url)
+            .query(&[("region", region), ("q", "name")])
+            .send()
+            .await?;
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Err(Error::RegionNotFound);
+        }
+
+        resp.error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Checks that `region`, already canonicalized, is a region name and, if configured, that
+    /// the region exists and that `nation` may post on its RMB.
+    async fn validate_region(&self, nation: &str, region: &str) -> Result<(), Error> {
+        if !name::is_valid(region) {
+            return Err(Error::InvalidRegion);
+        }
+
+        if self.verify_region {
+            self.check_region(region).await?;
+        }
+
+        if let Some(preflight) = &self.preflight {
+            preflight.check(&name::canonicalize(nation), region).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Restricted action availability and queued posts for every rmbpost nation.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn nation_status(&self) -> Result<Vec<NationStatus>, Error> {
+        let queued: HashMap<String, i64> = sqlx::query(
+            "SELECT nation, COUNT(*) AS queued
+            FROM rmbpost_queue
+            WHERE status = ANY($1)
+            GROUP BY nation;",
+        )
+        .bind(JobStatus::PENDING)
+        .map(|row: PgRow| (row.get("nation"), row.get("queued")))
+        .fetch_all(self.pools.get(Pool::Primary))
+        .await?
+        .into_iter()
+        .collect();
+
+        let mut statuses = Vec::new();
+
+        for nation in self.nations.list_nations().await? {
+            statuses.push(NationStatus {
+                restricted_ready_in: self
+                    .limiter
+                    .peek(Target::restricted(&nation, RestrictedAction::RmbPost))
+                    .await
+                    .as_secs_f64()
+                    .ceil() as u64,
+                healthy: self.nations.is_healthy(&nation).await?,
+                queued_dispatches: None,
+                queued_rmbposts: Some(queued.get(&nation).copied().unwrap_or(0)),
+                nation,
+            });
+        }
+
+        Ok(statuses)
+    }
+
+    /// What the RMB post worker is doing, answered even while it's busy posting.
+    pub(crate) fn worker(&self) -> response::WorkerStatus {
+        self.tx.status()
+    }
+
+    /// Pauses, resumes or reports on the RMB post worker.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn control(
+        &self,
+        control: workers::Control,
+    ) -> Result<response::PipelineStatus, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .send(rmbpost::Command::new(Action::Control(control), tx))
+            .await?;
+
+        match rx.await {
+            Ok(rmbpost::Response::Pipeline(status)) => Ok(status),
+            Ok(_) => Err(Error::Internal),
+            Err(e) => {
+                tracing::error!("received error: {}", e);
+
+                Err(Error::Internal)
+            }
+        }
     }
 
     #[tracing::instrument(skip_all)]
     pub(crate) async fn queue(
         &self,
+        user: &AuthorizedUser,
         rmbpost: NewRmbPost,
-    ) -> Result<response::RmbPostStatus, Error> {
+        key: Option<idempotency::Key>,
+        request_id: &RequestId,
+    ) -> Result<Submitted<response::RmbPostStatus>, Error> {
+        let nation = self.configured_nation(&rmbpost.nation).await?;
+
+        tenant::check_nation(
+            self.pools.get(Pool::Primary),
+            user.tenant_id,
+            &name::canonicalize(&nation),
+        )
+        .await?;
+
+        if !self.nations.is_healthy(&nation).await? {
+            return Err(Error::CredentialUnhealthy(nation));
+        }
+
+        let region = name::canonicalize(&rmbpost.region);
+
+        let warnings = self.policy.check(&[&rmbpost.text])?;
+
+        self.validate_region(&nation, &region).await?;
+
+        if let Some(job_id) =
+            idempotency::previous_job(key.as_ref(), self.pools.get(Pool::Primary)).await?
+        {
+            return Ok(Submitted::Replayed(
+                self.fetch_status(job_id, Pool::Primary).await?,
+            ));
+        }
+
+        let mut transaction = self.pools.get(Pool::Primary).begin().await?;
+
+        self.quota
+            .check(&mut transaction, "rmbpost_queue", user, 1)
+            .await?;
+
         let status = sqlx::query(
-            "INSERT INTO rmbpost_queue (nation, region, content, status) VALUES ($1, $2, $3, 'queued') RETURNING
+            "INSERT INTO rmbpost_queue (nation, region, content, status, created_by, request_id, warnings, tenant_id) VALUES ($1, $2, $3, 'queued', $4, $5, $6, $7) RETURNING
                 id,
+                region,
                 status,
                 rmbpost_id,
                 error,
+                error_code,
+                warnings,
                 created_at,
+                claimed_at,
+                started_at,
+                prepared_at,
+                executing_at,
+                finished_at,
                 modified_at;",
         )
-            .bind(&rmbpost.nation)
-            .bind(&rmbpost.region)
+            .bind(&nation)
+            .bind(&region)
             .bind(&rmbpost.text)
+            .bind(&user.username)
+            .bind(&request_id.0)
+            .bind(&warnings)
+            .bind(user.tenant_id)
             .map(map_rmbpost_status)
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *transaction)
             .await?;
 
-        let rmbpost =
-            IntermediateRmbPost::new(status.id, rmbpost.nation, rmbpost.region, rmbpost.text);
+        if let Some(job_id) = idempotency::commit(
+            key.as_ref(),
+            transaction,
+            self.pools.get(Pool::Primary),
+            status.id,
+        )
+        .await?
+        {
+            return Ok(Submitted::Replayed(
+                self.fetch_status(job_id, Pool::Primary).await?,
+            ));
+        }
 
-        let (tx, rx) = oneshot::channel();
+        let rmbpost = IntermediateRmbPost::new(status.id, nation, region, rmbpost.text)
+            .with_request_id(request_id);
 
-        self.tx
-            .send(rmbpost::Command::new(Action::queue(rmbpost), tx))
+        self.submit(vec![rmbpost]).await?;
+
+        let status = response::RmbPostStatus {
+            estimated_completion_at: self.estimated_completion(status.id, Pool::Primary).await,
+            ..status
+        };
+
+        Ok(Submitted::Created(status))
+    }
+
+    /// Queues `batch` for each of its regions in one transaction, so either every job is queued
+    /// or none is. A region that can't be posted to doesn't fail the batch, its job is created
+    /// failed instead; so are the jobs NS rejects later, e.g. for a missing embassy, while the
+    /// others go ahead.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn queue_batch(
+        &self,
+        user: &AuthorizedUser,
+        batch: NewRmbPostBatch,
+        key: Option<idempotency::Key>,
+        request_id: &RequestId,
+    ) -> Result<Submitted<response::RmbPostGroup>, Error> {
+        let nation = self.configured_nation(&batch.nation).await?;
+
+        tenant::check_nation(
+            self.pools.get(Pool::Primary),
+            user.tenant_id,
+            &name::canonicalize(&nation),
+        )
+        .await?;
+
+        if !self.nations.is_healthy(&nation).await? {
+            return Err(Error::CredentialUnhealthy(nation));
+        }
+
+        let mut regions: Vec<String> = Vec::new();
+
+        for region in batch
+            .regions
+            .iter()
+            .map(|region| name::canonicalize(region))
+        {
+            if !regions.contains(&region) {
+                regions.push(region);
+            }
+        }
+
+        if regions.is_empty() || regions.len() > MAX_BATCH_SIZE {
+            return Err(Error::InvalidRmbPostBatch(MAX_BATCH_SIZE));
+        }
+
+        let warnings = self.policy.check(&[&batch.text])?;
+
+        if let Some(job_id) =
+            idempotency::previous_job(key.as_ref(), self.pools.get(Pool::Primary)).await?
+        {
+            return Ok(Submitted::Replayed(
+                self.get_group_of_job(job_id, user.tenant_id).await?,
+            ));
+        }
+
+        let mut checked = Vec::new();
+
+        for region in regions {
+            let error = match self.validate_region(&nation, &region).await {
+                Ok(()) => None,
+                Err(
+                    e @ (Error::InvalidRegion | Error::RegionNotFound | Error::RmbPostRefused(_)),
+                ) => Some(e),
+                Err(e) => return Err(e),
+            };
+
+            checked.push((region, error));
+        }
+
+        let mut transaction = self.pools.get(Pool::Primary).begin().await?;
+
+        self.quota
+            .check(
+                &mut transaction,
+                "rmbpost_queue",
+                user,
+                checked.len() as i64,
+            )
+            .await?;
+
+        let group_id: i32 = sqlx::query(
+            "INSERT INTO rmbpost_groups (created_by, tenant_id) VALUES ($1, $2) RETURNING id;",
+        )
+        .bind(&user.username)
+        .bind(user.tenant_id)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        let mut first_job = None;
+        let mut jobs = Vec::new();
+
+        for (region, error) in checked {
+            let status = match error {
+                Some(_) => JobStatus::Failed,
+                None => JobStatus::Queued,
+            };
+
+            let job_id: i32 = sqlx::query(
+                "INSERT INTO rmbpost_queue (nation, region, content, status, error, error_code, finished_at, created_by, request_id, group_id, warnings, tenant_id)
+                VALUES ($1, $2, $3, $4, $5, $6, CASE WHEN $4 = 'failed' THEN NOW() END, $7, $8, $9, $10, $11)
+                RETURNING id;",
+            )
+            .bind(&nation)
+            .bind(&region)
+            .bind(&batch.text)
+            .bind(status)
+            .bind(error.as_ref().map(Error::to_string))
+            .bind(error.as_ref().and_then(Error::job_code))
+            .bind(&user.username)
+            .bind(&request_id.0)
+            .bind(group_id)
+            .bind(&warnings)
+            .bind(user.tenant_id)
+            .map(|row: PgRow| row.get("id"))
+            .fetch_one(&mut *transaction)
+            .await?;
+
+            first_job.get_or_insert(job_id);
+
+            if error.is_none() {
+                jobs.push((job_id, region));
+            }
+        }
+
+        if let Some(job_id) = idempotency::commit(
+            key.as_ref(),
+            transaction,
+            self.pools.get(Pool::Primary),
+            first_job.unwrap_or_default(),
+        )
+        .await?
+        {
+            return Ok(Submitted::Replayed(
+                self.get_group_of_job(job_id, user.tenant_id).await?,
+            ));
+        }
+
+        let rmbposts = jobs
+            .into_iter()
+            .map(|(job_id, region)| {
+                IntermediateRmbPost::new(job_id, nation.clone(), region, batch.text.clone())
+                    .with_request_id(request_id)
+            })
+            .collect();
+
+        self.submit(rmbposts).await?;
+
+        Ok(Submitted::Created(
+            self.get_group(group_id, Some(user.tenant_id)).await?,
+        ))
+    }
+
+    /// Hands the posts, already stored as `queued`, to the worker in order. If its channel stays
+    /// full, the post that didn't fit and those after it are marked `deferred` for the worker to
+    /// pick up once it has room, and the request is refused naming them.
+    async fn submit(&self, rmbposts: Vec<IntermediateRmbPost>) -> Result<(), Error> {
+        let job_ids: Vec<i32> = rmbposts.iter().map(|rmbpost| rmbpost.job_id).collect();
+
+        for (i, rmbpost) in rmbposts.into_iter().enumerate() {
+            let (tx, rx) = oneshot::channel();
+
+            match self
+                .tx
+                .send(rmbpost::Command::new(Action::queue(rmbpost), tx))
+                .await
+            {
+                Err(Error::PipelineBackedUp(pipeline, _)) => {
+                    let deferred = &job_ids[i..];
+
+                    sqlx::query(
+                        "UPDATE rmbpost_queue SET status = 'deferred', modified_at = $2
+                        WHERE id = ANY($1) AND status = 'queued';",
+                    )
+                    .bind(deferred)
+                    .bind(chrono::Utc::now())
+                    .execute(self.pools.get(Pool::Primary))
+                    .await?;
+
+                    return Err(Error::PipelineBackedUp(pipeline, deferred.to_vec()));
+                }
+                result => result?,
+            }
+
+            if let Err(e) = rx.await {
+                tracing::error!("Error sending rmbpost response, {:?}", e);
+
+                return Err(Error::Internal);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Groups of tenants other than `tenant` aren't found.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get_group(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+    ) -> Result<response::RmbPostGroup, Error> {
+        let (created_by, created_at) = sqlx::query(
+            "SELECT created_by, created_at FROM rmbpost_groups
+            WHERE id = $1
+            AND ($2::INTEGER IS NULL OR tenant_id = $2);",
+        )
+        .bind(id)
+        .bind(tenant)
+        .map(|row: PgRow| (row.get("created_by"), row.get("created_at")))
+        .fetch_optional(self.pools.get(Pool::Primary))
+        .await?
+        .ok_or(Error::GroupNotFound)?;
+
+        let jobs = sqlx::query(
+            "SELECT
+                id,
+                region,
+                status,
+                rmbpost_id,
+                error,
+                error_code,
+                warnings,
+                created_at,
+                claimed_at,
+                started_at,
+                prepared_at,
+                executing_at,
+                finished_at,
+                modified_at
+            FROM rmbpost_queue
+            WHERE group_id = $1
+            ORDER BY id;",
+        )
+        .bind(id)
+        .map(map_rmbpost_status)
+        .fetch_all(self.pools.get(Pool::Primary))
+        .await?;
+
+        Ok(response::RmbPostGroup {
+            id,
+            status: response::RmbPostGroup::status(&jobs).to_string(),
+            self_url: response::RmbPostGroup::self_url(id),
+            created_by,
+            created_at,
+            jobs,
+        })
+    }
+
+    /// The group a job was queued in, for replaying an idempotent batch submission.
+    async fn get_group_of_job(
+        &self,
+        job_id: i32,
+        tenant: i32,
+    ) -> Result<response::RmbPostGroup, Error> {
+        let group_id: Option<i32> =
+            sqlx::query("SELECT group_id FROM rmbpost_queue WHERE id = $1;")
+                .bind(job_id)
+                .map(|row: PgRow| row.get("group_id"))
+                .fetch_optional(self.pools.get(Pool::Primary))
+                .await?
+                .flatten();
+
+        self.get_group(group_id.ok_or(Error::GroupNotFound)?, Some(tenant))
             .await
-            .unwrap();
+    }
 
-        if let Err(e) = rx.await {
-            tracing::error!("Error sending rmbpost response, {:?}", e);
+    /// Percentiles of the time RMB post jobs spent in each phase.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn timings(
+        &self,
+        query: &TimingsQuery,
+        tenant_id: i32,
+    ) -> Result<response::JobTimings, Error> {
+        timings::summarize(
+            self.pools.get(Pool::Read),
+            "rmbpost_queue",
+            JobKind::Rmbpost,
+            query,
+            tenant_id,
+        )
+        .await
+    }
+
+    /// Read from the replica, so a job queued a moment ago may not be found yet. Jobs of tenants
+    /// other than `tenant` aren't found.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get_status(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+    ) -> Result<response::RmbPostStatus, Error> {
+        let mut status = self.fetch_scoped_status(id, tenant, Pool::Read).await?;
+
+        if !status.status.is_finished() {
+            status.estimated_completion_at = self.estimated_completion(id, Pool::Read).await;
         }
 
         Ok(status)
     }
 
+    /// When job `job_id` is likely to be done, if it's waiting: once its nation's RMB post
+    /// cooldown allows, plus a cooldown for every post of the nation ahead of it. The worker
+    /// doesn't take commands while it posts, so this goes by the queue table instead of asking it.
+    /// It's only an estimate, so one that can't be made is left out instead of failing the request.
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn get_status(&self, id: i32) -> Result<response::RmbPostStatus, Error> {
+    async fn estimated_completion(
+        &self,
+        job_id: i32,
+        pool: Pool,
+    ) -> Option<chrono::DateTime<chrono::Utc>> {
+        let waiting = sqlx::query(
+            "SELECT
+                nation,
+                (
+                    SELECT COUNT(*) FROM rmbpost_queue ahead
+                    WHERE ahead.nation = job.nation
+                    AND ahead.id < job.id
+                    AND ahead.status = ANY($2)
+                ) AS ahead
+            FROM rmbpost_queue job
+            WHERE id = $1
+            AND status IN ('queued', 'deferred');",
+        )
+        .bind(job_id)
+        .bind(JobStatus::PENDING)
+        .map(|row: PgRow| (row.get::<String, _>("nation"), row.get::<i64, _>("ahead")))
+        .fetch_optional(self.pools.get(pool))
+        .await;
+
+        let (nation, ahead) = match waiting {
+            Ok(waiting) => waiting?,
+            Err(e) => {
+                tracing::warn!(job_id, "couldn't estimate completion: {}", e);
+
+                return None;
+            }
+        };
+
+        let wait = self
+            .limiter
+            .peek(Target::restricted(&nation, RestrictedAction::RmbPost))
+            .await
+            + self
+                .limiter
+                .limits()
+                .restricted_cooldown(RestrictedAction::RmbPost)
+                * ahead as u32;
+
+        Some(chrono::Utc::now() + chrono::Duration::from_std(wait).ok()?)
+    }
+
+    async fn fetch_status(&self, id: i32, pool: Pool) -> Result<response::RmbPostStatus, Error> {
+        self.fetch_scoped_status(id, None, pool).await
+    }
+
+    async fn fetch_scoped_status(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+        pool: Pool,
+    ) -> Result<response::RmbPostStatus, Error> {
         match sqlx::query(
             "SELECT
                 id,
+                region,
                 status,
                 rmbpost_id,
                 error,
+                error_code,
+                warnings,
                 created_at,
+                claimed_at,
+                started_at,
+                prepared_at,
+                executing_at,
+                finished_at,
                 modified_at
             FROM rmbpost_queue
-            WHERE id = $1;",
+            WHERE id = $1
+            AND ($2::INTEGER IS NULL OR tenant_id = $2);",
         )
         .bind(id)
+        .bind(tenant)
         .map(map_rmbpost_status)
-        .fetch_one(&self.pool)
+        .fetch_one(self.pools.get(pool))
         .await
         {
             Ok(status) => Ok(status),
@@ -95,12 +776,36 @@ impl Controller {
 }
 
 fn map_rmbpost_status(row: PgRow) -> response::RmbPostStatus {
+    let (id, status, rmbpost_id) = (
+        row.get("id"),
+        row.get::<JobStatus, _>("status"),
+        row.get("rmbpost_id"),
+    );
+
     response::RmbPostStatus {
-        id: row.get("id"),
-        status: row.get("status"),
-        rmbpost_id: row.get("rmbpost_id"),
+        self_url: response::RmbPostStatus::self_url(id),
+        resource: response::RmbPostStatus::resource_url(status, row.get("region"), rmbpost_id),
+        id,
+        status,
+        rmbpost_id,
         error: row.get("error"),
+        error_code: row.get("error_code"),
+        warnings: row.get("warnings"),
         created_at: row.get("created_at"),
+        claimed_at: row.get("claimed_at"),
+        started_at: row.get("started_at"),
+        prepared_at: row.get("prepared_at"),
+        executing_at: row.get("executing_at"),
+        finished_at: row.get("finished_at"),
         modified_at: row.get("modified_at"),
+        durations: response::JobDurations::new(
+            row.get("created_at"),
+            row.get("claimed_at"),
+            row.get("started_at"),
+            row.get("prepared_at"),
+            row.get("executing_at"),
+            row.get("finished_at"),
+        ),
+        estimated_completion_at: None,
     }
 }
@@ -1,46 +1,499 @@
+use crate::controllers::tenant;
+use crate::core::db::{DbPools, Pool};
 use crate::core::error::{ConfigError, Error};
-use crate::ns::telegram::{Command, Header, Params, Response};
+use crate::ns::dump::Index;
+use crate::ns::region;
+use crate::ns::telegram::{
+    self, Command, DeleteScope, Header, Job, NewCampaign, Params, RecipientFilter, RecipientSource,
+    Response, TgType,
+};
+use crate::sync::dump::Dump;
 use crate::sync::ratelimiter;
+use crate::sync::telegram_types::TelegramTypes;
+use crate::types::AuthorizedUser;
+use crate::types::request::{TelegramStatsGroup, TelegramStatsQuery};
 use crate::types::response;
+use crate::utils::name;
 use crate::workers;
-use sqlx::PgPool;
-use std::collections::HashMap;
-use tokio::sync::{mpsc, oneshot};
+use crate::workers::telegram::ClientKeys;
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+/// Telegrams of a batch stored and handed to the worker at once.
+const QUEUE_CHUNK: usize = 500;
 
 #[derive(Clone, Debug)]
 pub(crate) struct Controller {
-    pool: PgPool,
-    tx: mpsc::Sender<Command>,
+    url: String,
+    client: reqwest::Client,
+    pools: DbPools,
+    tx: workers::Handle<Command>,
+    keys: ClientKeys,
+    types: TelegramTypes,
+    limiter: ratelimiter::Sender,
+    dump: Dump,
 }
 
 impl Controller {
     pub(crate) fn new(
         user_agent: &str,
         url: &str,
-        key: String,
+        keys: ClientKeys,
         limiter: ratelimiter::Sender,
-        pool: PgPool,
+        pools: DbPools,
+        channel: workers::Channel,
+        dump: Dump,
     ) -> Result<Self, ConfigError> {
-        let (tx, mut client) = workers::telegram::new(user_agent, url, key, limiter)?;
+        let types = TelegramTypes::new(pools.get(Pool::Primary).clone());
 
-        tokio::spawn(async move {
-            client.run().await;
-        });
+        let tx = workers::supervise("telegram", channel.send_timeout, {
+            let (user_agent, url, pool, keys, types) = (
+                user_agent.to_string(),
+                url.to_string(),
+                pools.get(Pool::Primary).clone(),
+                keys.clone(),
+                types.clone(),
+            );
+
+            let limiter = limiter.clone();
+
+            move |activity| {
+                let (tx, mut client) = workers::telegram::new(
+                    &user_agent,
+                    &url,
+                    pool.clone(),
+                    keys.clone(),
+                    types.clone(),
+                    limiter.clone(),
+                    channel.capacity,
+                    activity,
+                )?;
+
+                Ok((tx, async move { client.run().await }))
+            }
+        })?;
+
+        Ok(Self {
+            url: url.to_string(),
+            client: reqwest::Client::builder().user_agent(user_agent).build()?,
+            pools,
+            tx,
+            keys,
+            types,
+            limiter,
+            dump,
+        })
+    }
+
+    /// Commands waiting for the worker, and how many its channel holds.
+    pub(crate) fn channel_occupancy(&self) -> (usize, usize) {
+        self.tx.occupancy()
+    }
+
+    /// Refuses telegrams queued with another type than the one NS was found to give them, which
+    /// would hold them to the wrong cooldown.
+    async fn check_types(&self, telegrams: &[(&str, &TgType)]) -> Result<(), Error> {
+        let recorded = self
+            .types
+            .recorded(telegrams.iter().map(|(id, _)| *id))
+            .await?;
+
+        for (id, tg_type) in telegrams {
+            match recorded.get(*id) {
+                Some(recorded) if recorded != *tg_type => {
+                    return Err(Error::TelegramTypeMismatch(
+                        id.to_string(),
+                        recorded.clone(),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queued telegrams, only those queued by `created_by` if set.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get(
+        &mut self,
+        created_by: Option<&str>,
+        tenant_id: i32,
+    ) -> Result<HashMap<String, Vec<response::Telegram>>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .send(Command::list(created_by.map(str::to_string), tx))
+            .await?;
+
+        match rx.await {
+            Ok(Response::List(mut list)) => {
+                let own = self
+                    .own_senders(tenant_id, list.keys().cloned().collect())
+                    .await?;
+
+                list.retain(|sender, _| own.contains(sender));
+
+                Ok(list)
+            }
+            Ok(_) => unreachable!(),
+            Err(e) => {
+                tracing::error!("{}", e);
+                Err(Error::Internal)
+            }
+        }
+    }
+
+    /// Those of `senders` that belong to `tenant_id`.
+    async fn own_senders(
+        &self,
+        tenant_id: i32,
+        senders: Vec<String>,
+    ) -> Result<Vec<String>, Error> {
+        tenant::own_nations(self.pools.get(Pool::Primary), tenant_id, senders).await
+    }
+
+    /// Who may delete which queued telegrams: `user`'s own, or with `telegrams.delete.any`,
+    /// any from their tenant's senders. Only operators reach every tenant's.
+    pub(crate) async fn delete_scope(
+        &mut self,
+        user: &AuthorizedUser,
+    ) -> Result<DeleteScope, Error> {
+        Ok(match DeleteScope::for_user(user) {
+            DeleteScope::Any if !user.is_operator() => {
+                DeleteScope::Senders(self.get(None, user.tenant_id).await?.into_keys().collect())
+            }
+            scope => scope,
+        })
+    }
+
+    /// What the telegram worker is doing, answered even while it's busy posting.
+    pub(crate) fn worker(&self) -> response::WorkerStatus {
+        self.tx.status()
+    }
+
+    /// Pauses, resumes or reports on the telegram worker.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn control(
+        &self,
+        control: workers::Control,
+    ) -> Result<response::PipelineStatus, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx.send(Command::control(control, tx)).await?;
+
+        match rx.await {
+            Ok(Response::Pipeline(status)) => Ok(status),
+            Ok(_) => Err(Error::Internal),
+            Err(e) => {
+                tracing::error!("received error: {}", e);
+
+                Err(Error::Internal)
+            }
+        }
+    }
 
-        Ok(Self { pool, tx })
+    /// Telegram counts per sender or telegram type, from the persisted queue.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn stats(
+        &self,
+        query: &TelegramStatsQuery,
+        tenant_id: i32,
+    ) -> Result<Vec<response::TelegramStats>, Error> {
+        let key = match query.group_by {
+            TelegramStatsGroup::Sender => "sender",
+            TelegramStatsGroup::Type => "tg_type",
+        };
+
+        Ok(sqlx::query(&format!(
+            "SELECT
+                {key} AS key,
+                COUNT(*) FILTER (WHERE status = 'sent') AS sent,
+                COUNT(*) FILTER (WHERE status = 'failed') AS failed,
+                COUNT(*) FILTER (WHERE status = 'skipped') AS skipped
+            FROM telegram_queue
+            WHERE ($1::TIMESTAMPTZ IS NULL OR created_at >= $1)
+            AND ($2::TIMESTAMPTZ IS NULL OR created_at < $2)
+            AND tenant_id = $3
+            GROUP BY 1
+            ORDER BY 1;"
+        ))
+        .bind(query.from)
+        .bind(query.to)
+        .bind(tenant_id)
+        .map(|row: PgRow| response::TelegramStats {
+            key: row.get("key"),
+            sent: row.get("sent"),
+            failed: row.get("failed"),
+            skipped: row.get("skipped"),
+        })
+        .fetch_all(self.pools.get(Pool::Read))
+        .await?)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn summary(
+        &self,
+        tenant_id: i32,
+    ) -> Result<BTreeMap<String, response::TelegramSenderSummary>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx.send(Command::summary(tx)).await?;
+
+        match rx.await {
+            Ok(Response::Summary(mut summary)) => {
+                let own = self
+                    .own_senders(tenant_id, summary.keys().cloned().collect())
+                    .await?;
+
+                summary.retain(|sender, _| own.contains(sender));
+
+                Ok(summary)
+            }
+            Ok(_) => unreachable!(),
+            Err(e) => {
+                tracing::error!("{}", e);
+                Err(Error::Internal)
+            }
+        }
     }
 
+    /// Recruitment telegram capacity per sender nation.
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn get(&mut self) -> Result<HashMap<String, Vec<response::Telegram>>, Error> {
+    pub(crate) async fn capacity(
+        &self,
+        tenant_id: i32,
+    ) -> Result<BTreeMap<String, response::TelegramCapacity>, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx.send(Command::stats(tx)).await?;
+
+        match rx.await {
+            Ok(Response::Stats(mut stats)) => {
+                let own = self
+                    .own_senders(tenant_id, stats.keys().cloned().collect())
+                    .await?;
+
+                stats.retain(|sender, _| own.contains(sender));
+
+                Ok(stats)
+            }
+            Ok(_) => unreachable!(),
+            Err(e) => {
+                tracing::error!("{}", e);
+                Err(Error::Internal)
+            }
+        }
+    }
+
+    /// The nations dump index to apply `filter` with, `None` when there's nothing to filter.
+    fn dump_index(&self, filter: &RecipientFilter) -> Result<Option<Arc<Index>>, Error> {
+        if filter.is_empty() {
+            return Ok(None);
+        }
+
+        self.dump
+            .index()
+            .map(Some)
+            .ok_or(Error::NationsDumpUnavailable)
+    }
+
+    /// Queues a batch of telegrams, leaving out invalid recipients, those `filter` excludes,
+    /// repeats within the batch and telegrams already waiting in the queue for the same
+    /// recipient. The whole batch is checked
+    /// first, then stored and handed to the worker [`QUEUE_CHUNK`] telegrams at a time, each
+    /// chunk acknowledged before the next is sent. If a chunk fails after others went through,
+    /// it and the rest are reported as `not_queued`.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn queue(
+        &mut self,
+        user: &AuthorizedUser,
+        params: Vec<Params>,
+        filter: &RecipientFilter,
+    ) -> Result<response::QueuedTelegrams, Error> {
+        let (mut params, mut dropped) = telegram::normalize(params);
+
+        if params
+            .iter()
+            .any(|(_, params)| self.keys.get(&params.sender).is_none())
+        {
+            return Err(Error::UnknownTelegramSender(self.keys.senders()));
+        }
+
+        let senders = params
+            .iter()
+            .map(|(_, params)| params.sender.clone())
+            .collect::<BTreeSet<_>>();
+        let own = self
+            .own_senders(user.tenant_id, senders.into_iter().collect())
+            .await?;
+
+        if let Some((_, params)) = params
+            .iter()
+            .find(|(_, params)| !own.contains(&params.sender))
+        {
+            return Err(Error::NationNotInTenant(params.sender.clone()));
+        }
+
+        let types: Vec<_> = params
+            .iter()
+            .map(|(_, params)| (params.id.as_str(), &params.tg_type))
+            .collect();
+        self.check_types(&types).await?;
+
+        if let Some(index) = self.dump_index(filter)? {
+            params.retain_mut(|(index_in_batch, params)| {
+                match filter.excludes(&index, &params.recipient) {
+                    Some(reason) => {
+                        dropped.push(response::DroppedTelegram {
+                            index: *index_in_batch,
+                            recipient: params.recipient.clone(),
+                            telegram_id: params.id.clone(),
+                            reason: reason.to_string(),
+                        });
+
+                        false
+                    }
+                    None => true,
+                }
+            });
+        }
+
+        let mut queued = Vec::with_capacity(params.len());
+
+        while !params.is_empty() {
+            let chunk: Vec<_> = params.drain(..params.len().min(QUEUE_CHUNK)).collect();
+
+            let pending: Vec<_> = chunk
+                .iter()
+                .map(|(index, params)| (*index, params.recipient.clone(), params.id.clone()))
+                .collect();
+
+            match self.queue_chunk(user, chunk, &mut dropped).await {
+                Ok(chunk) => queued.extend(chunk),
+                Err(e) if queued.is_empty() => return Err(e),
+                Err(e) => {
+                    tracing::error!("queueing failed after {} telegrams: {}", queued.len(), e);
+
+                    let rest = params
+                        .drain(..)
+                        .map(|(index, params)| (index, params.recipient, params.id));
+
+                    dropped.extend(pending.into_iter().chain(rest).map(
+                        |(index, recipient, telegram_id)| response::DroppedTelegram {
+                            index,
+                            recipient,
+                            telegram_id,
+                            reason: "not_queued".to_string(),
+                        },
+                    ));
+                }
+            }
+        }
+
+        Ok(response::QueuedTelegrams::new(queued, dropped))
+    }
+
+    /// Stores a chunk of a batch and waits for the worker to take it, adding the telegrams
+    /// already waiting in the queue to `dropped`.
+    async fn queue_chunk(
+        &mut self,
+        user: &AuthorizedUser,
+        params: Vec<(usize, Params)>,
+        dropped: &mut Vec<response::DroppedTelegram>,
+    ) -> Result<Vec<response::QueuedTelegram>, Error> {
+        let waiting = self.waiting(&params).await?;
+
+        let (params, repeated): (Vec<_>, Vec<_>) = params.into_iter().partition(|(_, params)| {
+            !waiting.contains(&(params.recipient.clone(), params.id.clone()))
+        });
+
+        dropped.extend(
+            repeated
+                .into_iter()
+                .map(|(index, params)| response::DroppedTelegram {
+                    index,
+                    recipient: params.recipient,
+                    telegram_id: params.id,
+                    reason: "already_queued".to_string(),
+                }),
+        );
+
+        if params.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (senders, recipients, telegram_ids, tg_types) = params.iter().fold(
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new()),
+            |mut columns, (_, params)| {
+                columns.0.push(params.sender.clone());
+                columns.1.push(params.recipient.clone());
+                columns.2.push(params.id.clone());
+                columns.3.push(params.tg_type.to_string());
+                columns
+            },
+        );
+
+        // rows are inserted in input order, so ascending ids line up with `params`
+        let mut queued = sqlx::query(
+            "INSERT INTO telegram_queue (sender, recipient, telegram_id, tg_type, status, created_by, tenant_id)
+            SELECT sender, recipient, telegram_id, tg_type, 'queued', $5, $6
+            FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::VARCHAR[], $4::VARCHAR[])
+                WITH ORDINALITY AS input(sender, recipient, telegram_id, tg_type, position)
+            ORDER BY position
+            RETURNING
+                id,
+                recipient,
+                telegram_id,
+                tg_type AS queue;",
+        )
+        .bind(senders)
+        .bind(recipients)
+        .bind(telegram_ids)
+        .bind(tg_types)
+        .bind(&user.username)
+        .bind(user.tenant_id)
+        .map(map_queued_telegram)
+        .fetch_all(self.pools.get(Pool::Primary))
+        .await?;
+
+        queued.sort_by_key(|telegram| telegram.id);
+
+        let jobs = queued
+            .iter()
+            .zip(params)
+            .map(|(telegram, (_, params))| Job {
+                id: telegram.id,
+                params,
+                created_by: user.username.clone(),
+            })
+            .collect();
+
         let (tx, rx) = oneshot::channel();
 
-        if let Err(e) = self.tx.send(Command::list(tx)).await {
-            tracing::error!("{}", e);
-            return Err(Error::Internal);
+        match self.tx.send(Command::queue(jobs, tx)).await {
+            // the worker only learns of telegrams through its channel, so rather than deferred
+            // they're withdrawn, for the request to be retried as a whole
+            Err(e @ Error::PipelineBackedUp(..)) => {
+                let job_ids: Vec<i32> = queued.iter().map(|telegram| telegram.id).collect();
+
+                sqlx::query("DELETE FROM telegram_queue WHERE id = ANY($1) AND status = 'queued';")
+                    .bind(job_ids)
+                    .execute(self.pools.get(Pool::Primary))
+                    .await?;
+
+                return Err(e);
+            }
+            result => result?,
         }
 
         match rx.await {
-            Ok(Response::List(list)) => Ok(list),
+            Ok(Response::Ok) => Ok(queued),
+            Ok(Response::Error(e)) => Err(e),
             Ok(_) => unreachable!(),
             Err(e) => {
                 tracing::error!("{}", e);
@@ -49,17 +502,366 @@ impl Controller {
         }
     }
 
+    /// `(recipient, telegram_id)` of the queued telegrams going to any recipient in `params`.
+    async fn waiting(
+        &self,
+        params: &[(usize, Params)],
+    ) -> Result<HashSet<(String, String)>, Error> {
+        let recipients: Vec<_> = params
+            .iter()
+            .map(|(_, params)| params.recipient.clone())
+            .collect();
+
+        Ok(sqlx::query(
+            "SELECT recipient, telegram_id FROM telegram_queue
+            WHERE status = 'queued' AND recipient = ANY($1);",
+        )
+        .bind(recipients)
+        .map(|row: PgRow| (row.get("recipient"), row.get("telegram_id")))
+        .fetch_all(self.pools.get(Pool::Primary))
+        .await?
+        .into_iter()
+        .collect())
+    }
+
+    /// Stores a campaign and its recipient list, then hands it to the worker to feed into the
+    /// queue in batches.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn create_campaign(
+        &self,
+        user: &AuthorizedUser,
+        mut campaign: NewCampaign,
+    ) -> Result<response::TelegramCampaign, Error> {
+        campaign.sender = name::canonicalize(&campaign.sender);
+
+        if self.keys.get(&campaign.sender).is_none() {
+            return Err(Error::UnknownTelegramSender(self.keys.senders()));
+        }
+
+        tenant::check_nation(
+            self.pools.get(Pool::Primary),
+            user.tenant_id,
+            &campaign.sender,
+        )
+        .await?;
+
+        self.check_types(&[(campaign.telegram_id.as_str(), &campaign.tg_type)])
+            .await?;
+
+        let (mut recipients, rejected) = campaign.recipients();
+
+        // resolved now rather than as the campaign goes, so the list can be checked up front
+        let expanded = match &campaign.recipients_from {
+            Some(source) => {
+                let nations =
+                    region::expand(&self.client, &self.url, &self.limiter, source).await?;
+
+                if nations.is_empty() {
+                    return Err(Error::EmptyRecipientSource(source.region.clone()));
+                }
+
+                let before = recipients.len();
+                let mut seen: HashSet<_> = recipients.iter().cloned().collect();
+
+                for nation in nations {
+                    if seen.insert(nation.clone()) {
+                        recipients.push(nation);
+                    }
+                }
+
+                Some((recipients.len() - before) as i32)
+            }
+            None => None,
+        };
+
+        let mut filtered = BTreeMap::new();
+
+        if let Some(index) = self.dump_index(&campaign.filter)? {
+            recipients.retain(
+                |recipient| match campaign.filter.excludes(&index, recipient) {
+                    Some(reason) => {
+                        *filtered.entry(reason.to_string()).or_insert(0) += 1;
+                        false
+                    }
+                    None => true,
+                },
+            );
+        }
+
+        if recipients.is_empty() {
+            return Err(Error::NoRecipients);
+        }
+
+        let mut transaction = self.pools.get(Pool::Primary).begin().await?;
+
+        let id: i32 = sqlx::query(
+            "INSERT INTO telegram_campaigns
+                (name, sender, telegram_id, tg_type, status, created_by,
+                recipients_from, expanded_recipients, tenant_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id;",
+        )
+        .bind(&campaign.name)
+        .bind(&campaign.sender)
+        .bind(&campaign.telegram_id)
+        .bind(campaign.tg_type.to_string())
+        .bind(if campaign.paused { "paused" } else { "active" })
+        .bind(&user.username)
+        .bind(campaign.recipients_from.as_ref().map(Json))
+        .bind(expanded)
+        .bind(user.tenant_id)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO telegram_campaign_secrets (campaign_id, secret_key) VALUES ($1, $2);",
+        )
+        .bind(id)
+        .bind(campaign.secret_key.expose())
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO telegram_campaign_recipients (campaign_id, position, recipient)
+            SELECT $1, position, recipient
+            FROM UNNEST($2::VARCHAR[]) WITH ORDINALITY AS input(recipient, position);",
+        )
+        .bind(id)
+        .bind(&recipients)
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+
+        if !campaign.paused {
+            self.campaign_command(id, Command::start_campaign).await?;
+        }
+
+        let mut campaign = self.get_campaign(id, Some(user.tenant_id)).await?;
+        campaign.rejected = rejected;
+        campaign.filtered = filtered;
+
+        Ok(campaign)
+    }
+
+    /// Campaigns of tenants other than `tenant` aren't found.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get_campaign(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+    ) -> Result<response::TelegramCampaign, Error> {
+        match sqlx::query(
+            "SELECT
+                telegram_campaigns.id,
+                telegram_campaigns.name,
+                telegram_campaigns.sender,
+                telegram_campaigns.telegram_id,
+                telegram_campaigns.tg_type,
+                telegram_campaigns.status,
+                telegram_campaigns.created_by,
+                telegram_campaigns.created_at,
+                telegram_campaigns.modified_at,
+                telegram_campaigns.recipients_from,
+                telegram_campaigns.expanded_recipients,
+                COUNT(recipients.position) AS recipients,
+                COUNT(*) FILTER (WHERE telegram_queue.status = 'sent') AS sent,
+                COUNT(*) FILTER (WHERE telegram_queue.status = 'failed') AS failed,
+                COUNT(*) FILTER (WHERE telegram_queue.status = 'skipped') AS skipped,
+                COUNT(recipients.position) FILTER (
+                    WHERE telegram_queue.status IS NULL OR telegram_queue.status = 'queued'
+                ) AS remaining
+            FROM telegram_campaigns
+            LEFT JOIN telegram_campaign_recipients recipients
+                ON recipients.campaign_id = telegram_campaigns.id
+            LEFT JOIN telegram_queue ON telegram_queue.id = recipients.job_id
+            WHERE telegram_campaigns.id = $1
+            AND ($2::INTEGER IS NULL OR telegram_campaigns.tenant_id = $2)
+            GROUP BY telegram_campaigns.id;",
+        )
+        .bind(id)
+        .bind(tenant)
+        .map(map_telegram_campaign)
+        .fetch_one(self.pools.get(Pool::Primary))
+        .await
+        {
+            Ok(campaign) => Ok(campaign),
+            Err(sqlx::Error::RowNotFound) => Err(Error::CampaignNotFound),
+            Err(e) => Err(Error::Sql(e)),
+        }
+    }
+
+    /// The recipient list of campaign `id` in send order, with how far each has got.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn campaign_recipients(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+    ) -> Result<Vec<response::CampaignRecipient>, Error> {
+        let recipients = sqlx::query(
+            "SELECT recipients.position, recipients.recipient, telegram_queue.status
+            FROM telegram_campaign_recipients recipients
+            JOIN telegram_campaigns ON telegram_campaigns.id = recipients.campaign_id
+            LEFT JOIN telegram_queue ON telegram_queue.id = recipients.job_id
+            WHERE recipients.campaign_id = $1
+            AND ($2::INTEGER IS NULL OR telegram_campaigns.tenant_id = $2)
+            ORDER BY recipients.position;",
+        )
+        .bind(id)
+        .bind(tenant)
+        .map(|row: PgRow| response::CampaignRecipient {
+            position: row.get("position"),
+            recipient: row.get("recipient"),
+            status: row
+                .get::<Option<String>, _>("status")
+                .unwrap_or_else(|| "pending".to_string()),
+        })
+        .fetch_all(self.pools.get(Pool::Primary))
+        .await?;
+
+        if recipients.is_empty() {
+            // campaigns can't be created without recipients
+            return Err(Error::CampaignNotFound);
+        }
+
+        Ok(recipients)
+    }
+
+    /// Stops feeding campaign `id`. Telegrams already waiting in the queue are taken back out
+    /// and go out again once the campaign is resumed.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn pause_campaign(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+    ) -> Result<response::TelegramCampaign, Error> {
+        self.set_campaign_status(id, "paused", tenant).await?;
+        self.campaign_command(id, Command::pause_campaign).await?;
+
+        self.get_campaign(id, tenant).await
+    }
+
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn queue(&mut self, params: Vec<Params>) -> Result<(), Error> {
+    pub(crate) async fn resume_campaign(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+    ) -> Result<response::TelegramCampaign, Error> {
+        self.set_campaign_status(id, "active", tenant).await?;
+        self.campaign_command(id, Command::start_campaign).await?;
+
+        self.get_campaign(id, tenant).await
+    }
+
+    async fn campaign_command(
+        &self,
+        id: i32,
+        command: fn(i32, oneshot::Sender<Response>) -> Command,
+    ) -> Result<(), Error> {
         let (tx, rx) = oneshot::channel();
 
-        if let Err(e) = self.tx.send(Command::queue(params, tx)).await {
-            tracing::error!("{}", e);
-            return Err(Error::Internal);
+        self.tx.send(command(id, tx)).await?;
+
+        match rx.await {
+            Ok(Response::Ok) => Ok(()),
+            Ok(_) => Err(Error::Internal),
+            Err(e) => {
+                tracing::error!("{}", e);
+                Err(Error::Internal)
+            }
         }
+    }
+
+    async fn set_campaign_status(
+        &self,
+        id: i32,
+        status: &str,
+        tenant: Option<i32>,
+    ) -> Result<(), Error> {
+        let current: Option<String> = sqlx::query(
+            "SELECT status FROM telegram_campaigns
+            WHERE id = $1
+            AND ($2::INTEGER IS NULL OR tenant_id = $2);",
+        )
+        .bind(id)
+        .bind(tenant)
+        .map(|row: PgRow| row.get("status"))
+        .fetch_optional(self.pools.get(Pool::Primary))
+        .await?;
+
+        match current.as_deref() {
+            None => return Err(Error::CampaignNotFound),
+            Some("completed") => return Err(Error::CampaignCompleted),
+            Some(_) => {}
+        }
+
+        sqlx::query(
+            "UPDATE telegram_campaigns SET status = $1, modified_at = $2
+            WHERE id = $3 AND status <> 'completed';",
+        )
+        .bind(status)
+        .bind(chrono::Utc::now())
+        .bind(id)
+        .execute(self.pools.get(Pool::Primary))
+        .await?;
+
+        Ok(())
+    }
+
+    /// Telegrams of tenants other than `tenant` aren't found.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get_status(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+    ) -> Result<response::TelegramStatus, Error> {
+        match sqlx::query(
+            "SELECT
+                id,
+                sender,
+                recipient,
+                telegram_id,
+                tg_type AS queue,
+                status,
+                error,
+                error_code,
+                created_at,
+                modified_at
+            FROM telegram_queue
+            WHERE id = $1
+            AND ($2::INTEGER IS NULL OR tenant_id = $2);",
+        )
+        .bind(id)
+        .bind(tenant)
+        .map(map_telegram_status)
+        .fetch_one(self.pools.get(Pool::Read))
+        .await
+        {
+            Ok(status) => Ok(status),
+            Err(sqlx::Error::RowNotFound) => Err(Error::JobNotFound),
+            Err(e) => Err(Error::Sql(e)),
+        }
+    }
+
+    /// Takes telegram `id` out of the queue. Telegrams of tenants other than `tenant` aren't
+    /// found.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn delete_job(
+        &mut self,
+        id: i32,
+        scope: DeleteScope,
+        tenant: Option<i32>,
+    ) -> Result<(), Error> {
+        self.get_status(id, tenant).await?;
+
+        let (tx, rx) = oneshot::channel();
+
+        self.tx.send(Command::delete_job(id, scope, tx)).await?;
 
         match rx.await {
             Ok(Response::Ok) => Ok(()),
+            Ok(Response::Error(e)) => Err(e),
             Ok(_) => unreachable!(),
             Err(e) => {
                 tracing::error!("{}", e);
@@ -69,13 +871,16 @@ impl Controller {
     }
 
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn delete(&mut self, header: Header) -> Result<(), Error> {
+    pub(crate) async fn delete(
+        &mut self,
+        mut header: Header,
+        scope: DeleteScope,
+    ) -> Result<(), Error> {
+        header.recipient = name::canonicalize(&header.recipient);
+
         let (tx, rx) = oneshot::channel();
 
-        if let Err(e) = self.tx.send(Command::delete(header, tx)).await {
-            tracing::error!("{}", e);
-            return Err(Error::Internal);
-        }
+        self.tx.send(Command::delete(header, scope, tx)).await?;
 
         match rx.await {
             Ok(Response::Ok) => Ok(()),
@@ -87,3 +892,52 @@ impl Controller {
         }
     }
 }
+
+fn map_queued_telegram(row: PgRow) -> response::QueuedTelegram {
+    response::QueuedTelegram {
+        id: row.get("id"),
+        recipient: row.get("recipient"),
+        telegram_id: row.get("telegram_id"),
+        queue: row.get("queue"),
+    }
+}
+
+fn map_telegram_campaign(row: PgRow) -> response::TelegramCampaign {
+    response::TelegramCampaign {
+        id: row.get("id"),
+        name: row.get("name"),
+        sender: row.get("sender"),
+        telegram_id: row.get("telegram_id"),
+        tg_type: row.get("tg_type"),
+        status: row.get("status"),
+        recipients: row.get("recipients"),
+        sent: row.get("sent"),
+        failed: row.get("failed"),
+        skipped: row.get("skipped"),
+        remaining: row.get("remaining"),
+        rejected: Vec::new(),
+        recipients_from: row
+            .get::<Option<Json<RecipientSource>>, _>("recipients_from")
+            .map(|source| source.0),
+        expanded_recipients: row.get("expanded_recipients"),
+        filtered: BTreeMap::new(),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        modified_at: row.get("modified_at"),
+    }
+}
+
+fn map_telegram_status(row: PgRow) -> response::TelegramStatus {
+    response::TelegramStatus {
+        id: row.get("id"),
+        sender: row.get("sender"),
+        recipient: row.get("recipient"),
+        telegram_id: row.get("telegram_id"),
+        queue: row.get("queue"),
+        status: row.get("status"),
+        error: row.get("error"),
+        error_code: row.get("error_code"),
+        created_at: row.get("created_at"),
+        modified_at: row.get("modified_at"),
+    }
+}
@@ -0,0 +1,161 @@
+//! Tenants: regions sharing one deployment, each seeing only its own dispatches, RMB posts,
+//! telegrams, webhooks and audit trail, and posting only as its own nations. A nation belongs to
+//! the tenant it's assigned to in `tenant_nations`, or to the default tenant if it isn't
+//! assigned to any. The rate limiter stays shared, as the
+//! tenants still share the outbound IP NS counts requests against.
+
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+use crate::core::error::Error;
+use crate::types::{DEFAULT_TENANT, request, response};
+use crate::utils::name;
+
+#[derive(Clone, Debug)]
+pub(crate) struct Controller {
+    pool: PgPool,
+}
+
+impl Controller {
+    pub(crate) fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// See [`own_nations`].
+    pub(crate) async fn own_nations(
+        &self,
+        tenant_id: i32,
+        nations: Vec<String>,
+    ) -> Result<Vec<String>, Error> {
+        own_nations(&self.pool, tenant_id, nations).await
+    }
+
+    /// See [`check_nation`].
+    pub(crate) async fn check_nation(&self, tenant_id: i32, nation: &str) -> Result<(), Error> {
+        check_nation(&self.pool, tenant_id, nation).await
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn list(&self) -> Result<Vec<response::Tenant>, Error> {
+        Ok(sqlx::query(
+            "SELECT
+                tenants.id,
+                tenants.name,
+                tenants.created_at,
+                COALESCE(
+                    (SELECT array_agg(nation ORDER BY nation) FROM tenant_nations WHERE tenant_id = tenants.id),
+                    '{}'
+                ) AS nations,
+                (SELECT COUNT(*) FROM users WHERE tenant_id = tenants.id) AS users
+            FROM tenants
+            ORDER BY tenants.id;",
+        )
+        .map(map_tenant)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Creates a tenant owning `tenant.nations`, which other tenants lose.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn create(
+        &self,
+        tenant: request::NewTenant,
+    ) -> Result<response::Tenant, Error> {
+        let name = tenant.name.trim();
+
+        if name.is_empty() {
+            return Err(Error::InvalidTenant("a tenant needs a name".to_string()));
+        }
+
+        let nations: Vec<String> = tenant
+            .nations
+            .iter()
+            .map(|nation| name::canonicalize(nation))
+            .collect();
+
+        let mut tx = self.pool.begin().await?;
+
+        let id: i32 = sqlx::query(
+            "INSERT INTO tenants (name) VALUES ($1)
+            ON CONFLICT (name) DO NOTHING
+            RETURNING id;",
+        )
+        .bind(name)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| Error::TenantExists(name.to_string()))?;
+
+        sqlx::query(
+            "INSERT INTO tenant_nations (nation, tenant_id)
+            SELECT UNNEST($1::VARCHAR[]), $2
+            ON CONFLICT (nation) DO UPDATE SET tenant_id = EXCLUDED.tenant_id;",
+        )
+        .bind(&nations)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        let created = sqlx::query(
+            "SELECT id, name, created_at, $2::VARCHAR[] AS nations, 0::BIGINT AS users
+            FROM tenants
+            WHERE id = $1;",
+        )
+        .bind(id)
+        .bind(&nations)
+        .map(map_tenant)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(created)
+    }
+}
+
+/// Those of `nations` that belong to `tenant_id`, in order.
+pub(crate) async fn own_nations(
+    pool: &PgPool,
+    tenant_id: i32,
+    nations: Vec<String>,
+) -> Result<Vec<String>, Error> {
+    Ok(sqlx::query_scalar(
+        "SELECT configured.nation
+        FROM UNNEST($1::VARCHAR[]) WITH ORDINALITY AS configured (nation, position)
+        LEFT JOIN tenant_nations ON tenant_nations.nation = configured.nation
+        WHERE COALESCE(tenant_nations.tenant_id, $3) = $2
+        ORDER BY configured.position;",
+    )
+    .bind(nations)
+    .bind(tenant_id)
+    .bind(DEFAULT_TENANT)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Fails unless `nation` belongs to `tenant_id`.
+pub(crate) async fn check_nation(pool: &PgPool, tenant_id: i32, nation: &str) -> Result<(), Error> {
+    let owner: i32 = sqlx::query_scalar(
+        "SELECT COALESCE((SELECT tenant_id FROM tenant_nations WHERE nation = $1), $2);",
+    )
+    .bind(nation)
+    .bind(DEFAULT_TENANT)
+    .fetch_one(pool)
+    .await?;
+
+    if owner == tenant_id {
+        Ok(())
+    } else {
+        Err(Error::NationNotInTenant(nation.to_string()))
+    }
+}
+
+fn map_tenant(row: PgRow) -> response::Tenant {
+    response::Tenant {
+        id: row.get("id"),
+        name: row.get("name"),
+        nations: row.get("nations"),
+        users: row.get("users"),
+        created_at: row.get("created_at"),
+    }
+}
@@ -0,0 +1,282 @@
+//! Failed login tracking, per username and per client IP. Attempts are counted before the password
+//! is checked, under the same lock as the limit check, so concurrent requests can't all slip in
+//! under the limit; a successful login takes its attempt back.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Scope {
+    Username,
+    Ip,
+}
+
+impl Scope {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Scope::Username => "username",
+            Scope::Ip => "ip",
+        }
+    }
+}
+
+/// Why an attempt was rejected.
+#[derive(Debug)]
+pub(crate) struct Lockout {
+    pub(crate) scope: Scope,
+    pub(crate) retry_after: Duration,
+    /// the first rejection since the limit was reached, the one worth recording
+    pub(crate) started: bool,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+enum Key {
+    Username(String),
+    Ip(IpAddr),
+}
+
+impl Key {
+    fn scope(&self) -> Scope {
+        match self {
+            Key::Username(_) => Scope::Username,
+            Key::Ip(_) => Scope::Ip,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Attempts {
+    failures: VecDeque<Instant>,
+    locked_out: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Throttle {
+    max_attempts: usize,
+    window: Duration,
+    attempts: Arc<Mutex<HashMap<Key, Attempts>>>,
+}
+
+impl Throttle {
+    pub(crate) fn new(max_attempts: usize, window: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            window,
+            attempts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Counts an attempt made at `now` against the username and the IP, unless either already has
+    /// `max_attempts` failures within the window.
+    pub(crate) fn attempt(
+        &self,
+        username: &str,
+        ip: Option<IpAddr>,
+        now: Instant,
+    ) -> Result<(), Lockout> {
+        let mut attempts = self.attempts.lock().unwrap_or_else(PoisonError::into_inner);
+
+        attempts.retain(|_, attempts| {
+            while attempts
+                .failures
+                .front()
+                .is_some_and(|failure| *failure + self.window <= now)
+            {
+                attempts.failures.pop_front();
+            }
+
+            if attempts.failures.len() < self.max_attempts {
+                attempts.locked_out = false;
+            }
+
+            !attempts.failures.is_empty()
+        });
+
+        let keys = keys(username, ip);
+        let mut lockout: Option<Lockout> = None;
+
+        for key in &keys {
+            let Some(attempts) = attempts.get_mut(key) else {
+                continue;
+            };
+
+            if attempts.failures.len() < self.max_attempts {
+                continue;
+            }
+
+            // the failure that has to expire to bring the count back under the limit
+            let oldest = attempts.failures[attempts.failures.len() - self.max_attempts];
+            let retry_after = (oldest + self.window).saturating_duration_since(now);
+            let started = !attempts.locked_out;
+            attempts.locked_out = true;
+
+            // report the longest wait, a lockout starting on either key
+            lockout = Some(match lockout {
+                Some(lockout) if lockout.retry_after >= retry_after => Lockout {
+                    started: lockout.started || started,
+                    ..lockout
+                },
+                previous => Lockout {
+                    scope: key.scope(),
+                    retry_after,
+                    started: started || previous.is_some_and(|lockout| lockout.started),
+                },
+            });
+        }
+
+        if let Some(lockout) = lockout {
+            return Err(lockout);
+        }
+
+        for key in keys {
+            attempts.entry(key).or_default().failures.push_back(now);
+        }
+
+        Ok(())
+    }
+
+    /// Clears the username's failures and takes back the attempt made at `at` from the IP.
+    pub(crate) fn succeeded(&self, username: &str, ip: Option<IpAddr>, at: Instant) {
+        let mut attempts = self.attempts.lock().unwrap_or_else(PoisonError::into_inner);
+
+        attempts.remove(&Key::Username(username.to_string()));
+
+        if let Some(ip) = ip {
+            release(&mut attempts, &Key::Ip(ip), at);
+        }
+    }
+
+    /// Takes back the attempt made at `at`, for attempts that failed for reasons other than the
+    /// credentials.
+    pub(crate) fn release(&self, username: &str, ip: Option<IpAddr>, at: Instant) {
+        let mut attempts = self.attempts.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for key in keys(username, ip) {
+            release(&mut attempts, &key, at);
+        }
+    }
+}
+
+fn keys(username: &str, ip: Option<IpAddr>) -> Vec<Key> {
+    let mut keys = vec![Key::Username(username.to_string())];
+    keys.extend(ip.map(Key::Ip));
+    keys
+}
+
+fn release(attempts: &mut HashMap<Key, Attempts>, key: &Key, at: Instant) {
+    if let Some(entry) = attempts.get_mut(key) {
+        if let Some(index) = entry.failures.iter().rposition(|failure| *failure == at) {
+            entry.failures.remove(index);
+        }
+
+        if entry.failures.is_empty() {
+            attempts.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const IP: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    #[test]
+    fn test_failures_decay() {
+        let throttle = Throttle::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(throttle.attempt("alice", IP, at(0)).is_ok());
+        assert!(throttle.attempt("alice", IP, at(10)).is_ok());
+
+        let lockout = throttle.attempt("alice", IP, at(20)).unwrap_err();
+        assert_eq!(lockout.retry_after, Duration::from_secs(40));
+        assert!(lockout.started);
+
+        let lockout = throttle.attempt("alice", IP, at(30)).unwrap_err();
+        assert_eq!(lockout.retry_after, Duration::from_secs(30));
+        assert!(!lockout.started);
+
+        // the first failure has expired, the second hasn't
+        assert!(throttle.attempt("alice", IP, at(60)).is_ok());
+        assert!(throttle.attempt("alice", IP, at(65)).unwrap_err().started);
+
+        // everything has expired
+        assert!(throttle.attempt("alice", IP, at(200)).is_ok());
+        assert!(throttle.attempt("alice", IP, at(200)).is_ok());
+    }
+
+    #[test]
+    fn test_success_clears_username() {
+        let throttle = Throttle::new(3, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(throttle.attempt("alice", None, now).is_ok());
+        assert!(throttle.attempt("alice", None, now).is_ok());
+        assert!(throttle.attempt("alice", None, now).is_ok());
+        throttle.succeeded("alice", None, now);
+
+        for _ in 0..3 {
+            assert!(throttle.attempt("alice", None, now).is_ok());
+        }
+        assert!(throttle.attempt("alice", None, now).is_err());
+    }
+
+    #[test]
+    fn test_success_keeps_ip_failures() {
+        let throttle = Throttle::new(3, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(throttle.attempt("alice", IP, now).is_ok());
+        assert!(throttle.attempt("alice", IP, now).is_ok());
+
+        // logging in to an account of your own doesn't reset the failures of the IP
+        assert!(throttle.attempt("mallory", IP, now).is_ok());
+        throttle.succeeded("mallory", IP, now);
+
+        assert!(throttle.attempt("bob", IP, now).is_ok());
+
+        let lockout = throttle.attempt("carol", IP, now).unwrap_err();
+        assert_eq!(lockout.scope, Scope::Ip);
+        assert_eq!(lockout.retry_after, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_release() {
+        let throttle = Throttle::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(throttle.attempt("alice", IP, now).is_ok());
+        throttle.release("alice", IP, now);
+
+        assert!(throttle.attempt("alice", IP, now).is_ok());
+        assert_eq!(
+            throttle.attempt("alice", IP, now).unwrap_err().scope,
+            Scope::Username
+        );
+    }
+
+    #[test]
+    fn test_concurrent_attempts_respect_limit() {
+        let throttle = Throttle::new(5, Duration::from_secs(60));
+        let now = Instant::now();
+
+        let allowed = std::thread::scope(|scope| {
+            let attempts: Vec<_> = (0..50)
+                .map(|_| scope.spawn(|| throttle.attempt("alice", IP, now).is_ok()))
+                .collect();
+
+            attempts
+                .into_iter()
+                .map(|attempt| attempt.join().unwrap())
+                .filter(|allowed| *allowed)
+                .count()
+        });
+
+        assert_eq!(allowed, 5);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::core::error::Error;
+use crate::types::request::{JobKind, TimingsQuery};
+use crate::types::response::{JobTimings, PhasePercentiles};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+const PHASES: [&str; 4] = [
+    "queued_ms",
+    "ratelimit_wait_ms",
+    "ns_prepare_ms",
+    "ns_execute_ms",
+];
+
+/// p50 and p95 of each phase of the jobs that finished within the window, by default the last
+/// day, among `tenant_id`'s jobs. `table` must be one of the job queue tables, it is interpolated into the query as-is.
+/// The phases are computed as [`crate::types::response::JobDurations`] computes them for a
+/// single job.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn summarize(
+    pool: &PgPool,
+    table: &str,
+    job: JobKind,
+    query: &TimingsQuery,
+    tenant_id: i32,
+) -> Result<JobTimings, Error> {
+    let percentiles = PHASES
+        .iter()
+        .map(|phase| {
+            format!(
+                "ROUND(PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY {phase}))::BIGINT AS {phase}_p50,
+                ROUND(PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY {phase}))::BIGINT AS {phase}_p95"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    Ok(sqlx::query(&format!(
+        "WITH durations AS (
+            SELECT
+                EXTRACT(EPOCH FROM claimed_at - created_at)::DOUBLE PRECISION * 1000 AS queued_ms,
+                EXTRACT(EPOCH FROM (started_at - claimed_at) + COALESCE(executing_at - prepared_at, INTERVAL '0'))::DOUBLE PRECISION * 1000 AS ratelimit_wait_ms,
+                EXTRACT(EPOCH FROM prepared_at - started_at)::DOUBLE PRECISION * 1000 AS ns_prepare_ms,
+                EXTRACT(EPOCH FROM finished_at - executing_at)::DOUBLE PRECISION * 1000 AS ns_execute_ms
+            FROM {table}
+            WHERE finished_at >= COALESCE($1::TIMESTAMPTZ, NOW() - INTERVAL '1 day')
+            AND ($2::TIMESTAMPTZ IS NULL OR finished_at < $2)
+            AND tenant_id = $3
+        )
+        SELECT COUNT(*) AS jobs, {percentiles}
+        FROM durations;"
+    ))
+    .bind(query.from)
+    .bind(query.to)
+    .bind(tenant_id)
+    .map(|row: PgRow| {
+        let phase = |phase: &str| PhasePercentiles {
+            p50: row.get(format!("{phase}_p50").as_str()),
+            p95: row.get(format!("{phase}_p95").as_str()),
+        };
+
+        JobTimings {
+            job,
+            jobs: row.get("jobs"),
+            queued_ms: phase("queued_ms"),
+            ratelimit_wait_ms: phase("ratelimit_wait_ms"),
+            ns_prepare_ms: phase("ns_prepare_ms"),
+            ns_execute_ms: phase("ns_execute_ms"),
+        }
+    })
+    .fetch_one(pool)
+    .await?)
+}
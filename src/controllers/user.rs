@@ -1,16 +1,76 @@
+use crate::controllers::api_key;
+use crate::controllers::throttle::Throttle;
+use crate::core::authorization::Claim;
 use crate::core::error::{self, Error};
 use crate::core::state::AppState;
+use crate::types::request::UsersQuery;
+use crate::types::response::UserAccount;
 use crate::types::user::Claims;
-use crate::types::{AuthorizedUser, Username};
+use crate::types::{AuthorizedUser, IssuedToken, Username};
 use axum::body::Body;
 use axum::extract::{Request, State};
-use axum::http::{Response, header};
+use axum::http::{HeaderName, HeaderValue, Response, header};
 use axum::middleware::Next;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, TokenData, Validation};
 use regex::Regex;
 use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row};
+use std::net::IpAddr;
+use std::time::Instant;
+
+/// How long after expiry a token may still be exchanged at `/login/refresh`, in seconds.
+const REFRESH_GRACE_PERIOD: u64 = 60 * 60;
+
+/// Lowest bcrypt cost accepted from the config, the minimum bcrypt supports.
+const MIN_BCRYPT_COST: u32 = 4;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Header carrying an API key, an alternative to `Authorization: Bearer`.
+pub(crate) const API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+
+/// `iss` claim set on every token we issue; tokens with any other issuer are rejected.
+const ISSUER: &str = "https://api.europeia.dev";
+
+/// Parsed contents of the `Authorization` header.
+#[derive(Debug, PartialEq)]
+pub(crate) enum AuthHeader<'a> {
+    NoHeader,
+    Bearer(&'a str),
+    Malformed(&'static str),
+}
+
+pub(crate) fn parse_auth_header(value: Option<&HeaderValue>) -> AuthHeader<'_> {
+    let value = match value {
+        Some(value) => value,
+        None => return AuthHeader::NoHeader,
+    };
+
+    let value = match value.to_str() {
+        Ok(value) => value,
+        Err(_) => return AuthHeader::Malformed("Authorization header is not valid ASCII"),
+    };
+
+    let mut parts = value.split_whitespace();
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (None, _, _) => AuthHeader::Malformed("Authorization header is empty"),
+        (Some(scheme), _, _) if !scheme.eq_ignore_ascii_case("bearer") => {
+            AuthHeader::Malformed("Unsupported authorization scheme, expected Bearer")
+        }
+        (Some(_), None, _) => AuthHeader::Malformed("Missing bearer token"),
+        (Some(_), Some(_), Some(_)) => AuthHeader::Malformed("Unexpected data after bearer token"),
+        (Some(_), Some(token), None) => AuthHeader::Bearer(token),
+    }
+}
+
+fn validation() -> Validation {
+    let mut validation = Validation::default();
+    validation.set_issuer(&[ISSUER]);
+    validation
+}
 
 #[derive(Clone)]
 pub(crate) struct Controller {
@@ -18,23 +78,46 @@ pub(crate) struct Controller {
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
     username_pattern: Regex,
+    jwt_ttl: Duration,
+    bcrypt_cost: u32,
+    throttle: Throttle,
 }
 
 impl std::fmt::Debug for Controller {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("UserController")
             .field("username_pattern", &self.username_pattern.as_str())
+            .field("jwt_ttl", &self.jwt_ttl)
+            .field("bcrypt_cost", &self.bcrypt_cost)
+            .field("throttle", &self.throttle)
             .finish()
     }
 }
 
 impl Controller {
-    pub(crate) fn new(pool: PgPool, jwt_secret: String) -> Result<Self, error::ConfigError> {
+    pub(crate) fn new(
+        pool: PgPool,
+        jwt_secret: String,
+        jwt_ttl: Duration,
+        bcrypt_cost: u32,
+        throttle: Throttle,
+    ) -> Result<Self, error::ConfigError> {
+        if bcrypt_cost < MIN_BCRYPT_COST {
+            tracing::warn!(
+                "bcrypt cost {} is too low, using {}",
+                bcrypt_cost,
+                MIN_BCRYPT_COST
+            );
+        }
+
         Ok(Self {
             pool,
             encoding_key: EncodingKey::from_secret(jwt_secret.as_bytes()),
             decoding_key: DecodingKey::from_secret(jwt_secret.as_bytes()),
             username_pattern: Regex::new(r"^[a-zA-Z0-9_-]{3,20}$")?,
+            jwt_ttl,
+            bcrypt_cost: bcrypt_cost.max(MIN_BCRYPT_COST),
+            throttle,
         })
     }
 
@@ -43,12 +126,42 @@ impl Controller {
         &self,
         username: &str,
     ) -> Result<Option<AuthorizedUser>, Error> {
+        Ok(self.find_user(username).await?.map(|(user, _)| user))
+    }
+
+    /// Like [`Controller::get_user_by_username`], but a disabled account is an error rather than
+    /// a user, so it can't authenticate.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get_active_user(
+        &self,
+        username: &str,
+    ) -> Result<Option<AuthorizedUser>, Error> {
+        match self.find_user(username).await? {
+            Some((_, true)) => Err(Error::AccountDisabled),
+            user => Ok(user.map(|(user, _)| user)),
+        }
+    }
+
+    /// The active user a token was issued to. A token from before the user moved to another
+    /// tenant is refused, so it can't reach the old tenant's rows.
+    async fn token_user(&self, claims: &Claims) -> Result<Option<AuthorizedUser>, Error> {
+        match self.get_active_user(&claims.sub).await? {
+            Some(user) if user.tenant_id != claims.tenant => Err(Error::Unauthorized),
+            user => Ok(user),
+        }
+    }
+
+    /// The user and whether their account is disabled.
+    async fn find_user(&self, username: &str) -> Result<Option<(AuthorizedUser, bool)>, Error> {
         match sqlx::query(
             "SELECT
             users.id,
             users.username,
             users.password_hash,
-            COALESCE(array_agg(permissions.name), '{}') AS permissions
+            users.created_at,
+            users.tenant_id,
+            users.disabled_at IS NOT NULL AS disabled,
+            COALESCE(array_agg(permissions.name) FILTER (WHERE permissions.name IS NOT NULL), '{}') AS permissions
             FROM
                 users
             LEFT JOIN
@@ -61,7 +174,11 @@ impl Controller {
                 users.id, users.username;",
         )
         .bind(username)
-        .map(map_user)
+        .map(|row: PgRow| {
+            let disabled = row.get("disabled");
+
+            (map_user(row), disabled)
+        })
         .fetch_one(&self.pool)
         .await
         {
@@ -71,13 +188,23 @@ impl Controller {
         }
     }
 
+    /// Users of tenants other than `tenant` aren't found.
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn get_username_by_id(&self, id: i32) -> Result<Option<Username>, Error> {
-        match sqlx::query("SELECT username FROM users WHERE id = $1")
-            .bind(id)
-            .map(|row: PgRow| row.get("username"))
-            .fetch_one(&self.pool)
-            .await
+    pub(crate) async fn get_username_by_id(
+        &self,
+        id: i32,
+        tenant: Option<i32>,
+    ) -> Result<Option<Username>, Error> {
+        match sqlx::query(
+            "SELECT username FROM users
+            WHERE id = $1
+            AND ($2::INTEGER IS NULL OR tenant_id = $2)",
+        )
+        .bind(id)
+        .bind(tenant)
+        .map(|row: PgRow| row.get("username"))
+        .fetch_one(&self.pool)
+        .await
         {
             Ok(username) => Ok(Some(username)),
             Err(sqlx::Error::RowNotFound) => Ok(None),
@@ -90,7 +217,7 @@ impl Controller {
         &self,
         username: &str,
         password: &str,
-    ) -> Result<(AuthorizedUser, String), Error> {
+    ) -> Result<(AuthorizedUser, IssuedToken), Error> {
         if !self.username_pattern.is_match(username) {
             return Err(Error::InvalidUsername);
         }
@@ -101,18 +228,18 @@ impl Controller {
             ));
         }
 
-        let password_hash = self.hash(password)?;
+        let password_hash = self.hash(password).await?;
 
-        let id: i32 = match sqlx::query(
-            "INSERT INTO users (username, password_hash) VALUES ($1, $2) RETURNING id;",
+        let (id, created_at, tenant_id) = match sqlx::query(
+            "INSERT INTO users (username, password_hash) VALUES ($1, $2) RETURNING id, created_at, tenant_id;",
         )
         .bind(username)
         .bind(&password_hash)
-        .map(|row: PgRow| row.get("id"))
+        .map(|row: PgRow| (row.get("id"), row.get("created_at"), row.get("tenant_id")))
         .fetch_one(&self.pool)
         .await
         {
-            Ok(id) => id,
+            Ok(row) => row,
             Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
                 return Err(Error::UserAlreadyExists);
             }
@@ -124,6 +251,9 @@ impl Controller {
             username: username.into(),
             password_hash,
             claims: Vec::new(),
+            created_at,
+            tenant_id,
+            api_key_id: None,
         };
 
         let token = self.encode_jwt(&user)?;
@@ -131,21 +261,75 @@ impl Controller {
         Ok((user, token))
     }
 
+    /// Checks the credentials, unless the username or `ip` has too many recent failures.
     #[tracing::instrument(skip_all)]
     pub(crate) async fn login(
         &self,
         username: &str,
         password: &str,
-    ) -> Result<(AuthorizedUser, String), Error> {
-        let user = self
-            .get_user_by_username(username)
+        ip: Option<IpAddr>,
+    ) -> Result<(AuthorizedUser, IssuedToken), Error> {
+        let now = Instant::now();
+
+        self.throttle
+            .attempt(username, ip, now)
+            .map_err(Error::LoginThrottled)?;
+
+        let result = self.verify_login(username, password).await;
+
+        match &result {
+            Ok(_) => self.throttle.succeeded(username, ip, now),
+            Err(Error::InvalidUsername | Error::Unauthorized) => {}
+            Err(_) => self.throttle.release(username, ip, now),
+        }
+
+        result
+    }
+
+    async fn verify_login(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<(AuthorizedUser, IssuedToken), Error> {
+        let (user, disabled) = self
+            .find_user(username)
             .await?
             .ok_or(Error::InvalidUsername)?;
 
-        if !bcrypt::verify(password, &user.password_hash)? {
+        if !verify(password, &user.password_hash).await? {
             return Err(Error::Unauthorized);
         };
 
+        // only once the password checks out, so it doesn't tell anyone else the account exists
+        if disabled {
+            return Err(Error::AccountDisabled);
+        }
+
+        sqlx::query("UPDATE users SET last_login_at = NOW() WHERE id = $1;")
+            .bind(user.id)
+            .execute(&self.pool)
+            .await?;
+
+        let token = self.encode_jwt(&user)?;
+
+        Ok((user, token))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn refresh(
+        &self,
+        token: &str,
+    ) -> Result<(AuthorizedUser, IssuedToken), Error> {
+        let mut validation = validation();
+        validation.leeway = REFRESH_GRACE_PERIOD;
+
+        let token_data = self.decode_jwt_with(token, &validation)?;
+
+        let user = self
+            .token_user(&token_data.claims)
+            .await?
+            .ok_or(Error::Unauthorized)?;
+
         let token = self.encode_jwt(&user)?;
 
         Ok((user, token))
@@ -158,7 +342,7 @@ impl Controller {
         password: &str,
     ) -> Result<(), Error> {
         sqlx::query("UPDATE users SET password_hash = $1 WHERE username = $2;")
-            .bind(self.hash(password)?)
+            .bind(self.hash(password).await?)
             .bind(username)
             .execute(&self.pool)
             .await?;
@@ -166,13 +350,208 @@ impl Controller {
         Ok(())
     }
 
-    fn hash(&self, value: &str) -> Result<String, Error> {
-        bcrypt::hash(value, 12).map_err(Error::Bcrypt)
+    /// Accounts by id, disabled ones included, only `tenant`'s if one is given.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn list(
+        &self,
+        query: &UsersQuery,
+        tenant: Option<i32>,
+    ) -> Result<Vec<UserAccount>, Error> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+
+        Ok(sqlx::query(
+            "SELECT
+            users.id,
+            users.username,
+            users.created_at,
+            users.last_login_at,
+            users.disabled_at,
+            users.tenant_id,
+            COALESCE(array_agg(permissions.name) FILTER (WHERE permissions.name IS NOT NULL), '{}') AS permissions
+            FROM
+                users
+            LEFT JOIN
+                user_permissions ON users.id = user_permissions.user_id
+            LEFT JOIN
+                permissions ON user_permissions.permission_id = permissions.id
+            WHERE
+                ($1::TEXT IS NULL OR STRPOS(LOWER(users.username), LOWER($1)) > 0)
+                AND ($4::INTEGER IS NULL OR users.tenant_id = $4)
+            GROUP BY
+                users.id
+            ORDER BY users.id
+            LIMIT $2
+            OFFSET $3;",
+        )
+        .bind(&query.username)
+        .bind(limit)
+        .bind(query.offset.unwrap_or(0).max(0))
+        .bind(tenant)
+        .map(map_account)
+        .fetch_all(&self.pool)
+        .await?)
     }
 
-    pub(crate) fn encode_jwt(&self, user: &AuthorizedUser) -> Result<String, Error> {
+    /// Disables the account so it can no longer log in or use its tokens and API keys. The row
+    /// stays, jobs and dispatches keep naming their creator. Disabling twice keeps the first
+    /// `disabled_at`. Users of tenants other than `tenant` aren't found.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn disable(&self, id: i32, tenant: Option<i32>) -> Result<UserAccount, Error> {
+        match sqlx::query(
+            "WITH disabled AS (
+                UPDATE users SET disabled_at = COALESCE(disabled_at, NOW())
+                WHERE id = $1
+                AND ($2::INTEGER IS NULL OR tenant_id = $2)
+                RETURNING id, username, created_at, last_login_at, disabled_at, tenant_id
+            )
+            SELECT
+            disabled.*,
+            COALESCE(array_agg(permissions.name) FILTER (WHERE permissions.name IS NOT NULL), '{}') AS permissions
+            FROM
+                disabled
+            LEFT JOIN
+                user_permissions ON disabled.id = user_permissions.user_id
+            LEFT JOIN
+                permissions ON user_permissions.permission_id = permissions.id
+            GROUP BY
+                disabled.id, disabled.username, disabled.created_at, disabled.last_login_at, disabled.disabled_at, disabled.tenant_id;",
+        )
+        .bind(id)
+        .bind(tenant)
+        .map(map_account)
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(account) => Ok(account),
+            Err(sqlx::Error::RowNotFound) => Err(Error::UserNotFound),
+            Err(e) => Err(Error::Sql(e)),
+        }
+    }
+
+    /// Moves the account to `tenant_id`. Its tokens name the tenant it had, so it has to log in
+    /// again; its API keys act as the account and follow it.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn set_tenant(&self, id: i32, tenant_id: i32) -> Result<UserAccount, Error> {
+        match sqlx::query(
+            "WITH moved AS (
+                UPDATE users SET tenant_id = $2
+                WHERE id = $1
+                RETURNING id, username, created_at, last_login_at, disabled_at, tenant_id
+            )
+            SELECT
+            moved.*,
+            COALESCE(array_agg(permissions.name) FILTER (WHERE permissions.name IS NOT NULL), '{}') AS permissions
+            FROM
+                moved
+            LEFT JOIN
+                user_permissions ON moved.id = user_permissions.user_id
+            LEFT JOIN
+                permissions ON user_permissions.permission_id = permissions.id
+            GROUP BY
+                moved.id, moved.username, moved.created_at, moved.last_login_at, moved.disabled_at, moved.tenant_id;",
+        )
+        .bind(id)
+        .bind(tenant_id)
+        .map(map_account)
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(account) => Ok(account),
+            Err(sqlx::Error::RowNotFound) => Err(Error::UserNotFound),
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(Error::TenantNotFound)
+            }
+            Err(e) => Err(Error::Sql(e)),
+        }
+    }
+
+    /// Gives the account of `username` each of `claims` it doesn't have yet.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn grant(
+        &self,
+        username: &str,
+        claims: &[Claim],
+    ) -> Result<UserAccount, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let id: i32 = sqlx::query("SELECT id FROM users WHERE username = $1;")
+            .bind(username)
+            .map(|row: PgRow| row.get("id"))
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(Error::UserNotFound)?;
+
+        for claim in claims {
+            sqlx::query(
+                "INSERT INTO permissions (name) SELECT $1
+                WHERE NOT EXISTS (SELECT 1 FROM permissions WHERE name = $1);",
+            )
+            .bind(claim.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                "INSERT INTO user_permissions (user_id, permission_id)
+                SELECT $1, id FROM permissions WHERE name = $2
+                ON CONFLICT DO NOTHING;",
+            )
+            .bind(id)
+            .bind(claim.as_str())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let account = sqlx::query(
+            "SELECT
+            users.id,
+            users.username,
+            users.created_at,
+            users.last_login_at,
+            users.disabled_at,
+            users.tenant_id,
+            COALESCE(array_agg(permissions.name) FILTER (WHERE permissions.name IS NOT NULL), '{}') AS permissions
+            FROM
+                users
+            LEFT JOIN
+                user_permissions ON users.id = user_permissions.user_id
+            LEFT JOIN
+                permissions ON user_permissions.permission_id = permissions.id
+            WHERE
+                users.id = $1
+            GROUP BY
+                users.id;",
+        )
+        .bind(id)
+        .map(map_account)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(account)
+    }
+
+    /// bcrypt is deliberately slow, so hashing runs on the blocking pool instead of stalling a
+    /// runtime thread.
+    async fn hash(&self, value: &str) -> Result<String, Error> {
+        let (value, cost) = (value.to_string(), self.bcrypt_cost);
+
+        match tokio::task::spawn_blocking(move || bcrypt::hash(value, cost)).await {
+            Ok(hash) => Ok(hash?),
+            Err(e) => {
+                tracing::error!("password hashing task failed: {}", e);
+
+                Err(Error::Internal)
+            }
+        }
+    }
+
+    pub(crate) fn encode_jwt(&self, user: &AuthorizedUser) -> Result<IssuedToken, Error> {
         let current_time = Utc::now();
-        let expiration_time = current_time + Duration::days(1);
+        let expiration_time = current_time + self.jwt_ttl;
 
         let exp = expiration_time.timestamp() as usize;
         let iat = current_time.timestamp() as usize;
@@ -181,52 +560,89 @@ impl Controller {
             exp,
             iat,
             sub: user.username.to_string(),
-            iss: "https://api.europeia.dev".into(),
+            iss: ISSUER.into(),
+            tenant: user.tenant_id,
         };
 
-        Ok(jsonwebtoken::encode(
-            &Header::default(),
-            &claims,
-            &self.encoding_key,
-        )?)
+        let token = jsonwebtoken::encode(&Header::default(), &claims, &self.encoding_key)?;
+
+        Ok(IssuedToken {
+            token,
+            expires_at: expiration_time,
+        })
     }
 
     pub(crate) fn decode_jwt(&self, token: String) -> Result<TokenData<Claims>, Error> {
-        match jsonwebtoken::decode::<Claims>(&token, &self.decoding_key, &Validation::default()) {
+        self.decode_jwt_with(&token, &validation())
+    }
+
+    fn decode_jwt_with(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> Result<TokenData<Claims>, Error> {
+        match jsonwebtoken::decode::<Claims>(token, &self.decoding_key, validation) {
             Ok(token_data) => Ok(token_data),
             Err(e) => match e.kind() {
                 jsonwebtoken::errors::ErrorKind::ExpiredSignature => Err(Error::ExpiredJWT),
+                jsonwebtoken::errors::ErrorKind::InvalidIssuer => Err(Error::Unauthorized),
                 _ => Err(Error::Jwt(e)),
             },
         }
     }
 }
 
+/// Checks `password` against `hash` on the blocking pool, see `Controller::hash`.
+async fn verify(password: &str, hash: &str) -> Result<bool, Error> {
+    let (password, hash) = (password.to_string(), hash.to_string());
+
+    match tokio::task::spawn_blocking(move || bcrypt::verify(password, &hash)).await {
+        Ok(valid) => Ok(valid?),
+        Err(e) => {
+            tracing::error!("password verification task failed: {}", e);
+
+            Err(Error::Internal)
+        }
+    }
+}
+
 #[tracing::instrument(skip_all)]
 pub(crate) async fn authenticate(
     State(state): State<AppState>,
     mut request: Request,
     next: Next,
 ) -> Result<Response<Body>, Error> {
-    let headers = request.headers_mut();
+    let authorization = parse_auth_header(request.headers().get(header::AUTHORIZATION));
+
+    if let Some(key) = request.headers().get(API_KEY_HEADER) {
+        if authorization != AuthHeader::NoHeader {
+            return Err(Error::MalformedAuthHeader(
+                "Send either an API key or a bearer token, not both",
+            ));
+        }
+
+        let key = key.to_str().map_err(|_| Error::InvalidApiKey)?;
+        let user = authenticate_api_key(&state, key).await?;
 
-    let auth_header = match headers.get(header::AUTHORIZATION) {
-        Some(auth_header) => auth_header,
-        None => {
+        request.extensions_mut().insert(Some(user));
+
+        return Ok(next.run(request).await);
+    }
+
+    let token = match authorization {
+        AuthHeader::NoHeader => {
             request.extensions_mut().insert(None::<AuthorizedUser>);
             return Ok(next.run(request).await);
         }
+        AuthHeader::Bearer(token) => token.to_string(),
+        AuthHeader::Malformed(reason) => return Err(Error::MalformedAuthHeader(reason)),
     };
 
-    let mut header = auth_header.to_str()?.split_whitespace();
-
-    let (_bearer, token) = (header.next(), header.next().unwrap_or_default());
-
-    let token_data = state.user_controller.decode_jwt(token.into())?;
+    let token_data = state.user_controller.decode_jwt(token)?;
 
     let user = state
         .user_controller
-        .get_user_by_username(&token_data.claims.sub)
+        .token_user(&token_data.claims)
         .await?
         .ok_or_else(|| Error::InvalidUsername)?;
 
@@ -235,6 +651,22 @@ pub(crate) async fn authenticate(
     Ok(next.run(request).await)
 }
 
+/// The key owner, acting with the key's scope instead of their own claims.
+async fn authenticate_api_key(state: &AppState, key: &str) -> Result<AuthorizedUser, Error> {
+    let grant = state.api_key_controller.authenticate(key).await?;
+
+    let mut user = state
+        .user_controller
+        .get_active_user(&grant.username)
+        .await?
+        .ok_or(Error::InvalidApiKey)?;
+
+    user.claims = api_key::scope(&grant, &user.claims);
+    user.api_key_id = Some(grant.id);
+
+    Ok(user)
+}
+
 fn map_user(row: PgRow) -> AuthorizedUser {
     AuthorizedUser {
         id: row.get("id"),
@@ -243,5 +675,180 @@ fn map_user(row: PgRow) -> AuthorizedUser {
         claims: row
             .get::<Option<Vec<String>>, _>("permissions")
             .unwrap_or_default(),
+        created_at: row.get("created_at"),
+        tenant_id: row.get("tenant_id"),
+        api_key_id: None,
+    }
+}
+
+fn map_account(row: PgRow) -> UserAccount {
+    UserAccount {
+        id: row.get("id"),
+        username: row.get("username"),
+        claims: row
+            .get::<Option<Vec<String>>, _>("permissions")
+            .unwrap_or_default(),
+        created_at: row.get("created_at"),
+        last_login_at: row.get("last_login_at"),
+        disabled_at: row.get("disabled_at"),
+        tenant_id: row.get("tenant_id"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DEFAULT_TENANT;
+
+    #[test]
+    fn test_parse_auth_header() {
+        assert_eq!(parse_auth_header(None), AuthHeader::NoHeader);
+
+        let value = HeaderValue::from_static("Bearer abc.def.ghi");
+        assert_eq!(
+            parse_auth_header(Some(&value)),
+            AuthHeader::Bearer("abc.def.ghi")
+        );
+
+        let value = HeaderValue::from_static("bearer  abc.def.ghi ");
+        assert_eq!(
+            parse_auth_header(Some(&value)),
+            AuthHeader::Bearer("abc.def.ghi")
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_auth_header() {
+        for value in [
+            "",
+            "Bearer",
+            "Bearer ",
+            "Basic dXNlcjpwYXNz",
+            "abc.def.ghi",
+            "Bearer a b",
+        ] {
+            let header = HeaderValue::from_str(value).unwrap();
+
+            assert!(
+                matches!(parse_auth_header(Some(&header)), AuthHeader::Malformed(_)),
+                "{:?} should be malformed",
+                value
+            );
+        }
+
+        let value = HeaderValue::from_bytes(&[0x42, 0xff]).unwrap();
+        assert!(matches!(
+            parse_auth_header(Some(&value)),
+            AuthHeader::Malformed(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_decode_rejects_foreign_issuer() {
+        let controller = controller(4);
+
+        let user = AuthorizedUser {
+            id: 1,
+            username: "alice".to_string(),
+            password_hash: String::new(),
+            claims: vec![],
+            created_at: Utc::now(),
+            tenant_id: DEFAULT_TENANT,
+            api_key_id: None,
+        };
+
+        let token = controller.encode_jwt(&user).unwrap().token;
+        assert_eq!(controller.decode_jwt(token).unwrap().claims.sub, "alice");
+
+        let claims = Claims {
+            exp: (Utc::now() + Duration::hours(1)).timestamp() as usize,
+            iat: Utc::now().timestamp() as usize,
+            sub: "alice".to_string(),
+            iss: "https://example.com".to_string(),
+            tenant: DEFAULT_TENANT,
+        };
+        let token = jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            controller.decode_jwt(token),
+            Err(Error::Unauthorized)
+        ));
+    }
+
+    fn controller(bcrypt_cost: u32) -> Controller {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/eurocore")
+            .unwrap();
+
+        Controller::new(
+            pool,
+            "secret".to_string(),
+            Duration::hours(1),
+            bcrypt_cost,
+            Throttle::new(5, std::time::Duration::from_secs(60)),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hash_round_trip() {
+        let controller = controller(MIN_BCRYPT_COST);
+
+        let hash = controller.hash("correct horse").await.unwrap();
+
+        assert!(verify("correct horse", &hash).await.unwrap());
+        assert!(!verify("battery staple", &hash).await.unwrap());
+        assert!(verify("correct horse", "not a hash").await.is_err());
+    }
+
+    #[test]
+    fn test_bcrypt_cost_floor() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let _guard = runtime.enter();
+
+        assert_eq!(controller(1).bcrypt_cost, MIN_BCRYPT_COST);
+        assert_eq!(controller(10).bcrypt_cost, 10);
+    }
+
+    /// 20 concurrent logins must not starve other tasks of the runtime's two threads.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_concurrent_logins_do_not_block_runtime() {
+        let controller = controller(8);
+
+        let hash = controller.hash("password").await.unwrap();
+
+        let logins = (0..20)
+            .map(|_| {
+                let hash = hash.clone();
+                tokio::spawn(async move { verify("password", &hash).await })
+            })
+            .collect::<Vec<_>>();
+
+        let mut worst = std::time::Duration::ZERO;
+
+        for _ in 0..10 {
+            let start = std::time::Instant::now();
+            tokio::spawn(async {}).await.unwrap();
+            worst = worst.max(start.elapsed());
+
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        for login in logins {
+            assert!(login.await.unwrap().unwrap());
+        }
+
+        assert!(
+            worst < std::time::Duration::from_millis(500),
+            "heartbeat took {:?}",
+            worst
+        );
     }
 }
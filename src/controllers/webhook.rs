@@ -0,0 +1,252 @@
+use crate::core::error::{ConfigError, Error};
+use crate::sync::events::{self, Message};
+use crate::types::request::{EventsQuery, NewWebhook};
+use crate::types::response::{JobEvent, SignatureScheme, Webhook};
+use crate::utils::signature;
+use chrono::Utc;
+use futures_util::StreamExt;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::fmt::Write;
+use std::time::Duration;
+
+const MIN_SECRET_LENGTH: usize = 16;
+
+/// Random bytes in a rotated secret.
+const SECRET_BYTES: usize = 32;
+
+/// Deliveries are best-effort; a receiver that doesn't answer in time misses the event.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub(crate) struct Controller {
+    pool: PgPool,
+}
+
+impl Controller {
+    /// Starts delivering every event published on `events` to the webhooks registered by the
+    /// job's tenant.
+    pub(crate) fn new(
+        user_agent: &str,
+        pool: PgPool,
+        events: &events::Sender,
+    ) -> Result<Self, ConfigError> {
+        let client = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .timeout(DELIVERY_TIMEOUT)
+            .build()?;
+
+        let stream = events.subscribe(EventsQuery {
+            job: None,
+            id: None,
+            user: None,
+            tenant: None,
+        });
+
+        tracing::info!("starting webhook delivery");
+        tokio::spawn(deliver_events(pool.clone(), client, stream));
+
+        Ok(Self { pool })
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn register(
+        &self,
+        username: &str,
+        tenant_id: i32,
+        webhook: NewWebhook,
+    ) -> Result<Webhook, Error> {
+        validate_url(&webhook.url)?;
+
+        if webhook.secret.chars().count() < MIN_SECRET_LENGTH {
+            return Err(Error::InvalidWebhookSecret(MIN_SECRET_LENGTH));
+        }
+
+        Ok(sqlx::query(
+            "INSERT INTO webhooks (url, secret, created_by, tenant_id)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, url, created_by, created_at, secret_rotated_at;",
+        )
+        .bind(&webhook.url)
+        .bind(&webhook.secret)
+        .bind(username)
+        .bind(tenant_id)
+        .map(map_webhook)
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn list(&self, tenant_id: i32) -> Result<Vec<Webhook>, Error> {
+        Ok(sqlx::query(
+            "SELECT id, url, created_by, created_at, secret_rotated_at
+            FROM webhooks
+            WHERE tenant_id = $1
+            ORDER BY id;",
+        )
+        .bind(tenant_id)
+        .map(map_webhook)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn delete(&self, id: i32, tenant_id: i32) -> Result<(), Error> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = $1 AND tenant_id = $2;")
+            .bind(id)
+            .bind(tenant_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::WebhookNotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the secret with a generated one, which is only returned here. Deliveries are
+    /// signed with the new secret from the next event on.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn rotate_secret(&self, id: i32, tenant_id: i32) -> Result<Webhook, Error> {
+        let secret = generate();
+
+        let mut webhook = sqlx::query(
+            "UPDATE webhooks SET secret = $1, secret_rotated_at = now()
+            WHERE id = $2 AND tenant_id = $3
+            RETURNING id, url, created_by, created_at, secret_rotated_at;",
+        )
+        .bind(&secret)
+        .bind(id)
+        .bind(tenant_id)
+        .map(map_webhook)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::WebhookNotFound)?;
+
+        webhook.secret = Some(secret);
+
+        Ok(webhook)
+    }
+}
+
+async fn deliver_events(
+    pool: PgPool,
+    client: reqwest::Client,
+    stream: impl futures_util::Stream<Item = Message>,
+) {
+    let mut stream = std::pin::pin!(stream);
+
+    while let Some(message) = stream.next().await {
+        match message {
+            Message::Job(event) => deliver(&pool, &client, &event).await,
+            Message::Lagged(skipped) => {
+                tracing::warn!("webhook delivery fell behind, {} events dropped", skipped)
+            }
+        }
+    }
+}
+
+async fn deliver(pool: &PgPool, client: &reqwest::Client, event: &JobEvent) {
+    let webhooks = match sqlx::query("SELECT id, url, secret FROM webhooks WHERE tenant_id = $1;")
+        .bind(event.tenant_id)
+        .map(|row: PgRow| {
+            (
+                row.get::<i32, _>("id"),
+                row.get::<String, _>("url"),
+                row.get::<String, _>("secret"),
+            )
+        })
+        .fetch_all(pool)
+        .await
+    {
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            tracing::error!("unable to load webhooks: {}", e);
+            return;
+        }
+    };
+
+    if webhooks.is_empty() {
+        return;
+    }
+
+    let body = match serde_json::to_vec(event) {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("unable to serialize job event: {}", e);
+            return;
+        }
+    };
+
+    for (id, url, secret) in webhooks {
+        let request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(
+                signature::HEADER,
+                signature::sign(&secret, Utc::now().timestamp(), &body),
+            )
+            .body(body.clone());
+
+        // one slow receiver shouldn't hold up the others
+        tokio::spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => tracing::debug!("delivered job event to webhook {}", id),
+                Err(e) => tracing::warn!("unable to deliver job event to webhook {}: {}", id, e),
+            }
+        });
+    }
+}
+
+fn validate_url(url: &str) -> Result<(), Error> {
+    match reqwest::Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(()),
+        _ => Err(Error::InvalidWebhookUrl),
+    }
+}
+
+fn generate() -> String {
+    let bytes: [u8; SECRET_BYTES] = rand::random();
+
+    bytes.iter().fold(String::new(), |mut output, byte| {
+        let _ = write!(output, "{:02x}", byte);
+        output
+    })
+}
+
+fn map_webhook(row: PgRow) -> Webhook {
+    Webhook {
+        id: row.get("id"),
+        url: row.get("url"),
+        secret: None,
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        secret_rotated_at: row.get("secret_rotated_at"),
+        signature: SignatureScheme::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_url() {
+        for url in [
+            "https://example.com/hooks/eurocore",
+            "http://127.0.0.1:8080/",
+        ] {
+            assert!(validate_url(url).is_ok(), "{url}");
+        }
+
+        for url in [
+            "",
+            "example.com/hook",
+            "ftp://example.com/",
+            "file:///etc/passwd",
+        ] {
+            assert!(validate_url(url).is_err(), "{url}");
+        }
+    }
+}
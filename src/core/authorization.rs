@@ -0,0 +1,217 @@
+//! The claims users can hold, and the per-route checks of them. Routes declare what they need in
+//! the router with [`require`], so handlers get a user that has already been checked.
+
+use axum::extract::{FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::routing::Route;
+use std::convert::Infallible;
+use tower::{Layer, Service};
+
+use crate::core::error::Error;
+use crate::types::AuthorizedUser;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Claim {
+    Admin,
+    DispatchesCreate,
+    DispatchesEdit,
+    DispatchesEditAny,
+    DispatchesDelete,
+    DispatchesDeleteAny,
+    ExportRead,
+    RmbpostsCreate,
+    StatsRead,
+    TelegramsRead,
+    TelegramsCreate,
+    TelegramsDelete,
+    TelegramsDeleteAny,
+}
+
+impl Claim {
+    /// Every claim, in the order they're listed to admins.
+    pub(crate) const ALL: &[Claim] = &[
+        Claim::Admin,
+        Claim::DispatchesCreate,
+        Claim::DispatchesEdit,
+        Claim::DispatchesEditAny,
+        Claim::DispatchesDelete,
+        Claim::DispatchesDeleteAny,
+        Claim::ExportRead,
+        Claim::RmbpostsCreate,
+        Claim::StatsRead,
+        Claim::TelegramsRead,
+        Claim::TelegramsCreate,
+        Claim::TelegramsDelete,
+        Claim::TelegramsDeleteAny,
+    ];
+
+    /// The name stored in `users.claims` and API key scopes.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Claim::Admin => "admin",
+            Claim::DispatchesCreate => "dispatches.create",
+            Claim::DispatchesEdit => "dispatches.edit",
+            Claim::DispatchesEditAny => "dispatches.edit.any",
+            Claim::DispatchesDelete => "dispatches.delete",
+            Claim::DispatchesDeleteAny => "dispatches.delete.any",
+            Claim::ExportRead => "export.read",
+            Claim::RmbpostsCreate => "rmbposts.create",
+            Claim::StatsRead => "stats.read",
+            Claim::TelegramsRead => "telegrams.read",
+            Claim::TelegramsCreate => "telegrams.create",
+            Claim::TelegramsDelete => "telegrams.delete",
+            Claim::TelegramsDeleteAny => "telegrams.delete.any",
+        }
+    }
+
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            Claim::Admin => {
+                "Manage users, pipelines, nations and webhooks, read the audit log and skip quotas"
+            }
+            Claim::DispatchesCreate => "Post and import dispatches",
+            Claim::DispatchesEdit => "Edit dispatches they posted",
+            Claim::DispatchesEditAny => "Edit dispatches posted by anyone, with dispatches.edit",
+            Claim::DispatchesDelete => "Delete dispatches they posted",
+            Claim::DispatchesDeleteAny => {
+                "Delete dispatches posted by anyone, with dispatches.delete"
+            }
+            Claim::ExportRead => "Export every dispatch revision",
+            Claim::RmbpostsCreate => "Post to regional message boards",
+            Claim::StatsRead => "Read job statistics and timings",
+            Claim::TelegramsRead => "Read queued telegrams, campaigns and sender capacity",
+            Claim::TelegramsCreate => "Queue telegrams and run campaigns",
+            Claim::TelegramsDelete => "Delete telegrams they queued",
+            Claim::TelegramsDeleteAny => "Delete telegrams queued by anyone, with telegrams.delete",
+        }
+    }
+}
+
+impl AuthorizedUser {
+    pub(crate) fn holds(&self, claim: Claim) -> bool {
+        self.claims.iter().any(|held| held == claim.as_str())
+    }
+}
+
+/// The user of a request that passed its route's [`require`] check. Routes without one reject
+/// the request, so a handler taking it can't be reached unchecked.
+#[derive(Clone, Debug)]
+pub(crate) struct Authorized(pub(crate) AuthorizedUser);
+
+impl<S: Send + Sync> FromRequestParts<S> for Authorized {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Authorized>()
+            .cloned()
+            .ok_or(Error::Unauthorized)
+    }
+}
+
+/// Fails unless `user` holds one of `any_of`. Anonymous requests have no credentials to check;
+/// an empty `any_of` lets any user through.
+pub(crate) fn require_claim<'a>(
+    user: Option<&'a AuthorizedUser>,
+    any_of: &[Claim],
+) -> Result<&'a AuthorizedUser, Error> {
+    let user = user.ok_or(Error::NoCredentials)?;
+
+    if any_of.is_empty() || any_of.iter().any(|claim| user.holds(*claim)) {
+        Ok(user)
+    } else {
+        Err(Error::Unauthorized)
+    }
+}
+
+/// Route layer letting through users holding one of `any_of`, see [`require_claim`].
+pub(crate) fn require(
+    any_of: &'static [Claim],
+) -> impl Layer<
+    Route,
+    Service: Service<Request, Response = Response, Error = Infallible, Future: Send + 'static>
+                 + Clone
+                 + Send
+                 + Sync
+                 + 'static,
+> + Clone
++ Send
++ Sync
++ 'static {
+    middleware::from_fn_with_state(any_of, check)
+}
+
+async fn check(
+    State(any_of): State<&'static [Claim]>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    let user = require_claim(
+        request
+            .extensions()
+            .get::<Option<AuthorizedUser>>()
+            .and_then(Option::as_ref),
+        any_of,
+    )?
+    .clone();
+
+    request.extensions_mut().insert(Authorized(user));
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(claims: &[&str]) -> AuthorizedUser {
+        AuthorizedUser {
+            id: 1,
+            username: "alice".to_string(),
+            password_hash: String::new(),
+            claims: claims.iter().map(|claim| claim.to_string()).collect(),
+            created_at: chrono::Utc::now(),
+            tenant_id: crate::types::DEFAULT_TENANT,
+            api_key_id: None,
+        }
+    }
+
+    #[test]
+    fn test_claim_names_unique() {
+        for (i, claim) in Claim::ALL.iter().enumerate() {
+            assert!(
+                Claim::ALL[..i]
+                    .iter()
+                    .all(|other| other.as_str() != claim.as_str()),
+                "{:?}",
+                claim
+            );
+        }
+    }
+
+    #[test]
+    fn test_require_claim() {
+        let editor = user(&["dispatches.edit", "dispatches.edit.anything"]);
+
+        assert!(require_claim(Some(&editor), &[Claim::DispatchesEdit]).is_ok());
+        assert!(matches!(
+            require_claim(Some(&editor), &[Claim::DispatchesEditAny]),
+            Err(Error::Unauthorized)
+        ));
+        assert!(
+            require_claim(
+                Some(&editor),
+                &[Claim::DispatchesCreate, Claim::DispatchesEdit]
+            )
+            .is_ok()
+        );
+        assert!(require_claim(Some(&user(&[])), &[]).is_ok());
+        assert!(matches!(
+            require_claim(None, &[Claim::Admin]),
+            Err(Error::NoCredentials)
+        ));
+    }
+}
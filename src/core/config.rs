@@ -1,17 +1,773 @@
 use serde::Deserialize;
+use sqlx::{Connection, PgConnection};
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
 
+use crate::core::error::ConfigError as Error;
+use crate::core::logging::LogFormat;
+use crate::sync::nations;
+use crate::workers::telegram::ClientKeys;
+
+/// The app's configuration, read from `EUROCORE_`-prefixed environment variables.
 #[derive(Debug, Deserialize, Clone)]
-pub(crate) struct Args {
+pub struct Args {
     pub(crate) user: String,
     pub(crate) database_host: String,
     pub(crate) database_port: u16,
     pub(crate) database_name: String,
     pub(crate) database_user: String,
     pub(crate) database_password: String,
+    /// host of a read replica for archive reads such as listings, search and stats, which run on
+    /// the primary when unset; the replica shares the primary's name and credentials
+    pub(crate) database_read_host: Option<String>,
+    /// port of the read replica, the primary's when unset
+    pub(crate) database_read_port: Option<u16>,
+    /// connections the pool opens at most, shared by the handlers and the workers
+    #[serde(default = "default_database_max_connections")]
+    pub(crate) database_max_connections: u32,
+    /// seconds a query waits for a free connection before failing
+    #[serde(default = "default_database_acquire_timeout_secs")]
+    pub(crate) database_acquire_timeout_secs: u64,
     pub(crate) log_level: String,
+    /// `text`, or `json` for log aggregators
+    #[serde(default)]
+    pub(crate) log_format: LogFormat,
+    /// address or hostname the server listens on, 0.0.0.0 for every interface
+    #[serde(default = "default_bind_address")]
+    pub(crate) bind_address: String,
+    /// 0 picks a free port; not needed with `unix_socket_path`
+    #[serde(default)]
     pub(crate) port: u16,
+    /// path of a Unix domain socket to serve on instead of `bind_address` and `port`, for a
+    /// reverse proxy on the same host; a stale socket left at the path is replaced
+    pub(crate) unix_socket_path: Option<PathBuf>,
+    /// permissions of the socket, in octal
+    #[serde(default = "default_unix_socket_mode")]
+    pub(crate) unix_socket_mode: String,
     pub(crate) dispatch_nations: String,
+    /// dispatch jobs posted at once, each for a different nation
+    #[serde(default = "default_dispatch_concurrency")]
+    pub(crate) dispatch_concurrency: usize,
+    /// refuse edits of protected dispatches as well as their deletion
+    #[serde(default)]
+    pub(crate) protect_dispatch_edits: bool,
+    /// share of NS's dispatch length limit a revision may use before its job warns
+    #[serde(default = "default_dispatch_size_warn_utilization")]
+    pub(crate) dispatch_size_warn_utilization: f64,
+    /// refuse new dispatches titled like an active dispatch of the same nation, ignoring case,
+    /// unless the request sets `allow_duplicate_title`
+    #[serde(default = "default_unique_dispatch_titles")]
+    pub(crate) unique_dispatch_titles: bool,
     pub(crate) rmbpost_nations: String,
+    /// check that the target region exists before queueing an RMB post
+    #[serde(default)]
+    pub(crate) rmbpost_verify_region: bool,
+    /// check that the nation may post on the target region's RMB before queueing an RMB post
+    #[serde(default)]
+    pub(crate) rmbpost_preflight: bool,
     pub(crate) secret: String,
-    pub(crate) telegram_client_key: String,
+    pub(crate) telegram_client_key: Option<String>,
+    /// comma-separated `nation:key` pairs
+    pub(crate) telegram_client_keys: Option<String>,
+    /// commands the telegram worker buffers before queueing requests wait for it
+    #[serde(default = "default_telegram_channel_capacity")]
+    pub(crate) telegram_channel_capacity: usize,
+    /// URL or path of NS's nations dump, indexed for the telegram recipient filters
+    pub(crate) nations_dump: Option<String>,
+    #[serde(default = "default_nations_dump_refresh_hours")]
+    pub(crate) nations_dump_refresh_hours: u64,
+    #[serde(default = "default_jwt_ttl_hours")]
+    pub(crate) jwt_ttl_hours: i64,
+    /// bcrypt cost for password hashes, lower it only for test environments
+    #[serde(default = "default_bcrypt_cost")]
+    pub(crate) bcrypt_cost: u32,
+    /// failed logins allowed per username and per client IP within the window
+    #[serde(default = "default_login_max_attempts")]
+    pub(crate) login_max_attempts: usize,
+    #[serde(default = "default_login_window_secs")]
+    pub(crate) login_window_secs: u64,
+    #[serde(default = "default_queue_quota_pending")]
+    pub(crate) queue_quota_pending: i64,
+    pub(crate) queue_quota_daily: Option<i64>,
+    /// gzip or brotli responses for clients that accept it
+    #[serde(default = "default_compression")]
+    pub(crate) compression: bool,
+    /// let anyone read the default tenant's dispatches without credentials, turn it off for
+    /// private deployments
+    #[serde(default = "default_public_read")]
+    pub(crate) public_read: bool,
+    /// requests per window a client may read dispatches with without credentials
+    #[serde(default = "default_public_read_max_requests")]
+    pub(crate) public_read_max_requests: u32,
+    #[serde(default = "default_public_read_window_secs")]
+    pub(crate) public_read_window_secs: u64,
+    /// serve `/openapi.json` and Swagger UI at `/docs`
+    #[serde(default)]
+    pub(crate) api_docs: bool,
+    /// NS API endpoint, override to point the workers at a test shard or a mock server
+    #[serde(default = "default_ns_api_url")]
+    pub(crate) ns_api_url: String,
+    /// how NS moderation can reach the operators, e.g. an email address; added to the user agent
+    pub(crate) contact: Option<String>,
+    /// days finished dispatch jobs are kept, 0 keeps them forever
+    #[serde(default)]
+    pub(crate) retention_dispatch_queue_days: u32,
+    /// days finished RMB post jobs are kept, 0 keeps them forever
+    #[serde(default)]
+    pub(crate) retention_rmbpost_queue_days: u32,
+    /// days sent, failed and skipped telegrams are kept, 0 keeps them forever
+    #[serde(default)]
+    pub(crate) retention_telegram_queue_days: u32,
+    /// revisions kept per dispatch, 0 keeps all of them
+    #[serde(default)]
+    pub(crate) retention_dispatch_revisions: u32,
+    /// only report what the retention sweeper would prune
+    #[serde(default)]
+    pub(crate) retention_dry_run: bool,
+    /// seconds over which the views and score of every active dispatch are sampled from NS, 0
+    /// turns sampling off
+    #[serde(default)]
+    pub(crate) dispatch_metrics_interval_secs: u64,
+    /// requests allowed per bucket, NS's API rate limit
+    #[serde(default = "default_ratelimit_max_requests")]
+    pub(crate) ratelimit_max_requests: usize,
+    #[serde(default = "default_ratelimit_bucket_length_secs")]
+    pub(crate) ratelimit_bucket_length_secs: u64,
+    #[serde(default = "default_ratelimit_telegram_cooldown_secs")]
+    pub(crate) ratelimit_telegram_cooldown_secs: u64,
+    #[serde(default = "default_ratelimit_recruitment_cooldown_secs")]
+    pub(crate) ratelimit_recruitment_cooldown_secs: u64,
+    /// cooldown between restricted actions (creating dispatches, posting to the RMB) of a single
+    /// nation, for the actions without a cooldown of their own
+    #[serde(default = "default_ratelimit_restricted_cooldown_secs")]
+    pub(crate) ratelimit_restricted_cooldown_secs: u64,
+    /// share of NS's request cap in use, as NS reports it, at which a warning is logged
+    #[serde(default = "default_ratelimit_warn_utilization")]
+    pub(crate) ratelimit_warn_utilization: f64,
+    /// cooldown between dispatches created by a single nation
+    pub(crate) ratelimit_dispatch_cooldown_secs: Option<u64>,
+    /// cooldown between RMB posts of a single nation
+    pub(crate) ratelimit_rmbpost_cooldown_secs: Option<u64>,
+    /// hours a dispatch or RMB post job may have waited and still be posted after a restart,
+    /// older ones are cancelled; 0 posts them however old they are
+    #[serde(default = "default_job_recovery_max_age_hours")]
+    pub(crate) job_recovery_max_age_hours: u32,
+    /// commands the dispatch and RMB post workers buffer before queueing requests wait for them
+    #[serde(default = "default_worker_channel_capacity")]
+    pub(crate) worker_channel_capacity: usize,
+    /// how long a request waits for room in a full worker channel before it's answered with a
+    /// 503; a job it queued is deferred rather than lost
+    #[serde(default = "default_worker_send_timeout_ms")]
+    pub(crate) worker_send_timeout_ms: u64,
+}
+
+/// Settings without a default, so that all of the missing ones are reported at once rather than
+/// the first the deserializer trips over.
+const REQUIRED: &[&str] = &[
+    "user",
+    "database_host",
+    "database_port",
+    "database_name",
+    "database_user",
+    "database_password",
+    "log_level",
+    "port",
+    "dispatch_nations",
+    "rmbpost_nations",
+    "secret",
+];
+
+/// Bytes the JWT signing secret needs at least.
+const MIN_SECRET_LENGTH: usize = 32;
+
+impl Args {
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_source(
+            ::config::Config::builder()
+                .add_source(::config::Environment::with_prefix("EUROCORE"))
+                .build()?,
+        )
+    }
+
+    fn from_source(source: ::config::Config) -> Result<Self, Error> {
+        let mut report = Report::default();
+
+        let unix_socket = source.get::<::config::Value>("unix_socket_path").is_ok();
+
+        for key in REQUIRED {
+            // the socket takes the port's place
+            if *key == "port" && unix_socket {
+                continue;
+            }
+
+            if source.get::<::config::Value>(key).is_err() {
+                report.problem(format!("{} is not set", variable(key)));
+            }
+        }
+
+        if report.has_problems() {
+            return Err(Error::Invalid(report));
+        }
+
+        source.try_deserialize().map_err(|e| {
+            report.problem(e.to_string());
+
+            Error::Invalid(report)
+        })
+    }
+
+    /// Checks what deserializing can't, and that the databases can be connected to. Run before
+    /// the app starts, so a misconfigured deployment fails with every problem it has at once.
+    pub async fn validate(&self) -> Report {
+        let mut report = self.check();
+
+        let mut databases = vec![(self.database_host.as_str(), self.database_port)];
+
+        if let Some(host) = &self.database_read_host {
+            databases.push((host, self.database_read_port.unwrap_or(self.database_port)));
+        }
+
+        for (host, port) in databases {
+            // a port of 0 is already reported
+            if port == 0 {
+                continue;
+            }
+
+            if let Err(e) = self.check_database(host, port).await {
+                report.problem(format!(
+                    "can't connect to the database at {host}:{port}: {e}"
+                ));
+            }
+        }
+
+        if self.unix_socket_path.is_none()
+            && let Err(e) = tokio::net::lookup_host((self.bind_address.as_str(), self.port)).await
+        {
+            report.problem(format!(
+                "{} `{}` doesn't resolve: {}",
+                variable("bind_address"),
+                self.bind_address,
+                e
+            ));
+        }
+
+        report
+    }
+
+    /// Every check but the database connections.
+    fn check(&self) -> Report {
+        let mut report = Report::default();
+
+        for (key, value) in [
+            ("dispatch_nations", &self.dispatch_nations),
+            ("rmbpost_nations", &self.rmbpost_nations),
+        ] {
+            match nations::names(value) {
+                Ok(names) if names.is_empty() => report.problem(format!(
+                    "{} has no nations, it takes `nation:password` pairs separated by commas",
+                    variable(key)
+                )),
+                Ok(_) => {}
+                Err(Error::Nations(message)) => {
+                    report.problem(format!("{}: {}", variable(key), message))
+                }
+                Err(e) => report.problem(format!("{}: {}", variable(key), e)),
+            }
+        }
+
+        if self.secret.len() < MIN_SECRET_LENGTH {
+            report.problem(format!(
+                "{} is {} bytes long, it has to be at least {}",
+                variable("secret"),
+                self.secret.len(),
+                MIN_SECRET_LENGTH
+            ));
+        }
+
+        if self.database_port == 0 {
+            report.problem(format!("{} can't be 0", variable("database_port")));
+        }
+
+        if self.database_read_port == Some(0) {
+            report.problem(format!("{} can't be 0", variable("database_read_port")));
+        }
+
+        match &self.unix_socket_path {
+            Some(path) if cfg!(not(unix)) => report.problem(format!(
+                "{} is {}, but Unix sockets aren't supported on this platform",
+                variable("unix_socket_path"),
+                path.display()
+            )),
+            Some(path) => {
+                if let Err(e) = self.unix_socket_mode() {
+                    report.problem(format!("{}: {}", variable("unix_socket_mode"), e));
+                }
+
+                if let Some(directory) = path
+                    .parent()
+                    .filter(|directory| !directory.as_os_str().is_empty() && !directory.is_dir())
+                {
+                    report.problem(format!(
+                        "{} is {}, but {} isn't a directory",
+                        variable("unix_socket_path"),
+                        path.display(),
+                        directory.display()
+                    ));
+                }
+            }
+            None => {}
+        }
+
+        if self.unix_socket_path.is_none() && (1..1024).contains(&self.port) {
+            report.warning(format!(
+                "{} is {}, binding to it needs root or CAP_NET_BIND_SERVICE",
+                variable("port"),
+                self.port
+            ));
+        }
+
+        if let Err(e) = EnvFilter::try_new(&self.log_level) {
+            report.problem(format!("{}: {}", variable("log_level"), e));
+        }
+
+        if let Err(e) = ClientKeys::new(
+            self.telegram_client_key.clone(),
+            self.telegram_client_keys.as_deref(),
+        ) {
+            report.problem(e.to_string());
+        }
+
+        report
+    }
+
+    async fn check_database(&self, host: &str, port: u16) -> Result<(), String> {
+        let connect = PgConnection::connect(&self.database_url(host, port));
+
+        let connection = tokio::time::timeout(
+            Duration::from_secs(self.database_acquire_timeout_secs),
+            connect,
+        )
+        .await
+        .map_err(|_| "timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+
+        connection.close().await.map_err(|e| e.to_string())
+    }
+
+    /// `unix_socket_mode` as permission bits.
+    pub(crate) fn unix_socket_mode(&self) -> Result<u32, String> {
+        u32::from_str_radix(&self.unix_socket_mode, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o777)
+            .ok_or_else(|| {
+                format!(
+                    "`{}` isn't an octal file mode like 660",
+                    self.unix_socket_mode
+                )
+            })
+    }
+
+    /// URL of the database in the configuration, served at `host` and `port`.
+    pub(crate) fn database_url(&self, host: &str, port: u16) -> String {
+        format!(
+            "postgresql://{}:{}@{}:{}/{}",
+            self.database_user, self.database_password, host, port, self.database_name
+        )
+    }
+}
+
+/// The environment variable `key` is read from.
+fn variable(key: &str) -> String {
+    format!("EUROCORE_{}", key.to_uppercase())
+}
+
+/// Everything wrong with a configuration. Problems keep the app from starting, warnings are
+/// logged once it has.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    problems: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Report {
+    fn problem(&mut self, problem: String) {
+        self.problems.push(problem);
+    }
+
+    fn warning(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    pub(crate) fn has_problems(&self) -> bool {
+        !self.problems.is_empty()
+    }
+
+    pub(crate) fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn count(n: usize, noun: &str) -> String {
+            match n {
+                1 => format!("1 {noun}"),
+                n => format!("{n} {noun}s"),
+            }
+        }
+
+        writeln!(
+            f,
+            "the configuration has {}:",
+            count(self.problems.len(), "problem")
+        )?;
+
+        for problem in &self.problems {
+            writeln!(f, "  - {problem}")?;
+        }
+
+        if !self.warnings.is_empty() {
+            writeln!(f, "and {}:", count(self.warnings.len(), "warning"))?;
+
+            for warning in &self.warnings {
+                writeln!(f, "  - {warning}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn default_database_max_connections() -> u32 {
+    5
+}
+
+fn default_database_acquire_timeout_secs() -> u64 {
+    30
+}
+
+fn default_dispatch_concurrency() -> usize {
+    3
+}
+
+fn default_dispatch_size_warn_utilization() -> f64 {
+    0.9
+}
+
+fn default_unique_dispatch_titles() -> bool {
+    true
+}
+
+fn default_bind_address() -> String {
+    Ipv4Addr::UNSPECIFIED.to_string()
+}
+
+fn default_unix_socket_mode() -> String {
+    "660".to_string()
+}
+
+fn default_ratelimit_max_requests() -> usize {
+    50
+}
+
+fn default_ratelimit_bucket_length_secs() -> u64 {
+    30
+}
+
+fn default_ratelimit_telegram_cooldown_secs() -> u64 {
+    30
+}
+
+fn default_ratelimit_recruitment_cooldown_secs() -> u64 {
+    180
+}
+
+fn default_ratelimit_restricted_cooldown_secs() -> u64 {
+    60
+}
+
+fn default_ratelimit_warn_utilization() -> f64 {
+    0.8
+}
+
+fn default_job_recovery_max_age_hours() -> u32 {
+    24
+}
+
+fn default_worker_channel_capacity() -> usize {
+    16
+}
+
+fn default_worker_send_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_jwt_ttl_hours() -> i64 {
+    24
+}
+
+fn default_bcrypt_cost() -> u32 {
+    12
+}
+
+fn default_login_max_attempts() -> usize {
+    5
+}
+
+fn default_login_window_secs() -> u64 {
+    15 * 60
+}
+
+fn default_queue_quota_pending() -> i64 {
+    20
+}
+
+fn default_compression() -> bool {
+    true
+}
+
+fn default_telegram_channel_capacity() -> usize {
+    64
+}
+
+fn default_nations_dump_refresh_hours() -> u64 {
+    24
+}
+
+fn default_public_read() -> bool {
+    true
+}
+
+fn default_public_read_max_requests() -> u32 {
+    60
+}
+
+fn default_public_read_window_secs() -> u64 {
+    60
+}
+
+fn default_ns_api_url() -> String {
+    "https://www.nationstates.net/cgi-bin/api.cgi".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(settings: &[(&str, &str)]) -> ::config::Config {
+        settings
+            .iter()
+            .fold(::config::Config::builder(), |builder, (key, value)| {
+                builder.set_override(*key, *value).unwrap()
+            })
+            .build()
+            .unwrap()
+    }
+
+    fn args(overrides: &[(&str, &str)]) -> Args {
+        let mut settings = vec![
+            ("user", "Testlandia"),
+            ("database_host", "localhost"),
+            ("database_port", "5432"),
+            ("database_name", "eurocore"),
+            ("database_user", "eurocore"),
+            ("database_password", "password"),
+            ("log_level", "info"),
+            ("port", "8080"),
+            ("dispatch_nations", "testlandia:password"),
+            ("rmbpost_nations", "testlandia:password"),
+            ("secret", "0123456789abcdef0123456789abcdef"),
+            ("telegram_client_key", "key"),
+        ];
+        settings.extend_from_slice(overrides);
+
+        Args::from_source(source(&settings)).unwrap()
+    }
+
+    fn problems(overrides: &[(&str, &str)]) -> Vec<String> {
+        args(overrides).check().problems
+    }
+
+    #[test]
+    fn test_missing_settings() {
+        match Args::from_source(source(&[("user", "Testlandia"), ("port", "8080")])) {
+            Err(Error::Invalid(report)) => {
+                assert_eq!(report.problems.len(), REQUIRED.len() - 2);
+                assert!(
+                    report
+                        .problems
+                        .contains(&"EUROCORE_SECRET is not set".to_string())
+                );
+            }
+            other => panic!("expected a report, got {other:?}"),
+        }
+
+        match Args::from_source(source(&[("port", "eighty")])) {
+            Err(Error::Invalid(report)) => assert!(!report.problems.is_empty()),
+            other => panic!("expected a report, got {other:?}"),
+        }
+
+        assert!(args(&[]).check().problems.is_empty());
+    }
+
+    #[test]
+    fn test_nations() {
+        assert_eq!(
+            problems(&[("dispatch_nations", "")]),
+            vec![
+                "EUROCORE_DISPATCH_NATIONS has no nations, it takes `nation:password` pairs \
+                 separated by commas"
+            ]
+        );
+        assert_eq!(
+            problems(&[("rmbpost_nations", "a:b,A:c")]),
+            vec!["EUROCORE_RMBPOST_NATIONS: entry 2 repeats a, already entry 1"]
+        );
+    }
+
+    #[test]
+    fn test_secret() {
+        assert_eq!(
+            problems(&[("secret", "short")]),
+            vec!["EUROCORE_SECRET is 5 bytes long, it has to be at least 32"]
+        );
+    }
+
+    #[test]
+    fn test_ports() {
+        assert_eq!(
+            problems(&[("database_port", "0"), ("database_read_port", "0")]),
+            vec![
+                "EUROCORE_DATABASE_PORT can't be 0",
+                "EUROCORE_DATABASE_READ_PORT can't be 0"
+            ]
+        );
+
+        let report = args(&[("port", "80")]).check();
+        assert!(report.problems.is_empty());
+        assert_eq!(
+            report.warnings,
+            vec!["EUROCORE_PORT is 80, binding to it needs root or CAP_NET_BIND_SERVICE"]
+        );
+
+        assert!(args(&[("port", "0")]).check().warnings.is_empty());
+
+        // the socket replaces the port
+        assert!(
+            args(&[("port", "80"), ("unix_socket_path", "/tmp/eurocore.sock")])
+                .check()
+                .warnings
+                .is_empty()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket() {
+        let settings = [
+            ("user", "Testlandia"),
+            ("database_host", "localhost"),
+            ("database_port", "5432"),
+            ("database_name", "eurocore"),
+            ("database_user", "eurocore"),
+            ("database_password", "password"),
+            ("log_level", "info"),
+            ("dispatch_nations", "testlandia:password"),
+            ("rmbpost_nations", "testlandia:password"),
+            ("secret", "0123456789abcdef0123456789abcdef"),
+            ("unix_socket_path", "/tmp/eurocore.sock"),
+        ];
+
+        let args = Args::from_source(source(&settings)).unwrap();
+        assert_eq!(args.unix_socket_mode(), Ok(0o660));
+
+        assert_eq!(
+            problems(&[
+                ("unix_socket_path", "/nonexistent/eurocore.sock"),
+                ("unix_socket_mode", "rw-rw----"),
+            ]),
+            vec![
+                "EUROCORE_UNIX_SOCKET_MODE: `rw-rw----` isn't an octal file mode like 660",
+                "EUROCORE_UNIX_SOCKET_PATH is /nonexistent/eurocore.sock, but /nonexistent \
+                 isn't a directory",
+            ]
+        );
+
+        assert!(problems(&[("unix_socket_mode", "888")]).is_empty());
+    }
+
+    #[test]
+    fn test_log_level() {
+        let problems = problems(&[("log_level", "eurocore=loud")]);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("EUROCORE_LOG_LEVEL: "));
+    }
+
+    #[test]
+    fn test_client_keys() {
+        let mut args = args(&[]);
+        args.telegram_client_key = None;
+
+        assert_eq!(
+            args.check().problems,
+            vec!["telegram client key error: no telegram client keys configured"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_database_connection() {
+        let mut args = args(&[("database_host", "127.0.0.1"), ("database_port", "1")]);
+        args.database_acquire_timeout_secs = 5;
+
+        let report = args.validate().await;
+        assert_eq!(report.problems.len(), 1, "{report}");
+        assert!(
+            report.problems[0].starts_with("can't connect to the database at 127.0.0.1:1: "),
+            "{report}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bind_address() {
+        let mut args = args(&[("bind_address", "localhost")]);
+        args.database_acquire_timeout_secs = 5;
+
+        assert!(
+            !args
+                .validate()
+                .await
+                .problems
+                .iter()
+                .any(|problem| problem.starts_with("EUROCORE_BIND_ADDRESS"))
+        );
+
+        args.bind_address = "nonexistent.invalid".to_string();
+
+        assert!(args.validate().await.problems.iter().any(|problem| {
+            problem.starts_with("EUROCORE_BIND_ADDRESS `nonexistent.invalid` doesn't resolve: ")
+        }));
+    }
+
+    #[test]
+    fn test_report_format() {
+        let report = args(&[
+            ("secret", "short"),
+            ("dispatch_nations", "testlandia:a,hunter2"),
+            ("port", "443"),
+        ])
+        .check();
+
+        assert_eq!(
+            Error::Invalid(report).to_string(),
+            "the configuration has 2 problems:
+  - EUROCORE_DISPATCH_NATIONS: entry 2 has no `:` between the nation and its password
+  - EUROCORE_SECRET is 5 bytes long, it has to be at least 32
+and 1 warning:
+  - EUROCORE_PORT is 443, binding to it needs root or CAP_NET_BIND_SERVICE
+"
+        );
+    }
 }
@@ -0,0 +1,37 @@
+//! The database pools. Archive reads far outnumber writes, so they may be sent to a read
+//! replica, configured with `database_read_host`; without one every query runs on the primary.
+
+use sqlx::PgPool;
+
+/// Where a query runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Pool {
+    /// writes, and reads that have to see them, e.g. a job's status as an idempotent request
+    /// is replayed
+    Primary,
+    /// reads that can lag behind the primary by as much as the replica does
+    Read,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct DbPools {
+    primary: PgPool,
+    read: PgPool,
+}
+
+impl DbPools {
+    /// `read` falls back to `primary` when no replica is configured.
+    pub(crate) fn new(primary: PgPool, read: Option<PgPool>) -> Self {
+        Self {
+            read: read.unwrap_or_else(|| primary.clone()),
+            primary,
+        }
+    }
+
+    pub(crate) fn get(&self, pool: Pool) -> &PgPool {
+        match pool {
+            Pool::Primary => &self.primary,
+            Pool::Read => &self.read,
+        }
+    }
+}
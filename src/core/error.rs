@@ -1,9 +1,22 @@
-use axum::BoxError;
-use axum::http::StatusCode;
+use crate::controllers::throttle::Lockout;
+use crate::core::config::Report;
+use crate::ns::error::NsError;
+use crate::ns::region::RmbRefusal;
+use crate::ns::telegram::TgType;
+use crate::types::job::JobStatus;
+use crate::types::response::{
+    DeletedDispatch, EditConflict, ErrorBody, InvalidBody, PendingJobs, ProtectedDispatch,
+    QuotaExceeded,
+};
+use crate::workers;
 use axum::http::header::InvalidHeaderName;
+use axum::http::{StatusCode, header};
 use axum::response::{IntoResponse, Response};
+use axum::{BoxError, Json};
+use serde_json::json;
 use std::env;
 use std::num::ParseIntError;
+use std::time::Duration;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
@@ -23,6 +36,12 @@ pub enum ConfigError {
     Env(#[from] env::VarError),
     #[error("parse nations error: {0}")]
     Nations(String),
+    #[error("telegram client key error: {0}")]
+    ClientKeys(String),
+    #[error("listener error: {0}")]
+    Listener(String),
+    #[error("{0}")]
+    Invalid(Report),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -37,34 +56,86 @@ pub enum Error {
     Deserialize(#[from] quick_xml::DeError),
     #[error("Invalid factbook category")]
     InvalidFactbookCategory,
+    #[error("Dispatch has no category and its nation no preset for one")]
+    MissingDispatchCategory,
+    #[error("Nation has no preset")]
+    PresetNotFound,
+    #[error("Content breaks the rules {0:?}")]
+    ContentPolicyViolation(Vec<String>),
+    #[error("Invalid content rule: {0}")]
+    InvalidContentRule(String),
+    #[error("A content rule named {0} already exists")]
+    ContentRuleExists(String),
+    #[error("Content rule not found")]
+    ContentRuleNotFound,
+    #[error("A tenant named {0} already exists")]
+    TenantExists(String),
+    #[error("Tenant not found")]
+    TenantNotFound,
+    #[error("Invalid tenant: {0}")]
+    InvalidTenant(String),
+    #[error("Nation belongs to another tenant")]
+    NationNotInTenant(String),
     #[error("Parse int error: {0}")]
     ParseInt(#[from] ParseIntError),
     #[error("SQL error: {0}")]
     Sql(#[from] sqlx::Error),
     #[error("NS error: {0}")]
-    NationStates(String),
+    NationStates(NsError),
     #[error("Dispatch not found")]
     DispatchNotFound,
+    #[error("Dispatch has been deleted")]
+    DispatchInactive,
+    #[error("Dispatch has been deleted: {0:?}")]
+    DispatchDeleted(Box<DeletedDispatch>),
+    #[error("Dispatch is protected: {0:?}")]
+    DispatchProtected(Box<ProtectedDispatch>),
+    #[error("Dispatch {0} is already tracked")]
+    DispatchAlreadyTracked(i32),
+    #[error("Nation already has active dispatch {0} with this title")]
+    DuplicateDispatchTitle(i32),
+    #[error("Dispatch was written by {0}")]
+    DispatchAuthorMismatch(String),
     #[error("JWT error: {0}")]
     Jwt(#[from] jsonwebtoken::errors::Error),
     #[error("No credentials provided")]
     NoCredentials,
     #[error("Expired JWT")]
     ExpiredJWT,
+    #[error("Malformed authorization header: {0}")]
+    MalformedAuthHeader(&'static str),
     #[error("Unauthorized")]
     Unauthorized,
     #[error("User already exists")]
     UserAlreadyExists,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Account has been disabled")]
+    AccountDisabled,
     #[error("Bcrypt error: {0}")]
     Bcrypt(#[from] bcrypt::BcryptError),
     #[error("Serialization error: {0}")]
     Serialize(#[from] serde_json::Error),
     #[error("Invalid nation")]
     InvalidNation,
+    #[error("Invalid region name")]
+    InvalidRegion,
+    #[error("Region does not exist")]
+    RegionNotFound,
+    #[error("{}", .0.message())]
+    RmbPostRefused(RmbRefusal),
     #[error("Internal server error")]
     Internal,
     #[error("Job not found")]
     JobNotFound,
+    #[error("Group not found")]
+    GroupNotFound,
+    #[error("A dispatch group needs 1 to {0} nations")]
+    InvalidDispatchGroup(usize),
+    #[error("An RMB post batch needs 1 to {0} regions")]
+    InvalidRmbPostBatch(usize),
+    #[error("Invalid idempotency key")]
+    InvalidIdempotencyKey,
     #[error("Invalid header value: {0}")]
     Header(#[from] axum::http::header::InvalidHeaderValue),
     #[error("Invalid username")]
@@ -73,47 +144,618 @@ pub enum Error {
     InvalidPassword(String),
     #[error("Invalid header name: {0}")]
     InvalidHeaderName(#[from] InvalidHeaderName),
+    #[error("Dispatch is owned by {0}")]
+    NotDispatchOwner(String),
+    #[error("Telegram was queued by {0}")]
+    NotTelegramOwner(String),
+    #[error("Unknown telegram sender, configured senders: {0:?}")]
+    UnknownTelegramSender(Vec<String>),
+    #[error("NS treats telegram {0} as a {1} telegram")]
+    TelegramTypeMismatch(String, TgType),
+    #[error("Campaign not found")]
+    CampaignNotFound,
+    #[error("Campaign has no valid recipients")]
+    NoRecipients,
+    #[error("No nations in {0} match the recipient filter")]
+    EmptyRecipientSource(String),
+    #[error("Campaign has already completed")]
+    CampaignCompleted,
+    #[error("The nations dump the recipient filters need hasn't been indexed")]
+    NationsDumpUnavailable,
+    #[error("NS rejected the password of {0}")]
+    CredentialUnhealthy(String),
+    #[error("Invalid API key")]
+    InvalidApiKey,
+    #[error("API key has been revoked")]
+    ApiKeyRevoked,
+    #[error("API key has expired")]
+    ApiKeyExpired,
+    #[error("API key not found")]
+    ApiKeyNotFound,
+    #[error("Invalid API key name")]
+    InvalidApiKeyName,
+    #[error("API key expiry is in the past")]
+    InvalidApiKeyExpiry,
+    #[error("Cannot grant claim {0}")]
+    ClaimNotGranted(String),
+    #[error("Invalid request body: {0:?}")]
+    InvalidBody(Box<InvalidBody>),
+    #[error("Expected a JSON request body")]
+    UnsupportedMediaType,
+    #[error("Request body too large")]
+    PayloadTooLarge,
+    #[error("Too many failed logins, locked out by {} for {:?}", .0.scope.as_str(), .0.retry_after)]
+    LoginThrottled(Lockout),
+    #[error("Too many anonymous requests, retry after {0:?}")]
+    PublicReadThrottled(Duration),
+    #[error("Job quota exceeded: {0:?}")]
+    QuotaExceeded(QuotaExceeded),
+    #[error("Edit conflict: {0:?}")]
+    EditConflict(Box<EditConflict>),
+    #[error("Conflicting jobs pending: {0:?}")]
+    PendingJobs(Box<PendingJobs>),
+    #[error("Job {0} is a {2} {1} job, only failed edits can be reapplied")]
+    NotReapplicable(i32, String, JobStatus),
+    #[error("Invalid If-Match header")]
+    InvalidIfMatch,
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+    #[error("Invalid query string: {0}")]
+    InvalidQuery(String),
+    #[error("Invalid log level: {0}")]
+    InvalidLogLevel(String),
+    #[error("Logging isn't set up by this process")]
+    LoggingUnavailable,
+    #[error("Invalid rate limit: {0}")]
+    InvalidRatelimit(String),
+    #[error("Webhook not found")]
+    WebhookNotFound,
+    #[error("Webhook URL must be an absolute http or https URL")]
+    InvalidWebhookUrl,
+    #[error("Webhook secret must be at least {0} characters")]
+    InvalidWebhookSecret(usize),
+    #[error("The {0} worker is backed up, deferred jobs {1:?}")]
+    PipelineBackedUp(&'static str, Vec<i32>),
+    #[error("Job was still queued after a restart and too old to post")]
+    JobAbandoned,
+    #[error("Job was interrupted by a restart and may or may not have reached NationStates")]
+    JobInterrupted,
+    #[error("NationStates couldn't be asked whether it posted the dispatch")]
+    JobUnverified,
+    #[error("NationStates never posted the dispatch")]
+    JobNotPosted,
+}
+
+impl Error {
+    /// Machine-readable code stored with failed jobs.
+    pub(crate) fn job_code(&self) -> Option<&'static str> {
+        match self {
+            Error::NationStates(error) => Some(error.code()),
+            Error::CredentialUnhealthy(_) => Some("credential_unhealthy"),
+            Error::InvalidRegion => Some("invalid_region"),
+            Error::RegionNotFound => Some("region_not_found"),
+            Error::RmbPostRefused(refusal) => Some(refusal.code()),
+            Error::JobAbandoned => Some("abandoned"),
+            Error::JobInterrupted => Some("interrupted"),
+            Error::JobUnverified => Some("unverified"),
+            Error::JobNotPosted => Some("not_posted"),
+            _ => None,
+        }
+    }
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         tracing::error!("{:?}", self);
 
-        let (status, message) = match self {
-            Error::HTTPClient(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Reqwest error"),
-            Error::URLEncode(_) => (StatusCode::INTERNAL_SERVER_ERROR, "URL encoding error"),
-            Error::HeaderDecode(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Header decode error"),
-            Error::Deserialize(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Deserialization error"),
-            Error::InvalidFactbookCategory => {
-                (StatusCode::BAD_REQUEST, "Invalid factbook category")
+        let (status, body) = match self {
+            Error::HTTPClient(_) => internal("Reqwest error"),
+            Error::URLEncode(_) => internal("URL encoding error"),
+            Error::HeaderDecode(_) => internal("Header decode error"),
+            Error::Deserialize(_) => internal("Deserialization error"),
+            Error::InvalidFactbookCategory => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_factbook_category", "Invalid factbook category"),
+            ),
+            Error::MissingDispatchCategory => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "missing_dispatch_category",
+                    "Category and subcategory are required unless the nation's preset has them",
+                ),
+            ),
+            Error::PresetNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("preset_not_found", "Nation has no preset"),
+            ),
+            Error::ContentPolicyViolation(rules) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorBody::new(
+                    "content_policy_violation",
+                    format!("Content breaks the rules: {}", rules.join(", ")),
+                )
+                .details(&json!({ "rules": rules })),
+            ),
+            Error::InvalidContentRule(message) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_content_rule", message),
+            ),
+            Error::ContentRuleExists(name) => (
+                StatusCode::CONFLICT,
+                ErrorBody::new(
+                    "content_rule_exists",
+                    format!("A content rule named {} already exists", name),
+                ),
+            ),
+            Error::ContentRuleNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("content_rule_not_found", "Content rule not found"),
+            ),
+            Error::TenantExists(name) => (
+                StatusCode::CONFLICT,
+                ErrorBody::new(
+                    "tenant_exists",
+                    format!("A tenant named {} already exists", name),
+                ),
+            ),
+            Error::TenantNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("tenant_not_found", "Tenant not found"),
+            ),
+            Error::InvalidTenant(message) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_tenant", message),
+            ),
+            Error::NationNotInTenant(nation) => (
+                StatusCode::FORBIDDEN,
+                ErrorBody::new(
+                    "nation_not_in_tenant",
+                    format!("{} belongs to another tenant", nation),
+                ),
+            ),
+            Error::ParseInt(_) => internal("Parse int error"),
+            Error::Sql(_) => internal("SQL error"),
+            Error::NationStates(error) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorBody::new("nationstates_error", "NationStates error")
+                    .details(&json!({ "ns_code": error.code() })),
+            ),
+            Error::DispatchNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("dispatch_not_found", "Dispatch not found"),
+            ),
+            Error::DispatchDeleted(deleted) => (
+                StatusCode::GONE,
+                ErrorBody::new("dispatch_deleted", "Dispatch has been deleted").details(&deleted),
+            ),
+            Error::DispatchProtected(protected) => (
+                StatusCode::LOCKED,
+                ErrorBody::new(
+                    "dispatch_protected",
+                    match &protected.protected_by {
+                        Some(admin) => format!("Dispatch has been protected by {}", admin),
+                        None => "Dispatch has been protected".to_string(),
+                    },
+                )
+                .details(&protected),
+            ),
+            Error::DispatchAlreadyTracked(id) => (
+                StatusCode::CONFLICT,
+                ErrorBody::new(
+                    "dispatch_already_tracked",
+                    format!("Dispatch {} is already tracked", id),
+                )
+                .details(&json!({ "id": id })),
+            ),
+            Error::DuplicateDispatchTitle(id) => (
+                StatusCode::CONFLICT,
+                ErrorBody::new(
+                    "duplicate_dispatch_title",
+                    format!(
+                        "Nation already has dispatch {} with this title, edit it with PUT /dispatches/{} or set allow_duplicate_title=true",
+                        id, id
+                    ),
+                )
+                .details(&json!({ "id": id })),
+            ),
+            Error::DispatchAuthorMismatch(author) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "dispatch_author_mismatch",
+                    format!("Dispatch was written by {}", author),
+                )
+                .details(&json!({ "author": author })),
+            ),
+            Error::DispatchInactive => (
+                StatusCode::CONFLICT,
+                ErrorBody::new(
+                    "dispatch_inactive",
+                    "Dispatch has been deleted and can no longer be modified",
+                ),
+            ),
+            Error::Jwt(_) => internal("JWT error"),
+            Error::NoCredentials => (
+                StatusCode::UNAUTHORIZED,
+                ErrorBody::new("no_credentials", "No credentials provided"),
+            ),
+            Error::ExpiredJWT => (
+                StatusCode::UNAUTHORIZED,
+                ErrorBody::new("expired_token", "Expired JWT"),
+            ),
+            Error::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                ErrorBody::new("unauthorized", "Unauthorized"),
+            ),
+            Error::UserAlreadyExists => (
+                StatusCode::CONFLICT,
+                ErrorBody::new("user_already_exists", "User already exists"),
+            ),
+            Error::UserNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("user_not_found", "User not found"),
+            ),
+            Error::AccountDisabled => (
+                StatusCode::UNAUTHORIZED,
+                ErrorBody::new("account_disabled", "Account has been disabled"),
+            ),
+            Error::Bcrypt(_) => internal("Bcrypt error"),
+            Error::Serialize(_) => internal("Serialization error"),
+            Error::InvalidNation => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_nation", "Invalid nation"),
+            ),
+            Error::InvalidRegion => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_region", "Invalid region name"),
+            ),
+            Error::RegionNotFound => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("region_not_found", "Region does not exist"),
+            ),
+            Error::RmbPostRefused(refusal) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorBody::new("rmbpost_refused", refusal.message())
+                    .details(&json!({ "reason": refusal.code() })),
+            ),
+            Error::Internal => internal("Internal server error"),
+            Error::JobNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("job_not_found", "Job not found"),
+            ),
+            Error::GroupNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("group_not_found", "Group not found"),
+            ),
+            Error::InvalidDispatchGroup(max) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "invalid_dispatch_group",
+                    format!("A dispatch group needs 1 to {} nations", max),
+                ),
+            ),
+            Error::InvalidRmbPostBatch(max) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "invalid_rmbpost_batch",
+                    format!("An RMB post batch needs 1 to {} regions", max),
+                ),
+            ),
+            Error::CampaignNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("campaign_not_found", "Campaign not found"),
+            ),
+            Error::InvalidApiKey => (
+                StatusCode::UNAUTHORIZED,
+                ErrorBody::new("invalid_api_key", "Invalid API key"),
+            ),
+            Error::ApiKeyRevoked => (
+                StatusCode::UNAUTHORIZED,
+                ErrorBody::new("api_key_revoked", "API key has been revoked"),
+            ),
+            Error::ApiKeyExpired => (
+                StatusCode::UNAUTHORIZED,
+                ErrorBody::new("api_key_expired", "API key has expired"),
+            ),
+            Error::ApiKeyNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("api_key_not_found", "API key not found"),
+            ),
+            Error::InvalidApiKeyName => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "invalid_api_key_name",
+                    "API key name must be 1 to 100 characters",
+                ),
+            ),
+            Error::InvalidApiKeyExpiry => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_api_key_expiry", "expires_at must be in the future"),
+            ),
+            Error::ClaimNotGranted(claim) => (
+                StatusCode::FORBIDDEN,
+                ErrorBody::new(
+                    "claim_not_granted",
+                    format!("Cannot grant {}, it isn't one of your claims", claim),
+                )
+                .details(&json!({ "claim": claim })),
+            ),
+            Error::NoRecipients => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("no_recipients", "Campaign has no valid recipients"),
+            ),
+            Error::EmptyRecipientSource(region) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "empty_recipient_source",
+                    format!("No nations in {} match the recipient filter", region),
+                )
+                .details(&json!({ "region": region })),
+            ),
+            Error::CampaignCompleted => (
+                StatusCode::CONFLICT,
+                ErrorBody::new("campaign_completed", "Campaign has already completed"),
+            ),
+            Error::NationsDumpUnavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorBody::new(
+                    "nations_dump_unavailable",
+                    "The nations dump the recipient filters need hasn't been indexed",
+                ),
+            ),
+            Error::InvalidIdempotencyKey => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "invalid_idempotency_key",
+                    "Idempotency-Key must be 1 to 255 visible ASCII characters",
+                ),
+            ),
+            Error::Header(_) => internal("Invalid header value"),
+            Error::InvalidUsername => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_username", "Invalid username"),
+            ),
+            Error::InvalidPassword(_) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_password", "Invalid password"),
+            ),
+            Error::InvalidHeaderName(_) => internal("Invalid header name"),
+            Error::NotDispatchOwner(owner) => (
+                StatusCode::FORBIDDEN,
+                ErrorBody::new(
+                    "not_dispatch_owner",
+                    format!("Dispatch is owned by {}", owner),
+                )
+                .details(&json!({ "owner": owner })),
+            ),
+            Error::NotTelegramOwner(owner) => (
+                StatusCode::FORBIDDEN,
+                ErrorBody::new(
+                    "not_telegram_owner",
+                    format!("Telegram was queued by {}", owner),
+                )
+                .details(&json!({ "created_by": owner })),
+            ),
+            Error::CredentialUnhealthy(nation) => (
+                StatusCode::CONFLICT,
+                ErrorBody::new(
+                    "credential_unhealthy",
+                    format!(
+                        "NS rejected the password of {}, update it and revalidate the nation",
+                        nation
+                    ),
+                )
+                .details(&json!({ "nation": nation })),
+            ),
+            Error::UnknownTelegramSender(senders) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "unknown_telegram_sender",
+                    format!(
+                        "Unknown telegram sender, configured senders: {}",
+                        senders.join(", ")
+                    ),
+                )
+                .details(&json!({ "senders": senders })),
+            ),
+            Error::TelegramTypeMismatch(telegram_id, tg_type) => (
+                StatusCode::CONFLICT,
+                ErrorBody::new(
+                    "telegram_type_mismatch",
+                    format!(
+                        "NS treats telegram {} as a {} telegram, queue it as one",
+                        telegram_id, tg_type
+                    ),
+                )
+                .details(&json!({ "telegram_id": telegram_id, "tg_type": tg_type })),
+            ),
+            Error::MalformedAuthHeader(reason) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    [(
+                        header::WWW_AUTHENTICATE,
+                        format!(
+                            "Bearer error=\"invalid_request\", error_description=\"{}\"",
+                            reason
+                        ),
+                    )],
+                    Json(ErrorBody::new("malformed_auth_header", reason)),
+                )
+                    .into_response();
+            }
+            Error::LoginThrottled(lockout) => {
+                // round up, retrying early would only be rejected again
+                let retry_after = lockout.retry_after.as_secs()
+                    + u64::from(lockout.retry_after.subsec_nanos() > 0);
+
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after.to_string())],
+                    Json(
+                        ErrorBody::new(
+                            "login_throttled",
+                            "Too many failed logins, try again later",
+                        )
+                        .details(&json!({ "retry_after_secs": retry_after })),
+                    ),
+                )
+                    .into_response();
+            }
+            Error::PipelineBackedUp(pipeline, deferred_jobs) => {
+                let retry_after = workers::DEFERRED_PERIOD.as_secs().max(1);
+
+                return (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(header::RETRY_AFTER, retry_after.to_string())],
+                    Json(
+                        ErrorBody::new(
+                            "pipeline_backed_up",
+                            format!("The {pipeline} worker is backed up, try again later"),
+                        )
+                        .details(&json!({
+                            "pipeline": pipeline,
+                            "deferred_jobs": deferred_jobs,
+                            "retry_after_secs": retry_after,
+                        })),
+                    ),
+                )
+                    .into_response();
             }
-            Error::ParseInt(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Parse int error"),
-            Error::Sql(_) => (StatusCode::INTERNAL_SERVER_ERROR, "SQL error"),
-            Error::NationStates(_) => (StatusCode::INTERNAL_SERVER_ERROR, "NationStates error"),
-            Error::DispatchNotFound => (StatusCode::NOT_FOUND, "Dispatch not found"),
-            Error::Jwt(_) => (StatusCode::INTERNAL_SERVER_ERROR, "JWT error"),
-            Error::NoCredentials => (StatusCode::UNAUTHORIZED, "No credentials provided"),
-            Error::ExpiredJWT => (StatusCode::UNAUTHORIZED, "Expired JWT"),
-            Error::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
-            Error::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
-            Error::Bcrypt(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Bcrypt error"),
-            Error::Serialize(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Serialization error"),
-            Error::InvalidNation => (StatusCode::BAD_REQUEST, "Invalid nation"),
-            Error::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
-            Error::JobNotFound => (StatusCode::NOT_FOUND, "Job not found"),
-            Error::Header(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Invalid header value"),
-            Error::InvalidUsername => (StatusCode::BAD_REQUEST, "Invalid username"),
-            Error::InvalidPassword(_) => (StatusCode::BAD_REQUEST, "Invalid password"),
-            Error::InvalidHeaderName(_) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Invalid header name")
+            Error::PublicReadThrottled(retry_after) => {
+                let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after.to_string())],
+                    Json(
+                        ErrorBody::new(
+                            "public_read_throttled",
+                            "Too many requests without credentials, try again later or log in",
+                        )
+                        .details(&json!({ "retry_after_secs": retry_after })),
+                    ),
+                )
+                    .into_response();
+            }
+            Error::QuotaExceeded(quota) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                ErrorBody::new("quota_exceeded", quota.message()).details(&quota),
+            ),
+            Error::EditConflict(conflict) => (
+                StatusCode::CONFLICT,
+                ErrorBody::new(
+                    "edit_conflict",
+                    "Dispatch has been edited since the given revision",
+                )
+                .details(&conflict),
+            ),
+            Error::PendingJobs(pending) => (
+                StatusCode::CONFLICT,
+                ErrorBody::new(
+                    "pending_jobs",
+                    "Dispatch has pending jobs this one can't be queued behind",
+                )
+                .details(&pending),
+            ),
+            Error::NotReapplicable(job_id, action, status) => (
+                StatusCode::CONFLICT,
+                ErrorBody::new("not_reapplicable", "Only failed edits can be reapplied").details(
+                    &json!({ "job_id": job_id, "action": action, "status": status }),
+                ),
+            ),
+            Error::InvalidIfMatch => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "invalid_if_match",
+                    "If-Match must be a quoted dispatch revision, e.g. \"42\"",
+                ),
+            ),
+            Error::InvalidBody(body) => {
+                let status = match body.code {
+                    "malformed_json" => StatusCode::BAD_REQUEST,
+                    _ => StatusCode::UNPROCESSABLE_ENTITY,
+                };
+
+                (
+                    status,
+                    ErrorBody::new(body.code, body.message.clone()).details(&body),
+                )
             }
+            Error::UnsupportedMediaType => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                ErrorBody::new(
+                    "unsupported_media_type",
+                    "Expected a request body with Content-Type: application/json",
+                ),
+            ),
+            Error::PayloadTooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                ErrorBody::new("payload_too_large", "Request body too large"),
+            ),
+            Error::InvalidPath(message) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_path", message),
+            ),
+            Error::InvalidQuery(message) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_query", message),
+            ),
+            Error::InvalidLogLevel(message) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_log_level", message),
+            ),
+            Error::LoggingUnavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorBody::new(
+                    "logging_unavailable",
+                    "Logging isn't set up by this process",
+                ),
+            ),
+            Error::InvalidRatelimit(message) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new("invalid_ratelimit", message),
+            ),
+            Error::WebhookNotFound => (
+                StatusCode::NOT_FOUND,
+                ErrorBody::new("webhook_not_found", "Webhook not found"),
+            ),
+            Error::InvalidWebhookUrl => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "invalid_webhook_url",
+                    "Webhook URL must be an absolute http or https URL",
+                ),
+            ),
+            Error::InvalidWebhookSecret(length) => (
+                StatusCode::BAD_REQUEST,
+                ErrorBody::new(
+                    "invalid_webhook_secret",
+                    format!("Webhook secret must be at least {} characters", length),
+                )
+                .details(&json!({ "min_length": length })),
+            ),
+            // only ever stored with jobs
+            Error::JobAbandoned
+            | Error::JobInterrupted
+            | Error::JobUnverified
+            | Error::JobNotPosted => internal("Internal server error"),
         };
 
-        (status, message).into_response()
+        (status, Json(body)).into_response()
     }
 }
 
-pub(crate) async fn handle_middleware_errors(err: BoxError) -> (StatusCode, &'static str) {
+/// 500s don't say more than which part failed, the details are only logged.
+fn internal(message: &'static str) -> (StatusCode, ErrorBody) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorBody::new("internal_error", message),
+    )
+}
+
+pub(crate) async fn handle_middleware_errors(err: BoxError) -> Response {
     tracing::error!("Unhandled error: {:?}", err);
-    (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+
+    let (status, body) = internal("Internal server error");
+
+    (status, Json(body)).into_response()
 }
@@ -0,0 +1,323 @@
+use crate::core::error::Error;
+use crate::types::response::InvalidBody;
+use axum::body::Bytes;
+use axum::extract::{FromRequest, FromRequestParts, Request};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// `axum::Json`, but rejections are our [`Error`]s, so clients are told which field is wrong and
+/// why instead of getting axum's plain-text message.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Json<T>(pub(crate) T);
+
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json(request.headers()) {
+            return Err(Error::UnsupportedMediaType);
+        }
+
+        let bytes = match Bytes::from_request(request, state).await {
+            Ok(bytes) => bytes,
+            Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                return Err(Error::PayloadTooLarge);
+            }
+            Err(rejection) => {
+                tracing::warn!("unable to read request body: {}", rejection.body_text());
+
+                return Err(Error::Internal);
+            }
+        };
+
+        Ok(Json(deserialize(&bytes)?))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// `axum::extract::Path`, rejecting with [`Error::InvalidPath`] instead of plain text.
+#[derive(Debug)]
+pub(crate) struct Path<T>(pub(crate) T);
+
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => Ok(Path(value)),
+            // a route and its handler disagreeing is our bug, not the client's
+            Err(rejection) if rejection.status().is_server_error() => {
+                tracing::error!("unable to extract path: {}", rejection.body_text());
+
+                Err(Error::Internal)
+            }
+            Err(rejection) => Err(Error::InvalidPath(rejection.body_text())),
+        }
+    }
+}
+
+/// `axum::extract::Query`, rejecting with [`Error::InvalidQuery`] instead of plain text.
+#[derive(Debug)]
+pub(crate) struct Query<T>(pub(crate) T);
+
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Query::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Query(value)) => Ok(Query(value)),
+            Err(rejection) => Err(Error::InvalidQuery(rejection.body_text())),
+        }
+    }
+}
+
+fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        invalid_body(Some(path).filter(|path| path != "."), e.into_inner())
+    })?;
+
+    deserializer.end().map_err(|e| invalid_body(None, e))?;
+
+    Ok(value)
+}
+
+/// `application/json` or any `application/*+json`, parameters like `charset` are ignored.
+fn is_json(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    essence == "application/json"
+        || (essence.starts_with("application/") && essence.ends_with("+json"))
+}
+
+fn invalid_body(path: Option<String>, e: serde_json::Error) -> Error {
+    let message = e.to_string();
+    // serde_json appends the position, which is reported separately
+    let message = message
+        .strip_suffix(&format!(" at line {} column {}", e.line(), e.column()))
+        .unwrap_or(&message);
+
+    let (code, expected, received) = describe(message);
+
+    Error::InvalidBody(Box::new(InvalidBody {
+        code: if e.is_syntax() || e.is_eof() {
+            "malformed_json"
+        } else {
+            code
+        },
+        message: message.to_string(),
+        path,
+        expected,
+        received,
+        line: e.line(),
+        column: e.column(),
+    }))
+}
+
+/// Picks apart serde's standard messages, e.g. `invalid type: string "x", expected i16`.
+fn describe(message: &str) -> (&'static str, Option<String>, Option<String>) {
+    if let Some(rest) = message.strip_prefix("invalid type: ") {
+        return match rest.split_once(", expected ") {
+            Some((received, expected)) => (
+                "invalid_type",
+                Some(expected.to_string()),
+                Some(received.to_string()),
+            ),
+            None => ("invalid_type", None, Some(rest.to_string())),
+        };
+    }
+
+    if let Some(rest) = message.strip_prefix("invalid value: ") {
+        return match rest.split_once(", expected ") {
+            Some((received, expected)) => (
+                "invalid_value",
+                Some(expected.to_string()),
+                Some(received.to_string()),
+            ),
+            None => ("invalid_value", None, Some(rest.to_string())),
+        };
+    }
+
+    if let Some(rest) = message.strip_prefix("unknown variant ") {
+        return match rest.split_once(", expected ") {
+            Some((received, expected)) => (
+                "invalid_value",
+                Some(expected.to_string()),
+                Some(received.to_string()),
+            ),
+            None => ("invalid_value", None, Some(rest.to_string())),
+        };
+    }
+
+    if let Some(field) = message.strip_prefix("missing field ") {
+        return ("missing_field", Some(field.to_string()), None);
+    }
+
+    ("invalid_body", None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ns::dispatch::NewDispatch;
+    use crate::types::request::DispatchQuery;
+    use axum::body::{Body, to_bytes};
+    use axum::http::HeaderValue;
+    use serde_json::Value;
+
+    async fn reject(body: &str) -> (StatusCode, Value) {
+        let response = deserialize::<NewDispatch>(body.as_bytes())
+            .expect_err("body was accepted")
+            .into_response();
+
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_missing_field() {
+        let (status, body) =
+            reject(r#"{"nation": "testlandia", "title": "t", "category": 1, "subcategory": 100}"#)
+                .await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "missing_field");
+        assert_eq!(body["details"]["expected"], "`text`");
+        assert_eq!(body["details"]["path"], Value::Null);
+        assert_eq!(body["message"], "missing field `text`");
+    }
+
+    #[tokio::test]
+    async fn test_type_mismatch() {
+        let (status, body) = reject(
+            r#"{"nation": "testlandia", "title": "t", "text": "x", "category": "one", "subcategory": 100}"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "invalid_type");
+        assert_eq!(body["details"]["path"], "category");
+        assert_eq!(body["details"]["expected"], "i16");
+        assert_eq!(body["details"]["received"], "string \"one\"");
+        assert_eq!(body["details"]["line"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_trailing_garbage() {
+        let (status, body) = reject(
+            r#"{"nation": "testlandia", "title": "t", "text": "x", "category": 1, "subcategory": 100} }"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "malformed_json");
+        assert_eq!(body["message"], "trailing characters");
+        assert_eq!(body["details"]["path"], Value::Null);
+        assert!(body["details"]["column"].as_u64().unwrap() > 80);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_query() {
+        let (mut parts, _) = Request::builder()
+            .uri("/dispatches?include_inactive=maybe")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let Err(rejection) = Query::<DispatchQuery>::from_request_parts(&mut parts, &()).await
+        else {
+            panic!("query was accepted");
+        };
+
+        let response = rejection.into_response();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["code"], "invalid_query");
+        assert!(
+            body["message"]
+                .as_str()
+                .unwrap()
+                .contains("include_inactive")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_other_content_types() {
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"))
+            .body(Body::from("{}"))
+            .unwrap();
+
+        let rejection = Json::<Value>::from_request(request, &()).await.unwrap_err();
+
+        assert_eq!(
+            rejection.into_response().status(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        let request = Request::builder()
+            .header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/merge-patch+json; charset=utf-8"),
+            )
+            .body(Body::from("{}"))
+            .unwrap();
+
+        assert!(Json::<Value>::from_request(request, &()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_oversized_bodies() {
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(vec![b' '; 3 * 1024 * 1024]))
+            .unwrap();
+
+        let rejection = Json::<Value>::from_request(request, &()).await.unwrap_err();
+
+        assert_eq!(
+            rejection.into_response().status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! The listener the app is served on: TCP at `bind_address` and `port`, or a Unix domain socket
+//! at `unix_socket_path` for a reverse proxy on the same host, which takes precedence.
+
+use axum::Router;
+use std::fmt;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+use crate::core::config::Args;
+use crate::core::error::ConfigError as Error;
+
+/// Where the app is served.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Tcp(address) => write!(f, "{address}"),
+            #[cfg(unix)]
+            Address::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+pub(crate) enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, SocketFile),
+}
+
+impl Listener {
+    pub(crate) async fn bind(config: &Args) -> Result<Self, Error> {
+        match &config.unix_socket_path {
+            #[cfg(unix)]
+            Some(path) => {
+                let mode = config.unix_socket_mode().map_err(Error::Listener)?;
+
+                bind_unix(path, mode).await
+            }
+            #[cfg(not(unix))]
+            Some(path) => Err(Error::Listener(format!(
+                "can't serve on {}, Unix sockets aren't supported on this platform",
+                path.display()
+            ))),
+            None => Ok(Listener::Tcp(
+                TcpListener::bind((config.bind_address.as_str(), config.port)).await?,
+            )),
+        }
+    }
+
+    pub(crate) fn local_addr(&self) -> Result<Address, Error> {
+        match self {
+            Listener::Tcp(listener) => Ok(Address::Tcp(listener.local_addr()?)),
+            #[cfg(unix)]
+            Listener::Unix(_, socket) => Ok(Address::Unix(socket.0.clone())),
+        }
+    }
+
+    /// Serves `router` until `signal` resolves and open requests are answered. A socket file is
+    /// removed once the server stops, whether or not it stopped cleanly.
+    pub(crate) async fn serve<F>(self, router: Router, signal: F) -> Result<(), Error>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        match self {
+            Listener::Tcp(listener) => {
+                axum::serve(
+                    listener,
+                    router.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(signal)
+                .await?
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener, _socket) => {
+                // peers on a socket have no address; they share loopback's, e.g. for rate limits
+                let peer = axum::extract::ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0)));
+
+                axum::serve(
+                    listener,
+                    router.layer(axum::Extension(peer)).into_make_service(),
+                )
+                .with_graceful_shutdown(signal)
+                .await?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes the socket file when dropped.
+#[cfg(unix)]
+pub(crate) struct SocketFile(PathBuf);
+
+#[cfg(unix)]
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::error!("unable to remove socket {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Binds a socket at `path` with permissions `mode`. A socket already there that nothing answers
+/// on is left from a server that didn't shut down cleanly and is replaced; anything else there is
+/// refused rather than deleted.
+#[cfg(unix)]
+async fn bind_unix(path: &Path, mode: u32) -> Result<Listener, Error> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            if tokio::net::UnixStream::connect(path).await.is_ok() {
+                return Err(Error::Listener(format!(
+                    "{} is in use by another server",
+                    path.display()
+                )));
+            }
+
+            tracing::warn!("removing stale socket {}", path.display());
+            std::fs::remove_file(path)?;
+        }
+        Ok(_) => {
+            return Err(Error::Listener(format!(
+                "{} exists and isn't a socket",
+                path.display()
+            )));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let listener = UnixListener::bind(path)?;
+    let socket = SocketFile(path.to_path_buf());
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+
+    Ok(Listener::Unix(listener, socket))
+}
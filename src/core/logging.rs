@@ -0,0 +1,121 @@
+//! The tracing subscriber: lines of text or JSON on stdout, behind a level filter admins can
+//! change while the app runs, see `PATCH /admin/logging`.
+
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry, fmt, reload};
+
+use crate::core::error::Error;
+use crate::types::response;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// an object per line, with the event's fields at the top level and the spans' fields,
+    /// e.g. a request's `request_id` or a job's `job_id`, under `span` and `spans`
+    Json,
+}
+
+/// The filter of the installed subscriber, none until [`init`] has run.
+static FILTER: OnceLock<Filter> = OnceLock::new();
+
+#[derive(Debug)]
+struct Filter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    format: LogFormat,
+}
+
+impl Filter {
+    fn status(&self) -> Result<response::Logging, Error> {
+        Ok(response::Logging {
+            level: self
+                .handle
+                .with_current(ToString::to_string)
+                .map_err(internal)?,
+            format: self.format,
+        })
+    }
+
+    fn reload(&self, filter: EnvFilter) -> Result<response::Logging, Error> {
+        self.handle.reload(filter).map_err(internal)?;
+
+        self.status()
+    }
+}
+
+fn internal(e: reload::Error) -> Error {
+    tracing::error!("{}", e);
+
+    Error::Internal
+}
+
+/// Installs the subscriber, filtering by `level` until it's changed. Invalid directives log
+/// everything at the default level, ERROR.
+pub(crate) fn init(level: &str, format: LogFormat) {
+    let (filter, handle) = reload::Layer::new(EnvFilter::try_new(level).unwrap_or_default());
+    let registry = tracing_subscriber::registry().with(filter);
+
+    match format {
+        LogFormat::Text => registry.with(fmt::layer()).init(),
+        LogFormat::Json => registry
+            .with(fmt::layer().json().flatten_event(true))
+            .init(),
+    }
+
+    if FILTER.set(Filter { handle, format }).is_err() {
+        tracing::warn!("logging was already initialized");
+    }
+}
+
+/// The directives in effect, as `EnvFilter` prints them, and the format.
+pub(crate) fn status() -> Result<response::Logging, Error> {
+    installed()?.status()
+}
+
+/// Replaces the directives, e.g. `info,eurocore=debug`. They're checked first, so invalid ones
+/// are refused whether or not a subscriber was installed.
+pub(crate) fn set_level(directives: &str) -> Result<response::Logging, Error> {
+    let filter =
+        EnvFilter::try_new(directives).map_err(|e| Error::InvalidLogLevel(e.to_string()))?;
+
+    installed()?.reload(filter)
+}
+
+/// The app runs without a subscriber of its own in tests.
+fn installed() -> Result<&'static Filter, Error> {
+    FILTER.get().ok_or(Error::LoggingUnavailable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload() {
+        let (layer, handle) = reload::Layer::new(EnvFilter::new("info"));
+        // not installed, it only has to outlive the filter's use
+        let _subscriber = tracing_subscriber::registry().with(layer);
+
+        let filter = Filter {
+            handle,
+            format: LogFormat::Json,
+        };
+
+        assert_eq!(filter.status().unwrap().level, "info");
+
+        let status = filter
+            .reload(EnvFilter::new("warn,eurocore=debug"))
+            .unwrap();
+        assert_eq!(status.level, "eurocore=debug,warn");
+        assert_eq!(status.format, LogFormat::Json);
+
+        assert!(matches!(
+            set_level("eurocore=loud"),
+            Err(Error::InvalidLogLevel(_))
+        ));
+    }
+}
@@ -1,3 +1,8 @@
+pub(crate) mod authorization;
 pub(crate) mod config;
+pub(crate) mod db;
 pub mod error;
+pub(crate) mod extract;
+pub(crate) mod listener;
+pub(crate) mod logging;
 pub(crate) mod state;
@@ -1,4 +1,9 @@
-use crate::controllers::{dispatch, rmbpost, telegram, user};
+use crate::controllers::content_policy::ContentPolicy;
+use crate::controllers::{
+    api_key, audit, dispatch, retention, rmbpost, telegram, tenant, user, webhook,
+};
+use crate::sync::{events, ratelimiter};
+use sqlx::PgPool;
 
 #[derive(Clone, Debug)]
 pub(crate) struct AppState {
@@ -6,20 +11,50 @@ pub(crate) struct AppState {
     pub(crate) dispatch_controller: dispatch::Controller,
     pub(crate) rmbpost_controller: rmbpost::Controller,
     pub(crate) telegram_controller: telegram::Controller,
+    pub(crate) audit_controller: audit::Controller,
+    pub(crate) api_key_controller: api_key::Controller,
+    pub(crate) retention_controller: retention::Controller,
+    pub(crate) webhook_controller: webhook::Controller,
+    pub(crate) tenant_controller: tenant::Controller,
+    /// shared with the dispatch and RMB post controllers, which check jobs against it
+    pub(crate) content_policy: ContentPolicy,
+    pub(crate) ratelimiter: ratelimiter::Sender,
+    pub(crate) events: events::Sender,
+    /// only for `/health`, everything else reaches the database through its controller
+    pub(crate) db_pool: PgPool,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         user_controller: user::Controller,
         dispatch_controller: dispatch::Controller,
         rmbpost_controller: rmbpost::Controller,
         telegram_controller: telegram::Controller,
+        audit_controller: audit::Controller,
+        api_key_controller: api_key::Controller,
+        retention_controller: retention::Controller,
+        webhook_controller: webhook::Controller,
+        tenant_controller: tenant::Controller,
+        content_policy: ContentPolicy,
+        ratelimiter: ratelimiter::Sender,
+        events: events::Sender,
+        db_pool: PgPool,
     ) -> Self {
         AppState {
             user_controller,
             dispatch_controller,
             rmbpost_controller,
             telegram_controller,
+            audit_controller,
+            api_key_controller,
+            retention_controller,
+            webhook_controller,
+            tenant_controller,
+            content_policy,
+            ratelimiter,
+            events,
+            db_pool,
         }
     }
 }
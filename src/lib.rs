@@ -1,107 +1,384 @@
+pub mod cli;
+#[cfg(feature = "client")]
+pub mod client;
 pub(crate) mod controllers;
 pub(crate) mod core;
 pub(crate) mod ns;
 pub(crate) mod routes;
 pub(crate) mod sync;
-pub(crate) mod token;
+#[cfg(test)]
+mod testing;
 pub(crate) mod types;
 pub(crate) mod utils;
 pub(crate) mod workers;
 
-use crate::controllers::{dispatch, rmbpost, telegram, user};
+use crate::controllers::content_policy::ContentPolicy;
+use crate::controllers::quota::Quota;
+use crate::controllers::throttle::Throttle;
+use crate::controllers::{
+    api_key, audit, dispatch, dispatch_metrics, retention, rmbpost, telegram, tenant, user, webhook,
+};
+use crate::core::db::{DbPools, Pool};
 use crate::core::error::ConfigError as Error;
-use crate::core::{config::Args, state::AppState};
-use crate::routes::router;
-use crate::sync::nations;
-use crate::sync::ratelimiter;
-use config::Config;
+use crate::core::listener::Listener;
+use crate::core::logging;
+use crate::core::state::AppState;
+use crate::routes::{public, router};
+use crate::sync::dump::Dump;
+use crate::sync::ratelimiter::{self, RestrictedAction};
+use crate::sync::{events, nations};
+use crate::workers::telegram::ClientKeys;
+use axum::Router;
+use sqlx::PgPool;
+use sqlx::migrate::Migrate;
 use sqlx::postgres::PgPoolOptions;
 use std::time::Duration;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
 
+pub use crate::core::config::Args;
+pub use crate::core::error::ConfigError;
+pub use crate::core::listener::Address;
+
+/// Serves the app configured by the environment until Ctrl+C or SIGTERM, unless the
+/// configuration doesn't pass [`Args::validate`].
 pub async fn run() -> Result<(), Error> {
-    let config = Config::builder()
-        .add_source(config::Environment::with_prefix("EUROCORE"))
-        .build()?;
-
-    let config = config.try_deserialize::<Args>()?;
-
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::try_new(config.log_level).unwrap_or_default())
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
-    let database_url = format!(
-        "postgresql://{}:{}@{}:{}/{}",
-        config.database_user,
-        config.database_password,
-        config.database_host,
-        config.database_port,
-        config.database_name
-    );
+    let config = Args::from_env()?;
 
-    let db_pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
-        .await?;
-
-    let ratelimiter = ratelimiter::new(
-        50,
-        Duration::from_secs(30),
-        Duration::from_secs(30),
-        Duration::from_secs(180),
-        Duration::from_secs(60),
-    );
+    let report = config.validate().await;
+
+    if report.has_problems() {
+        return Err(Error::Invalid(report));
+    }
+
+    logging::init(&config.log_level, config.log_format);
+
+    for warning in report.warnings() {
+        tracing::warn!("{}", warning);
+    }
+
+    let application = Application::from_config(config).await?;
+
+    tracing::debug!("listening on {}", application.local_addr()?);
+
+    application.serve_with_shutdown(shutdown()).await
+}
+
+/// The app with its workers started, its database migrated and its listener bound, ready to
+/// serve or to take requests through [`Application::router`].
+pub struct Application {
+    listener: Listener,
+    router: Router,
+    events: events::Sender,
+}
+
+impl Application {
+    /// Connects to the database in `config` and builds the app with NS's rate limits.
+    pub async fn from_config(config: Args) -> Result<Self, Error> {
+        let pools = pools(&config).await?;
+        let ratelimiter = ratelimiter(&config);
+
+        Self::new(config, pools, events::Sender::new(), ratelimiter).await
+    }
+
+    /// Like [`Application::from_config`], on pools and rate limits of the caller's.
+    pub(crate) async fn new(
+        config: Args,
+        pools: DbPools,
+        events: events::Sender,
+        ratelimiter: ratelimiter::Sender,
+    ) -> Result<Self, Error> {
+        let listener = Listener::bind(&config).await?;
+
+        let router = app(config, pools, events.clone(), ratelimiter).await?;
+
+        Ok(Self {
+            listener,
+            router,
+            events,
+        })
+    }
+
+    /// The address the listener is bound to, with the port picked when `port` is 0.
+    pub fn local_addr(&self) -> Result<Address, Error> {
+        self.listener.local_addr()
+    }
+
+    pub fn router(&self) -> Router {
+        self.router.clone()
+    }
+
+    /// Serves until `signal` resolves, then ends the event streams that would otherwise keep
+    /// graceful shutdown waiting forever, and returns once open requests are answered and a
+    /// Unix socket is removed.
+    pub async fn serve_with_shutdown<F>(self, signal: F) -> Result<(), Error>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let events = self.events;
+
+        self.listener
+            .serve(self.router, async move {
+                signal.await;
+
+                events.close();
+            })
+            .await
+    }
+}
+
+/// Resolves on Ctrl+C or SIGTERM.
+async fn shutdown() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("unable to listen for ctrl+c: {}", e);
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("unable to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("shutting down");
+}
+
+/// The pools on the databases in `config`, the read replica's if one is configured.
+pub(crate) async fn pools(config: &Args) -> Result<DbPools, Error> {
+    let primary = connect(config, &config.database_host, config.database_port).await?;
+
+    let read = match &config.database_read_host {
+        Some(host) => Some(
+            connect(
+                config,
+                host,
+                config.database_read_port.unwrap_or(config.database_port),
+            )
+            .await?,
+        ),
+        None => None,
+    };
+
+    Ok(DbPools::new(primary, read))
+}
+
+/// Runs the migrations the database hasn't seen yet, returning their versions.
+pub(crate) async fn migrate(pool: &PgPool) -> Result<Vec<i64>, Error> {
+    let migrator = sqlx::migrate!();
+
+    let applied: Vec<i64> = {
+        let mut conn = pool.acquire().await?;
 
-    let dispatch_nations = nations::new(nations::Source::Str(config.dispatch_nations))?;
-    let rmbpost_nations = nations::new(nations::Source::Str(config.rmbpost_nations))?;
+        conn.ensure_migrations_table().await?;
+
+        conn.list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect()
+    };
+
+    migrator.run(pool).await?;
+
+    Ok(migrator
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect())
+}
+
+/// A pool on the database in `config`, served at `host` and `port`.
+pub(crate) async fn connect(config: &Args, host: &str, port: u16) -> Result<PgPool, Error> {
+    Ok(PgPoolOptions::new()
+        .max_connections(config.database_max_connections.max(1))
+        .acquire_timeout(Duration::from_secs(config.database_acquire_timeout_secs))
+        .connect(&config.database_url(host, port))
+        .await?)
+}
+
+/// NS's rate limits, with the cooldowns in `config`.
+pub(crate) fn ratelimiter(config: &Args) -> ratelimiter::Sender {
+    ratelimiter::new(
+        config.ratelimit_max_requests,
+        Duration::from_secs(config.ratelimit_bucket_length_secs),
+        Duration::from_secs(config.ratelimit_telegram_cooldown_secs),
+        Duration::from_secs(config.ratelimit_recruitment_cooldown_secs),
+        Duration::from_secs(config.ratelimit_restricted_cooldown_secs),
+        [
+            (
+                RestrictedAction::Dispatch,
+                config.ratelimit_dispatch_cooldown_secs,
+            ),
+            (
+                RestrictedAction::RmbPost,
+                config.ratelimit_rmbpost_cooldown_secs,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(action, secs)| Some((action, Duration::from_secs(secs?))))
+        .collect(),
+        config.ratelimit_warn_utilization,
+    )
+}
+
+/// The accounts, on `pool`, with the secret and limits in `config`.
+pub(crate) fn user_controller(config: &Args, pool: PgPool) -> Result<user::Controller, Error> {
+    user::Controller::new(
+        pool,
+        config.secret.clone(),
+        chrono::Duration::hours(config.jwt_ttl_hours),
+        config.bcrypt_cost,
+        Throttle::new(
+            config.login_max_attempts,
+            Duration::from_secs(config.login_window_secs),
+        ),
+    )
+}
+
+/// Migrates the database, starts the workers and builds the router. The controllers share
+/// `pools` and `ratelimiter`, so tests can hand in their own instead of NS's cooldowns.
+pub(crate) async fn app(
+    config: Args,
+    pools: DbPools,
+    events: events::Sender,
+    ratelimiter: ratelimiter::Sender,
+) -> Result<Router, Error> {
+    let retention_policy = retention::Policy::from_config(&config);
+    let db_pool = pools.get(Pool::Primary).clone();
+
+    let dispatch_nations = nations::new(&config.dispatch_nations)?;
+    let rmbpost_nations = nations::new(&config.rmbpost_nations)?;
 
     let dispatch_nation_names = dispatch_nations.list_nations().await.unwrap();
     let rmbpost_nation_names = rmbpost_nations.list_nations().await.unwrap();
 
+    let quota = Quota::new(config.queue_quota_pending, config.queue_quota_daily);
+
+    migrate(&db_pool).await?;
+
+    let content_policy = ContentPolicy::load(db_pool.clone()).await?;
+
+    let user_controller = user_controller(&config, db_pool.clone())?;
+
+    let user_agent = ns::user_agent(&config.user, config.contact.as_deref());
+
+    let recovery = workers::Recovery::new(config.job_recovery_max_age_hours);
+
+    let send_timeout = Duration::from_millis(config.worker_send_timeout_ms);
+    let channel = workers::Channel {
+        capacity: config.worker_channel_capacity,
+        send_timeout,
+    };
+
     let dispatch_controller = dispatch::Controller::new(
-        &config.user,
-        "https://www.nationstates.net/cgi-bin/api.cgi",
-        db_pool.clone(),
+        &user_agent,
+        &config.ns_api_url,
+        pools.clone(),
         ratelimiter.clone(),
         dispatch_nations,
+        events.clone(),
+        quota,
+        content_policy.clone(),
+        recovery,
+        channel,
+        config.dispatch_concurrency,
+        config.protect_dispatch_edits,
+        config.dispatch_size_warn_utilization,
+        config.unique_dispatch_titles,
     )?;
 
     let rmbpost_controller = rmbpost::Controller::new(
-        &config.user,
-        "https://www.nationstates.net/cgi-bin/api.cgi",
-        db_pool.clone(),
+        &user_agent,
+        &config.ns_api_url,
+        pools.clone(),
         ratelimiter.clone(),
         rmbpost_nations,
+        events.clone(),
+        quota,
+        content_policy.clone(),
+        config.rmbpost_verify_region,
+        config.rmbpost_preflight,
+        recovery,
+        channel,
     )?;
 
     let telegram_controller = telegram::Controller::new(
-        &config.user,
-        "https://www.nationstates.net/cgi-bin/api.cgi",
-        config.telegram_client_key,
+        &user_agent,
+        &config.ns_api_url,
+        ClientKeys::new(
+            config.telegram_client_key,
+            config.telegram_client_keys.as_deref(),
+        )?,
         ratelimiter.clone(),
-        db_pool.clone(),
+        pools.clone(),
+        workers::Channel {
+            capacity: config.telegram_channel_capacity,
+            send_timeout,
+        },
+        Dump::new(
+            &user_agent,
+            config.nations_dump,
+            Duration::from_secs(config.nations_dump_refresh_hours.max(1) * 60 * 60),
+        )?,
     )?;
 
-    let user_controller = user::Controller::new(db_pool.clone(), config.secret)?;
+    let audit_controller = audit::Controller::new(db_pool.clone());
+
+    let api_key_controller = api_key::Controller::new(db_pool.clone());
+
+    dispatch_metrics::start(
+        dispatch_controller.clone(),
+        Duration::from_secs(config.dispatch_metrics_interval_secs),
+    );
+
+    let retention_controller = retention::Controller::new(db_pool.clone(), retention_policy);
+
+    let webhook_controller = webhook::Controller::new(&user_agent, db_pool.clone(), &events)?;
+
+    let tenant_controller = tenant::Controller::new(db_pool.clone());
 
     let state = AppState::new(
         user_controller,
         dispatch_controller,
         rmbpost_controller,
         telegram_controller,
+        audit_controller,
+        api_key_controller,
+        retention_controller,
+        webhook_controller,
+        tenant_controller,
+        content_policy,
+        ratelimiter,
+        events,
+        db_pool.clone(),
     );
 
-    sqlx::migrate!().run(&db_pool).await?;
-
-    let app = router::routes(state.clone(), dispatch_nation_names, rmbpost_nation_names).await;
-
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port)).await?;
-
-    tracing::debug!("listening on port {}", config.port);
-
-    axum::serve(listener, app).await?;
-
-    Ok(())
+    Ok(router::routes(
+        state,
+        dispatch_nation_names,
+        rmbpost_nation_names,
+        config.compression,
+        config.api_docs,
+        public::Tier::new(
+            config.public_read,
+            config.public_read_max_requests,
+            Duration::from_secs(config.public_read_window_secs),
+        ),
+    )
+    .await)
 }
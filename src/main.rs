@@ -1,9 +1,8 @@
+use std::process::ExitCode;
+
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     // console_subscriber::init();
 
-    if let Err(e) = eurocore::run().await {
-        eprintln!("{:?}", e);
-        std::process::exit(1);
-    }
+    eurocore::cli::run(std::env::args().skip(1)).await
 }
@@ -1,8 +1,104 @@
+use chrono::{DateTime, Utc};
+use quick_xml::de;
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::time::Duration;
 use tokio::sync::oneshot;
 
 use crate::core::error::Error;
 use crate::ns::types::Mode;
+use crate::sync::ratelimiter::{self, Target};
+use crate::types::request::RequestId;
+use crate::types::response::{DispatchCategory, DispatchSubcategory, PipelineStatus};
+use crate::utils::encode::encode;
+use crate::utils::markdown;
+use crate::workers::Control;
+
+/// Longest dispatch text NS accepts, counted after non-ASCII characters have been encoded.
+pub(crate) const MAX_TEXT_LENGTH: usize = 200_000;
+
+/// Length of `text` as NS holds it against [`MAX_TEXT_LENGTH`]. The encoded text is ASCII, so
+/// this is its size in bytes as well.
+pub(crate) fn encoded_length(text: &str) -> usize {
+    encode(text).len()
+}
+
+/// Warning for a revision of `size` that has used up `warn_utilization` of [`MAX_TEXT_LENGTH`]
+/// or more, so its author knows before an edit is refused.
+pub(crate) fn size_warning(size: usize, warn_utilization: f64) -> Option<String> {
+    let utilization = size as f64 / MAX_TEXT_LENGTH as f64;
+
+    (utilization >= warn_utilization).then(|| {
+        format!(
+            "text is {} of the {} characters NS accepts ({:.0}%)",
+            size,
+            MAX_TEXT_LENGTH,
+            utilization * 100.0
+        )
+    })
+}
+
+/// A category or subcategory number with the name NS shows for it.
+type Named = (i16, &'static str);
+
+/// Every category NS accepts with its subcategories.
+const CATEGORIES: &[(Named, &[Named])] = &[
+    (
+        (1, "Factbook"),
+        &[
+            (100, "Overview"),
+            (101, "History"),
+            (102, "Geography"),
+            (103, "Culture"),
+            (104, "Politics"),
+            (105, "Legislation"),
+            (106, "Religion"),
+            (107, "Military"),
+            (108, "Economy"),
+            (109, "International"),
+            (110, "Trivia"),
+            (111, "Miscellaneous"),
+        ],
+    ),
+    (
+        (3, "Bulletin"),
+        &[
+            (305, "Policy"),
+            (315, "News"),
+            (325, "Opinion"),
+            (385, "Campaign"),
+        ],
+    ),
+    (
+        (5, "Account"),
+        &[
+            (505, "Military"),
+            (515, "Trade"),
+            (525, "Sport"),
+            (535, "Drama"),
+            (545, "Diplomacy"),
+            (555, "Science"),
+            (565, "Culture"),
+            (595, "Other"),
+        ],
+    ),
+    ((8, "Meta"), &[(835, "Gameplay"), (845, "Reference")]),
+];
+
+/// The category tree, for clients building a category picker.
+pub(crate) fn categories() -> Vec<DispatchCategory> {
+    CATEGORIES
+        .iter()
+        .map(|((id, name), subcategories)| DispatchCategory {
+            id: *id,
+            name,
+            subcategories: subcategories
+                .iter()
+                .map(|(id, name)| DispatchSubcategory { id: *id, name })
+                .collect(),
+        })
+        .collect()
+}
 
 #[derive(Clone, Debug, Serialize)]
 pub(crate) enum FactbookCategory {
@@ -51,6 +147,45 @@ impl FactbookCategory {
             },
         }
     }
+
+    /// The category NS's public API calls `category` and `subcategory`, e.g. `Factbook` and
+    /// `Overview`.
+    pub(crate) fn from_names(category: &str, subcategory: &str) -> Result<Self, Error> {
+        let codes = match (
+            category.to_lowercase().as_str(),
+            subcategory.to_lowercase().as_str(),
+        ) {
+            ("factbook", "overview") => (1, 100),
+            ("factbook", "history") => (1, 101),
+            ("factbook", "geography") => (1, 102),
+            ("factbook", "culture") => (1, 103),
+            ("factbook", "politics") => (1, 104),
+            ("factbook", "legislation") => (1, 105),
+            ("factbook", "religion") => (1, 106),
+            ("factbook", "military") => (1, 107),
+            ("factbook", "economy") => (1, 108),
+            ("factbook", "international") => (1, 109),
+            ("factbook", "trivia") => (1, 110),
+            ("factbook", "miscellaneous") => (1, 111),
+            ("bulletin", "policy") => (3, 305),
+            ("bulletin", "news") => (3, 315),
+            ("bulletin", "opinion") => (3, 325),
+            ("bulletin", "campaign") => (3, 385),
+            ("account", "military") => (5, 505),
+            ("account", "trade") => (5, 515),
+            ("account", "sport") => (5, 525),
+            ("account", "drama") => (5, 535),
+            ("account", "diplomacy") => (5, 545),
+            ("account", "science") => (5, 555),
+            ("account", "culture") => (5, 565),
+            ("account", "other") => (5, 595),
+            ("meta", "gameplay") => (8, 835),
+            ("meta", "reference") => (8, 845),
+            _ => return Err(Error::InvalidFactbookCategory),
+        };
+
+        Self::try_from(codes)
+    }
 }
 
 impl TryFrom<(i16, i16)> for FactbookCategory {
@@ -147,21 +282,176 @@ pub(crate) enum MetaSubcategory {
     Reference, // 845
 }
 
+/// Markup the dispatch text is written in. Markdown is converted to BBCode on ingestion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextFormat {
+    #[default]
+    Bbcode,
+    Markdown,
+}
+
+impl TextFormat {
+    pub(crate) const ALL: &[TextFormat] = &[TextFormat::Bbcode, TextFormat::Markdown];
+
+    /// Value of the `dispatch_content.source_format` column.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            TextFormat::Bbcode => "bbcode",
+            TextFormat::Markdown => "markdown",
+        }
+    }
+
+    pub(crate) fn from_column(value: &str) -> Self {
+        match value {
+            "markdown" => TextFormat::Markdown,
+            _ => TextFormat::Bbcode,
+        }
+    }
+
+    /// Converts `text` written in this format to NS BBCode.
+    pub(crate) fn to_bbcode(self, text: &str) -> markdown::Converted {
+        match self {
+            TextFormat::Bbcode => markdown::Converted {
+                bbcode: text.to_string(),
+                warnings: Vec::new(),
+            },
+            TextFormat::Markdown => markdown::to_bbcode(text),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub(crate) struct NewDispatch {
-    pub(crate) nation: String,
-    pub(crate) title: String,
-    pub(crate) text: String,
-    pub(crate) category: i16,
-    pub(crate) subcategory: i16,
+pub struct NewDispatch {
+    pub nation: String,
+    pub title: String,
+    pub text: String,
+    /// may be left out when the nation's preset has a default, see `Controller::post`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<i16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subcategory: Option<i16>,
+    #[serde(default)]
+    pub format: TextFormat,
 }
 
+impl NewDispatch {
+    pub(crate) fn factbook_category(&self) -> Result<FactbookCategory, Error> {
+        match (self.category, self.subcategory) {
+            (Some(category), Some(subcategory)) => {
+                FactbookCategory::try_from((category, subcategory))
+            }
+            _ => Err(Error::MissingDispatchCategory),
+        }
+    }
+}
+
+/// The same dispatch posted by several nations, one job each.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub(crate) struct EditDispatch {
-    pub(crate) title: String,
-    pub(crate) text: String,
-    pub(crate) category: i16,
-    pub(crate) subcategory: i16,
+pub struct NewDispatchGroup {
+    pub nations: Vec<String>,
+    pub title: String,
+    pub text: String,
+    pub category: i16,
+    pub subcategory: i16,
+    #[serde(default)]
+    pub format: TextFormat,
+}
+
+impl NewDispatchGroup {
+    pub(crate) fn for_nation(&self, nation: &str) -> NewDispatch {
+        NewDispatch {
+            nation: nation.to_string(),
+            title: self.title.clone(),
+            text: self.text.clone(),
+            category: Some(self.category),
+            subcategory: Some(self.subcategory),
+            format: self.format,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EditDispatch {
+    pub title: String,
+    pub text: String,
+    pub category: i16,
+    pub subcategory: i16,
+    #[serde(default)]
+    pub format: TextFormat,
+    /// revision the edit is based on, the edit is rejected if the dispatch has changed since
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_revision: Option<i32>,
+}
+
+/// Payload stored in `dispatch_queue.payload`, i.e. exactly what was submitted for a job.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueuedDispatchPayload {
+    Add(NewDispatch),
+    Edit { id: i32, params: EditDispatch },
+    Remove { id: i32 },
+}
+
+impl QueuedDispatchPayload {
+    /// Value of the `type` column for this payload.
+    pub(crate) fn action(&self) -> &'static str {
+        match self {
+            QueuedDispatchPayload::Add(_) => "add",
+            QueuedDispatchPayload::Edit { .. } => "edit",
+            QueuedDispatchPayload::Remove { .. } => "delete",
+        }
+    }
+
+    /// Reads a payload back from the queue. Jobs queued before the payload was typed stored the
+    /// bare request body, so `action` and `dispatch_id` are used to recover the variant; returns
+    /// `None` if that isn't possible.
+    pub(crate) fn from_stored(
+        action: &str,
+        dispatch_id: Option<i32>,
+        value: serde_json::Value,
+    ) -> Option<Self> {
+        if let Ok(payload) = serde_json::from_value::<Self>(value.clone()) {
+            return Some(payload);
+        }
+
+        match action {
+            "add" => serde_json::from_value(value).ok().map(Self::Add),
+            "edit" => {
+                let params = serde_json::from_value(value).ok()?;
+
+                dispatch_id.map(|id| Self::Edit { id, params })
+            }
+            "delete" => serde_json::from_value(value)
+                .ok()
+                .map(|id| Self::Remove { id }),
+            _ => None,
+        }
+    }
+
+    /// The dispatch the job acts on, and the actions of pending jobs on it that it can't be
+    /// queued behind: nothing follows a delete, and a delete doesn't overtake edits.
+    pub(crate) fn blocked_by(&self) -> Option<(i32, &'static [&'static str])> {
+        match self {
+            QueuedDispatchPayload::Add(_) => None,
+            QueuedDispatchPayload::Edit { id, .. } => Some((*id, &["delete"])),
+            QueuedDispatchPayload::Remove { id } => Some((*id, &["edit", "delete"])),
+        }
+    }
+
+    /// Shortens the dispatch text to at most `max_chars` characters, marking it if cut.
+    pub(crate) fn truncate_text(&mut self, max_chars: usize) {
+        let text = match self {
+            QueuedDispatchPayload::Add(params) => &mut params.text,
+            QueuedDispatchPayload::Edit { params, .. } => &mut params.text,
+            QueuedDispatchPayload::Remove { .. } => return,
+        };
+
+        if let Some((index, _)) = text.char_indices().nth(max_chars) {
+            text.truncate(index);
+            text.push('…');
+        }
+    }
 }
 
 /// Intermediate representation of dispatch -- includes all information
@@ -174,6 +464,8 @@ pub(crate) struct IntermediateDispatch {
     pub(crate) nation: String,
     pub(crate) user: String,
     pub(crate) action: Action,
+    /// request that queued the job, for log correlation
+    pub(crate) request_id: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -181,12 +473,14 @@ pub(crate) enum Action {
     Add {
         title: String,
         text: String,
+        source: Source,
         category: FactbookCategory,
     },
     Edit {
         id: i32,
         title: String,
         text: String,
+        source: Source,
         category: FactbookCategory,
     },
     Remove {
@@ -194,6 +488,24 @@ pub(crate) enum Action {
     },
 }
 
+/// Text as submitted, before conversion to BBCode. Stored alongside the BBCode so edits can be
+/// made in the author's format.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Source {
+    pub(crate) format: TextFormat,
+    /// `None` when submitted as BBCode, the text is then stored only once
+    pub(crate) text: Option<String>,
+}
+
+impl Source {
+    fn new(format: TextFormat, text: String) -> Self {
+        Self {
+            format,
+            text: (format != TextFormat::Bbcode).then_some(text),
+        }
+    }
+}
+
 impl std::fmt::Display for Action {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -205,15 +517,25 @@ impl std::fmt::Display for Action {
 }
 
 impl IntermediateDispatch {
-    pub(crate) fn add(job_id: i32, user: String, params: NewDispatch) -> Result<Self, Error> {
+    /// `text` is the BBCode to post, converted from `params.text` if needed.
+    pub(crate) fn add(
+        job_id: i32,
+        user: String,
+        params: NewDispatch,
+        text: String,
+    ) -> Result<Self, Error> {
+        let category = params.factbook_category()?;
+
         Ok(Self {
             job_id,
             nation: params.nation,
             user,
+            request_id: None,
             action: Action::Add {
                 title: params.title,
-                text: params.text,
-                category: FactbookCategory::try_from((params.category, params.subcategory))?,
+                text,
+                source: Source::new(params.format, params.text),
+                category,
             },
         })
     }
@@ -224,15 +546,18 @@ impl IntermediateDispatch {
         id: i32,
         nation: String,
         params: EditDispatch,
+        text: String,
     ) -> Result<Self, Error> {
         Ok(Self {
             job_id,
             nation,
             user,
+            request_id: None,
             action: Action::Edit {
                 id,
                 title: params.title,
-                text: params.text,
+                text,
+                source: Source::new(params.format, params.text),
                 category: FactbookCategory::try_from((params.category, params.subcategory))?,
             },
         })
@@ -244,6 +569,44 @@ impl IntermediateDispatch {
             nation,
             user,
             action: Action::Remove { id },
+            request_id: None,
+        }
+    }
+
+    /// NS id of the dispatch the job acts on, `None` for adds.
+    pub(crate) fn dispatch_id(&self) -> Option<i32> {
+        match self.action {
+            Action::Add { .. } => None,
+            Action::Edit { id, .. } | Action::Remove { id } => Some(id),
+        }
+    }
+
+    pub(crate) fn with_request_id(mut self, request_id: &RequestId) -> Self {
+        self.request_id = Some(request_id.0.clone());
+        self
+    }
+
+    /// Rebuilds a job from what was stored when it was queued. Edits and removals don't store
+    /// the nation, so it's taken from the dispatch they target; `None` if it's unknown or the
+    /// payload no longer validates.
+    pub(crate) fn restore(
+        job_id: i32,
+        user: String,
+        payload: QueuedDispatchPayload,
+        nation: Option<String>,
+    ) -> Option<Self> {
+        match payload {
+            QueuedDispatchPayload::Add(params) => {
+                let text = params.format.to_bbcode(&params.text).bbcode;
+
+                Self::add(job_id, user, params, text).ok()
+            }
+            QueuedDispatchPayload::Edit { id, params } => {
+                let text = params.format.to_bbcode(&params.text).bbcode;
+
+                Self::edit(job_id, user, id, nation?, params, text).ok()
+            }
+            QueuedDispatchPayload::Remove { id } => Some(Self::delete(job_id, user, id, nation?)),
         }
     }
 }
@@ -310,6 +673,7 @@ impl From<IntermediateDispatch> for Dispatch {
                 title,
                 text,
                 category,
+                ..
             } => {
                 let (category, subcategory) = category.to_tuple();
 
@@ -328,6 +692,7 @@ impl From<IntermediateDispatch> for Dispatch {
                 title,
                 text,
                 category,
+                ..
             } => {
                 let (category, subcategory) = category.to_tuple();
 
@@ -354,19 +719,518 @@ impl From<IntermediateDispatch> for Dispatch {
     }
 }
 
+/// Body of the public API's `q=dispatch` shard.
+#[derive(Debug, Deserialize)]
+pub(crate) struct PublicDispatchResponse {
+    #[serde(rename = "DISPATCH")]
+    pub(crate) dispatch: PublicDispatch,
+}
+
+/// A dispatch as anyone can read it from NS.
+#[derive(Debug, Deserialize)]
+pub(crate) struct PublicDispatch {
+    #[serde(rename = "TITLE")]
+    pub(crate) title: String,
+    /// nation id of the author, e.g. `upper_canadan_empire`
+    #[serde(rename = "AUTHOR")]
+    pub(crate) author: String,
+    #[serde(rename = "CATEGORY")]
+    pub(crate) category: String,
+    #[serde(rename = "SUBCATEGORY")]
+    pub(crate) subcategory: String,
+    #[serde(rename = "TEXT", default)]
+    pub(crate) text: String,
+    #[serde(rename = "VIEWS")]
+    pub(crate) views: Option<i32>,
+    #[serde(rename = "SCORE")]
+    pub(crate) score: Option<i32>,
+}
+
+/// Body of the public API's `q=dispatchlist` shard of a nation.
+#[derive(Debug, Deserialize)]
+struct DispatchListResponse {
+    #[serde(rename = "DISPATCHLIST", default)]
+    list: DispatchList,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct DispatchList {
+    #[serde(rename = "DISPATCH", default)]
+    dispatches: Vec<ListedDispatch>,
+}
+
+/// A dispatch as its nation's dispatch list shows it, without its text.
+#[derive(Debug, Deserialize)]
+struct ListedDispatch {
+    #[serde(rename = "@id")]
+    id: i32,
+    #[serde(rename = "TITLE")]
+    title: String,
+    /// unix timestamp
+    #[serde(rename = "CREATED")]
+    created: i64,
+}
+
+impl DispatchListResponse {
+    /// Ids of the dispatches titled `title` created at or after `since`, newest first.
+    fn matching(self, title: &str, since: DateTime<Utc>) -> Vec<i32> {
+        let mut dispatches: Vec<_> = self
+            .list
+            .dispatches
+            .into_iter()
+            .filter(|dispatch| {
+                dispatch.created >= since.timestamp() && dispatch.title.trim() == title.trim()
+            })
+            .collect();
+
+        dispatches.sort_by_key(|dispatch| Reverse(dispatch.created));
+
+        dispatches.into_iter().map(|dispatch| dispatch.id).collect()
+    }
+}
+
+/// Ids of the dispatches `nation` has posted titled `title` since `since`, newest first. Read
+/// from the public API within the standard rate limit, for a worker that can't tell from NS's
+/// reply whether its dispatch was posted.
+#[tracing::instrument(skip(client, limiter))]
+pub(crate) async fn find_posted(
+    client: &reqwest::Client,
+    url: &str,
+    limiter: &ratelimiter::Sender,
+    nation: &str,
+    title: &str,
+    since: DateTime<Utc>,
+) -> Result<Vec<i32>, Error> {
+    if let Err(duration) = limiter.acquire(Target::Standard).await {
+        tokio::time::sleep(duration).await;
+    }
+
+    let body = client
+        .get(url)
+        .query(&[("nation", nation), ("q", "dispatchlist")])
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    Ok(de::from_str::<DispatchListResponse>(&body)?.matching(title, since))
+}
+
 #[derive(Debug)]
 pub(crate) struct Command {
-    pub(crate) dispatch: IntermediateDispatch,
+    pub(crate) operation: Operation,
     pub(crate) tx: oneshot::Sender<Response>,
 }
 
 impl Command {
-    pub(crate) fn new(dispatch: IntermediateDispatch, tx: oneshot::Sender<Response>) -> Self {
-        Self { dispatch, tx }
+    pub(crate) fn new(operation: Operation, tx: oneshot::Sender<Response>) -> Self {
+        Self { operation, tx }
     }
 }
 
+#[derive(Debug)]
+pub(crate) enum Operation {
+    Queue(IntermediateDispatch),
+    /// Queue an edit in place of an edit of the same dispatch, by the same user, that's still
+    /// waiting, if it's the last job waiting on the dispatch. Queued like any other otherwise.
+    Coalesce(IntermediateDispatch),
+    /// Summarize the worker's queue with respect to `nation`.
+    Inspect {
+        nation: String,
+    },
+    /// How long job `job_id` is likely to wait before it's posted, if it's waiting in the queue.
+    Estimate {
+        job_id: i32,
+    },
+    Control(Control),
+    /// Check a nation's password with NS and clear its unhealthy flag if it works.
+    Revalidate {
+        nation: String,
+    },
+    /// Crash the worker, used to exercise the supervisor.
+    #[cfg(test)]
+    Panic,
+}
+
 #[derive(Debug)]
 pub(crate) enum Response {
     Success,
+    Error(Error),
+    Inspect(QueueSummary),
+    Estimate(Option<Duration>),
+    Pipeline(PipelineStatus),
+}
+
+/// Snapshot of the jobs waiting in the dispatch worker's queue.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct QueueSummary {
+    pub(crate) total: usize,
+    pub(crate) for_nation: usize,
+    /// jobs for the nation that will consume a restricted action slot
+    pub(crate) restricted_for_nation: usize,
+}
+
+impl QueueSummary {
+    pub(crate) fn new<'a>(
+        queue: impl IntoIterator<Item = &'a IntermediateDispatch>,
+        nation: &str,
+    ) -> Self {
+        queue
+            .into_iter()
+            .fold(Self::default(), |mut summary, dispatch| {
+                summary.total += 1;
+
+                if dispatch.nation == nation {
+                    summary.for_nation += 1;
+
+                    if let Action::Add { .. } = dispatch.action {
+                        summary.restricted_for_nation += 1;
+                    }
+                }
+
+                summary
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_dispatch(text: &str) -> NewDispatch {
+        NewDispatch {
+            nation: "testlandia".to_string(),
+            title: "Title".to_string(),
+            text: text.to_string(),
+            category: Some(1),
+            subcategory: Some(100),
+            format: TextFormat::Bbcode,
+        }
+    }
+
+    #[test]
+    fn test_categories_match_names() {
+        for category in categories() {
+            for subcategory in category.subcategories {
+                let parsed = FactbookCategory::from_names(category.name, subcategory.name).unwrap();
+
+                assert_eq!(parsed.to_tuple(), (category.id, subcategory.id));
+            }
+        }
+
+        let count: usize = categories().iter().map(|c| c.subcategories.len()).sum();
+        assert_eq!(count, 26);
+    }
+
+    #[test]
+    fn test_payload_round_trip() {
+        let payload = QueuedDispatchPayload::Edit {
+            id: 7,
+            params: EditDispatch {
+                title: "Title".to_string(),
+                text: "text".to_string(),
+                category: 1,
+                subcategory: 100,
+                format: TextFormat::Markdown,
+                base_revision: Some(3),
+            },
+        };
+
+        let value = serde_json::to_value(&payload).unwrap();
+
+        match QueuedDispatchPayload::from_stored("edit", None, value) {
+            Some(QueuedDispatchPayload::Edit { id, params }) => {
+                assert_eq!(id, 7);
+                assert_eq!(params.text, "text");
+                assert_eq!(params.format, TextFormat::Markdown);
+                assert_eq!(params.base_revision, Some(3));
+            }
+            other => panic!("expected edit payload, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_legacy_payloads() {
+        let add = serde_json::to_value(new_dispatch("text")).unwrap();
+        assert!(matches!(
+            QueuedDispatchPayload::from_stored("add", None, add),
+            Some(QueuedDispatchPayload::Add(_))
+        ));
+
+        assert!(matches!(
+            QueuedDispatchPayload::from_stored("delete", None, serde_json::json!(12)),
+            Some(QueuedDispatchPayload::Remove { id: 12 })
+        ));
+
+        let edit = serde_json::json!({
+            "title": "Title",
+            "text": "text",
+            "category": 1,
+            "subcategory": 100,
+        });
+        assert!(matches!(
+            QueuedDispatchPayload::from_stored("edit", Some(3), edit.clone()),
+            Some(QueuedDispatchPayload::Edit { id: 3, .. })
+        ));
+        assert!(QueuedDispatchPayload::from_stored("edit", None, edit).is_none());
+    }
+
+    #[test]
+    fn test_category_from_names() {
+        assert_eq!(
+            FactbookCategory::from_names("Factbook", "Overview")
+                .unwrap()
+                .to_tuple(),
+            (1, 100)
+        );
+        assert_eq!(
+            FactbookCategory::from_names("Account", "Culture")
+                .unwrap()
+                .to_tuple(),
+            (5, 565)
+        );
+        assert_eq!(
+            FactbookCategory::from_names("meta", "REFERENCE")
+                .unwrap()
+                .to_tuple(),
+            (8, 845)
+        );
+        assert!(FactbookCategory::from_names("Bulletin", "Overview").is_err());
+        assert!(FactbookCategory::from_names("Factbook", "").is_err());
+    }
+
+    #[test]
+    fn test_public_dispatch() {
+        let xml = r#"<WORLD>
+<DISPATCH id="1234">
+<TITLE>Laws &amp; Customs</TITLE>
+<AUTHOR>testlandia</AUTHOR>
+<CATEGORY>Bulletin</CATEGORY>
+<SUBCATEGORY>Policy</SUBCATEGORY>
+<CREATED>1577836800</CREATED>
+<EDITED>0</EDITED>
+<VIEWS>12</VIEWS>
+<SCORE>3</SCORE>
+<TEXT><![CDATA[[b]hello[/b] & <welcome>]]></TEXT>
+</DISPATCH>
+</WORLD>"#;
+
+        let dispatch = quick_xml::de::from_str::<PublicDispatchResponse>(xml)
+            .unwrap()
+            .dispatch;
+
+        assert_eq!(dispatch.title, "Laws & Customs");
+        assert_eq!(dispatch.author, "testlandia");
+        assert_eq!(dispatch.category, "Bulletin");
+        assert_eq!(dispatch.subcategory, "Policy");
+        assert_eq!(dispatch.text, "[b]hello[/b] & <welcome>");
+    }
+
+    #[test]
+    fn test_dispatch_list_matching() {
+        let xml = r#"<NATION id="testlandia">
+<DISPATCHLIST>
+<DISPATCH id="1">
+<TITLE>Laws &amp; Customs</TITLE>
+<AUTHOR>testlandia</AUTHOR>
+<CATEGORY>Bulletin</CATEGORY>
+<SUBCATEGORY>Policy</SUBCATEGORY>
+<CREATED>1577836800</CREATED>
+<EDITED>0</EDITED>
+<VIEWS>12</VIEWS>
+<SCORE>3</SCORE>
+</DISPATCH>
+<DISPATCH id="2">
+<TITLE>Laws &amp; Customs</TITLE>
+<AUTHOR>testlandia</AUTHOR>
+<CATEGORY>Bulletin</CATEGORY>
+<SUBCATEGORY>Policy</SUBCATEGORY>
+<CREATED>1577840400</CREATED>
+<EDITED>0</EDITED>
+<VIEWS>1</VIEWS>
+<SCORE>0</SCORE>
+</DISPATCH>
+<DISPATCH id="3">
+<TITLE>Laws &amp; Customs</TITLE>
+<AUTHOR>testlandia</AUTHOR>
+<CATEGORY>Bulletin</CATEGORY>
+<SUBCATEGORY>Policy</SUBCATEGORY>
+<CREATED>1577844000</CREATED>
+<EDITED>0</EDITED>
+<VIEWS>1</VIEWS>
+<SCORE>0</SCORE>
+</DISPATCH>
+<DISPATCH id="4">
+<TITLE>Something Else</TITLE>
+<AUTHOR>testlandia</AUTHOR>
+<CATEGORY>Bulletin</CATEGORY>
+<SUBCATEGORY>Policy</SUBCATEGORY>
+<CREATED>1577844000</CREATED>
+<EDITED>0</EDITED>
+<VIEWS>1</VIEWS>
+<SCORE>0</SCORE>
+</DISPATCH>
+</DISPATCHLIST>
+</NATION>"#;
+
+        let since = DateTime::from_timestamp(1577840400, 0).unwrap();
+        let list = || quick_xml::de::from_str::<DispatchListResponse>(xml).unwrap();
+
+        // too old and differently titled dispatches are left out
+        assert_eq!(list().matching("Laws & Customs", since), vec![3, 2]);
+        assert!(list().matching("Laws", since).is_empty());
+
+        let empty = r#"<NATION id="testlandia"><DISPATCHLIST></DISPATCHLIST></NATION>"#;
+        assert!(
+            quick_xml::de::from_str::<DispatchListResponse>(empty)
+                .unwrap()
+                .matching("Laws & Customs", since)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_queue_summary() {
+        let queue = vec![
+            IntermediateDispatch::add(1, "alice".to_string(), new_dispatch("a"), "a".to_string())
+                .unwrap(),
+            IntermediateDispatch::delete(2, "alice".to_string(), 10, "testlandia".to_string()),
+            IntermediateDispatch::add(
+                3,
+                "alice".to_string(),
+                NewDispatch {
+                    nation: "other".to_string(),
+                    ..new_dispatch("b")
+                },
+                "b".to_string(),
+            )
+            .unwrap(),
+        ];
+
+        assert_eq!(
+            QueueSummary::new(&queue, "testlandia"),
+            QueueSummary {
+                total: 3,
+                for_nation: 2,
+                restricted_for_nation: 1,
+            }
+        );
+        assert_eq!(QueueSummary::new(&queue, "nowhere").for_nation, 0);
+        assert_eq!(
+            QueueSummary::new(&[], "testlandia"),
+            QueueSummary::default()
+        );
+    }
+
+    #[test]
+    fn test_markdown_source() {
+        let params = NewDispatch {
+            format: TextFormat::Markdown,
+            ..new_dispatch("**bold**")
+        };
+        let converted = params.format.to_bbcode(&params.text);
+
+        let dispatch =
+            IntermediateDispatch::add(1, "alice".to_string(), params, converted.bbcode).unwrap();
+
+        match dispatch.action {
+            Action::Add { text, source, .. } => {
+                assert_eq!(text, "[b]bold[/b]");
+                assert_eq!(source.format, TextFormat::Markdown);
+                assert_eq!(source.text.as_deref(), Some("**bold**"));
+            }
+            other => panic!("expected add, got {:?}", other),
+        }
+
+        let dispatch = IntermediateDispatch::add(
+            2,
+            "alice".to_string(),
+            new_dispatch("[b]x[/b]"),
+            "[b]x[/b]".to_string(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            dispatch.action,
+            Action::Add {
+                source: Source {
+                    format: TextFormat::Bbcode,
+                    text: None
+                },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_restore() {
+        let payload = QueuedDispatchPayload::Add(NewDispatch {
+            format: TextFormat::Markdown,
+            ..new_dispatch("**bold**")
+        });
+
+        match IntermediateDispatch::restore(1, "alice".to_string(), payload, None) {
+            Some(IntermediateDispatch {
+                action: Action::Add { text, .. },
+                ..
+            }) => assert_eq!(text, "[b]bold[/b]"),
+            other => panic!("expected add, got {:?}", other),
+        }
+
+        let payload = QueuedDispatchPayload::Remove { id: 10 };
+
+        assert!(
+            IntermediateDispatch::restore(2, "alice".to_string(), payload.clone(), None).is_none()
+        );
+
+        let dispatch = IntermediateDispatch::restore(
+            2,
+            "alice".to_string(),
+            payload,
+            Some("testlandia".to_string()),
+        )
+        .unwrap();
+        assert_eq!(dispatch.nation, "testlandia");
+        assert!(matches!(dispatch.action, Action::Remove { id: 10 }));
+    }
+
+    #[test]
+    fn test_size_warning() {
+        // every non-ASCII character becomes a reference of several characters
+        assert_eq!(encoded_length("é"), "&#233;".len());
+        assert_eq!(encoded_length("😀"), "&#128512;".len());
+
+        let close = "é".repeat(30_000);
+        assert_eq!(encoded_length(&close), 180_000);
+        assert_eq!(
+            size_warning(encoded_length(&close), 0.9).as_deref(),
+            Some("text is 180000 of the 200000 characters NS accepts (90%)")
+        );
+
+        // 179,994 characters once encoded, although only 29,999 of them
+        let below = "é".repeat(29_999);
+        assert_eq!(size_warning(encoded_length(&below), 0.9), None);
+
+        assert!(size_warning(MAX_TEXT_LENGTH, 1.0).is_some());
+    }
+
+    #[test]
+    fn test_truncate_text() {
+        let mut payload = QueuedDispatchPayload::Add(new_dispatch("äöü long text"));
+        payload.truncate_text(3);
+
+        match payload {
+            QueuedDispatchPayload::Add(params) => assert_eq!(params.text, "äöü…"),
+            _ => unreachable!(),
+        }
+
+        let mut payload = QueuedDispatchPayload::Add(new_dispatch("short"));
+        payload.truncate_text(5);
+
+        match payload {
+            QueuedDispatchPayload::Add(params) => assert_eq!(params.text, "short"),
+            _ => unreachable!(),
+        }
+    }
 }
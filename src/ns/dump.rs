@@ -0,0 +1,242 @@
+//! NS's daily nations dump, `nations.xml.gz`: a `NATION` element for every nation in the game.
+//! Unpacked it runs to gigabytes, so it's read an event at a time and only what recipient
+//! filters look at is kept.
+
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::utils::name;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// What the dump says about a nation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Nation {
+    /// position in [`Index::regions`], the names repeat too often to store them per nation
+    region: u32,
+    pub(crate) wa: bool,
+    /// the epoch for nations founded before NS recorded it
+    pub(crate) founded: DateTime<Utc>,
+}
+
+/// The nations of a dump by canonical name.
+#[derive(Default)]
+pub(crate) struct Index {
+    nations: HashMap<String, Nation>,
+    regions: Vec<String>,
+}
+
+impl Index {
+    pub(crate) fn get(&self, nation: &str) -> Option<&Nation> {
+        self.nations.get(nation)
+    }
+
+    /// Canonical name of `nation`'s region.
+    pub(crate) fn region(&self, nation: &Nation) -> &str {
+        &self.regions[nation.region as usize]
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.nations.len()
+    }
+}
+
+impl std::fmt::Debug for Index {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Index")
+            .field("nations", &self.nations.len())
+            .field("regions", &self.regions.len())
+            .finish()
+    }
+}
+
+/// The fields of the `NATION` being read that make it into the index.
+#[derive(Default)]
+struct Fields {
+    name: String,
+    region: String,
+    unstatus: String,
+    founded: String,
+}
+
+impl Fields {
+    fn field(&mut self, tag: &[u8]) -> Option<&mut String> {
+        match tag {
+            b"NAME" => Some(&mut self.name),
+            b"REGION" => Some(&mut self.region),
+            b"UNSTATUS" => Some(&mut self.unstatus),
+            b"FOUNDEDTIME" => Some(&mut self.founded),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a dump, gzipped as NS serves it or already unpacked.
+pub(crate) fn parse(reader: impl Read) -> io::Result<Index> {
+    let mut reader = BufReader::with_capacity(64 * 1024, reader);
+
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        parse_xml(BufReader::new(MultiGzDecoder::new(reader)))
+    } else {
+        parse_xml(reader)
+    }
+}
+
+fn parse_xml(reader: impl BufRead) -> io::Result<Index> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buffer = Vec::new();
+
+    let mut index = Index::default();
+    let mut region_ids: HashMap<String, u32> = HashMap::new();
+
+    let mut depth = 0;
+    let mut fields = Fields::default();
+    // the field whose text is being read, `NATIONS` > `NATION` > field
+    let mut current: Option<Vec<u8>> = None;
+
+    loop {
+        match reader
+            .read_event_into(&mut buffer)
+            .map_err(io::Error::other)?
+        {
+            Event::Start(start) => {
+                depth += 1;
+
+                if depth == 2 && start.name().as_ref() == b"NATION" {
+                    fields = Fields::default();
+                } else if depth == 3 {
+                    current = Some(start.name().as_ref().to_vec());
+                }
+            }
+            Event::Text(text) => {
+                if let Some(field) = current.as_deref().and_then(|tag| fields.field(tag)) {
+                    field.push_str(&text.unescape().map_err(io::Error::other)?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(field) = current.as_deref().and_then(|tag| fields.field(tag)) {
+                    field.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Event::End(end) => {
+                if depth == 3 {
+                    current = None;
+                } else if depth == 2 && end.name().as_ref() == b"NATION" {
+                    let fields = std::mem::take(&mut fields);
+
+                    let region = name::canonicalize(fields.region.trim());
+                    let next = region_ids.len() as u32;
+                    let region = *region_ids.entry(region.clone()).or_insert_with(|| {
+                        index.regions.push(region);
+                        next
+                    });
+
+                    index.nations.insert(
+                        name::canonicalize(fields.name.trim()),
+                        Nation {
+                            region,
+                            wa: fields.unstatus.trim() != "Non-member"
+                                && !fields.unstatus.trim().is_empty(),
+                            founded: fields
+                                .founded
+                                .trim()
+                                .parse()
+                                .ok()
+                                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                                .unwrap_or_default(),
+                        },
+                    );
+                }
+
+                depth -= 1;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+
+        buffer.clear();
+    }
+
+    if depth != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "nations dump cut short",
+        ));
+    }
+
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    const DUMP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<NATIONS api_version="12">
+<NATION>
+<NAME>Testlandia</NAME>
+<TYPE>Hive Mind</TYPE>
+<FREEDOM><CIVILRIGHTS>Excellent</CIVILRIGHTS><NAME>nested</NAME></FREEDOM>
+<REGION>Testregionia</REGION>
+<UNSTATUS>WA Delegate</UNSTATUS>
+<FOUNDEDTIME>0</FOUNDEDTIME>
+</NATION>
+<NATION>
+<NAME>New Shiny</NAME>
+<REGION>The North Pacific</REGION>
+<UNSTATUS>Non-member</UNSTATUS>
+<FOUNDEDTIME>1760000000</FOUNDEDTIME>
+</NATION>
+<NATION>
+<NAME>Member</NAME>
+<REGION>testregionia</REGION>
+<UNSTATUS>WA Member</UNSTATUS>
+<FOUNDEDTIME>1500000000</FOUNDEDTIME>
+</NATION>
+</NATIONS>
+"#;
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        for bytes in [DUMP.as_bytes().to_vec(), gzip(DUMP)] {
+            let index = parse(bytes.as_slice()).unwrap();
+
+            assert_eq!(index.len(), 3);
+            assert_eq!(index.regions.len(), 2);
+
+            let testlandia = index.get("testlandia").unwrap();
+            assert_eq!(index.region(testlandia), "testregionia");
+            assert!(testlandia.wa);
+            assert_eq!(testlandia.founded, DateTime::<Utc>::default());
+
+            let shiny = index.get("new_shiny").unwrap();
+            assert_eq!(index.region(shiny), "the_north_pacific");
+            assert!(!shiny.wa);
+            assert_eq!(shiny.founded.timestamp(), 1760000000);
+
+            assert!(index.get("member").unwrap().wa);
+            assert!(index.get("nested").is_none());
+        }
+    }
+
+    #[test]
+    fn test_cut_short() {
+        let bytes = gzip(DUMP);
+
+        assert!(parse(&DUMP.as_bytes()[..DUMP.len() / 2]).is_err());
+        assert!(parse(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
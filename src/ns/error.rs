@@ -0,0 +1,192 @@
+use crate::core::error::Error;
+use reqwest::StatusCode;
+
+/// Failures reported by NS, recognised from the HTTP status or the text of an `<ERROR>` element.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub(crate) enum NsError {
+    #[error("Rate limited by NS")]
+    RateLimited,
+    /// NS holds the telegram to the recruitment cooldown, whatever type it was sent as.
+    #[error("Recruitment telegram sent before the recruitment cooldown was over")]
+    RecruitmentRateLimited,
+    /// NS is down or answered with something other than its API, e.g. a maintenance page. It
+    /// never got to the request, so sending it again later may work.
+    #[error("NS is unavailable")]
+    Unavailable,
+    #[error("Pin expired")]
+    PinExpired,
+    #[error("Invalid password")]
+    InvalidPassword,
+    #[error("Dispatch does not exist")]
+    DispatchMissing,
+    #[error("Region password required")]
+    RegionPasswordRequired,
+    #[error("Client key not registered")]
+    ClientKeyInvalid,
+    #[error("Telegram ID or secret key is wrong")]
+    TelegramInvalid,
+    #[error("Recipient does not exist")]
+    RecipientMissing,
+    #[error("Recipient does not accept recruitment telegrams")]
+    RecruitmentBlocked,
+    #[error("Recipient's region does not accept recruitment telegrams")]
+    RegionRecruitmentBlocked,
+    #[error("{0}")]
+    Unknown(String),
+}
+
+impl NsError {
+    /// Classifies the text of an `<ERROR>` element.
+    pub(crate) fn from_text(text: &str) -> Self {
+        let lowercase = text.to_lowercase();
+        let contains = |phrases: &[&str]| phrases.iter().any(|phrase| lowercase.contains(phrase));
+
+        if contains(&[
+            "recruitment tg rate-limit",
+            "recruitment telegram rate-limit",
+        ]) {
+            NsError::RecruitmentRateLimited
+        } else if contains(&["rate-limit", "rate limit", "too many requests"]) {
+            NsError::RateLimited
+        } else if contains(&["pin expired", "invalid pin"]) {
+            NsError::PinExpired
+        } else if contains(&["authentication failed", "incorrect password"]) {
+            NsError::InvalidPassword
+        } else if contains(&["client not registered", "client key"]) {
+            NsError::ClientKeyInvalid
+        } else if contains(&["no such telegram", "secret key"]) {
+            NsError::TelegramInvalid
+        } else if contains(&["region does not accept recruitment", "regional recruitment"]) {
+            NsError::RegionRecruitmentBlocked
+        } else if contains(&["not accepting recruitment", "blocked recruitment"]) {
+            NsError::RecruitmentBlocked
+        } else if contains(&["unknown nation", "no such nation", "nation does not exist"]) {
+            NsError::RecipientMissing
+        } else if contains(&["no such dispatch", "dispatch does not exist"]) {
+            NsError::DispatchMissing
+        } else if contains(&["does not have permission", "region password"]) {
+            NsError::RegionPasswordRequired
+        } else {
+            NsError::Unknown(text.to_string())
+        }
+    }
+
+    /// Classifies an HTTP error status. NS answers bad credentials with 403, which means the pin
+    /// went stale when one was sent, and a wrong password otherwise. Gateway errors mean NS is
+    /// down.
+    pub(crate) fn from_status(status: StatusCode, pin_sent: bool) -> Option<Self> {
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => Some(NsError::RateLimited),
+            StatusCode::FORBIDDEN if pin_sent => Some(NsError::PinExpired),
+            StatusCode::FORBIDDEN => Some(NsError::InvalidPassword),
+            StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => Some(NsError::Unavailable),
+            _ => None,
+        }
+    }
+
+    /// Stable identifier stored with failed jobs and returned in their status.
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            NsError::RateLimited => "rate_limited",
+            NsError::RecruitmentRateLimited => "recruitment_rate_limited",
+            NsError::Unavailable => "ns_unavailable",
+            NsError::PinExpired => "pin_expired",
+            NsError::InvalidPassword => "invalid_password",
+            NsError::DispatchMissing => "dispatch_missing",
+            NsError::RegionPasswordRequired => "region_password_required",
+            NsError::ClientKeyInvalid => "client_key_invalid",
+            NsError::TelegramInvalid => "telegram_invalid",
+            NsError::RecipientMissing => "recipient_missing",
+            NsError::RecruitmentBlocked => "recruitment_blocked",
+            NsError::RegionRecruitmentBlocked => "region_recruitment_blocked",
+            NsError::Unknown(_) => "unknown",
+        }
+    }
+}
+
+/// Like `error_for_status`, but with the failures NS signals through the status code recognised.
+pub(crate) fn check_status(
+    resp: reqwest::Response,
+    pin_sent: bool,
+) -> Result<reqwest::Response, Error> {
+    match NsError::from_status(resp.status(), pin_sent) {
+        Some(error) => Err(Error::NationStates(error)),
+        None => Ok(resp.error_for_status()?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ns::reply;
+
+    fn parse(xml: &str) -> NsError {
+        NsError::from_text(&reply::parse(xml).unwrap().error.unwrap())
+    }
+
+    #[test]
+    fn test_from_text_fixtures() {
+        let fixtures = [
+            (
+                "<NATION id=\"testlandia\"><ERROR>You are being rate-limited. Please wait before making further requests.</ERROR></NATION>",
+                NsError::RateLimited,
+            ),
+            (
+                "<NATION id=\"testlandia\"><ERROR>Invalid pin: the pin has expired.</ERROR></NATION>",
+                NsError::PinExpired,
+            ),
+            (
+                "<NATION id=\"testlandia\"><ERROR>Authentication Failed</ERROR></NATION>",
+                NsError::InvalidPassword,
+            ),
+            (
+                "<NATION id=\"testlandia\"><ERROR>No such dispatch.</ERROR></NATION>",
+                NsError::DispatchMissing,
+            ),
+            (
+                "<NATION id=\"testlandia\"><ERROR>Testlandia does not have permission to post on the Testregionia Regional Message Board.</ERROR></NATION>",
+                NsError::RegionPasswordRequired,
+            ),
+            (
+                "<NATION id=\"testlandia\"><ERROR>Dispatch title is too long.</ERROR></NATION>",
+                NsError::Unknown("Dispatch title is too long.".to_string()),
+            ),
+        ];
+
+        for (xml, expected) in fixtures {
+            assert_eq!(parse(xml), expected, "{}", xml);
+        }
+    }
+
+    #[test]
+    fn test_from_status() {
+        assert_eq!(
+            NsError::from_status(StatusCode::TOO_MANY_REQUESTS, false),
+            Some(NsError::RateLimited)
+        );
+        assert_eq!(
+            NsError::from_status(StatusCode::FORBIDDEN, true),
+            Some(NsError::PinExpired)
+        );
+        assert_eq!(
+            NsError::from_status(StatusCode::FORBIDDEN, false),
+            Some(NsError::InvalidPassword)
+        );
+        assert_eq!(NsError::from_status(StatusCode::OK, true), None);
+        assert_eq!(
+            NsError::from_status(StatusCode::BAD_GATEWAY, true),
+            Some(NsError::Unavailable)
+        );
+        assert_eq!(NsError::from_status(StatusCode::BAD_REQUEST, true), None);
+    }
+
+    #[test]
+    fn test_unknown_keeps_text() {
+        let error = NsError::from_text("Something new went wrong.");
+
+        assert_eq!(error.code(), "unknown");
+        assert_eq!(error.to_string(), "Something new went wrong.");
+    }
+}
@@ -1,4 +1,118 @@
 pub(crate) mod dispatch;
+pub(crate) mod dump;
+pub(crate) mod error;
+pub(crate) mod region;
+pub(crate) mod reply;
 pub(crate) mod rmbpost;
 pub(crate) mod telegram;
 pub(crate) mod types;
+
+use crate::core::error::Error;
+use crate::ns::error::NsError;
+use crate::sync::nations;
+use crate::sync::ratelimiter::{self, Target};
+use crate::types::Secret;
+use crate::types::response::CredentialCheck;
+
+/// Authenticates as `nation` with the cheap `ping` shard, returning the pin NS hands back.
+/// A rejected password comes back as `NsError::InvalidPassword`.
+pub(crate) async fn ping(
+    client: &reqwest::Client,
+    url: &str,
+    nation: &str,
+    password: &Secret,
+) -> Result<Option<String>, Error> {
+    let resp = client
+        .get(url)
+        .query(&[("nation", nation), ("q", "ping")])
+        .header("X-Password", password.expose())
+        .send()
+        .await?;
+
+    let resp = error::check_status(resp, false)?;
+
+    Ok(resp
+        .headers()
+        .get("X-Pin")
+        .map(|pin| pin.to_str())
+        .transpose()?
+        .map(str::to_string))
+}
+
+/// Checks the stored password of `nation` with a ping, through the standard ratelimit target.
+/// A pin NS hands back is kept for the next jobs and a rejected password marks the nation
+/// unhealthy; anything else is only reported. Fails with `Error::InvalidNation` if `nations`
+/// doesn't hold `nation`.
+pub(crate) async fn verify(
+    client: &reqwest::Client,
+    url: &str,
+    limiter: &ratelimiter::Sender,
+    nations: &nations::Sender,
+    nation: &str,
+) -> Result<CredentialCheck, Error> {
+    let password = nations.get_password_unchecked(nation).await?;
+
+    while let Err(duration) = limiter.acquire(Target::Standard).await {
+        tokio::time::sleep(duration).await;
+    }
+
+    match ping(client, url, nation, &password).await {
+        Ok(pin) => {
+            if let Some(pin) = &pin {
+                nations.set_pin(nation, pin).await?;
+            }
+
+            Ok(CredentialCheck {
+                verified: true,
+                pin: pin.is_some(),
+                error: None,
+                error_code: None,
+            })
+        }
+        Err(e) => {
+            if let Error::NationStates(NsError::InvalidPassword) = e {
+                nations.mark_unhealthy(nation).await?;
+            }
+
+            Ok(CredentialCheck {
+                verified: false,
+                pin: false,
+                error: Some(e.to_string()),
+                error_code: e.job_code(),
+            })
+        }
+    }
+}
+
+/// User agent sent with every NS API request. `user` is the nation operating this instance.
+pub(crate) fn user_agent(user: &str, contact: Option<&str>) -> String {
+    let user_agent = format!("{} eurocore/{}", user, env!("CARGO_PKG_VERSION"));
+
+    match contact.map(str::trim).filter(|contact| !contact.is_empty()) {
+        Some(contact) => format!("{} ({})", user_agent, contact),
+        None => user_agent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_agent() {
+        let version = env!("CARGO_PKG_VERSION");
+
+        assert_eq!(
+            user_agent("testlandia", None),
+            format!("testlandia eurocore/{version}")
+        );
+        assert_eq!(
+            user_agent("testlandia", Some(" ")),
+            format!("testlandia eurocore/{version}")
+        );
+        assert_eq!(
+            user_agent("testlandia", Some("admin@example.com")),
+            format!("testlandia eurocore/{version} (admin@example.com)")
+        );
+    }
+}
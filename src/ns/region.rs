@@ -0,0 +1,378 @@
+//! Public API lookups of a region's nations and RMB policy, each within the standard rate limit.
+
+use chrono::{DateTime, Utc};
+use quick_xml::de;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::core::error::Error;
+use crate::ns::telegram::RecipientSource;
+use crate::sync::ratelimiter::{self, Target};
+use crate::utils::name;
+
+#[derive(Debug, Deserialize)]
+struct Region {
+    /// nations separated by `:`
+    #[serde(rename = "NATIONS", default)]
+    nations: String,
+    /// World Assembly members separated by `,`
+    #[serde(rename = "UNNATIONS", default)]
+    wa_nations: String,
+    #[serde(rename = "EMBASSIES", default)]
+    embassies: Embassies,
+    #[serde(rename = "EMBASSYRMB", default)]
+    embassy_rmb: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Embassies {
+    #[serde(rename = "EMBASSY", default)]
+    embassies: Vec<Embassy>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Embassy {
+    /// `invited`, `requested`, `pending`, `denied`, `rejected` or `closing`, none once established
+    #[serde(rename = "@type", default)]
+    kind: Option<String>,
+    #[serde(rename = "$text")]
+    region: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Nation {
+    #[serde(rename = "REGION")]
+    region: String,
+}
+
+/// Who may post on a region's RMB, as far as it concerns nations from elsewhere.
+#[derive(Clone, Debug)]
+pub(crate) struct RmbPolicy {
+    /// embassy type by canonical region name, `None` for established embassies
+    embassies: HashMap<String, Option<String>>,
+    /// `0` when only residents may post, otherwise which residents of embassy regions may
+    embassy_rmb: String,
+}
+
+/// Why posting on a region's RMB can't succeed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RmbRefusal {
+    NoEmbassy,
+    EmbassyPending,
+    ResidentsOnly,
+}
+
+impl RmbRefusal {
+    pub(crate) fn code(self) -> &'static str {
+        match self {
+            RmbRefusal::NoEmbassy => "no_embassy",
+            RmbRefusal::EmbassyPending => "embassy_pending",
+            RmbRefusal::ResidentsOnly => "residents_only",
+        }
+    }
+
+    pub(crate) fn message(self) -> &'static str {
+        match self {
+            RmbRefusal::NoEmbassy => "The nation's region has no embassy with the region",
+            RmbRefusal::EmbassyPending => {
+                "The embassy between the nation's region and the region isn't open yet"
+            }
+            RmbRefusal::ResidentsOnly => "Only residents may post on the region's RMB",
+        }
+    }
+}
+
+impl RmbPolicy {
+    /// Why a resident of `home` clearly can't post here, if they can't. Regions that let only
+    /// some residents of embassy regions post, such as officers, are given the benefit of the
+    /// doubt.
+    pub(crate) fn refusal(&self, home: &str) -> Option<RmbRefusal> {
+        if self.embassy_rmb == "0" {
+            return Some(RmbRefusal::ResidentsOnly);
+        }
+
+        match self.embassies.get(home).map(Option::as_deref) {
+            None | Some(Some("denied" | "rejected")) => Some(RmbRefusal::NoEmbassy),
+            Some(Some("invited" | "requested" | "pending")) => Some(RmbRefusal::EmbassyPending),
+            // a closing embassy is still open
+            Some(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct World {
+    #[serde(rename = "HAPPENINGS", default)]
+    happenings: Happenings,
+}
+
+impl World {
+    /// Nations named in the founding happenings.
+    fn founders(&self) -> HashSet<String> {
+        self.happenings
+            .events
+            .iter()
+            .filter_map(|event| event.text.split("@@").nth(1))
+            .map(name::canonicalize)
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Happenings {
+    #[serde(rename = "EVENT", default)]
+    events: Vec<Event>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Event {
+    /// e.g. `@@testlandia@@ was founded in %%the_north_pacific%%.`
+    #[serde(rename = "TEXT")]
+    text: String,
+}
+
+/// Resolves `source` into the nations it currently stands for, in NS's order.
+#[tracing::instrument(skip(client, limiter))]
+pub(crate) async fn expand(
+    client: &reqwest::Client,
+    url: &str,
+    limiter: &ratelimiter::Sender,
+    source: &RecipientSource,
+) -> Result<Vec<String>, Error> {
+    let region = name::canonicalize(&source.region);
+
+    if !name::is_valid(&region) {
+        return Err(Error::InvalidRegion);
+    }
+
+    let body = get(
+        client,
+        url,
+        limiter,
+        &[("region", &region), ("q", "nations")],
+    )
+    .await?;
+    let mut nations = split(&de::from_str::<Region>(&body)?.nations, ':');
+
+    if let Some(since) = source.founded_since {
+        let founders = founded_since(client, url, limiter, &region, since).await?;
+
+        // founders that have moved on since aren't in the region anymore
+        nations.retain(|nation| founders.contains(nation));
+    }
+
+    if source.non_wa {
+        let body = get(
+            client,
+            url,
+            limiter,
+            &[("region", &region), ("q", "wanations")],
+        )
+        .await?;
+        let members: HashSet<_> = split(&de::from_str::<Region>(&body)?.wa_nations, ',')
+            .into_iter()
+            .collect();
+
+        nations.retain(|nation| !members.contains(nation));
+    }
+
+    Ok(nations)
+}
+
+/// The embassies of `region` and who from them may post on its RMB.
+#[tracing::instrument(skip(client, limiter))]
+pub(crate) async fn rmb_policy(
+    client: &reqwest::Client,
+    url: &str,
+    limiter: &ratelimiter::Sender,
+    region: &str,
+) -> Result<RmbPolicy, Error> {
+    let body = get(
+        client,
+        url,
+        limiter,
+        &[("region", region), ("q", "embassies+embassyrmb")],
+    )
+    .await?;
+
+    Ok(policy(de::from_str::<Region>(&body)?))
+}
+
+/// The region `nation` resides in, canonicalized.
+#[tracing::instrument(skip(client, limiter))]
+pub(crate) async fn home_region(
+    client: &reqwest::Client,
+    url: &str,
+    limiter: &ratelimiter::Sender,
+    nation: &str,
+) -> Result<String, Error> {
+    let body = get(client, url, limiter, &[("nation", nation), ("q", "region")]).await?;
+
+    Ok(name::canonicalize(&de::from_str::<Nation>(&body)?.region))
+}
+
+/// A missing `EMBASSYRMB` is taken as unknown rather than as residents only.
+fn policy(region: Region) -> RmbPolicy {
+    RmbPolicy {
+        embassies: region
+            .embassies
+            .embassies
+            .into_iter()
+            .map(|embassy| (name::canonicalize(&embassy.region), embassy.kind))
+            .collect(),
+        embassy_rmb: region.embassy_rmb.unwrap_or_default(),
+    }
+}
+
+/// Nations founded, or refounded, in `region` since `since`. NS only reports the latest 200.
+async fn founded_since(
+    client: &reqwest::Client,
+    url: &str,
+    limiter: &ratelimiter::Sender,
+    region: &str,
+    since: DateTime<Utc>,
+) -> Result<HashSet<String>, Error> {
+    let body = get(
+        client,
+        url,
+        limiter,
+        &[
+            ("q", "happenings"),
+            ("view", &format!("region.{}", region)),
+            ("filter", "founding"),
+            ("sincetime", &since.timestamp().to_string()),
+            ("limit", "200"),
+        ],
+    )
+    .await?;
+
+    Ok(de::from_str::<World>(&body)?.founders())
+}
+
+async fn get(
+    client: &reqwest::Client,
+    url: &str,
+    limiter: &ratelimiter::Sender,
+    query: &[(&str, &str)],
+) -> Result<String, Error> {
+    if let Err(duration) = limiter.acquire(Target::Standard).await {
+        tokio::time::sleep(duration).await;
+    }
+
+    let resp = client.get(url).query(query).send().await?;
+
+    if resp.status() == StatusCode::NOT_FOUND {
+        return Err(Error::RegionNotFound);
+    }
+
+    Ok(resp.error_for_status()?.text().await?)
+}
+
+fn split(list: &str, separator: char) -> Vec<String> {
+    list.split(separator)
+        .map(str::trim)
+        .filter(|nation| !nation.is_empty())
+        .map(name::canonicalize)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_lists() {
+        let region: Region = de::from_str(
+            "<REGION id=\"the_north_pacific\"><NATIONS>testlandia:Upper Canadan Empire:</NATIONS></REGION>",
+        )
+        .unwrap();
+
+        assert_eq!(
+            split(&region.nations, ':'),
+            vec!["testlandia", "upper_canadan_empire"]
+        );
+
+        let region: Region = de::from_str(
+            "<REGION id=\"the_north_pacific\"><UNNATIONS>testlandia,europeia</UNNATIONS></REGION>",
+        )
+        .unwrap();
+
+        assert_eq!(
+            split(&region.wa_nations, ','),
+            vec!["testlandia", "europeia"]
+        );
+
+        let region: Region =
+            de::from_str("<REGION id=\"empty\"><NATIONS></NATIONS></REGION>").unwrap();
+
+        assert!(split(&region.nations, ':').is_empty());
+    }
+
+    fn policy_of(xml: &str) -> RmbPolicy {
+        policy(de::from_str(xml).unwrap())
+    }
+
+    #[test]
+    fn test_rmb_policy() {
+        let policy = policy_of(
+            "<REGION id=\"the_north_pacific\"><EMBASSIES>\
+            <EMBASSY>Europeia</EMBASSY>\
+            <EMBASSY type=\"closing\">Lazarus</EMBASSY>\
+            <EMBASSY type=\"pending\">The South Pacific</EMBASSY>\
+            <EMBASSY type=\"rejected\">Osiris</EMBASSY>\
+            </EMBASSIES><EMBASSYRMB>con</EMBASSYRMB></REGION>",
+        );
+
+        assert_eq!(policy.refusal("europeia"), None);
+        assert_eq!(policy.refusal("lazarus"), None);
+        assert_eq!(
+            policy.refusal("the_south_pacific"),
+            Some(RmbRefusal::EmbassyPending)
+        );
+        assert_eq!(policy.refusal("osiris"), Some(RmbRefusal::NoEmbassy));
+        assert_eq!(policy.refusal("balder"), Some(RmbRefusal::NoEmbassy));
+
+        let policy = policy_of(
+            "<REGION id=\"closed\"><EMBASSIES><EMBASSY>Europeia</EMBASSY></EMBASSIES>\
+            <EMBASSYRMB>0</EMBASSYRMB></REGION>",
+        );
+
+        assert_eq!(policy.refusal("europeia"), Some(RmbRefusal::ResidentsOnly));
+
+        let policy = policy_of(
+            "<REGION id=\"unknown\"><EMBASSIES><EMBASSY>Europeia</EMBASSY></EMBASSIES></REGION>",
+        );
+
+        assert_eq!(policy.refusal("europeia"), None);
+    }
+
+    #[test]
+    fn test_home_region() {
+        let nation: Nation =
+            de::from_str("<NATION id=\"testlandia\"><REGION>Testregionia</REGION></NATION>")
+                .unwrap();
+
+        assert_eq!(nation.region, "Testregionia");
+    }
+
+    #[test]
+    fn test_founding_happenings() {
+        let world: World = de::from_str(
+            "<WORLD><HAPPENINGS>\
+            <EVENT id=\"2\"><TIMESTAMP>1700000100</TIMESTAMP><TEXT>@@new_nation@@ was founded in %%the_north_pacific%%.</TEXT></EVENT>\
+            <EVENT id=\"1\"><TIMESTAMP>1700000000</TIMESTAMP><TEXT>@@Old Nation@@ was refounded in %%the_north_pacific%%.</TEXT></EVENT>\
+            </HAPPENINGS></WORLD>",
+        )
+        .unwrap();
+
+        assert_eq!(
+            world.founders(),
+            HashSet::from(["new_nation".to_string(), "old_nation".to_string()])
+        );
+
+        let world: World = de::from_str("<WORLD><HAPPENINGS></HAPPENINGS></WORLD>").unwrap();
+
+        assert!(world.founders().is_empty());
+    }
+}
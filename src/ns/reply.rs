@@ -0,0 +1,281 @@
+//! NS's answers to the prepare and execute steps of private commands. NS isn't consistent about
+//! them: tag names come in either case, some answers carry more elements than `SUCCESS` or
+//! `ERROR`, and while the API is down it serves an HTML page instead.
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::ns::error::NsError;
+
+/// How much of an unreadable body is kept in the error.
+const EXCERPT_LENGTH: usize = 200;
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Reply {
+    pub(crate) success: Option<String>,
+    pub(crate) error: Option<String>,
+    /// any other elements, as `(tag, text)`, kept for diagnostics
+    pub(crate) raw: Vec<(String, String)>,
+}
+
+impl Reply {
+    /// The text of `SUCCESS`, or the classified `ERROR`.
+    pub(crate) fn into_result(self) -> Result<String, NsError> {
+        if !self.raw.is_empty() {
+            tracing::debug!("additional elements in NS reply: {:?}", self.raw);
+        }
+
+        match (self.success, self.error) {
+            (Some(success), _) => Ok(success),
+            (None, Some(error)) => Err(NsError::from_text(&error)),
+            (None, None) => Err(NsError::Unknown(format!(
+                "NS replied without SUCCESS or ERROR: {:?}",
+                self.raw
+            ))),
+        }
+    }
+
+    fn capture(&mut self, tag: String, text: String) {
+        match tag.as_str() {
+            "SUCCESS" => self.success = Some(text),
+            "ERROR" => self.error = Some(text),
+            _ => self.raw.push((tag, text)),
+        }
+    }
+}
+
+/// Reads the elements of the root, e.g. `<NATION>`, of an answer to a private command. Anything
+/// that isn't XML is taken for an outage page and fails with [`NsError::Unavailable`].
+pub(crate) fn parse(body: &str) -> Result<Reply, NsError> {
+    let body = body.trim_start_matches('\u{feff}').trim();
+
+    if !is_xml(body) {
+        tracing::warn!(
+            "NS replied with something other than XML: {}",
+            excerpt(body)
+        );
+
+        return Err(NsError::Unavailable);
+    }
+
+    let unreadable = |reason: String| {
+        NsError::Unknown(format!(
+            "Unreadable NS reply ({}): {}",
+            reason,
+            excerpt(body)
+        ))
+    };
+
+    let mut reader = Reader::from_str(body);
+    let mut reply = Reply::default();
+    let mut depth = 0;
+    // tag and text of the element being captured, with the depth it was opened at
+    let mut current: Option<(String, String, usize)> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                depth += 1;
+
+                let tag = tag_name(start.name().as_ref());
+
+                if current.is_none() && captures(depth, &tag) {
+                    current = Some((tag, String::new(), depth));
+                }
+            }
+            Ok(Event::Empty(start)) => {
+                let tag = tag_name(start.name().as_ref());
+
+                if current.is_none() && captures(depth + 1, &tag) {
+                    reply.capture(tag, String::new());
+                }
+            }
+            Ok(Event::Text(text)) => {
+                if let Some((_, value, _)) = &mut current {
+                    value.push_str(&text.unescape().map_err(|e| unreadable(e.to_string()))?);
+                }
+            }
+            Ok(Event::CData(data)) => {
+                if let Some((_, value, _)) = &mut current {
+                    value.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Ok(Event::End(_)) => {
+                if let Some((tag, value, _)) = current.take_if(|(_, _, at)| *at == depth) {
+                    reply.capture(tag, value.trim().to_string());
+                }
+
+                depth -= 1;
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => return Err(unreadable(e.to_string())),
+        }
+    }
+
+    if depth != 0 {
+        return Err(unreadable("cut short".to_string()));
+    }
+
+    Ok(reply)
+}
+
+/// The root's children are captured, and the root itself if NS sent `SUCCESS` or `ERROR` bare.
+fn captures(depth: usize, tag: &str) -> bool {
+    depth == 2 || (depth == 1 && matches!(tag, "SUCCESS" | "ERROR"))
+}
+
+fn tag_name(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).to_uppercase()
+}
+
+fn is_xml(body: &str) -> bool {
+    let start = body
+        .get(..body.len().min(16))
+        .unwrap_or_default()
+        .to_lowercase();
+
+    start.starts_with('<') && !start.starts_with("<!doctype html") && !start.starts_with("<html")
+}
+
+fn excerpt(body: &str) -> String {
+    body.chars().take(EXCERPT_LENGTH).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replies as NS sent them, by file name.
+    const FIXTURES: &[(&str, &str)] = &[
+        ("prepare.xml", include_str!("fixtures/prepare.xml")),
+        ("add.xml", include_str!("fixtures/add.xml")),
+        ("edit.xml", include_str!("fixtures/edit.xml")),
+        ("remove.xml", include_str!("fixtures/remove.xml")),
+        ("rmbpost.xml", include_str!("fixtures/rmbpost.xml")),
+        (
+            "rate_limited.xml",
+            include_str!("fixtures/rate_limited.xml"),
+        ),
+        ("pin_expired.xml", include_str!("fixtures/pin_expired.xml")),
+        (
+            "invalid_password.xml",
+            include_str!("fixtures/invalid_password.xml"),
+        ),
+        (
+            "dispatch_missing.xml",
+            include_str!("fixtures/dispatch_missing.xml"),
+        ),
+        (
+            "region_password.xml",
+            include_str!("fixtures/region_password.xml"),
+        ),
+        (
+            "unknown_error.xml",
+            include_str!("fixtures/unknown_error.xml"),
+        ),
+        ("lowercase.xml", include_str!("fixtures/lowercase.xml")),
+        ("bom.xml", include_str!("fixtures/bom.xml")),
+        ("outage.html", include_str!("fixtures/outage.html")),
+    ];
+
+    fn fixture(name: &str) -> &'static str {
+        FIXTURES
+            .iter()
+            .find(|(fixture, _)| *fixture == name)
+            .map(|(_, body)| *body)
+            .unwrap()
+    }
+
+    fn classify(name: &str) -> Result<String, NsError> {
+        parse(fixture(name)).and_then(Reply::into_result)
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let expected: &[(&str, Result<&str, NsError>)] = &[
+            ("prepare.xml", Ok("a1b2c3d4e5")),
+            (
+                "add.xml",
+                Ok(
+                    "New factbook posted! <a href=\"/nation=testlandia/detail=factbook/id=2468013\">View your Factbook</a>",
+                ),
+            ),
+            (
+                "edit.xml",
+                Ok(
+                    "Factbook edited! <a href=\"/nation=testlandia/detail=factbook/id=2468013\">View your Factbook</a>",
+                ),
+            ),
+            ("remove.xml", Ok("Removed dispatch \"Testlandian News\".")),
+            (
+                "rmbpost.xml",
+                Ok(
+                    "<a href=\"/region=testregionia/page=display_region_rmb?postid=55501234#p55501234\">Your message</a> has been lodged.",
+                ),
+            ),
+            ("rate_limited.xml", Err(NsError::RateLimited)),
+            ("pin_expired.xml", Err(NsError::PinExpired)),
+            ("invalid_password.xml", Err(NsError::InvalidPassword)),
+            ("dispatch_missing.xml", Err(NsError::DispatchMissing)),
+            ("region_password.xml", Err(NsError::RegionPasswordRequired)),
+            (
+                "unknown_error.xml",
+                Err(NsError::Unknown("Dispatch title is too long.".to_string())),
+            ),
+            ("lowercase.xml", Ok("a1b2c3d4e5")),
+            ("bom.xml", Ok("a1b2c3d4e5")),
+            ("outage.html", Err(NsError::Unavailable)),
+        ];
+
+        assert_eq!(expected.len(), FIXTURES.len());
+
+        for (name, result) in expected {
+            assert_eq!(
+                classify(name),
+                result.clone().map(str::to_string),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_additional_elements_kept() {
+        let reply = parse(fixture("add.xml")).unwrap();
+
+        assert_eq!(
+            reply.raw,
+            vec![("DISPATCHID".to_string(), "2468013".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_bare_and_broken_replies() {
+        assert_eq!(
+            classify_body("<SUCCESS>token</SUCCESS>"),
+            Ok("token".to_string())
+        );
+        assert_eq!(classify_body(""), Err(NsError::Unavailable));
+        assert_eq!(
+            classify_body("Service Temporarily Unavailable"),
+            Err(NsError::Unavailable)
+        );
+
+        for body in [
+            "<NATION><SUCCESS>token</SUCCESS>",
+            "<NATION><SUCCESS>token</ERROR></NATION>",
+            "<NATION><OTHER>x</OTHER></NATION>",
+        ] {
+            assert!(
+                matches!(classify_body(body), Err(NsError::Unknown(_))),
+                "{}",
+                body
+            );
+        }
+    }
+
+    fn classify_body(body: &str) -> Result<String, NsError> {
+        parse(body).and_then(Reply::into_result)
+    }
+}
@@ -1,14 +1,25 @@
 use super::types::{Mode, Prepared, PrivateCommand, Unprepared};
 use crate::core::error::Error;
+use crate::types::request::RequestId;
+use crate::types::response::PipelineStatus;
+use crate::workers::Control;
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
 use tokio::sync::oneshot;
 
-#[derive(Debug, Clone, Deserialize)]
-pub(crate) struct NewRmbPost {
-    pub(crate) nation: String,
-    pub(crate) region: String,
-    pub(crate) text: String,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewRmbPost {
+    pub nation: String,
+    pub region: String,
+    pub text: String,
+}
+
+/// The same post to several regions, from `POST /rmbposts/batch`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewRmbPostBatch {
+    pub nation: String,
+    pub regions: Vec<String>,
+    pub text: String,
 }
 
 #[derive(Clone, Debug)]
@@ -17,6 +28,8 @@ pub(crate) struct IntermediateRmbPost {
     pub(crate) nation: String,
     pub(crate) region: String,
     pub(crate) text: String,
+    /// request that queued the job, for log correlation
+    pub(crate) request_id: Option<String>,
 }
 
 impl IntermediateRmbPost {
@@ -26,10 +39,19 @@ impl IntermediateRmbPost {
             nation,
             region,
             text,
+            request_id: None,
         }
     }
+
+    pub(crate) fn with_request_id(mut self, request_id: &RequestId) -> Self {
+        self.request_id = Some(request_id.0.clone());
+        self
+    }
 }
 
+/// The `rmbpost` private command. NS has no private command for editing or suppressing RMB
+/// posts (suppression is only available to regional officers through the site), so posts
+/// can't be changed through eurocore once they're made.
 #[derive(Clone, Debug, Serialize)]
 pub(crate) struct RmbPost<T: Serialize + PrivateCommand> {
     #[serde(rename = "c")]
@@ -43,12 +65,6 @@ pub(crate) struct RmbPost<T: Serialize + PrivateCommand> {
     _state: PhantomData<T>,
 }
 
-impl<T: Serialize + PrivateCommand> RmbPost<T> {
-    pub(crate) fn nation(&self) -> &str {
-        &self.nation
-    }
-}
-
 impl RmbPost<Unprepared> {
     fn new(nation: String, region: String, text: String) -> Self {
         Self {
@@ -83,7 +99,14 @@ impl From<IntermediateRmbPost> for RmbPost<Unprepared> {
 
 #[derive(Debug)]
 pub(crate) enum Action {
-    Queue { post: IntermediateRmbPost },
+    Queue {
+        post: IntermediateRmbPost,
+    },
+    Control(Control),
+    /// Check a nation's password with NS and clear its unhealthy flag if it works.
+    Revalidate {
+        nation: String,
+    },
 }
 
 impl Action {
@@ -108,4 +131,5 @@ impl Command {
 pub(crate) enum Response {
     Success,
     Error(Error),
+    Pipeline(PipelineStatus),
 }
@@ -1,25 +1,45 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use tokio::sync::oneshot;
 
+use crate::core::authorization::Claim;
+use crate::core::error::Error;
+use crate::ns::dump;
+use crate::ns::error::NsError;
 use crate::types::response;
+use crate::types::secret;
+use crate::types::{AuthorizedUser, Secret, Username};
+use crate::utils::name;
+use crate::workers::Control;
+use crate::workers::telegram::ClientKeys;
 
 #[derive(Clone, Debug, Serialize)]
 pub(crate) struct Telegram {
+    #[serde(skip)]
+    pub(crate) job_id: i32,
     #[serde(skip)]
     pub(crate) sender: String,
     #[serde(rename = "a")]
     action: String,
-    #[serde(rename = "client")]
-    client_key: String,
+    #[serde(rename = "client", serialize_with = "secret::expose")]
+    client_key: Secret,
     #[serde(rename = "tgid")]
     pub(crate) telegram_id: String,
-    #[serde(rename = "key")]
-    secret_key: String,
+    #[serde(rename = "key", serialize_with = "secret::expose")]
+    secret_key: Secret,
     #[serde(rename = "to")]
     pub(crate) recipient: String,
     #[serde(skip)]
     pub(crate) tg_type: TgType,
+    #[serde(skip)]
+    pub(crate) queued_at: DateTime<Utc>,
+    /// set for telegrams fed in from a campaign's recipient list
+    #[serde(skip)]
+    pub(crate) campaign_id: Option<i32>,
+    /// the user who queued the telegram, or who created its campaign
+    #[serde(skip)]
+    pub(crate) created_by: Username,
 }
 
 impl std::fmt::Display for Telegram {
@@ -36,25 +56,99 @@ impl Telegram {
         }
     }
 
-    pub(crate) fn from_params(client_key: &str, params: Params) -> Self {
-        Self {
+    /// Returns `None` if no client key is configured for `params.sender`.
+    pub(crate) fn from_params(client_keys: &ClientKeys, job: Job) -> Option<Self> {
+        let Job {
+            id,
+            params,
+            created_by,
+        } = job;
+
+        let client_key = client_keys.get(&params.sender)?;
+
+        Some(Self {
+            job_id: id,
             sender: params.sender,
             action: "sendTG".to_string(),
-            client_key: client_key.to_string(),
+            client_key: client_key.clone(),
             telegram_id: params.id,
             secret_key: params.secret_key,
             recipient: params.recipient,
             tg_type: params.tg_type,
+            queued_at: Utc::now(),
+            campaign_id: None,
+            created_by,
+        })
+    }
+}
+
+/// Whose queued telegrams a delete may remove.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum DeleteScope {
+    Any,
+    /// telegrams from these sender nations, a tenant's
+    Senders(Vec<String>),
+    Own(Username),
+}
+
+impl DeleteScope {
+    /// Users may only delete telegrams they queued themselves, unless they hold
+    /// `telegrams.delete.any`.
+    pub(crate) fn for_user(user: &AuthorizedUser) -> Self {
+        if user.holds(Claim::TelegramsDeleteAny) {
+            DeleteScope::Any
+        } else {
+            DeleteScope::Own(user.username.clone())
+        }
+    }
+
+    pub(crate) fn allows(&self, telegram: &Telegram) -> bool {
+        match self {
+            DeleteScope::Any => true,
+            DeleteScope::Senders(senders) => senders.contains(&telegram.sender),
+            DeleteScope::Own(username) => telegram.created_by == *username,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub(crate) enum TgType {
+/// What NS answers a successful `sendTG` with.
+const SENT: &str = "queued";
+
+/// NS answers `sendTG` with 200 either way, so success is told apart by the body alone.
+pub(crate) fn check_sent(body: &str) -> Result<(), NsError> {
+    let body = body.trim();
+
+    if body.eq_ignore_ascii_case(SENT) {
+        Ok(())
+    } else {
+        Err(NsError::from_text(body))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TgType {
     Recruitment,
     Standard,
 }
 
+impl TgType {
+    pub(crate) fn from_column(value: &str) -> Self {
+        match value {
+            "recruitment" => TgType::Recruitment,
+            _ => TgType::Standard,
+        }
+    }
+}
+
+impl std::fmt::Display for TgType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TgType::Recruitment => write!(f, "recruitment"),
+            TgType::Standard => write!(f, "standard"),
+        }
+    }
+}
+
 impl Serialize for TgType {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -82,19 +176,179 @@ impl<'de> Deserialize<'de> for TgType {
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub(crate) struct Params {
-    pub(crate) sender: String,
-    pub(crate) id: String,
-    pub(crate) recipient: String,
-    pub(crate) secret_key: String,
-    pub(crate) tg_type: TgType,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Params {
+    pub sender: String,
+    pub id: String,
+    pub recipient: String,
+    /// write-only, never returned or logged
+    #[serde(serialize_with = "secret::expose")]
+    pub secret_key: Secret,
+    pub tg_type: TgType,
 }
 
-#[derive(Debug, Deserialize)]
-pub(crate) struct Header {
-    pub(crate) recipient: String,
-    pub(crate) telegram_id: String,
+/// Canonicalizes the names in a batch of telegrams and drops those with a recipient that isn't
+/// a valid nation name, e.g. a pasted `region:` token, and repeats of the same telegram to the
+/// same recipient. The rest keep their order, along with their position in `batch`.
+pub(crate) fn normalize(
+    batch: Vec<Params>,
+) -> (Vec<(usize, Params)>, Vec<response::DroppedTelegram>) {
+    let mut seen = HashSet::new();
+    let mut kept = Vec::new();
+    let mut dropped = Vec::new();
+
+    for (index, mut params) in batch.into_iter().enumerate() {
+        let recipient = name::canonicalize(params.recipient.trim());
+
+        let reason = if !name::is_valid(&recipient) {
+            "invalid_recipient"
+        } else if !seen.insert((recipient.clone(), params.id.clone())) {
+            "duplicate"
+        } else {
+            params.sender = name::canonicalize(&params.sender);
+            params.recipient = recipient;
+            kept.push((index, params));
+            continue;
+        };
+
+        dropped.push(response::DroppedTelegram {
+            index,
+            recipient: params.recipient,
+            telegram_id: params.id,
+            reason: reason.to_string(),
+        });
+    }
+
+    (kept, dropped)
+}
+
+/// A recruitment campaign: one telegram sent from `sender` to every nation on a recipient list.
+/// Recipients can be given as a list, as a newline or comma separated blob, or both.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewCampaign {
+    pub name: String,
+    pub sender: String,
+    pub telegram_id: String,
+    /// write-only, never returned or logged
+    #[serde(serialize_with = "secret::expose")]
+    pub secret_key: Secret,
+    pub tg_type: TgType,
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipients_text: Option<String>,
+    /// a region whose nations are added to the recipients when the campaign is created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipients_from: Option<RecipientSource>,
+    /// applied to every recipient, submitted or added from `recipients_from`
+    #[serde(default, skip_serializing_if = "RecipientFilter::is_empty")]
+    pub filter: RecipientFilter,
+    /// create the campaign paused, so its recipients can be reviewed before anything is sent
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// The nations of a region, optionally narrowed down, as resolved from the public API.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecipientSource {
+    pub region: String,
+    /// only nations founded in the region since then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub founded_since: Option<DateTime<Utc>>,
+    /// leave out World Assembly members
+    #[serde(default)]
+    pub non_wa: bool,
+}
+
+/// Recipients to leave out, as told by the nations dump. Nations missing from the dump, e.g.
+/// those founded since it was made, are kept.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecipientFilter {
+    /// leave out nations already in this region
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude_region: Option<String>,
+    /// leave out World Assembly members
+    #[serde(default)]
+    pub exclude_wa: bool,
+    /// leave out nations founded before then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub founded_after: Option<DateTime<Utc>>,
+}
+
+impl RecipientFilter {
+    /// The filters, by the name their removals are reported under.
+    pub(crate) const NAMES: [&str; 3] = ["exclude_region", "exclude_wa", "founded_after"];
+
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The first filter that leaves out `recipient`, a canonical name.
+    pub(crate) fn excludes(&self, index: &dump::Index, recipient: &str) -> Option<&'static str> {
+        let nation = index.get(recipient)?;
+
+        if self
+            .exclude_region
+            .as_deref()
+            .is_some_and(|region| name::canonicalize(region) == index.region(nation))
+        {
+            Some("exclude_region")
+        } else if self.exclude_wa && nation.wa {
+            Some("exclude_wa")
+        } else if self
+            .founded_after
+            .is_some_and(|cutoff| nation.founded < cutoff)
+        {
+            Some("founded_after")
+        } else {
+            None
+        }
+    }
+}
+
+impl NewCampaign {
+    /// Canonical recipient names in submission order with duplicates dropped, and the entries
+    /// that aren't valid nation names.
+    pub(crate) fn recipients(&self) -> (Vec<String>, Vec<String>) {
+        let text = self.recipients_text.as_deref().unwrap_or_default();
+
+        let mut seen = HashSet::new();
+        let mut recipients = Vec::new();
+        let mut rejected = Vec::new();
+
+        for entry in self
+            .recipients
+            .iter()
+            .map(String::as_str)
+            .chain(text.split(['\n', ',']))
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let recipient = name::canonicalize(entry);
+
+            if !name::is_valid(&recipient) {
+                rejected.push(entry.to_string());
+            } else if seen.insert(recipient.clone()) {
+                recipients.push(recipient);
+            }
+        }
+
+        (recipients, rejected)
+    }
+}
+
+/// Telegram parameters that have been recorded in `telegram_queue` under `id`.
+#[derive(Debug)]
+pub(crate) struct Job {
+    pub(crate) id: i32,
+    pub(crate) params: Params,
+    pub(crate) created_by: Username,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Header {
+    pub recipient: String,
+    pub telegram_id: String,
 }
 
 impl PartialEq for Header {
@@ -110,30 +364,69 @@ pub(crate) struct Command {
 }
 
 impl Command {
-    pub(crate) fn new(action: Operation, tx: oneshot::Sender<Response>) -> Self {
+    pub(crate) fn queue(jobs: Vec<Job>, tx: oneshot::Sender<Response>) -> Self {
+        Self {
+            operation: Operation::Queue(jobs),
+            tx,
+        }
+    }
+
+    pub(crate) fn delete(
+        header: Header,
+        scope: DeleteScope,
+        tx: oneshot::Sender<Response>,
+    ) -> Self {
+        Self {
+            operation: Operation::Delete(header, scope),
+            tx,
+        }
+    }
+
+    pub(crate) fn delete_job(id: i32, scope: DeleteScope, tx: oneshot::Sender<Response>) -> Self {
+        Self {
+            operation: Operation::DeleteJob(id, scope),
+            tx,
+        }
+    }
+
+    pub(crate) fn list(created_by: Option<Username>, tx: oneshot::Sender<Response>) -> Self {
+        Self {
+            operation: Operation::List(created_by),
+            tx,
+        }
+    }
+
+    pub(crate) fn summary(tx: oneshot::Sender<Response>) -> Self {
+        Self {
+            operation: Operation::Summary,
+            tx,
+        }
+    }
+
+    pub(crate) fn stats(tx: oneshot::Sender<Response>) -> Self {
         Self {
-            operation: action,
+            operation: Operation::Stats,
             tx,
         }
     }
 
-    pub(crate) fn queue(params: Vec<Params>, tx: oneshot::Sender<Response>) -> Self {
+    pub(crate) fn start_campaign(id: i32, tx: oneshot::Sender<Response>) -> Self {
         Self {
-            operation: Operation::Queue(params),
+            operation: Operation::StartCampaign(id),
             tx,
         }
     }
 
-    pub(crate) fn delete(header: Header, tx: oneshot::Sender<Response>) -> Self {
+    pub(crate) fn pause_campaign(id: i32, tx: oneshot::Sender<Response>) -> Self {
         Self {
-            operation: Operation::Delete(header),
+            operation: Operation::PauseCampaign(id),
             tx,
         }
     }
 
-    pub(crate) fn list(tx: oneshot::Sender<Response>) -> Self {
+    pub(crate) fn control(control: Control, tx: oneshot::Sender<Response>) -> Self {
         Self {
-            operation: Operation::List,
+            operation: Operation::Control(control),
             tx,
         }
     }
@@ -141,14 +434,271 @@ impl Command {
 
 #[derive(Debug)]
 pub(crate) enum Operation {
-    Queue(Vec<Params>),
-    Delete(Header),
-    List,
+    Queue(Vec<Job>),
+    Delete(Header, DeleteScope),
+    DeleteJob(i32, DeleteScope),
+    /// Queued telegrams, only those queued by the given user if set.
+    List(Option<Username>),
+    /// Queue sizes and cooldowns per sender nation.
+    Summary,
+    /// Recruitment telegram capacity per sender nation.
+    Stats,
+    /// Starts feeding a campaign's recipients into the queue, or resumes a paused one.
+    StartCampaign(i32),
+    /// Stops feeding a campaign and takes its unsent telegrams back out of the queue.
+    PauseCampaign(i32),
+    Control(Control),
 }
 
 #[derive(Debug)]
 pub(crate) enum Response {
     Ok,
-    // Error(Error),
+    Error(Error),
     List(HashMap<String, Vec<response::Telegram>>),
+    Summary(BTreeMap<String, response::TelegramSenderSummary>),
+    Stats(BTreeMap<String, response::TelegramCapacity>),
+    Pipeline(response::PipelineStatus),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_sent_fixtures() {
+        let fixtures = [
+            ("queued\n", Ok(())),
+            (
+                "Client not registered for API.",
+                Err(NsError::ClientKeyInvalid),
+            ),
+            ("No such telegram.", Err(NsError::TelegramInvalid)),
+            ("Incorrect secret key.", Err(NsError::TelegramInvalid)),
+            (
+                "Unknown nation: \"testlandia\".",
+                Err(NsError::RecipientMissing),
+            ),
+            (
+                "Testlandia is not accepting recruitment telegrams.",
+                Err(NsError::RecruitmentBlocked),
+            ),
+            (
+                "Testlandia's region does not accept recruitment telegrams.",
+                Err(NsError::RegionRecruitmentBlocked),
+            ),
+            ("API rate limit exceeded.", Err(NsError::RateLimited)),
+            (
+                "API Recruitment TG rate-limit exceeded.",
+                Err(NsError::RecruitmentRateLimited),
+            ),
+            (
+                "Something else.",
+                Err(NsError::Unknown("Something else.".to_string())),
+            ),
+        ];
+
+        for (body, expected) in fixtures {
+            assert_eq!(check_sent(body), expected, "{:?}", body);
+        }
+    }
+
+    #[test]
+    fn test_campaign_recipients() {
+        let campaign = NewCampaign {
+            name: "founders".to_string(),
+            sender: "recruiter".to_string(),
+            telegram_id: "1".to_string(),
+            secret_key: Secret::new("secret"),
+            tg_type: TgType::Recruitment,
+            recipients: vec!["Testlandia".to_string(), " ".to_string()],
+            recipients_text: Some(
+                "The North Pacific, testlandia\r\nnew_nation,,\nbad&name\n".to_string(),
+            ),
+            recipients_from: None,
+            filter: RecipientFilter::default(),
+            paused: false,
+        };
+
+        let (recipients, rejected) = campaign.recipients();
+
+        assert_eq!(
+            recipients,
+            vec!["testlandia", "the_north_pacific", "new_nation"]
+        );
+        assert_eq!(rejected, vec!["bad&name"]);
+    }
+
+    #[test]
+    fn test_recipient_filter() {
+        let index = dump::parse(
+            "<NATIONS>
+            <NATION><NAME>Local</NAME><REGION>Europeia</REGION><UNSTATUS>Non-member</UNSTATUS><FOUNDEDTIME>1700000000</FOUNDEDTIME></NATION>
+            <NATION><NAME>Member</NAME><REGION>Elsewhere</REGION><UNSTATUS>WA Member</UNSTATUS><FOUNDEDTIME>1700000000</FOUNDEDTIME></NATION>
+            <NATION><NAME>Old</NAME><REGION>Elsewhere</REGION><UNSTATUS>Non-member</UNSTATUS><FOUNDEDTIME>0</FOUNDEDTIME></NATION>
+            <NATION><NAME>Target</NAME><REGION>Elsewhere</REGION><UNSTATUS>Non-member</UNSTATUS><FOUNDEDTIME>1700000000</FOUNDEDTIME></NATION>
+            </NATIONS>"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let filter = RecipientFilter {
+            exclude_region: Some("Europeia".to_string()),
+            exclude_wa: true,
+            founded_after: DateTime::from_timestamp(1600000000, 0),
+        };
+
+        assert_eq!(filter.excludes(&index, "local"), Some("exclude_region"));
+        assert_eq!(filter.excludes(&index, "member"), Some("exclude_wa"));
+        assert_eq!(filter.excludes(&index, "old"), Some("founded_after"));
+        assert_eq!(filter.excludes(&index, "target"), None);
+        assert_eq!(filter.excludes(&index, "not_in_dump"), None);
+
+        assert!(RecipientFilter::default().is_empty());
+        assert!(!filter.is_empty());
+        assert_eq!(RecipientFilter::default().excludes(&index, "member"), None);
+    }
+
+    fn params(recipient: &str, id: &str) -> Params {
+        Params {
+            sender: "Recruiter".to_string(),
+            id: id.to_string(),
+            recipient: recipient.to_string(),
+            secret_key: Secret::new("secret"),
+            tg_type: TgType::Recruitment,
+        }
+    }
+
+    #[test]
+    fn test_normalize_batch() {
+        let (kept, dropped) = normalize(vec![
+            params("Testlandia", "1"),
+            params(" testlandia ", "1"),
+            params("testlandia", "2"),
+            params("", "1"),
+            params("region:the_north_pacific", "1"),
+            params("New Nation", "1"),
+        ]);
+
+        assert_eq!(
+            kept.iter()
+                .map(|(index, params)| (*index, params.recipient.as_str(), params.id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, "testlandia", "1"),
+                (2, "testlandia", "2"),
+                (5, "new_nation", "1")
+            ]
+        );
+        assert!(kept.iter().all(|(_, params)| params.sender == "recruiter"));
+
+        assert_eq!(
+            dropped
+                .iter()
+                .map(|dropped| (
+                    dropped.index,
+                    dropped.recipient.as_str(),
+                    dropped.reason.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, " testlandia ", "duplicate"),
+                (3, "", "invalid_recipient"),
+                (4, "region:the_north_pacific", "invalid_recipient"),
+            ]
+        );
+    }
+
+    fn user(username: &str, claims: &[&str]) -> AuthorizedUser {
+        AuthorizedUser {
+            id: 1,
+            username: username.to_string(),
+            password_hash: String::new(),
+            claims: claims.iter().map(|claim| claim.to_string()).collect(),
+            created_at: Utc::now(),
+            tenant_id: crate::types::DEFAULT_TENANT,
+            api_key_id: None,
+        }
+    }
+
+    fn queued_by(created_by: &str) -> Telegram {
+        let keys = ClientKeys::new(Some("key".to_string()), None).unwrap();
+
+        Telegram::from_params(
+            &keys,
+            Job {
+                id: 1,
+                params: Params {
+                    sender: "recruiter".to_string(),
+                    id: "1".to_string(),
+                    recipient: "testlandia".to_string(),
+                    secret_key: Secret::new("secret"),
+                    tg_type: TgType::Recruitment,
+                },
+                created_by: created_by.to_string(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_keys_redacted() {
+        let keys = ClientKeys::new(Some("client-key".to_string()), None).unwrap();
+        let params = || Params {
+            secret_key: Secret::new("secret-key"),
+            ..params("testlandia", "1")
+        };
+        let job = || Job {
+            id: 1,
+            params: params(),
+            created_by: "recruiter".to_string(),
+        };
+        let telegram = Telegram::from_params(&keys, job()).unwrap();
+        let (tx, _) = oneshot::channel();
+
+        for debug in [
+            format!("{:?}", params()),
+            format!("{:?}", job()),
+            format!("{telegram:?}"),
+            format!("{:?}", Command::queue(vec![job()], tx)),
+        ] {
+            assert!(!debug.contains("secret-key"), "{debug}");
+            assert!(!debug.contains("client-key"), "{debug}");
+        }
+
+        // NS still gets both
+        let query = serde_urlencoded::to_string(&telegram).unwrap();
+        assert!(query.contains("client=client-key"), "{query}");
+        assert!(query.contains("key=secret-key"), "{query}");
+    }
+
+    #[test]
+    fn test_delete_own_telegrams() {
+        let scope = DeleteScope::for_user(&user("alice", &["telegrams.delete"]));
+
+        assert_eq!(scope, DeleteScope::Own("alice".to_string()));
+        assert!(scope.allows(&queued_by("alice")));
+        assert!(!scope.allows(&queued_by("bob")));
+    }
+
+    #[test]
+    fn test_delete_any_telegram() {
+        let scope = DeleteScope::for_user(&user(
+            "alice",
+            &["telegrams.delete", "telegrams.delete.any"],
+        ));
+
+        assert_eq!(scope, DeleteScope::Any);
+        assert!(scope.allows(&queued_by("alice")));
+        assert!(scope.allows(&queued_by("bob")));
+    }
+
+    #[test]
+    fn test_delete_scope_is_per_user() {
+        let scope = DeleteScope::for_user(&user("bob", &["telegrams.delete", "telegrams.read"]));
+
+        assert!(scope.allows(&queued_by("bob")));
+        assert!(!scope.allows(&queued_by("alice")));
+        // usernames are compared exactly
+        assert!(!scope.allows(&queued_by("Bob")));
+    }
 }
@@ -1,22 +1,5 @@
 use serde::Serialize;
 
-enum Command {
-    Dispatch,
-    RmbPost,
-}
-
-impl Serialize for Command {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::ser::Serializer,
-    {
-        match self {
-            Command::Dispatch => serializer.serialize_str("dispatch"),
-            Command::RmbPost => serializer.serialize_str("rmbpost"),
-        }
-    }
-}
-
 #[derive(Clone, Debug)]
 pub(crate) enum Mode {
     Prepare,
@@ -1,38 +1,548 @@
-use axum::extract::{Extension, Json, Path, State};
+use axum::extract::State;
+use axum::http::StatusCode;
 use axum::response::IntoResponse;
+use serde_json::json;
+use std::collections::BTreeMap;
 use tracing::instrument;
 
+use crate::controllers::audit;
+use crate::core::authorization::{Authorized, Claim};
 use crate::core::error::Error;
+use crate::core::extract::{Json, Path, Query};
+use crate::core::logging;
 use crate::core::state::AppState;
 use crate::types::request;
+use crate::types::response::{
+    NationHealth, NationVerification, Permission, PipelineStatus, RatelimiterLimits,
+};
 use crate::types::{AuthorizedUser, Username};
+use crate::utils::name;
+use crate::workers::Control;
 
 #[instrument(skip_all)]
 pub(crate) async fn change_user_password(
     State(state): State<AppState>,
-    Extension(user): Extension<Option<AuthorizedUser>>,
+    Authorized(user): Authorized,
     Path(id): Path<i32>,
     Json(params): Json<request::UpdatePasswordData>,
 ) -> Result<impl IntoResponse, Error> {
-    match user {
-        Some(user) => {
-            if !user.claims.contains(&"admin".to_string()) {
-                return Err(Error::Unauthorized);
-            }
-        }
-        None => return Err(Error::Unauthorized),
-    }
-
-    let username: Username = match state.user_controller.get_username_by_id(id).await {
+    let username: Username = match state
+        .user_controller
+        .get_username_by_id(id, user.admin_scope())
+        .await
+    {
         Ok(Some(user)) => user,
         Ok(None) => return Err(Error::InvalidUsername),
         Err(e) => return Err(e),
     };
 
-    state
+    let result = state
         .user_controller
         .update_password(&username, &params.new_password)
-        .await?;
+        .await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "admin.password_reset", "user").target(id),
+        &result,
+    );
+
+    result?;
 
     Ok(Json("Password reset successfully"))
 }
+
+#[instrument(skip_all)]
+pub(crate) async fn users(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Query(query): Query<request::UsersQuery>,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json(
+        state
+            .user_controller
+            .list(&query, user.admin_scope())
+            .await?,
+    ))
+}
+
+/// Every claim there is, so admins can pick which to grant.
+#[instrument(skip_all)]
+pub(crate) async fn permissions() -> Json<Vec<Permission>> {
+    Json(
+        Claim::ALL
+            .iter()
+            .map(|claim| Permission {
+                claim: claim.as_str(),
+                description: claim.description(),
+            })
+            .collect(),
+    )
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn disable_user(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let result = state.user_controller.disable(id, user.admin_scope()).await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "admin.user_disable", "user").target(id),
+        &result,
+    );
+
+    Ok(Json(result?))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn audit(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Query(query): Query<request::AuditQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let entries = state
+        .audit_controller
+        .list(&query, user.admin_scope())
+        .await?;
+
+    Ok(Json(entries))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn pipelines(State(state): State<AppState>) -> Result<impl IntoResponse, Error> {
+    let mut statuses = Vec::new();
+
+    for pipeline in [
+        request::Pipeline::Dispatch,
+        request::Pipeline::Rmbpost,
+        request::Pipeline::Telegram,
+    ] {
+        statuses.push(control(&state, pipeline, Control::Status).await?);
+    }
+
+    Ok(Json(statuses))
+}
+
+/// What each worker is doing right now, by pipeline.
+#[instrument(skip_all)]
+pub(crate) async fn workers(State(state): State<AppState>) -> Result<impl IntoResponse, Error> {
+    Ok(Json(BTreeMap::from([
+        (
+            request::Pipeline::Dispatch.name(),
+            state.dispatch_controller.worker(),
+        ),
+        (
+            request::Pipeline::Rmbpost.name(),
+            state.rmbpost_controller.worker(),
+        ),
+        (
+            request::Pipeline::Telegram.name(),
+            state.telegram_controller.worker(),
+        ),
+    ])))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn retention(State(state): State<AppState>) -> Result<impl IntoResponse, Error> {
+    Ok(Json(state.retention_controller.status()))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn ratelimiter(State(state): State<AppState>) -> Result<impl IntoResponse, Error> {
+    Ok(Json(state.ratelimiter.inspect().await))
+}
+
+/// The rate limiter is shared by every tenant, so only operators configure it.
+#[instrument(skip_all)]
+pub(crate) async fn configure_ratelimiter(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Json(params): Json<request::RatelimiterPatch>,
+) -> Result<impl IntoResponse, Error> {
+    if !user.is_operator() {
+        return Err(Error::Unauthorized);
+    }
+
+    let previous = state.ratelimiter.limits();
+
+    let result = match previous.patched(&params) {
+        Ok(limits) => Ok(state.ratelimiter.configure(limits).await),
+        Err(e) => Err(e),
+    };
+
+    let event =
+        audit::Event::new(&user, "admin.ratelimiter_configure", "ratelimiter").summary(json!({
+            "from": RatelimiterLimits::from(previous),
+            "to": result.as_ref().ok().map(|status| &status.limits),
+        }));
+
+    state.audit_controller.record(event, &result);
+
+    Ok(Json(result?))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn logging() -> Result<impl IntoResponse, Error> {
+    Ok(Json(logging::status()?))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn configure_logging(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Json(params): Json<request::LoggingPatch>,
+) -> Result<impl IntoResponse, Error> {
+    if !user.is_operator() {
+        return Err(Error::Unauthorized);
+    }
+
+    let previous = logging::status().ok().map(|status| status.level);
+
+    let result = logging::set_level(&params.level);
+
+    let event = audit::Event::new(&user, "admin.logging_configure", "logging").summary(json!({
+        "from": previous,
+        "to": result.as_ref().ok().map(|status| &status.level),
+    }));
+
+    state.audit_controller.record(event, &result);
+
+    Ok(Json(result?))
+}
+
+/// Pipelines are shared by every tenant, so only operators pause and resume them.
+#[instrument(skip_all)]
+pub(crate) async fn pause_pipeline(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(pipeline): Path<request::Pipeline>,
+) -> Result<impl IntoResponse, Error> {
+    if !user.is_operator() {
+        return Err(Error::Unauthorized);
+    }
+
+    let result = control(&state, pipeline, Control::Pause).await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "admin.pipeline_pause", "pipeline").target(pipeline.name()),
+        &result,
+    );
+
+    Ok(Json(result?))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn resume_pipeline(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(pipeline): Path<request::Pipeline>,
+) -> Result<impl IntoResponse, Error> {
+    if !user.is_operator() {
+        return Err(Error::Unauthorized);
+    }
+
+    let result = control(&state, pipeline, Control::Resume).await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "admin.pipeline_resume", "pipeline").target(pipeline.name()),
+        &result,
+    );
+
+    Ok(Json(result?))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn revalidate_nation(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(nation): Path<String>,
+    Query(query): Query<request::RevalidateQuery>,
+) -> Result<impl IntoResponse, Error> {
+    check_nation(&state, &user, &nation).await?;
+
+    let result = revalidate(&state, &nation, query.ping).await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "admin.nation_revalidate", "nation").target(&nation),
+        &result,
+    );
+
+    Ok(Json(result?))
+}
+
+/// Dispatch and RMB post nations track health separately, so a nation configured for both is
+/// revalidated by both workers.
+async fn revalidate(state: &AppState, nation: &str, ping: bool) -> Result<NationHealth, Error> {
+    let dispatch = state.dispatch_controller.revalidate(nation, ping).await?;
+    let rmbpost = state.rmbpost_controller.revalidate(nation, ping).await?;
+
+    if !dispatch && !rmbpost {
+        return Err(Error::InvalidNation);
+    }
+
+    Ok(NationHealth {
+        nation: name::canonicalize(nation),
+        healthy: true,
+    })
+}
+
+/// Pings NS with the stored password of a nation, without touching its jobs or health unless NS
+/// rejects the password.
+#[instrument(skip_all)]
+pub(crate) async fn verify_nation(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(nation): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    check_nation(&state, &user, &nation).await?;
+
+    let result = verify(&state, &nation).await;
+
+    let mut event = audit::Event::new(&user, "admin.nation_verify", "nation").target(&nation);
+
+    if let Ok(verification) = &result {
+        event = event.summary(json!({
+            "verified": verification.verified,
+            "dispatch": verification.dispatch.as_ref().map(|check| check.error_code.unwrap_or("ok")),
+            "rmbpost": verification.rmbpost.as_ref().map(|check| check.error_code.unwrap_or("ok")),
+        }));
+    }
+
+    state.audit_controller.record(event, &result);
+
+    Ok(Json(result?))
+}
+
+async fn verify(state: &AppState, nation: &str) -> Result<NationVerification, Error> {
+    let dispatch = state.dispatch_controller.verify(nation).await?;
+    let rmbpost = state.rmbpost_controller.verify(nation).await?;
+
+    NationVerification::new(nation, dispatch, rmbpost).ok_or(Error::InvalidNation)
+}
+
+/// Operators reach every nation, the other tenants' admins only their own.
+async fn check_nation(state: &AppState, user: &AuthorizedUser, nation: &str) -> Result<(), Error> {
+    if user.is_operator() {
+        return Ok(());
+    }
+
+    state
+        .tenant_controller
+        .check_nation(user.tenant_id, &name::canonicalize(nation))
+        .await
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn preset(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(nation): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    check_nation(&state, &user, &nation).await?;
+
+    Ok(Json(state.dispatch_controller.preset(&nation).await?))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn set_preset(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(nation): Path<String>,
+    Json(params): Json<request::NationPreset>,
+) -> Result<impl IntoResponse, Error> {
+    check_nation(&state, &user, &nation).await?;
+
+    let event = audit::Event::new(&user, "admin.nation_preset_set", "nation")
+        .target(&nation)
+        .summary(json!({
+            "default_category": params.default_category,
+            "default_subcategory": params.default_subcategory,
+            "title_prefix": params.title_prefix,
+        }));
+
+    let result = state
+        .dispatch_controller
+        .set_preset(&user.username, &nation, params)
+        .await;
+
+    state.audit_controller.record(event, &result);
+
+    Ok(Json(result?))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn delete_preset(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(nation): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    check_nation(&state, &user, &nation).await?;
+
+    let result = state.dispatch_controller.delete_preset(&nation).await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "admin.nation_preset_delete", "nation").target(&nation),
+        &result,
+    );
+
+    result?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn content_rules(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json(state.content_policy.list().await?))
+}
+
+/// Content rules apply to every tenant's jobs, so only operators change them.
+#[instrument(skip_all)]
+pub(crate) async fn create_content_rule(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Json(params): Json<request::ContentRule>,
+) -> Result<impl IntoResponse, Error> {
+    if !user.is_operator() {
+        return Err(Error::Unauthorized);
+    }
+
+    let mut event =
+        audit::Event::new(&user, "admin.content_rule_create", "content_rule").summary(json!({
+            "name": params.name,
+            "action": params.action,
+        }));
+
+    let result = state.content_policy.create(&user.username, params).await;
+
+    if let Ok(rule) = &result {
+        event = event.target(rule.id);
+    }
+
+    state.audit_controller.record(event, &result);
+
+    Ok((StatusCode::CREATED, Json(result?)))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn update_content_rule(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+    Json(params): Json<request::ContentRule>,
+) -> Result<impl IntoResponse, Error> {
+    if !user.is_operator() {
+        return Err(Error::Unauthorized);
+    }
+
+    let event = audit::Event::new(&user, "admin.content_rule_update", "content_rule")
+        .target(id)
+        .summary(json!({
+            "name": params.name,
+            "action": params.action,
+        }));
+
+    let result = state.content_policy.update(id, params).await;
+
+    state.audit_controller.record(event, &result);
+
+    Ok(Json(result?))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn delete_content_rule(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    if !user.is_operator() {
+        return Err(Error::Unauthorized);
+    }
+
+    let result = state.content_policy.delete(id).await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "admin.content_rule_delete", "content_rule").target(id),
+        &result,
+    );
+
+    result?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Tenants are managed by the default tenant's admins, the others only run their own.
+#[instrument(skip_all)]
+pub(crate) async fn tenants(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+) -> Result<impl IntoResponse, Error> {
+    if !user.is_operator() {
+        return Err(Error::Unauthorized);
+    }
+
+    Ok(Json(state.tenant_controller.list().await?))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn create_tenant(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Json(params): Json<request::NewTenant>,
+) -> Result<impl IntoResponse, Error> {
+    if !user.is_operator() {
+        return Err(Error::Unauthorized);
+    }
+
+    let mut event = audit::Event::new(&user, "admin.tenant_create", "tenant").summary(json!({
+        "name": params.name,
+        "nations": params.nations,
+    }));
+
+    let result = state.tenant_controller.create(params).await;
+
+    if let Ok(tenant) = &result {
+        event = event.target(tenant.id);
+    }
+
+    state.audit_controller.record(event, &result);
+
+    Ok((StatusCode::CREATED, Json(result?)))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn set_user_tenant(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+    Json(params): Json<request::UserTenant>,
+) -> Result<impl IntoResponse, Error> {
+    if !user.is_operator() {
+        return Err(Error::Unauthorized);
+    }
+
+    let result = state.user_controller.set_tenant(id, params.tenant_id).await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "admin.user_tenant", "user")
+            .target(id)
+            .summary(json!({ "tenant_id": params.tenant_id })),
+        &result,
+    );
+
+    Ok(Json(result?))
+}
+
+async fn control(
+    state: &AppState,
+    pipeline: request::Pipeline,
+    control: Control,
+) -> Result<PipelineStatus, Error> {
+    match pipeline {
+        request::Pipeline::Dispatch => state.dispatch_controller.control(control).await,
+        request::Pipeline::Rmbpost => state.rmbpost_controller.control(control).await,
+        request::Pipeline::Telegram => state.telegram_controller.control(control).await,
+    }
+}
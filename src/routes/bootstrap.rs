@@ -0,0 +1,40 @@
+use axum::Json;
+use axum::extract::State;
+use axum::response::IntoResponse;
+
+use crate::core::authorization::Authorized;
+use crate::core::error::Error;
+use crate::core::state::AppState;
+use crate::ns::dispatch;
+use crate::types::response::{Bootstrap, Limits, SessionUser};
+
+/// Bumped when a field of [`Bootstrap`] changes or goes away, not when one is added.
+const VERSION: u32 = 1;
+
+/// The user, the nations they can post as, the dispatch categories, the server's limits and the
+/// nations' presets, in one request.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn get(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json(Bootstrap {
+        version: VERSION,
+        user: SessionUser::new(&user),
+        dispatch_nations: state
+            .tenant_controller
+            .own_nations(user.tenant_id, state.dispatch_controller.nations().await?)
+            .await?,
+        rmbpost_nations: state
+            .tenant_controller
+            .own_nations(user.tenant_id, state.rmbpost_controller.nations().await?)
+            .await?,
+        categories: dispatch::categories(),
+        limits: Limits {
+            dispatches: state.dispatch_controller.limits(),
+            rmbposts: state.rmbpost_controller.limits(),
+            quota: state.dispatch_controller.quota(&user),
+        },
+        presets: state.dispatch_controller.presets().await?,
+    }))
+}
@@ -1,105 +1,419 @@
 use axum::Extension;
-use axum::extract::{Json, Path, State};
-use axum::http::{StatusCode, header};
-use axum::response::IntoResponse;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde_json::json;
 
+use crate::controllers::{audit, idempotency};
+use crate::core::authorization::Authorized;
 use crate::core::error::Error;
+use crate::core::extract::{Json, Path, Query};
 use crate::core::state::AppState;
-use crate::ns::dispatch::{EditDispatch, NewDispatch};
-use crate::types::AuthorizedUser;
+use crate::ns::dispatch::{self, EditDispatch, MAX_TEXT_LENGTH, NewDispatch, NewDispatchGroup};
+use crate::routes::queue;
+use crate::types::request::{
+    DispatchFormat, DispatchMetricsQuery, DispatchQuery, DispatchSearchQuery, EditQuery,
+    ImportDispatch, NewDispatchQuery, PreviewData, RequestId,
+};
+use crate::types::{AuthorizedUser, response, tenant_scope};
+use crate::utils::encode::{self, encode};
+use crate::utils::etag;
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn get(
     State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
     Path(id): Path<i32>,
-) -> Result<impl IntoResponse, Error> {
-    let dispatch = state.dispatch_controller.get_one(id).await?;
+    Query(query): Query<DispatchQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let tenant = Some(tenant_scope(user.as_ref()));
+
+    let etag = etag(
+        &state
+            .dispatch_controller
+            .validator(Some(id), tenant)
+            .await?,
+        &query,
+    );
+
+    if etag::matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    let mut dispatch = state
+        .dispatch_controller
+        .get_one(id, query.include_inactive, tenant)
+        .await?;
+
+    if query.format == DispatchFormat::Html {
+        dispatch.render();
+    }
+
+    Ok(([(header::ETAG, etag)], Json(dispatch)).into_response())
+}
 
-    Ok(Json(dispatch))
+#[tracing::instrument(skip_all)]
+pub(crate) async fn metrics(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Path(id): Path<i32>,
+    Query(query): Query<DispatchMetricsQuery>,
+) -> Result<Json<response::DispatchMetrics>, Error> {
+    Ok(Json(
+        state
+            .dispatch_controller
+            .metrics(id, &query, tenant_scope(user.as_ref()))
+            .await?,
+    ))
 }
 
 #[tracing::instrument(skip_all)]
-pub(crate) async fn get_all(State(state): State<AppState>) -> Result<impl IntoResponse, Error> {
-    let dispatches = state.dispatch_controller.get(None).await?;
+pub(crate) async fn get_all(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Query(query): Query<DispatchQuery>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let tenant = Some(tenant_scope(user.as_ref()));
+
+    let etag = etag(
+        &state.dispatch_controller.validator(None, tenant).await?,
+        &query,
+    );
+
+    if etag::matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    if query.summary {
+        let summaries = state
+            .dispatch_controller
+            .get_summaries(None, query.include_inactive, tenant)
+            .await?;
+
+        return Ok(([(header::ETAG, etag)], Json(summaries)).into_response());
+    }
+
+    let mut dispatches = state
+        .dispatch_controller
+        .get(None, query.include_inactive, tenant)
+        .await?;
+
+    if query.format == DispatchFormat::Html {
+        dispatches.iter_mut().for_each(response::Dispatch::render);
+    }
 
-    Ok(Json(dispatches))
+    Ok(([(header::ETAG, etag)], Json(dispatches)).into_response())
 }
 
 #[tracing::instrument(skip_all)]
-pub(crate) async fn post(
+pub(crate) async fn search(
     State(state): State<AppState>,
     Extension(user): Extension<Option<AuthorizedUser>>,
+    Query(query): Query<DispatchSearchQuery>,
+) -> Result<Json<Vec<response::DispatchSearchResult>>, Error> {
+    Ok(Json(
+        state
+            .dispatch_controller
+            .search(&query, Some(tenant_scope(user.as_ref())))
+            .await?,
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn categories() -> Json<Vec<response::DispatchCategory>> {
+    Json(dispatch::categories())
+}
+
+/// The representation depends on the query as well as the stored dispatches.
+fn etag(validator: &str, query: &DispatchQuery) -> String {
+    etag::strong(&format!(
+        "{}-{}-{}-{}",
+        validator,
+        query.include_inactive as u8,
+        (query.format == DispatchFormat::Html) as u8,
+        query.summary as u8
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn post(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Extension(request_id): Extension<RequestId>,
+    headers: HeaderMap,
+    Query(query): Query<NewDispatchQuery>,
     Json(params): Json<NewDispatch>,
 ) -> Result<impl IntoResponse, Error> {
-    let user = match user {
-        Some(user) => {
-            if !user.claims.contains(&"dispatches.create".to_string()) {
-                return Err(Error::Unauthorized);
-            }
-
-            user
-        }
-        None => return Err(Error::Unauthorized),
-    };
+    let event = audit::Event::new(&user, "dispatch.create", "dispatch_job").summary(json!({
+        "nation": params.nation,
+        "title": params.title,
+        "category": params.category,
+        "subcategory": params.subcategory,
+    }));
 
-    let status = state.dispatch_controller.post(user, params).await?;
+    let key = idempotency::Key::from_headers(&headers, &user, "dispatch.create")?;
+
+    let result = state
+        .dispatch_controller
+        .post(user, params, query.allow_duplicate_title, key, &request_id)
+        .await;
+
+    state.audit_controller.record(
+        match &result {
+            Ok(submitted) => event
+                .target(submitted.inner().id)
+                .warnings(&submitted.inner().warnings),
+            Err(_) => event,
+        },
+        &result,
+    );
+
+    let submitted = result?;
 
     Ok((
-        StatusCode::ACCEPTED,
-        [(header::LOCATION, format!("/queue/dispatches/{}", status.id))],
-        Json(status),
+        submitted.status_code(),
+        [(header::LOCATION, submitted.inner().self_url.clone())],
+        queue::retry_after(submitted.inner().estimated_completion_at),
+        Json(submitted.into_inner()),
     ))
 }
 
+/// Posts the same dispatch as several nations, see `Controller::post_group`.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn post_group(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Extension(request_id): Extension<RequestId>,
+    headers: HeaderMap,
+    Json(params): Json<NewDispatchGroup>,
+) -> Result<impl IntoResponse, Error> {
+    let event =
+        audit::Event::new(&user, "dispatch.create_group", "dispatch_group").summary(json!({
+            "nations": params.nations,
+            "title": params.title,
+            "category": params.category,
+            "subcategory": params.subcategory,
+        }));
+
+    let key = idempotency::Key::from_headers(&headers, &user, "dispatch.create_group")?;
+
+    let result = state
+        .dispatch_controller
+        .post_group(user, params, key, &request_id)
+        .await;
+
+    state.audit_controller.record(
+        match &result {
+            Ok(submitted) => event.target(submitted.inner().id).warnings(
+                submitted
+                    .inner()
+                    .jobs
+                    .first()
+                    .map_or(&[], |job| job.warnings.as_slice()),
+            ),
+            Err(_) => event,
+        },
+        &result,
+    );
+
+    let submitted = result?;
+
+    Ok((
+        submitted.status_code(),
+        [(header::LOCATION, submitted.inner().self_url.clone())],
+        Json(submitted.into_inner()),
+    ))
+}
+
+/// Starts tracking a dispatch that was posted without eurocore, see `Controller::import`.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn import(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Json(params): Json<ImportDispatch>,
+) -> Result<impl IntoResponse, Error> {
+    let event = audit::Event::new(&user, "dispatch.import", "dispatch")
+        .target(params.dispatch_id)
+        .summary(json!({ "nation": params.nation }));
+
+    let result = state.dispatch_controller.import(&user, params).await;
+
+    state.audit_controller.record(event, &result);
+
+    let dispatch = result?;
+
+    Ok((
+        StatusCode::CREATED,
+        [(header::LOCATION, format!("/dispatches/{}", dispatch.id))],
+        Json(dispatch),
+    ))
+}
+
+/// Shows the text as it will be sent to NS, without queueing anything.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn preview(Json(data): Json<PreviewData>) -> Json<response::EncodingPreview> {
+    let text = encode(&data.text);
+
+    let converted = encode::conversions(&data.text)
+        .into_iter()
+        .map(|(character, count)| response::ConvertedCharacter {
+            entity: encode(character.encode_utf8(&mut [0; 4])),
+            character: character.to_string(),
+            count,
+        })
+        .collect();
+
+    Json(response::EncodingPreview {
+        length: text.len(),
+        max_length: MAX_TEXT_LENGTH,
+        converted,
+        text,
+    })
+}
+
 #[tracing::instrument(skip_all)]
 pub(crate) async fn put(
     State(state): State<AppState>,
-    Extension(user): Extension<Option<AuthorizedUser>>,
+    Authorized(user): Authorized,
+    Extension(request_id): Extension<RequestId>,
     Path(id): Path<i32>,
-    Json(params): Json<EditDispatch>,
+    Query(query): Query<EditQuery>,
+    headers: HeaderMap,
+    Json(mut params): Json<EditDispatch>,
+) -> Result<impl IntoResponse, Error> {
+    // the header takes precedence over the body
+    if let Some(revision) = etag::if_match_revision(&headers)? {
+        params.base_revision = Some(revision);
+    }
+
+    let event = audit::Event::new(&user, "dispatch.edit", "dispatch")
+        .target(id)
+        .summary(json!({
+            "title": params.title,
+            "category": params.category,
+            "subcategory": params.subcategory,
+            "base_revision": params.base_revision,
+            "coalesce": query.coalesce,
+        }));
+
+    let key = idempotency::Key::from_headers(&headers, &user, "dispatch.edit")?;
+
+    let result = state
+        .dispatch_controller
+        .put(user, id, params, query.coalesce, key, &request_id)
+        .await;
+
+    let event = match &result {
+        Ok(submitted) => event.warnings(&submitted.inner().warnings),
+        Err(_) => event,
+    };
+
+    state.audit_controller.record(event, &result);
+
+    let submitted = result?;
+
+    Ok((
+        submitted.status_code(),
+        [(header::LOCATION, submitted.inner().self_url.clone())],
+        queue::retry_after(submitted.inner().estimated_completion_at),
+        Json(submitted.into_inner()),
+    ))
+}
+
+/// Queues the content of a failed edit of the dispatch again. `If-Match` sets the revision it's
+/// based on, as with an edit.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn reapply(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Extension(request_id): Extension<RequestId>,
+    Path((id, job_id)): Path<(i32, i32)>,
+    headers: HeaderMap,
 ) -> Result<impl IntoResponse, Error> {
-    let user = match user {
-        Some(user) => {
-            if !user.claims.contains(&"dispatches.edit".to_string()) {
-                return Err(Error::Unauthorized);
-            }
-
-            user
-        }
-        None => return Err(Error::Unauthorized),
+    let base_revision = etag::if_match_revision(&headers)?;
+
+    let event = audit::Event::new(&user, "dispatch.reapply", "dispatch")
+        .target(id)
+        .summary(json!({
+            "job_id": job_id,
+            "base_revision": base_revision,
+        }));
+
+    let result = state
+        .dispatch_controller
+        .reapply(user, id, job_id, base_revision, &request_id)
+        .await;
+
+    let event = match &result {
+        Ok(submitted) => event.warnings(&submitted.inner().warnings),
+        Err(_) => event,
     };
 
-    let status = state.dispatch_controller.put(user, id, params).await?;
+    state.audit_controller.record(event, &result);
+
+    let submitted = result?.into_inner();
 
     Ok((
         StatusCode::ACCEPTED,
-        [(header::LOCATION, format!("/queue/dispatches/{}", status.id))],
-        Json(status),
+        [(header::LOCATION, submitted.self_url.clone())],
+        Json(submitted),
     ))
 }
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn delete(
     State(state): State<AppState>,
-    Extension(user): Extension<Option<AuthorizedUser>>,
+    Authorized(user): Authorized,
+    Extension(request_id): Extension<RequestId>,
     Path(id): Path<i32>,
 ) -> Result<impl IntoResponse, Error> {
-    let user = match user {
-        Some(user) => {
-            if !user.claims.contains(&"dispatches.delete".to_string()) {
-                return Err(Error::Unauthorized);
-            }
-
-            user
-        }
-        None => return Err(Error::Unauthorized),
-    };
+    let event = audit::Event::new(&user, "dispatch.delete", "dispatch").target(id);
+
+    let result = state
+        .dispatch_controller
+        .delete(user, id, &request_id)
+        .await;
+
+    state.audit_controller.record(event, &result);
 
-    let status = state.dispatch_controller.delete(user, id).await?;
+    let status = result?;
 
     Ok((
         StatusCode::ACCEPTED,
-        [(header::LOCATION, format!("/queue/dispatches/{}", status.id))],
+        [(header::LOCATION, status.self_url.clone())],
         Json(status),
     ))
 }
+
+/// Locks the dispatch against deletion, see `Controller::protect`.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn protect(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<Json<response::Dispatch>, Error> {
+    let event = audit::Event::new(&user, "dispatch.protect", "dispatch").target(id);
+
+    let result = state.dispatch_controller.protect(&user, id).await;
+
+    state.audit_controller.record(event, &result);
+
+    Ok(Json(result?))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn unprotect(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<Json<response::Dispatch>, Error> {
+    let event = audit::Event::new(&user, "dispatch.unprotect", "dispatch").target(id);
+
+    let result = state.dispatch_controller.unprotect(&user, id).await;
+
+    state.audit_controller.record(event, &result);
+
+    Ok(Json(result?))
+}
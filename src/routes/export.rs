@@ -0,0 +1,327 @@
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, header};
+use axum::response::IntoResponse;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io;
+use tokio::sync::mpsc;
+use tracing::instrument;
+
+use crate::controllers::audit;
+use crate::core::authorization::Authorized;
+use crate::core::error::Error;
+use crate::core::extract::Query;
+use crate::core::state::AppState;
+use crate::types::request::{self, ExportFormat};
+use crate::types::response::{self, ExportChecksum, ExportManifest, ExportTrailer, ExportedFile};
+use crate::utils::zip;
+
+const NDJSON: &str = "application/x-ndjson";
+const ZIP: &str = "application/zip";
+
+/// Longest title kept in a folder name, in characters.
+const MAX_TITLE_LENGTH: usize = 64;
+
+/// Streams every dispatch revision, as NDJSON lines or as BBCode files in a zip.
+#[instrument(skip_all)]
+pub(crate) async fn dispatches(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    headers: HeaderMap,
+    Query(query): Query<request::ExportQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let format = query.format.unwrap_or_else(|| negotiate(&headers));
+    let generated_at = Utc::now();
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "dispatch.export", "dispatch").summary(json!({
+            "format": match format {
+                ExportFormat::Ndjson => "ndjson",
+                ExportFormat::Zip => "zip",
+            },
+            "since": query.since,
+        })),
+        &Ok::<_, Error>(()),
+    );
+
+    let revisions = state
+        .dispatch_controller
+        .export(query.since, user.tenant_id);
+    let encoder = Encoder::new(format, query.since, generated_at);
+
+    let (content_type, extension) = match format {
+        ExportFormat::Ndjson => (NDJSON, "ndjson"),
+        ExportFormat::Zip => (ZIP, "zip"),
+    };
+
+    let disposition = format!(
+        "attachment; filename=\"dispatches-{}.{}\"",
+        generated_at.format("%Y%m%dT%H%M%SZ"),
+        extension
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+            (
+                header::CONTENT_DISPOSITION,
+                HeaderValue::from_str(&disposition)?,
+            ),
+        ],
+        Body::from_stream(encode(revisions, encoder)),
+    ))
+}
+
+/// Zip when the client asks for it, NDJSON otherwise.
+fn negotiate(headers: &HeaderMap) -> ExportFormat {
+    let wants_zip = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|range| range.split(';').next())
+        .any(|media_type| media_type.trim().eq_ignore_ascii_case(ZIP));
+
+    if wants_zip {
+        ExportFormat::Zip
+    } else {
+        ExportFormat::Ndjson
+    }
+}
+
+/// The archive bytes, chunk by chunk. A failed read ends the body with an error instead of the
+/// trailer or manifest, so clients can tell a partial export from a complete one.
+fn encode(
+    revisions: mpsc::Receiver<Result<response::Dispatch, Error>>,
+    encoder: Encoder,
+) -> impl futures_util::Stream<Item = io::Result<Vec<u8>>> {
+    futures_util::stream::unfold(Some((revisions, encoder)), |state| async move {
+        let (mut revisions, mut encoder) = state?;
+
+        match revisions.recv().await {
+            Some(Ok(dispatch)) => match encoder.revision(&dispatch) {
+                Ok(chunk) => Some((Ok(chunk), Some((revisions, encoder)))),
+                Err(e) => Some((Err(e), None)),
+            },
+            Some(Err(e)) => {
+                tracing::error!("unable to export dispatches: {}", e);
+
+                Some((Err(io::Error::other(e)), None))
+            }
+            None => Some((encoder.finish(), None)),
+        }
+    })
+}
+
+enum Encoder {
+    Ndjson {
+        hasher: Sha256,
+        revisions: usize,
+    },
+    Zip {
+        writer: zip::Writer,
+        /// folder of each dispatch, named after its title when first seen
+        folders: HashMap<i32, String>,
+        manifest: ExportManifest,
+    },
+}
+
+impl Encoder {
+    fn new(
+        format: ExportFormat,
+        since: Option<DateTime<Utc>>,
+        generated_at: DateTime<Utc>,
+    ) -> Self {
+        match format {
+            ExportFormat::Ndjson => Encoder::Ndjson {
+                hasher: Sha256::new(),
+                revisions: 0,
+            },
+            ExportFormat::Zip => Encoder::Zip {
+                writer: zip::Writer::new(),
+                folders: HashMap::new(),
+                manifest: ExportManifest {
+                    generated_at,
+                    since,
+                    files: Vec::new(),
+                },
+            },
+        }
+    }
+
+    fn revision(&mut self, dispatch: &response::Dispatch) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Ndjson { hasher, revisions } => {
+                let mut line = serde_json::to_vec(dispatch)?;
+                line.push(b'\n');
+
+                hasher.update(&line);
+                *revisions += 1;
+
+                Ok(line)
+            }
+            Encoder::Zip {
+                writer,
+                folders,
+                manifest,
+            } => {
+                let folder = folders
+                    .entry(dispatch.id)
+                    .or_insert_with(|| folder(dispatch));
+                let path = format!("{}/{}.bbcode", folder, dispatch.revision);
+
+                let chunk = writer.file(&path, dispatch.text.as_bytes(), dispatch.modified_at)?;
+
+                manifest.files.push(ExportedFile {
+                    path,
+                    dispatch_id: dispatch.id,
+                    revision: dispatch.revision,
+                    job_id: dispatch.job_id,
+                    created_by: dispatch.created_by.clone(),
+                    created_at: dispatch.modified_at,
+                    sha256: hex(&Sha256::digest(dispatch.text.as_bytes())),
+                });
+
+                Ok(chunk)
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Ndjson { hasher, revisions } => {
+                let mut line = serde_json::to_vec(&ExportTrailer {
+                    trailer: ExportChecksum {
+                        revisions,
+                        sha256: hex(&hasher.finalize()),
+                    },
+                })?;
+                line.push(b'\n');
+
+                Ok(line)
+            }
+            Encoder::Zip {
+                mut writer,
+                manifest,
+                ..
+            } => {
+                let mut chunk = writer.file(
+                    "manifest.json",
+                    &serde_json::to_vec_pretty(&manifest)?,
+                    manifest.generated_at,
+                )?;
+                chunk.extend(writer.finish()?);
+
+                Ok(chunk)
+            }
+        }
+    }
+}
+
+/// `{nation}/{id} {title}`, with anything that isn't safe in a file name replaced.
+fn folder(dispatch: &response::Dispatch) -> String {
+    let title: String = dispatch
+        .title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_TITLE_LENGTH)
+        .collect();
+
+    match title.trim() {
+        "" => format!("{}/{}", dispatch.nation, dispatch.id),
+        title => format!("{}/{} {}", dispatch.nation, dispatch.id, title),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut output, byte| {
+        let _ = write!(output, "{:02x}", byte);
+        output
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ns::dispatch::TextFormat;
+
+    fn dispatch(title: &str) -> response::Dispatch {
+        response::Dispatch {
+            id: 1234,
+            nation: "testlandia".to_string(),
+            category: 1,
+            subcategory: 100,
+            title: title.to_string(),
+            text: "[b]Hello[/b]".to_string(),
+            revision: 7,
+            job_id: Some(3),
+            size: Some(12),
+            source_format: TextFormat::default(),
+            source: None,
+            created_by: "alice".to_string(),
+            modified_at: Utc::now(),
+            is_active: true,
+            is_protected: false,
+            rendered: None,
+            pending_or_failed_edits: None,
+        }
+    }
+
+    #[test]
+    fn test_folder() {
+        assert_eq!(
+            folder(&dispatch("Factbook: History")),
+            "testlandia/1234 Factbook_ History"
+        );
+        assert_eq!(
+            folder(&dispatch("../../etc/passwd")),
+            "testlandia/1234 ______etc_passwd"
+        );
+        assert_eq!(folder(&dispatch("  ")), "testlandia/1234");
+        assert_eq!(
+            folder(&dispatch(&"a".repeat(100))),
+            format!("testlandia/1234 {}", "a".repeat(MAX_TITLE_LENGTH))
+        );
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let accept = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+
+        assert_eq!(negotiate(&HeaderMap::new()), ExportFormat::Ndjson);
+        assert_eq!(negotiate(&accept("*/*")), ExportFormat::Ndjson);
+        assert_eq!(negotiate(&accept(NDJSON)), ExportFormat::Ndjson);
+        assert_eq!(
+            negotiate(&accept("application/zip;q=0.9, */*;q=0.1")),
+            ExportFormat::Zip
+        );
+    }
+
+    #[test]
+    fn test_ndjson_trailer() {
+        let mut encoder = Encoder::new(ExportFormat::Ndjson, None, Utc::now());
+
+        let mut body = encoder.revision(&dispatch("one")).unwrap();
+        body.extend(encoder.revision(&dispatch("two")).unwrap());
+        let digest = hex(&Sha256::digest(&body));
+
+        let trailer: ExportTrailer = serde_json::from_slice(&encoder.finish().unwrap()).unwrap();
+
+        assert_eq!(trailer.trailer.revisions, 2);
+        assert_eq!(trailer.trailer.sha256, digest);
+    }
+}
@@ -0,0 +1,53 @@
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use std::time::{Duration, Instant};
+
+use crate::core::state::AppState;
+use crate::types::response::{DatabaseHealth, Health};
+
+/// Longest the database gets to answer before it's reported unreachable. Shorter than the pool's
+/// acquire timeout, so a check doesn't hang while every connection is busy.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Reports the connection pool and checks the database answers, with 503 when it doesn't.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn get(State(state): State<AppState>) -> impl IntoResponse {
+    let pool = &state.db_pool;
+    let started = Instant::now();
+
+    let latency_ms =
+        match tokio::time::timeout(TIMEOUT, sqlx::query("SELECT 1;").execute(pool)).await {
+            Ok(Ok(_)) => Some(started.elapsed().as_millis() as u64),
+            Ok(Err(e)) => {
+                tracing::warn!("database unavailable: {}", e);
+                None
+            }
+            Err(_) => {
+                tracing::warn!("database didn't answer within {:?}", TIMEOUT);
+                None
+            }
+        };
+
+    let reachable = latency_ms.is_some();
+
+    let health = Health {
+        status: if reachable { "ok" } else { "unavailable" },
+        database: DatabaseHealth {
+            reachable,
+            latency_ms,
+            connections: pool.size(),
+            idle_connections: pool.num_idle(),
+            max_connections: pool.options().get_max_connections(),
+        },
+    };
+
+    let status = if reachable {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(health))
+}
@@ -0,0 +1,103 @@
+//! Gauges and counters in the Prometheus text format, for scraping with a `stats.read` key.
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use std::fmt::Write;
+
+use crate::core::state::AppState;
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Appends one metric with its help and type lines.
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP eurocore_{name} {help}");
+    let _ = writeln!(out, "# TYPE eurocore_{name} {kind}");
+    let _ = writeln!(out, "eurocore_{name} {value}");
+}
+
+/// Appends one metric with a value for each pipeline.
+fn per_pipeline(out: &mut String, name: &str, help: &str, values: &[(&str, usize)]) {
+    let _ = writeln!(out, "# HELP eurocore_{name} {help}");
+    let _ = writeln!(out, "# TYPE eurocore_{name} gauge");
+
+    for (pipeline, value) in values {
+        let _ = writeln!(out, "eurocore_{name}{{pipeline=\"{pipeline}\"}} {value}");
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn get(State(state): State<AppState>) -> impl IntoResponse {
+    let status = state.ratelimiter.inspect().await;
+    let mut out = String::new();
+
+    metric(
+        &mut out,
+        "ratelimit_requests",
+        "gauge",
+        "Requests to NS in the current bucket, as counted by us.",
+        status.requests,
+    );
+    metric(
+        &mut out,
+        "ratelimit_max_requests",
+        "gauge",
+        "Requests to NS allowed per bucket.",
+        status.limits.max_requests,
+    );
+    metric(
+        &mut out,
+        "ratelimit_external_requests",
+        "gauge",
+        "Requests to NS from others sharing our IP in the current bucket, as observed.",
+        status.external_requests,
+    );
+
+    if let Some(observed) = &status.observed {
+        let limit = observed.limit.unwrap_or(status.limits.max_requests).max(1);
+
+        metric(
+            &mut out,
+            "ratelimit_observed_requests",
+            "gauge",
+            "Requests from our IP NS reported seeing in its window.",
+            observed.seen,
+        );
+        metric(
+            &mut out,
+            "ratelimit_observed_utilization",
+            "gauge",
+            "Share of NS's request cap NS reported in use.",
+            observed.seen as f64 / limit as f64,
+        );
+    }
+
+    metric(
+        &mut out,
+        "ratelimit_utilization_warnings_total",
+        "counter",
+        "Times NS's count of our requests crossed the warning threshold.",
+        status.utilization_warnings,
+    );
+
+    let channels = [
+        ("dispatch", state.dispatch_controller.channel_occupancy()),
+        ("rmbpost", state.rmbpost_controller.channel_occupancy()),
+        ("telegram", state.telegram_controller.channel_occupancy()),
+    ];
+
+    per_pipeline(
+        &mut out,
+        "worker_channel_commands",
+        "Commands waiting in a worker's channel.",
+        &channels.map(|(pipeline, (commands, _))| (pipeline, commands)),
+    );
+    per_pipeline(
+        &mut out,
+        "worker_channel_capacity",
+        "Commands a worker's channel holds; past that, requests wait and are then refused.",
+        &channels.map(|(pipeline, (_, capacity))| (pipeline, capacity)),
+    );
+
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], out)
+}
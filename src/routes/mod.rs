@@ -1,8 +1,17 @@
 mod admin;
+mod bootstrap;
 mod dispatch;
+mod export;
+mod health;
+mod metrics;
 mod nations;
+mod openapi;
+pub(crate) mod public;
 mod queue;
+mod request_id;
 mod rmbpost;
 pub(crate) mod router;
+mod stats;
 mod telegram;
 mod user;
+mod webhook;
@@ -1,17 +1,90 @@
+use crate::core::error::Error;
+use crate::core::state::AppState;
+use crate::types::AuthorizedUser;
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+
+/// Restricted action availability for every configured dispatch and rmbpost nation of the
+/// user's tenant.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn status(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+) -> Result<impl IntoResponse, Error> {
+    let Some(user) = user else {
+        return Err(Error::Unauthorized);
+    };
+
+    let mut statuses = state.dispatch_controller.nation_status().await?;
+
+    for status in state.rmbpost_controller.nation_status().await? {
+        match statuses
+            .iter_mut()
+            .find(|existing| existing.nation == status.nation)
+        {
+            Some(existing) => {
+                existing.queued_rmbposts = status.queued_rmbposts;
+                existing.healthy &= status.healthy;
+            }
+            None => statuses.push(status),
+        }
+    }
+
+    let own = state
+        .tenant_controller
+        .own_nations(
+            user.tenant_id,
+            statuses
+                .iter()
+                .map(|status| status.nation.clone())
+                .collect(),
+        )
+        .await?;
+
+    statuses.retain(|status| own.contains(&status.nation));
+    statuses.sort_by(|a, b| a.nation.cmp(&b.nation));
+
+    Ok(Json(statuses))
+}
+
 pub(super) mod dispatches {
     use crate::core::error::Error;
+    use crate::core::extract::{Path, Query};
     use crate::core::state::AppState;
-    use axum::Json;
-    use axum::extract::{Path, State};
-    use axum::response::IntoResponse;
+    use crate::types::request::{DispatchFormat, DispatchQuery};
+    use crate::types::{AuthorizedUser, response, tenant_scope};
+    use axum::extract::State;
+    use axum::response::{IntoResponse, Response};
+    use axum::{Extension, Json};
 
     #[tracing::instrument(skip_all)]
     pub(crate) async fn get(
         State(state): State<AppState>,
+        Extension(user): Extension<Option<AuthorizedUser>>,
         Path(nation): Path<String>,
-    ) -> Result<impl IntoResponse, Error> {
-        let dispatches = state.dispatch_controller.get(Some(nation)).await?;
+        Query(query): Query<DispatchQuery>,
+    ) -> Result<Response, Error> {
+        let tenant = Some(tenant_scope(user.as_ref()));
+
+        if query.summary {
+            let summaries = state
+                .dispatch_controller
+                .get_summaries(Some(nation), query.include_inactive, tenant)
+                .await?;
+
+            return Ok(Json(summaries).into_response());
+        }
+
+        let mut dispatches = state
+            .dispatch_controller
+            .get(Some(nation), query.include_inactive, tenant)
+            .await?;
+
+        if query.format == DispatchFormat::Html {
+            dispatches.iter_mut().for_each(response::Dispatch::render);
+        }
 
-        Ok(Json(dispatches))
+        Ok(Json(dispatches).into_response())
     }
 }
@@ -0,0 +1,2363 @@
+use crate::ns::dispatch::FactbookCategory;
+use axum::Json;
+use axum::response::Html;
+use serde_json::{Value, json};
+use std::sync::LazyLock;
+
+/// Built once; the document only depends on the code.
+static DOCUMENT: LazyLock<Value> = LazyLock::new(document);
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn spec() -> Json<Value> {
+    Json(DOCUMENT.clone())
+}
+
+/// Swagger UI for `/openapi.json`, loaded from a CDN so nothing has to be bundled.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn docs() -> Html<&'static str> {
+    Html(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>eurocore API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>"##,
+    )
+}
+
+/// Category and subcategory numbers NS accepts, as validated by `FactbookCategory`.
+fn categories() -> (Vec<i16>, Vec<i16>) {
+    let pairs: Vec<(i16, i16)> = (1..10)
+        .flat_map(|category| (100..1000).map(move |subcategory| (category, subcategory)))
+        .filter(|pair| FactbookCategory::try_from(*pair).is_ok())
+        .collect();
+
+    let mut categories: Vec<i16> = pairs.iter().map(|(category, _)| *category).collect();
+    categories.dedup();
+
+    (categories, pairs.into_iter().map(|(_, sub)| sub).collect())
+}
+
+fn schema(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// An [`ErrorBody`](crate::types::response::ErrorBody) whose `details` are the schema `details`.
+fn error_with(details: &str) -> Value {
+    json!({
+        "allOf": [
+            schema("ErrorBody"),
+            { "type": "object", "required": ["details"], "properties": { "details": schema(details) } },
+        ],
+    })
+}
+
+/// A 422 for content checked against the content rules.
+fn content_policy() -> Value {
+    json!({ "422": { "$ref": "#/components/responses/ContentPolicyViolation" } })
+}
+
+fn body(name: &str) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": schema(name) } },
+    })
+}
+
+fn ok(description: &str, content: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": content } },
+    })
+}
+
+fn id_parameter(description: &str) -> Value {
+    json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": { "type": "integer", "format": "int32" },
+    })
+}
+
+fn errors(codes: &[&str]) -> Value {
+    codes
+        .iter()
+        .map(|code| {
+            let response = match *code {
+                "422" => "InvalidBody",
+                "429" => "QuotaExceeded",
+                "503" => "Unavailable",
+                _ => "ErrorBody",
+            };
+
+            (
+                code.to_string(),
+                json!({ "$ref": format!("#/components/responses/{}", response) }),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// The 423 of changes an admin has locked the dispatch against.
+fn protected() -> Value {
+    json!({
+        "description": "an admin protected the dispatch, `dispatch_protected`",
+        "content": { "application/json": { "schema": error_with("ProtectedDispatch") } },
+    })
+}
+
+/// Merges `extra` into the object `base`.
+fn with(mut base: Value, extra: Value) -> Value {
+    if let (Some(base), Value::Object(extra)) = (base.as_object_mut(), extra) {
+        base.extend(extra);
+    }
+
+    base
+}
+
+const BEARER: &str = "bearerAuth";
+const API_KEY: &str = "apiKey";
+
+fn authenticated() -> Value {
+    json!([{ BEARER: [] }, { API_KEY: [] }])
+}
+
+fn job_headers() -> Value {
+    json!({
+        "Location": {
+            "description": "where the job status can be polled",
+            "schema": { "type": "string" },
+        },
+    })
+}
+
+fn retry_after() -> Value {
+    json!({
+        "Retry-After": {
+            "description": "seconds until the job is expected to be done, while it's waiting in the worker's queue",
+            "schema": { "type": "integer" },
+        },
+    })
+}
+
+/// Headers of a job queued by itself, which comes with an estimate of when it's done.
+fn queued_headers() -> Value {
+    with(job_headers(), retry_after())
+}
+
+fn idempotency_key() -> Value {
+    json!({
+        "name": "Idempotency-Key",
+        "in": "header",
+        "required": false,
+        "description": "replays the original response instead of queueing a second job",
+        "schema": { "type": "string", "maxLength": 255 },
+    })
+}
+
+fn paths() -> Value {
+    // split up like the schemas, one literal exceeds the `json!` recursion limit
+    let accounts_and_dispatches = json!({
+        "/register": {
+            "post": {
+                "tags": ["users"],
+                "summary": "Create an account and log in",
+                "requestBody": body("LoginData"),
+                "responses": with(json!({ "202": ok("account created", schema("Login")) }), errors(&["400", "409", "413", "415", "422"])),
+            },
+        },
+        "/login": {
+            "post": {
+                "tags": ["users"],
+                "summary": "Exchange a username and password for a token",
+                "requestBody": body("LoginData"),
+                "responses": with(json!({
+                    "200": ok("logged in", schema("Login")),
+                    "429": { "$ref": "#/components/responses/LoginThrottled" },
+                }), errors(&["401", "413", "415", "422"])),
+            },
+        },
+        "/login/refresh": {
+            "post": {
+                "tags": ["users"],
+                "summary": "Exchange a token that hasn't expired yet for a fresh one",
+                "requestBody": body("RefreshData"),
+                "responses": with(json!({ "200": ok("logged in", schema("Login")) }), errors(&["401", "413", "415", "422"])),
+            },
+        },
+        "/users/me/api-keys": {
+            "get": {
+                "tags": ["users"],
+                "summary": "List your API keys, including revoked ones",
+                "security": authenticated(),
+                "responses": with(json!({
+                    "200": ok("API keys", json!({ "type": "array", "items": schema("ApiKey") })),
+                }), errors(&["401"])),
+            },
+            "post": {
+                "tags": ["users"],
+                "summary": "Create an API key scoped to some of your claims",
+                "security": authenticated(),
+                "requestBody": body("NewApiKey"),
+                "responses": with(json!({
+                    "201": ok("created, `key` is only ever returned here", schema("ApiKey")),
+                }), errors(&["400", "401", "403", "413", "415", "422"])),
+            },
+        },
+        "/users/me/api-keys/{id}": {
+            "parameters": [id_parameter("API key id")],
+            "delete": {
+                "tags": ["users"],
+                "summary": "Revoke an API key",
+                "security": authenticated(),
+                "responses": with(json!({ "200": ok("revoked", schema("ApiKey")) }), errors(&["401", "404"])),
+            },
+        },
+        "/bootstrap": {
+            "get": {
+                "tags": ["users"],
+                "summary": "Everything a UI needs to render its forms: you, your nations, the categories and the limits",
+                "security": authenticated(),
+                "responses": with(json!({ "200": ok("bootstrap document", schema("Bootstrap")) }), errors(&["401"])),
+            },
+        },
+        "/dispatches": {
+            "get": {
+                "tags": ["dispatches"],
+                "summary": "List dispatches",
+                "parameters": [
+                    { "name": "include_inactive", "in": "query", "schema": { "type": "boolean", "default": false } },
+                    { "name": "summary", "in": "query", "description": "leave out the text", "schema": { "type": "boolean", "default": false } },
+                    { "name": "format", "in": "query", "schema": { "type": "string", "enum": ["bbcode", "html"], "default": "bbcode" } },
+                ],
+                "responses": {
+                    "200": ok("dispatches, as summaries with `summary=true`", json!({
+                        "oneOf": [
+                            { "type": "array", "items": schema("Dispatch") },
+                            { "type": "array", "items": schema("DispatchSummary") },
+                        ],
+                    })),
+                    "304": { "description": "matches `If-None-Match`" },
+                    "401": { "$ref": "#/components/responses/ErrorBody" },
+                    "429": { "$ref": "#/components/responses/PublicReadThrottled" },
+                },
+            },
+            "post": {
+                "tags": ["dispatches"],
+                "summary": "Queue a new dispatch",
+                "security": authenticated(),
+                "parameters": [
+                    idempotency_key(),
+                    { "name": "allow_duplicate_title", "in": "query", "description": "post even if the nation has an active dispatch with the same title, which is otherwise refused with `duplicate_dispatch_title`", "schema": { "type": "boolean", "default": false } },
+                ],
+                "requestBody": body("NewDispatch"),
+                "responses": with(json!({
+                    "200": ok("replayed for a repeated Idempotency-Key", schema("DispatchStatus")),
+                    "202": with(ok("queued", schema("DispatchStatus")), json!({ "headers": queued_headers() })),
+                }), with(errors(&["400", "401", "409", "429", "413", "415", "503"]), content_policy())),
+            },
+        },
+        "/dispatches/multi": {
+            "post": {
+                "tags": ["dispatches"],
+                "summary": "Queue the same new dispatch for several nations",
+                "security": authenticated(),
+                "parameters": [idempotency_key()],
+                "requestBody": body("NewDispatchGroup"),
+                "responses": with(json!({
+                    "200": ok("replayed for a repeated Idempotency-Key", schema("DispatchGroup")),
+                    "202": with(ok("queued, one job per nation", schema("DispatchGroup")), json!({ "headers": job_headers() })),
+                }), with(errors(&["400", "401", "409", "429", "413", "415", "503"]), content_policy())),
+            },
+        },
+        "/dispatches/categories": {
+            "get": {
+                "tags": ["dispatches"],
+                "summary": "List the categories and subcategories a dispatch can be filed under",
+                "responses": {
+                    "200": ok("categories", json!({ "type": "array", "items": schema("DispatchCategory") })),
+                    "401": { "$ref": "#/components/responses/ErrorBody" },
+                    "429": { "$ref": "#/components/responses/PublicReadThrottled" },
+                },
+            },
+        },
+        "/dispatches/search": {
+            "get": {
+                "tags": ["dispatches"],
+                "summary": "Search the titles and texts of dispatches, best matches first",
+                "parameters": [
+                    { "name": "q", "in": "query", "required": true, "description": "words to look for, with web search syntax: `\"phrases\"`, `or` and `-excluded`", "schema": { "type": "string" } },
+                    { "name": "nation", "in": "query", "schema": { "type": "string" } },
+                    { "name": "category", "in": "query", "schema": { "type": "integer" } },
+                    { "name": "subcategory", "in": "query", "schema": { "type": "integer" } },
+                    { "name": "include_inactive", "in": "query", "schema": { "type": "boolean", "default": false } },
+                    { "name": "limit", "in": "query", "schema": { "type": "integer", "default": 20, "maximum": 100 } },
+                    { "name": "offset", "in": "query", "schema": { "type": "integer", "default": 0 } },
+                ],
+                "responses": {
+                    "200": ok("matching dispatches", json!({ "type": "array", "items": schema("DispatchSearchResult") })),
+                    "400": { "$ref": "#/components/responses/ErrorBody" },
+                    "401": { "$ref": "#/components/responses/ErrorBody" },
+                    "429": { "$ref": "#/components/responses/PublicReadThrottled" },
+                },
+            },
+        },
+        "/dispatches/preview": {
+            "post": {
+                "tags": ["dispatches"],
+                "summary": "Show a dispatch text as it will be sent to NS, with non-ASCII characters encoded",
+                "requestBody": body("PreviewData"),
+                "responses": with(json!({ "200": ok("encoded text", schema("EncodingPreview")) }), errors(&["413", "415", "422"])),
+            },
+        },
+        "/dispatches/import": {
+            "post": {
+                "tags": ["dispatches"],
+                "summary": "Start tracking a dispatch posted without eurocore, read from NS's public API",
+                "description": "The dispatch must have been written by `nation`, one of the dispatch nations. The importing user becomes its owner.",
+                "security": authenticated(),
+                "requestBody": body("ImportDispatch"),
+                "responses": with(json!({
+                    "201": ok("the dispatch as imported", schema("Dispatch")),
+                }), errors(&["400", "401", "404", "409", "415", "422"])),
+            },
+        },
+        "/export/dispatches": {
+            "get": {
+                "tags": ["dispatches"],
+                "summary": "Download every dispatch revision, deleted dispatches included",
+                "description": "Needs `admin` or `export.read`. NDJSON has a Dispatch per line and ends with `{\"trailer\": {\"revisions\", \"sha256\"}}` over the preceding lines. The zip holds `{nation}/{id} {title}/{revision}.bbcode` files and ends with `manifest.json`. A body without either was cut short.",
+                "security": authenticated(),
+                "parameters": [
+                    { "name": "format", "in": "query", "description": "overrides `Accept`", "schema": { "type": "string", "enum": ["ndjson", "zip"] } },
+                    { "name": "since", "in": "query", "description": "only revisions created at or after this", "schema": { "type": "string", "format": "date-time" } },
+                ],
+                "responses": with(json!({
+                    "200": {
+                        "description": "the export, streamed",
+                        "content": {
+                            "application/x-ndjson": { "schema": schema("Dispatch") },
+                            "application/zip": { "schema": { "type": "string", "format": "binary" } },
+                        },
+                    },
+                }), errors(&["400", "401"])),
+            },
+        },
+        "/queue/groups/{id}": {
+            "parameters": [id_parameter("group id")],
+            "get": {
+                "tags": ["queue"],
+                "summary": "Progress of a dispatch group",
+                "responses": with(json!({ "200": ok("group status", schema("DispatchGroup")) }), errors(&["404"])),
+            },
+        },
+        "/dispatches/{id}": {
+            "parameters": [id_parameter("NS dispatch id")],
+            "get": {
+                "tags": ["dispatches"],
+                "summary": "Get a dispatch",
+                "parameters": [
+                    { "name": "format", "in": "query", "schema": { "type": "string", "enum": ["bbcode", "html"], "default": "bbcode" } },
+                ],
+                "responses": with(json!({
+                    "200": ok("the dispatch", schema("Dispatch")),
+                    "304": { "description": "matches `If-None-Match`" },
+                    "410": {
+                        "description": "the dispatch was deleted, `dispatch_deleted`",
+                        "content": { "application/json": { "schema": error_with("DeletedDispatch") } },
+                    },
+                    "429": { "$ref": "#/components/responses/PublicReadThrottled" },
+                }), errors(&["400", "401", "404"])),
+            },
+            "put": {
+                "tags": ["dispatches"],
+                "summary": "Queue an edit",
+                "security": authenticated(),
+                "parameters": [
+                    idempotency_key(),
+                    {
+                        "name": "If-Match",
+                        "in": "header",
+                        "required": false,
+                        "description": "the quoted `revision` the edit is based on, e.g. `\"42\"`, takes precedence over `base_revision`",
+                        "schema": { "type": "string" },
+                    },
+                    {
+                        "name": "coalesce",
+                        "in": "query",
+                        "required": false,
+                        "description": "if your own edit of the dispatch is still waiting, and is the last job on it, post this one in its place; the replaced job becomes `superseded`",
+                        "schema": { "type": "boolean", "default": false },
+                    },
+                ],
+                "requestBody": body("EditDispatch"),
+                "responses": with(with(json!({
+                    "200": ok("replayed for a repeated Idempotency-Key", schema("DispatchStatus")),
+                    "202": with(ok("queued", schema("DispatchStatus")), json!({ "headers": queued_headers() })),
+                }), with(errors(&["400", "401", "403", "404", "429", "413", "415", "503"]), content_policy())), json!({
+                    "409": {
+                        "description": "the dispatch was deleted, its nation's password was rejected, it was edited since the base revision, or its deletion is pending",
+                        "content": {
+                            "application/json": {
+                                "schema": {
+                                    "description": "`edit_conflict` comes with the latest revision in `details`, `pending_jobs` with the pending delete",
+                                    "oneOf": [schema("ErrorBody"), error_with("EditConflict"), error_with("PendingJobs")],
+                                },
+                            },
+                        },
+                    },
+                    "423": protected(),
+                })),
+            },
+            "delete": {
+                "tags": ["dispatches"],
+                "summary": "Queue a deletion",
+                "security": authenticated(),
+                "responses": with(json!({
+                    "202": with(ok("queued", schema("DispatchStatus")), json!({ "headers": job_headers() })),
+                    "409": {
+                        "description": "the dispatch was deleted, its nation's password was rejected, or edits or a deletion of it are pending",
+                        "content": {
+                            "application/json": {
+                                "schema": {
+                                    "description": "`pending_jobs` lists the jobs to wait for in `details`",
+                                    "oneOf": [schema("ErrorBody"), error_with("PendingJobs")],
+                                },
+                            },
+                        },
+                    },
+                    "423": protected(),
+                }), errors(&["401", "403", "404", "429", "503"])),
+            },
+        },
+        "/dispatches/{id}/metrics": {
+            "parameters": [id_parameter("NS dispatch id")],
+            "get": {
+                "tags": ["dispatches"],
+                "summary": "Views and score sampled from NS",
+                "description": "Samples are only taken while `EUROCORE_DISPATCH_METRICS_INTERVAL_SECS` is set.",
+                "parameters": [
+                    { "name": "from", "in": "query", "description": "defaults to a week ago", "schema": { "type": "string", "format": "date-time" } },
+                    { "name": "to", "in": "query", "schema": { "type": "string", "format": "date-time" } },
+                ],
+                "responses": with(json!({ "200": ok("samples, oldest first", schema("DispatchMetrics")) }), errors(&["400", "401", "404"])),
+            },
+        },
+        "/dispatches/{id}/protect": {
+            "parameters": [id_parameter("NS dispatch id")],
+            "post": {
+                "tags": ["dispatches"],
+                "summary": "Protect a dispatch from deletion",
+                "security": authenticated(),
+                "responses": with(json!({ "200": ok("the protected dispatch", schema("Dispatch")) }), errors(&["401", "403", "404"])),
+            },
+        },
+        "/dispatches/{id}/unprotect": {
+            "parameters": [id_parameter("NS dispatch id")],
+            "post": {
+                "tags": ["dispatches"],
+                "summary": "Let a dispatch be deleted again",
+                "security": authenticated(),
+                "responses": with(json!({ "200": ok("the dispatch", schema("Dispatch")) }), errors(&["401", "403", "404"])),
+            },
+        },
+        "/dispatches/{id}/reapply/{job_id}": {
+            "parameters": [id_parameter("NS dispatch id"), {
+                "name": "job_id",
+                "in": "path",
+                "required": true,
+                "description": "the failed edit",
+                "schema": { "type": "integer", "format": "int32" },
+            }],
+            "post": {
+                "tags": ["dispatches"],
+                "summary": "Queue a failed edit again",
+                "description": "Queues the content of a failed edit as a new edit, based on the revision the failed one was based on, or, without one, the revision that was latest when it was queued.",
+                "security": authenticated(),
+                "parameters": [
+                    {
+                        "name": "If-Match",
+                        "in": "header",
+                        "required": false,
+                        "description": "the quoted `revision` to base the edit on instead, e.g. `\"42\"`",
+                        "schema": { "type": "string" },
+                    },
+                ],
+                "responses": with(with(json!({
+                    "202": with(ok("queued", schema("DispatchStatus")), json!({ "headers": job_headers() })),
+                }), with(errors(&["400", "401", "403", "404", "429", "503"]), content_policy())), json!({
+                    "409": {
+                        "description": "the job isn't a failed edit, `not_reapplicable`, or the edit is refused as `PUT /dispatches/{id}` would refuse it",
+                        "content": {
+                            "application/json": {
+                                "schema": {
+                                    "description": "`edit_conflict` comes with the latest revision in `details`, `pending_jobs` with the pending delete",
+                                    "oneOf": [schema("ErrorBody"), error_with("EditConflict"), error_with("PendingJobs")],
+                                },
+                            },
+                        },
+                    },
+                    "423": protected(),
+                })),
+            },
+        },
+        "/queue/dispatches/{id}": {
+            "parameters": [id_parameter("job id")],
+            "get": {
+                "tags": ["queue"],
+                "summary": "Dispatch job status",
+                "parameters": [
+                    { "name": "include_payload", "in": "query", "schema": { "type": "boolean", "default": false } },
+                ],
+                "responses": with(json!({
+                    "200": with(ok("job status", schema("DispatchStatus")), json!({ "headers": retry_after() })),
+                }), errors(&["401", "404"])),
+            },
+        },
+    });
+
+    let rmbposts_and_telegrams = json!({
+        "/rmbposts": {
+            "post": {
+                "tags": ["rmbposts"],
+                "summary": "Queue an RMB post",
+                "security": authenticated(),
+                "parameters": [idempotency_key()],
+                "requestBody": body("NewRmbPost"),
+                "responses": with(json!({
+                    "200": ok("replayed for a repeated Idempotency-Key", schema("RmbPostStatus")),
+                    "202": with(ok("queued", schema("RmbPostStatus")), json!({ "headers": queued_headers() })),
+                }), with(errors(&["400", "401", "409", "429", "413", "415", "503"]), json!({
+                    "422": { "$ref": "#/components/responses/RmbPostRefused" },
+                }))),
+            },
+        },
+        "/rmbposts/batch": {
+            "post": {
+                "tags": ["rmbposts"],
+                "summary": "Queue the same RMB post for several regions",
+                "security": authenticated(),
+                "parameters": [idempotency_key()],
+                "requestBody": body("NewRmbPostBatch"),
+                "responses": with(json!({
+                    "200": ok("replayed for a repeated Idempotency-Key", schema("RmbPostGroup")),
+                    "202": with(ok("queued, one job per region; jobs for regions that can't be posted to are failed from the start", schema("RmbPostGroup")), json!({ "headers": job_headers() })),
+                }), with(errors(&["400", "401", "409", "429", "413", "415", "503"]), content_policy())),
+            },
+        },
+        "/queue/rmbposts/groups/{id}": {
+            "parameters": [id_parameter("group id")],
+            "get": {
+                "tags": ["queue"],
+                "summary": "Progress of an RMB post batch",
+                "responses": with(json!({ "200": ok("group status", schema("RmbPostGroup")) }), errors(&["404"])),
+            },
+        },
+        "/queue/rmbposts/{id}": {
+            "parameters": [id_parameter("job id")],
+            "get": {
+                "tags": ["queue"],
+                "summary": "RMB post job status",
+                "responses": with(json!({
+                    "200": with(ok("job status", schema("RmbPostStatus")), json!({ "headers": retry_after() })),
+                }), errors(&["404"])),
+            },
+        },
+        "/telegrams": {
+            "get": {
+                "tags": ["telegrams"],
+                "summary": "Queued telegrams by sender nation",
+                "security": authenticated(),
+                "parameters": [
+                    { "name": "mine", "in": "query", "description": "only telegrams queued by the caller", "schema": { "type": "boolean", "default": false } },
+                ],
+                "responses": with(json!({
+                    "200": ok("queued telegrams", json!({
+                        "type": "object",
+                        "additionalProperties": { "type": "array", "items": schema("Telegram") },
+                    })),
+                }), errors(&["401"])),
+            },
+            "post": {
+                "tags": ["telegrams"],
+                "summary": "Queue telegrams",
+                "security": authenticated(),
+                "parameters": [
+                    { "name": "exclude_region", "in": "query", "description": "leave out nations already in this region", "schema": { "type": "string" } },
+                    { "name": "exclude_wa", "in": "query", "description": "leave out World Assembly members", "schema": { "type": "boolean", "default": false } },
+                    { "name": "founded_after", "in": "query", "description": "leave out nations founded before then", "schema": { "type": "string", "format": "date-time" } },
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": { "type": "array", "items": schema("TelegramParams") } } },
+                },
+                "description": "Recipients are canonicalized. Telegrams to invalid recipients, repeats within the batch and telegrams already waiting in the queue are dropped and reported rather than failing the request. Large batches are queued in chunks; should queueing fail partway, the telegrams left over are reported as not_queued. The filters are checked against the nations dump, `nations_dump_unavailable` until it's indexed; nations missing from the dump are kept. A telegram NS has been seen treating as another type than the one given fails the whole batch with 409, `telegram_type_mismatch`.",
+                "responses": with(json!({
+                    "202": ok("queued and dropped telegrams", schema("QueuedTelegrams")),
+                }), errors(&["400", "401", "409", "413", "415", "422", "503"])),
+            },
+            "delete": {
+                "tags": ["telegrams"],
+                "summary": "Remove a queued telegram",
+                "description": "Only the caller's own telegrams are removed unless they hold telegrams.delete.any.",
+                "security": authenticated(),
+                "requestBody": body("TelegramHeader"),
+                "responses": with(json!({ "200": { "description": "removed" } }), errors(&["401", "413", "415", "422"])),
+            },
+        },
+        "/telegrams/summary": {
+            "get": {
+                "tags": ["telegrams"],
+                "summary": "Queued telegrams and cooldowns by sender nation",
+                "security": authenticated(),
+                "responses": with(json!({
+                    "200": ok("summary by sender nation", json!({ "type": "object", "additionalProperties": schema("TelegramSenderSummary") })),
+                }), errors(&["401"])),
+            },
+        },
+        "/telegrams/capacity": {
+            "get": {
+                "tags": ["telegrams"],
+                "summary": "Recruitment telegram capacity",
+                "description": "Per sender nation. `known` holds hard numbers, `estimated` projections from the recruitment cooldown.",
+                "security": authenticated(),
+                "responses": with(json!({
+                    "200": ok("capacity by sender nation", json!({ "type": "object", "additionalProperties": schema("TelegramCapacity") })),
+                }), errors(&["401"])),
+            },
+        },
+        "/telegrams/{id}": {
+            "parameters": [id_parameter("job id")],
+            "delete": {
+                "tags": ["telegrams"],
+                "summary": "Remove a queued telegram by job id",
+                "description": "Telegrams queued by someone else need telegrams.delete.any.",
+                "security": authenticated(),
+                "responses": with(json!({ "204": { "description": "removed" } }), errors(&["401", "403", "404"])),
+            },
+        },
+        "/queue/telegrams/{id}": {
+            "parameters": [id_parameter("job id")],
+            "get": {
+                "tags": ["queue"],
+                "summary": "Telegram job status",
+                "responses": with(json!({ "200": ok("job status", schema("TelegramStatus")) }), errors(&["404"])),
+            },
+        },
+        "/telegrams/campaigns": {
+            "post": {
+                "tags": ["telegrams"],
+                "summary": "Start a recruitment campaign",
+                "description": "`recipients_from` is resolved through the NationStates API when the campaign is created; the request fails if the region can't be looked up or nothing in it matches. A telegram NS has been seen treating as another type than the one given is refused with 409, `telegram_type_mismatch`.",
+                "security": authenticated(),
+                "requestBody": body("NewCampaign"),
+                "responses": with(json!({ "201": ok("campaign created", schema("TelegramCampaign")) }), errors(&["400", "401", "409", "413", "415", "422", "503"])),
+            },
+        },
+        "/telegrams/campaigns/{id}": {
+            "parameters": [id_parameter("campaign id")],
+            "get": {
+                "tags": ["telegrams"],
+                "summary": "Campaign progress",
+                "security": authenticated(),
+                "responses": with(json!({ "200": ok("campaign", schema("TelegramCampaign")) }), errors(&["401", "404"])),
+            },
+        },
+        "/telegrams/campaigns/{id}/recipients": {
+            "parameters": [id_parameter("campaign id")],
+            "get": {
+                "tags": ["telegrams"],
+                "summary": "Campaign recipients",
+                "security": authenticated(),
+                "responses": with(json!({
+                    "200": ok("recipients in send order", json!({ "type": "array", "items": schema("CampaignRecipient") })),
+                }), errors(&["401", "404"])),
+            },
+        },
+        "/telegrams/campaigns/{id}/pause": {
+            "parameters": [id_parameter("campaign id")],
+            "post": {
+                "tags": ["telegrams"],
+                "summary": "Pause a campaign",
+                "security": authenticated(),
+                "responses": with(json!({ "200": ok("campaign", schema("TelegramCampaign")) }), errors(&["401", "404", "409"])),
+            },
+        },
+        "/telegrams/campaigns/{id}/resume": {
+            "parameters": [id_parameter("campaign id")],
+            "post": {
+                "tags": ["telegrams"],
+                "summary": "Resume a campaign",
+                "security": authenticated(),
+                "responses": with(json!({ "200": ok("campaign", schema("TelegramCampaign")) }), errors(&["401", "404", "409"])),
+            },
+        },
+        "/queue/events": {
+            "get": {
+                "tags": ["queue"],
+                "summary": "Server-sent events for dispatch and RMB post status changes",
+                "parameters": [
+                    { "name": "job", "in": "query", "schema": schema("JobKind") },
+                    { "name": "id", "in": "query", "schema": { "type": "integer", "format": "int32" } },
+                    { "name": "user", "in": "query", "description": "username of whoever queued the job", "schema": { "type": "string" } },
+                ],
+                "responses": {
+                    "200": {
+                        "description": "`dispatch` and `rmbpost` events carry a JobEvent, `lagged` the number of dropped events",
+                        "content": { "text/event-stream": { "schema": schema("JobEvent") } },
+                    },
+                },
+            },
+        },
+    });
+
+    let nations_and_queue = json!({
+        "/nations/{nation}/dispatches": {
+            "parameters": [{ "name": "nation", "in": "path", "required": true, "schema": { "type": "string" } }],
+            "get": {
+                "tags": ["dispatches"],
+                "summary": "List a nation's dispatches",
+                "parameters": [
+                    { "name": "include_inactive", "in": "query", "schema": { "type": "boolean", "default": false } },
+                    { "name": "summary", "in": "query", "description": "leave out the text", "schema": { "type": "boolean", "default": false } },
+                    { "name": "format", "in": "query", "schema": { "type": "string", "enum": ["bbcode", "html"], "default": "bbcode" } },
+                ],
+                "responses": {
+                    "200": ok("dispatches, as summaries with `summary=true`", json!({
+                        "oneOf": [
+                            { "type": "array", "items": schema("Dispatch") },
+                            { "type": "array", "items": schema("DispatchSummary") },
+                        ],
+                    })),
+                    "304": { "description": "matches `If-None-Match`" },
+                    "401": { "$ref": "#/components/responses/ErrorBody" },
+                    "429": { "$ref": "#/components/responses/PublicReadThrottled" },
+                },
+            },
+        },
+        "/queue/estimate": {
+            "get": {
+                "tags": ["queue"],
+                "summary": "When a dispatch job queued now would start",
+                "parameters": [
+                    { "name": "nation", "in": "query", "required": true, "schema": { "type": "string" } },
+                    { "name": "action", "in": "query", "required": true, "schema": { "type": "string", "enum": ["add", "edit", "remove", "delete"] } },
+                ],
+                "responses": with(json!({ "200": ok("estimate", schema("QueueEstimate")) }), errors(&["400", "404"])),
+            },
+        },
+    });
+
+    with(
+        with(accounts_and_dispatches, rmbposts_and_telegrams),
+        nations_and_queue,
+    )
+}
+
+fn schemas() -> Value {
+    let (categories, subcategories) = categories();
+
+    let timestamp = json!({ "type": "string", "format": "date-time" });
+    let nullable_timestamp = json!({ "type": ["string", "null"], "format": "date-time" });
+    let string = json!({ "type": "string" });
+    let nullable_string = json!({ "type": ["string", "null"] });
+    let integer = json!({ "type": "integer", "format": "int32" });
+    let nullable_integer = json!({ "type": ["integer", "null"], "format": "int32" });
+    let count = json!({ "type": "integer", "format": "int64" });
+    let category = json!({ "type": "integer", "enum": categories });
+    let subcategory = json!({
+        "type": "integer",
+        "enum": subcategories,
+        "description": "must belong to `category`: 1xx for 1, 3xx for 3 and so on",
+    });
+    let superseded_by = json!({
+        "type": "integer",
+        "format": "int32",
+        "description": "id of the job that replaced this one, once it's `superseded`",
+    });
+
+    // split up, one literal this size exceeds the `json!` recursion limit
+    let accounts_and_dispatches = json!({
+        "ErrorBody": {
+            "type": "object",
+            "required": ["code", "message"],
+            "properties": {
+                "code": { "type": "string", "description": "stable, e.g. `dispatch_not_found` or `invalid_factbook_category`" },
+                "message": { "type": "string", "description": "for humans, may change" },
+                "details": { "type": "object", "description": "more about the error, depending on `code`" },
+            },
+        },
+        "InvalidBody": {
+            "type": "object",
+            "required": ["line", "column"],
+            "properties": {
+                "path": { "type": "string", "description": "the offending field, e.g. `[0].tg_type`" },
+                "expected": string,
+                "received": string,
+                "line": integer,
+                "column": integer,
+            },
+        },
+        "QuotaExceeded": {
+            "type": "object",
+            "required": ["quota", "limit", "oldest_job_created_at", "oldest_job_age_secs", "available_at"],
+            "properties": {
+                "quota": string,
+                "limit": count,
+                "oldest_job_created_at": nullable_timestamp,
+                "oldest_job_age_secs": { "type": ["integer", "null"], "format": "int64" },
+                "available_at": nullable_timestamp,
+            },
+        },
+        "LoginThrottled": {
+            "type": "object",
+            "required": ["retry_after_secs"],
+            "properties": { "retry_after_secs": count },
+        },
+        "LoginData": {
+            "type": "object",
+            "required": ["username", "password"],
+            "properties": { "username": string, "password": { "type": "string", "format": "password" } },
+        },
+        "Login": {
+            "type": "object",
+            "required": ["username", "token", "expires_at"],
+            "properties": { "username": string, "token": string, "expires_at": timestamp },
+        },
+        "RefreshData": {
+            "type": "object",
+            "required": ["token"],
+            "properties": { "token": string },
+        },
+        "NewApiKey": {
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string", "maxLength": 100 },
+                "claims": { "type": "array", "items": string, "default": [], "description": "a subset of your own claims" },
+                "expires_at": nullable_timestamp,
+            },
+        },
+        "ApiKey": {
+            "type": "object",
+            "required": ["id", "name", "claims", "expires_at", "last_used_at", "revoked_at", "created_at"],
+            "properties": {
+                "id": integer,
+                "name": string,
+                "key": string,
+                "claims": { "type": "array", "items": string },
+                "expires_at": nullable_timestamp,
+                "last_used_at": nullable_timestamp,
+                "revoked_at": nullable_timestamp,
+                "created_at": timestamp,
+            },
+        },
+        "Bootstrap": {
+            "type": "object",
+            "required": ["version", "user", "dispatch_nations", "rmbpost_nations", "categories", "limits", "presets"],
+            "properties": {
+                "version": { "type": "integer", "enum": [1], "description": "only bumped when a field changes or goes away, new fields may appear within a version" },
+                "user": {
+                    "type": "object",
+                    "required": ["id", "username", "claims"],
+                    "properties": { "id": integer, "username": string, "claims": { "type": "array", "items": string } },
+                },
+                "dispatch_nations": { "type": "array", "items": string },
+                "rmbpost_nations": { "type": "array", "items": string },
+                "categories": { "type": "array", "items": schema("DispatchCategory") },
+                "limits": {
+                    "type": "object",
+                    "required": ["dispatches", "rmbposts", "quota"],
+                    "properties": {
+                        "dispatches": {
+                            "type": "object",
+                            "required": ["max_text_length", "max_group_size", "text_formats", "scheduled_posts"],
+                            "properties": {
+                                "max_text_length": { "type": "integer", "description": "counted after non-ASCII characters have been encoded" },
+                                "max_group_size": { "type": "integer", "description": "nations one `POST /dispatches/multi` may post as" },
+                                "text_formats": { "type": "array", "items": { "type": "string", "enum": ["bbcode", "markdown"] } },
+                                "scheduled_posts": { "type": "boolean" },
+                            },
+                        },
+                        "rmbposts": {
+                            "type": "object",
+                            "required": ["max_batch_size"],
+                            "properties": { "max_batch_size": { "type": "integer", "description": "regions one `POST /rmbposts/batch` may post to" } },
+                        },
+                        "quota": {
+                            "type": "object",
+                            "required": ["max_pending", "max_daily"],
+                            "description": "jobs of each kind you may queue, `null` where there's no limit",
+                            "properties": {
+                                "max_pending": { "type": ["integer", "null"], "format": "int64" },
+                                "max_daily": { "type": ["integer", "null"], "format": "int64" },
+                            },
+                        },
+                    },
+                },
+                "presets": { "type": "array", "items": schema("NationPreset"), "description": "what new dispatches of each nation with a preset are filled in with" },
+            },
+        },
+        "NationPreset": {
+            "type": "object",
+            "required": ["nation", "default_category", "default_subcategory", "title_prefix", "signature_text", "updated_by", "updated_at"],
+            "properties": {
+                "nation": string,
+                "default_category": { "type": ["integer", "null"], "description": "used when a new dispatch leaves out `category` and `subcategory`" },
+                "default_subcategory": { "type": ["integer", "null"] },
+                "title_prefix": { "type": ["string", "null"], "description": "put before the title, unless it already starts with it" },
+                "signature_text": { "type": ["string", "null"], "description": "appended to the text after a blank line, unless it already ends with it" },
+                "updated_by": string,
+                "updated_at": { "type": "string", "format": "date-time" },
+            },
+        },
+        "TextFormat": { "type": "string", "enum": ["bbcode", "markdown"] },
+        "NewDispatch": {
+            "type": "object",
+            "description": "`category` and `subcategory` may be left out if the nation's preset has defaults for them, else the dispatch is refused with `missing_dispatch_category`. The preset's title prefix and signature are added before the text is encoded.",
+            "required": ["nation", "title", "text"],
+            "properties": {
+                "nation": string,
+                "title": string,
+                "text": string,
+                "category": category,
+                "subcategory": subcategory,
+                "format": with(schema("TextFormat"), json!({ "default": "bbcode" })),
+            },
+        },
+        "EditDispatch": {
+            "type": "object",
+            "required": ["title", "text", "category", "subcategory"],
+            "properties": {
+                "title": string,
+                "text": string,
+                "category": category,
+                "subcategory": subcategory,
+                "format": with(schema("TextFormat"), json!({ "default": "bbcode" })),
+                "base_revision": { "type": "integer", "description": "`revision` the edit is based on, rejected with 409 if the dispatch has changed since" },
+            },
+        },
+        "Dispatch": {
+            "type": "object",
+            "required": ["id", "nation", "category", "subcategory", "title", "text", "revision", "source_format", "created_by", "modified_at", "is_active", "is_protected"],
+            "properties": {
+                "id": integer,
+                "nation": string,
+                "category": category,
+                "subcategory": subcategory,
+                "title": string,
+                "text": { "type": "string", "description": "BBCode as posted to NS" },
+                "revision": { "type": "integer", "description": "pass as `If-Match` or `base_revision` when editing" },
+                "job_id": { "type": ["integer", "null"], "format": "int32", "description": "the job that wrote this revision, null for imported revisions and older ones" },
+                "size": { "type": ["integer", "null"], "format": "int32", "description": "length of `text` as NS counts it, null for older revisions" },
+                "source_format": schema("TextFormat"),
+                "source": { "type": "string", "description": "text as submitted, only present when it wasn't BBCode" },
+                "created_by": string,
+                "modified_at": timestamp,
+                "is_active": { "type": "boolean" },
+                "is_protected": { "type": "boolean", "description": "can't be deleted until an admin unprotects it" },
+                "rendered": { "type": "string", "description": "only present with `format=html`" },
+                "pending_or_failed_edits": with(schema("UnappliedEdits"), json!({ "description": "only present for a single dispatch" })),
+            },
+        },
+        "UnappliedEdits": {
+            "type": "object",
+            "description": "edits the latest revision doesn't reflect: those pending, and those that failed after it was written",
+            "required": ["pending", "failed"],
+            "properties": {
+                "pending": integer,
+                "failed": integer,
+                "latest_failed": { "oneOf": [schema("FailedEdit"), { "type": "null" }] },
+            },
+        },
+        "FailedEdit": {
+            "type": "object",
+            "description": "its content is at `/queue/dispatches/{job_id}?include_payload=true`, and `POST /dispatches/{id}/reapply/{job_id}` queues it again",
+            "required": ["job_id", "created_by", "created_at", "failed_at"],
+            "properties": {
+                "job_id": integer,
+                "created_by": string,
+                "created_at": timestamp,
+                "failed_at": timestamp,
+                "error_code": { "type": ["string", "null"] },
+            },
+        },
+        "NewDispatchGroup": {
+            "type": "object",
+            "required": ["nations", "title", "text", "category", "subcategory"],
+            "properties": {
+                "nations": { "type": "array", "items": string, "minItems": 1, "maxItems": 10 },
+                "title": string,
+                "text": string,
+                "category": category,
+                "subcategory": subcategory,
+                "format": with(schema("TextFormat"), json!({ "default": "bbcode" })),
+            },
+        },
+        "DispatchGroup": {
+            "type": "object",
+            "required": ["id", "status", "self", "created_by", "created_at", "jobs"],
+            "properties": {
+                "id": integer,
+                "status": { "type": "string", "enum": ["queued", "succeeded", "failed", "partial"] },
+                "self": string,
+                "created_by": string,
+                "created_at": timestamp,
+                "jobs": { "type": "array", "items": schema("DispatchStatus") },
+            },
+        },
+        "DispatchSummary": {
+            "type": "object",
+            "required": ["id", "nation", "category", "subcategory", "title", "created_by", "modified_at", "is_active", "is_protected"],
+            "properties": {
+                "id": integer,
+                "nation": string,
+                "category": category,
+                "subcategory": subcategory,
+                "title": string,
+                "created_by": string,
+                "modified_at": timestamp,
+                "is_active": { "type": "boolean" },
+                "is_protected": { "type": "boolean" },
+            },
+        },
+        "DispatchMetrics": {
+            "type": "object",
+            "required": ["id", "samples"],
+            "properties": {
+                "id": integer,
+                "samples": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["views", "score", "sampled_at"],
+                        "properties": {
+                            "views": integer,
+                            "score": integer,
+                            "sampled_at": timestamp,
+                        },
+                    },
+                },
+            },
+        },
+        "DispatchSearchResult": {
+            "type": "object",
+            "required": ["id", "nation", "category", "subcategory", "title", "created_by", "modified_at", "is_active", "is_protected", "rank", "title_highlight", "snippet"],
+            "properties": {
+                "id": integer,
+                "nation": string,
+                "category": category,
+                "subcategory": subcategory,
+                "title": string,
+                "created_by": string,
+                "modified_at": timestamp,
+                "is_active": { "type": "boolean" },
+                "is_protected": { "type": "boolean" },
+                "rank": { "type": "number", "description": "higher for better matches, title matches count more than text matches" },
+                "title_highlight": { "type": "string", "description": "the title with the matched words in `<mark>` tags" },
+                "snippet": { "type": "string", "description": "passages of the text around the matched words, marked the same way" },
+            },
+        },
+        "JobDurations": {
+            "type": "object",
+            "description": "where the job's time went, in milliseconds, null for phases it hasn't got through",
+            "required": ["queued_ms", "ratelimit_wait_ms", "ns_prepare_ms", "ns_execute_ms"],
+            "properties": {
+                "queued_ms": nullable_integer,
+                "ratelimit_wait_ms": nullable_integer,
+                "ns_prepare_ms": nullable_integer,
+                "ns_execute_ms": nullable_integer,
+            },
+        },
+    });
+
+    let jobs = json!({
+        "EditConflict": {
+            "type": "object",
+            "required": ["base_revision", "revision", "revised_by", "revised_at"],
+            "properties": {
+                "base_revision": { "type": "integer", "description": "revision the edit was based on" },
+                "revision": { "type": "integer", "description": "latest revision" },
+                "revised_by": { "type": "string", "description": "author of the latest revision" },
+                "revised_at": timestamp,
+            },
+        },
+        "PendingJobs": {
+            "type": "object",
+            "required": ["dispatch_id", "jobs"],
+            "properties": {
+                "dispatch_id": { "type": "integer" },
+                "jobs": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "action", "status"],
+                        "properties": {
+                            "id": { "type": "integer", "description": "job id" },
+                            "action": { "type": "string", "enum": ["edit", "delete"] },
+                            "status": schema("JobStatus"),
+                        },
+                    },
+                },
+            },
+        },
+        "DeletedDispatch": {
+            "type": "object",
+            "required": ["id", "deleted_at", "deleted_by"],
+            "properties": {
+                "id": integer,
+                "deleted_at": { "type": ["string", "null"], "format": "date-time", "description": "unknown for dispatches deleted before it was recorded" },
+                "deleted_by": nullable_string,
+            },
+        },
+        "ProtectedDispatch": {
+            "type": "object",
+            "required": ["id", "protected_by", "protected_at"],
+            "properties": {
+                "id": integer,
+                "protected_by": nullable_string,
+                "protected_at": { "type": ["string", "null"], "format": "date-time" },
+            },
+        },
+        "ImportDispatch": {
+            "type": "object",
+            "required": ["dispatch_id", "nation"],
+            "properties": {
+                "dispatch_id": integer,
+                "nation": { "type": "string", "description": "the author" },
+            },
+        },
+        "PreviewData": {
+            "type": "object",
+            "required": ["text"],
+            "properties": { "text": string },
+        },
+        "EncodingPreview": {
+            "type": "object",
+            "required": ["text", "length", "max_length", "converted"],
+            "properties": {
+                "text": string,
+                "length": { "type": "integer", "description": "what NS holds against `max_length`" },
+                "max_length": { "type": "integer" },
+                "converted": {
+                    "type": "array",
+                    "description": "non-ASCII characters that were replaced, most frequent first",
+                    "items": {
+                        "type": "object",
+                        "required": ["character", "entity", "count"],
+                        "properties": { "character": string, "entity": string, "count": { "type": "integer" } },
+                    },
+                },
+            },
+        },
+        "JobStatus": {
+            "type": "string",
+            "enum": ["scheduled", "queued", "deferred", "claimed", "posting", "unknown", "succeeded", "failed", "cancelled", "superseded"],
+        },
+        "NewRmbPost": {
+            "type": "object",
+            "required": ["nation", "region", "text"],
+            "properties": { "nation": string, "region": string, "text": string },
+        },
+        "RmbPostStatus": {
+            "type": "object",
+            "required": ["id", "status", "rmbpost_id", "error", "self", "resource", "created_at", "modified_at"],
+            "properties": {
+                "id": integer,
+                "status": schema("JobStatus"),
+                "rmbpost_id": nullable_integer,
+                "error": nullable_string,
+                "error_code": string,
+                "warnings": { "type": "array", "items": string, "description": "content rules the text matched that only warn" },
+                "self": string,
+                "resource": nullable_string,
+                "created_at": timestamp,
+                "claimed_at": nullable_timestamp,
+                "started_at": nullable_timestamp,
+                "prepared_at": nullable_timestamp,
+                "executing_at": nullable_timestamp,
+                "finished_at": nullable_timestamp,
+                "modified_at": timestamp,
+                "durations": schema("JobDurations"),
+                "estimated_completion_at": { "type": "string", "format": "date-time", "description": "when the job is expected to be done, while it's waiting in the worker's queue" },
+            },
+        },
+        "TgType": { "type": "string", "enum": ["recruitment", "standard"] },
+        "TelegramParams": {
+            "type": "object",
+            "required": ["sender", "id", "recipient", "secret_key", "tg_type"],
+            "properties": {
+                "sender": string,
+                "id": { "type": "string", "description": "telegram id" },
+                "recipient": string,
+                "secret_key": { "type": "string", "writeOnly": true },
+                "tg_type": schema("TgType"),
+            },
+        },
+        "TelegramHeader": {
+            "type": "object",
+            "required": ["recipient", "telegram_id"],
+            "properties": { "recipient": string, "telegram_id": string },
+        },
+        "Telegram": {
+            "type": "object",
+            "required": ["sender", "recipient", "id", "created_by"],
+            "properties": {
+                "sender": string,
+                "recipient": string,
+                "id": { "type": "string", "description": "telegram id" },
+                "created_by": { "type": "string", "description": "the user who queued the telegram, or created its campaign" },
+            },
+        },
+        "QueuedTelegram": {
+            "type": "object",
+            "required": ["id", "recipient", "telegram_id", "queue"],
+            "properties": {
+                "id": integer,
+                "recipient": string,
+                "telegram_id": string,
+                "queue": schema("TgType"),
+            },
+        },
+        "QueuedTelegrams": {
+            "type": "object",
+            "required": ["accepted", "duplicates_dropped", "invalid", "filtered", "queued", "dropped"],
+            "properties": {
+                "accepted": { "type": "integer", "description": "number of telegrams queued" },
+                "duplicates_dropped": { "type": "integer", "description": "repeats within the request and telegrams already waiting for the same recipient" },
+                "invalid": { "type": "array", "items": { "type": "integer" }, "description": "positions in the request of telegrams to recipients that aren't nation names" },
+                "filtered": { "type": "object", "additionalProperties": { "type": "integer" }, "description": "recipients each filter left out, by filter; filters that left out none aren't listed" },
+                "queued": { "type": "array", "items": schema("QueuedTelegram"), "description": "in request order" },
+                "dropped": { "type": "array", "items": schema("DroppedTelegram"), "description": "in request order" },
+            },
+        },
+        "DroppedTelegram": {
+            "type": "object",
+            "required": ["index", "recipient", "telegram_id", "reason"],
+            "properties": {
+                "index": { "type": "integer", "description": "position in the request" },
+                "recipient": { "type": "string", "description": "as submitted" },
+                "telegram_id": string,
+                "reason": { "type": "string", "enum": ["invalid_recipient", "duplicate", "already_queued", "not_queued", "exclude_region", "exclude_wa", "founded_after"], "description": "or the filter that left the recipient out" },
+            },
+        },
+        "TelegramStatus": {
+            "type": "object",
+            "required": ["id", "sender", "recipient", "telegram_id", "queue", "status", "error", "created_at", "modified_at"],
+            "properties": {
+                "id": integer,
+                "sender": string,
+                "recipient": string,
+                "telegram_id": string,
+                "queue": schema("TgType"),
+                "status": { "type": "string", "enum": ["queued", "sent", "failed", "skipped"] },
+                "error": nullable_string,
+                "error_code": string,
+                "created_at": timestamp,
+                "modified_at": timestamp,
+            },
+        },
+    });
+
+    let campaigns = json!({
+        "NewCampaign": {
+            "type": "object",
+            "required": ["name", "sender", "telegram_id", "secret_key", "tg_type"],
+            "properties": {
+                "name": string,
+                "sender": string,
+                "telegram_id": string,
+                "secret_key": { "type": "string", "writeOnly": true },
+                "tg_type": schema("TgType"),
+                "recipients": { "type": "array", "items": string, "default": [] },
+                "recipients_text": { "type": "string", "description": "newline or comma separated recipients" },
+                "recipients_from": schema("RecipientSource"),
+                "filter": schema("RecipientFilter"),
+                "paused": { "type": "boolean", "default": false, "description": "create the campaign paused, to review its recipients first" },
+            },
+        },
+        "RecipientSource": {
+            "type": "object",
+            "required": ["region"],
+            "properties": {
+                "region": string,
+                "founded_since": { "type": "string", "format": "date-time", "description": "only nations founded in the region since then" },
+                "non_wa": { "type": "boolean", "default": false, "description": "leave out World Assembly members" },
+            },
+        },
+        "RecipientFilter": {
+            "type": "object",
+            "description": "checked against the nations dump; nations missing from it are kept",
+            "required": [],
+            "properties": {
+                "exclude_region": { "type": "string", "description": "leave out nations already in this region" },
+                "exclude_wa": { "type": "boolean", "default": false, "description": "leave out World Assembly members" },
+                "founded_after": { "type": "string", "format": "date-time", "description": "leave out nations founded before then" },
+            },
+        },
+        "CampaignRecipient": {
+            "type": "object",
+            "required": ["position", "recipient", "status"],
+            "properties": {
+                "position": integer,
+                "recipient": string,
+                "status": { "type": "string", "enum": ["pending", "queued", "sent", "failed", "skipped"] },
+            },
+        },
+        "TelegramCampaign": {
+            "type": "object",
+            "required": ["id", "name", "sender", "telegram_id", "tg_type", "status", "recipients", "sent", "failed", "skipped", "remaining", "created_by", "created_at", "modified_at"],
+            "properties": {
+                "id": integer,
+                "name": string,
+                "sender": string,
+                "telegram_id": string,
+                "tg_type": schema("TgType"),
+                "status": { "type": "string", "enum": ["active", "paused", "completed"] },
+                "recipients": count,
+                "sent": count,
+                "failed": count,
+                "skipped": count,
+                "remaining": count,
+                "rejected": { "type": "array", "items": string, "description": "only reported on creation" },
+                "recipients_from": schema("RecipientSource"),
+                "expanded_recipients": { "type": "integer", "description": "recipients `recipients_from` added on top of the submitted list" },
+                "filtered": { "type": "object", "additionalProperties": { "type": "integer" }, "description": "recipients each filter left out, by filter, only reported on creation" },
+                "created_by": string,
+                "created_at": timestamp,
+                "modified_at": timestamp,
+            },
+        },
+        "JobKind": { "type": "string", "enum": ["dispatch", "rmbpost"] },
+        "JobEvent": {
+            "type": "object",
+            "required": ["job", "id", "status", "self", "resource"],
+            "properties": {
+                "job": schema("JobKind"),
+                "id": integer,
+                "status": schema("JobStatus"),
+                "error_code": string,
+                "self": string,
+                "resource": nullable_string,
+            },
+        },
+    });
+
+    let capacity = json!({
+        "TelegramSenderSummary": {
+            "type": "object",
+            "required": ["recruitment_count", "standard_count", "oldest_queued_at", "recruitment_ready_in", "standard_ready_in"],
+            "properties": {
+                "recruitment_count": { "type": "integer" },
+                "standard_count": { "type": "integer" },
+                "oldest_queued_at": { "type": ["string", "null"], "format": "date-time" },
+                "recruitment_ready_in": { "type": "integer", "description": "seconds until the sender can send its next recruitment telegram" },
+                "standard_ready_in": { "type": "integer", "description": "seconds until the sender can send its next standard telegram" },
+            },
+        },
+        "TelegramCapacity": {
+            "type": "object",
+            "required": ["known", "estimated"],
+            "properties": {
+                "known": schema("CapacityCounts"),
+                "estimated": schema("CapacityEstimates"),
+            },
+        },
+        "CapacityCounts": {
+            "type": "object",
+            "description": "hard numbers, counted from sent telegrams, the queue and the ratelimiter",
+            "required": ["sent_last_24h", "queued", "campaign_backlog", "cooldown_secs", "ready_in"],
+            "properties": {
+                "sent_last_24h": count,
+                "queued": count,
+                "campaign_backlog": { "type": "integer", "format": "int64", "description": "recipients of active recruitment campaigns not queued yet" },
+                "cooldown_secs": count,
+                "ready_in": { "type": "integer", "format": "int64", "description": "seconds until the next recruitment telegram can be sent" },
+            },
+        },
+        "CapacityEstimates": {
+            "type": "object",
+            "description": "projections assuming one recruitment telegram per cooldown and nothing else holding the nation back",
+            "required": ["max_per_day", "max_remaining_today", "projected_completion_at"],
+            "properties": {
+                "max_per_day": count,
+                "max_remaining_today": { "type": "integer", "format": "int64", "description": "before midnight UTC" },
+                "projected_completion_at": { "type": ["string", "null"], "format": "date-time", "description": "when the queue and campaign backlog would be sent, null if there is nothing to send" },
+            },
+        },
+    });
+
+    let statuses = json!({
+        "DispatchStatus": {
+            "type": "object",
+            "required": ["id", "action", "status", "dispatch_id", "error", "self", "resource", "created_at", "modified_at"],
+            "properties": {
+                "id": integer,
+                "action": { "type": "string", "enum": ["add", "edit", "delete"] },
+                "status": schema("JobStatus"),
+                "dispatch_id": nullable_integer,
+                "error": nullable_string,
+                "error_code": string,
+                "self": string,
+                "resource": nullable_string,
+                "warnings": { "type": "array", "items": string, "description": "conversion problems, content rules the text matched that only warn, and whether the text is close to NS's length limit" },
+                "created_at": timestamp,
+                "claimed_at": nullable_timestamp,
+                "started_at": nullable_timestamp,
+                "prepared_at": nullable_timestamp,
+                "executing_at": nullable_timestamp,
+                "finished_at": nullable_timestamp,
+                "modified_at": timestamp,
+                "durations": schema("JobDurations"),
+                "superseded_by": superseded_by,
+                "revision_id": { "type": ["integer", "null"], "format": "int32", "description": "the revision the job wrote, once an add or edit has succeeded" },
+                "estimated_completion_at": { "type": "string", "format": "date-time", "description": "when the job is expected to be done, while it's waiting in the worker's queue" },
+                "payload": { "type": "object", "description": "the submitted job, only with `include_payload=true`" },
+            },
+        },
+        "QueueEstimate": {
+            "type": "object",
+            "required": ["nation", "queue_position", "pending_for_nation", "estimated_start_at"],
+            "properties": {
+                "nation": string,
+                "queue_position": { "type": "integer", "description": "place in the worker's queue the job would take" },
+                "pending_for_nation": { "type": "integer" },
+                "estimated_start_at": timestamp,
+            },
+        },
+        "DispatchCategory": {
+            "type": "object",
+            "required": ["id", "name", "subcategories"],
+            "properties": {
+                "id": category,
+                "name": { "type": "string", "description": "as NS shows it, e.g. `Factbook`" },
+                "subcategories": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "name"],
+                        "properties": { "id": subcategory, "name": string },
+                    },
+                },
+            },
+        },
+        "PublicReadThrottled": {
+            "type": "object",
+            "required": ["retry_after_secs"],
+            "properties": { "retry_after_secs": count },
+        },
+    });
+
+    let batches = json!({
+        "NewRmbPostBatch": {
+            "type": "object",
+            "required": ["nation", "regions", "text"],
+            "properties": {
+                "nation": string,
+                "regions": { "type": "array", "items": string, "minItems": 1, "maxItems": 20 },
+                "text": string,
+            },
+        },
+        "RmbPostGroup": {
+            "type": "object",
+            "required": ["id", "status", "self", "created_by", "created_at", "jobs"],
+            "properties": {
+                "id": integer,
+                "status": { "type": "string", "enum": ["queued", "succeeded", "failed", "partial"] },
+                "self": string,
+                "created_by": string,
+                "created_at": timestamp,
+                "jobs": { "type": "array", "items": schema("RmbPostStatus") },
+            },
+        },
+        "PipelineBackedUp": {
+            "type": "object",
+            "required": ["pipeline", "deferred_jobs", "retry_after_secs"],
+            "properties": {
+                "pipeline": { "type": "string", "enum": ["dispatch", "rmbpost", "telegram"] },
+                "deferred_jobs": { "type": "array", "items": integer, "description": "ids of the jobs left for the worker to pick up" },
+                "retry_after_secs": count,
+            },
+        },
+        "ContentPolicyViolation": {
+            "type": "object",
+            "required": ["rules"],
+            "properties": {
+                "rules": { "type": "array", "items": string, "description": "names of the rules the content broke" },
+            },
+        },
+        "RmbPostRefused": {
+            "type": "object",
+            "required": ["reason"],
+            "properties": {
+                "reason": { "type": "string", "enum": ["no_embassy", "embassy_pending", "residents_only"] },
+            },
+        },
+    });
+
+    with(
+        with(
+            with(
+                with(with(accounts_and_dispatches, jobs), campaigns),
+                capacity,
+            ),
+            statuses,
+        ),
+        batches,
+    )
+}
+
+/// The OpenAPI description of the public API.
+pub(crate) fn document() -> Value {
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "eurocore",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths(),
+        "components": {
+            "securitySchemes": {
+                BEARER: {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "bearerFormat": "JWT",
+                    "description": "token from `/login`",
+                },
+                API_KEY: {
+                    "type": "apiKey",
+                    "in": "header",
+                    "name": "X-API-Key",
+                    "description": "key from `/users/me/api-keys`, acting with only the claims it was granted",
+                },
+            },
+            "schemas": schemas(),
+            "responses": {
+                "ErrorBody": {
+                    "description": "error code and message, e.g. `job_not_found` or `unsupported_media_type`",
+                    "content": { "application/json": { "schema": schema("ErrorBody") } },
+                },
+                "InvalidBody": {
+                    "description": "the JSON body couldn't be read into the expected type, 400 for malformed JSON; `code` is `malformed_json`, `missing_field`, `invalid_type`, `invalid_value` or `invalid_body`",
+                    "content": { "application/json": { "schema": error_with("InvalidBody") } },
+                },
+                "QuotaExceeded": {
+                    "description": "too many pending or daily jobs, `quota_exceeded`",
+                    "content": { "application/json": { "schema": error_with("QuotaExceeded") } },
+                },
+                "Unavailable": {
+                    "description": "`pipeline_backed_up`: the worker's channel stayed full. Jobs the request stored are `deferred` and posted once the worker has room, they're named in `details` and mustn't be queued again",
+                    "headers": {
+                        "Retry-After": {
+                            "description": "seconds until the worker looks for deferred jobs",
+                            "schema": { "type": "integer" },
+                        },
+                    },
+                    "content": { "application/json": { "schema": { "oneOf": [schema("ErrorBody"), error_with("PipelineBackedUp")] } } },
+                },
+                "RmbPostRefused": {
+                    "description": "the JSON body couldn't be read, as for `InvalidBody`, the text breaks a content rule, as for `ContentPolicyViolation`, or, with `rmbpost_preflight` on, `rmbpost_refused`: the nation can't post on the region's RMB",
+                    "content": { "application/json": { "schema": { "oneOf": [error_with("InvalidBody"), error_with("ContentPolicyViolation"), error_with("RmbPostRefused")] } } },
+                },
+                "ContentPolicyViolation": {
+                    "description": "the JSON body couldn't be read, as for `InvalidBody`, or `content_policy_violation`: the title or text matched content rules that ban it",
+                    "content": { "application/json": { "schema": { "oneOf": [error_with("InvalidBody"), error_with("ContentPolicyViolation")] } } },
+                },
+                "LoginThrottled": {
+                    "description": "too many failed logins for the username or from the client's address, `login_throttled`",
+                    "headers": {
+                        "Retry-After": {
+                            "description": "seconds until the next attempt is accepted",
+                            "schema": { "type": "integer" },
+                        },
+                    },
+                    "content": { "application/json": { "schema": error_with("LoginThrottled") } },
+                },
+                "PublicReadThrottled": {
+                    "description": "too many requests without credentials from the client's address, `public_read_throttled`; log in for a higher limit",
+                    "headers": {
+                        "Retry-After": {
+                            "description": "seconds until the next request is accepted",
+                            "schema": { "type": "integer" },
+                        },
+                    },
+                    "content": { "application/json": { "schema": error_with("PublicReadThrottled") } },
+                },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ns::dispatch::{self, EditDispatch, NewDispatch, NewDispatchGroup, TextFormat};
+    use crate::ns::region::RmbRefusal;
+    use crate::ns::rmbpost::{NewRmbPost, NewRmbPostBatch};
+    use crate::ns::telegram::{Header, NewCampaign, Params, RecipientFilter, RecipientSource};
+    use crate::types::job::JobStatus;
+    use crate::types::request::{
+        ImportDispatch, JobKind, LoginData, NewApiKey, PreviewData, RefreshData,
+    };
+    use crate::types::response::{
+        ApiKey, Bootstrap, CampaignRecipient, CapacityCounts, CapacityEstimates,
+        ConvertedCharacter, DeletedDispatch, Dispatch, DispatchGroup, DispatchLimits,
+        DispatchMetricSample, DispatchMetrics, DispatchSearchResult, DispatchStatus,
+        DispatchSummary, DroppedTelegram, EditConflict, EncodingPreview, ErrorBody, FailedEdit,
+        InvalidBody, JobDurations, JobEvent, Limits, Login, NationPreset, PendingJob, PendingJobs,
+        ProtectedDispatch, QueueEstimate, QueuedTelegram, QueuedTelegrams, QuotaExceeded,
+        QuotaLimits, RmbPostGroup, RmbPostStatus, RmbpostLimits, SessionUser, Telegram,
+        TelegramCampaign, TelegramCapacity, TelegramSenderSummary, TelegramStatus, UnappliedEdits,
+    };
+    use serde::Serialize;
+    use serde::de::DeserializeOwned;
+    use std::collections::BTreeMap;
+
+    fn component(name: &str) -> Value {
+        DOCUMENT["components"]["schemas"][name].clone()
+    }
+
+    fn resolve(schema: &Value) -> Value {
+        match schema["$ref"].as_str() {
+            Some(reference) => component(reference.rsplit('/').next().unwrap()),
+            None => schema.clone(),
+        }
+    }
+
+    /// A value of the shape `schema` describes, with only required properties if `required_only`.
+    fn sample(schema: &Value, required_only: bool) -> Value {
+        let schema = resolve(schema);
+
+        if let Some(values) = schema["enum"].as_array() {
+            return values[0].clone();
+        }
+
+        let kind = match &schema["type"] {
+            Value::Array(kinds) => kinds[0].as_str().unwrap().to_string(),
+            kind => kind.as_str().unwrap_or("object").to_string(),
+        };
+
+        match kind.as_str() {
+            "object" => {
+                let required: Vec<&str> = schema["required"]
+                    .as_array()
+                    .map(|required| required.iter().filter_map(Value::as_str).collect())
+                    .unwrap_or_default();
+
+                schema["properties"]
+                    .as_object()
+                    .map(|properties| {
+                        properties
+                            .iter()
+                            .filter(|(name, _)| !required_only || required.contains(&name.as_str()))
+                            .map(|(name, property)| (name.clone(), sample(property, required_only)))
+                            .collect::<serde_json::Map<_, _>>()
+                    })
+                    .unwrap_or_default()
+                    .into()
+            }
+            "array" => json!([sample(&schema["items"], required_only)]),
+            "integer" => json!(1),
+            "boolean" => json!(false),
+            _ if schema["format"] == "date-time" => json!("2026-10-15T12:00:00Z"),
+            _ => json!("x"),
+        }
+    }
+
+    /// Requests built from the schema are accepted by the type the handler extracts.
+    fn assert_accepts<T: DeserializeOwned>(name: &str) {
+        for required_only in [true, false] {
+            let value = sample(&component(name), required_only);
+
+            if let Err(e) = serde_json::from_value::<T>(value.clone()) {
+                panic!("{name} rejects {value}: {e}");
+            }
+        }
+    }
+
+    /// `schema` with its reference resolved and the parts of an `allOf` merged into one object.
+    fn flatten(schema: &Value) -> Value {
+        let schema = resolve(schema);
+
+        let Some(parts) = schema["allOf"].as_array() else {
+            return schema;
+        };
+
+        let (mut properties, mut required) = (serde_json::Map::new(), Vec::new());
+
+        for part in parts.iter().map(flatten) {
+            if let Some(part) = part["properties"].as_object() {
+                properties.extend(part.clone());
+            }
+
+            required.extend(part["required"].as_array().cloned().unwrap_or_default());
+        }
+
+        json!({ "type": "object", "required": required, "properties": properties })
+    }
+
+    fn is_kind(value: &Value, kind: &str) -> bool {
+        match kind {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            "null" => value.is_null(),
+            _ => panic!("unknown type {kind}"),
+        }
+    }
+
+    /// Where `value`, at `path`, strays from `schema`: a property that isn't documented or a
+    /// required one that's missing, a value of the wrong type or outside its enum, all the way
+    /// down through nested objects and arrays.
+    fn mismatches(schema: &Value, value: &Value, path: &str) -> Vec<String> {
+        let schema = flatten(schema);
+
+        if let Some(branches) = schema["oneOf"].as_array() {
+            return match branches
+                .iter()
+                .any(|branch| mismatches(branch, value, path).is_empty())
+            {
+                true => Vec::new(),
+                false => vec![format!("{path} matches none of its schemas: {value}")],
+            };
+        }
+
+        if let Some(values) = schema["enum"].as_array()
+            && !values.contains(value)
+        {
+            return vec![format!("{path} isn't one of {values:?}: {value}")];
+        }
+
+        let kinds: Vec<&str> = match &schema["type"] {
+            Value::String(kind) => vec![kind.as_str()],
+            Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+
+        if !kinds.is_empty() && !kinds.iter().any(|kind| is_kind(value, kind)) {
+            return vec![format!("{path} isn't {}: {value}", kinds.join(" or "))];
+        }
+
+        match value {
+            Value::Object(object) => {
+                let properties = schema["properties"].as_object();
+                let additional = &schema["additionalProperties"];
+                let mut found = Vec::new();
+
+                for (key, property) in object {
+                    let path = format!("{path}.{key}");
+
+                    match properties.and_then(|properties| properties.get(key)) {
+                        Some(documented) => found.extend(mismatches(documented, property, &path)),
+                        None if additional.is_object() => {
+                            found.extend(mismatches(additional, property, &path))
+                        }
+                        // a free-form object, e.g. error details, documents none of its keys
+                        None if properties.is_none() => {}
+                        None => found.push(format!("{path} isn't documented")),
+                    }
+                }
+
+                for key in schema["required"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                {
+                    if !object.contains_key(key) {
+                        found.push(format!("{path}.{key} is required but not sent"));
+                    }
+                }
+
+                found
+            }
+            Value::Array(items) => items
+                .iter()
+                .enumerate()
+                .flat_map(|(i, item)| mismatches(&schema["items"], item, &format!("{path}[{i}]")))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// What the server sends matches the schema, see [`mismatches`].
+    fn assert_matches<T: Serialize>(name: &str, value: &T) {
+        let value = serde_json::to_value(value).unwrap();
+        let found = mismatches(&schema(name), &value, name);
+
+        assert!(found.is_empty(), "{}", found.join("\n"));
+    }
+
+    #[test]
+    fn test_requests_match_schema() {
+        assert_accepts::<NewDispatch>("NewDispatch");
+        assert_accepts::<EditDispatch>("EditDispatch");
+        assert_accepts::<NewDispatchGroup>("NewDispatchGroup");
+        assert_accepts::<NewRmbPost>("NewRmbPost");
+        assert_accepts::<NewRmbPostBatch>("NewRmbPostBatch");
+        assert_accepts::<Params>("TelegramParams");
+        assert_accepts::<Header>("TelegramHeader");
+        assert_accepts::<NewCampaign>("NewCampaign");
+        assert_accepts::<LoginData>("LoginData");
+        assert_accepts::<RefreshData>("RefreshData");
+        assert_accepts::<NewApiKey>("NewApiKey");
+        assert_accepts::<PreviewData>("PreviewData");
+        assert_accepts::<ImportDispatch>("ImportDispatch");
+    }
+
+    #[test]
+    fn test_responses_match_schema() {
+        let now = chrono::Utc::now();
+
+        let dispatch_status = DispatchStatus {
+            id: 1,
+            action: "add".to_string(),
+            status: JobStatus::Succeeded,
+            dispatch_id: Some(2),
+            error: None,
+            error_code: Some("pin_expired".to_string()),
+            self_url: DispatchStatus::self_url(1),
+            resource: DispatchStatus::resource_url("add", JobStatus::Succeeded, Some(2)),
+            warnings: vec!["warning".to_string()],
+            created_at: now,
+            claimed_at: Some(now),
+            started_at: Some(now),
+            prepared_at: Some(now),
+            executing_at: Some(now),
+            finished_at: Some(now),
+            modified_at: now,
+            durations: JobDurations::new(
+                now,
+                Some(now),
+                Some(now),
+                Some(now),
+                Some(now),
+                Some(now),
+            ),
+            superseded_by: Some(3),
+            revision_id: Some(4),
+            estimated_completion_at: Some(now),
+            payload: None,
+        };
+        assert_matches("DispatchStatus", &dispatch_status);
+        assert_matches(
+            "Dispatch",
+            &Dispatch {
+                id: 1,
+                nation: "testlandia".to_string(),
+                category: 1,
+                subcategory: 100,
+                title: "title".to_string(),
+                text: "text".to_string(),
+                revision: 3,
+                job_id: Some(1),
+                size: Some(4),
+                source_format: TextFormat::Markdown,
+                source: Some("text".to_string()),
+                created_by: "user".to_string(),
+                modified_at: now,
+                is_active: true,
+                is_protected: false,
+                rendered: Some("text".to_string()),
+                pending_or_failed_edits: Some(UnappliedEdits {
+                    pending: 1,
+                    failed: 1,
+                    latest_failed: Some(FailedEdit {
+                        job_id: 2,
+                        created_by: "user".to_string(),
+                        created_at: now,
+                        failed_at: now,
+                        error_code: Some("pin_expired".to_string()),
+                    }),
+                }),
+            },
+        );
+        assert_matches(
+            "DispatchGroup",
+            &DispatchGroup {
+                id: 1,
+                status: "queued".to_string(),
+                self_url: DispatchGroup::self_url(1),
+                created_by: "user".to_string(),
+                created_at: now,
+                jobs: vec![dispatch_status],
+            },
+        );
+        assert_matches(
+            "EncodingPreview",
+            &EncodingPreview {
+                text: "&#8212;".to_string(),
+                length: 7,
+                max_length: 200_000,
+                converted: vec![ConvertedCharacter {
+                    character: "\u{2014}".to_string(),
+                    entity: "&#8212;".to_string(),
+                    count: 1,
+                }],
+            },
+        );
+        assert_matches("DispatchCategory", &dispatch::categories()[0]);
+        assert_matches(
+            "QueueEstimate",
+            &QueueEstimate {
+                nation: "testlandia".to_string(),
+                queue_position: 2,
+                pending_for_nation: 1,
+                estimated_start_at: now,
+            },
+        );
+        assert_matches(
+            "JobEvent",
+            &JobEvent {
+                job: JobKind::Dispatch,
+                id: 1,
+                status: JobStatus::Failed,
+                error_code: Some("pin_expired".to_string()),
+                self_url: DispatchStatus::self_url(1),
+                resource: None,
+                created_by: Some("user".to_string()),
+                tenant_id: 1,
+            },
+        );
+        assert_matches(
+            "Bootstrap",
+            &Bootstrap {
+                version: 1,
+                user: SessionUser {
+                    id: 1,
+                    username: "user".to_string(),
+                    claims: vec!["dispatches.create".to_string()],
+                },
+                dispatch_nations: vec!["testlandia".to_string()],
+                rmbpost_nations: Vec::new(),
+                categories: dispatch::categories(),
+                limits: Limits {
+                    dispatches: DispatchLimits {
+                        max_text_length: 200_000,
+                        max_group_size: 10,
+                        text_formats: TextFormat::ALL,
+                        scheduled_posts: false,
+                    },
+                    rmbposts: RmbpostLimits { max_batch_size: 20 },
+                    quota: QuotaLimits {
+                        max_pending: Some(10),
+                        max_daily: None,
+                    },
+                },
+                presets: vec![NationPreset {
+                    nation: "testlandia".to_string(),
+                    default_category: Some(3),
+                    default_subcategory: Some(315),
+                    title_prefix: Some("News: ".to_string()),
+                    signature_text: None,
+                    updated_by: "admin".to_string(),
+                    updated_at: chrono::Utc::now(),
+                }],
+            },
+        );
+        assert_matches(
+            "DispatchSummary",
+            &DispatchSummary {
+                id: 1,
+                nation: "testlandia".to_string(),
+                category: 1,
+                subcategory: 100,
+                title: "title".to_string(),
+                created_by: "user".to_string(),
+                modified_at: now,
+                is_active: true,
+                is_protected: false,
+            },
+        );
+        assert_matches(
+            "DispatchMetrics",
+            &DispatchMetrics {
+                id: 1,
+                samples: vec![DispatchMetricSample {
+                    views: 120,
+                    score: 4,
+                    sampled_at: now,
+                }],
+            },
+        );
+        assert_matches(
+            "DispatchSearchResult",
+            &DispatchSearchResult {
+                dispatch: DispatchSummary {
+                    id: 1,
+                    nation: "testlandia".to_string(),
+                    category: 1,
+                    subcategory: 100,
+                    title: "title".to_string(),
+                    created_by: "user".to_string(),
+                    modified_at: now,
+                    is_active: true,
+                    is_protected: false,
+                },
+                rank: 0.6,
+                title_highlight: "<mark>title</mark>".to_string(),
+                snippet: "text".to_string(),
+            },
+        );
+        let rmbpost_status = RmbPostStatus {
+            id: 1,
+            status: JobStatus::Succeeded,
+            rmbpost_id: Some(2),
+            error: None,
+            error_code: Some("rate_limited".to_string()),
+            warnings: vec!["matches content rule `shouting`".to_string()],
+            self_url: RmbPostStatus::self_url(1),
+            resource: RmbPostStatus::resource_url(JobStatus::Succeeded, "testregion", Some(2)),
+            created_at: now,
+            claimed_at: Some(now),
+            started_at: None,
+            prepared_at: None,
+            executing_at: None,
+            finished_at: None,
+            modified_at: now,
+            durations: JobDurations::new(now, Some(now), None, None, None, None),
+            estimated_completion_at: Some(now),
+        };
+        assert_matches("RmbPostStatus", &rmbpost_status);
+        assert_matches(
+            "JobDurations",
+            &JobDurations::new(now, Some(now), None, None, None, None),
+        );
+        assert_matches(
+            "RmbPostGroup",
+            &RmbPostGroup {
+                id: 1,
+                status: "partial".to_string(),
+                self_url: RmbPostGroup::self_url(1),
+                created_by: "user".to_string(),
+                created_at: now,
+                jobs: vec![rmbpost_status],
+            },
+        );
+        assert_matches(
+            "QueuedTelegram",
+            &QueuedTelegram {
+                id: 1,
+                recipient: "testlandia".to_string(),
+                telegram_id: "1".to_string(),
+                queue: "recruitment".to_string(),
+            },
+        );
+        assert_matches(
+            "QueuedTelegrams",
+            &QueuedTelegrams::new(
+                Vec::new(),
+                vec![DroppedTelegram {
+                    index: 0,
+                    recipient: "bad&name".to_string(),
+                    telegram_id: "1".to_string(),
+                    reason: "invalid_recipient".to_string(),
+                }],
+            ),
+        );
+        assert_matches(
+            "DroppedTelegram",
+            &DroppedTelegram {
+                index: 3,
+                recipient: "Testlandia".to_string(),
+                telegram_id: "1".to_string(),
+                reason: "duplicate".to_string(),
+            },
+        );
+        assert_matches(
+            "CampaignRecipient",
+            &CampaignRecipient {
+                position: 1,
+                recipient: "testlandia".to_string(),
+                status: "pending".to_string(),
+            },
+        );
+        assert_matches(
+            "RecipientFilter",
+            &RecipientFilter {
+                exclude_region: Some("europeia".to_string()),
+                exclude_wa: true,
+                founded_after: Some(now),
+            },
+        );
+        assert_matches(
+            "RecipientSource",
+            &RecipientSource {
+                region: "the_north_pacific".to_string(),
+                founded_since: Some(now),
+                non_wa: true,
+            },
+        );
+        let capacity = TelegramCapacity {
+            known: CapacityCounts {
+                sent_last_24h: 12,
+                queued: 30,
+                campaign_backlog: 200,
+                cooldown_secs: 180,
+                ready_in: 42,
+            },
+            estimated: CapacityEstimates {
+                max_per_day: 480,
+                max_remaining_today: 96,
+                projected_completion_at: None,
+            },
+        };
+        assert_matches("TelegramCapacity", &capacity);
+        assert_matches(
+            "TelegramSenderSummary",
+            &TelegramSenderSummary {
+                recruitment_count: 2,
+                standard_count: 0,
+                oldest_queued_at: Some(now),
+                recruitment_ready_in: 30,
+                standard_ready_in: 0,
+            },
+        );
+        assert_matches("CapacityCounts", &capacity.known);
+        assert_matches("CapacityEstimates", &capacity.estimated);
+        assert_matches(
+            "TelegramStatus",
+            &TelegramStatus {
+                id: 1,
+                sender: "recruiter".to_string(),
+                recipient: "testlandia".to_string(),
+                telegram_id: "1".to_string(),
+                queue: "recruitment".to_string(),
+                status: "failed".to_string(),
+                error: Some("error".to_string()),
+                error_code: Some("recruitment_blocked".to_string()),
+                created_at: now,
+                modified_at: now,
+            },
+        );
+        assert_matches(
+            "TelegramCampaign",
+            &TelegramCampaign {
+                id: 1,
+                name: "campaign".to_string(),
+                sender: "recruiter".to_string(),
+                telegram_id: "1".to_string(),
+                tg_type: "recruitment".to_string(),
+                status: "active".to_string(),
+                recipients: 2,
+                sent: 1,
+                failed: 0,
+                skipped: 0,
+                remaining: 1,
+                rejected: vec!["?".to_string()],
+                recipients_from: Some(RecipientSource {
+                    region: "the_north_pacific".to_string(),
+                    founded_since: None,
+                    non_wa: false,
+                }),
+                expanded_recipients: Some(1),
+                filtered: BTreeMap::from([("exclude_wa".to_string(), 3)]),
+                created_by: "user".to_string(),
+                created_at: now,
+                modified_at: now,
+            },
+        );
+        assert_matches(
+            "Telegram",
+            &Telegram::new("recruiter", "testlandia", "1", "alice"),
+        );
+        assert_matches(
+            "ApiKey",
+            &ApiKey {
+                id: 1,
+                name: "forum".to_string(),
+                key: Some("eck_0".to_string()),
+                claims: vec!["rmbposts.create".to_string()],
+                expires_at: Some(now),
+                last_used_at: None,
+                revoked_at: None,
+                created_at: now,
+            },
+        );
+        assert_matches(
+            "Login",
+            &Login {
+                username: "user".to_string(),
+                token: "token".to_string(),
+                expires_at: now,
+            },
+        );
+        assert_matches(
+            "InvalidBody",
+            &InvalidBody {
+                code: "invalid_type",
+                message: "invalid type: string \"one\", expected i16".to_string(),
+                path: Some("category".to_string()),
+                expected: Some("i16".to_string()),
+                received: Some("string \"one\"".to_string()),
+                line: 1,
+                column: 20,
+            },
+        );
+        assert_matches(
+            "QuotaExceeded",
+            &QuotaExceeded::new("pending", 20, Some(now), None),
+        );
+        assert_matches(
+            "LoginThrottled",
+            &serde_json::json!({ "retry_after_secs": 60 }),
+        );
+        assert_matches(
+            "RmbPostRefused",
+            &serde_json::json!({ "reason": RmbRefusal::EmbassyPending.code() }),
+        );
+        assert_matches(
+            "PipelineBackedUp",
+            &serde_json::json!({ "pipeline": "rmbpost", "deferred_jobs": [1], "retry_after_secs": 2 }),
+        );
+        assert_matches(
+            "ContentPolicyViolation",
+            &serde_json::json!({ "rules": ["invite-links"] }),
+        );
+        assert_matches("EditConflict", &EditConflict::new(2, 3, "user", now));
+        assert_matches(
+            "PendingJobs",
+            &PendingJobs {
+                dispatch_id: 1,
+                jobs: vec![PendingJob {
+                    id: 2,
+                    action: "edit".to_string(),
+                    status: JobStatus::Queued,
+                }],
+            },
+        );
+        assert_matches(
+            "DeletedDispatch",
+            &DeletedDispatch {
+                id: 1,
+                deleted_at: Some(now),
+                deleted_by: None,
+            },
+        );
+        assert_matches(
+            "ProtectedDispatch",
+            &ProtectedDispatch {
+                id: 1,
+                protected_by: Some("admin".to_string()),
+                protected_at: Some(now),
+            },
+        );
+        assert_matches(
+            "ErrorBody",
+            &ErrorBody::new("dispatch_not_found", "Dispatch not found"),
+        );
+        assert_matches(
+            "ErrorBody",
+            &ErrorBody::new("not_dispatch_owner", "Dispatch is owned by alice")
+                .details(&serde_json::json!({ "owner": "alice" })),
+        );
+    }
+
+    #[test]
+    fn test_mismatches_reach_nested_values() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "jobs": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["id"],
+                        "properties": { "id": { "type": "integer" }, "status": schema("JobStatus") },
+                    },
+                },
+            },
+        });
+
+        assert!(
+            mismatches(
+                &schema,
+                &json!({ "jobs": [{ "id": 1, "status": "queued" }] }),
+                "group"
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            mismatches(
+                &schema,
+                &json!({ "jobs": [{ "status": "lost", "extra": true }] }),
+                "group"
+            ),
+            vec![
+                "group.jobs[0].extra isn't documented".to_string(),
+                format!(
+                    "group.jobs[0].status isn't one of {:?}: \"lost\"",
+                    component("JobStatus")["enum"].as_array().unwrap()
+                ),
+                "group.jobs[0].id is required but not sent".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_categories_match_factbook_categories() {
+        let (categories, subcategories) = categories();
+
+        assert_eq!(categories, vec![1, 3, 5, 8]);
+        assert!(subcategories.contains(&100));
+        assert!(subcategories.contains(&845));
+        assert!(!subcategories.contains(&300));
+    }
+
+    #[test]
+    fn test_job_statuses_match() {
+        let documented = component("JobStatus")["enum"].as_array().unwrap().clone();
+
+        assert_eq!(documented.len(), 10);
+
+        for status in documented {
+            assert!(
+                serde_json::from_value::<JobStatus>(status.clone()).is_ok(),
+                "{status} isn't a job status"
+            );
+        }
+    }
+
+    #[test]
+    fn test_references_resolve() {
+        fn references(value: &Value, found: &mut Vec<String>) {
+            match value {
+                Value::Object(object) => {
+                    if let Some(Value::String(reference)) = object.get("$ref") {
+                        found.push(reference.clone());
+                    }
+                    object.values().for_each(|value| references(value, found));
+                }
+                Value::Array(values) => values.iter().for_each(|value| references(value, found)),
+                _ => {}
+            }
+        }
+
+        let mut found = Vec::new();
+        references(&DOCUMENT, &mut found);
+
+        for reference in found {
+            let pointer = reference.trim_start_matches('#');
+
+            assert!(
+                DOCUMENT.pointer(pointer).is_some(),
+                "{reference} is dangling"
+            );
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! The two tiers of the API. Dispatches can be read by anyone, unless the deployment turns
+//! `public_read` off, with anonymous readers limited per IP on top of the global rate limit.
+//! Everything that changes something needs credentials, whatever the handler does with them.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, MatchedPath, Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::core::error::Error;
+use crate::types::AuthorizedUser;
+
+/// Routes taking requests that aren't GET, HEAD or OPTIONS without credentials.
+const ANONYMOUS_WRITES: &[&str] = &[
+    "/register",
+    "/login",
+    "/login/refresh",
+    "/dispatches/preview",
+];
+
+/// Tracked addresses before the ones whose window has ended are dropped.
+const PRUNE_AFTER: usize = 1024;
+
+#[derive(Clone, Debug)]
+pub(crate) struct Tier {
+    enabled: bool,
+    limiter: Limiter,
+}
+
+impl Tier {
+    pub(crate) fn new(enabled: bool, max_requests: u32, window: Duration) -> Self {
+        Self {
+            enabled,
+            limiter: Limiter::new(max_requests, window),
+        }
+    }
+}
+
+/// Fixed window request counts per client address.
+#[derive(Clone, Debug)]
+struct Limiter {
+    max_requests: u32,
+    window: Duration,
+    windows: Arc<Mutex<HashMap<IpAddr, (Instant, u32)>>>,
+}
+
+impl Limiter {
+    fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests: max_requests.max(1),
+            window,
+            windows: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Counts a request from `ip` at `now`, or returns how long until it would be accepted.
+    fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut windows = self.windows.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if windows.len() >= PRUNE_AFTER && !windows.contains_key(&ip) {
+            windows.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+        }
+
+        let (started, count) = windows.entry(ip).or_insert((now, 0));
+
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+
+        if *count >= self.max_requests {
+            return Err(self.window - now.duration_since(*started));
+        }
+
+        *count += 1;
+
+        Ok(())
+    }
+}
+
+/// Lets anonymous requests through to the public routes while the tier is enabled and the
+/// client hasn't used up its window. Authenticated requests only face the global limit.
+pub(crate) async fn read(
+    State(tier): State<Tier>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    let anonymous = matches!(
+        request.extensions().get::<Option<AuthorizedUser>>(),
+        None | Some(None)
+    );
+
+    if anonymous {
+        if !tier.enabled {
+            return Err(Error::NoCredentials);
+        }
+
+        tier.limiter
+            .check(address.ip(), Instant::now())
+            .map_err(Error::PublicReadThrottled)?;
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Refuses anonymous requests that could change something, so a handler extracting its body
+/// before it looks at the user can't be reached without credentials.
+pub(crate) async fn require_credentials(request: Request, next: Next) -> Result<Response, Error> {
+    let safe = [Method::GET, Method::HEAD, Method::OPTIONS].contains(request.method());
+
+    let anonymous = matches!(
+        request.extensions().get::<Option<AuthorizedUser>>(),
+        None | Some(None)
+    );
+
+    let allowed = request
+        .extensions()
+        .get::<MatchedPath>()
+        .is_some_and(|path| ANONYMOUS_WRITES.contains(&path.as_str()));
+
+    if anonymous && !safe && !allowed {
+        return Err(Error::NoCredentials);
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limiter_windows() {
+        let limiter = Limiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+        let client = IpAddr::from([192, 0, 2, 1]);
+
+        assert!(limiter.check(client, now).is_ok());
+        assert!(limiter.check(client, now).is_ok());
+        assert_eq!(
+            limiter.check(client, now + Duration::from_secs(15)),
+            Err(Duration::from_secs(45))
+        );
+
+        // other clients have windows of their own
+        assert!(limiter.check(IpAddr::from([192, 0, 2, 2]), now).is_ok());
+
+        assert!(limiter.check(client, now + Duration::from_secs(60)).is_ok());
+    }
+}
@@ -1,25 +1,287 @@
+use crate::core::authorization::{self, Claim};
 use crate::core::error::Error;
+use crate::core::extract::{Path, Query};
 use crate::core::state::AppState;
-use axum::Json;
-use axum::extract::{Path, State};
+use crate::sync::events::Message;
+use crate::types::request::{
+    EstimateAction, EstimateQuery, EventsQuery, JobKind, QueueStatusQuery,
+};
+use crate::types::{AuthorizedUser, tenant_scope};
+use crate::utils::name;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, header};
 use axum::response::IntoResponse;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use std::convert::Infallible;
+use std::time::Duration;
+
+/// Maximum number of characters of dispatch text included when the payload is requested.
+const PAYLOAD_TEXT_LIMIT: usize = 2000;
+
+/// Keeps idle event streams from being cut off by proxies.
+const HEARTBEAT: Duration = Duration::from_secs(15);
+
+/// Claims letting users see the payload of a queued dispatch.
+const DISPATCH_CLAIMS: &[Claim] = &[
+    Claim::DispatchesCreate,
+    Claim::DispatchesEdit,
+    Claim::DispatchesDelete,
+];
+
+const JOB_STATUS: HeaderName = HeaderName::from_static("x-job-status");
+
+/// The parts of a job status a poller needs, as headers for `HEAD` requests.
+fn job_headers(status: &str, resource: Option<&str>) -> Result<HeaderMap, Error> {
+    let mut headers = HeaderMap::new();
+
+    headers.insert(JOB_STATUS, HeaderValue::from_str(status)?);
+
+    if let Some(resource) = resource {
+        headers.insert(
+            header::LINK,
+            HeaderValue::from_str(&format!("<{}>; rel=\"related\"", resource))?,
+        );
+    }
+
+    Ok(headers)
+}
+
+/// `Retry-After` for a job expected to be done at `estimate`, none without an estimate. In whole
+/// seconds, rounded up since polling early would only find the job still waiting.
+pub(crate) fn retry_after(estimate: Option<DateTime<Utc>>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    if let Some(estimate) = estimate {
+        let millis = (estimate - Utc::now()).num_milliseconds();
+
+        headers.insert(
+            header::RETRY_AFTER,
+            HeaderValue::from((millis + 999).div_euclid(1000).max(1)),
+        );
+    }
+
+    headers
+}
+
+/// Streams dispatch and RMB post status changes as server-sent events, instead of polling.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn events(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Query(mut query): Query<EventsQuery>,
+) -> impl IntoResponse {
+    query.tenant = Some(tenant_scope(user.as_ref()));
+
+    let stream = state.events.subscribe(query).map(|message| {
+        Ok::<_, Infallible>(match message {
+            Message::Job(event) => Event::default()
+                .event(match event.job {
+                    JobKind::Dispatch => "dispatch",
+                    JobKind::Rmbpost => "rmbpost",
+                })
+                .json_data(&event)
+                .unwrap_or_else(|e| Event::default().comment(e.to_string())),
+            // events were dropped, so anything the client is waiting on should be polled once
+            Message::Lagged(skipped) => Event::default().event("lagged").data(skipped.to_string()),
+        })
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(HEARTBEAT))
+}
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn dispatch(
     State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
     Path(id): Path<i32>,
+    Query(query): Query<QueueStatusQuery>,
 ) -> Result<impl IntoResponse, Error> {
-    let status = state.dispatch_controller.get_status(id).await?;
+    let mut status = state
+        .dispatch_controller
+        .get_status(id, Some(tenant_scope(user.as_ref())))
+        .await?;
 
-    Ok(Json(status))
+    if query.include_payload {
+        authorization::require_claim(user.as_ref(), DISPATCH_CLAIMS)?;
+
+        status.payload = state
+            .dispatch_controller
+            .get_payload(id)
+            .await?
+            .map(|mut payload| {
+                payload.truncate_text(PAYLOAD_TEXT_LIMIT);
+                payload
+            });
+    }
+
+    Ok((retry_after(status.estimated_completion_at), Json(status)))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn dispatch_head(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let status = state
+        .dispatch_controller
+        .get_status(id, Some(tenant_scope(user.as_ref())))
+        .await?;
+
+    let mut headers = job_headers(status.status.as_str(), status.resource.as_deref())?;
+
+    headers.extend(retry_after(status.estimated_completion_at));
+
+    Ok(headers)
+}
+
+/// Progress of every job queued by one `POST /dispatches/multi`.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn group(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let group = state
+        .dispatch_controller
+        .get_group(id, Some(tenant_scope(user.as_ref())))
+        .await?;
+
+    Ok(Json(group))
 }
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn rmbpost(
     State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
     Path(id): Path<i32>,
 ) -> Result<impl IntoResponse, Error> {
-    let status = state.rmbpost_controller.get_status(id).await?;
+    let status = state
+        .rmbpost_controller
+        .get_status(id, Some(tenant_scope(user.as_ref())))
+        .await?;
+
+    Ok((retry_after(status.estimated_completion_at), Json(status)))
+}
+
+/// Progress of every job queued by one `POST /rmbposts/batch`.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn rmbpost_group(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let group = state
+        .rmbpost_controller
+        .get_group(id, Some(tenant_scope(user.as_ref())))
+        .await?;
+
+    Ok(Json(group))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn rmbpost_head(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let status = state
+        .rmbpost_controller
+        .get_status(id, Some(tenant_scope(user.as_ref())))
+        .await?;
+
+    let mut headers = job_headers(status.status.as_str(), status.resource.as_deref())?;
+
+    headers.extend(retry_after(status.estimated_completion_at));
+
+    Ok(headers)
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn telegram(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let status = state
+        .telegram_controller
+        .get_status(id, Some(tenant_scope(user.as_ref())))
+        .await?;
 
     Ok(Json(status))
 }
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn telegram_head(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let status = state
+        .telegram_controller
+        .get_status(id, Some(tenant_scope(user.as_ref())))
+        .await?;
+
+    job_headers(&status.status, None)
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn estimate(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Query(query): Query<EstimateQuery>,
+) -> Result<impl IntoResponse, Error> {
+    state
+        .tenant_controller
+        .check_nation(
+            tenant_scope(user.as_ref()),
+            &name::canonicalize(&query.nation),
+        )
+        .await?;
+
+    let restricted = match query.action {
+        EstimateAction::Add => true,
+        EstimateAction::Edit | EstimateAction::Remove => false,
+    };
+
+    let estimate = state
+        .dispatch_controller
+        .estimate(&query.nation, restricted)
+        .await?;
+
+    Ok(Json(estimate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_headers() {
+        let headers = job_headers("success", Some("/dispatches/345")).unwrap();
+
+        assert_eq!(headers[JOB_STATUS], "success");
+        assert_eq!(headers[header::LINK], "</dispatches/345>; rel=\"related\"");
+
+        let headers = job_headers("queued", None).unwrap();
+
+        assert_eq!(headers[JOB_STATUS], "queued");
+        assert!(!headers.contains_key(header::LINK));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let in_a_bit = Utc::now() + chrono::Duration::milliseconds(1500);
+
+        assert_eq!(retry_after(Some(in_a_bit))[header::RETRY_AFTER], "2");
+        assert_eq!(retry_after(Some(Utc::now()))[header::RETRY_AFTER], "1");
+        assert_eq!(
+            retry_after(Some(Utc::now() - chrono::Duration::seconds(30)))[header::RETRY_AFTER],
+            "1"
+        );
+        assert!(retry_after(None).is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use crate::types::request::RequestId;
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+
+pub(crate) const HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+const MAX_LENGTH: usize = 128;
+
+/// Tags the request with the client's `X-Request-Id`, or a fresh one, and echoes it back.
+pub(crate) async fn propagate(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid(value))
+        .map(str::to_string)
+        .unwrap_or_else(generate);
+
+    request
+        .extensions_mut()
+        .insert(RequestId(request_id.clone()));
+
+    let mut response = next.run(request).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(HEADER, value);
+    }
+
+    response
+}
+
+fn generate() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+fn is_valid(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_LENGTH
+        && value.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::{Extension, Router, middleware};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|Extension(request_id): Extension<RequestId>| async move { request_id.0 }),
+            )
+            .layer(middleware::from_fn(propagate))
+    }
+
+    async fn send(request_id: Option<&str>) -> (String, String) {
+        let mut request = Request::builder().uri("/");
+
+        if let Some(request_id) = request_id {
+            request = request.header(HEADER, request_id);
+        }
+
+        let response = app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let header = response.headers()[HEADER].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (header, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_request_id_round_trip() {
+        let (header, seen) = send(Some("client-42")).await;
+
+        assert_eq!(header, "client-42");
+        assert_eq!(seen, "client-42");
+    }
+
+    #[tokio::test]
+    async fn test_request_id_generated() {
+        let (header, seen) = send(None).await;
+
+        assert_eq!(header.len(), 32);
+        assert_eq!(header, seen);
+
+        let (header, _) = send(Some(&"x".repeat(MAX_LENGTH + 1))).await;
+        assert_eq!(header.len(), 32);
+    }
+}
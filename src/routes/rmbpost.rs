@@ -1,32 +1,97 @@
+use crate::controllers::{audit, idempotency};
+use crate::core::authorization::Authorized;
 use crate::core::error::Error;
+use crate::core::extract::Json;
 use crate::core::state::AppState;
-use crate::ns::rmbpost::NewRmbPost;
-use crate::types::AuthorizedUser;
+use crate::ns::rmbpost::{NewRmbPost, NewRmbPostBatch};
+use crate::routes::queue;
+use crate::types::request::RequestId;
+use axum::Extension;
 use axum::extract::State;
-use axum::http::{StatusCode, header};
+use axum::http::{HeaderMap, header};
 use axum::response::IntoResponse;
-use axum::{Extension, Json};
+use serde_json::json;
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn post(
     State(state): State<AppState>,
-    Extension(user): Extension<Option<AuthorizedUser>>,
+    Authorized(user): Authorized,
+    Extension(request_id): Extension<RequestId>,
+    headers: HeaderMap,
     Json(params): Json<NewRmbPost>,
 ) -> Result<impl IntoResponse, Error> {
-    match user {
-        Some(user) => {
-            if !user.claims.contains(&"rmbposts.create".to_string()) {
-                return Err(Error::Unauthorized);
-            }
-        }
-        None => return Err(Error::Unauthorized),
-    }
+    let event = audit::Event::new(&user, "rmbpost.create", "rmbpost_job").summary(json!({
+        "nation": params.nation,
+        "region": params.region,
+    }));
 
-    let status = state.rmbpost_controller.queue(params.clone()).await?;
+    let key = idempotency::Key::from_headers(&headers, &user, "rmbpost.create")?;
+
+    let result = state
+        .rmbpost_controller
+        .queue(&user, params, key, &request_id)
+        .await;
+
+    state.audit_controller.record(
+        match &result {
+            Ok(submitted) => event
+                .target(submitted.inner().id)
+                .warnings(&submitted.inner().warnings),
+            Err(_) => event,
+        },
+        &result,
+    );
+
+    let submitted = result?;
+
+    Ok((
+        submitted.status_code(),
+        [(header::LOCATION, submitted.inner().self_url.clone())],
+        queue::retry_after(submitted.inner().estimated_completion_at),
+        Json(submitted.into_inner()),
+    ))
+}
+
+/// Posts the same text to several regions, see `Controller::queue_batch`.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn post_batch(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Extension(request_id): Extension<RequestId>,
+    headers: HeaderMap,
+    Json(params): Json<NewRmbPostBatch>,
+) -> Result<impl IntoResponse, Error> {
+    let event = audit::Event::new(&user, "rmbpost.create_batch", "rmbpost_group").summary(json!({
+        "nation": params.nation,
+        "regions": params.regions,
+    }));
+
+    let key = idempotency::Key::from_headers(&headers, &user, "rmbpost.create_batch")?;
+
+    let result = state
+        .rmbpost_controller
+        .queue_batch(&user, params, key, &request_id)
+        .await;
+
+    state.audit_controller.record(
+        match &result {
+            Ok(submitted) => event.target(submitted.inner().id).warnings(
+                submitted
+                    .inner()
+                    .jobs
+                    .first()
+                    .map_or(&[], |job| job.warnings.as_slice()),
+            ),
+            Err(_) => event,
+        },
+        &result,
+    );
+
+    let submitted = result?;
 
     Ok((
-        StatusCode::ACCEPTED,
-        [(header::LOCATION, format!("/queue/rmbposts/{}", status.id))],
-        Json(status),
+        submitted.status_code(),
+        [(header::LOCATION, submitted.inner().self_url.clone())],
+        Json(submitted.into_inner()),
     ))
 }
@@ -1,19 +1,25 @@
 use crate::controllers;
+use crate::core::authorization::{Claim, require};
 use crate::core::error;
 use crate::core::state::AppState;
-use crate::routes::{admin, dispatch, nations, queue, rmbpost, telegram, user};
+use crate::routes::{
+    admin, bootstrap, dispatch, export, health, metrics, nations, openapi, public, queue,
+    request_id, rmbpost, stats, telegram, user, webhook,
+};
+use crate::types::request::RequestId;
 use axum::error_handling::HandleErrorLayer;
-use axum::routing::{options, patch};
+use axum::routing::patch;
 use axum::{
     Router,
     extract::{MatchedPath, Request},
-    http::{HeaderName, HeaderValue, Method, StatusCode},
+    http::{HeaderName, HeaderValue, Method, StatusCode, header},
     middleware,
-    routing::{get, post},
+    routing::{delete, get, post, put},
 };
 use std::time::Duration;
 use tower::ServiceBuilder;
 use tower_http::{
+    compression::CompressionLayer,
     cors::{self, CorsLayer},
     set_header::SetResponseHeaderLayer,
     trace::TraceLayer,
@@ -24,20 +30,63 @@ pub(crate) async fn routes(
     state: AppState,
     dispatch_nations: Vec<String>,
     rmbpost_nations: Vec<String>,
+    compression: bool,
+    api_docs: bool,
+    public_tier: public::Tier,
 ) -> Router {
     let dispatch_nations = Box::leak(Box::new(dispatch_nations.join(",")));
 
     let rmbpost_nations = Box::leak(Box::new(rmbpost_nations.join(",")));
 
+    // GET /dispatches/..., readable without credentials while public reads are enabled
+    let public_router = Router::new()
+        .route("/dispatches", get(dispatch::get_all))
+        .route("/dispatches/categories", get(dispatch::categories))
+        .route("/dispatches/search", get(dispatch::search))
+        .route("/dispatches/{id}", get(dispatch::get))
+        .route("/dispatches/{id}/metrics", get(dispatch::metrics))
+        .route(
+            "/nations/{nation}/dispatches",
+            get(nations::dispatches::get),
+        )
+        .route_layer(middleware::from_fn_with_state(public_tier, public::read));
+
     // /dispatches/...
     let dispatch_router = Router::new()
-        .route("/dispatches", get(dispatch::get_all).post(dispatch::post))
+        .route(
+            "/dispatches",
+            post(dispatch::post).route_layer(require(&[Claim::DispatchesCreate])),
+        )
+        .route(
+            "/dispatches/multi",
+            post(dispatch::post_group).route_layer(require(&[Claim::DispatchesCreate])),
+        )
+        .route("/dispatches/preview", post(dispatch::preview))
+        .route(
+            "/dispatches/import",
+            post(dispatch::import).route_layer(require(&[Claim::DispatchesCreate])),
+        )
+        .route(
+            "/dispatches/{id}",
+            put(dispatch::put).route_layer(require(&[Claim::DispatchesEdit])),
+        )
         .route(
             "/dispatches/{id}",
-            get(dispatch::get)
-                .put(dispatch::put)
-                .delete(dispatch::delete),
+            delete(dispatch::delete).route_layer(require(&[Claim::DispatchesDelete])),
+        )
+        .route(
+            "/dispatches/{id}/reapply/{job_id}",
+            post(dispatch::reapply).route_layer(require(&[Claim::DispatchesEdit])),
+        )
+        .route(
+            "/dispatches/{id}/protect",
+            post(dispatch::protect).route_layer(require(&[Claim::Admin])),
         )
+        .route(
+            "/dispatches/{id}/unprotect",
+            post(dispatch::unprotect).route_layer(require(&[Claim::Admin])),
+        )
+        .merge(public_router)
         .route_layer(
             ServiceBuilder::new().layer(SetResponseHeaderLayer::if_not_present(
                 HeaderName::from_static("dispatch-nations"),
@@ -46,16 +95,42 @@ pub(crate) async fn routes(
         );
 
     // /telegrams/...
-    let telegram_router = Router::new().route(
-        "/telegrams",
-        get(telegram::get)
-            .post(telegram::post)
-            .delete(telegram::delete),
-    );
+    let telegram_read_router = Router::new()
+        .route("/telegrams", get(telegram::get))
+        .route("/telegrams/summary", get(telegram::summary))
+        .route("/telegrams/capacity", get(telegram::capacity))
+        .route("/telegrams/campaigns/{id}", get(telegram::get_campaign))
+        .route(
+            "/telegrams/campaigns/{id}/recipients",
+            get(telegram::campaign_recipients),
+        )
+        .route_layer(require(&[Claim::TelegramsRead]));
+
+    let telegram_create_router = Router::new()
+        .route("/telegrams", post(telegram::post))
+        .route("/telegrams/campaigns", post(telegram::create_campaign))
+        .route(
+            "/telegrams/campaigns/{id}/pause",
+            post(telegram::pause_campaign),
+        )
+        .route(
+            "/telegrams/campaigns/{id}/resume",
+            post(telegram::resume_campaign),
+        )
+        .route_layer(require(&[Claim::TelegramsCreate]));
+
+    let telegram_router = Router::new()
+        .route("/telegrams", delete(telegram::delete))
+        .route("/telegrams/{id}", delete(telegram::delete_by_id))
+        .route_layer(require(&[Claim::TelegramsDelete]))
+        .merge(telegram_read_router)
+        .merge(telegram_create_router);
 
     // /rmbposts/...
     let rmbpost_router = Router::new()
         .route("/rmbposts", post(rmbpost::post))
+        .route("/rmbposts/batch", post(rmbpost::post_batch))
+        .route_layer(require(&[Claim::RmbpostsCreate]))
         .route_layer(SetResponseHeaderLayer::if_not_present(
             HeaderName::from_static("rmbpost-nations"),
             HeaderValue::from_static(rmbpost_nations),
@@ -63,33 +138,152 @@ pub(crate) async fn routes(
 
     // /queue/...
     let queue_router = Router::new()
-        .route("/queue/dispatches/{id}", get(queue::dispatch))
-        .route("/queue/rmbposts/{id}", get(queue::rmbpost));
+        .route("/queue/estimate", get(queue::estimate))
+        .route("/queue/events", get(queue::events))
+        .route("/queue/groups/{id}", get(queue::group))
+        .route(
+            "/queue/dispatches/{id}",
+            get(queue::dispatch).head(queue::dispatch_head),
+        )
+        .route(
+            "/queue/rmbposts/{id}",
+            get(queue::rmbpost).head(queue::rmbpost_head),
+        )
+        .route("/queue/rmbposts/groups/{id}", get(queue::rmbpost_group))
+        .route(
+            "/queue/telegrams/{id}",
+            get(queue::telegram)
+                .head(queue::telegram_head)
+                .route_layer(require(&[Claim::TelegramsRead])),
+        );
 
     // /nations/...
-    let nation_router = Router::new().route(
-        "/nations/{nation}/dispatches",
-        get(nations::dispatches::get),
-    );
+    let nation_router = Router::new().route("/nations/status", get(nations::status));
+
+    // /stats/...
+    let stats_router = Router::new()
+        .route("/stats/dispatches", get(stats::dispatches))
+        .route("/stats/dispatches/top", get(stats::top_dispatches))
+        .route("/stats/telegrams", get(stats::telegrams))
+        .route("/stats/timings", get(stats::timings))
+        .route_layer(require(&[Claim::StatsRead]));
+
+    // /stats/dispatches/largest, an admin view
+    let largest_router = Router::new()
+        .route("/stats/dispatches/largest", get(stats::largest_dispatches))
+        .route_layer(require(&[Claim::Admin]));
+
+    // /metrics
+    let metrics_router = Router::new()
+        .route("/metrics", get(metrics::get))
+        .route_layer(require(&[Claim::Admin, Claim::StatsRead]));
+
+    // /export/...
+    let export_router = Router::new()
+        .route("/export/dispatches", get(export::dispatches))
+        .route_layer(require(&[Claim::Admin, Claim::ExportRead]));
 
     // /users/...
     let user_router = Router::new()
+        .route("/users/me", get(user::me))
         .route("/users/{id}", get(user::get))
         .route("/users/username/{username}", get(user::get_by_username))
         .route("/users/me/password", patch(user::update_password))
-        .route("/users/{id}/password", patch(admin::change_user_password));
+        .route(
+            "/users/me/api-keys",
+            get(user::api_keys).post(user::create_api_key),
+        )
+        .route("/users/me/api-keys/{id}", delete(user::revoke_api_key))
+        .route(
+            "/users/{id}/password",
+            patch(admin::change_user_password).route_layer(require(&[Claim::Admin])),
+        );
 
-    Router::new()
+    // /admin/...
+    let admin_router = Router::new()
+        .route("/admin/audit", get(admin::audit))
+        .route("/admin/users", get(admin::users))
+        .route("/admin/permissions", get(admin::permissions))
+        .route("/admin/users/{id}", delete(admin::disable_user))
+        .route("/admin/users/{id}/tenant", put(admin::set_user_tenant))
+        .route(
+            "/admin/tenants",
+            get(admin::tenants).post(admin::create_tenant),
+        )
+        .route("/admin/pipelines", get(admin::pipelines))
+        .route("/admin/workers", get(admin::workers))
+        .route("/admin/retention", get(admin::retention))
+        .route(
+            "/admin/logging",
+            get(admin::logging).patch(admin::configure_logging),
+        )
+        .route(
+            "/admin/ratelimiter",
+            get(admin::ratelimiter).patch(admin::configure_ratelimiter),
+        )
+        .route("/admin/pipelines/{name}/pause", post(admin::pause_pipeline))
+        .route(
+            "/admin/nations/{name}/revalidate",
+            post(admin::revalidate_nation),
+        )
+        .route("/admin/nations/{name}/verify", post(admin::verify_nation))
+        .route(
+            "/admin/nations/{name}/presets",
+            get(admin::preset)
+                .put(admin::set_preset)
+                .delete(admin::delete_preset),
+        )
+        .route(
+            "/admin/content-rules",
+            get(admin::content_rules).post(admin::create_content_rule),
+        )
+        .route(
+            "/admin/content-rules/{id}",
+            put(admin::update_content_rule).delete(admin::delete_content_rule),
+        )
+        .route(
+            "/admin/pipelines/{name}/resume",
+            post(admin::resume_pipeline),
+        )
+        .route_layer(require(&[Claim::Admin]));
+
+    // /webhooks/...
+    let webhook_router = Router::new()
+        .route("/webhooks", get(webhook::get_all).post(webhook::create))
+        .route("/webhooks/{id}", delete(webhook::delete))
+        .route("/webhooks/{id}/rotate-secret", post(webhook::rotate_secret))
+        .route_layer(require(&[Claim::Admin]));
+
+    // /openapi.json, /docs
+    let docs_router = if api_docs {
+        Router::new()
+            .route("/openapi.json", get(openapi::spec))
+            .route("/docs", get(openapi::docs))
+    } else {
+        Router::new()
+    };
+
+    let app = Router::new()
         .route("/", get(|| async { "Hello, World!" }))
         .route("/heartbeat", get(|| async { StatusCode::OK }))
+        .route("/health", get(health::get))
         .route("/register", post(user::register))
         .route("/login", post(user::login))
+        .route("/login/refresh", post(user::refresh))
+        .route("/bootstrap", get(bootstrap::get).route_layer(require(&[])))
         .merge(dispatch_router)
         .merge(telegram_router)
         .merge(rmbpost_router)
         .merge(queue_router)
         .merge(nation_router)
+        .merge(stats_router)
+        .merge(largest_router)
+        .merge(metrics_router)
+        .merge(export_router)
         .merge(user_router)
+        .merge(admin_router)
+        .merge(webhook_router)
+        .merge(docs_router)
         .with_state(state.clone())
         .route_layer(
             ServiceBuilder::new()
@@ -97,6 +291,7 @@ pub(crate) async fn routes(
                     state.clone(),
                     controllers::user::authenticate,
                 ))
+                .layer(middleware::from_fn(public::require_credentials))
                 .layer(
                     TraceLayer::new_for_http().make_span_with(|request: &Request<_>| {
                         let matched_path = request
@@ -104,10 +299,16 @@ pub(crate) async fn routes(
                             .get::<MatchedPath>()
                             .map(MatchedPath::as_str);
 
+                        let request_id = request
+                            .extensions()
+                            .get::<RequestId>()
+                            .map(|request_id| request_id.0.as_str());
+
                         info_span!(
                             "request",
-                            method = ?request.method(),
+                            method = %request.method(),
                             matched_path,
+                            request_id,
                         )
                     }),
                 )
@@ -127,7 +328,21 @@ pub(crate) async fn routes(
                         .expose_headers([
                             HeaderName::from_static("dispatch-nations"),
                             HeaderName::from_static("rmbpost-nations"),
+                            header::ETAG,
+                            header::LOCATION,
+                            header::LINK,
+                            HeaderName::from_static("x-job-status"),
+                            request_id::HEADER,
                         ]),
                 ),
-        )
+        );
+
+    let app = app.layer(middleware::from_fn(request_id::propagate));
+
+    if compression {
+        // streams as it compresses, and leaves event streams alone
+        app.layer(CompressionLayer::new())
+    } else {
+        app
+    }
 }
@@ -0,0 +1,89 @@
+use axum::extract::{Json, State};
+use axum::response::IntoResponse;
+use tracing::instrument;
+
+use crate::core::authorization::Authorized;
+use crate::core::error::Error;
+use crate::core::extract::Query;
+use crate::core::state::AppState;
+use crate::types::request::{self, JobKind};
+
+#[instrument(skip_all)]
+pub(crate) async fn dispatches(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Query(query): Query<request::DispatchStatsQuery>,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json(
+        state
+            .dispatch_controller
+            .stats(&query, user.tenant_id)
+            .await?,
+    ))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn largest_dispatches(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Query(query): Query<request::LargestDispatchesQuery>,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json(
+        state
+            .dispatch_controller
+            .largest(&query, user.tenant_id)
+            .await?,
+    ))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn top_dispatches(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Query(query): Query<request::TopDispatchesQuery>,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json(
+        state
+            .dispatch_controller
+            .top(&query, user.tenant_id)
+            .await?,
+    ))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn telegrams(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Query(query): Query<request::TelegramStatsQuery>,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json(
+        state
+            .telegram_controller
+            .stats(&query, user.tenant_id)
+            .await?,
+    ))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn timings(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Query(query): Query<request::TimingsQuery>,
+) -> Result<impl IntoResponse, Error> {
+    let timings = match query.job {
+        JobKind::Dispatch => {
+            state
+                .dispatch_controller
+                .timings(&query, user.tenant_id)
+                .await?
+        }
+        JobKind::Rmbpost => {
+            state
+                .rmbpost_controller
+                .timings(&query, user.tenant_id)
+                .await?
+        }
+    };
+
+    Ok(Json(timings))
+}
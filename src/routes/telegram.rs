@@ -1,68 +1,225 @@
-use axum::Extension;
-use axum::extract::{Json, State};
-use std::collections::HashMap;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap};
 
+use crate::controllers::audit;
+use crate::core::authorization::Authorized;
 use crate::core::error::Error;
+use crate::core::extract::{Json, Path, Query};
 use crate::core::state::AppState;
-use crate::ns::telegram::{Header, Params};
-use crate::types::AuthorizedUser;
+use crate::ns::telegram::{DeleteScope, Header, NewCampaign, Params, RecipientFilter};
+use crate::types::request::TelegramListQuery;
 use crate::types::response;
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn get(
     State(mut state): State<AppState>,
-    Extension(user): Extension<Option<AuthorizedUser>>,
+    Authorized(user): Authorized,
+    Query(query): Query<TelegramListQuery>,
 ) -> Result<Json<HashMap<String, Vec<response::Telegram>>>, Error> {
-    match user {
-        Some(user) => {
-            if !user.claims.contains(&"telegrams.read".to_string()) {
-                return Err(Error::Unauthorized);
-            }
-        }
-        None => return Err(Error::Unauthorized),
-    }
+    let created_by = query.mine.then_some(user.username.as_str());
 
-    let telegrams = state.telegram_controller.get().await?;
+    let telegrams = state
+        .telegram_controller
+        .get(created_by, user.tenant_id)
+        .await?;
 
     Ok(Json(telegrams))
 }
 
+/// Queued telegrams and cooldowns per sender nation.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn summary(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json(
+        state.telegram_controller.summary(user.tenant_id).await?,
+    ))
+}
+
+/// Recruitment telegrams sent, queued and still possible today per sender nation.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn capacity(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json(
+        state.telegram_controller.capacity(user.tenant_id).await?,
+    ))
+}
+
 #[tracing::instrument(skip_all)]
 pub(crate) async fn post(
     State(mut state): State<AppState>,
-    Extension(user): Extension<Option<AuthorizedUser>>,
+    Authorized(user): Authorized,
+    Query(filter): Query<RecipientFilter>,
     Json(params): Json<Vec<Params>>,
-) -> Result<String, Error> {
-    match user {
-        Some(user) => {
-            if !user.claims.contains(&"telegrams.create".to_string()) {
-                return Err(Error::Unauthorized);
-            }
-        }
-        None => return Err(Error::Unauthorized),
-    }
-
-    state.telegram_controller.queue(params).await?;
-
-    Ok("Telegrams queued".to_string())
+) -> Result<impl IntoResponse, Error> {
+    let senders: BTreeSet<_> = params.iter().map(|params| params.sender.clone()).collect();
+    let telegram_ids: BTreeSet<_> = params.iter().map(|params| params.id.clone()).collect();
+
+    let event = audit::Event::new(&user, "telegram.create", "telegram").summary(json!({
+        "count": params.len(),
+        "senders": senders,
+        "telegram_ids": telegram_ids,
+        "filter": filter,
+    }));
+
+    let result = state
+        .telegram_controller
+        .queue(&user, params, &filter)
+        .await;
+
+    state.audit_controller.record(event, &result);
+
+    let queued = result?;
+
+    Ok((StatusCode::ACCEPTED, Json(queued)))
 }
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn delete(
     State(mut state): State<AppState>,
-    Extension(user): Extension<Option<AuthorizedUser>>,
+    Authorized(user): Authorized,
     Json(params): Json<Header>,
 ) -> Result<String, Error> {
-    match user {
-        Some(user) => {
-            if !user.claims.contains(&"telegrams.delete".to_string()) {
-                return Err(Error::Unauthorized);
-            }
-        }
-        None => return Err(Error::Unauthorized),
-    }
+    let scope = state.telegram_controller.delete_scope(&user).await?;
+
+    let event = audit::Event::new(&user, "telegram.delete", "telegram").summary(json!({
+        "recipient": params.recipient,
+        "telegram_id": params.telegram_id,
+        "any": !matches!(scope, DeleteScope::Own(_)),
+    }));
+
+    let result = state.telegram_controller.delete(params, scope).await;
+
+    state.audit_controller.record(event, &result);
+
+    result?;
+
+    Ok("Telegram deleted".to_string())
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn delete_by_id(
+    State(mut state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<String, Error> {
+    let scope = state.telegram_controller.delete_scope(&user).await?;
+
+    let event = audit::Event::new(&user, "telegram.delete", "telegram_job")
+        .target(id)
+        .summary(json!({ "any": !matches!(scope, DeleteScope::Own(_)) }));
+
+    let result = state
+        .telegram_controller
+        .delete_job(id, scope, Some(user.tenant_id))
+        .await;
 
-    state.telegram_controller.delete(params).await?;
+    state.audit_controller.record(event, &result);
+
+    result?;
 
     Ok("Telegram deleted".to_string())
 }
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn create_campaign(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Json(campaign): Json<NewCampaign>,
+) -> Result<impl IntoResponse, Error> {
+    let event =
+        audit::Event::new(&user, "telegram.campaign_create", "telegram_campaign").summary(json!({
+            "name": campaign.name,
+            "sender": campaign.sender,
+            "telegram_id": campaign.telegram_id,
+            "recipients_from": campaign.recipients_from,
+            "filter": campaign.filter,
+            "paused": campaign.paused,
+        }));
+
+    let result = state
+        .telegram_controller
+        .create_campaign(&user, campaign)
+        .await;
+
+    state.audit_controller.record(
+        match &result {
+            Ok(campaign) => event.target(campaign.id),
+            Err(_) => event,
+        },
+        &result,
+    );
+
+    Ok((StatusCode::CREATED, Json(result?)))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn get_campaign(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<Json<response::TelegramCampaign>, Error> {
+    Ok(Json(
+        state
+            .telegram_controller
+            .get_campaign(id, Some(user.tenant_id))
+            .await?,
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn campaign_recipients(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<response::CampaignRecipient>>, Error> {
+    Ok(Json(
+        state
+            .telegram_controller
+            .campaign_recipients(id, Some(user.tenant_id))
+            .await?,
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn pause_campaign(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<Json<response::TelegramCampaign>, Error> {
+    let event = audit::Event::new(&user, "telegram.campaign_pause", "telegram_campaign").target(id);
+
+    let result = state
+        .telegram_controller
+        .pause_campaign(id, Some(user.tenant_id))
+        .await;
+
+    state.audit_controller.record(event, &result);
+
+    Ok(Json(result?))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn resume_campaign(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<Json<response::TelegramCampaign>, Error> {
+    let event =
+        audit::Event::new(&user, "telegram.campaign_resume", "telegram_campaign").target(id);
+
+    let result = state
+        .telegram_controller
+        .resume_campaign(id, Some(user.tenant_id))
+        .await;
+
+    state.audit_controller.record(event, &result);
+
+    Ok(Json(result?))
+}
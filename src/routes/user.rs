@@ -1,23 +1,36 @@
-use axum::Json;
-use axum::extract::{Extension, Path, State};
+use axum::extract::{ConnectInfo, Extension, State};
 use axum::http::{StatusCode, header};
 use axum::response::IntoResponse;
+use serde_json::json;
+use std::net::SocketAddr;
 
+use crate::controllers::audit;
 use crate::core::error::Error;
+use crate::core::extract::{Json, Path};
 use crate::core::state::AppState;
-use crate::types::AuthorizedUser;
 use crate::types::request;
 use crate::types::response;
+use crate::types::{AuthorizedUser, tenant_scope};
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn register(
     State(state): State<AppState>,
     Json(input): Json<request::LoginData>,
 ) -> Result<impl IntoResponse, Error> {
-    let (user, token) = state
+    let result = state
         .user_controller
         .register(&input.username, &input.password)
-        .await?;
+        .await;
+
+    // there is no authorized user to attribute a failed registration to
+    if let Ok((user, _)) = &result {
+        state.audit_controller.record(
+            audit::Event::new(user, "user.register", "user").target(user.id),
+            &result,
+        );
+    }
+
+    let (user, token) = result?;
 
     Ok((
         StatusCode::ACCEPTED,
@@ -29,12 +42,40 @@ pub(crate) async fn register(
 #[tracing::instrument(skip_all)]
 pub(crate) async fn login(
     State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
     Json(input): Json<request::LoginData>,
 ) -> Result<Json<response::Login>, Error> {
-    let (user, token) = state
+    let result = state
         .user_controller
-        .login(&input.username, &input.password)
-        .await?;
+        .login(&input.username, &input.password, Some(address.ip()))
+        .await;
+
+    if let Err(Error::LoginThrottled(lockout)) = &result
+        && lockout.started
+    {
+        state.audit_controller.record(
+            audit::Event::anonymous("user.lockout", "user")
+                .target(&input.username)
+                .summary(json!({
+                    "scope": lockout.scope.as_str(),
+                    "ip": address.ip(),
+                    "retry_after_secs": lockout.retry_after.as_secs(),
+                })),
+            &result,
+        );
+    }
+
+    let (user, token) = result?;
+
+    Ok(Json(response::Login::new(&user.username, &token)))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn refresh(
+    State(state): State<AppState>,
+    Json(input): Json<request::RefreshData>,
+) -> Result<Json<response::Login>, Error> {
+    let (user, token) = state.user_controller.refresh(&input.token).await?;
 
     Ok(Json(response::Login::new(&user.username, &token)))
 }
@@ -42,20 +83,37 @@ pub(crate) async fn login(
 #[tracing::instrument(skip_all)]
 pub(crate) async fn get(
     State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
     Path(id): Path<i32>,
 ) -> Result<impl IntoResponse, Error> {
     let username = state
         .user_controller
-        .get_username_by_id(id)
+        .get_username_by_id(id, Some(tenant_scope(user.as_ref())))
         .await?
         .ok_or(Error::InvalidUsername)?;
 
     Ok(Json(response::User::new(id, &username)))
 }
 
+#[tracing::instrument(skip_all)]
+pub(crate) async fn me(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+) -> Result<impl IntoResponse, Error> {
+    let user = user.ok_or(Error::NoCredentials)?;
+
+    let dispatch_nations = state
+        .tenant_controller
+        .own_nations(user.tenant_id, state.dispatch_controller.nations().await?)
+        .await?;
+
+    Ok(Json(response::Profile::new(&user, dispatch_nations)))
+}
+
 #[tracing::instrument(skip_all)]
 pub(crate) async fn get_by_username(
     State(state): State<AppState>,
+    Extension(caller): Extension<Option<AuthorizedUser>>,
     Path(username): Path<String>,
 ) -> Result<impl IntoResponse, Error> {
     let user = match state
@@ -63,8 +121,8 @@ pub(crate) async fn get_by_username(
         .get_user_by_username(&username)
         .await?
     {
-        Some(user) => user,
-        None => return Err(Error::Unauthorized),
+        Some(user) if user.tenant_id == tenant_scope(caller.as_ref()) => user,
+        _ => return Err(Error::Unauthorized),
     };
 
     Ok(Json(response::User::new(user.id, &user.username)))
@@ -77,14 +135,76 @@ pub(crate) async fn update_password(
     Json(params): Json<request::UpdatePasswordData>,
 ) -> Result<impl IntoResponse, Error> {
     let user = match user {
-        Some(user) => user,
-        None => return Err(Error::Unauthorized),
+        // a scoped key must not be able to take over the account it belongs to
+        Some(user) if user.api_key_id.is_none() => user,
+        _ => return Err(Error::Unauthorized),
     };
 
-    state
+    let result = state
         .user_controller
         .update_password(&user.username, &params.new_password)
-        .await?;
+        .await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "user.password", "user").target(user.id),
+        &result,
+    );
+
+    result?;
 
     Ok(Json("Password reset successfully"))
 }
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn create_api_key(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Json(params): Json<request::NewApiKey>,
+) -> Result<impl IntoResponse, Error> {
+    let user = user.ok_or(Error::NoCredentials)?;
+
+    let event = audit::Event::new(&user, "api_key.create", "api_key").summary(json!({
+        "name": params.name,
+        "claims": params.claims,
+        "expires_at": params.expires_at,
+    }));
+
+    let result = state.api_key_controller.create(&user, params).await;
+
+    let event = match &result {
+        Ok(api_key) => event.target(api_key.id),
+        Err(_) => event,
+    };
+
+    state.audit_controller.record(event, &result);
+
+    Ok((StatusCode::CREATED, Json(result?)))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn api_keys(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+) -> Result<impl IntoResponse, Error> {
+    let user = user.ok_or(Error::NoCredentials)?;
+
+    Ok(Json(state.api_key_controller.list(user.id).await?))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn revoke_api_key(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<AuthorizedUser>>,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let user = user.ok_or(Error::NoCredentials)?;
+
+    let result = state.api_key_controller.revoke(user.id, id).await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "api_key.revoke", "api_key").target(id),
+        &result,
+    );
+
+    Ok(Json(result?))
+}
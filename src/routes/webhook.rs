@@ -0,0 +1,82 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde_json::json;
+use tracing::instrument;
+
+use crate::controllers::audit;
+use crate::core::authorization::Authorized;
+use crate::core::error::Error;
+use crate::core::extract::{Json, Path};
+use crate::core::state::AppState;
+use crate::types::request;
+
+#[instrument(skip_all)]
+pub(crate) async fn create(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Json(params): Json<request::NewWebhook>,
+) -> Result<impl IntoResponse, Error> {
+    // the secret stays out of the audit log
+    let event =
+        audit::Event::new(&user, "webhook.create", "webhook").summary(json!({ "url": params.url }));
+
+    let result = state
+        .webhook_controller
+        .register(&user.username, user.tenant_id, params)
+        .await;
+
+    let event = match &result {
+        Ok(webhook) => event.target(webhook.id),
+        Err(_) => event,
+    };
+
+    state.audit_controller.record(event, &result);
+
+    Ok((StatusCode::CREATED, Json(result?)))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn get_all(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+) -> Result<impl IntoResponse, Error> {
+    Ok(Json(state.webhook_controller.list(user.tenant_id).await?))
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn delete(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let result = state.webhook_controller.delete(id, user.tenant_id).await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "webhook.delete", "webhook").target(id),
+        &result,
+    );
+
+    result?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn rotate_secret(
+    State(state): State<AppState>,
+    Authorized(user): Authorized,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let result = state
+        .webhook_controller
+        .rotate_secret(id, user.tenant_id)
+        .await;
+
+    state.audit_controller.record(
+        audit::Event::new(&user, "webhook.rotate_secret", "webhook").target(id),
+        &result,
+    );
+
+    Ok(Json(result?))
+}
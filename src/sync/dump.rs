@@ -0,0 +1,131 @@
+//! The nations dump, indexed in memory and refreshed in the background. A refresh that fails
+//! keeps the index from the last one that didn't.
+
+use axum::body::Bytes;
+use std::fs::File;
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+use crate::core::error::ConfigError;
+use crate::ns::dump::{self, Index};
+
+/// Downloaded chunks buffered ahead of the parser.
+const CHUNK_BUFFER: usize = 16;
+
+#[derive(Clone, Debug)]
+pub(crate) struct Dump {
+    rx: watch::Receiver<Option<Arc<Index>>>,
+}
+
+impl Dump {
+    /// Starts indexing `source`, a URL or a file path, every `period`. Without a source there's
+    /// never an index.
+    pub(crate) fn new(
+        user_agent: &str,
+        source: Option<String>,
+        period: Duration,
+    ) -> Result<Self, ConfigError> {
+        let (tx, rx) = watch::channel(None);
+
+        if let Some(source) = source {
+            let client = reqwest::Client::builder().user_agent(user_agent).build()?;
+
+            tracing::info!("indexing the nations dump from {}", source);
+            tokio::spawn(run(client, source, period, tx));
+        }
+
+        Ok(Self { rx })
+    }
+
+    /// The last index built, `None` until the first one is.
+    pub(crate) fn index(&self) -> Option<Arc<Index>> {
+        self.rx.borrow().clone()
+    }
+}
+
+async fn run(
+    client: reqwest::Client,
+    source: String,
+    period: Duration,
+    tx: watch::Sender<Option<Arc<Index>>>,
+) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+
+        match load(&client, &source).await {
+            Ok(index) => {
+                tracing::info!("indexed {} nations from the nations dump", index.len());
+
+                tx.send_replace(Some(Arc::new(index)));
+            }
+            Err(e) => tracing::error!("unable to index the nations dump: {}", e),
+        }
+    }
+}
+
+async fn load(client: &reqwest::Client, source: &str) -> io::Result<Index> {
+    if !(source.starts_with("http://") || source.starts_with("https://")) {
+        let path = source.to_string();
+
+        return tokio::task::spawn_blocking(move || dump::parse(File::open(path)?))
+            .await
+            .map_err(io::Error::other)?;
+    }
+
+    let mut response = client
+        .get(source)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(io::Error::other)?;
+
+    // parsed as it comes in, the unpacked dump doesn't fit in memory
+    let (tx, rx) = mpsc::channel(CHUNK_BUFFER);
+    let parser = tokio::task::spawn_blocking(move || dump::parse(Chunks::new(rx)));
+
+    while let Some(chunk) = response.chunk().await.map_err(io::Error::other)? {
+        if tx.send(chunk).await.is_err() {
+            // the parser gave up, its error is the one to report
+            break;
+        }
+    }
+
+    drop(tx);
+
+    parser.await.map_err(io::Error::other)?
+}
+
+/// Reads the chunks of a download as they arrive, blocking while it waits for the next one.
+struct Chunks {
+    rx: mpsc::Receiver<Bytes>,
+    current: Bytes,
+}
+
+impl Chunks {
+    fn new(rx: mpsc::Receiver<Bytes>) -> Self {
+        Self {
+            rx,
+            current: Bytes::new(),
+        }
+    }
+}
+
+impl Read for Chunks {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+
+        Ok(n)
+    }
+}
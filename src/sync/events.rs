@@ -0,0 +1,125 @@
+use crate::types::request::EventsQuery;
+use crate::types::response::JobEvent;
+use futures_util::Stream;
+use futures_util::stream;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::watch;
+
+/// Events a subscriber can fall behind by before it starts missing them.
+const CAPACITY: usize = 256;
+
+/// What a subscriber receives.
+#[derive(Debug)]
+pub(crate) enum Message {
+    Job(JobEvent),
+    /// the subscriber fell behind and this many events were dropped
+    Lagged(u64),
+}
+
+/// Fans job status changes out from the workers to `GET /queue/events` subscribers.
+#[derive(Clone, Debug)]
+pub(crate) struct Sender {
+    tx: broadcast::Sender<JobEvent>,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Sender {
+    pub(crate) fn new() -> Self {
+        let (tx, _) = broadcast::channel(CAPACITY);
+        let (shutdown, _) = watch::channel(false);
+
+        Self { tx, shutdown }
+    }
+
+    /// Publishing with nobody subscribed is fine; the event is dropped.
+    pub(crate) fn publish(&self, event: JobEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    /// Ends every open stream so graceful shutdown doesn't wait on them.
+    pub(crate) fn close(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Events matching `filter`, until [`Sender::close`] is called.
+    pub(crate) fn subscribe(&self, filter: EventsQuery) -> impl Stream<Item = Message> + use<> {
+        let state = (self.tx.subscribe(), self.shutdown.subscribe(), filter);
+
+        stream::unfold(state, |(mut rx, mut shutdown, filter)| async move {
+            loop {
+                let event = tokio::select! {
+                    biased;
+
+                    // anything already published is still delivered before the stream ends
+                    event = rx.recv() => event,
+
+                    _ = shutdown.wait_for(|closed| *closed) => return None,
+                };
+
+                match event {
+                    Ok(event) if matches(&filter, &event) => {
+                        return Some((Message::Job(event), (rx, shutdown, filter)));
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        return Some((Message::Lagged(skipped), (rx, shutdown, filter)));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+fn matches(filter: &EventsQuery, event: &JobEvent) -> bool {
+    filter.job.is_none_or(|job| job == event.job)
+        && filter.id.is_none_or(|id| id == event.id)
+        && filter.tenant.is_none_or(|tenant| tenant == event.tenant_id)
+        && filter
+            .user
+            .as_ref()
+            .is_none_or(|user| event.created_by.as_ref() == Some(user))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::job::JobStatus;
+    use crate::types::request::JobKind;
+    use futures_util::StreamExt;
+
+    fn event(job: JobKind, id: i32, user: &str) -> JobEvent {
+        JobEvent {
+            job,
+            id,
+            status: JobStatus::Succeeded,
+            error_code: None,
+            self_url: String::new(),
+            resource: None,
+            created_by: Some(user.to_string()),
+            tenant_id: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_filters_and_closes() {
+        let events = Sender::new();
+
+        let stream = events.subscribe(EventsQuery {
+            job: Some(JobKind::Dispatch),
+            id: None,
+            user: Some("alice".to_string()),
+            tenant: None,
+        });
+
+        events.publish(event(JobKind::Rmbpost, 1, "alice"));
+        events.publish(event(JobKind::Dispatch, 2, "bob"));
+        events.publish(event(JobKind::Dispatch, 3, "alice"));
+        events.close();
+
+        let received: Vec<_> = stream.collect().await;
+
+        assert_eq!(received.len(), 1);
+        assert!(matches!(&received[0], Message::Job(event) if event.id == 3));
+    }
+}
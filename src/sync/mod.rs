@@ -1,2 +1,5 @@
+pub(crate) mod dump;
+pub(crate) mod events;
 pub(crate) mod nations;
 pub(crate) mod ratelimiter;
+pub(crate) mod telegram_types;
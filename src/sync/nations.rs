@@ -1,22 +1,22 @@
 use crate::core::error::{ConfigError, Error};
+use crate::types::Secret;
+use crate::utils::name;
 use std::collections::HashMap;
-use std::env;
-use std::fs;
-use std::path::PathBuf;
 use tokio::sync::{mpsc, oneshot};
 
 struct Nation {
-    name: String,
-    password: String,
+    password: Secret,
     pin: Option<String>,
+    /// false once NS has rejected the password, until the nation next succeeds
+    healthy: bool,
 }
 
 impl Nation {
-    fn new(name: &str, password: &str) -> Self {
+    fn new(password: &str) -> Self {
         Self {
-            name: name.into(),
-            password: password.into(),
+            password: Secret::from(password),
             pin: None,
+            healthy: true,
         }
     }
 }
@@ -26,6 +26,10 @@ enum Action {
     GetPassword { nation: String },
     GetPin { nation: String },
     SetPin { nation: String, pin: String },
+    ClearPin { nation: String },
+    IsHealthy { nation: String },
+    MarkUnhealthy { nation: String },
+    MarkHealthy { nation: String },
 }
 
 struct Command {
@@ -42,9 +46,19 @@ impl Command {
 #[derive(Debug)]
 enum Response {
     Ok,
-    List { nations: Vec<String> },
-    Password { password: Option<String> },
-    Pin { pin: Option<String> },
+    List {
+        nations: Vec<String>,
+    },
+    Password {
+        password: Option<Secret>,
+        healthy: bool,
+    },
+    Pin {
+        pin: Option<String>,
+    },
+    Healthy {
+        healthy: bool,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -76,8 +90,23 @@ impl Sender {
         }
     }
 
+    /// Fails with `Error::CredentialUnhealthy` once NS has rejected the password, so jobs for the
+    /// nation stop reaching NS until it is revalidated.
     #[tracing::instrument(skip_all)]
-    pub(crate) async fn get_password(&self, nation: &str) -> Result<String, Error> {
+    pub(crate) async fn get_password(&self, nation: &str) -> Result<Secret, Error> {
+        match self.password(nation).await? {
+            (password, true) => Ok(password),
+            (_, false) => Err(Error::CredentialUnhealthy(nation.to_string())),
+        }
+    }
+
+    /// The password regardless of health, for checking whether it works again.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn get_password_unchecked(&self, nation: &str) -> Result<Secret, Error> {
+        Ok(self.password(nation).await?.0)
+    }
+
+    async fn password(&self, nation: &str) -> Result<(Secret, bool), Error> {
         let (tx, rx) = oneshot::channel();
 
         if let Err(e) = self
@@ -95,9 +124,9 @@ impl Sender {
         };
 
         match rx.await {
-            Ok(Response::Password { password }) => {
+            Ok(Response::Password { password, healthy }) => {
                 if let Some(password) = password {
-                    Ok(password)
+                    Ok((password, healthy))
                 } else {
                     Err(Error::InvalidNation)
                 }
@@ -166,6 +195,99 @@ impl Sender {
             }
         }
     }
+
+    /// Forgets the pin of `nation`, so the next request authenticates with its password.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn clear_pin(&self, nation: &str) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        if let Err(e) = self
+            .tx
+            .send(Command::new(
+                Action::ClearPin {
+                    nation: nation.to_owned(),
+                },
+                tx,
+            ))
+            .await
+        {
+            tracing::error!("failed to send message: {}", e);
+            return Err(Error::Internal);
+        };
+
+        match rx.await {
+            Ok(Response::Ok) => Ok(()),
+            Ok(_) => unreachable!(),
+            Err(e) => {
+                tracing::error!("failed to clear pin: {}", e);
+                Err(Error::Internal)
+            }
+        }
+    }
+
+    /// Whether NS accepted the nation's password the last time it was used. Unknown nations
+    /// count as healthy.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn is_healthy(&self, nation: &str) -> Result<bool, Error> {
+        let (tx, rx) = oneshot::channel();
+
+        if let Err(e) = self
+            .tx
+            .send(Command::new(
+                Action::IsHealthy {
+                    nation: nation.to_owned(),
+                },
+                tx,
+            ))
+            .await
+        {
+            tracing::error!("failed to send message: {}", e);
+            return Err(Error::Internal);
+        };
+
+        match rx.await {
+            Ok(Response::Healthy { healthy }) => Ok(healthy),
+            Ok(_) => unreachable!(),
+            Err(e) => {
+                tracing::error!("failed to get health: {}", e);
+                Err(Error::Internal)
+            }
+        }
+    }
+
+    /// Records that NS rejected the nation's password.
+    pub(crate) async fn mark_unhealthy(&self, nation: &str) -> Result<(), Error> {
+        self.set_health(Action::MarkUnhealthy {
+            nation: nation.to_owned(),
+        })
+        .await
+    }
+
+    pub(crate) async fn mark_healthy(&self, nation: &str) -> Result<(), Error> {
+        self.set_health(Action::MarkHealthy {
+            nation: nation.to_owned(),
+        })
+        .await
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn set_health(&self, action: Action) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+
+        if let Err(e) = self.tx.send(Command::new(action, tx)).await {
+            tracing::error!("failed to send message: {}", e);
+            return Err(Error::Internal);
+        };
+
+        match rx.await {
+            Ok(Response::Ok) => Ok(()),
+            Ok(_) => unreachable!(),
+            Err(e) => {
+                tracing::error!("failed to set health: {}", e);
+                Err(Error::Internal)
+            }
+        }
+    }
 }
 
 pub(crate) struct Receiver {
@@ -178,6 +300,14 @@ impl Receiver {
         Self { rx, nations }
     }
 
+    fn get(&self, nation: &str) -> Option<&Nation> {
+        self.nations.get(&name::canonicalize(nation))
+    }
+
+    fn get_mut(&mut self, nation: &str) -> Option<&mut Nation> {
+        self.nations.get_mut(&name::canonicalize(nation))
+    }
+
     #[tracing::instrument(skip_all)]
     fn process(&mut self, command: Command) {
         let resp = match command.action {
@@ -189,17 +319,21 @@ impl Receiver {
             }
             Action::GetPassword { nation } => {
                 tracing::debug!("retrieving password for nation: {}", &nation);
-                if let Some(nation) = self.nations.get(&nation) {
+                if let Some(nation) = self.get(&nation) {
                     Response::Password {
                         password: Some(nation.password.clone()),
+                        healthy: nation.healthy,
                     }
                 } else {
-                    Response::Password { password: None }
+                    Response::Password {
+                        password: None,
+                        healthy: true,
+                    }
                 }
             }
             Action::GetPin { nation } => {
                 tracing::debug!("retrieving pin for nation: {}", &nation);
-                if let Some(nation) = self.nations.get(&nation) {
+                if let Some(nation) = self.get(&nation) {
                     Response::Pin {
                         pin: nation.pin.clone(),
                     }
@@ -209,10 +343,37 @@ impl Receiver {
             }
             Action::SetPin { nation, pin } => {
                 tracing::debug!("setting pin for nation: {}", &nation);
-                if let Some(nation) = self.nations.get_mut(&nation) {
+                if let Some(nation) = self.get_mut(&nation) {
                     nation.pin = Some(pin);
                 }
 
+                Response::Ok
+            }
+            Action::ClearPin { nation } => {
+                tracing::debug!("clearing pin for nation: {}", &nation);
+                if let Some(nation) = self.get_mut(&nation) {
+                    nation.pin = None;
+                }
+
+                Response::Ok
+            }
+            Action::IsHealthy { nation } => Response::Healthy {
+                healthy: self.get(&nation).is_none_or(|nation| nation.healthy),
+            },
+            Action::MarkUnhealthy { nation } => {
+                tracing::warn!("marking nation unhealthy: {}", &nation);
+                if let Some(nation) = self.get_mut(&nation) {
+                    nation.healthy = false;
+                }
+
+                Response::Ok
+            }
+            Action::MarkHealthy { nation } => {
+                tracing::info!("marking nation healthy: {}", &nation);
+                if let Some(nation) = self.get_mut(&nation) {
+                    nation.healthy = true;
+                }
+
                 Response::Ok
             }
         };
@@ -239,20 +400,9 @@ impl Receiver {
     }
 }
 
-pub(crate) enum Source {
-    Env(String),
-    File(PathBuf),
-    Str(String),
-}
-
-pub(crate) fn new(source: Source) -> Result<Sender, ConfigError> {
-    let nations = match source {
-        Source::Str(var) => var,
-        Source::Env(var) => env::var(var)?,
-        Source::File(path) => fs::read_to_string(&path)?,
-    };
-
-    let nations = parse_nations(&nations)?;
+/// Starts the actor for `nations`, comma-separated `nation:password` pairs.
+pub(crate) fn new(nations: &str) -> Result<Sender, ConfigError> {
+    let nations = parse_nations(nations)?;
 
     let (tx, rx) = mpsc::channel(16);
 
@@ -267,24 +417,148 @@ pub(crate) fn new(source: Source) -> Result<Sender, ConfigError> {
     Ok(sender)
 }
 
+/// Names of the nations in `nations`, checking it the way [`new`] does.
+pub(crate) fn names(nations: &str) -> Result<Vec<String>, ConfigError> {
+    let mut names: Vec<String> = parse_nations(nations)?.into_keys().collect();
+
+    names.sort();
+
+    Ok(names)
+}
+
+/// Comma-separated `nation:password` pairs, none when `nations` is blank. Errors point at the
+/// entry by position, so passwords don't end up in the logs.
 fn parse_nations(nations: &str) -> Result<HashMap<String, Nation>, ConfigError> {
-    nations
-        .split(",")
-        .map(parse_nation)
-        .collect::<Result<HashMap<String, Nation>, ConfigError>>()
+    let mut parsed: HashMap<String, (usize, Nation)> = HashMap::new();
+
+    if nations.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    for (index, value) in nations.split(',').enumerate() {
+        let position = index + 1;
+        let (nation, password) = parse_nation(position, value)?;
+
+        if let Some((first, _)) = parsed.get(&nation) {
+            return Err(ConfigError::Nations(format!(
+                "entry {position} repeats {nation}, already entry {first}"
+            )));
+        }
+
+        parsed.insert(nation, (position, Nation::new(password)));
+    }
+
+    Ok(parsed
+        .into_iter()
+        .map(|(nation, (_, credentials))| (nation, credentials))
+        .collect())
 }
 
-fn parse_nation(value: &str) -> Result<(String, Nation), ConfigError> {
-    let mut split = value.splitn(2, ":");
+fn parse_nation(position: usize, value: &str) -> Result<(String, &str), ConfigError> {
+    if value.trim().is_empty() {
+        return Err(ConfigError::Nations(format!("entry {position} is empty")));
+    }
+
+    let (nation, password) = value.split_once(':').ok_or_else(|| {
+        ConfigError::Nations(format!(
+            "entry {position} has no `:` between the nation and its password"
+        ))
+    })?;
 
-    let nation = split
-        .next()
-        .ok_or(ConfigError::Nations(value.to_string()))?
-        .trim();
+    let nation = name::canonicalize(nation);
 
-    let password = split
-        .next()
-        .ok_or(ConfigError::Nations(value.to_string()))?;
+    if nation.is_empty() {
+        return Err(ConfigError::Nations(format!(
+            "entry {position} has no nation name"
+        )));
+    }
 
-    Ok((nation.to_string(), Nation::new(nation, password)))
+    if password.is_empty() {
+        return Err(ConfigError::Nations(format!(
+            "entry {position} ({nation}) has an empty password"
+        )));
+    }
+
+    Ok((nation, password))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unhealthy_nation_refuses_password() {
+        let nations = new("testlandia:password").unwrap();
+
+        nations.mark_unhealthy("testlandia").await.unwrap();
+
+        assert!(!nations.is_healthy("testlandia").await.unwrap());
+        assert!(matches!(
+            nations.get_password("testlandia").await,
+            Err(Error::CredentialUnhealthy(nation)) if nation == "testlandia"
+        ));
+        assert_eq!(
+            nations
+                .get_password_unchecked("testlandia")
+                .await
+                .unwrap()
+                .expose(),
+            "password"
+        );
+
+        nations.mark_healthy("testlandia").await.unwrap();
+
+        assert!(nations.is_healthy("testlandia").await.unwrap());
+        assert_eq!(
+            nations.get_password("testlandia").await.unwrap().expose(),
+            "password"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_password_redacted() {
+        let nations = new("testlandia:hunter2").unwrap();
+        let password = nations.get_password("testlandia").await.unwrap();
+
+        let response = Response::Password {
+            password: Some(password.clone()),
+            healthy: true,
+        };
+
+        for debug in [format!("{password:?}"), format!("{response:?}")] {
+            assert!(!debug.contains("hunter2"), "{debug}");
+        }
+    }
+
+    fn parse_error(nations: &str) -> String {
+        match parse_nations(nations) {
+            Err(ConfigError::Nations(message)) => message,
+            Err(e) => panic!("unexpected error {e}"),
+            Ok(_) => panic!("{nations:?} parsed"),
+        }
+    }
+
+    #[test]
+    fn test_parse_nations() {
+        assert_eq!(
+            names("Testlandia:secret, other_nation:a:b").unwrap(),
+            vec!["other_nation", "testlandia"]
+        );
+        assert!(names(" ").unwrap().is_empty());
+
+        assert_eq!(parse_error("testlandia:a,"), "entry 2 is empty");
+        assert_eq!(
+            parse_error("testlandia:a,hunter2"),
+            "entry 2 has no `:` between the nation and its password"
+        );
+        assert_eq!(parse_error(":hunter2"), "entry 1 has no nation name");
+        assert_eq!(
+            parse_error("a:b,testlandia:"),
+            "entry 2 (testlandia) has an empty password"
+        );
+        assert_eq!(
+            parse_error("testlandia:a,b:c,TESTLANDIA:d"),
+            "entry 3 repeats testlandia, already entry 1"
+        );
+    }
 }
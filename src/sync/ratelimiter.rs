@@ -1,42 +1,230 @@
 use crate::core::error::Error;
-use std::collections::{HashMap, VecDeque};
+use crate::types::request::RatelimiterPatch;
+use crate::types::response::{ObservedRequests, RatelimiterLimits, RatelimiterStatus};
+use crate::utils::name;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::ops::{Add, Mul};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio::time::{Duration, Instant};
 
-#[derive(Debug)]
+/// Longest bucket or cooldown accepted at runtime, anything longer is surely a typo.
+const MAX_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long past its expected start a reservation is kept, in case the worker holding it went
+/// away without acquiring or cancelling it.
+const RESERVATION_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// Private commands NS cools down independently of each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RestrictedAction {
+    Dispatch,
+    RmbPost,
+}
+
+impl RestrictedAction {
+    const ALL: [Self; 2] = [Self::Dispatch, Self::RmbPost];
+}
+
+/// Limits NS imposes, adjustable at runtime for when NS changes them temporarily.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Limits {
+    pub(crate) max_requests: usize,
+    pub(crate) bucket_length: Duration,
+    pub(crate) telegram_cooldown: Duration,
+    pub(crate) recruitment_cooldown: Duration,
+    /// cooldown of restricted actions without their own entry in `restricted_cooldowns`
+    pub(crate) restricted_action_cooldown: Duration,
+    pub(crate) restricted_cooldowns: BTreeMap<RestrictedAction, Duration>,
+}
+
+impl Limits {
+    pub(crate) fn restricted_cooldown(&self, action: RestrictedAction) -> Duration {
+        self.restricted_cooldowns
+            .get(&action)
+            .copied()
+            .unwrap_or(self.restricted_action_cooldown)
+    }
+
+    /// `self` with the values set in `patch`, which must all be positive.
+    pub(crate) fn patched(&self, patch: &RatelimiterPatch) -> Result<Self, Error> {
+        fn duration(name: &str, secs: Option<f64>, current: Duration) -> Result<Duration, Error> {
+            match secs {
+                None => Ok(current),
+                Some(secs) => match Duration::try_from_secs_f64(secs) {
+                    Ok(duration) if !duration.is_zero() && duration <= MAX_DURATION => Ok(duration),
+                    _ => Err(Error::InvalidRatelimit(format!(
+                        "{} must be more than 0 and at most {} seconds",
+                        name,
+                        MAX_DURATION.as_secs()
+                    ))),
+                },
+            }
+        }
+
+        if patch.max_requests == Some(0) {
+            return Err(Error::InvalidRatelimit(
+                "max_requests must be at least 1".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            max_requests: patch.max_requests.unwrap_or(self.max_requests),
+            bucket_length: duration(
+                "bucket_length_secs",
+                patch.bucket_length_secs,
+                self.bucket_length,
+            )?,
+            telegram_cooldown: duration(
+                "telegram_cooldown_secs",
+                patch.telegram_cooldown_secs,
+                self.telegram_cooldown,
+            )?,
+            recruitment_cooldown: duration(
+                "recruitment_cooldown_secs",
+                patch.recruitment_cooldown_secs,
+                self.recruitment_cooldown,
+            )?,
+            restricted_action_cooldown: duration(
+                "restricted_action_cooldown_secs",
+                patch.restricted_action_cooldown_secs,
+                self.restricted_action_cooldown,
+            )?,
+            restricted_cooldowns: {
+                let mut cooldowns = self.restricted_cooldowns.clone();
+
+                for (action, secs) in patch.restricted_cooldowns_secs.iter().flatten() {
+                    cooldowns.insert(
+                        *action,
+                        duration(
+                            "restricted_cooldowns_secs",
+                            Some(*secs),
+                            self.restricted_cooldown(*action),
+                        )?,
+                    );
+                }
+
+                cooldowns
+            },
+        })
+    }
+}
+
+impl From<Limits> for RatelimiterLimits {
+    fn from(limits: Limits) -> Self {
+        Self {
+            max_requests: limits.max_requests,
+            bucket_length_secs: limits.bucket_length.as_secs_f64(),
+            telegram_cooldown_secs: limits.telegram_cooldown.as_secs_f64(),
+            recruitment_cooldown_secs: limits.recruitment_cooldown.as_secs_f64(),
+            restricted_action_cooldown_secs: limits.restricted_action_cooldown.as_secs_f64(),
+            restricted_cooldowns_secs: RestrictedAction::ALL
+                .into_iter()
+                .map(|action| (action, limits.restricted_cooldown(action).as_secs_f64()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub(crate) enum Target {
-    RecruitmentTelegram { sender: String },
-    Telegram { sender: String },
-    Restricted { sender: String },
+    RecruitmentTelegram {
+        sender: String,
+    },
+    Telegram {
+        sender: String,
+    },
+    Restricted {
+        sender: String,
+        action: RestrictedAction,
+    },
     Standard,
 }
 
+/// Constructors canonicalize the sender, so every spelling of a nation shares one bucket.
 impl Target {
     pub(crate) fn recruitment(sender: &str) -> Self {
         Self::RecruitmentTelegram {
-            sender: sender.to_string(),
+            sender: name::canonicalize(sender),
         }
     }
 
     pub(crate) fn telegram(sender: &str) -> Self {
         Self::Telegram {
-            sender: sender.to_string(),
+            sender: name::canonicalize(sender),
         }
     }
 
-    pub(crate) fn restricted(sender: &str) -> Self {
+    pub(crate) fn restricted(sender: &str, action: RestrictedAction) -> Self {
         Self::Restricted {
-            sender: sender.to_string(),
+            sender: name::canonicalize(sender),
+            action,
         }
     }
 }
 
+/// Work a worker has accepted for `target` but not started, see [`Sender::reserve`].
+#[derive(Debug)]
+pub(crate) struct Reservation {
+    id: u64,
+    target: Target,
+}
+
+/// A reservation as the receiver keeps it.
+#[derive(Debug)]
+struct Reserved {
+    target: Target,
+    /// when it's dropped unless a peek finds it still has to wait
+    expires: Instant,
+}
+
 #[derive(Debug)]
 enum Action {
     Peek(Target),
+    /// like `Peek`, but behind every reservation as well
+    PeekQueued(Target),
+    /// like `Peek`, but behind the reservations made before this one
+    PeekReserved(u64, Target),
     Acquire(Target),
-    Update,
+    AcquireReserved(u64, Target),
+    Reserve(Target),
+    Cancel(u64),
+    Release(Target, Instant),
+    Inspect,
+    Configure(Limits),
+    /// NS's count of the requests from our IP in its current window, and its cap if it said
+    Observe {
+        seen: usize,
+        limit: Option<usize>,
+    },
+}
+
+/// What NS said about its window in the ratelimit headers of a response, as
+/// `X-Ratelimit-Requests-Seen` or as `RateLimit-Limit` and `RateLimit-Remaining`.
+fn observation(headers: &HeaderMap) -> Option<(usize, Option<usize>)> {
+    let number =
+        |name: &str| -> Option<usize> { headers.get(name)?.to_str().ok()?.trim().parse().ok() };
+
+    let limit = number("ratelimit-limit");
+    let seen = number("x-ratelimit-requests-seen").or_else(|| {
+        let remaining = number("ratelimit-remaining")?;
+
+        Some(limit?.saturating_sub(remaining))
+    })?;
+
+    Some((seen, limit))
+}
+
+/// The last observation, which holds for a bucket length.
+#[derive(Debug)]
+struct Observed {
+    seen: usize,
+    limit: Option<usize>,
+    at: Instant,
+    /// requests in `seen` we didn't make, from other tools sharing the IP
+    external: usize,
 }
 
 struct Command {
@@ -54,15 +242,34 @@ impl Command {
 enum Response {
     Ok,
     Peek(Duration),
-    Acquire(Result<(), Duration>),
+    Reserve(u64),
+    Acquire(Instant, Result<(), Duration>),
+    Inspect(RatelimiterStatus),
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct Sender {
     tx: mpsc::Sender<Command>,
+    limits: watch::Receiver<Limits>,
 }
 
 impl Sender {
+    pub(crate) fn telegram_cooldown(&self) -> Duration {
+        self.limits.borrow().telegram_cooldown
+    }
+
+    pub(crate) fn recruitment_cooldown(&self) -> Duration {
+        self.limits.borrow().recruitment_cooldown
+    }
+
+    pub(crate) fn restricted_action_cooldown(&self) -> Duration {
+        self.limits.borrow().restricted_action_cooldown
+    }
+
+    pub(crate) fn limits(&self) -> Limits {
+        self.limits.borrow().clone()
+    }
+
     #[tracing::instrument(skip_all)]
     pub(crate) async fn peek(&self, target: Target) -> Duration {
         let (tx, rx) = oneshot::channel();
@@ -80,6 +287,13 @@ impl Sender {
 
     #[tracing::instrument(skip_all)]
     pub(crate) async fn acquire(&self, target: Target) -> Result<(), Duration> {
+        self.acquire_slot(target).await.1
+    }
+
+    /// Like `acquire`, but also returns the instant the slot was booked for, which can later be
+    /// passed to `release` if the action turns out not to have been performed.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn acquire_slot(&self, target: Target) -> (Instant, Result<(), Duration>) {
         let (tx, rx) = oneshot::channel();
 
         if let Err(e) = self
@@ -91,7 +305,154 @@ impl Sender {
         }
 
         match rx.await {
-            Ok(Response::Acquire(result)) => result,
+            Ok(Response::Acquire(at, result)) => (at, result),
+            Ok(_) => unreachable!(),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// How long work submitted now would wait for `target`, behind what's booked and the work
+    /// workers have reserved slots for.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn peek_queued(&self, target: Target) -> Duration {
+        match self.send(Action::PeekQueued(target)).await {
+            Response::Peek(duration) => duration,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Registers work a worker has accepted for `target`. Until it's acquired with
+    /// [`Sender::acquire_reserved`] or cancelled, later reservations and
+    /// [`Sender::peek_queued`] wait behind it.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn reserve(&self, target: Target) -> Reservation {
+        match self.send(Action::Reserve(target.clone())).await {
+            Response::Reserve(id) => Reservation { id, target },
+            _ => unreachable!(),
+        }
+    }
+
+    /// How long the reserved work would wait, behind what's booked and the reservations made
+    /// before it.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn peek_reserved(&self, reservation: &Reservation) -> Duration {
+        match self
+            .send(Action::PeekReserved(
+                reservation.id,
+                reservation.target.clone(),
+            ))
+            .await
+        {
+            Response::Peek(duration) => duration,
+            _ => unreachable!(),
+        }
+    }
+
+    /// [`Sender::acquire_slot`] for the reserved work, which stops counting as reserved.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn acquire_reserved(
+        &self,
+        reservation: Reservation,
+    ) -> (Instant, Result<(), Duration>) {
+        match self
+            .send(Action::AcquireReserved(reservation.id, reservation.target))
+            .await
+        {
+            Response::Acquire(at, result) => (at, result),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Drops a reservation whose work won't run.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn cancel(&self, reservation: Reservation) {
+        match self.send(Action::Cancel(reservation.id)).await {
+            Response::Ok => (),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn send(&self, action: Action) -> Response {
+        let (tx, rx) = oneshot::channel();
+
+        if let Err(e) = self.tx.send(Command::new(action, tx)).await {
+            tracing::error!("Failed to send message: {}", e);
+        }
+
+        match rx.await {
+            Ok(response) => response,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Gives back the cooldown slot booked at `at` for `target`. Only call this when NS did not
+    /// perform the action, otherwise the next action will be sent too early.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn release(&self, target: Target, at: Instant) {
+        let (tx, rx) = oneshot::channel();
+
+        if let Err(e) = self
+            .tx
+            .send(Command::new(Action::Release(target, at), tx))
+            .await
+        {
+            tracing::error!("Failed to send message: {}", e);
+        }
+
+        match rx.await {
+            Ok(Response::Ok) => (),
+            Ok(_) => unreachable!(),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Current limits and how full the buckets are.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn inspect(&self) -> RatelimiterStatus {
+        self.request(Action::Inspect).await
+    }
+
+    /// Reports the ratelimit headers of an NS response. Requests others made from our IP then
+    /// count against the bucket as if they were ours.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn observe(&self, headers: &HeaderMap) {
+        let Some((seen, limit)) = observation(headers) else {
+            return;
+        };
+
+        let (tx, rx) = oneshot::channel();
+
+        if let Err(e) = self
+            .tx
+            .send(Command::new(Action::Observe { seen, limit }, tx))
+            .await
+        {
+            tracing::error!("Failed to send message: {}", e);
+        }
+
+        match rx.await {
+            Ok(Response::Ok) => (),
+            Ok(_) => unreachable!(),
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Applies `limits` to every calculation from now on. Requests already booked stay booked,
+    /// they're only measured against the new limits.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn configure(&self, limits: Limits) -> RatelimiterStatus {
+        self.request(Action::Configure(limits)).await
+    }
+
+    async fn request(&self, action: Action) -> RatelimiterStatus {
+        let (tx, rx) = oneshot::channel();
+
+        if let Err(e) = self.tx.send(Command::new(action, tx)).await {
+            tracing::error!("Failed to send message: {}", e);
+        }
+
+        match rx.await {
+            Ok(Response::Inspect(status)) => status,
             Ok(_) => unreachable!(),
             Err(_) => unreachable!(),
         }
@@ -100,6 +461,7 @@ impl Sender {
 
 pub(crate) struct Receiver {
     rx: mpsc::Receiver<Command>,
+    limits: watch::Sender<Limits>,
     max_requests: usize,
     bucket_length: Duration,
     requests: VecDeque<Instant>,
@@ -108,33 +470,164 @@ pub(crate) struct Receiver {
     recruitment_cooldown: Duration,
     recruitment_telegrams: VecDeque<Instant>,
     restricted_action_cooldown: Duration,
-    /// the last restrcted action performed by a given nation name, if any
-    restricted_actions: HashMap<String, VecDeque<Instant>>,
+    restricted_cooldowns: BTreeMap<RestrictedAction, Duration>,
+    /// restricted actions still cooling down per nation, by kind; telegrams count as every kind
+    restricted_actions: HashMap<String, BTreeMap<RestrictedAction, VecDeque<Instant>>>,
+    observed: Option<Observed>,
+    /// share of NS's cap observed in use at which a warning is logged
+    warn_utilization: f64,
+    /// whether the last observation was over `warn_utilization`, so a warning is only logged
+    /// when utilization crosses it
+    over_threshold: bool,
+    utilization_warnings: u64,
+    /// work accepted by workers and not yet acquired, by id, which is the order it was reserved
+    reservations: BTreeMap<u64, Reserved>,
+    next_reservation: u64,
+    reservation_grace: Duration,
+}
+
+/// The buckets' entries, put back after booking reservations to see how long they'd take.
+struct Bookings {
+    requests: VecDeque<Instant>,
+    telegrams: VecDeque<Instant>,
+    recruitment_telegrams: VecDeque<Instant>,
+    restricted_actions: HashMap<String, BTreeMap<RestrictedAction, VecDeque<Instant>>>,
 }
 
 impl Receiver {
     fn new(
         rx: mpsc::Receiver<Command>,
-        max_requests: usize,
-        bucket_length: Duration,
-        telegram_cooldown: Duration,
-        recruitment_cooldown: Duration,
-        restricted_action_cooldown: Duration,
+        limits: watch::Sender<Limits>,
+        warn_utilization: f64,
     ) -> Self {
+        let current = limits.borrow().clone();
+
         Self {
             rx,
-            max_requests,
-            bucket_length,
-            requests: VecDeque::with_capacity(max_requests),
-            telegram_cooldown,
+            limits,
+            max_requests: current.max_requests,
+            bucket_length: current.bucket_length,
+            requests: VecDeque::with_capacity(current.max_requests),
+            telegram_cooldown: current.telegram_cooldown,
             telegrams: VecDeque::new(),
-            recruitment_cooldown,
+            recruitment_cooldown: current.recruitment_cooldown,
             recruitment_telegrams: VecDeque::new(),
-            restricted_action_cooldown,
+            restricted_action_cooldown: current.restricted_action_cooldown,
+            restricted_cooldowns: current.restricted_cooldowns,
             restricted_actions: HashMap::new(),
+            observed: None,
+            warn_utilization,
+            over_threshold: false,
+            utilization_warnings: 0,
+            reservations: BTreeMap::new(),
+            next_reservation: 0,
+            reservation_grace: RESERVATION_GRACE,
+        }
+    }
+
+    /// Switches to `limits` without touching the buckets, so what was booked under the old
+    /// limits keeps counting; entries outside the new windows drop out on the next cleanup.
+    #[tracing::instrument(skip_all)]
+    fn configure(&mut self, limits: Limits) {
+        tracing::info!("reconfiguring ratelimiter: {:?}", limits);
+
+        self.max_requests = limits.max_requests;
+        self.bucket_length = limits.bucket_length;
+        self.telegram_cooldown = limits.telegram_cooldown;
+        self.recruitment_cooldown = limits.recruitment_cooldown;
+        self.restricted_action_cooldown = limits.restricted_action_cooldown;
+        self.restricted_cooldowns = limits.restricted_cooldowns.clone();
+
+        self.limits.send_replace(limits);
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn inspect(&mut self) -> RatelimiterStatus {
+        self.clean_buckets();
+
+        let limits = self.limits.borrow().clone();
+
+        RatelimiterStatus {
+            limits: limits.into(),
+            requests: self.requests.len(),
+            telegrams: self.telegrams.len(),
+            recruitment_telegrams: self.recruitment_telegrams.len(),
+            restricted_actions: self
+                .restricted_actions
+                .iter()
+                .map(|(nation, buckets)| (nation, buckets.values().map(VecDeque::len).sum()))
+                .filter(|(_, count)| *count > 0)
+                .map(|(nation, count)| (nation.clone(), count))
+                .collect::<BTreeMap<_, _>>(),
+            standard_wait_secs: self.peek_standard().as_secs_f64(),
+            observed: self.observed.as_ref().map(|observed| ObservedRequests {
+                seen: observed.seen,
+                limit: observed.limit,
+                observed_secs_ago: observed.at.elapsed().as_secs_f64(),
+            }),
+            external_requests: self.external_requests(),
+            utilization_warnings: self.utilization_warnings,
+            reservations: self.reservations.len(),
         }
     }
 
+    /// Takes in what NS counted, `seen` requests from our IP in its window. Whatever it counted
+    /// beyond the requests we sent within a bucket length is someone else's, and is held against
+    /// the bucket until the observation runs out.
+    #[tracing::instrument(skip_all)]
+    fn observe(&mut self, seen: usize, limit: Option<usize>) {
+        self.clean_buckets();
+
+        let now = Instant::now();
+        // booked requests that haven't been sent yet can't have been seen
+        let sent = self.requests.iter().filter(|at| **at <= now).count();
+
+        self.observed = Some(Observed {
+            seen,
+            limit,
+            at: now,
+            external: seen.saturating_sub(sent),
+        });
+
+        let cap = limit.unwrap_or(self.max_requests).max(1);
+        let utilization = seen as f64 / cap as f64;
+
+        if utilization >= self.warn_utilization {
+            if !self.over_threshold {
+                tracing::warn!(
+                    "NS has seen {} of {} requests from us this window, {} of them not ours",
+                    seen,
+                    cap,
+                    seen.saturating_sub(sent)
+                );
+
+                self.utilization_warnings += 1;
+            }
+
+            self.over_threshold = true;
+        } else {
+            self.over_threshold = false;
+        }
+    }
+
+    /// Requests others made within the current window, by the last observation.
+    fn external_requests(&self) -> usize {
+        self.observed
+            .as_ref()
+            .filter(|observed| observed.at.elapsed() < self.bucket_length)
+            .map_or(0, |observed| observed.external)
+    }
+
+    /// Our limit, or NS's while an observation says it's lower.
+    fn max_requests(&self) -> usize {
+        self.observed
+            .as_ref()
+            .filter(|observed| observed.at.elapsed() < self.bucket_length)
+            .and_then(|observed| observed.limit)
+            .map_or(self.max_requests, |limit| limit.min(self.max_requests))
+            .max(1)
+    }
+
     /// remove expired requests from bucket
     #[tracing::instrument(skip_all)]
     fn clean_buckets(&mut self) {
@@ -149,9 +642,27 @@ impl Receiver {
         self.recruitment_telegrams
             .retain(|v| now.duration_since(*v) < self.recruitment_cooldown);
 
-        for vec in self.restricted_actions.values_mut() {
-            vec.retain(|&request| now.duration_since(request) < self.restricted_action_cooldown);
+        for buckets in self.restricted_actions.values_mut() {
+            for (action, bucket) in buckets.iter_mut() {
+                let cooldown = self
+                    .restricted_cooldowns
+                    .get(action)
+                    .copied()
+                    .unwrap_or(self.restricted_action_cooldown);
+
+                bucket.retain(|&request| now.duration_since(request) < cooldown);
+            }
         }
+
+        self.reservations
+            .retain(|_, reserved| reserved.expires > now);
+    }
+
+    fn restricted_cooldown(&self, action: RestrictedAction) -> Duration {
+        self.restricted_cooldowns
+            .get(&action)
+            .copied()
+            .unwrap_or(self.restricted_action_cooldown)
     }
 
     #[tracing::instrument(skip_all)]
@@ -161,19 +672,22 @@ impl Receiver {
                 vec![
                     self.peek_recruitment(),
                     self.peek_telegram(),
-                    self.peek_restricted(sender),
+                    self.peek_restricted(sender, &RestrictedAction::ALL),
                     self.peek_standard(),
                 ]
             }
             Target::Telegram { sender } => {
                 vec![
                     self.peek_telegram(),
-                    self.peek_restricted(sender),
+                    self.peek_restricted(sender, &RestrictedAction::ALL),
                     self.peek_standard(),
                 ]
             }
-            Target::Restricted { sender } => {
-                vec![self.peek_restricted(sender), self.peek_standard()]
+            Target::Restricted { sender, action } => {
+                vec![
+                    self.peek_restricted(sender, &[*action]),
+                    self.peek_standard(),
+                ]
             }
             Target::Standard => {
                 vec![self.peek_standard()]
@@ -220,43 +734,71 @@ impl Receiver {
         }
     }
 
-    /// Naive method to check when the next restricted action can be performed by a given nation.
-    /// In this context, naive means that it does not take into account other limits that may
-    /// prevent a restricted action from being performed (e.g. the standard rate limit).
+    /// Naive method to check when a given nation can next perform a restricted action of each of
+    /// `actions`, the longest of those waits. In this context, naive means that it does not take
+    /// into account other limits that may prevent a restricted action from being performed (e.g.
+    /// the standard rate limit).
     #[tracing::instrument(skip_all)]
-    fn peek_restricted(&mut self, sender: &str) -> Duration {
+    fn peek_restricted(&mut self, sender: &str, actions: &[RestrictedAction]) -> Duration {
         self.clean_buckets();
 
-        if let Some(bucket) = self.restricted_actions.get(sender) {
-            if bucket.is_empty() {
-                Duration::ZERO
-            } else {
-                self.restricted_action_cooldown
-                    .mul(bucket.len() as u32)
-                    .saturating_sub(
-                        Instant::now().saturating_duration_since(*bucket.front().unwrap()),
-                    )
-            }
-        } else {
-            Duration::ZERO
+        let Some(buckets) = self.restricted_actions.get(sender) else {
+            return Duration::ZERO;
+        };
+
+        actions
+            .iter()
+            .filter_map(|action| {
+                let bucket = buckets.get(action)?;
+
+                Some(
+                    self.restricted_cooldown(*action)
+                        .mul(bucket.len() as u32)
+                        .saturating_sub(Instant::now().saturating_duration_since(*bucket.front()?)),
+                )
+            })
+            .max()
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Books `at` in the buckets of `actions` for `sender`.
+    fn book_restricted(&mut self, sender: &str, actions: &[RestrictedAction], at: Instant) {
+        let buckets = self
+            .restricted_actions
+            .entry(sender.to_string())
+            .or_default();
+
+        for action in actions {
+            buckets.entry(*action).or_default().push_back(at);
         }
     }
 
+    /// Requests observed from others count as sent when they were observed.
     #[tracing::instrument(skip_all)]
     fn peek_standard(&mut self) -> Duration {
         self.clean_buckets();
 
-        if self.requests.is_empty() {
-            Duration::ZERO
-        } else {
-            self.bucket_length
-                .mul((self.requests.len() / self.max_requests) as u32)
-                .saturating_sub(Instant::now().duration_since(*self.requests.front().unwrap()))
-        }
+        let external = self.external_requests();
+
+        let oldest = match (self.requests.front(), &self.observed) {
+            (Some(front), Some(observed)) if external > 0 => *front.min(&observed.at),
+            (Some(front), _) => *front,
+            (None, Some(observed)) if external > 0 => observed.at,
+            (None, _) => return Duration::ZERO,
+        };
+
+        self.bucket_length
+            .mul(((self.requests.len() + external) / self.max_requests()) as u32)
+            .saturating_sub(Instant::now().saturating_duration_since(oldest))
     }
 
     #[tracing::instrument(skip_all)]
     fn acquire(&mut self, target: Target) -> Result<(), Duration> {
+        self.acquire_slot(target).1
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn acquire_slot(&mut self, target: Target) -> (Instant, Result<(), Duration>) {
         let wait = self.peek(&target);
 
         let request_at = Instant::now().add(wait);
@@ -267,28 +809,19 @@ impl Receiver {
 
                 self.telegrams.push_back(request_at);
 
-                self.restricted_actions
-                    .entry(sender.to_string())
-                    .or_default()
-                    .push_back(request_at);
+                self.book_restricted(&sender, &RestrictedAction::ALL, request_at);
 
                 self.requests.push_back(request_at);
             }
             Target::Telegram { sender } => {
                 self.telegrams.push_back(request_at);
 
-                self.restricted_actions
-                    .entry(sender.to_string())
-                    .or_default()
-                    .push_back(request_at);
+                self.book_restricted(&sender, &RestrictedAction::ALL, request_at);
 
                 self.requests.push_back(request_at);
             }
-            Target::Restricted { sender } => {
-                self.restricted_actions
-                    .entry(sender.to_string())
-                    .or_default()
-                    .push_back(request_at);
+            Target::Restricted { sender, action } => {
+                self.book_restricted(&sender, &[action], request_at);
 
                 self.requests.push_back(request_at);
             }
@@ -298,9 +831,130 @@ impl Receiver {
         }
 
         if let Duration::ZERO = wait {
-            Ok(())
+            (request_at, Ok(()))
         } else {
-            Err(wait)
+            (request_at, Err(wait))
+        }
+    }
+
+    fn bookings(&self) -> Bookings {
+        Bookings {
+            requests: self.requests.clone(),
+            telegrams: self.telegrams.clone(),
+            recruitment_telegrams: self.recruitment_telegrams.clone(),
+            restricted_actions: self.restricted_actions.clone(),
+        }
+    }
+
+    fn restore(&mut self, bookings: Bookings) {
+        self.requests = bookings.requests;
+        self.telegrams = bookings.telegrams;
+        self.recruitment_telegrams = bookings.recruitment_telegrams;
+        self.restricted_actions = bookings.restricted_actions;
+    }
+
+    /// How long `target` would wait if the reservations before `before`, all of them if `None`,
+    /// were acquired first, in the order they were made.
+    #[tracing::instrument(skip_all)]
+    fn peek_behind(&mut self, target: &Target, before: Option<u64>) -> Duration {
+        self.clean_buckets();
+
+        let ahead = self
+            .reservations
+            .range(..before.unwrap_or(u64::MAX))
+            .map(|(_, reserved)| reserved.target.clone())
+            .collect::<Vec<_>>();
+
+        if ahead.is_empty() {
+            return self.peek(target);
+        }
+
+        let bookings = self.bookings();
+
+        for reserved in ahead {
+            let _ = self.acquire_slot(reserved);
+        }
+
+        let wait = self.peek(target);
+
+        self.restore(bookings);
+
+        wait
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn reserve(&mut self, target: Target) -> u64 {
+        let wait = self.peek_behind(&target, None);
+
+        let id = self.next_reservation;
+        self.next_reservation += 1;
+
+        self.reservations.insert(
+            id,
+            Reserved {
+                target,
+                expires: Instant::now() + wait + self.reservation_grace,
+            },
+        );
+
+        id
+    }
+
+    /// A reservation still waiting is kept for the grace period past its new expected start.
+    #[tracing::instrument(skip_all)]
+    fn peek_reserved(&mut self, id: u64, target: &Target) -> Duration {
+        let wait = self.peek_behind(target, Some(id));
+
+        if let Some(reserved) = self.reservations.get_mut(&id) {
+            reserved.expires = reserved
+                .expires
+                .max(Instant::now() + wait + self.reservation_grace);
+        }
+
+        wait
+    }
+
+    /// Acquires `target` in place of reservation `id`. One that expired meanwhile is acquired
+    /// all the same.
+    #[tracing::instrument(skip_all)]
+    fn acquire_reserved(&mut self, id: u64, target: Target) -> (Instant, Result<(), Duration>) {
+        self.reservations.remove(&id);
+
+        self.acquire_slot(target)
+    }
+
+    /// Removes the cooldown entries booked at `at` for `target`. The standard request bucket is
+    /// left untouched, as the HTTP request that failed still counts against the NS ratelimit.
+    #[tracing::instrument(skip_all)]
+    fn release(&mut self, target: Target, at: Instant) {
+        fn remove(bucket: &mut VecDeque<Instant>, at: Instant) {
+            if let Some(index) = bucket.iter().position(|request| *request == at) {
+                bucket.remove(index);
+            }
+        }
+
+        let (sender, actions) = match target {
+            Target::RecruitmentTelegram { sender } => {
+                remove(&mut self.recruitment_telegrams, at);
+                remove(&mut self.telegrams, at);
+
+                (sender, RestrictedAction::ALL.to_vec())
+            }
+            Target::Telegram { sender } => {
+                remove(&mut self.telegrams, at);
+
+                (sender, RestrictedAction::ALL.to_vec())
+            }
+            Target::Restricted { sender, action } => (sender, vec![action]),
+            Target::Standard => return,
+        };
+
+        if let Some(buckets) = self.restricted_actions.get_mut(&sender) {
+            for action in actions {
+                if let Some(bucket) = buckets.get_mut(&action) {
+                    remove(bucket, at);
+                }
+            }
         }
     }
 
@@ -308,8 +962,34 @@ impl Receiver {
     fn process(&mut self, action: Action) -> Result<Response, Error> {
         match action {
             Action::Peek(target) => Ok(Response::Peek(self.peek(&target))),
-            Action::Acquire(target) => Ok(Response::Acquire(self.acquire(target))),
-            _ => Ok(Response::Ok),
+            Action::PeekQueued(target) => Ok(Response::Peek(self.peek_behind(&target, None))),
+            Action::PeekReserved(id, target) => Ok(Response::Peek(self.peek_reserved(id, &target))),
+            Action::Acquire(target) => {
+                let (at, result) = self.acquire_slot(target);
+                Ok(Response::Acquire(at, result))
+            }
+            Action::AcquireReserved(id, target) => {
+                let (at, result) = self.acquire_reserved(id, target);
+                Ok(Response::Acquire(at, result))
+            }
+            Action::Reserve(target) => Ok(Response::Reserve(self.reserve(target))),
+            Action::Cancel(id) => {
+                self.reservations.remove(&id);
+                Ok(Response::Ok)
+            }
+            Action::Release(target, at) => {
+                self.release(target, at);
+                Ok(Response::Ok)
+            }
+            Action::Inspect => Ok(Response::Inspect(self.inspect())),
+            Action::Configure(limits) => {
+                self.configure(limits);
+                Ok(Response::Inspect(self.inspect()))
+            }
+            Action::Observe { seen, limit } => {
+                self.observe(seen, limit);
+                Ok(Response::Ok)
+            }
         }
     }
 
@@ -339,19 +1019,26 @@ pub(crate) fn new(
     telegram_cooldown: Duration,
     recruitment_cooldown: Duration,
     restricted_action_cooldown: Duration,
+    restricted_cooldowns: BTreeMap<RestrictedAction, Duration>,
+    warn_utilization: f64,
 ) -> Sender {
     let (tx, rx) = mpsc::channel(16);
 
-    let sender = Sender { tx };
-
-    let mut receiver = Receiver::new(
-        rx,
+    let (limits, limits_rx) = watch::channel(Limits {
         max_requests,
         bucket_length,
         telegram_cooldown,
         recruitment_cooldown,
         restricted_action_cooldown,
-    );
+        restricted_cooldowns,
+    });
+
+    let sender = Sender {
+        tx,
+        limits: limits_rx,
+    };
+
+    let mut receiver = Receiver::new(rx, limits, warn_utilization);
 
     tokio::task::spawn(async move {
         receiver.run().await;
@@ -368,11 +1055,19 @@ mod tests {
     fn make_receiver() -> Receiver {
         Receiver::new(
             mpsc::channel(1).1,
-            2,
-            Duration::from_secs(10),
-            Duration::from_secs(5),
-            Duration::from_secs(15),
-            Duration::from_secs(20),
+            watch::channel(Limits {
+                max_requests: 2,
+                bucket_length: Duration::from_secs(10),
+                telegram_cooldown: Duration::from_secs(5),
+                recruitment_cooldown: Duration::from_secs(15),
+                restricted_action_cooldown: Duration::from_secs(20),
+                restricted_cooldowns: BTreeMap::from([(
+                    RestrictedAction::RmbPost,
+                    Duration::from_secs(8),
+                )]),
+            })
+            .0,
+            0.8,
         )
     }
 
@@ -414,6 +1109,32 @@ mod tests {
         assert!(wait >= Duration::from_secs(4));
     }
 
+    #[test]
+    fn test_restricted_spellings_share_bucket() {
+        let mut limiter = make_receiver();
+
+        assert_eq!(
+            limiter.acquire(Target::restricted(
+                "UPPER CANADAN  EMPIRE",
+                RestrictedAction::Dispatch
+            )),
+            Ok(())
+        );
+
+        for variant in [
+            "upper_canadan_empire",
+            "Upper Canadan Empire",
+            " upper__Canadan empire ",
+        ] {
+            assert!(
+                limiter.peek(&Target::restricted(variant, RestrictedAction::Dispatch))
+                    >= Duration::from_secs(14),
+                "{:?} got its own bucket",
+                variant
+            );
+        }
+    }
+
     #[test]
     fn test_recruitment_telegram_peek_and_acquire() {
         let mut limiter = make_receiver();
@@ -439,14 +1160,497 @@ mod tests {
 
         assert_eq!(
             limiter.acquire(Target::Restricted {
-                sender: sender.clone()
+                sender: sender.clone(),
+                action: RestrictedAction::Dispatch,
             }),
             Ok(())
         );
 
         let wait = limiter.peek(&Target::Restricted {
             sender: sender.clone(),
+            action: RestrictedAction::Dispatch,
         });
         assert!(wait >= Duration::from_secs(19));
     }
+
+    #[test]
+    fn test_restricted_actions_cool_down_per_kind() {
+        let mut limiter = make_receiver();
+        let limits = limiter.limits.borrow().clone();
+
+        // room for every request, so only the cooldowns are measured
+        limiter.configure(Limits {
+            max_requests: 10,
+            ..limits
+        });
+        let dispatch = Target::restricted("nation", RestrictedAction::Dispatch);
+        let rmbpost = Target::restricted("nation", RestrictedAction::RmbPost);
+
+        assert_eq!(limiter.acquire(dispatch.clone()), Ok(()));
+
+        // a dispatch doesn't hold an RMB post back
+        assert_eq!(limiter.peek(&rmbpost), Duration::ZERO);
+        assert_eq!(limiter.acquire(rmbpost.clone()), Ok(()));
+
+        // both cool down at once, each by its own cooldown
+        let dispatch_wait = limiter.peek(&dispatch);
+        let rmbpost_wait = limiter.peek(&rmbpost);
+        assert!(
+            dispatch_wait > Duration::from_secs(19) && dispatch_wait <= Duration::from_secs(20)
+        );
+        assert!(rmbpost_wait > Duration::from_secs(7) && rmbpost_wait <= Duration::from_secs(8));
+
+        // a second RMB post queues behind the first
+        assert!(limiter.acquire(rmbpost.clone()).is_err());
+        assert!(limiter.peek(&rmbpost) > Duration::from_secs(15));
+        assert!(limiter.peek(&dispatch) <= Duration::from_secs(20));
+
+        // telegrams wait for every kind, the longest applicable wait
+        assert!(limiter.peek(&Target::telegram("nation")) > Duration::from_secs(15));
+        assert_eq!(
+            limiter.peek(&Target::telegram("other_nation")),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_telegram_holds_every_restricted_kind() {
+        let mut limiter = make_receiver();
+
+        assert_eq!(limiter.acquire(Target::telegram("nation")), Ok(()));
+
+        assert!(
+            limiter.peek(&Target::restricted("nation", RestrictedAction::Dispatch))
+                > Duration::from_secs(19)
+        );
+        assert!(
+            limiter.peek(&Target::restricted("nation", RestrictedAction::RmbPost))
+                > Duration::from_secs(7)
+        );
+    }
+
+    #[test]
+    fn test_reservations_hold_back_queued_peeks() {
+        let mut limiter = make_receiver();
+        let limits = limiter.limits.borrow().clone();
+
+        limiter.configure(Limits {
+            max_requests: 10,
+            ..limits
+        });
+        let dispatch = Target::restricted("nation", RestrictedAction::Dispatch);
+
+        let first = limiter.reserve(dispatch.clone());
+        let second = limiter.reserve(dispatch.clone());
+        let third = limiter.reserve(dispatch.clone());
+
+        // nothing has been sent, so plain peeks aren't held back
+        assert_eq!(limiter.peek(&dispatch), Duration::ZERO);
+
+        // a new job queues behind all three
+        let wait = limiter.peek_behind(&dispatch, None);
+        assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60));
+
+        // each reservation only waits for those made before it
+        assert_eq!(limiter.peek_reserved(first, &dispatch), Duration::ZERO);
+        let wait = limiter.peek_reserved(third, &dispatch);
+        assert!(wait > Duration::from_secs(39) && wait <= Duration::from_secs(40));
+
+        // the simulation leaves no entries behind
+        assert!(!limiter.restricted_actions.contains_key("nation"));
+        assert!(limiter.requests.is_empty());
+
+        // another nation's reservations don't count
+        assert_eq!(
+            limiter.peek_behind(
+                &Target::restricted("other_nation", RestrictedAction::Dispatch),
+                None
+            ),
+            Duration::ZERO
+        );
+
+        // a cancelled job no longer holds the later ones back
+        assert!(matches!(
+            limiter.process(Action::Cancel(second)),
+            Ok(Response::Ok)
+        ));
+        let wait = limiter.peek_reserved(third, &dispatch);
+        assert!(wait > Duration::from_secs(19) && wait <= Duration::from_secs(20));
+        assert_eq!(limiter.inspect().reservations, 2);
+    }
+
+    #[test]
+    fn test_acquire_reserved() {
+        let mut limiter = make_receiver();
+        let dispatch = Target::restricted("nation", RestrictedAction::Dispatch);
+
+        let first = limiter.reserve(dispatch.clone());
+        let second = limiter.reserve(dispatch.clone());
+
+        let (_, result) = limiter.acquire_reserved(first, dispatch.clone());
+        assert_eq!(result, Ok(()));
+
+        // the reservation became an actual entry, counted once
+        assert_eq!(limiter.reservations.len(), 1);
+        assert_eq!(
+            limiter.restricted_actions["nation"][&RestrictedAction::Dispatch].len(),
+            1
+        );
+        let wait = limiter.peek_reserved(second, &dispatch);
+        assert!(wait > Duration::from_secs(19) && wait <= Duration::from_secs(20));
+        let wait = limiter.peek_behind(&dispatch, None);
+        assert!(wait > Duration::from_secs(39) && wait <= Duration::from_secs(40));
+    }
+
+    #[test]
+    fn test_reservations_expire() {
+        let mut limiter = make_receiver();
+        limiter.reservation_grace = Duration::ZERO;
+        let dispatch = Target::restricted("nation", RestrictedAction::Dispatch);
+
+        // the first starts at once, so with no grace it's already expired
+        limiter.reserve(dispatch.clone());
+        assert_eq!(limiter.peek_behind(&dispatch, None), Duration::ZERO);
+        assert_eq!(limiter.inspect().reservations, 0);
+
+        limiter.reservation_grace = Duration::from_secs(60);
+        limiter.reserve(dispatch.clone());
+        assert!(limiter.peek_behind(&dispatch, None) > Duration::from_secs(19));
+        assert_eq!(limiter.inspect().reservations, 1);
+    }
+
+    #[test]
+    fn test_release_restricted_action() {
+        let mut limiter = make_receiver();
+        let target = Target::restricted("nation", RestrictedAction::Dispatch);
+
+        let (at, result) = limiter.acquire_slot(target.clone());
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            limiter.restricted_actions["nation"][&RestrictedAction::Dispatch].len(),
+            1
+        );
+        assert!(limiter.peek(&target) >= Duration::from_secs(19));
+
+        limiter.release(target.clone(), at);
+
+        assert!(limiter.restricted_actions["nation"][&RestrictedAction::Dispatch].is_empty());
+        assert_eq!(limiter.peek(&target), Duration::ZERO);
+        // the HTTP request itself still happened
+        assert_eq!(limiter.requests.len(), 1);
+    }
+
+    #[test]
+    fn test_release_only_removes_matching_slot() {
+        let mut limiter = make_receiver();
+        let target = Target::restricted("nation", RestrictedAction::Dispatch);
+
+        let (first, _) = limiter.acquire_slot(target.clone());
+        let (second, result) = limiter.acquire_slot(target.clone());
+        assert!(result.is_err());
+        assert_eq!(
+            limiter.restricted_actions["nation"][&RestrictedAction::Dispatch].len(),
+            2
+        );
+
+        limiter.release(target.clone(), second);
+
+        assert_eq!(
+            limiter.restricted_actions["nation"][&RestrictedAction::Dispatch].len(),
+            1
+        );
+        assert_eq!(
+            limiter.restricted_actions["nation"][&RestrictedAction::Dispatch][0],
+            first
+        );
+
+        // releasing a slot that was never booked is a no-op
+        limiter.release(target.clone(), second);
+        limiter.release(
+            Target::restricted("other_nation", RestrictedAction::Dispatch),
+            first,
+        );
+        assert_eq!(
+            limiter.restricted_actions["nation"][&RestrictedAction::Dispatch].len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_release_recruitment_telegram() {
+        let mut limiter = make_receiver();
+        let target = Target::recruitment("recruiter");
+
+        let (at, _) = limiter.acquire_slot(target.clone());
+        assert_eq!(limiter.recruitment_telegrams.len(), 1);
+        assert_eq!(limiter.telegrams.len(), 1);
+
+        limiter.release(target.clone(), at);
+
+        assert!(limiter.recruitment_telegrams.is_empty());
+        assert!(limiter.telegrams.is_empty());
+        assert!(
+            limiter.restricted_actions["recruiter"]
+                .values()
+                .all(VecDeque::is_empty)
+        );
+        assert_eq!(limiter.peek(&target), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_configure_max_requests_mid_bucket() {
+        let mut limiter = make_receiver();
+
+        assert_eq!(limiter.acquire(Target::Standard), Ok(()));
+        assert_eq!(limiter.peek(&Target::Standard), Duration::ZERO);
+
+        let limits = limiter.limits.borrow().clone();
+
+        limiter.configure(Limits {
+            max_requests: 1,
+
+            ..limits
+        });
+
+        // the request booked under the old limit fills the smaller bucket
+        assert_eq!(limiter.requests.len(), 1);
+        assert!(limiter.peek(&Target::Standard) >= Duration::from_secs(9));
+
+        let limits = limiter.limits.borrow().clone();
+
+        limiter.configure(Limits {
+            max_requests: 3,
+
+            ..limits
+        });
+
+        assert_eq!(limiter.peek(&Target::Standard), Duration::ZERO);
+        assert_eq!(limiter.acquire(Target::Standard), Ok(()));
+        assert_eq!(limiter.requests.len(), 2);
+    }
+
+    #[test]
+    fn test_configure_restricted_cooldown_mid_bucket() {
+        let mut limiter = make_receiver();
+        let target = Target::restricted("nation", RestrictedAction::Dispatch);
+
+        assert_eq!(limiter.acquire(target.clone()), Ok(()));
+        assert!(limiter.peek(&target) >= Duration::from_secs(19));
+
+        let limits = limiter.limits.borrow().clone();
+
+        limiter.configure(Limits {
+            restricted_action_cooldown: Duration::from_secs(5),
+
+            ..limits
+        });
+
+        let wait = limiter.peek(&target);
+        assert!(wait >= Duration::from_secs(4) && wait <= Duration::from_secs(5));
+
+        // still booked, so lengthening the cooldown again applies to it
+        let limits = limiter.limits.borrow().clone();
+        limiter.configure(Limits {
+            restricted_action_cooldown: Duration::from_secs(60),
+            ..limits
+        });
+
+        assert_eq!(
+            limiter.restricted_actions["nation"][&RestrictedAction::Dispatch].len(),
+            1
+        );
+        assert!(limiter.peek(&target) >= Duration::from_secs(59));
+    }
+
+    #[test]
+    fn test_inspect() {
+        let mut limiter = make_receiver();
+
+        limiter
+            .acquire(Target::restricted("nation", RestrictedAction::Dispatch))
+            .unwrap();
+        limiter.acquire(Target::Standard).unwrap();
+
+        let status = limiter.inspect();
+
+        assert_eq!(status.limits.max_requests, 2);
+        assert_eq!(status.limits.restricted_action_cooldown_secs, 20.0);
+        assert_eq!(status.requests, 2);
+        assert_eq!(status.telegrams, 0);
+        assert_eq!(status.restricted_actions.get("nation"), Some(&1));
+        assert!(status.standard_wait_secs > 9.0);
+    }
+
+    #[test]
+    fn test_observation() {
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+                .collect::<HeaderMap>()
+        };
+
+        assert_eq!(
+            observation(&headers(&[("X-Ratelimit-Requests-Seen", "12")])),
+            Some((12, None))
+        );
+        assert_eq!(
+            observation(&headers(&[
+                ("RateLimit-Limit", "50"),
+                ("RateLimit-Remaining", "38")
+            ])),
+            Some((12, Some(50)))
+        );
+        assert_eq!(observation(&headers(&[("RateLimit-Limit", "50")])), None);
+        assert_eq!(
+            observation(&headers(&[("X-Ratelimit-Requests-Seen", "many")])),
+            None
+        );
+        assert_eq!(observation(&HeaderMap::new()), None);
+    }
+
+    /// NS counting requests we didn't make leaves less of the bucket for ours.
+    #[test]
+    fn test_observed_requests_shrink_budget() {
+        let mut limiter = make_receiver();
+
+        assert_eq!(limiter.acquire(Target::Standard), Ok(()));
+        // our own count alone would let the second request go right away
+        assert_eq!(limiter.peek(&Target::Standard), Duration::ZERO);
+
+        limiter.observe(2, None);
+
+        assert_eq!(limiter.external_requests(), 1);
+        assert!(limiter.peek(&Target::Standard) >= Duration::from_secs(9));
+        assert!(limiter.acquire(Target::Standard).is_err());
+
+        // an observation only counting what we sent changes nothing
+        let mut limiter = make_receiver();
+
+        assert_eq!(limiter.acquire(Target::Standard), Ok(()));
+        limiter.observe(1, None);
+
+        assert_eq!(limiter.external_requests(), 0);
+        assert_eq!(limiter.peek(&Target::Standard), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_observed_limit_below_ours() {
+        let mut limiter = make_receiver();
+
+        limiter.observe(0, Some(1));
+        assert_eq!(limiter.acquire(Target::Standard), Ok(()));
+        assert!(limiter.peek(&Target::Standard) >= Duration::from_secs(9));
+    }
+
+    #[test]
+    fn test_utilization_warnings() {
+        let mut limiter = make_receiver();
+
+        limiter.observe(10, Some(50));
+        assert_eq!(limiter.utilization_warnings, 0);
+
+        // warned once per crossing, not on every observation above the threshold
+        limiter.observe(40, Some(50));
+        limiter.observe(45, Some(50));
+        assert_eq!(limiter.utilization_warnings, 1);
+
+        limiter.observe(5, Some(50));
+        limiter.observe(41, Some(50));
+        assert_eq!(limiter.utilization_warnings, 2);
+
+        let status = limiter.inspect();
+        assert_eq!(status.utilization_warnings, 2);
+        assert_eq!(status.observed.as_ref().unwrap().seen, 41);
+        assert_eq!(status.observed.unwrap().limit, Some(50));
+        assert_eq!(status.external_requests, 41);
+    }
+
+    #[tokio::test]
+    async fn test_configure_updates_sender() {
+        let sender = new(
+            2,
+            Duration::from_secs(10),
+            Duration::from_secs(5),
+            Duration::from_secs(15),
+            Duration::from_secs(20),
+            BTreeMap::new(),
+            0.8,
+        );
+
+        let limits = sender
+            .limits()
+            .patched(&RatelimiterPatch {
+                telegram_cooldown_secs: Some(7.5),
+                restricted_action_cooldown_secs: Some(40.0),
+                restricted_cooldowns_secs: Some(BTreeMap::from([(
+                    RestrictedAction::RmbPost,
+                    10.0,
+                )])),
+                ..RatelimiterPatch::default()
+            })
+            .unwrap();
+
+        let status = sender.configure(limits).await;
+
+        assert_eq!(status.limits.telegram_cooldown_secs, 7.5);
+        assert_eq!(status.limits.max_requests, 2);
+        assert_eq!(sender.telegram_cooldown(), Duration::from_millis(7500));
+        assert_eq!(sender.restricted_action_cooldown(), Duration::from_secs(40));
+        assert_eq!(
+            sender
+                .limits()
+                .restricted_cooldown(RestrictedAction::Dispatch),
+            Duration::from_secs(40)
+        );
+        assert_eq!(
+            sender
+                .limits()
+                .restricted_cooldown(RestrictedAction::RmbPost),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            status.limits.restricted_cooldowns_secs[&RestrictedAction::RmbPost],
+            10.0
+        );
+    }
+
+    #[test]
+    fn test_patch_rejects_nonsense() {
+        let limits = make_receiver().limits.borrow().clone();
+
+        for patch in [
+            RatelimiterPatch {
+                max_requests: Some(0),
+                ..RatelimiterPatch::default()
+            },
+            RatelimiterPatch {
+                bucket_length_secs: Some(0.0),
+                ..RatelimiterPatch::default()
+            },
+            RatelimiterPatch {
+                telegram_cooldown_secs: Some(-1.0),
+                ..RatelimiterPatch::default()
+            },
+            RatelimiterPatch {
+                recruitment_cooldown_secs: Some(f64::NAN),
+                ..RatelimiterPatch::default()
+            },
+            RatelimiterPatch {
+                restricted_action_cooldown_secs: Some(1e9),
+                ..RatelimiterPatch::default()
+            },
+        ] {
+            assert!(
+                matches!(limits.patched(&patch), Err(Error::InvalidRatelimit(_))),
+                "{:?} was accepted",
+                patch
+            );
+        }
+
+        assert_eq!(
+            limits.patched(&RatelimiterPatch::default()).unwrap(),
+            limits
+        );
+    }
 }
@@ -0,0 +1,117 @@
+//! The type NS gives each telegram template. NS flags recruitment telegrams on the template, so a
+//! template queued as standard can still be held to the recruitment cooldown. NS only says so by
+//! refusing a send, and the type it revealed is recorded, by telegram id, in `telegram_types`.
+
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::core::error::Error;
+use crate::ns::telegram::TgType;
+
+#[derive(Clone, Debug)]
+pub(crate) struct TelegramTypes {
+    pool: PgPool,
+    /// every telegram id looked up so far, `None` for those NS hasn't revealed a type for
+    cache: Arc<Mutex<HashMap<String, Option<TgType>>>>,
+}
+
+impl TelegramTypes {
+    pub(crate) fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The recorded types of `telegram_ids`, for those that have one. Ids that aren't cached
+    /// are looked up once, whether or not there's a record of them.
+    pub(crate) async fn recorded<'a>(
+        &self,
+        telegram_ids: impl IntoIterator<Item = &'a str>,
+    ) -> Result<HashMap<String, TgType>, Error> {
+        let mut recorded = HashMap::new();
+        let mut missing = Vec::new();
+
+        {
+            let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+
+            for telegram_id in telegram_ids {
+                match cache.get(telegram_id) {
+                    Some(Some(tg_type)) => {
+                        recorded.insert(telegram_id.to_string(), tg_type.clone());
+                    }
+                    Some(None) => {}
+                    None => missing.push(telegram_id.to_string()),
+                }
+            }
+        }
+
+        if missing.is_empty() {
+            return Ok(recorded);
+        }
+
+        let found: HashMap<String, TgType> = sqlx::query(
+            "SELECT telegram_id, tg_type FROM telegram_types WHERE telegram_id = ANY($1);",
+        )
+        .bind(&missing)
+        .map(|row: PgRow| {
+            (
+                row.get("telegram_id"),
+                TgType::from_column(row.get("tg_type")),
+            )
+        })
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .collect();
+
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for telegram_id in missing {
+            let tg_type = found.get(&telegram_id).cloned();
+
+            if let Some(tg_type) = &tg_type {
+                recorded.insert(telegram_id.clone(), tg_type.clone());
+            }
+
+            // a record made meanwhile is already in the cache and mustn't be overwritten
+            cache.entry(telegram_id).or_insert(tg_type);
+        }
+
+        Ok(recorded)
+    }
+
+    /// The recorded type of `telegram_id`, if it's been looked up before.
+    pub(crate) fn cached(&self, telegram_id: &str) -> Option<TgType> {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(telegram_id)
+            .cloned()
+            .flatten()
+    }
+
+    /// Records that NS treats `telegram_id` as a `tg_type` telegram.
+    pub(crate) async fn record(&self, telegram_id: &str, tg_type: TgType) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO telegram_types (telegram_id, tg_type, recorded_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (telegram_id) DO UPDATE
+            SET tg_type = EXCLUDED.tg_type, recorded_at = EXCLUDED.recorded_at;",
+        )
+        .bind(telegram_id)
+        .bind(tg_type.to_string())
+        .bind(chrono::Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(telegram_id.to_string(), Some(tg_type));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+use axum::Router;
+use axum::body::Body;
+use axum::extract::connect_info::MockConnectInfo;
+use axum::http::{HeaderMap, Method, Request, StatusCode, header};
+use config::Config;
+use serde_json::Value;
+use sqlx::PgPool;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use tower::ServiceExt;
+
+use super::ns::{MockNs, PASSWORD};
+use crate::core::config::Args;
+use crate::core::db::DbPools;
+use crate::sync::{events, ratelimiter};
+use crate::{Application, ConfigError};
+
+/// The nation every pipeline posts as.
+pub(crate) const NATION: &str = "testlandia";
+
+/// Short enough that tests don't wait, long enough for the scheduling to still matter.
+const COOLDOWN: Duration = Duration::from_millis(100);
+
+/// The full router with its workers, on a database of its own and pointed at a [`MockNs`].
+pub(crate) struct TestApp {
+    pub(crate) router: Router,
+    pub(crate) pool: PgPool,
+    /// the app's read pool, on the same database; it connects lazily, so its size shows whether
+    /// a query was sent to it
+    pub(crate) read: PgPool,
+    pub(crate) events: events::Sender,
+    pub(crate) ns: MockNs,
+    admin: PgPool,
+    database: String,
+}
+
+impl TestApp {
+    /// Creates a database on the server `EUROCORE_TEST_DATABASE_URL` points at and boots the app
+    /// on it. Returns `None` when the variable isn't set, so tests needing Postgres are skipped.
+    pub(crate) async fn start() -> Option<Self> {
+        Self::start_with(&[]).await
+    }
+
+    /// [`TestApp::start`] with some config keys set, e.g. `("public_read", "false")`.
+    pub(crate) async fn start_with(overrides: &[(&str, &str)]) -> Option<Self> {
+        let url = std::env::var("EUROCORE_TEST_DATABASE_URL").ok()?;
+        let options = PgConnectOptions::from_str(&url).unwrap();
+
+        let admin = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(options.clone())
+            .await
+            .unwrap();
+
+        let database = format!("eurocore_test_{}", rand::random_range(0..u32::MAX));
+
+        sqlx::query(&format!("CREATE DATABASE {database};"))
+            .execute(&admin)
+            .await
+            .unwrap();
+
+        let options = options.database(&database);
+
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect_with(options.clone())
+            .await
+            .unwrap();
+
+        let read = PgPoolOptions::new()
+            .max_connections(5)
+            .connect_lazy_with(options);
+
+        let ns = MockNs::start().await;
+        let events = events::Sender::new();
+
+        let router = crate::app(
+            config(ns.url(), overrides),
+            DbPools::new(pool.clone(), Some(read.clone())),
+            events.clone(),
+            limiter(),
+        )
+        .await
+        .unwrap()
+        .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0))));
+
+        Some(Self {
+            router,
+            pool,
+            read,
+            events,
+            ns,
+            admin,
+            database,
+        })
+    }
+
+    /// Another app on the same database and mock NS, with its listener bound to a free port.
+    pub(crate) async fn application(&self) -> Application {
+        self.application_with(&[("bind_address", "127.0.0.1")])
+            .await
+            .unwrap()
+    }
+
+    /// Like [`TestApp::application`], with `overrides` on top of the test config.
+    pub(crate) async fn application_with(
+        &self,
+        overrides: &[(&str, &str)],
+    ) -> Result<Application, ConfigError> {
+        Application::new(
+            config(self.ns.url(), overrides),
+            DbPools::new(self.pool.clone(), Some(self.read.clone())),
+            events::Sender::new(),
+            limiter(),
+        )
+        .await
+    }
+
+    /// Serves the router on a free local port and returns its base URL, for clients that talk
+    /// HTTP rather than calling the router directly.
+    #[cfg(feature = "client")]
+    pub(crate) async fn serve(&self) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = self.router.clone();
+
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        format!("http://{address}")
+    }
+
+    /// The config the app was started with, without its overrides.
+    pub(crate) fn config(&self) -> Args {
+        config(self.ns.url(), &[])
+    }
+
+    /// Drops the database. Not done on drop, so a failed test leaves its data behind to look at.
+    pub(crate) async fn close(self) {
+        self.pool.close().await;
+        self.read.close().await;
+
+        sqlx::query(&format!(
+            "DROP DATABASE IF EXISTS {} WITH (FORCE);",
+            self.database
+        ))
+        .execute(&self.admin)
+        .await
+        .unwrap();
+    }
+
+    /// Registers a user holding `claims` and returns their username and token.
+    pub(crate) async fn user(&self, claims: &[&str]) -> (String, String) {
+        let username = format!("user{}", rand::random_range(0..u32::MAX));
+        let credentials = serde_json::json!({ "username": username, "password": "password123" });
+
+        let (status, _) = self
+            .send(Method::POST, "/register", None, credentials.clone())
+            .await;
+        assert!(status.is_success(), "register returned {status}");
+
+        for claim in claims {
+            sqlx::query(
+                "INSERT INTO permissions (name) SELECT $1
+                WHERE NOT EXISTS (SELECT 1 FROM permissions WHERE name = $1);",
+            )
+            .bind(claim)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+
+            sqlx::query(
+                "INSERT INTO user_permissions (user_id, permission_id)
+                SELECT users.id, permissions.id FROM users, permissions
+                WHERE users.username = $1 AND permissions.name = $2;",
+            )
+            .bind(&username)
+            .bind(claim)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        }
+
+        let (status, login) = self.send(Method::POST, "/login", None, credentials).await;
+        assert_eq!(status, StatusCode::OK);
+
+        (username, login["token"].as_str().unwrap().to_string())
+    }
+
+    /// Sends a JSON request, `Value::Null` for none, and returns the status and JSON response.
+    pub(crate) async fn send(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let (status, _, body) = self.send_with_headers(method, uri, token, body).await;
+
+        (status, body)
+    }
+
+    /// Like [`TestApp::send`], also returning the response headers.
+    pub(crate) async fn send_with_headers(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Value,
+    ) -> (StatusCode, HeaderMap, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        let request = match body {
+            Value::Null => request.body(Body::empty()).unwrap(),
+            body => request.body(Body::from(body.to_string())).unwrap(),
+        };
+
+        let (parts, body) = self
+            .router
+            .clone()
+            .oneshot(request)
+            .await
+            .unwrap()
+            .into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+
+        (
+            parts.status,
+            parts.headers,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    /// Polls the job status at `uri` until the worker is done with the job.
+    pub(crate) async fn finished_job(&self, uri: &str, token: &str) -> Value {
+        let mut job = Value::Null;
+
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            job = self
+                .send(Method::GET, uri, Some(token), Value::Null)
+                .await
+                .1;
+
+            if !["queued", "deferred", "claimed", "posting", "unknown"]
+                .contains(&job["status"].as_str().unwrap_or(""))
+            {
+                return job;
+            }
+        }
+
+        panic!("job at {uri} never finished: {job}");
+    }
+}
+
+fn limiter() -> ratelimiter::Sender {
+    ratelimiter::new(
+        50,
+        Duration::from_secs(30),
+        COOLDOWN,
+        COOLDOWN,
+        COOLDOWN,
+        Default::default(),
+        0.8,
+    )
+}
+
+fn config(ns_api_url: &str, overrides: &[(&str, &str)]) -> Args {
+    let nations = format!("{NATION}:{PASSWORD}");
+
+    let mut builder = Config::builder()
+        .set_override("user", NATION)
+        .unwrap()
+        .set_override("database_host", "")
+        .unwrap()
+        .set_override("database_port", 0)
+        .unwrap()
+        .set_override("database_name", "")
+        .unwrap()
+        .set_override("database_user", "")
+        .unwrap()
+        .set_override("database_password", "")
+        .unwrap()
+        .set_override("log_level", "info")
+        .unwrap()
+        .set_override("port", 0)
+        .unwrap()
+        .set_override("dispatch_nations", nations.as_str())
+        .unwrap()
+        .set_override("rmbpost_nations", nations.as_str())
+        .unwrap()
+        .set_override("secret", "secret")
+        .unwrap()
+        .set_override("telegram_client_key", "key")
+        .unwrap()
+        .set_override("bcrypt_cost", 4)
+        .unwrap()
+        .set_override("ns_api_url", ns_api_url)
+        .unwrap();
+
+    for (key, value) in overrides {
+        builder = builder.set_override(*key, *value).unwrap();
+    }
+
+    builder.build().unwrap().try_deserialize::<Args>().unwrap()
+}
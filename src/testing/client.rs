@@ -0,0 +1,108 @@
+//! The typed client against the real router, so it breaks when the wire format drifts.
+
+use reqwest::StatusCode;
+
+use super::app::{NATION, TestApp};
+use crate::client::{ClientError, EurocoreClient, NewDispatch, Params, TextFormat, TgType};
+
+fn new_dispatch() -> NewDispatch {
+    NewDispatch {
+        nation: NATION.to_string(),
+        title: "Title".to_string(),
+        text: "text".to_string(),
+        category: Some(1),
+        subcategory: Some(100),
+        format: TextFormat::Bbcode,
+    }
+}
+
+#[tokio::test]
+async fn test_login_and_create_dispatch() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (username, _) = app.user(&["dispatches.create"]).await;
+    let mut client = EurocoreClient::new(&app.serve().await);
+
+    assert!(matches!(
+        client.create_dispatch(&new_dispatch()).await,
+        Err(ClientError::NoToken)
+    ));
+
+    let login = client.login(&username, "password123").await.unwrap();
+    assert_eq!(login.username, username);
+    assert_eq!(client.token(), Some(login.token.as_str()));
+
+    let job = client.create_dispatch(&new_dispatch()).await.unwrap();
+    assert_eq!(job.action, "add");
+
+    let status = client.get_queue_status(job.id).await.unwrap();
+    assert_eq!(status.id, job.id);
+    assert_eq!(status.action, "add");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_api_errors() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (username, _) = app.user(&["dispatches.create"]).await;
+    let url = app.serve().await;
+
+    let mut client = EurocoreClient::new(&url);
+
+    match client.login(&username, "wrong").await {
+        Err(ClientError::Api { status, code, .. }) => {
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(code.as_deref(), Some("unauthorized"));
+        }
+        other => panic!("expected API error, got {:?}", other),
+    }
+
+    // a token without dispatches.create
+    let (_, token) = app.user(&[]).await;
+    let client = EurocoreClient::new(&url).with_token(&token);
+
+    assert!(matches!(
+        client.create_dispatch(&new_dispatch()).await,
+        Err(ClientError::Api {
+            status: StatusCode::UNAUTHORIZED,
+            ..
+        })
+    ));
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_queue_telegrams() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["telegrams.create"]).await;
+    let client = EurocoreClient::new(&app.serve().await).with_token(&token);
+
+    let queued = client
+        .queue_telegrams(vec![Params {
+            sender: NATION.to_string(),
+            id: "123".to_string(),
+            recipient: "recipient".to_string(),
+            secret_key: "secret".to_string().into(),
+            tg_type: TgType::Recruitment,
+        }])
+        .await
+        .unwrap();
+
+    assert_eq!(queued.queued.len(), 1);
+    assert_eq!(queued.queued[0].recipient, "recipient");
+    assert_eq!(queued.queued[0].telegram_id, "123");
+    assert_eq!(queued.queued[0].queue, "recruitment");
+    assert!(queued.dropped.is_empty());
+
+    app.close().await;
+}
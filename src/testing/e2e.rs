@@ -0,0 +1,4536 @@
+//! Each test boots its own app with [`TestApp::start`] and returns early without
+//! `EUROCORE_TEST_DATABASE_URL`.
+
+use axum::http::{Method, StatusCode, header};
+use futures_util::StreamExt;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+
+use super::app::{NATION, TestApp};
+use super::ns::{PASSWORD, Request};
+use crate::controllers::retention;
+use crate::core::authorization::Claim;
+use crate::sync::{events, nations, ratelimiter};
+use crate::types::job::JobStatus;
+use crate::types::request::{EventsQuery, JobKind};
+use crate::types::response::PrunedTable;
+use crate::utils::signature;
+use crate::workers::{self, Recovery};
+use crate::{Address, ConfigError};
+
+fn new_dispatch(title: &str) -> Value {
+    json!({
+        "nation": NATION,
+        "title": title,
+        "text": "[b]hello[/b]",
+        "category": 1,
+        "subcategory": 100,
+    })
+}
+
+/// Queues a dispatch add and waits for it, returning the finished job.
+async fn add_dispatch(app: &TestApp, token: &str, title: &str) -> Value {
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(token),
+            new_dispatch(title),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    app.finished_job(job["self"].as_str().unwrap(), token).await
+}
+
+#[tokio::test]
+async fn test_dispatch_add_end_to_end() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (username, token) = app.user(&["dispatches.create", "stats.read"]).await;
+
+    let mut stream = Box::pin(app.events.subscribe(EventsQuery {
+        job: Some(JobKind::Dispatch),
+        id: None,
+        user: Some(username),
+        tenant: None,
+    }));
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            new_dispatch("End to end"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(job["status"], "queued");
+
+    let job = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+    assert!(job["claimed_at"].as_str() <= job["started_at"].as_str());
+    assert!(job["started_at"].as_str() <= job["prepared_at"].as_str());
+    assert!(job["prepared_at"].as_str() <= job["executing_at"].as_str());
+    assert!(job["executing_at"].as_str() <= job["finished_at"].as_str());
+
+    for phase in [
+        "queued_ms",
+        "ratelimit_wait_ms",
+        "ns_prepare_ms",
+        "ns_execute_ms",
+    ] {
+        assert!(
+            job["durations"][phase].as_i64() >= Some(0),
+            "{phase} in {job}"
+        );
+    }
+
+    let (status, timings) = app
+        .send(
+            Method::GET,
+            "/stats/timings?job=dispatch",
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{timings}");
+    assert!(timings["jobs"].as_i64() >= Some(1));
+    assert!(timings["ns_prepare_ms"]["p50"].as_i64() <= timings["ns_prepare_ms"]["p95"].as_i64());
+
+    let dispatch_id = job["dispatch_id"].as_i64().unwrap();
+    assert_eq!(job["resource"], format!("/dispatches/{dispatch_id}"));
+    assert_eq!(
+        app.ns.commands(),
+        vec!["dispatch:prepare", "dispatch:execute"]
+    );
+
+    for status in [JobStatus::Claimed, JobStatus::Posting, JobStatus::Succeeded] {
+        match stream.next().await {
+            Some(events::Message::Job(event)) => {
+                assert_eq!(event.id, job["id"]);
+                assert_eq!(event.status, status);
+
+                if status.is_finished() {
+                    assert_eq!(event.resource, job["resource"].as_str().map(String::from));
+                }
+            }
+            other => panic!("expected a job event, got {other:?}"),
+        }
+    }
+
+    let (status, dispatch) = app
+        .send(
+            Method::GET,
+            &format!("/dispatches/{dispatch_id}"),
+            None,
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(dispatch["title"], "End to end");
+    assert_eq!(dispatch["text"], "[b]hello[/b]");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_dispatch_edit_and_delete() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (username, token) = app
+        .user(&["dispatches.create", "dispatches.edit", "dispatches.delete"])
+        .await;
+
+    let job = add_dispatch(&app, &token, "Before").await;
+    let uri = format!("/dispatches/{}", job["dispatch_id"]);
+
+    let (_, dispatch) = app.send(Method::GET, &uri, None, Value::Null).await;
+    assert_eq!(dispatch["job_id"], job["id"]);
+    assert_eq!(job["revision_id"], dispatch["revision"]);
+
+    let (status, job) = app
+        .send(
+            Method::PUT,
+            &uri,
+            Some(&token),
+            json!({
+                "title": "After",
+                "text": "edited",
+                "category": 1,
+                "subcategory": 100,
+                "base_revision": dispatch["revision"],
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    let job = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    let (_, edited) = app.send(Method::GET, &uri, None, Value::Null).await;
+    assert_eq!(edited["title"], "After");
+    assert_eq!(edited["text"], "edited");
+    assert!(edited["revision"].as_i64() > dispatch["revision"].as_i64());
+    assert_eq!(edited["job_id"], job["id"]);
+    assert_eq!(job["revision_id"], edited["revision"]);
+
+    let edit = &app.ns.requests()[2];
+    assert_eq!(edit.param("dispatch"), Some("edit"));
+    assert_eq!(
+        edit.param("dispatchid"),
+        Some(job["dispatch_id"].to_string().as_str())
+    );
+    assert_eq!(edit.param("title"), Some("After"));
+
+    let (status, job) = app
+        .send(Method::DELETE, &uri, Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    let job = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+    assert!(job["revision_id"].is_null(), "{job}");
+
+    let (status, body) = app.send(Method::GET, &uri, None, Value::Null).await;
+    assert_eq!(status, StatusCode::GONE);
+    assert_eq!(body["code"], "dispatch_deleted");
+    assert_eq!(body["details"]["id"], job["dispatch_id"]);
+    assert_eq!(body["details"]["deleted_by"], username.as_str());
+    assert!(body["details"]["deleted_at"].is_string());
+
+    let (status, body) = app
+        .send(Method::GET, "/dispatches/999999", None, Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "dispatch_not_found");
+
+    assert_eq!(
+        app.ns
+            .requests()
+            .iter()
+            .filter(|request| request.command == "dispatch:execute")
+            .map(|request| request.param("dispatch").unwrap())
+            .collect::<Vec<_>>(),
+        vec!["add", "edit", "remove"]
+    );
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_protected_dispatch() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app
+        .user(&["dispatches.create", "dispatches.edit", "dispatches.delete"])
+        .await;
+    let (admin, admin_token) = app
+        .user(&["admin", "dispatches.delete", "dispatches.delete.any"])
+        .await;
+
+    let job = add_dispatch(&app, &token, "Law").await;
+    let uri = format!("/dispatches/{}", job["dispatch_id"]);
+
+    for action in ["protect", "unprotect"] {
+        let (status, error) = app
+            .send(
+                Method::POST,
+                &format!("{uri}/{action}"),
+                Some(&token),
+                Value::Null,
+            )
+            .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{action}: {error}");
+        assert_eq!(error["code"], "unauthorized");
+    }
+
+    let (status, dispatch) = app
+        .send(
+            Method::POST,
+            &format!("{uri}/protect"),
+            Some(&admin_token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{dispatch}");
+    assert_eq!(dispatch["is_protected"], true);
+
+    let (_, dispatch) = app.send(Method::GET, &uri, None, Value::Null).await;
+    assert_eq!(dispatch["is_protected"], true);
+
+    for listing in ["/dispatches", "/dispatches?summary=true"] {
+        let (_, dispatches) = app.send(Method::GET, listing, None, Value::Null).await;
+        assert_eq!(dispatches[0]["is_protected"], true, "{listing}");
+    }
+
+    for token in [&token, &admin_token] {
+        let (status, error) = app
+            .send(Method::DELETE, &uri, Some(token), Value::Null)
+            .await;
+        assert_eq!(status, StatusCode::LOCKED, "{error}");
+        assert_eq!(error["code"], "dispatch_protected");
+        assert_eq!(error["details"]["id"], job["dispatch_id"]);
+        assert_eq!(error["details"]["protected_by"], admin.as_str());
+        assert!(error["details"]["protected_at"].is_string());
+    }
+
+    // edits go through unless protect_dispatch_edits is set
+    let edit = json!({ "title": "Law", "text": "amended", "category": 1, "subcategory": 100 });
+    let (status, edit_job) = app.send(Method::PUT, &uri, Some(&token), edit).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{edit_job}");
+
+    let edit_job = app
+        .finished_job(edit_job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(edit_job["status"], "succeeded", "job ended as {edit_job}");
+
+    let (status, dispatch) = app
+        .send(
+            Method::POST,
+            &format!("{uri}/unprotect"),
+            Some(&admin_token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{dispatch}");
+    assert_eq!(dispatch["is_protected"], false);
+
+    let (status, delete_job) = app
+        .send(Method::DELETE, &uri, Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{delete_job}");
+
+    let delete_job = app
+        .finished_job(delete_job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(
+        delete_job["status"], "succeeded",
+        "job ended as {delete_job}"
+    );
+
+    let (status, _) = app
+        .send(
+            Method::POST,
+            &format!("{uri}/protect"),
+            Some(&admin_token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_protected_dispatch_edits() {
+    let Some(app) = TestApp::start_with(&[("protect_dispatch_edits", "true")]).await else {
+        return;
+    };
+
+    let (_, token) = app
+        .user(&["admin", "dispatches.create", "dispatches.edit"])
+        .await;
+
+    let job = add_dispatch(&app, &token, "Charter").await;
+    let uri = format!("/dispatches/{}", job["dispatch_id"]);
+
+    app.send(
+        Method::POST,
+        &format!("{uri}/protect"),
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+
+    let edit = json!({ "title": "Charter", "text": "amended", "category": 1, "subcategory": 100 });
+    let (status, error) = app.send(Method::PUT, &uri, Some(&token), edit).await;
+    assert_eq!(status, StatusCode::LOCKED, "{error}");
+    assert_eq!(error["code"], "dispatch_protected");
+
+    app.close().await;
+}
+
+/// Short texts keep the plain column, so reading them is no different from before compression;
+/// long ones are stored gzipped and read back intact.
+#[tokio::test]
+async fn test_dispatch_text_storage() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create"]).await;
+
+    for (title, text, compressed) in [
+        ("Short text", "[b]hello[/b]".to_string(), false),
+        ("Long text", vec!["[b]hello[/b]"; 200].join(" "), true),
+    ] {
+        let (status, job) = app
+            .send(
+                Method::POST,
+                "/dispatches",
+                Some(&token),
+                json!({ "nation": NATION, "title": title, "text": text, "category": 1, "subcategory": 100 }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+        let job = app
+            .finished_job(job["self"].as_str().unwrap(), &token)
+            .await;
+        assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+        let (plain, gzipped): (Option<String>, Option<Vec<u8>>) =
+            sqlx::query_as("SELECT text, text_compressed FROM dispatch_content WHERE job_id = $1;")
+                .bind(job["id"].as_i64().unwrap() as i32)
+                .fetch_one(&app.pool)
+                .await
+                .unwrap();
+        assert_eq!(plain.is_none(), compressed, "{title}");
+        assert_eq!(gzipped.is_some(), compressed, "{title}");
+
+        let (status, dispatch) = app
+            .send(
+                Method::GET,
+                job["resource"].as_str().unwrap(),
+                None,
+                Value::Null,
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{dispatch}");
+        assert_eq!(dispatch["text"], text, "{title}");
+    }
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_dispatch_size_warning() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["admin", "dispatches.create"]).await;
+
+    let small = add_dispatch(&app, &token, "Small").await;
+    assert!(small.get("warnings").is_none(), "{small}");
+
+    // 30,000 characters, but each is sent as `&#233;`, 90% of what NS accepts
+    let mut dispatch = new_dispatch("Large");
+    dispatch["text"] = json!("é".repeat(30_000));
+
+    let (status, job) = app
+        .send(Method::POST, "/dispatches", Some(&token), dispatch)
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+    assert!(job.get("warnings").is_none(), "{job}");
+
+    let large = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(large["status"], "succeeded", "{large}");
+    assert_eq!(
+        large["warnings"],
+        json!(["text is 180000 of the 200000 characters NS accepts (90%)"])
+    );
+
+    let (status, body) = app
+        .send(
+            Method::GET,
+            &format!("/dispatches/{}", large["dispatch_id"]),
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["size"], 180_000);
+
+    let (status, largest) = app
+        .send(
+            Method::GET,
+            "/stats/dispatches/largest?limit=5",
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{largest}");
+    assert_eq!(largest[0]["id"], large["dispatch_id"]);
+    assert_eq!(largest[0]["size"], 180_000);
+    assert_eq!(largest[0]["utilization"], 0.9);
+    assert_eq!(largest[1]["id"], small["dispatch_id"]);
+    assert_eq!(largest[1]["size"], "[b]hello[/b]".len());
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_dispatch_metrics() {
+    let Some(app) = TestApp::start_with(&[("dispatch_metrics_interval_secs", "1")]).await else {
+        return;
+    };
+
+    let (_, token) = app
+        .user(&["admin", "dispatches.create", "stats.read"])
+        .await;
+
+    let popular = add_dispatch(&app, &token, "Popular").await;
+    let obscure = add_dispatch(&app, &token, "Obscure").await;
+    let popular_id = popular["dispatch_id"].as_i64().unwrap() as i32;
+    let obscure_id = obscure["dispatch_id"].as_i64().unwrap() as i32;
+
+    for id in [popular_id, obscure_id] {
+        app.ns.publish_dispatch(id, NATION, "title", "[b]hello[/b]");
+    }
+    app.ns.set_dispatch_metrics(popular_id, 100, 1);
+    app.ns.set_dispatch_metrics(obscure_id, 10, 0);
+
+    let samples = |id: i32| {
+        let (app, token) = (&app, &token);
+
+        async move {
+            let (status, body) = app
+                .send(
+                    Method::GET,
+                    &format!("/dispatches/{id}/metrics"),
+                    Some(token),
+                    Value::Null,
+                )
+                .await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+
+            body["samples"].as_array().unwrap().clone()
+        }
+    };
+
+    let wait_for = |id: i32, views: i32| async move {
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        while !samples(id)
+            .await
+            .iter()
+            .any(|sample| sample["views"] == views)
+        {
+            assert!(Instant::now() < deadline, "no sample of {views} views");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    };
+
+    wait_for(popular_id, 100).await;
+    wait_for(obscure_id, 10).await;
+
+    app.ns.set_dispatch_metrics(popular_id, 250, 6);
+    app.ns.set_dispatch_metrics(obscure_id, 12, 0);
+
+    wait_for(popular_id, 250).await;
+    wait_for(obscure_id, 12).await;
+
+    let popular_samples = samples(popular_id).await;
+    assert_eq!(popular_samples[0]["views"], 100);
+    assert_eq!(popular_samples[0]["score"], 1);
+    assert!(popular_samples[0]["sampled_at"].is_string());
+
+    let (status, top) = app
+        .send(
+            Method::GET,
+            "/stats/dispatches/top?hours=1",
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{top}");
+    assert_eq!(top[0]["id"], popular_id, "{top}");
+    assert_eq!(top[0]["title"], "Popular");
+    assert_eq!(top[0]["views"], 250);
+    assert_eq!(top[0]["score"], 6);
+    assert_eq!(top[0]["views_delta"], 150);
+    assert_eq!(top[0]["score_delta"], 5);
+    assert_eq!(top[1]["id"], obscure_id, "{top}");
+    assert_eq!(top[1]["views_delta"], 2);
+    assert_eq!(top[1]["score_delta"], 0);
+
+    // requests are spread over the interval, half a second apart for two dispatches
+    let reads: Vec<Instant> = app
+        .ns
+        .requests()
+        .into_iter()
+        .filter(|request| request.command == "dispatch")
+        .map(|request| request.at)
+        .collect();
+    assert!(reads.len() >= 4, "{reads:?}");
+    assert!(
+        reads
+            .windows(2)
+            .all(|pair| pair[1] - pair[0] >= Duration::from_millis(400)),
+        "{reads:?}"
+    );
+
+    let future = (chrono::Utc::now() + chrono::Duration::hours(1))
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let (status, body) = app
+        .send(
+            Method::GET,
+            &format!("/dispatches/{popular_id}/metrics?from={future}"),
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["samples"], json!([]));
+
+    let (status, body) = app
+        .send(
+            Method::GET,
+            "/dispatches/999999/metrics",
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{body}");
+
+    // a paused pipeline pauses sampling
+    app.send(
+        Method::POST,
+        "/admin/pipelines/dispatch/pause",
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    let sampled = app.ns.commands().len();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert_eq!(app.ns.commands().len(), sampled);
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_reads_use_read_pool() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create", "stats.read"]).await;
+
+    let job = add_dispatch(&app, &token, "Replica").await;
+    assert_eq!(job["status"], "succeeded", "{job}");
+
+    let (status, body) = app
+        .send(Method::GET, "/dispatches", Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(app.read.size() > 0);
+
+    // without the read pool, reads fail and writes go on
+    app.read.close().await;
+
+    for uri in [
+        "/dispatches",
+        &format!("/dispatches/{}", job["dispatch_id"]),
+        "/dispatches/search?q=replica",
+        "/stats/dispatches",
+        job["self"].as_str().unwrap(),
+    ] {
+        let (status, body) = app.send(Method::GET, uri, Some(&token), Value::Null).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{uri}: {body}");
+    }
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            new_dispatch("Primary"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{body}");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_duplicate_dispatch_title() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create"]).await;
+
+    let job = add_dispatch(&app, &token, "Factbook").await;
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            new_dispatch(" FACTBOOK "),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{error}");
+    assert_eq!(error["code"], "duplicate_dispatch_title");
+    assert_eq!(error["details"]["id"], job["dispatch_id"]);
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/dispatches?allow_duplicate_title=true",
+            Some(&token),
+            new_dispatch("Factbook"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    app.close().await;
+
+    let Some(app) = TestApp::start_with(&[("unique_dispatch_titles", "false")]).await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create"]).await;
+
+    add_dispatch(&app, &token, "Series").await;
+    let job = add_dispatch(&app, &token, "Series").await;
+    assert_eq!(job["status"], "succeeded", "{job}");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_nation_presets() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, admin) = app.user(&["admin"]).await;
+    let (_, token) = app.user(&["dispatches.create"]).await;
+    let uri = format!("/admin/nations/{NATION}/presets");
+
+    let uncategorized = json!({ "nation": NATION, "title": "Headline", "text": "story" });
+
+    // neither the dispatch nor a preset has a category
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            uncategorized.clone(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{error}");
+    assert_eq!(error["code"], "missing_dispatch_category");
+
+    let (status, error) = app.send(Method::GET, &uri, Some(&admin), Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{error}");
+    assert_eq!(error["code"], "preset_not_found");
+
+    let (status, error) = app
+        .send(
+            Method::PUT,
+            &uri,
+            Some(&admin),
+            json!({ "default_category": 3 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{error}");
+    assert_eq!(error["code"], "invalid_factbook_category");
+
+    let (status, error) = app
+        .send(
+            Method::PUT,
+            "/admin/nations/nowhere/presets",
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{error}");
+    assert_eq!(error["code"], "invalid_nation");
+
+    let (status, preset) = app
+        .send(
+            Method::PUT,
+            &uri,
+            Some(&admin),
+            json!({
+                "default_category": 3,
+                "default_subcategory": 315,
+                "title_prefix": "News: ",
+                "signature_text": "[i]The Herald[/i]",
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{preset}");
+    assert_eq!(preset["nation"], NATION);
+    assert_eq!(preset["default_subcategory"], 315);
+
+    let (_, body) = app
+        .send(Method::GET, "/bootstrap", Some(&token), Value::Null)
+        .await;
+    assert_eq!(body["presets"], json!([preset]));
+
+    // the preset fills in the category
+    let (status, job) = app
+        .send(Method::POST, "/dispatches", Some(&token), uncategorized)
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+    let job = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(job["status"], "succeeded", "{job}");
+
+    let (_, dispatch) = app
+        .send(
+            Method::GET,
+            &format!("/dispatches/{}", job["dispatch_id"]),
+            None,
+            Value::Null,
+        )
+        .await;
+    assert_eq!(dispatch["title"], "News: Headline");
+    assert_eq!(dispatch["text"], "story\n\n[i]The Herald[/i]");
+    assert_eq!(dispatch["category"], 3);
+    assert_eq!(dispatch["subcategory"], 315);
+
+    // an explicit category wins, and a prefix already there isn't repeated
+    let id = post_dispatch(&app, &token, "News: Explicit", "text", (1, 100)).await;
+    let (_, dispatch) = app
+        .send(Method::GET, &format!("/dispatches/{id}"), None, Value::Null)
+        .await;
+    assert_eq!(dispatch["title"], "News: Explicit");
+    assert_eq!(dispatch["category"], 1);
+    assert_eq!(dispatch["subcategory"], 100);
+
+    let (status, _) = app
+        .send(Method::DELETE, &uri, Some(&admin), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = app
+        .send(Method::DELETE, &uri, Some(&admin), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_content_rules() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, admin) = app.user(&["admin"]).await;
+    let (_, token) = app.user(&["dispatches.create", "rmbposts.create"]).await;
+
+    let (status, ban) = app
+        .send(
+            Method::POST,
+            "/admin/content-rules",
+            Some(&admin),
+            json!({ "name": "invite-links", "pattern": r"discord\.gg/\w+", "action": "ban" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{ban}");
+
+    let (status, warn) = app
+        .send(
+            Method::POST,
+            "/admin/content-rules",
+            Some(&admin),
+            json!({ "name": "other-regions", "pattern": r"\bthe north pacific\b", "action": "warn" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{warn}");
+
+    // the poster learns which rule, not its pattern
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            new_dispatch("Join us at Discord.gg/abc"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{error}");
+    assert_eq!(error["code"], "content_policy_violation");
+    assert_eq!(error["details"]["rules"], json!(["invite-links"]));
+    assert!(!error.to_string().contains(r"\w+"), "{error}");
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            new_dispatch("News from The North Pacific"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+    let job = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(job["status"], "succeeded", "{job}");
+    assert_eq!(
+        job["warnings"],
+        json!(["matches content rule `other-regions`"])
+    );
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/rmbposts",
+            Some(&token),
+            json!({ "nation": NATION, "region": "testregion", "text": "hello the north pacific" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+    assert_eq!(
+        job["warnings"],
+        json!(["matches content rule `other-regions`"])
+    );
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/rmbposts",
+            Some(&token),
+            json!({ "nation": NATION, "region": "testregion", "text": "discord.gg/abc" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{error}");
+    assert_eq!(error["code"], "content_policy_violation");
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/admin/content-rules",
+            Some(&admin),
+            json!({ "name": "broken", "pattern": "(unclosed", "action": "ban" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{error}");
+    assert_eq!(error["code"], "invalid_content_rule");
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/admin/content-rules",
+            Some(&admin),
+            json!({ "name": "invite-links", "pattern": "invite", "action": "warn" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{error}");
+    assert_eq!(error["code"], "content_rule_exists");
+
+    // downgraded to a warning, the ban no longer refuses the dispatch
+    let uri = format!("/admin/content-rules/{}", ban["id"]);
+    let (status, updated) = app
+        .send(
+            Method::PUT,
+            &uri,
+            Some(&admin),
+            json!({ "name": "invite-links", "pattern": r"discord\.gg/\w+", "action": "warn" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{updated}");
+    assert_eq!(updated["action"], "warn");
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            new_dispatch("Join us at Discord.gg/abc"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    let (_, rules) = app
+        .send(
+            Method::GET,
+            "/admin/content-rules",
+            Some(&admin),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(rules.as_array().map(Vec::len), Some(2), "{rules}");
+
+    let (status, _) = app
+        .send(Method::DELETE, &uri, Some(&admin), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = app
+        .send(Method::DELETE, &uri, Some(&admin), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_pending_jobs_block_conflicting_jobs() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app
+        .user(&[
+            "admin",
+            "dispatches.create",
+            "dispatches.edit",
+            "dispatches.delete",
+        ])
+        .await;
+
+    let job = add_dispatch(&app, &token, "Contested").await;
+    let dispatch_id = job["dispatch_id"].clone();
+    let uri = format!("/dispatches/{dispatch_id}");
+    let edit = json!({ "title": "Contested", "text": "edited", "category": 1, "subcategory": 100 });
+
+    let (status, _) = app
+        .send(
+            Method::POST,
+            "/admin/pipelines/dispatch/pause",
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, edit_job) = app
+        .send(Method::PUT, &uri, Some(&token), edit.clone())
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{edit_job}");
+
+    let (status, error) = app
+        .send(Method::DELETE, &uri, Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{error}");
+    assert_eq!(error["code"], "pending_jobs");
+    assert_eq!(error["details"]["dispatch_id"], dispatch_id);
+    assert_eq!(
+        error["details"]["jobs"],
+        json!([{ "id": edit_job["id"], "action": "edit", "status": "queued" }])
+    );
+
+    let (status, _) = app
+        .send(
+            Method::POST,
+            "/admin/pipelines/dispatch/resume",
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let job = app
+        .finished_job(edit_job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    app.send(
+        Method::POST,
+        "/admin/pipelines/dispatch/pause",
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+
+    let (status, delete_job) = app
+        .send(Method::DELETE, &uri, Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{delete_job}");
+
+    let (status, error) = app.send(Method::PUT, &uri, Some(&token), edit).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{error}");
+    assert_eq!(error["details"]["jobs"][0]["id"], delete_job["id"]);
+    assert_eq!(error["details"]["jobs"][0]["action"], "delete");
+
+    app.send(
+        Method::POST,
+        "/admin/pipelines/dispatch/resume",
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+
+    let job = app
+        .finished_job(delete_job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_coalesced_edits() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app
+        .user(&["admin", "dispatches.create", "dispatches.edit"])
+        .await;
+
+    let job = add_dispatch(&app, &token, "Draft").await;
+    let uri = format!("/dispatches/{}", job["dispatch_id"]);
+    let edit =
+        |text: &str| json!({ "title": "Draft", "text": text, "category": 1, "subcategory": 100 });
+
+    app.send(
+        Method::POST,
+        "/admin/pipelines/dispatch/pause",
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+
+    let mut jobs = Vec::new();
+
+    for (text, query) in [
+        ("first", ""),
+        ("second", "?coalesce=true"),
+        ("third", "?coalesce=true"),
+        // without the flag the edit is posted after the one before it
+        ("fourth", ""),
+    ] {
+        let (status, job) = app
+            .send(
+                Method::PUT,
+                &format!("{uri}{query}"),
+                Some(&token),
+                edit(text),
+            )
+            .await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+        jobs.push(job["self"].as_str().unwrap().to_string());
+    }
+
+    app.send(
+        Method::POST,
+        "/admin/pipelines/dispatch/resume",
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+
+    let mut finished = Vec::new();
+
+    for job in &jobs {
+        finished.push(app.finished_job(job, &token).await);
+    }
+
+    assert_eq!(finished[0]["status"], "superseded", "{}", finished[0]);
+    assert_eq!(finished[0]["superseded_by"], finished[1]["id"]);
+    assert_eq!(finished[1]["status"], "superseded", "{}", finished[1]);
+    assert_eq!(finished[1]["superseded_by"], finished[2]["id"]);
+    assert_eq!(finished[2]["status"], "succeeded", "{}", finished[2]);
+    assert_eq!(finished[3]["status"], "succeeded", "{}", finished[3]);
+    assert!(finished[3]["superseded_by"].is_null());
+
+    let edits: Vec<_> = app
+        .ns
+        .requests()
+        .into_iter()
+        .filter(|request| {
+            request.command == "dispatch:execute" && request.param("dispatchid").is_some()
+        })
+        .map(|request| request.param("text").map(str::to_string))
+        .collect();
+    assert_eq!(
+        edits,
+        vec![Some("third".to_string()), Some("fourth".to_string())]
+    );
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_edits_post_in_submission_order() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app
+        .user(&["admin", "dispatches.create", "dispatches.edit"])
+        .await;
+
+    let job = add_dispatch(&app, &token, "Draft").await;
+    let id = job["dispatch_id"].as_i64().unwrap() as i32;
+    let uri = format!("/dispatches/{id}");
+    let edit =
+        |text: &str| json!({ "title": "Draft", "text": text, "category": 1, "subcategory": 100 });
+
+    app.send(
+        Method::POST,
+        "/admin/pipelines/dispatch/pause",
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+
+    let (status, first) = app
+        .send(Method::PUT, &uri, Some(&token), edit("first"))
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{first}");
+    let (status, second) = app
+        .send(Method::PUT, &uri, Some(&token), edit("second"))
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{second}");
+
+    // the first edit was deferred, as when the worker's channel was full, and the second one
+    // reached the worker without it
+    sqlx::query("UPDATE dispatch_queue SET status = 'deferred' WHERE id = $1;")
+        .bind(first["id"].as_i64().unwrap() as i32)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    app.send(
+        Method::POST,
+        "/admin/pipelines/dispatch/resume",
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+
+    for job in [&first, &second] {
+        let job = app
+            .finished_job(job["self"].as_str().unwrap(), &token)
+            .await;
+        assert_eq!(job["status"], "succeeded", "{job}");
+    }
+
+    let posted: Vec<_> = app
+        .ns
+        .requests()
+        .into_iter()
+        .filter(|request| {
+            request.command == "dispatch:execute" && request.param("dispatchid").is_some()
+        })
+        .map(|request| request.param("text").map(str::to_string))
+        .collect();
+    assert_eq!(
+        posted,
+        vec![Some("first".to_string()), Some("second".to_string())]
+    );
+
+    let history: Vec<Option<i32>> = sqlx::query_scalar(
+        "SELECT dispatch_content.job_id FROM dispatch_content
+        JOIN dispatches ON dispatches.id = dispatch_content.dispatch_id
+        WHERE dispatches.dispatch_id = $1
+        ORDER BY dispatch_content.id;",
+    )
+    .bind(id)
+    .fetch_all(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        history,
+        [&job, &first, &second]
+            .iter()
+            .map(|job| job["id"].as_i64().map(|id| id as i32))
+            .collect::<Vec<_>>()
+    );
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_queued_jobs_carry_an_estimate() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app
+        .user(&["admin", "dispatches.create", "rmbposts.create"])
+        .await;
+    let estimate = |job: &Value| {
+        job["estimated_completion_at"]
+            .as_str()
+            .and_then(|at| at.parse::<chrono::DateTime<chrono::Utc>>().ok())
+    };
+    let control = |pipeline: &str, command: &str| {
+        let uri = format!("/admin/pipelines/{pipeline}/{command}");
+        let token = token.clone();
+        let app = &app;
+
+        async move {
+            app.send(Method::POST, &uri, Some(&token), Value::Null)
+                .await
+        }
+    };
+    // a finished job has nothing left to estimate
+    let finish = |uri: String| {
+        let token = token.clone();
+        let app = &app;
+
+        async move {
+            let job = app.finished_job(&uri, &token).await;
+            assert_eq!(job["status"], "succeeded", "job ended as {job}");
+            assert!(estimate(&job).is_none(), "{job}");
+
+            let (_, headers, _) = app
+                .send_with_headers(Method::GET, &uri, Some(&token), Value::Null)
+                .await;
+            assert!(!headers.contains_key(header::RETRY_AFTER));
+        }
+    };
+
+    // held in the queue, so there is something to estimate
+    control("dispatch", "pause").await;
+
+    let mut jobs = Vec::new();
+
+    for title in ["First estimate", "Second estimate"] {
+        let (status, headers, job) = app
+            .send_with_headers(
+                Method::POST,
+                "/dispatches",
+                Some(&token),
+                new_dispatch(title),
+            )
+            .await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+        assert!(estimate(&job).is_some(), "{job}");
+        assert!(
+            headers[header::RETRY_AFTER]
+                .to_str()
+                .unwrap()
+                .parse::<u64>()
+                .unwrap()
+                >= 1
+        );
+
+        jobs.push(job["self"].as_str().unwrap().to_string());
+    }
+
+    let (status, headers, first) = app
+        .send_with_headers(Method::GET, &jobs[0], Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{first}");
+    assert!(headers.contains_key(header::RETRY_AFTER));
+
+    // the second add waits behind the first
+    let second = app
+        .send(Method::GET, &jobs[1], Some(&token), Value::Null)
+        .await
+        .1;
+    assert!(estimate(&second) > estimate(&first), "{first} {second}");
+
+    control("dispatch", "resume").await;
+
+    for job in jobs {
+        finish(job).await;
+    }
+
+    control("rmbpost", "pause").await;
+
+    let (status, headers, rmbpost) = app
+        .send_with_headers(
+            Method::POST,
+            "/rmbposts",
+            Some(&token),
+            json!({ "nation": NATION, "region": "testregion", "text": "Soon" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{rmbpost}");
+    assert!(estimate(&rmbpost).is_some(), "{rmbpost}");
+    assert!(headers.contains_key(header::RETRY_AFTER));
+
+    control("rmbpost", "resume").await;
+
+    finish(rmbpost["self"].as_str().unwrap().to_string()).await;
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_dispatch_import() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (username, token) = app.user(&["dispatches.create", "dispatches.edit"]).await;
+
+    app.ns
+        .publish_dispatch(42, NATION, "Old news", "[i]from before[/i]");
+    app.ns
+        .publish_dispatch(43, "someone_else", "Not ours", "text");
+
+    let import = |dispatch_id: i32, nation: &str| {
+        app.send(
+            Method::POST,
+            "/dispatches/import",
+            Some(&token),
+            json!({ "dispatch_id": dispatch_id, "nation": nation }),
+        )
+    };
+
+    let (status, dispatch) = import(42, "Testlandia").await;
+    assert_eq!(status, StatusCode::CREATED, "{dispatch}");
+    assert_eq!(dispatch["id"], 42);
+    assert_eq!(dispatch["nation"], NATION);
+    assert_eq!(dispatch["title"], "Old news");
+    assert_eq!(dispatch["text"], "[i]from before[/i]");
+    assert_eq!(dispatch["category"], 1);
+    assert_eq!(dispatch["subcategory"], 100);
+    assert_eq!(dispatch["created_by"], username.as_str());
+
+    let (status, body) = import(42, NATION).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "dispatch_already_tracked");
+
+    let (status, body) = import(99, NATION).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "dispatch_not_found");
+
+    let (status, body) = import(43, NATION).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "dispatch_author_mismatch");
+    assert_eq!(body["details"]["author"], "someone_else");
+
+    let (status, body) = import(43, "someone_else").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_nation");
+
+    // once imported, it's edited like any other
+    let (status, job) = app
+        .send(
+            Method::PUT,
+            "/dispatches/42",
+            Some(&token),
+            json!({
+                "title": "New news",
+                "text": "edited",
+                "category": 1,
+                "subcategory": 100,
+                "base_revision": dispatch["revision"],
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    let job = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    let (_, edited) = app
+        .send(Method::GET, "/dispatches/42", None, Value::Null)
+        .await;
+    assert_eq!(edited["title"], "New news");
+
+    assert_eq!(
+        app.ns.commands(),
+        vec![
+            "dispatch",
+            "dispatch",
+            "dispatch",
+            "dispatch:prepare",
+            "dispatch:execute"
+        ]
+    );
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_responses_are_compressed() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create"]).await;
+
+    let job = add_dispatch(&app, &token, "Compressed").await;
+    assert_eq!(job["status"], "succeeded", "{job}");
+
+    let request = |accept_encoding: &str| {
+        axum::http::Request::builder()
+            .uri("/dispatches")
+            .header(header::ACCEPT_ENCODING, accept_encoding)
+            .body(axum::body::Body::empty())
+            .unwrap()
+    };
+
+    let response = app.router.clone().oneshot(request("br")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+
+    let response = app.router.clone().oneshot(request("gzip")).await.unwrap();
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let mut decoded = String::new();
+    std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&bytes[..]), &mut decoded)
+        .unwrap();
+    let listed: Value = serde_json::from_str(&decoded).unwrap();
+    assert_eq!(listed[0]["title"], "Compressed");
+
+    let response = app
+        .router
+        .clone()
+        .oneshot(request("identity"))
+        .await
+        .unwrap();
+    assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+
+    app.close().await;
+}
+
+/// GETs `uri` with an `Accept` header and returns the raw response, for bodies that aren't JSON.
+async fn download(
+    app: &TestApp,
+    uri: &str,
+    token: &str,
+    accept: &str,
+) -> axum::http::Response<axum::body::Bytes> {
+    let request = axum::http::Request::builder()
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(header::ACCEPT, accept)
+        .body(axum::body::Body::empty())
+        .unwrap();
+
+    let (parts, body) = app
+        .router
+        .clone()
+        .oneshot(request)
+        .await
+        .unwrap()
+        .into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+
+    axum::http::Response::from_parts(parts, bytes)
+}
+
+#[tokio::test]
+async fn test_dispatch_export() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create", "dispatches.edit"]).await;
+    let (_, exporter) = app.user(&["export.read"]).await;
+
+    let job = add_dispatch(&app, &token, "Exported").await;
+    let uri = format!("/dispatches/{}", job["dispatch_id"]);
+
+    let (status, job) = app
+        .send(
+            Method::PUT,
+            &uri,
+            Some(&token),
+            json!({ "title": "Exported", "text": "edited", "category": 1, "subcategory": 100 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+    let job = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    let response = download(&app, "/export/dispatches", &token, "*/*").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = download(&app, "/export/dispatches", &exporter, "*/*").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/x-ndjson"
+    );
+
+    let body = response.body();
+    let lines: Vec<Value> = body
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["text"], "[b]hello[/b]");
+    assert_eq!(lines[1]["text"], "edited");
+    assert!(lines[0]["revision"].as_i64() < lines[1]["revision"].as_i64());
+    assert_eq!(lines[1]["job_id"], job["id"]);
+
+    let trailer = &lines[2]["trailer"];
+    let content = &body[..body.len() - serde_json::to_vec(&lines[2]).unwrap().len() - 1];
+    assert_eq!(trailer["revisions"], 2);
+    assert_eq!(
+        trailer["sha256"],
+        Sha256::digest(content)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+    );
+
+    let since = (chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+    let response = download(
+        &app,
+        &format!("/export/dispatches?since={}", since.replace('+', "%2B")),
+        &exporter,
+        "*/*",
+    )
+    .await;
+    let trailer: Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(trailer["trailer"]["revisions"], 0);
+
+    let response = download(&app, "/export/dispatches", &exporter, "application/zip").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/zip");
+
+    // two revisions and the manifest, per the end of central directory record
+    let archive = response.body();
+    assert_eq!(&archive[..4], &[0x50, 0x4b, 0x03, 0x04]);
+    let end = &archive[archive.len() - 22..];
+    assert_eq!(&end[..4], &[0x50, 0x4b, 0x05, 0x06]);
+    assert_eq!(u16::from_le_bytes([end[10], end[11]]), 3);
+
+    let response = download(
+        &app,
+        "/export/dispatches?format=ndjson",
+        &exporter,
+        "application/zip",
+    )
+    .await;
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/x-ndjson"
+    );
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_ns_error_fails_job() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create"]).await;
+
+    app.ns.fail_next(
+        "dispatch:execute",
+        "This nation does not have permission to post in that category.",
+    );
+
+    let job = add_dispatch(&app, &token, "Rejected").await;
+
+    assert_eq!(job["status"], "failed", "job ended as {job}");
+    assert_eq!(job["error_code"], "region_password_required");
+    assert_eq!(job["dispatch_id"], Value::Null);
+
+    // the next job isn't affected
+    let job = add_dispatch(&app, &token, "Accepted").await;
+
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_lost_execute_finds_posted_dispatch() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (username, token) = app.user(&["dispatches.create"]).await;
+
+    let mut stream = Box::pin(app.events.subscribe(EventsQuery {
+        job: Some(JobKind::Dispatch),
+        id: None,
+        user: Some(username),
+        tenant: None,
+    }));
+
+    // NS posts the dispatch, but the reply never makes it back
+    app.ns
+        .lose_next("dispatch:execute", StatusCode::GATEWAY_TIMEOUT);
+
+    let job = add_dispatch(&app, &token, "Timed Out").await;
+
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+    assert_eq!(job["error_code"], Value::Null);
+    let dispatch_id = job["dispatch_id"].as_i64().unwrap();
+    assert_eq!(job["resource"], format!("/dispatches/{dispatch_id}"));
+
+    // found rather than posted a second time
+    assert_eq!(
+        app.ns.commands(),
+        vec!["dispatch:prepare", "dispatch:execute", "dispatchlist"]
+    );
+
+    for status in [
+        JobStatus::Claimed,
+        JobStatus::Posting,
+        JobStatus::Unknown,
+        JobStatus::Succeeded,
+    ] {
+        match stream.next().await {
+            Some(events::Message::Job(event)) => assert_eq!(event.status, status),
+            other => panic!("expected a job event, got {other:?}"),
+        }
+    }
+
+    let (status, dispatch) = app
+        .send(
+            Method::GET,
+            &format!("/dispatches/{dispatch_id}"),
+            None,
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{dispatch}");
+    assert_eq!(dispatch["title"], "Timed Out");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_lost_execute_fails_when_not_posted() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create"]).await;
+
+    // the same title, but from long before the job
+    app.ns.publish_dispatch(99, NATION, "Not Posted", "older");
+    app.ns
+        .fail_next_with_status("dispatch:execute", StatusCode::INTERNAL_SERVER_ERROR);
+
+    let job = add_dispatch(&app, &token, "Not Posted").await;
+
+    assert_eq!(job["status"], "failed", "job ended as {job}");
+    assert_eq!(job["dispatch_id"], Value::Null);
+    assert_eq!(
+        app.ns.commands(),
+        vec!["dispatch:prepare", "dispatch:execute", "dispatchlist"]
+    );
+
+    // a refusal NS explains isn't looked for
+    app.ns.fail_next(
+        "dispatch:execute",
+        "This nation does not have permission to post in that category.",
+    );
+
+    let job = add_dispatch(&app, &token, "Refused").await;
+
+    assert_eq!(job["status"], "failed", "job ended as {job}");
+    assert!(!app.ns.commands()[3..].contains(&"dispatchlist".to_string()));
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_lost_execute_stays_unknown_when_lookup_fails() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (username, token) = app.user(&["dispatches.create"]).await;
+
+    let mut stream = Box::pin(app.events.subscribe(EventsQuery {
+        job: Some(JobKind::Dispatch),
+        id: None,
+        user: Some(username),
+        tenant: None,
+    }));
+
+    // NS posts the dispatch and loses the reply, then can't be asked about it the first time
+    app.ns
+        .lose_next("dispatch:execute", StatusCode::GATEWAY_TIMEOUT);
+    app.ns
+        .fail_next_with_status("dispatchlist", StatusCode::INTERNAL_SERVER_ERROR);
+
+    let job = add_dispatch(&app, &token, "Looked For Twice").await;
+
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+    assert_eq!(job["error_code"], Value::Null);
+    assert!(job["dispatch_id"].is_i64(), "{job}");
+
+    // looked for again rather than failed or posted a second time
+    assert_eq!(
+        app.ns.commands(),
+        vec![
+            "dispatch:prepare",
+            "dispatch:execute",
+            "dispatchlist",
+            "dispatchlist"
+        ]
+    );
+
+    for status in [
+        JobStatus::Claimed,
+        JobStatus::Posting,
+        JobStatus::Unknown,
+        JobStatus::Succeeded,
+    ] {
+        match stream.next().await {
+            Some(events::Message::Job(event)) => assert_eq!(event.status, status),
+            other => panic!("expected a job event, got {other:?}"),
+        }
+    }
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_reapply_failed_edit() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (username, token) = app.user(&["dispatches.create", "dispatches.edit"]).await;
+
+    let job = add_dispatch(&app, &token, "Original").await;
+    let add_id = job["id"].as_i64().unwrap();
+    let uri = format!("/dispatches/{}", job["dispatch_id"]);
+
+    let (_, dispatch) = app.send(Method::GET, &uri, None, Value::Null).await;
+    assert_eq!(
+        dispatch["pending_or_failed_edits"],
+        json!({ "pending": 0, "failed": 0, "latest_failed": null })
+    );
+
+    app.ns.fail_next(
+        "dispatch:execute",
+        "This nation does not have permission to post in that category.",
+    );
+
+    let edit = json!({ "title": "Edited", "text": "edited", "category": 1, "subcategory": 100 });
+    let (status, job) = app.send(Method::PUT, &uri, Some(&token), edit).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    let failed = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(failed["status"], "failed", "job ended as {failed}");
+    let failed_id = failed["id"].as_i64().unwrap();
+
+    // NS and the latest revision still have the old content, and the dispatch says so
+    let (_, dispatch) = app.send(Method::GET, &uri, None, Value::Null).await;
+    assert_eq!(dispatch["title"], "Original");
+    let edits = &dispatch["pending_or_failed_edits"];
+    assert_eq!(edits["pending"], 0);
+    assert_eq!(edits["failed"], 1);
+    assert_eq!(edits["latest_failed"]["job_id"], failed_id);
+    assert_eq!(edits["latest_failed"]["created_by"], username);
+    assert_eq!(
+        edits["latest_failed"]["error_code"],
+        "region_password_required"
+    );
+
+    // the failed edit kept what it was meant to write
+    let (_, status) = app
+        .send(
+            Method::GET,
+            &format!("/queue/dispatches/{failed_id}?include_payload=true"),
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status["payload"]["edit"]["params"]["title"], "Edited");
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            &format!("{uri}/reapply/999999"),
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{error}");
+    assert_eq!(error["code"], "job_not_found");
+
+    // only edits of this dispatch are found
+    let (status, _) = app
+        .send(
+            Method::POST,
+            &format!("{uri}/reapply/{add_id}"),
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            &format!("{uri}/reapply/{failed_id}"),
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+    assert_ne!(job["id"], failed_id);
+
+    let reapplied = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(reapplied["status"], "succeeded", "job ended as {reapplied}");
+
+    // the new revision overwrote the failure
+    let (_, dispatch) = app.send(Method::GET, &uri, None, Value::Null).await;
+    assert_eq!(dispatch["title"], "Edited");
+    assert_eq!(dispatch["text"], "edited");
+    assert_eq!(dispatch["job_id"], reapplied["id"]);
+    assert_eq!(
+        dispatch["pending_or_failed_edits"],
+        json!({ "pending": 0, "failed": 0, "latest_failed": null })
+    );
+
+    // it was based on the revision the failed edit saw, which isn't the latest any more
+    let (status, error) = app
+        .send(
+            Method::POST,
+            &format!("{uri}/reapply/{failed_id}"),
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{error}");
+    assert_eq!(error["code"], "edit_conflict");
+    assert_eq!(error["details"]["revision"], dispatch["revision"]);
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            &format!("{uri}/reapply/{}", reapplied["id"]),
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{error}");
+    assert_eq!(error["code"], "not_reapplicable");
+    assert_eq!(error["details"]["status"], "succeeded");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_rejected_password_holds_nation_back() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create"]).await;
+
+    // no pin has been handed out yet, so a 403 means the password is wrong
+    app.ns
+        .fail_next_with_status("dispatch:prepare", StatusCode::FORBIDDEN);
+
+    let job = add_dispatch(&app, &token, "Rejected").await;
+
+    assert_eq!(job["status"], "failed", "job ended as {job}");
+    assert_eq!(job["error_code"], "invalid_password");
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            new_dispatch("Held back"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "credential_unhealthy");
+    assert_eq!(body["details"]["nation"], NATION);
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_verify_nation_credentials() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, admin) = app.user(&["admin"]).await;
+    let (_, token) = app.user(&["dispatches.create"]).await;
+    let uri = format!("/admin/nations/{NATION}/verify");
+
+    let (status, _) = app
+        .send(Method::POST, &uri, Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, verification) = app
+        .send(Method::POST, &uri, Some(&admin), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{verification}");
+    assert_eq!(verification["nation"], NATION);
+    assert_eq!(verification["verified"], true);
+    assert_eq!(verification["dispatch"]["pin"], true);
+    assert_eq!(verification["rmbpost"]["verified"], true);
+    assert_eq!(app.ns.commands(), vec!["ping", "ping"]);
+
+    // the pin from the check is used instead of logging in again
+    add_dispatch(&app, &token, "Verified").await;
+    let prepare = &app.ns.requests()[2];
+    assert_eq!(prepare.command, "dispatch:prepare");
+    assert!(prepare.pin.is_some());
+
+    app.ns.fail_next_with_status("ping", StatusCode::FORBIDDEN);
+
+    let (status, verification) = app
+        .send(Method::POST, &uri, Some(&admin), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{verification}");
+    assert_eq!(verification["verified"], false);
+    assert_eq!(verification["dispatch"]["verified"], false);
+    assert_eq!(verification["dispatch"]["error_code"], "invalid_password");
+    assert_eq!(verification["rmbpost"]["verified"], true);
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            new_dispatch("Held back"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "credential_unhealthy");
+
+    let (status, _) = app
+        .send(
+            Method::POST,
+            "/admin/nations/nowhere/verify",
+            Some(&admin),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_pin_refresh() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create"]).await;
+
+    let job = add_dispatch(&app, &token, "First").await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    app.ns.expire_pin();
+
+    let job = add_dispatch(&app, &token, "Second").await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    let requests = app.ns.requests();
+
+    assert_eq!(
+        requests
+            .iter()
+            .map(|request| (request.command.as_str(), request.pin.as_deref()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("dispatch:prepare", None),
+            ("dispatch:execute", Some("pin1")),
+            // rejected, then retried with the password
+            ("dispatch:prepare", Some("pin1")),
+            ("dispatch:prepare", None),
+            ("dispatch:execute", Some("pin2")),
+        ]
+    );
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_rmbpost_end_to_end() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["rmbposts.create"]).await;
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/rmbposts",
+            Some(&token),
+            json!({ "nation": NATION, "region": "testregion", "text": "Hello \u{2014} region" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    let job = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+    assert_eq!(job["rmbpost_id"], 1001);
+    assert!(
+        job["durations"]["ns_execute_ms"].as_i64() >= Some(0),
+        "{job}"
+    );
+
+    let requests = app.ns.requests();
+
+    assert_eq!(
+        app.ns.commands(),
+        vec!["rmbpost:prepare", "rmbpost:execute"]
+    );
+    assert_eq!(requests[1].param("region"), Some("testregion"));
+    assert_eq!(requests[1].param("text"), Some("Hello &#8212; region"));
+    assert_eq!(requests[1].param("token"), Some("token1"));
+
+    app.close().await;
+}
+
+/// The client's `X-Request-Id` is echoed back and stored with the job it queued.
+#[tokio::test]
+async fn test_request_id_stored_with_job() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create", "rmbposts.create"]).await;
+
+    for (uri, body, request_id, table) in [
+        (
+            "/dispatches",
+            new_dispatch("Traced"),
+            "client-dispatch-1",
+            "dispatch_queue",
+        ),
+        (
+            "/rmbposts",
+            json!({ "nation": NATION, "region": "testregion", "text": "Traced" }),
+            "client-rmbpost-1",
+            "rmbpost_queue",
+        ),
+    ] {
+        let request = axum::http::Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {token}"))
+            .header("x-request-id", request_id)
+            .body(axum::body::Body::from(body.to_string()))
+            .unwrap();
+
+        let response = app.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(response.headers()["x-request-id"], request_id);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let job: Value = serde_json::from_slice(&bytes).unwrap();
+
+        let stored: Option<String> =
+            sqlx::query_scalar(&format!("SELECT request_id FROM {table} WHERE id = $1;"))
+                .bind(job["id"].as_i64().unwrap() as i32)
+                .fetch_one(&app.pool)
+                .await
+                .unwrap();
+        assert_eq!(stored.as_deref(), Some(request_id));
+
+        // one at a time, the pipelines share the nation's login
+        app.finished_job(job["self"].as_str().unwrap(), &token)
+            .await;
+    }
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_rmbpost_batch() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (username, token) = app.user(&["rmbposts.create"]).await;
+
+    // the first region reached NS lacks an embassy
+    app.ns.fail_next(
+        "rmbpost:prepare",
+        "Your nation does not have permission to post on this region's RMB.",
+    );
+
+    let (status, group) = app
+        .send(
+            Method::POST,
+            "/rmbposts/batch",
+            Some(&token),
+            json!({
+                "nation": NATION,
+                "regions": ["Embassy Less", "bad&name", "testregion", "TestRegion"],
+                "text": "Embassy announcement",
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{group}");
+    assert_eq!(group["created_by"], username);
+
+    let jobs = group["jobs"].as_array().unwrap();
+    assert_eq!(jobs.len(), 3, "duplicate regions are posted once: {group}");
+
+    // an invalid region fails its own job, not the batch
+    assert_eq!(jobs[1]["status"], "failed");
+    assert_eq!(jobs[1]["error_code"], "invalid_region");
+
+    let group = app
+        .finished_job(group["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(group["status"], "partial", "group ended as {group}");
+
+    let jobs = group["jobs"].as_array().unwrap();
+    assert_eq!(jobs[0]["status"], "failed");
+    assert_eq!(jobs[0]["error_code"], "region_password_required");
+    assert_eq!(jobs[2]["status"], "succeeded", "{group}");
+
+    let requests = app.ns.requests();
+    let posted: Vec<_> = requests
+        .iter()
+        .filter(|request| request.command == "rmbpost:prepare")
+        .map(|request| request.param("region"))
+        .collect();
+    assert_eq!(posted, vec![Some("embassy_less"), Some("testregion")]);
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/rmbposts/batch",
+            Some(&token),
+            json!({ "nation": NATION, "regions": [], "text": "Nowhere" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["code"], "invalid_rmbpost_batch");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_webhook_deliveries_are_signed() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let receiver = axum::Router::new().route(
+        "/hook",
+        axum::routing::post(
+            move |headers: axum::http::HeaderMap, body: axum::body::Bytes| async move {
+                let signature = headers[signature::HEADER].to_str().unwrap().to_string();
+                tx.send((signature, body)).unwrap();
+                StatusCode::NO_CONTENT
+            },
+        ),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+    let (_, admin) = app.user(&["admin", "rmbposts.create"]).await;
+    let secret = "receiver chosen secret";
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/webhooks",
+            Some(&admin),
+            json!({ "url": url, "secret": "short" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["code"], "invalid_webhook_secret");
+
+    let (status, webhook) = app
+        .send(
+            Method::POST,
+            "/webhooks",
+            Some(&admin),
+            json!({ "url": url, "secret": secret }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{webhook}");
+    assert_eq!(webhook.get("secret"), None);
+    assert_eq!(webhook["signature"]["header"], signature::HEADER);
+    assert_eq!(webhook["signature"]["signed_payload"], "{timestamp}.{body}");
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/rmbposts",
+            Some(&admin),
+            json!({ "nation": NATION, "region": "testregion", "text": "Hello" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    loop {
+        let (header, body) = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .expect("no delivery")
+            .unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(signature::verify(secret, &header, &body, now), Ok(()));
+        assert_eq!(
+            signature::verify("another secret", &header, &body, now),
+            Err(signature::SignatureError::Mismatch)
+        );
+
+        let event: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(event["self"], job["self"]);
+
+        if event["status"] == "succeeded" {
+            break;
+        }
+    }
+
+    let uri = format!("/webhooks/{}/rotate-secret", webhook["id"]);
+    let (status, rotated) = app
+        .send(Method::POST, &uri, Some(&admin), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{rotated}");
+    let rotated_secret = rotated["secret"].as_str().unwrap();
+    assert_ne!(rotated_secret, secret);
+    assert!(rotated["secret_rotated_at"].is_string());
+
+    let (_, webhooks) = app
+        .send(Method::GET, "/webhooks", Some(&admin), Value::Null)
+        .await;
+    assert_eq!(webhooks.as_array().unwrap().len(), 1);
+    assert_eq!(webhooks[0].get("secret"), None);
+
+    let uri = format!("/webhooks/{}", webhook["id"]);
+    let (status, _) = app
+        .send(Method::DELETE, &uri, Some(&admin), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, error) = app
+        .send(Method::DELETE, &uri, Some(&admin), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error["code"], "webhook_not_found");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_restart_recovers_jobs() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&[]).await;
+
+    // left behind by a process that stopped before its workers were done with them
+    let dispatch = |status: &str, age: &str| {
+        let pool = app.pool.clone();
+        let (status, age) = (status.to_string(), age.to_string());
+
+        async move {
+            sqlx::query_scalar::<_, i32>(
+                "INSERT INTO dispatch_queue (type, payload, status, created_by, created_at)
+                VALUES ('add', $1, $2::job_status, 'user', NOW() - $3::INTERVAL)
+                RETURNING id;",
+            )
+            .bind(json!({ "add": new_dispatch("Recovered") }))
+            .bind(status)
+            .bind(age)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        }
+    };
+
+    let queued = dispatch("queued", "1 hour").await;
+    let interrupted = dispatch("claimed", "1 minute").await;
+    let abandoned = dispatch("queued", "2 days").await;
+    let unknown = dispatch("unknown", "1 minute").await;
+
+    let rmbpost: i32 = sqlx::query_scalar(
+        "INSERT INTO rmbpost_queue (nation, region, content, status, created_by)
+        VALUES ($1, 'testregion', 'Recovered', 'queued', 'user')
+        RETURNING id;",
+    )
+    .bind(NATION)
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+
+    let limiter = ratelimiter::new(
+        50,
+        Duration::from_secs(30),
+        Duration::from_millis(100),
+        Duration::from_millis(100),
+        Duration::from_millis(100),
+        Default::default(),
+        0.8,
+    );
+    let nations = || nations::new(&format!("{NATION}:{PASSWORD}")).unwrap();
+
+    // fresh workers, nothing is sent to them
+    let (_dispatch_tx, mut dispatch_worker) = workers::dispatch::new(
+        NATION,
+        app.ns.url(),
+        app.pool.clone(),
+        limiter.clone(),
+        nations(),
+        app.events.clone(),
+        Recovery::new(24),
+        1,
+        16,
+        workers::Activity::default(),
+    )
+    .unwrap();
+    let (_rmbpost_tx, mut rmbpost_worker) = workers::rmbpost::new(
+        NATION,
+        app.ns.url(),
+        app.pool.clone(),
+        limiter,
+        nations(),
+        app.events.clone(),
+        Recovery::new(24),
+        16,
+        workers::Activity::default(),
+    )
+    .unwrap();
+
+    tokio::spawn(async move { dispatch_worker.run().await });
+
+    let job = app
+        .finished_job(&format!("/queue/dispatches/{queued}"), &token)
+        .await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    // the workers keep separate pins for the nation, so the second one only starts once the
+    // first is done with NS, or it could log in between the first one's prepare and execute
+    tokio::spawn(async move { rmbpost_worker.run().await });
+
+    let job = app
+        .finished_job(&format!("/queue/rmbposts/{rmbpost}"), &token)
+        .await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    let job = app
+        .finished_job(&format!("/queue/dispatches/{interrupted}"), &token)
+        .await;
+    assert_eq!(job["status"], "failed", "job ended as {job}");
+    assert_eq!(job["error_code"], "interrupted");
+
+    let job = app
+        .finished_job(&format!("/queue/dispatches/{abandoned}"), &token)
+        .await;
+    assert_eq!(job["status"], "cancelled", "job ended as {job}");
+    assert_eq!(job["error_code"], "abandoned");
+
+    // looked for on NS, where it never showed up
+    let job = app
+        .finished_job(&format!("/queue/dispatches/{unknown}"), &token)
+        .await;
+    assert_eq!(job["status"], "failed", "job ended as {job}");
+    assert_eq!(job["error_code"], "not_posted");
+
+    let (lookups, commands): (Vec<_>, Vec<_>) = app
+        .ns
+        .commands()
+        .into_iter()
+        .partition(|command| command == "dispatchlist");
+    assert_eq!(lookups.len(), 1);
+
+    // only the recovered jobs reached NS
+    assert_eq!(
+        commands,
+        vec![
+            "dispatch:prepare",
+            "dispatch:execute",
+            "rmbpost:prepare",
+            "rmbpost:execute"
+        ]
+    );
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_telegram_queue_order() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["telegrams.create", "telegrams.read"]).await;
+
+    let telegram = |recipient: &str, tg_type: &str| {
+        json!({
+            "sender": NATION,
+            "id": "1",
+            "recipient": recipient,
+            "secret_key": "secret",
+            "tg_type": tg_type,
+        })
+    };
+
+    let (status, queued) = app
+        .send(
+            Method::POST,
+            "/telegrams",
+            Some(&token),
+            json!([
+                telegram("standard_one", "standard"),
+                telegram("recruit_one", "recruitment"),
+                telegram("standard_two", "standard"),
+                telegram("recruit_two", "recruitment"),
+                telegram("recruit_three", "recruitment"),
+            ]),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{queued}");
+
+    let mut recipients = Vec::new();
+
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        recipients = app
+            .ns
+            .requests()
+            .iter()
+            .filter(|request| request.command == "sendTG")
+            .map(|request| request.param("to").unwrap().to_string())
+            .collect();
+
+        if recipients.len() == 5 {
+            break;
+        }
+    }
+
+    // recruitment goes first, but standard telegrams get every other turn; each queue is FIFO
+    assert_eq!(
+        recipients,
+        vec![
+            "recruit_one",
+            "standard_one",
+            "recruit_two",
+            "standard_two",
+            "recruit_three"
+        ]
+    );
+
+    let job = app
+        .finished_job(
+            &format!("/queue/telegrams/{}", queued["queued"][0]["id"]),
+            &token,
+        )
+        .await;
+    assert_eq!(job["status"], "sent", "job ended as {job}");
+
+    let job = app
+        .finished_job(
+            &format!("/queue/telegrams/{}", queued["queued"][4]["id"]),
+            &token,
+        )
+        .await;
+    assert_eq!(job["status"], "sent", "job ended as {job}");
+
+    let (status, capacity) = app
+        .send(
+            Method::GET,
+            "/telegrams/capacity",
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{capacity}");
+    assert_eq!(capacity[NATION]["known"]["sent_last_24h"], 3);
+    assert_eq!(capacity[NATION]["known"]["queued"], 0);
+    assert_eq!(
+        capacity[NATION]["estimated"]["projected_completion_at"],
+        Value::Null
+    );
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_telegram_recipients_normalized() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["telegrams.create"]).await;
+
+    // waiting from an earlier batch, without the worker knowing about it
+    sqlx::query(
+        "INSERT INTO telegram_queue (sender, recipient, telegram_id, tg_type, status, created_by)
+        VALUES ($1, 'waiting', '1', 'standard', 'queued', 'someone');",
+    )
+    .bind(NATION)
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let telegram = |recipient: &str| {
+        json!({
+            "sender": NATION,
+            "id": "1",
+            "recipient": recipient,
+            "secret_key": "secret",
+            "tg_type": "standard",
+        })
+    };
+
+    let (status, response) = app
+        .send(
+            Method::POST,
+            "/telegrams",
+            Some(&token),
+            json!([
+                telegram("New Nation"),
+                telegram("new_nation"),
+                telegram(""),
+                telegram("region:the_north_pacific"),
+                telegram("waiting"),
+            ]),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{response}");
+
+    assert_eq!(response["queued"].as_array().unwrap().len(), 1);
+    assert_eq!(response["queued"][0]["recipient"], "new_nation");
+
+    let dropped: Vec<_> = response["dropped"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|dropped| {
+            (
+                dropped["recipient"].as_str().unwrap(),
+                dropped["reason"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        dropped,
+        vec![
+            ("new_nation", "duplicate"),
+            ("", "invalid_recipient"),
+            ("region:the_north_pacific", "invalid_recipient"),
+            ("waiting", "already_queued"),
+        ]
+    );
+    assert_eq!(response["accepted"], 1);
+    assert_eq!(response["duplicates_dropped"], 2);
+    assert_eq!(response["invalid"], json!([2, 3]));
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_large_telegram_batch() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["telegrams.create"]).await;
+
+    let telegram = |recipient: &str| {
+        json!({
+            "sender": NATION,
+            "id": "1",
+            "recipient": recipient,
+            "secret_key": "secret",
+            "tg_type": "recruitment",
+        })
+    };
+
+    let mut telegrams: Vec<_> = (0..3000)
+        .map(|i| telegram(&format!("recruit_{i}")))
+        .collect();
+    telegrams.insert(1234, telegram("region:the_north_pacific"));
+    telegrams.push(telegram("Recruit 0"));
+
+    let started = std::time::Instant::now();
+
+    let (status, response) = app
+        .send(Method::POST, "/telegrams", Some(&token), json!(telegrams))
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{response}");
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    assert_eq!(response["accepted"], 3000);
+    assert_eq!(response["duplicates_dropped"], 1);
+    assert_eq!(response["invalid"], json!([1234]));
+    assert_eq!(response["dropped"][1]["index"], 3001);
+
+    // every chunk made it into the queue, in request order
+    let queued = response["queued"].as_array().unwrap();
+    assert_eq!(queued[0]["recipient"], "recruit_0");
+    assert_eq!(queued[2999]["recipient"], "recruit_2999");
+    assert!(
+        queued
+            .windows(2)
+            .all(|pair| pair[0]["id"].as_i64() < pair[1]["id"].as_i64())
+    );
+
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM telegram_queue;")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, 3000);
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_campaign_recipients_from_region() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["telegrams.create", "telegrams.read"]).await;
+
+    app.ns.populate_region(
+        "the_north_pacific",
+        &["Listed", "founder_one", "founder_two", "old_timer"],
+        &["founder_two"],
+        &["founder_one", "founder_two", "moved_away"],
+    );
+
+    let campaign = |region: &str| {
+        json!({
+            "name": "founders",
+            "sender": NATION,
+            "telegram_id": "1",
+            "secret_key": "secret",
+            "tg_type": "recruitment",
+            "recipients": ["listed", "first"],
+            "recipients_from": {
+                "region": region,
+                "founded_since": "2026-10-01T00:00:00Z",
+                "non_wa": true,
+            },
+            "paused": true,
+        })
+    };
+
+    let (status, created) = app
+        .send(
+            Method::POST,
+            "/telegrams/campaigns",
+            Some(&token),
+            campaign("The North Pacific"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{created}");
+    assert_eq!(created["status"], "paused");
+    assert_eq!(created["recipients"], 3);
+    assert_eq!(created["expanded_recipients"], 1);
+
+    let (status, recipients) = app
+        .send(
+            Method::GET,
+            &format!("/telegrams/campaigns/{}/recipients", created["id"]),
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{recipients}");
+    assert_eq!(
+        recipients,
+        json!([
+            { "position": 1, "recipient": "listed", "status": "pending" },
+            { "position": 2, "recipient": "first", "status": "pending" },
+            { "position": 3, "recipient": "founder_one", "status": "pending" },
+        ])
+    );
+
+    // nothing goes out while paused
+    assert!(!app.ns.commands().contains(&"sendTG".to_string()));
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/telegrams/campaigns",
+            Some(&token),
+            campaign("nowhere"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{error}");
+    assert_eq!(error["code"], "region_not_found");
+
+    app.ns.populate_region("empty", &["old_timer"], &[], &[]);
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/telegrams/campaigns",
+            Some(&token),
+            campaign("empty"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{error}");
+    assert_eq!(error["code"], "empty_recipient_source");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_retention_sweep() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create", "dispatches.edit"]).await;
+
+    let job = add_dispatch(&app, &token, "Kept").await;
+    let uri = format!("/dispatches/{}", job["dispatch_id"]);
+
+    for title in ["Second", "Third"] {
+        let (_, job) = app
+            .send(
+                Method::PUT,
+                &uri,
+                Some(&token),
+                json!({ "title": title, "text": "edited", "category": 1, "subcategory": 100 }),
+            )
+            .await;
+        app.finished_job(job["self"].as_str().unwrap(), &token)
+            .await;
+    }
+
+    // the add and the first edit finished long ago, the last edit stays recent
+    sqlx::query(
+        "UPDATE dispatch_queue SET finished_at = NOW() - INTERVAL '100 days'
+        WHERE id < (SELECT MAX(id) FROM dispatch_queue);",
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let policy = retention::Policy {
+        dispatch_queue_days: 90,
+        dispatch_revisions: 1,
+        dry_run: true,
+        ..retention::Policy::default()
+    };
+
+    let pruned = |tables: Vec<PrunedTable>| {
+        tables
+            .into_iter()
+            .map(|table| (table.table, table.rows))
+            .collect::<Vec<_>>()
+    };
+
+    let expected = vec![
+        ("dispatch_queue".to_string(), 2),
+        ("dispatch_content".to_string(), 2),
+    ];
+
+    assert_eq!(
+        pruned(retention::sweep(&app.pool, &policy).await.unwrap()),
+        expected
+    );
+
+    let remaining = || async {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT (SELECT COUNT(*) FROM dispatch_queue) + (SELECT COUNT(*) FROM dispatch_content);",
+        )
+        .fetch_one(&app.pool)
+        .await
+        .unwrap()
+    };
+    assert_eq!(remaining().await, 6);
+
+    let policy = retention::Policy {
+        dry_run: false,
+        ..policy
+    };
+
+    assert_eq!(
+        pruned(retention::sweep(&app.pool, &policy).await.unwrap()),
+        expected
+    );
+    assert_eq!(remaining().await, 2);
+
+    let (_, dispatch) = app.send(Method::GET, &uri, None, Value::Null).await;
+    assert_eq!(dispatch["title"], "Third");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_disabled_user_cannot_authenticate() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, admin) = app.user(&["admin"]).await;
+    let (username, token) = app.user(&["dispatches.create"]).await;
+
+    let (status, key) = app
+        .send(
+            Method::POST,
+            "/users/me/api-keys",
+            Some(&token),
+            json!({ "name": "ci" }),
+        )
+        .await;
+    assert!(status.is_success(), "{key}");
+    let key = key["key"].as_str().unwrap().to_string();
+
+    let with_key = || {
+        axum::http::Request::builder()
+            .uri("/users/me")
+            .header("X-API-Key", &key)
+            .body(axum::body::Body::empty())
+            .unwrap()
+    };
+    let response = app.router.clone().oneshot(with_key()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let (status, _) = app
+        .send(Method::GET, "/admin/users", Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, users) = app
+        .send(
+            Method::GET,
+            &format!("/admin/users?username={}", username.to_uppercase()),
+            Some(&admin),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{users}");
+    assert_eq!(users.as_array().unwrap().len(), 1, "{users}");
+    assert_eq!(users[0]["username"], username);
+    assert_eq!(users[0]["claims"], json!(["dispatches.create"]));
+    assert!(users[0]["last_login_at"].is_string(), "{users}");
+    assert!(users[0]["disabled_at"].is_null());
+
+    let (status, disabled) = app
+        .send(
+            Method::DELETE,
+            &format!("/admin/users/{}", users[0]["id"]),
+            Some(&admin),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{disabled}");
+    assert!(disabled["disabled_at"].is_string(), "{disabled}");
+
+    // the token issued before the account was disabled is refused
+    let (status, error) = app
+        .send(Method::GET, "/users/me", Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["code"], "account_disabled");
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/login/refresh",
+            None,
+            json!({ "token": token }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["code"], "account_disabled");
+
+    let response = app.router.clone().oneshot(with_key()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let error: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["code"], "account_disabled");
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/login",
+            None,
+            json!({ "username": username, "password": "password123" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["code"], "account_disabled");
+
+    // a wrong password doesn't learn that the account exists but is disabled
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/login",
+            None,
+            json!({ "username": username, "password": "wrong password" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(error["code"], "unauthorized");
+
+    let (status, error) = app
+        .send(
+            Method::DELETE,
+            "/admin/users/999999",
+            Some(&admin),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error["code"], "user_not_found");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_backed_up_worker_defers_jobs() {
+    let Some(app) = TestApp::start_with(&[
+        ("worker_channel_capacity", "1"),
+        ("worker_send_timeout_ms", "200"),
+    ])
+    .await
+    else {
+        return;
+    };
+
+    let (_, token) = app.user(&["rmbposts.create", "stats.read"]).await;
+    let rmbpost = |text: &str| json!({ "nation": NATION, "region": "testregion", "text": text });
+
+    // the worker waits on NS while it posts, and doesn't read its channel meanwhile
+    app.ns.set_latency(Duration::from_millis(1500));
+
+    let (status, first) = app
+        .send(Method::POST, "/rmbposts", Some(&token), rmbpost("first"))
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{first}");
+
+    tokio::time::sleep(workers::PERIOD * 3).await;
+
+    // one post fills the channel and waits there, the other finds it full
+    let ((second, third), metrics) = tokio::join!(
+        async {
+            tokio::join!(
+                app.send(Method::POST, "/rmbposts", Some(&token), rmbpost("second")),
+                app.send(Method::POST, "/rmbposts", Some(&token), rmbpost("third")),
+            )
+        },
+        async {
+            tokio::time::sleep(Duration::from_millis(600)).await;
+            download(&app, "/metrics", &token, "text/plain").await
+        },
+    );
+
+    let metrics = std::str::from_utf8(metrics.body()).unwrap();
+    assert!(metrics.contains("eurocore_worker_channel_commands{pipeline=\"rmbpost\"} 1\n"));
+    assert!(metrics.contains("eurocore_worker_channel_capacity{pipeline=\"rmbpost\"} 1\n"));
+
+    let mut responses = [second, third];
+    responses.sort_by_key(|(status, _)| *status);
+    let [(status, queued), (backed_up, error)] = responses;
+
+    assert_eq!(status, StatusCode::ACCEPTED, "{queued}");
+    assert_eq!(backed_up, StatusCode::SERVICE_UNAVAILABLE, "{error}");
+    assert_eq!(error["code"], "pipeline_backed_up");
+    assert_eq!(error["details"]["pipeline"], "rmbpost");
+
+    let deferred = error["details"]["deferred_jobs"][0].as_i64().unwrap();
+    let uri = format!("/queue/rmbposts/{deferred}");
+
+    let (_, job) = app.send(Method::GET, &uri, Some(&token), Value::Null).await;
+    assert_eq!(job["status"], "deferred", "{job}");
+
+    app.ns.set_latency(Duration::ZERO);
+
+    // the deferred post is picked up once the worker is free, and nothing is posted twice
+    for uri in [
+        first["self"].as_str().unwrap(),
+        queued["self"].as_str().unwrap(),
+        &uri,
+    ] {
+        let job = app.finished_job(uri, &token).await;
+        assert_eq!(job["status"], "succeeded", "job ended as {job}");
+    }
+
+    assert_eq!(app.ns.commands().len(), 6);
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_concurrent_jobs_respect_quota() {
+    let Some(app) = TestApp::start_with(&[("queue_quota_pending", "1")]).await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["rmbposts.create"]).await;
+    let rmbpost = |text: &str| json!({ "nation": NATION, "region": "testregion", "text": text });
+
+    // keeps the accepted post pending while the others are checked
+    app.ns.set_latency(Duration::from_millis(1500));
+
+    let responses = futures_util::future::join_all(
+        ["first", "second", "third", "fourth"]
+            .map(|text| app.send(Method::POST, "/rmbposts", Some(&token), rmbpost(text))),
+    )
+    .await;
+
+    let accepted = responses
+        .iter()
+        .filter(|(status, _)| *status == StatusCode::ACCEPTED)
+        .count();
+    assert_eq!(accepted, 1, "{responses:?}");
+
+    for (status, body) in &responses {
+        if *status != StatusCode::ACCEPTED {
+            assert_eq!(*status, StatusCode::TOO_MANY_REQUESTS, "{body}");
+            assert_eq!(body["code"], "quota_exceeded");
+        }
+    }
+
+    app.ns.set_latency(Duration::ZERO);
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_tenants_are_isolated() {
+    let nations = format!("{NATION}:{PASSWORD},allied_nation:{PASSWORD}");
+    let Some(app) = TestApp::start_with(&[("dispatch_nations", nations.as_str())]).await else {
+        return;
+    };
+
+    let claims = [
+        "admin",
+        "dispatches.create",
+        "dispatches.edit",
+        "dispatches.edit.any",
+        "dispatches.delete",
+        "dispatches.delete.any",
+        "rmbposts.create",
+    ];
+
+    let (operator_name, operator) = app.user(&claims).await;
+    let (ally_name, stale_token) = app.user(&claims).await;
+
+    let (status, tenant) = app
+        .send(
+            Method::POST,
+            "/admin/tenants",
+            Some(&operator),
+            json!({ "name": "allies", "nations": ["Allied Nation"] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{tenant}");
+    assert_eq!(tenant["nations"], json!(["allied_nation"]));
+
+    let (_, account) = app
+        .send(
+            Method::GET,
+            &format!("/users/username/{ally_name}"),
+            Some(&operator),
+            Value::Null,
+        )
+        .await;
+    let (status, account) = app
+        .send(
+            Method::PUT,
+            &format!("/admin/users/{}/tenant", account["id"]),
+            Some(&operator),
+            json!({ "tenant_id": tenant["id"] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{account}");
+    assert_eq!(account["tenant_id"], tenant["id"]);
+
+    // a token names the tenant it was issued for
+    let (status, _) = app
+        .send(Method::GET, "/users/me", Some(&stale_token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, login) = app
+        .send(
+            Method::POST,
+            "/login",
+            None,
+            json!({ "username": ally_name, "password": "password123" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{login}");
+    let ally = login["token"].as_str().unwrap().to_string();
+
+    let ours = add_dispatch(&app, &operator, "Ours").await;
+    assert_eq!(ours["status"], "succeeded", "{ours}");
+
+    let mut dispatch = new_dispatch("Theirs");
+    dispatch["nation"] = json!("allied_nation");
+    let (status, job) = app
+        .send(Method::POST, "/dispatches", Some(&ally), dispatch)
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+    let theirs = app.finished_job(job["self"].as_str().unwrap(), &ally).await;
+    assert_eq!(theirs["status"], "succeeded", "{theirs}");
+
+    // each tenant posts only as its own nations
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&ally),
+            new_dispatch("Not ours"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{error}");
+    assert_eq!(error["code"], "nation_not_in_tenant");
+
+    let mut dispatch = new_dispatch("Not ours either");
+    dispatch["nation"] = json!("allied_nation");
+    let (status, _) = app
+        .send(Method::POST, "/dispatches", Some(&operator), dispatch)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // neither sees, edits nor deletes the other's dispatches or jobs
+    for (token, own, other) in [(&ally, &theirs, &ours), (&operator, &ours, &theirs)] {
+        let uri = format!("/dispatches/{}", other["dispatch_id"]);
+
+        let (status, _) = app.send(Method::GET, &uri, Some(token), Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = app
+            .send(
+                Method::GET,
+                &format!("{uri}/metrics"),
+                Some(token),
+                Value::Null,
+            )
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = app
+            .send(
+                Method::PUT,
+                &uri,
+                Some(token),
+                json!({ "title": "Taken over", "text": "text", "category": 1, "subcategory": 100 }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = app
+            .send(Method::DELETE, &uri, Some(token), Value::Null)
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = app
+            .send(
+                Method::GET,
+                &format!("/queue/dispatches/{}", other["id"]),
+                Some(token),
+                Value::Null,
+            )
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, listed) = app
+            .send(Method::GET, "/dispatches", Some(token), Value::Null)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{listed}");
+        let ids: Vec<&Value> = listed
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|dispatch| &dispatch["id"])
+            .collect();
+        assert_eq!(ids, vec![&own["dispatch_id"]]);
+    }
+
+    // anonymous reads see only the default tenant's dispatches
+    let uri = format!("/dispatches/{}", theirs["dispatch_id"]);
+    for uri in [uri.clone(), format!("{uri}/metrics")] {
+        let (status, _) = app.send(Method::GET, &uri, None, Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    let (status, _) = app
+        .send(
+            Method::GET,
+            &format!("/dispatches/{}", ours["dispatch_id"]),
+            None,
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // RMB posts are scoped like dispatches
+    let rmbpost = json!({ "nation": NATION, "region": "testregion", "text": "hello" });
+
+    let (status, error) = app
+        .send(Method::POST, "/rmbposts", Some(&ally), rmbpost.clone())
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{error}");
+    assert_eq!(error["code"], "nation_not_in_tenant");
+
+    let (status, job) = app
+        .send(Method::POST, "/rmbposts", Some(&operator), rmbpost)
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    let uri = job["self"].as_str().unwrap();
+    let (status, _) = app.send(Method::GET, uri, Some(&ally), Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = app
+        .send(Method::GET, uri, Some(&operator), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // the other tenants' admins only manage their own users and see their own audit trail
+    let (_, account) = app
+        .send(
+            Method::GET,
+            &format!("/users/username/{operator_name}"),
+            Some(&operator),
+            Value::Null,
+        )
+        .await;
+    let (status, _) = app
+        .send(
+            Method::DELETE,
+            &format!("/admin/users/{}", account["id"]),
+            Some(&ally),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, users) = app
+        .send(Method::GET, "/admin/users", Some(&ally), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{users}");
+    assert_eq!(users.as_array().unwrap().len(), 1, "{users}");
+    assert_eq!(users[0]["username"], ally_name);
+
+    let (status, entries) = app
+        .send(Method::GET, "/admin/audit", Some(&ally), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{entries}");
+    assert!(
+        entries
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|entry| entry["username"] == ally_name),
+        "{entries}"
+    );
+
+    // shared infrastructure stays with the operators
+    let (status, _) = app
+        .send(
+            Method::POST,
+            "/admin/pipelines/dispatch/pause",
+            Some(&ally),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // only the default tenant's admins manage tenants
+    let (status, _) = app
+        .send(Method::GET, "/admin/tenants", Some(&ally), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, tenants) = app
+        .send(Method::GET, "/admin/tenants", Some(&operator), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{tenants}");
+    assert_eq!(tenants[1]["name"], "allies");
+    assert_eq!(tenants[1]["users"], 1);
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_worker_status() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["admin", "dispatches.create"]).await;
+
+    let dispatch_worker = || async {
+        let (status, workers) = app
+            .send(Method::GET, "/admin/workers", Some(&token), Value::Null)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{workers}");
+        assert_eq!(workers.as_object().unwrap().len(), 3, "{workers}");
+
+        workers["dispatch"].clone()
+    };
+
+    let worker = dispatch_worker().await;
+    assert_eq!(worker["state"], "idle", "{worker}");
+    assert!(worker["current_job"].is_null());
+    assert!(worker["last_success_at"].is_null());
+    assert!(worker["last_error"].is_null());
+
+    // slow enough to catch the worker while NS has the job
+    app.ns.set_latency(Duration::from_millis(500));
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            new_dispatch("Slow"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    let worker = loop {
+        let worker = dispatch_worker().await;
+
+        if worker["state"] == "posting" {
+            break worker;
+        }
+
+        assert!(
+            Instant::now() < deadline,
+            "never saw the job posting: {worker}"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    assert_eq!(worker["current_job"]["id"], job["id"]);
+    assert_eq!(worker["current_job"]["nation"], NATION);
+
+    let finished = app
+        .finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+    assert_eq!(finished["status"], "succeeded", "{finished}");
+
+    let worker = dispatch_worker().await;
+    assert_eq!(worker["state"], "idle", "{worker}");
+    assert!(worker["current_job"].is_null());
+    assert!(worker["last_success_at"].is_string(), "{worker}");
+    assert!(worker["last_error"].is_null());
+
+    app.ns.set_latency(Duration::ZERO);
+    app.ns.fail_next(
+        "dispatch:execute",
+        "This nation does not have permission to post in that category.",
+    );
+
+    let failed = add_dispatch(&app, &token, "Rejected").await;
+    assert_eq!(failed["status"], "failed", "{failed}");
+
+    let worker = dispatch_worker().await;
+    assert_eq!(worker["state"], "idle", "{worker}");
+    assert!(
+        worker["last_error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Region password required"),
+        "{worker}"
+    );
+    assert!(worker["last_error"]["at"].is_string());
+
+    app.send(
+        Method::POST,
+        "/admin/pipelines/dispatch/pause",
+        Some(&token),
+        Value::Null,
+    )
+    .await;
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(&token),
+            new_dispatch("Held"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    loop {
+        let worker = dispatch_worker().await;
+
+        if worker["queue_depth"] == 1 {
+            assert_eq!(worker["state"], "paused", "{worker}");
+            break;
+        }
+
+        assert!(
+            Instant::now() < deadline,
+            "never saw the job queued: {worker}"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    app.close().await;
+}
+
+/// Every route that changes something, with a body the handler would reject before it got to
+/// the credentials if the routing let it.
+const MUTATIONS: &[(&str, &str)] = &[
+    ("POST", "/dispatches"),
+    ("POST", "/dispatches/multi"),
+    ("POST", "/dispatches/import"),
+    ("PUT", "/dispatches/1"),
+    ("DELETE", "/dispatches/1"),
+    ("POST", "/dispatches/1/reapply/1"),
+    ("POST", "/dispatches/1/protect"),
+    ("POST", "/dispatches/1/unprotect"),
+    ("POST", "/telegrams"),
+    ("DELETE", "/telegrams"),
+    ("DELETE", "/telegrams/1"),
+    ("POST", "/telegrams/campaigns"),
+    ("POST", "/telegrams/campaigns/1/pause"),
+    ("POST", "/telegrams/campaigns/1/resume"),
+    ("POST", "/rmbposts"),
+    ("POST", "/rmbposts/batch"),
+    ("PATCH", "/users/me/password"),
+    ("POST", "/users/me/api-keys"),
+    ("DELETE", "/users/me/api-keys/1"),
+    ("PATCH", "/users/1/password"),
+    ("DELETE", "/admin/users/1"),
+    ("PUT", "/admin/users/1/tenant"),
+    ("POST", "/admin/tenants"),
+    ("PATCH", "/admin/ratelimiter"),
+    ("PATCH", "/admin/logging"),
+    ("POST", "/admin/pipelines/dispatch/pause"),
+    ("POST", "/admin/pipelines/dispatch/resume"),
+    ("POST", "/admin/nations/testlandia/revalidate"),
+    ("POST", "/admin/content-rules"),
+    ("POST", "/webhooks"),
+    ("DELETE", "/webhooks/1"),
+    ("POST", "/webhooks/1/rotate-secret"),
+];
+
+async fn assert_mutations_need_credentials(app: &TestApp) {
+    for (method, uri) in MUTATIONS {
+        let method = Method::from_bytes(method.as_bytes()).unwrap();
+
+        let (status, error) = app.send(method.clone(), uri, None, json!({})).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{method} {uri}: {error}");
+        assert_eq!(error["code"], "no_credentials", "{method} {uri}");
+    }
+}
+
+/// The routes of the public tier, for a dispatch written by [`NATION`].
+fn public_reads(dispatch_id: i64) -> [String; 4] {
+    [
+        "/dispatches".to_string(),
+        format!("/dispatches/{dispatch_id}"),
+        "/dispatches/categories".to_string(),
+        format!("/nations/{NATION}/dispatches"),
+    ]
+}
+
+#[tokio::test]
+async fn test_public_read_tier() {
+    let Some(app) = TestApp::start_with(&[("public_read_max_requests", "4")]).await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create"]).await;
+    let job = add_dispatch(&app, &token, "Public").await;
+    let dispatch_id = job["dispatch_id"].as_i64().unwrap();
+
+    for uri in public_reads(dispatch_id) {
+        let (status, body) = app.send(Method::GET, &uri, None, Value::Null).await;
+        assert_eq!(status, StatusCode::OK, "{uri}: {body}");
+    }
+
+    let (status, error) = app
+        .send(Method::GET, "/dispatches", None, Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(error["code"], "public_read_throttled");
+    assert!(error["details"]["retry_after_secs"].as_u64() > Some(0));
+
+    // credentials lift the per-address limit
+    let (status, categories) = app
+        .send(
+            Method::GET,
+            "/dispatches/categories",
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(categories[0]["name"], "Factbook");
+    assert_eq!(categories[0]["subcategories"][0]["id"], 100);
+
+    assert_mutations_need_credentials(&app).await;
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_public_read_disabled() {
+    let Some(app) = TestApp::start_with(&[("public_read", "false")]).await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create"]).await;
+    let job = add_dispatch(&app, &token, "Private").await;
+    let dispatch_id = job["dispatch_id"].as_i64().unwrap();
+
+    for uri in public_reads(dispatch_id) {
+        let (status, error) = app.send(Method::GET, &uri, None, Value::Null).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{uri}: {error}");
+        assert_eq!(error["code"], "no_credentials");
+
+        let (status, body) = app.send(Method::GET, &uri, Some(&token), Value::Null).await;
+        assert_eq!(status, StatusCode::OK, "{uri}: {body}");
+    }
+
+    assert_mutations_need_credentials(&app).await;
+
+    app.close().await;
+}
+
+/// A route of each group with the claim it's declared with in the router.
+const CLAIMED_ROUTES: &[(&str, &str, &str)] = &[
+    ("POST", "/dispatches", "dispatches.create"),
+    ("PUT", "/dispatches/1", "dispatches.edit"),
+    ("DELETE", "/dispatches/1", "dispatches.delete"),
+    ("POST", "/dispatches/1/reapply/1", "dispatches.edit"),
+    ("POST", "/dispatches/1/protect", "admin"),
+    ("GET", "/telegrams", "telegrams.read"),
+    ("POST", "/telegrams", "telegrams.create"),
+    ("DELETE", "/telegrams/1", "telegrams.delete"),
+    ("GET", "/queue/telegrams/1", "telegrams.read"),
+    ("POST", "/rmbposts", "rmbposts.create"),
+    ("GET", "/stats/dispatches", "stats.read"),
+    ("GET", "/stats/dispatches/largest", "admin"),
+    ("GET", "/stats/dispatches/top", "stats.read"),
+    ("GET", "/export/dispatches", "export.read"),
+    ("GET", "/admin/permissions", "admin"),
+    ("GET", "/admin/workers", "admin"),
+    ("GET", "/admin/tenants", "admin"),
+    ("PUT", "/admin/nations/testlandia/presets", "admin"),
+    ("POST", "/admin/content-rules", "admin"),
+    ("PATCH", "/admin/logging", "admin"),
+    ("PATCH", "/users/1/password", "admin"),
+    ("GET", "/webhooks", "admin"),
+];
+
+#[tokio::test]
+async fn test_route_claims() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (status, permissions) = app
+        .send(Method::GET, "/admin/permissions", None, Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{permissions}");
+
+    for (method, uri, claim) in CLAIMED_ROUTES {
+        let method = Method::from_bytes(method.as_bytes()).unwrap();
+
+        // everything else, admin included unless it's the claim in question, isn't enough
+        let others: Vec<_> = Claim::ALL
+            .iter()
+            .map(|claim| claim.as_str())
+            .filter(|other| other != claim && *other != "admin")
+            .collect();
+        let (_, denied) = app.user(&others).await;
+
+        let (status, error) = app
+            .send(method.clone(), uri, Some(&denied), json!({}))
+            .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{method} {uri}: {error}");
+        assert_eq!(error["code"], "unauthorized", "{method} {uri}");
+
+        let (_, allowed) = app.user(&[claim]).await;
+
+        let (status, body) = app
+            .send(method.clone(), uri, Some(&allowed), json!({}))
+            .await;
+        assert_ne!(status, StatusCode::UNAUTHORIZED, "{method} {uri}: {body}");
+    }
+
+    let (_, admin) = app.user(&["admin"]).await;
+
+    let (status, permissions) = app
+        .send(Method::GET, "/admin/permissions", Some(&admin), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(permissions.as_array().unwrap().len(), Claim::ALL.len());
+    assert!(
+        permissions
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|permission| permission["claim"] == "dispatches.edit.any"
+                && permission["description"].is_string()),
+        "{permissions}"
+    );
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_application_serves_until_shutdown() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let application = app.application().await;
+    let Address::Tcp(address) = application.local_addr().unwrap() else {
+        panic!("expected a TCP listener");
+    };
+    assert!(address.ip().is_loopback());
+    assert_ne!(address.port(), 0);
+
+    let (shutdown, signal) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(application.serve_with_shutdown(async {
+        signal.await.ok();
+    }));
+
+    let response = reqwest::get(format!("http://{address}/heartbeat"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    shutdown.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server never shut down")
+        .unwrap()
+        .unwrap();
+
+    assert!(
+        reqwest::get(format!("http://{address}/heartbeat"))
+            .await
+            .is_err()
+    );
+
+    app.close().await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_application_serves_on_unix_socket() {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    async fn heartbeat(path: &std::path::Path) -> String {
+        let mut stream = tokio::net::UnixStream::connect(path).await.unwrap();
+        stream
+            .write_all(b"GET /heartbeat HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    let directory =
+        std::env::temp_dir().join(format!("eurocore-{}", rand::random_range(0..u32::MAX)));
+    std::fs::create_dir(&directory).unwrap();
+    let path = directory.join("eurocore.sock");
+    let overrides = [
+        ("unix_socket_path", path.to_str().unwrap()),
+        ("unix_socket_mode", "600"),
+    ];
+
+    // left behind by a server that was killed
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let application = app.application_with(&overrides).await.unwrap();
+    assert_eq!(
+        application.local_addr().unwrap(),
+        Address::Unix(path.clone())
+    );
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+
+    // a live socket isn't taken over
+    assert!(matches!(
+        app.application_with(&overrides).await,
+        Err(ConfigError::Listener(_))
+    ));
+
+    let (shutdown, signal) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(application.serve_with_shutdown(async {
+        signal.await.ok();
+    }));
+
+    let response = heartbeat(&path).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+    shutdown.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server never shut down")
+        .unwrap()
+        .unwrap();
+
+    assert!(!path.exists());
+
+    // a regular file in the way is refused rather than deleted
+    std::fs::write(&path, "").unwrap();
+    assert!(matches!(
+        app.application_with(&overrides).await,
+        Err(ConfigError::Listener(_))
+    ));
+    assert!(path.exists());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_bootstrap() {
+    let Some(app) = TestApp::start_with(&[("queue_quota_daily", "50")]).await else {
+        return;
+    };
+
+    let (status, _) = app.send(Method::GET, "/bootstrap", None, Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (username, token) = app.user(&["dispatches.create"]).await;
+
+    let (status, body) = app
+        .send(Method::GET, "/bootstrap", Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["version"], 1);
+    assert_eq!(body["user"]["username"], username);
+    assert_eq!(body["user"]["claims"], json!(["dispatches.create"]));
+    assert_eq!(body["dispatch_nations"], json!([NATION]));
+    assert_eq!(body["rmbpost_nations"], json!([NATION]));
+    assert_eq!(body["categories"][0]["name"], "Factbook");
+    assert_eq!(body["limits"]["dispatches"]["max_text_length"], 200_000);
+    assert_eq!(
+        body["limits"]["dispatches"]["text_formats"],
+        json!(["bbcode", "markdown"])
+    );
+    assert_eq!(body["limits"]["rmbposts"]["max_batch_size"], 20);
+    assert_eq!(body["limits"]["quota"]["max_daily"], 50);
+
+    let (_, admin) = app.user(&["admin"]).await;
+    let (_, body) = app
+        .send(Method::GET, "/bootstrap", Some(&admin), Value::Null)
+        .await;
+    assert!(body["limits"]["quota"]["max_pending"].is_null());
+    assert!(body["limits"]["quota"]["max_daily"].is_null());
+
+    app.close().await;
+}
+
+/// Requests other tools sharing the IP make, as NS reports them, count against our budget.
+#[tokio::test]
+async fn test_observed_ratelimit() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app
+        .user(&["admin", "dispatches.create", "stats.read"])
+        .await;
+
+    app.ns.share_ip(40);
+    post_dispatch(&app, &token, "Observed", "text", (1, 100)).await;
+
+    let (status, limiter) = app
+        .send(Method::GET, "/admin/ratelimiter", Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let seen = limiter["observed"]["seen"].as_u64().unwrap();
+    assert!(seen > 40, "{limiter}");
+    assert_eq!(limiter["observed"]["limit"], 50);
+    assert_eq!(limiter["external_requests"], 40);
+    assert_eq!(limiter["utilization_warnings"], 1);
+
+    let response = download(&app, "/metrics", &token, "text/plain").await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let metrics = std::str::from_utf8(response.body()).unwrap();
+    assert!(metrics.contains(&format!("eurocore_ratelimit_observed_requests {seen}\n")));
+    assert!(metrics.contains("eurocore_ratelimit_external_requests 40\n"));
+    assert!(metrics.contains("# TYPE eurocore_ratelimit_utilization_warnings_total counter\n"));
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_logging() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["admin"]).await;
+
+    let (status, body) = app
+        .send(
+            Method::PATCH,
+            "/admin/logging",
+            Some(&token),
+            json!({ "level": "eurocore=loud" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    assert_eq!(body["code"], "invalid_log_level");
+
+    // tests leave the subscriber to the test harness
+    let (status, body) = app
+        .send(
+            Method::PATCH,
+            "/admin/logging",
+            Some(&token),
+            json!({ "level": "info,eurocore=debug" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "{body}");
+    assert_eq!(body["code"], "logging_unavailable");
+
+    let (status, _) = app
+        .send(Method::GET, "/admin/logging", Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    app.close().await;
+}
+
+const SLOW_NATIONS: [&str; 3] = ["alpha", "beta", "gamma"];
+
+/// Posts two dispatches for each of [`SLOW_NATIONS`] on an NS taking 150ms per request, and
+/// returns how long that took and the requests NS saw.
+async fn post_on_slow_ns(concurrency: &str) -> Option<(Duration, Vec<Request>)> {
+    let nations = SLOW_NATIONS
+        .map(|nation| format!("{nation}:{PASSWORD}"))
+        .join(",");
+
+    let app = TestApp::start_with(&[
+        ("dispatch_nations", &nations),
+        ("dispatch_concurrency", concurrency),
+    ])
+    .await?;
+
+    app.ns.set_latency(Duration::from_millis(150));
+    let (_, token) = app.user(&["dispatches.create"]).await;
+
+    let started = Instant::now();
+    let mut jobs = Vec::new();
+
+    for round in 0..2 {
+        for nation in SLOW_NATIONS {
+            let mut dispatch = new_dispatch(&format!("{nation} {round}"));
+            dispatch["nation"] = json!(nation);
+
+            let (status, job) = app
+                .send(Method::POST, "/dispatches", Some(&token), dispatch)
+                .await;
+            assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+            jobs.push(job["self"].as_str().unwrap().to_string());
+        }
+    }
+
+    for job in jobs {
+        let job = app.finished_job(&job, &token).await;
+        assert_eq!(job["status"], "succeeded", "job ended as {job}");
+    }
+
+    let elapsed = started.elapsed();
+    let requests = app.ns.requests();
+
+    app.close().await;
+
+    Some((elapsed, requests))
+}
+
+/// Jobs for different nations are posted side by side, each nation's still one after another
+/// and within its cooldowns.
+#[tokio::test]
+async fn test_concurrent_dispatches() {
+    let Some((sequential, _)) = post_on_slow_ns("1").await else {
+        return;
+    };
+    let (concurrent, requests) = post_on_slow_ns("3").await.unwrap();
+
+    assert!(
+        concurrent * 3 < sequential * 2,
+        "{concurrent:?} concurrently, {sequential:?} one at a time"
+    );
+
+    for nation in SLOW_NATIONS {
+        let requests = requests
+            .iter()
+            .filter(|request| request.param("nation") == Some(nation))
+            .collect::<Vec<_>>();
+
+        let commands = requests
+            .iter()
+            .map(|request| request.command.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            commands,
+            [
+                "dispatch:prepare",
+                "dispatch:execute",
+                "dispatch:prepare",
+                "dispatch:execute"
+            ]
+        );
+
+        let titles = requests
+            .iter()
+            .filter(|request| request.command == "dispatch:prepare")
+            .map(|request| request.param("title").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, [format!("{nation} 0"), format!("{nation} 1")]);
+
+        // the restricted cooldown of the test limiter
+        assert!(requests[2].at - requests[0].at >= Duration::from_millis(100));
+    }
+
+    // the test limiter allows 50 requests per 30 seconds
+    assert!(requests.len() <= 50);
+    assert!(requests.last().unwrap().at - requests[0].at < Duration::from_secs(30));
+
+    // another nation's request landed while one was still being answered
+    assert!(requests.windows(2).any(|pair| {
+        pair[0].param("nation") != pair[1].param("nation")
+            && pair[1].at - pair[0].at < Duration::from_millis(150)
+    }));
+}
+
+#[tokio::test]
+async fn test_health() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (status, body) = app.send(Method::GET, "/health", None, Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["database"]["reachable"], true);
+    assert_eq!(body["database"]["max_connections"], 5);
+    assert!(body["database"]["connections"].as_u64().unwrap() >= 1);
+    assert!(body["database"]["latency_ms"].is_u64());
+
+    let (_, pipelines) = app
+        .send(
+            Method::GET,
+            "/admin/pipelines",
+            Some(&app.user(&["admin"]).await.1),
+            Value::Null,
+        )
+        .await;
+    assert!(
+        pipelines
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|pipeline| pipeline["unwritten"] == 0)
+    );
+
+    app.pool.close().await;
+
+    let (status, body) = app.send(Method::GET, "/health", None, Value::Null).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "unavailable");
+    assert_eq!(body["database"]["reachable"], false);
+    assert!(body["database"]["latency_ms"].is_null());
+
+    app.close().await;
+}
+
+/// Posts a dispatch through the worker and returns its NS id.
+async fn post_dispatch(
+    app: &TestApp,
+    token: &str,
+    title: &str,
+    text: &str,
+    (category, subcategory): (i16, i16),
+) -> i64 {
+    let mut dispatch = new_dispatch(title);
+    dispatch["text"] = json!(text);
+    dispatch["category"] = json!(category);
+    dispatch["subcategory"] = json!(subcategory);
+
+    let (status, job) = app
+        .send(Method::POST, "/dispatches", Some(token), dispatch)
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+
+    let job = app.finished_job(job["self"].as_str().unwrap(), token).await;
+    assert_eq!(job["status"], "succeeded", "job ended as {job}");
+
+    job["dispatch_id"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn test_dispatch_search() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["dispatches.create", "dispatches.delete"]).await;
+
+    let titled = post_dispatch(
+        &app,
+        &token,
+        "Embassy policy",
+        "Who we exchange with.",
+        (1, 100),
+    )
+    .await;
+    // long enough to be stored compressed
+    let text = format!(
+        "{} Our embassy policy was debated. {}",
+        "Filler text. ".repeat(100),
+        "More filler. ".repeat(100)
+    );
+    let mentioned = post_dispatch(&app, &token, "Regional news", &text, (3, 305)).await;
+    let deleted = post_dispatch(&app, &token, "Old embassy policy", "Superseded.", (1, 100)).await;
+    post_dispatch(&app, &token, "Unrelated", "Nothing to see.", (1, 100)).await;
+
+    let uri = format!("/dispatches/{deleted}");
+    let (status, job) = app
+        .send(Method::DELETE, &uri, Some(&token), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+    app.finished_job(job["self"].as_str().unwrap(), &token)
+        .await;
+
+    let search = |query: &str| {
+        let uri = format!("/dispatches/search?{query}");
+        let app = &app;
+
+        async move { app.send(Method::GET, &uri, None, Value::Null).await }
+    };
+    let ids = |results: &Value| {
+        results
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["id"].as_i64().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // title matches outrank text matches
+    let (status, results) = search("q=embassy%20policy").await;
+    assert_eq!(status, StatusCode::OK, "{results}");
+    assert_eq!(ids(&results), vec![titled, mentioned]);
+    assert!(results[0]["rank"].as_f64() > results[1]["rank"].as_f64());
+    assert_eq!(
+        results[0]["title_highlight"],
+        "<mark>Embassy</mark> <mark>policy</mark>"
+    );
+    assert!(
+        results[1]["snippet"]
+            .as_str()
+            .unwrap()
+            .contains("<mark>embassy</mark> <mark>policy</mark>"),
+        "{}",
+        results[1]
+    );
+
+    let (_, results) = search("q=embassy&include_inactive=true").await;
+    assert_eq!(ids(&results).len(), 3);
+    assert!(ids(&results).contains(&deleted));
+
+    let (_, results) = search("q=embassy&category=3").await;
+    assert_eq!(ids(&results), vec![mentioned]);
+
+    let (_, results) = search("q=embassy&nation=someone_else").await;
+    assert_eq!(ids(&results), Vec::<i64>::new());
+
+    let (_, results) = search("q=embassy&limit=1&offset=1").await;
+    assert_eq!(ids(&results), vec![mentioned]);
+
+    let (status, error) = search("q=%20").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["code"], "invalid_query");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_telegram_recipient_filters() {
+    let dump = std::env::temp_dir().join(format!(
+        "eurocore_nations_{}.xml",
+        rand::random_range(0..u32::MAX)
+    ));
+    std::fs::write(
+        &dump,
+        "<NATIONS>
+        <NATION><NAME>Local</NAME><REGION>Europeia</REGION><UNSTATUS>Non-member</UNSTATUS><FOUNDEDTIME>1790000000</FOUNDEDTIME></NATION>
+        <NATION><NAME>Member</NAME><REGION>Elsewhere</REGION><UNSTATUS>WA Member</UNSTATUS><FOUNDEDTIME>1790000000</FOUNDEDTIME></NATION>
+        <NATION><NAME>Old Timer</NAME><REGION>Elsewhere</REGION><UNSTATUS>Non-member</UNSTATUS><FOUNDEDTIME>1000000000</FOUNDEDTIME></NATION>
+        <NATION><NAME>Target</NAME><REGION>Elsewhere</REGION><UNSTATUS>Non-member</UNSTATUS><FOUNDEDTIME>1790000000</FOUNDEDTIME></NATION>
+        </NATIONS>",
+    )
+    .unwrap();
+
+    let Some(app) = TestApp::start_with(&[("nations_dump", dump.to_str().unwrap())]).await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["telegrams.create"]).await;
+
+    let telegram = |recipient: &str| {
+        json!({
+            "sender": NATION,
+            "id": "1",
+            "recipient": recipient,
+            "secret_key": "secret",
+            "tg_type": "recruitment",
+        })
+    };
+    let batch = json!([
+        telegram("Local"),
+        telegram("Member"),
+        telegram("Old Timer"),
+        telegram("Target"),
+        telegram("Founded Since The Dump"),
+    ]);
+    let uri =
+        "/telegrams?exclude_region=Europeia&exclude_wa=true&founded_after=2020-01-01T00:00:00Z";
+
+    // indexed in the background
+    let mut response = Value::Null;
+
+    for _ in 0..50 {
+        let status;
+        (status, response) = app
+            .send(Method::POST, uri, Some(&token), batch.clone())
+            .await;
+
+        if status != StatusCode::SERVICE_UNAVAILABLE {
+            assert_eq!(status, StatusCode::ACCEPTED, "{response}");
+            break;
+        }
+
+        assert_eq!(response["code"], "nations_dump_unavailable");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(response["accepted"], 2, "{response}");
+    assert_eq!(
+        response["filtered"],
+        json!({ "exclude_region": 1, "exclude_wa": 1, "founded_after": 1 })
+    );
+    assert_eq!(response["dropped"][2]["reason"], "founded_after");
+
+    let queued: Vec<_> = response["queued"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|queued| queued["recipient"].as_str().unwrap())
+        .collect();
+    assert_eq!(queued, vec!["target", "founded_since_the_dump"]);
+
+    let (status, created) = app
+        .send(
+            Method::POST,
+            "/telegrams/campaigns",
+            Some(&token),
+            json!({
+                "name": "filtered",
+                "sender": NATION,
+                "telegram_id": "2",
+                "secret_key": "secret",
+                "tg_type": "recruitment",
+                "recipients": ["local", "member", "target"],
+                "filter": { "exclude_region": "europeia", "exclude_wa": true },
+                "paused": true,
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{created}");
+    assert_eq!(created["recipients"], 1);
+    assert_eq!(
+        created["filtered"],
+        json!({ "exclude_region": 1, "exclude_wa": 1 })
+    );
+
+    app.close().await;
+    std::fs::remove_file(dump).unwrap();
+}
+
+#[tokio::test]
+async fn test_telegram_recipient_filters_need_dump() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["telegrams.create"]).await;
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/telegrams?exclude_wa=true",
+            Some(&token),
+            json!([{
+                "sender": NATION,
+                "id": "1",
+                "recipient": "target",
+                "secret_key": "secret",
+                "tg_type": "recruitment",
+            }]),
+        )
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "{error}");
+    assert_eq!(error["code"], "nations_dump_unavailable");
+
+    app.close().await;
+}
+
+#[tokio::test]
+async fn test_telegram_type_mismatch() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    let (_, token) = app.user(&["telegrams.create", "telegrams.read"]).await;
+
+    let telegram = |recipient: &str, tg_type: &str| {
+        json!({
+            "sender": NATION,
+            "id": "7",
+            "recipient": recipient,
+            "secret_key": "secret",
+            "tg_type": tg_type,
+        })
+    };
+
+    // NS holds the template to the recruitment cooldown
+    app.ns
+        .fail_next("sendTG", "API Recruitment TG rate-limit exceeded.");
+
+    let (status, queued) = app
+        .send(
+            Method::POST,
+            "/telegrams",
+            Some(&token),
+            json!([
+                telegram("first", "standard"),
+                telegram("second", "standard")
+            ]),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{queued}");
+
+    // the refused telegram is sent again, both as recruitment telegrams
+    for queued in queued["queued"].as_array().unwrap() {
+        let job = app
+            .finished_job(&format!("/queue/telegrams/{}", queued["id"]), &token)
+            .await;
+        assert_eq!(job["status"], "sent", "job ended as {job}");
+    }
+
+    let recipients: Vec<_> = app
+        .ns
+        .requests()
+        .into_iter()
+        .filter(|request| request.command == "sendTG")
+        .map(|request| request.param("to").unwrap().to_string())
+        .collect();
+    assert_eq!(recipients, vec!["first", "first", "second"]);
+
+    let (_, capacity) = app
+        .send(
+            Method::GET,
+            "/telegrams/capacity",
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(capacity[NATION]["known"]["sent_last_24h"], 2, "{capacity}");
+
+    let recorded: String =
+        sqlx::query_scalar("SELECT tg_type FROM telegram_types WHERE telegram_id = '7';")
+            .fetch_one(&app.pool)
+            .await
+            .unwrap();
+    assert_eq!(recorded, "recruitment");
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/telegrams",
+            Some(&token),
+            json!([
+                telegram("third", "recruitment"),
+                telegram("fourth", "standard")
+            ]),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{error}");
+    assert_eq!(error["code"], "telegram_type_mismatch");
+    assert_eq!(
+        error["details"],
+        json!({ "telegram_id": "7", "tg_type": "recruitment" })
+    );
+
+    let (status, error) = app
+        .send(
+            Method::POST,
+            "/telegrams/campaigns",
+            Some(&token),
+            json!({
+                "name": "mismatched",
+                "sender": NATION,
+                "telegram_id": "7",
+                "secret_key": "secret",
+                "tg_type": "standard",
+                "recipients": ["fifth"],
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{error}");
+    assert_eq!(error["code"], "telegram_type_mismatch");
+
+    let (status, queued) = app
+        .send(
+            Method::POST,
+            "/telegrams",
+            Some(&token),
+            json!([telegram("third", "recruitment")]),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{queued}");
+
+    app.close().await;
+}
+
+/// What the command line does without a server, on the database of a running app.
+#[tokio::test]
+async fn test_admin_commands() {
+    let Some(app) = TestApp::start().await else {
+        return;
+    };
+
+    // the app ran every migration as it started
+    assert!(crate::migrate(&app.pool).await.unwrap().is_empty());
+
+    let (username, _) = app.user(&[]).await;
+    let users = crate::user_controller(&app.config(), app.pool.clone()).unwrap();
+
+    // granting again changes nothing
+    for _ in 0..2 {
+        let mut account = users
+            .grant(&username, &[Claim::DispatchesCreate, Claim::Admin])
+            .await
+            .unwrap();
+        account.claims.sort();
+        assert_eq!(account.claims, vec!["admin", "dispatches.create"]);
+    }
+
+    assert!(matches!(
+        users.grant("nobody", &[Claim::Admin]).await,
+        Err(crate::core::error::Error::UserNotFound)
+    ));
+
+    let (status, login) = app
+        .send(
+            Method::POST,
+            "/login",
+            None,
+            json!({ "username": username, "password": "password123" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let token = login["token"].as_str().unwrap();
+
+    app.send(
+        Method::POST,
+        "/admin/pipelines/dispatch/pause",
+        Some(token),
+        Value::Null,
+    )
+    .await;
+
+    let (status, job) = app
+        .send(
+            Method::POST,
+            "/dispatches",
+            Some(token),
+            new_dispatch("Stuck"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{job}");
+    let job_id = job["id"].as_i64().unwrap() as i32;
+
+    // left queued for an hour, as if its worker had lost it
+    sqlx::query("UPDATE dispatch_queue SET modified_at = NOW() - INTERVAL '1 hour' WHERE id = $1;")
+        .bind(job_id)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let cutoff = chrono::Utc::now() - chrono::Duration::minutes(30);
+
+    let requeued = workers::requeue_stuck(&app.pool, "dispatch_queue", cutoff)
+        .await
+        .unwrap();
+    assert_eq!(requeued, vec![job_id]);
+
+    assert!(
+        workers::requeue_stuck(&app.pool, "rmbpost_queue", cutoff)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    app.send(
+        Method::POST,
+        "/admin/pipelines/dispatch/resume",
+        Some(token),
+        Value::Null,
+    )
+    .await;
+
+    let job = app.finished_job(job["self"].as_str().unwrap(), token).await;
+    assert_eq!(job["status"], "succeeded", "{job}");
+
+    // the job was in the channel twice, and posted once
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(
+        app.ns.commands(),
+        vec!["dispatch:prepare", "dispatch:execute"]
+    );
+
+    app.close().await;
+}
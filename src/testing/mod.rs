@@ -0,0 +1,7 @@
+//! End-to-end test support: the full app against a mock NS API and a scratch database.
+
+mod app;
+#[cfg(feature = "client")]
+mod client;
+mod e2e;
+mod ns;
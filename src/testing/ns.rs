@@ -0,0 +1,492 @@
+//! A stand-in for the NS API speaking just enough of it for the workers: the prepare/execute
+//! flow of dispatches and RMB posts, `sendTG`, pings, region, dispatch, dispatch list and
+//! happenings lookups, with NS's pin handling. Every response carries NS's ratelimit headers,
+//! and can be held back to simulate a slow NS.
+
+use axum::Router;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The password of every nation, anything else is rejected with 403.
+pub(crate) const PASSWORD: &str = "password";
+
+/// Requests NS takes from one IP per window.
+const RATELIMIT: usize = 50;
+
+/// One request as NS saw it.
+#[derive(Clone, Debug)]
+pub(crate) struct Request {
+    /// `dispatch:prepare`, `rmbpost:execute`, `sendTG`, `ping`, `region`, `nations`, `wanations`,
+    /// `happenings`, `dispatch` or `dispatchlist`
+    pub(crate) command: String,
+    /// query or form parameters
+    pub(crate) params: HashMap<String, String>,
+    pub(crate) pin: Option<String>,
+    /// when NS got to it, after any latency
+    pub(crate) at: Instant,
+}
+
+impl Request {
+    pub(crate) fn param(&self, key: &str) -> Option<&str> {
+        self.params.get(key).map(String::as_str)
+    }
+}
+
+enum Failure {
+    /// answered with 200 and the error in the body, as NS does for most failures
+    Error(String),
+    Status(StatusCode),
+    /// went through with the request, then answered with the status, as NS does when it times
+    /// out behind its gateway
+    Lost(StatusCode),
+}
+
+#[derive(Default)]
+struct Ns {
+    requests: Vec<Request>,
+    /// pin handed out on the last login with a password, by nation
+    pins: HashMap<String, String>,
+    logins: u32,
+    tokens: u32,
+    /// last dispatch or RMB post id handed out
+    last_id: i32,
+    failures: VecDeque<(String, Failure)>,
+    /// dispatches readable through the public API, by id, as `(author, title, text)`
+    dispatches: HashMap<i32, (String, String, String)>,
+    /// views and score of the public dispatches that report them, by id
+    metrics: HashMap<i32, (i32, i32)>,
+    /// when the public dispatches posted through the mock were created, as unix timestamps, by
+    /// id; the rest show up in dispatch lists as created long ago
+    created: HashMap<i32, i64>,
+    /// region members by region, see [`MockNs::populate_region`]
+    regions: HashMap<String, Region>,
+    /// requests other tools sharing the IP made, see [`MockNs::share_ip`]
+    others: usize,
+    /// how long every request waits before it's answered, see [`MockNs::set_latency`]
+    latency: Duration,
+}
+
+#[derive(Clone, Default)]
+struct Region {
+    nations: Vec<String>,
+    wa_members: Vec<String>,
+    /// reported in the founding happenings, whatever `sincetime` asks for
+    founders: Vec<String>,
+}
+
+#[derive(Clone)]
+pub(crate) struct MockNs {
+    url: String,
+    ns: Arc<Mutex<Ns>>,
+}
+
+impl MockNs {
+    pub(crate) async fn start() -> Self {
+        let ns = Arc::new(Mutex::new(Ns {
+            last_id: 1000,
+            ..Ns::default()
+        }));
+
+        let app = Router::new()
+            .route("/", get(query).post(form))
+            .layer(middleware::from_fn_with_state(ns.clone(), ratelimit))
+            .layer(middleware::from_fn_with_state(ns.clone(), delay))
+            .with_state(ns.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Self {
+            url: format!("http://{address}/"),
+            ns,
+        }
+    }
+
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub(crate) fn requests(&self) -> Vec<Request> {
+        self.ns.lock().unwrap().requests.clone()
+    }
+
+    pub(crate) fn commands(&self) -> Vec<String> {
+        self.requests()
+            .into_iter()
+            .map(|request| request.command)
+            .collect()
+    }
+
+    /// The next `command` is answered with `error` in NS's XML, or as text for `sendTG`.
+    pub(crate) fn fail_next(&self, command: &str, error: &str) {
+        self.ns
+            .lock()
+            .unwrap()
+            .failures
+            .push_back((command.to_string(), Failure::Error(error.to_string())));
+    }
+
+    /// The next `command` is answered with `status` and an empty body.
+    pub(crate) fn fail_next_with_status(&self, command: &str, status: StatusCode) {
+        self.ns
+            .lock()
+            .unwrap()
+            .failures
+            .push_back((command.to_string(), Failure::Status(status)));
+    }
+
+    /// The next `command` goes through, then is answered with `status` and an empty body.
+    pub(crate) fn lose_next(&self, command: &str, status: StatusCode) {
+        self.ns
+            .lock()
+            .unwrap()
+            .failures
+            .push_back((command.to_string(), Failure::Lost(status)));
+    }
+
+    /// Makes a dispatch readable through the public API, as if it had been posted on the site.
+    pub(crate) fn publish_dispatch(&self, id: i32, author: &str, title: &str, text: &str) {
+        self.ns.lock().unwrap().dispatches.insert(
+            id,
+            (author.to_string(), title.to_string(), text.to_string()),
+        );
+    }
+
+    /// Has the public API report `views` and `score` for dispatch `id`, once it's published.
+    pub(crate) fn set_dispatch_metrics(&self, id: i32, views: i32, score: i32) {
+        self.ns.lock().unwrap().metrics.insert(id, (views, score));
+    }
+
+    /// Gives `region` its nations, World Assembly members and recent founders. Unknown regions
+    /// are answered with 404.
+    pub(crate) fn populate_region(
+        &self,
+        region: &str,
+        nations: &[&str],
+        wa_members: &[&str],
+        founders: &[&str],
+    ) {
+        let owned = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+
+        self.ns.lock().unwrap().regions.insert(
+            region.to_string(),
+            Region {
+                nations: owned(nations),
+                wa_members: owned(wa_members),
+                founders: owned(founders),
+            },
+        );
+    }
+
+    /// Counts `requests` by someone else against the window reported in the ratelimit headers.
+    pub(crate) fn share_ip(&self, requests: usize) {
+        self.ns.lock().unwrap().others += requests;
+    }
+
+    /// Holds back every request for `latency` before answering it.
+    pub(crate) fn set_latency(&self, latency: Duration) {
+        self.ns.lock().unwrap().latency = latency;
+    }
+
+    /// Forgets the pins handed out, so the next request sending one is rejected.
+    pub(crate) fn expire_pin(&self) {
+        self.ns.lock().unwrap().pins.clear();
+    }
+}
+
+impl Ns {
+    fn failure(&mut self, command: &str) -> Option<Failure> {
+        let index = self
+            .failures
+            .iter()
+            .position(|(failing, _)| failing == command)?;
+
+        self.failures.remove(index).map(|(_, failure)| failure)
+    }
+
+    /// Checks the credentials of `nation` the way NS does: a pin, when sent, must be its current
+    /// one, and a password alone logs in and hands out a new pin.
+    fn authenticate(
+        &mut self,
+        nation: &str,
+        headers: &HeaderMap,
+    ) -> Result<Option<String>, StatusCode> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .filter(|value| !value.is_empty())
+        };
+
+        match header("X-Pin") {
+            Some(pin) if self.pins.get(nation).map(String::as_str) == Some(pin) => Ok(None),
+            Some(_) => Err(StatusCode::FORBIDDEN),
+            None if header("X-Password") == Some(PASSWORD) => {
+                self.logins += 1;
+                let pin = format!("pin{}", self.logins);
+                self.pins.insert(nation.to_string(), pin.clone());
+
+                Ok(Some(pin))
+            }
+            None => Err(StatusCode::FORBIDDEN),
+        }
+    }
+
+    fn record(&mut self, command: &str, params: HashMap<String, String>, headers: &HeaderMap) {
+        self.requests.push(Request {
+            command: command.to_string(),
+            params,
+            pin: headers
+                .get("X-Pin")
+                .and_then(|value| value.to_str().ok())
+                .filter(|value| !value.is_empty())
+                .map(str::to_string),
+            at: Instant::now(),
+        });
+    }
+
+    /// Answers a private command, i.e. the prepare or execute step of a dispatch or RMB post.
+    fn command(&mut self, params: &HashMap<String, String>, headers: &HeaderMap) -> Response {
+        let command = format!(
+            "{}:{}",
+            params.get("c").map_or("", String::as_str),
+            params.get("mode").map_or("", String::as_str)
+        );
+
+        self.record(&command, params.clone(), headers);
+
+        let nation = params.get("nation").map_or("", String::as_str);
+
+        let pin = match self.authenticate(nation, headers) {
+            Ok(pin) => pin,
+            Err(status) => return status.into_response(),
+        };
+
+        let body = match self.failure(&command) {
+            Some(Failure::Status(status)) => return status.into_response(),
+            Some(Failure::Lost(status)) => {
+                if command == "dispatch:execute" && !params.contains_key("dispatchid") {
+                    self.last_id += 1;
+
+                    let param = |key: &str| params.get(key).cloned().unwrap_or_default();
+                    self.dispatches.insert(
+                        self.last_id,
+                        (nation.to_string(), param("title"), param("text")),
+                    );
+                    self.created
+                        .insert(self.last_id, chrono::Utc::now().timestamp());
+                }
+
+                return status.into_response();
+            }
+            Some(Failure::Error(error)) => xml("ERROR", &error),
+            None => match command.as_str() {
+                "dispatch:prepare" | "rmbpost:prepare" => {
+                    self.tokens += 1;
+                    xml("SUCCESS", &format!("token{}", self.tokens))
+                }
+                "dispatch:execute" => {
+                    let id = match params.get("dispatchid") {
+                        Some(id) => id.parse().unwrap(),
+                        None => {
+                            self.last_id += 1;
+                            self.last_id
+                        }
+                    };
+
+                    xml("SUCCESS", &format!("page=dispatch/id={id}"))
+                }
+                "rmbpost:execute" => {
+                    self.last_id += 1;
+                    let id = self.last_id;
+
+                    xml(
+                        "SUCCESS",
+                        &format!("page=display_region_rmb?postid={id}#p{id}"),
+                    )
+                }
+                _ => return StatusCode::BAD_REQUEST.into_response(),
+            },
+        };
+
+        match pin {
+            Some(pin) => ([("X-Pin", pin)], body).into_response(),
+            None => body.into_response(),
+        }
+    }
+}
+
+/// Adds the headers NS reports its count of the window's requests in. The window never ends.
+async fn ratelimit(
+    State(ns): State<Arc<Mutex<Ns>>>,
+    request: axum::extract::Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+
+    let seen = {
+        let ns = ns.lock().unwrap();
+        ns.requests.len() + ns.others
+    };
+
+    let headers = response.headers_mut();
+    headers.insert("RateLimit-Limit", HeaderValue::from(RATELIMIT));
+    headers.insert(
+        "RateLimit-Remaining",
+        HeaderValue::from(RATELIMIT.saturating_sub(seen)),
+    );
+
+    response
+}
+
+async fn delay(
+    State(ns): State<Arc<Mutex<Ns>>>,
+    request: axum::extract::Request,
+    next: Next,
+) -> Response {
+    let latency = ns.lock().unwrap().latency;
+    tokio::time::sleep(latency).await;
+
+    next.run(request).await
+}
+
+fn xml(tag: &str, text: &str) -> String {
+    format!("<NATION><{tag}>{text}</{tag}></NATION>")
+}
+
+async fn form(State(ns): State<Arc<Mutex<Ns>>>, headers: HeaderMap, body: String) -> Response {
+    // the workers send their forms without a content type, as NS accepts
+    let params: HashMap<String, String> = serde_urlencoded::from_str(&body).unwrap();
+
+    ns.lock().unwrap().command(&params, &headers)
+}
+
+async fn query(
+    State(ns): State<Arc<Mutex<Ns>>>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let mut ns = ns.lock().unwrap();
+
+    if params.contains_key("c") {
+        return ns.command(&params, &headers);
+    }
+
+    let command = match (params.get("a"), params.get("q")) {
+        (Some(action), _) if action == "sendTG" => "sendTG",
+        (_, Some(shard)) if shard == "ping" => "ping",
+        (_, Some(shard)) if shard == "name" && params.contains_key("region") => "region",
+        (_, Some(shard)) if shard == "nations" && params.contains_key("region") => "nations",
+        (_, Some(shard)) if shard == "wanations" && params.contains_key("region") => "wanations",
+        (_, Some(shard)) if shard == "happenings" => "happenings",
+        (_, Some(shard)) if shard == "dispatch" => "dispatch",
+        (_, Some(shard)) if shard == "dispatchlist" => "dispatchlist",
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    ns.record(command, params.clone(), &headers);
+
+    match ns.failure(command) {
+        Some(Failure::Status(status) | Failure::Lost(status)) => status.into_response(),
+        Some(Failure::Error(error)) if command == "sendTG" => error.into_response(),
+        Some(Failure::Error(error)) => xml("ERROR", &error).into_response(),
+        None if command == "sendTG" => "queued\n".into_response(),
+        None if command == "ping" => match ns.authenticate(&params["nation"], &headers) {
+            Ok(Some(pin)) => ([("X-Pin", pin)], xml("PING", "1")).into_response(),
+            Ok(None) => xml("PING", "1").into_response(),
+            Err(status) => status.into_response(),
+        },
+        None if command == "dispatch" => {
+            let id = params
+                .get("dispatchid")
+                .and_then(|id| id.parse().ok())
+                .unwrap_or_default();
+
+            let metrics = ns
+                .metrics
+                .get(&id)
+                .map(|(views, score)| format!("<VIEWS>{views}</VIEWS><SCORE>{score}</SCORE>"))
+                .unwrap_or_default();
+
+            match ns.dispatches.get(&id) {
+                Some((author, title, text)) => format!(
+                    "<WORLD><DISPATCH id=\"{id}\"><TITLE>{title}</TITLE><AUTHOR>{author}</AUTHOR>\
+                    <CATEGORY>Factbook</CATEGORY><SUBCATEGORY>Overview</SUBCATEGORY>{metrics}\
+                    <TEXT><![CDATA[{text}]]></TEXT></DISPATCH></WORLD>"
+                )
+                .into_response(),
+                None => StatusCode::NOT_FOUND.into_response(),
+            }
+        }
+        None if command == "dispatchlist" => {
+            let nation = &params["nation"];
+
+            let dispatches: String = ns
+                .dispatches
+                .iter()
+                .filter(|(_, (author, _, _))| author == nation)
+                .map(|(id, (author, title, _))| {
+                    let created = ns.created.get(id).copied().unwrap_or_default();
+
+                    format!(
+                        "<DISPATCH id=\"{id}\"><TITLE>{title}</TITLE><AUTHOR>{author}</AUTHOR>\
+                        <CATEGORY>Factbook</CATEGORY><SUBCATEGORY>Overview</SUBCATEGORY>\
+                        <CREATED>{created}</CREATED><EDITED>0</EDITED></DISPATCH>"
+                    )
+                })
+                .collect();
+
+            format!("<NATION id=\"{nation}\"><DISPATCHLIST>{dispatches}</DISPATCHLIST></NATION>")
+                .into_response()
+        }
+        None if command == "nations" || command == "wanations" => {
+            let id = &params["region"];
+
+            match ns.regions.get(id) {
+                Some(region) if command == "nations" => format!(
+                    "<REGION id=\"{id}\"><NATIONS>{}</NATIONS></REGION>",
+                    region.nations.join(":")
+                )
+                .into_response(),
+                Some(region) => format!(
+                    "<REGION id=\"{id}\"><UNNATIONS>{}</UNNATIONS></REGION>",
+                    region.wa_members.join(",")
+                )
+                .into_response(),
+                None => StatusCode::NOT_FOUND.into_response(),
+            }
+        }
+        None if command == "happenings" => {
+            let id = params
+                .get("view")
+                .and_then(|view| view.strip_prefix("region."))
+                .unwrap_or_default();
+
+            let events: String = ns
+                .regions
+                .get(id)
+                .map(|region| region.founders.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .map(|nation| {
+                    format!("<EVENT><TEXT>@@{nation}@@ was founded in %%{id}%%.</TEXT></EVENT>")
+                })
+                .collect();
+
+            format!("<WORLD><HAPPENINGS>{events}</HAPPENINGS></WORLD>").into_response()
+        }
+        None => format!(
+            "<REGION id=\"{0}\"><NAME>{0}</NAME></REGION>",
+            params["region"]
+        )
+        .into_response(),
+    }
+}
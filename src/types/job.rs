@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Where a dispatch or RMB post job is, stored as the `job_status` Postgres enum. Jobs only move
+/// along the edges given by [`JobStatus::follows`]; workers refuse any other change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "job_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// waiting for a later start time before it's queued
+    Scheduled,
+    /// waiting for the worker
+    Queued,
+    /// accepted while the worker's channel was full; the worker takes it over once it has room
+    Deferred,
+    /// picked up by the worker, which is waiting for a rate limit slot
+    Claimed,
+    /// the prepare request has been sent to NS
+    Posting,
+    /// the execute request failed without saying whether NS posted the dispatch; the worker is
+    /// looking for it on NS before settling the job
+    Unknown,
+    Succeeded,
+    Failed,
+    Cancelled,
+    /// replaced, before it was posted, by a later edit of the same dispatch
+    Superseded,
+}
+
+impl JobStatus {
+    /// Statuses of a job that's due and not finished: waiting for the worker, or on its way to
+    /// NS. Scheduled jobs aren't due yet. Bound as an array, `status = ANY($n)`, wherever queries
+    /// ask what's still pending.
+    pub(crate) const PENDING: &'static [JobStatus] = &[
+        JobStatus::Queued,
+        JobStatus::Deferred,
+        JobStatus::Claimed,
+        JobStatus::Posting,
+        JobStatus::Unknown,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Scheduled => "scheduled",
+            JobStatus::Queued => "queued",
+            JobStatus::Deferred => "deferred",
+            JobStatus::Claimed => "claimed",
+            JobStatus::Posting => "posting",
+            JobStatus::Unknown => "unknown",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Superseded => "superseded",
+        }
+    }
+
+    /// Whether the job is done with, one way or another.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Succeeded | JobStatus::Failed | JobStatus::Cancelled | JobStatus::Superseded
+        )
+    }
+
+    /// The statuses a job can be in to move to this one.
+    pub(crate) fn follows(&self) -> &'static [JobStatus] {
+        match self {
+            JobStatus::Scheduled => &[],
+            JobStatus::Queued => &[JobStatus::Scheduled, JobStatus::Deferred],
+            JobStatus::Deferred => &[JobStatus::Queued],
+            JobStatus::Claimed => &[JobStatus::Queued],
+            JobStatus::Posting => &[JobStatus::Claimed],
+            JobStatus::Unknown => &[JobStatus::Posting],
+            JobStatus::Succeeded => &[JobStatus::Posting, JobStatus::Unknown],
+            // credentials can fail before anything is sent
+            JobStatus::Failed => &[JobStatus::Claimed, JobStatus::Posting, JobStatus::Unknown],
+            // once claimed the job may already be on its way to NS
+            JobStatus::Cancelled => &[JobStatus::Scheduled, JobStatus::Queued],
+            JobStatus::Superseded => &[JobStatus::Queued],
+        }
+    }
+}
+
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [JobStatus; 10] = [
+        JobStatus::Scheduled,
+        JobStatus::Queued,
+        JobStatus::Deferred,
+        JobStatus::Claimed,
+        JobStatus::Posting,
+        JobStatus::Unknown,
+        JobStatus::Succeeded,
+        JobStatus::Failed,
+        JobStatus::Cancelled,
+        JobStatus::Superseded,
+    ];
+
+    fn can_become(from: JobStatus, to: JobStatus) -> bool {
+        to.follows().contains(&from)
+    }
+
+    #[test]
+    fn test_happy_path() {
+        assert!(can_become(JobStatus::Scheduled, JobStatus::Queued));
+        assert!(can_become(JobStatus::Queued, JobStatus::Claimed));
+        assert!(can_become(JobStatus::Claimed, JobStatus::Posting));
+        assert!(can_become(JobStatus::Posting, JobStatus::Succeeded));
+        assert!(can_become(JobStatus::Posting, JobStatus::Failed));
+        // a job the worker had no room for, and then took over
+        assert!(can_become(JobStatus::Queued, JobStatus::Deferred));
+        assert!(can_become(JobStatus::Deferred, JobStatus::Queued));
+        // an execute request that failed ambiguously, settled once NS has been checked
+        assert!(can_become(JobStatus::Posting, JobStatus::Unknown));
+        assert!(can_become(JobStatus::Unknown, JobStatus::Succeeded));
+        assert!(can_become(JobStatus::Unknown, JobStatus::Failed));
+    }
+
+    #[test]
+    fn test_illegal_transitions() {
+        // skipping a phase
+        assert!(!can_become(JobStatus::Queued, JobStatus::Posting));
+        assert!(!can_become(JobStatus::Queued, JobStatus::Succeeded));
+        assert!(!can_become(JobStatus::Claimed, JobStatus::Succeeded));
+        // going backwards
+        assert!(!can_become(JobStatus::Posting, JobStatus::Claimed));
+        assert!(!can_become(JobStatus::Claimed, JobStatus::Queued));
+        // cancelling a job NS may already have seen
+        assert!(!can_become(JobStatus::Posting, JobStatus::Cancelled));
+        assert!(!can_become(JobStatus::Unknown, JobStatus::Cancelled));
+        // only a job the worker hasn't picked up can be replaced
+        assert!(!can_become(JobStatus::Claimed, JobStatus::Superseded));
+        // a deferred job goes back through the queue
+        assert!(!can_become(JobStatus::Deferred, JobStatus::Claimed));
+        assert!(!can_become(JobStatus::Claimed, JobStatus::Deferred));
+        // nothing is ever scheduled after the fact
+        assert!(
+            ALL.iter()
+                .all(|status| !can_become(*status, JobStatus::Scheduled))
+        );
+    }
+
+    #[test]
+    fn test_finished_jobs_stay_finished() {
+        for status in ALL.iter().filter(|status| status.is_finished()) {
+            assert!(
+                ALL.iter().all(|next| !can_become(*status, *next)),
+                "{status} can be left"
+            );
+        }
+    }
+
+    #[test]
+    fn test_pending_jobs_are_unfinished() {
+        for status in ALL {
+            assert_eq!(
+                JobStatus::PENDING.contains(&status),
+                !status.is_finished() && status != JobStatus::Scheduled,
+                "{status}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_names_match_serde() {
+        for status in ALL {
+            assert_eq!(
+                serde_json::to_value(status).unwrap(),
+                serde_json::Value::String(status.to_string())
+            );
+        }
+    }
+}
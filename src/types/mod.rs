@@ -1,5 +1,8 @@
+pub(crate) mod job;
 pub(crate) mod request;
 pub(crate) mod response;
+pub(crate) mod secret;
 pub(crate) mod user;
 
+pub(crate) use secret::Secret;
 pub(crate) use user::*;
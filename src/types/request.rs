@@ -1,4 +1,12 @@
-use serde::Deserialize;
+use crate::controllers::content_policy::RuleAction;
+use crate::sync::ratelimiter::RestrictedAction;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Correlates log lines and queued jobs with the HTTP request that caused them.
+#[derive(Clone, Debug)]
+pub(crate) struct RequestId(pub(crate) String);
 
 #[derive(Deserialize)]
 pub(crate) struct LoginData {
@@ -6,7 +14,316 @@ pub(crate) struct LoginData {
     pub(crate) password: String,
 }
 
+/// Body of `POST /dispatches/preview`.
+#[derive(Deserialize)]
+pub(crate) struct PreviewData {
+    pub(crate) text: String,
+}
+
+/// Body of `POST /dispatches/import`.
+#[derive(Deserialize)]
+pub(crate) struct ImportDispatch {
+    pub(crate) dispatch_id: i32,
+    /// the author, which must be one of the dispatch nations
+    pub(crate) nation: String,
+}
+
 #[derive(Deserialize)]
 pub(crate) struct UpdatePasswordData {
     pub(crate) new_password: String,
 }
+
+#[derive(Deserialize)]
+pub(crate) struct RefreshData {
+    pub(crate) token: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NewApiKey {
+    pub(crate) name: String,
+    /// must be a subset of the creator's claims
+    #[serde(default)]
+    pub(crate) claims: Vec<String>,
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NewWebhook {
+    /// receives a signed `POST` for every job status change
+    pub(crate) url: String,
+    /// signs the deliveries, chosen by whoever runs the receiver
+    pub(crate) secret: String,
+}
+
+/// A content rule as created or replaced under `/admin/content-rules`.
+#[derive(Deserialize)]
+pub(crate) struct ContentRule {
+    /// what refusals and warnings call the rule
+    pub(crate) name: String,
+    /// regex matched against the title and text, ignoring case
+    pub(crate) pattern: String,
+    pub(crate) action: RuleAction,
+}
+
+/// A tenant as created at `POST /admin/tenants`.
+#[derive(Deserialize)]
+pub(crate) struct NewTenant {
+    pub(crate) name: String,
+    /// nations only the tenant's users may post as, taken from any tenant they belonged to
+    #[serde(default)]
+    pub(crate) nations: Vec<String>,
+}
+
+/// Body of `PUT /admin/users/{id}/tenant`.
+#[derive(Deserialize)]
+pub(crate) struct UserTenant {
+    pub(crate) tenant_id: i32,
+}
+
+/// What `PUT /admin/nations/{name}/presets` sets, replacing the nation's preset as a whole.
+#[derive(Deserialize)]
+pub(crate) struct NationPreset {
+    /// used when a new dispatch leaves out its category, given together with the subcategory
+    pub(crate) default_category: Option<i16>,
+    pub(crate) default_subcategory: Option<i16>,
+    /// put before the title of new dispatches, as is, so any separator is part of it
+    pub(crate) title_prefix: Option<String>,
+    /// appended to the text of new dispatches, after a blank line
+    pub(crate) signature_text: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct QueueStatusQuery {
+    #[serde(default)]
+    pub(crate) include_payload: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum JobKind {
+    Dispatch,
+    Rmbpost,
+}
+
+/// Filters for `GET /queue/events`; every given field has to match.
+#[derive(Debug, Deserialize)]
+pub(crate) struct EventsQuery {
+    pub(crate) job: Option<JobKind>,
+    pub(crate) id: Option<i32>,
+    /// username of whoever queued the job
+    pub(crate) user: Option<String>,
+    /// tenant whose jobs are streamed, every tenant's if none; never taken from the query
+    #[serde(skip)]
+    pub(crate) tenant: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RevalidateQuery {
+    /// check the password with NS before clearing the flag
+    #[serde(default = "default_ping")]
+    pub(crate) ping: bool,
+}
+
+fn default_ping() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AuditQuery {
+    pub(crate) from: Option<DateTime<Utc>>,
+    pub(crate) to: Option<DateTime<Utc>>,
+    pub(crate) user_id: Option<i32>,
+    pub(crate) limit: Option<i64>,
+    pub(crate) offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UsersQuery {
+    /// only usernames containing this, ignoring case
+    pub(crate) username: Option<String>,
+    pub(crate) limit: Option<i64>,
+    pub(crate) offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct DispatchStatsQuery {
+    pub(crate) from: Option<DateTime<Utc>>,
+    pub(crate) to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub(crate) group_by: DispatchStatsGroup,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LargestDispatchesQuery {
+    pub(crate) limit: Option<i64>,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DispatchStatsGroup {
+    #[default]
+    Nation,
+    User,
+    Category,
+}
+
+/// Window of `GET /dispatches/{id}/metrics`, by when the samples were taken.
+#[derive(Deserialize)]
+pub(crate) struct DispatchMetricsQuery {
+    /// defaults to a week ago
+    pub(crate) from: Option<DateTime<Utc>>,
+    pub(crate) to: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TopDispatchesQuery {
+    /// hours the change in score is measured over, a day unless given
+    pub(crate) hours: Option<i32>,
+    pub(crate) limit: Option<i64>,
+}
+
+/// Window of `GET /stats/timings`, by jobs' `finished_at`.
+#[derive(Deserialize)]
+pub(crate) struct TimingsQuery {
+    pub(crate) job: JobKind,
+    /// defaults to a day ago
+    pub(crate) from: Option<DateTime<Utc>>,
+    pub(crate) to: Option<DateTime<Utc>>,
+}
+
+/// Archive format of `GET /export/dispatches`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ExportFormat {
+    Ndjson,
+    Zip,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ExportQuery {
+    /// takes precedence over the `Accept` header
+    pub(crate) format: Option<ExportFormat>,
+    /// only revisions created at or after this
+    pub(crate) since: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TelegramListQuery {
+    /// only telegrams queued by the caller
+    #[serde(default)]
+    pub(crate) mine: bool,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TelegramStatsQuery {
+    pub(crate) from: Option<DateTime<Utc>>,
+    pub(crate) to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub(crate) group_by: TelegramStatsGroup,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TelegramStatsGroup {
+    #[default]
+    Sender,
+    Type,
+}
+
+/// Worker named in `/admin/pipelines/{name}/...`.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Pipeline {
+    Dispatch,
+    Rmbpost,
+    Telegram,
+}
+
+impl Pipeline {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Dispatch => "dispatch",
+            Self::Rmbpost => "rmbpost",
+            Self::Telegram => "telegram",
+        }
+    }
+}
+
+#[derive(Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DispatchFormat {
+    #[default]
+    Bbcode,
+    Html,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct EditQuery {
+    /// post the edit in place of the user's own edit still waiting on the dispatch
+    #[serde(default)]
+    pub(crate) coalesce: bool,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct DispatchQuery {
+    #[serde(default)]
+    pub(crate) include_inactive: bool,
+    /// leave out the text when listing dispatches
+    #[serde(default)]
+    pub(crate) summary: bool,
+    #[serde(default)]
+    pub(crate) format: DispatchFormat,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NewDispatchQuery {
+    /// post even if the nation has an active dispatch with the same title
+    #[serde(default)]
+    pub(crate) allow_duplicate_title: bool,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct DispatchSearchQuery {
+    /// words to look for, with web search syntax: `"phrases"`, `or` and `-excluded`
+    pub(crate) q: String,
+    pub(crate) nation: Option<String>,
+    pub(crate) category: Option<i16>,
+    pub(crate) subcategory: Option<i16>,
+    #[serde(default)]
+    pub(crate) include_inactive: bool,
+    pub(crate) limit: Option<i64>,
+    pub(crate) offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EstimateAction {
+    Add,
+    Edit,
+    #[serde(alias = "delete")]
+    Remove,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct EstimateQuery {
+    pub(crate) nation: String,
+    pub(crate) action: EstimateAction,
+}
+
+/// Directives to filter logs by from now on, e.g. `info,eurocore=debug`.
+#[derive(Deserialize, Debug)]
+pub(crate) struct LoggingPatch {
+    pub(crate) level: String,
+}
+
+/// Limits to change through `PATCH /admin/ratelimiter`, the ones left out stay as they are.
+#[derive(Deserialize, Default, Debug)]
+pub(crate) struct RatelimiterPatch {
+    pub(crate) max_requests: Option<usize>,
+    pub(crate) bucket_length_secs: Option<f64>,
+    pub(crate) telegram_cooldown_secs: Option<f64>,
+    pub(crate) recruitment_cooldown_secs: Option<f64>,
+    pub(crate) restricted_action_cooldown_secs: Option<f64>,
+    /// merged into the current per-action cooldowns
+    pub(crate) restricted_cooldowns_secs: Option<BTreeMap<RestrictedAction, f64>>,
+}
@@ -1,63 +1,1052 @@
-use serde::Serialize;
+use crate::controllers::content_policy::RuleAction;
+use crate::core::logging::LogFormat;
+use crate::ns::dispatch::{QueuedDispatchPayload, TextFormat};
+use crate::ns::telegram::{RecipientFilter, RecipientSource};
+use crate::sync::ratelimiter::RestrictedAction;
+use crate::types::job::JobStatus;
+use crate::types::request::JobKind;
+use crate::types::{AuthorizedUser, IssuedToken};
+use crate::utils::{bbcode, name, signature};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Serialize)]
-pub(crate) struct DispatchHeader {
-    pub(crate) id: i32,
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Dispatch {
+    pub id: i32,
+    pub nation: String,
+    pub category: i16,
+    pub subcategory: i16,
+    pub title: String,
+    /// BBCode as posted to NS
+    pub text: String,
+    /// id of this revision, pass it as `If-Match` or `base_revision` when editing
+    #[serde(default)]
+    pub revision: i32,
+    /// the job that wrote this revision, none for imported revisions and those recorded before
+    /// jobs were linked to them
+    #[serde(default)]
+    pub job_id: Option<i32>,
+    /// length of `text` as NS counts it, with characters outside ASCII as entities; none for
+    /// revisions recorded before sizes were
+    #[serde(default)]
+    pub size: Option<i32>,
+    /// format the author submitted the text in
+    #[serde(default)]
+    pub source_format: TextFormat,
+    /// text as submitted, only present when it wasn't BBCode
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub source: Option<String>,
+    pub created_by: String,
+    pub modified_at: chrono::DateTime<chrono::Utc>,
+    pub is_active: bool,
+    /// protected dispatches can't be deleted through the API until an admin unprotects them
+    #[serde(default)]
+    pub is_protected: bool,
+    /// `text` rendered as HTML, only present when requested with `format=html`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rendered: Option<String>,
+    /// edits that haven't made it to NS, only present for a single dispatch
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub pending_or_failed_edits: Option<UnappliedEdits>,
+}
+
+/// Edits of a dispatch that its latest revision doesn't reflect: those still pending, and those
+/// that failed after the revision was written. A failed edit keeps its content, see
+/// `GET /queue/dispatches/{id}?include_payload=true`, and can be queued again with
+/// `POST /dispatches/{id}/reapply/{job_id}`.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct UnappliedEdits {
+    pub pending: i64,
+    pub failed: i64,
+    /// the failed edit that was queued last
+    #[serde(default)]
+    pub latest_failed: Option<FailedEdit>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct FailedEdit {
+    pub job_id: i32,
+    pub created_by: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub failed_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub error_code: Option<String>,
+}
+
+/// A dispatch without its text, for listings.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DispatchSummary {
+    pub id: i32,
+    pub nation: String,
+    pub category: i16,
+    pub subcategory: i16,
+    pub title: String,
+    pub created_by: String,
+    pub modified_at: chrono::DateTime<chrono::Utc>,
+    pub is_active: bool,
+    #[serde(default)]
+    pub is_protected: bool,
+}
+
+/// A dispatch found by `GET /dispatches/search`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DispatchSearchResult {
+    #[serde(flatten)]
+    pub dispatch: DispatchSummary,
+    /// higher for better matches, title matches count more than text matches
+    pub rank: f32,
+    /// the title with the matched words in `<mark>` tags
+    pub title_highlight: String,
+    /// passages of the text around the matched words, marked the same way
+    pub snippet: String,
+}
+
+/// A dispatch listed by `GET /stats/dispatches/largest`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DispatchSize {
+    #[serde(flatten)]
+    pub dispatch: DispatchSummary,
+    /// the latest revision, which the size is of
+    pub revision: i32,
+    /// length of the revision's text as NS counts it
+    pub size: i32,
+    /// share of NS's limit the text uses
+    pub utilization: f64,
+}
+
+/// Views and score of a dispatch as sampled from NS, oldest first, as reported by
+/// `GET /dispatches/{id}/metrics`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DispatchMetrics {
+    pub id: i32,
+    pub samples: Vec<DispatchMetricSample>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct DispatchMetricSample {
+    pub views: i32,
+    pub score: i32,
+    pub sampled_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A dispatch listed by `GET /stats/dispatches/top`, ranked by how much its score rose over the
+/// window.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TopDispatch {
+    #[serde(flatten)]
+    pub dispatch: DispatchSummary,
+    /// as of the latest sample
+    pub views: i32,
+    pub score: i32,
+    /// since the first sample in the window
+    pub views_delta: i32,
+    pub score_delta: i32,
+}
+
+/// Last line of an NDJSON export. A download that was cut short has none.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ExportTrailer {
+    pub(crate) trailer: ExportChecksum,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ExportChecksum {
+    pub(crate) revisions: usize,
+    /// hex SHA-256 of every line before the trailer, newlines included
+    pub(crate) sha256: String,
+}
+
+/// `manifest.json`, the last file of a zip export.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ExportManifest {
+    pub(crate) generated_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) since: Option<chrono::DateTime<chrono::Utc>>,
+    pub(crate) files: Vec<ExportedFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ExportedFile {
+    pub(crate) path: String,
+    pub(crate) dispatch_id: i32,
+    pub(crate) revision: i32,
+    pub(crate) job_id: Option<i32>,
+    pub(crate) created_by: String,
+    pub(crate) created_at: chrono::DateTime<chrono::Utc>,
+    /// hex SHA-256 of the file
+    pub(crate) sha256: String,
+}
+
+impl Dispatch {
+    pub(crate) fn render(&mut self) {
+        self.rendered = Some(bbcode::to_html(&self.text));
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DispatchStatus {
+    pub id: i32,
+    pub action: String,
+    pub status: JobStatus,
+    pub dispatch_id: Option<i32>,
+    pub error: Option<String>,
+    /// machine-readable NS failure, e.g. `pin_expired` or `dispatch_missing`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub error_code: Option<String>,
+    /// where this status can be polled
+    #[serde(rename = "self")]
+    pub self_url: String,
+    /// the dispatch the job produced, once it has succeeded
+    #[serde(default)]
+    pub resource: Option<String>,
+    /// parts of the submitted text that couldn't be converted to BBCode faithfully, content rules
+    /// it matched that only warn, and, once the job has succeeded, whether the text is close to
+    /// NS's length limit
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub warnings: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// when the worker picked the job up; the time before it was spent waiting in the queue
+    #[serde(default)]
+    pub claimed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// when the prepare request went to NS; the time before it was spent on rate limits
+    #[serde(default)]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// when NS answered the prepare request
+    #[serde(default)]
+    pub prepared_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// when the execute request went to NS; the time since `prepared_at` was spent on rate limits
+    #[serde(default)]
+    pub executing_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub modified_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub durations: JobDurations,
+    /// the job that replaced this one, once it's `superseded`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub superseded_by: Option<i32>,
+    /// the revision the job wrote, once an add or edit has succeeded
+    #[serde(default)]
+    pub revision_id: Option<i32>,
+    /// when the job is expected to be done, while it's waiting in the worker's queue; also sent
+    /// as `Retry-After`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub estimated_completion_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<QueuedDispatchPayload>,
+}
+
+/// Where the time of a job went, in milliseconds, derived from its timestamps. A phase the job
+/// hasn't got through, or never will because it failed first, is `None`.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct JobDurations {
+    /// from queueing until the worker picked the job up
+    pub queued_ms: Option<i64>,
+    /// on cooldowns and the API rate limit, before the prepare and before the execute request
+    pub ratelimit_wait_ms: Option<i64>,
+    pub ns_prepare_ms: Option<i64>,
+    /// the execute request, and recording its result
+    pub ns_execute_ms: Option<i64>,
+}
+
+impl JobDurations {
+    pub(crate) fn new(
+        created_at: chrono::DateTime<chrono::Utc>,
+        claimed_at: Option<chrono::DateTime<chrono::Utc>>,
+        started_at: Option<chrono::DateTime<chrono::Utc>>,
+        prepared_at: Option<chrono::DateTime<chrono::Utc>>,
+        executing_at: Option<chrono::DateTime<chrono::Utc>>,
+        finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Self {
+        let between = |from: Option<chrono::DateTime<chrono::Utc>>,
+                       to: Option<chrono::DateTime<chrono::Utc>>| {
+            Some((to? - from?).num_milliseconds())
+        };
+
+        Self {
+            queued_ms: between(Some(created_at), claimed_at),
+            // the wait before the execute request only exists once the prepare went through
+            ratelimit_wait_ms: between(claimed_at, started_at)
+                .map(|wait| wait + between(prepared_at, executing_at).unwrap_or_default()),
+            ns_prepare_ms: between(started_at, prepared_at),
+            ns_execute_ms: between(executing_at, finished_at),
+        }
+    }
+}
+
+/// Jobs queued together by `POST /dispatches/multi`, one per nation.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DispatchGroup {
+    pub id: i32,
+    /// `queued` until every job has finished, then `succeeded`, `failed` or `partial`
+    pub status: String,
+    #[serde(rename = "self")]
+    pub self_url: String,
+    pub created_by: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub jobs: Vec<DispatchStatus>,
+}
+
+impl DispatchGroup {
+    pub(crate) fn self_url(id: i32) -> String {
+        format!("/queue/groups/{}", id)
+    }
+
+    pub(crate) fn status(jobs: &[DispatchStatus]) -> &'static str {
+        group_status(jobs.iter().map(|job| job.status))
+    }
+}
+
+/// `queued` until every job has finished, then `succeeded`, `failed` or `partial`.
+fn group_status(statuses: impl ExactSizeIterator<Item = JobStatus>) -> &'static str {
+    let jobs = statuses.len();
+    let mut succeeded = 0;
+
+    for status in statuses {
+        if !status.is_finished() {
+            return "queued";
+        }
+
+        if status == JobStatus::Succeeded {
+            succeeded += 1;
+        }
+    }
+
+    match succeeded {
+        0 => "failed",
+        _ if succeeded == jobs => "succeeded",
+        _ => "partial",
+    }
+}
+
+/// Text as it will be sent to NS, from `POST /dispatches/preview`.
+#[derive(Serialize, Debug)]
+pub(crate) struct EncodingPreview {
+    pub(crate) text: String,
+    /// length of `text`, which is what NS holds against `max_length`
+    pub(crate) length: usize,
+    pub(crate) max_length: usize,
+    /// non-ASCII characters that were replaced, most frequent first
+    pub(crate) converted: Vec<ConvertedCharacter>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct ConvertedCharacter {
+    pub(crate) character: String,
+    pub(crate) entity: String,
+    pub(crate) count: usize,
+}
+
+/// A dispatch category and the subcategories NS files under it.
+#[derive(Serialize, Debug)]
+pub(crate) struct DispatchCategory {
+    pub(crate) id: i16,
+    pub(crate) name: &'static str,
+    pub(crate) subcategories: Vec<DispatchSubcategory>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct DispatchSubcategory {
+    pub(crate) id: i16,
+    pub(crate) name: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct QueueEstimate {
     pub(crate) nation: String,
+    pub(crate) queue_position: usize,
+    pub(crate) pending_for_nation: usize,
+    pub(crate) estimated_start_at: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(Serialize)]
-pub(crate) struct Dispatch {
+/// Dispatch jobs in one group of `GET /stats/dispatches`. Only successful jobs count as adds,
+/// edits and deletes.
+#[derive(Serialize, Debug)]
+pub(crate) struct DispatchStats {
+    /// `None` for jobs the grouping can't be determined for, e.g. old jobs without an author
+    pub(crate) key: Option<String>,
+    pub(crate) adds: i64,
+    pub(crate) edits: i64,
+    pub(crate) deletes: i64,
+    pub(crate) failures: i64,
+}
+
+/// Telegrams in one group of `GET /stats/telegrams`.
+#[derive(Serialize, Debug)]
+pub(crate) struct TelegramStats {
+    pub(crate) key: String,
+    pub(crate) sent: i64,
+    pub(crate) failed: i64,
+    pub(crate) skipped: i64,
+}
+
+/// Percentiles of one phase in `GET /stats/timings`, in milliseconds. `None` when no job in the
+/// window got through the phase.
+#[derive(Serialize, Debug)]
+pub(crate) struct PhasePercentiles {
+    pub(crate) p50: Option<i64>,
+    pub(crate) p95: Option<i64>,
+}
+
+/// How long the jobs that finished within the window of `GET /stats/timings` spent in each phase,
+/// see [`JobDurations`].
+#[derive(Serialize, Debug)]
+pub(crate) struct JobTimings {
+    pub(crate) job: JobKind,
+    pub(crate) jobs: i64,
+    pub(crate) queued_ms: PhasePercentiles,
+    pub(crate) ratelimit_wait_ms: PhasePercentiles,
+    pub(crate) ns_prepare_ms: PhasePercentiles,
+    pub(crate) ns_execute_ms: PhasePercentiles,
+}
+
+/// State of a worker as reported by `GET /admin/pipelines`.
+#[derive(Serialize, Debug)]
+pub(crate) struct PipelineStatus {
+    pub(crate) name: String,
+    pub(crate) paused: bool,
+    pub(crate) queue_depth: usize,
+    pub(crate) last_success_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// finished jobs whose outcome couldn't be written yet, held until the database is back
+    pub(crate) unwritten: usize,
+}
+
+/// What a worker is doing right now, as reported by `GET /admin/workers`.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct WorkerStatus {
+    pub(crate) state: WorkerState,
+    /// the job being posted, the longest running one if there are several
+    pub(crate) current_job: Option<CurrentJob>,
+    pub(crate) queue_depth: usize,
+    pub(crate) last_success_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub(crate) last_error: Option<WorkerError>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WorkerState {
+    /// nothing to post
+    Idle,
+    /// a job is with NS
+    Posting,
+    /// jobs are waiting, but none may be posted yet, usually because of a cooldown
+    SleepingRatelimit,
+    /// jobs are kept until the pipeline is resumed
+    Paused,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CurrentJob {
     pub(crate) id: i32,
+    /// the nation posting it, or sending it for a telegram
     pub(crate) nation: String,
-    pub(crate) category: i16,
-    pub(crate) subcategory: i16,
-    pub(crate) title: String,
-    pub(crate) text: String,
-    pub(crate) created_by: String,
-    pub(crate) modified_at: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(Serialize)]
-pub(crate) struct DispatchStatus {
+/// The last job a worker failed.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct WorkerError {
+    pub(crate) at: chrono::DateTime<chrono::Utc>,
+    pub(crate) message: String,
+}
+
+/// Outcome of the last retention sweep, as reported by `GET /admin/retention`.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct RetentionStatus {
+    pub(crate) enabled: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) last_sweep_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub(crate) tables: Vec<PrunedTable>,
+}
+
+/// Limits and bucket occupancy, as reported by `GET /admin/ratelimiter`.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct RatelimiterStatus {
+    pub(crate) limits: RatelimiterLimits,
+    /// requests in the current bucket
+    pub(crate) requests: usize,
+    /// telegrams still cooling down
+    pub(crate) telegrams: usize,
+    /// recruitment telegrams still cooling down
+    pub(crate) recruitment_telegrams: usize,
+    /// restricted actions still cooling down, per nation
+    pub(crate) restricted_actions: std::collections::BTreeMap<String, usize>,
+    /// how long a standard request would have to wait right now
+    pub(crate) standard_wait_secs: f64,
+    /// what NS last reported in its ratelimit headers, `None` until a response carried them
+    pub(crate) observed: Option<ObservedRequests>,
+    /// requests from others sharing our IP in the current bucket, counted on top of `requests`
+    pub(crate) external_requests: usize,
+    /// times NS's count crossed the warning threshold
+    pub(crate) utilization_warnings: u64,
+    /// jobs workers have accepted and not started, counted in queue estimates
+    pub(crate) reservations: usize,
+}
+
+/// NS's own count of our requests, from the headers of its last response.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct ObservedRequests {
+    pub(crate) seen: usize,
+    pub(crate) limit: Option<usize>,
+    pub(crate) observed_secs_ago: f64,
+}
+
+/// `GET /admin/logging`.
+#[derive(Serialize, Debug)]
+pub(crate) struct Logging {
+    /// the level filter's directives
+    pub(crate) level: String,
+    pub(crate) format: LogFormat,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct RatelimiterLimits {
+    pub(crate) max_requests: usize,
+    pub(crate) bucket_length_secs: f64,
+    pub(crate) telegram_cooldown_secs: f64,
+    pub(crate) recruitment_cooldown_secs: f64,
+    pub(crate) restricted_action_cooldown_secs: f64,
+    /// the cooldown each kind of restricted action is held to
+    pub(crate) restricted_cooldowns_secs: std::collections::BTreeMap<RestrictedAction, f64>,
+}
+
+/// Rows pruned from a table, or that would have been in a dry run.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct PrunedTable {
+    pub(crate) table: String,
+    pub(crate) rows: i64,
+}
+
+/// Restricted action availability for a configured nation.
+#[derive(Serialize, Debug)]
+pub(crate) struct NationStatus {
+    pub(crate) nation: String,
+    /// seconds until the nation can perform its next restricted action
+    pub(crate) restricted_ready_in: u64,
+    /// false once NS has rejected the nation's password, until it next succeeds
+    pub(crate) healthy: bool,
+    /// `None` if the nation isn't configured for dispatches
+    pub(crate) queued_dispatches: Option<usize>,
+    /// `None` if the nation isn't configured for RMB posts
+    pub(crate) queued_rmbposts: Option<i64>,
+}
+
+/// A job status change pushed over `GET /queue/events`. Carries no job content.
+#[derive(Clone, Serialize, Debug)]
+pub(crate) struct JobEvent {
+    pub(crate) job: JobKind,
     pub(crate) id: i32,
-    pub(crate) action: String,
-    pub(crate) status: String,
-    pub(crate) dispatch_id: Option<i32>,
+    pub(crate) status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error_code: Option<String>,
+    #[serde(rename = "self")]
+    pub(crate) self_url: String,
+    pub(crate) resource: Option<String>,
+    /// only used for filtering
+    #[serde(skip)]
+    pub(crate) created_by: Option<String>,
+    /// only used for filtering
+    #[serde(skip)]
+    pub(crate) tenant_id: i32,
+}
+
+/// Result of `POST /admin/nations/{name}/revalidate`.
+#[derive(Serialize, Debug)]
+pub(crate) struct NationHealth {
+    pub(crate) nation: String,
+    pub(crate) healthy: bool,
+}
+
+/// Result of `POST /admin/nations/{name}/verify`. Dispatch and RMB post nations are configured
+/// separately, so each copy of the password is checked.
+#[derive(Serialize, Debug)]
+pub(crate) struct NationVerification {
+    pub(crate) nation: String,
+    /// every checked copy works
+    pub(crate) verified: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) dispatch: Option<CredentialCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) rmbpost: Option<CredentialCheck>,
+}
+
+impl NationVerification {
+    /// `None` if neither kind of nation has `nation`, so there was nothing to check.
+    pub(crate) fn new(
+        nation: &str,
+        dispatch: Option<CredentialCheck>,
+        rmbpost: Option<CredentialCheck>,
+    ) -> Option<Self> {
+        if dispatch.is_none() && rmbpost.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            nation: name::canonicalize(nation),
+            verified: dispatch
+                .iter()
+                .chain(rmbpost.iter())
+                .all(|check| check.verified),
+            dispatch,
+            rmbpost,
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct CredentialCheck {
+    pub(crate) verified: bool,
+    /// NS handed back a pin, stored so the next jobs skip the password login
+    pub(crate) pin: bool,
     pub(crate) error: Option<String>,
-    pub(crate) created_at: chrono::DateTime<chrono::Utc>,
-    pub(crate) modified_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) error_code: Option<&'static str>,
+}
+
+impl DispatchStatus {
+    pub(crate) fn self_url(id: i32) -> String {
+        format!("/queue/dispatches/{}", id)
+    }
+
+    /// Deletions leave nothing behind to link to.
+    pub(crate) fn resource_url(
+        action: &str,
+        status: JobStatus,
+        dispatch_id: Option<i32>,
+    ) -> Option<String> {
+        match (action, status, dispatch_id) {
+            ("add" | "edit", JobStatus::Succeeded, Some(dispatch_id)) => {
+                Some(format!("/dispatches/{}", dispatch_id))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RmbPostStatus {
+    pub id: i32,
+    pub status: JobStatus,
+    pub rmbpost_id: Option<i32>,
+    pub error: Option<String>,
+    /// machine-readable NS failure, e.g. `rate_limited` or `region_password_required`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub error_code: Option<String>,
+    /// content rules the text matched that only warn
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub warnings: Vec<String>,
+    /// where this status can be polled
+    #[serde(rename = "self")]
+    pub self_url: String,
+    /// the post on NationStates, once the job has succeeded
+    #[serde(default)]
+    pub resource: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// when the worker picked the job up; the time before it was spent waiting in the queue
+    #[serde(default)]
+    pub claimed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// when the prepare request went to NS; the time before it was spent on rate limits
+    #[serde(default)]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// when NS answered the prepare request
+    #[serde(default)]
+    pub prepared_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// when the execute request went to NS; the time since `prepared_at` was spent on rate limits
+    #[serde(default)]
+    pub executing_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub modified_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub durations: JobDurations,
+    /// when the job is expected to be done, while it's waiting in the worker's queue; also sent
+    /// as `Retry-After`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub estimated_completion_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Jobs queued together by `POST /rmbposts/batch`, one per region.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RmbPostGroup {
+    pub id: i32,
+    /// `queued` until every job has finished, then `succeeded`, `failed` or `partial`
+    pub status: String,
+    #[serde(rename = "self")]
+    pub self_url: String,
+    pub created_by: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub jobs: Vec<RmbPostStatus>,
+}
+
+impl RmbPostGroup {
+    pub(crate) fn self_url(id: i32) -> String {
+        format!("/queue/rmbposts/groups/{}", id)
+    }
+
+    pub(crate) fn status(jobs: &[RmbPostStatus]) -> &'static str {
+        group_status(jobs.iter().map(|job| job.status))
+    }
+}
+
+impl RmbPostStatus {
+    pub(crate) fn self_url(id: i32) -> String {
+        format!("/queue/rmbposts/{}", id)
+    }
+
+    /// eurocore doesn't keep RMB posts, so this links to the post on NationStates.
+    pub(crate) fn resource_url(
+        status: JobStatus,
+        region: &str,
+        rmbpost_id: Option<i32>,
+    ) -> Option<String> {
+        match (status, rmbpost_id) {
+            (JobStatus::Succeeded, Some(id)) => Some(format!(
+                "https://www.nationstates.net/region={}/page=display_region_rmb?postid={}#p{}",
+                region, id, id
+            )),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Serialize)]
-pub(crate) struct RmbPostStatus {
+pub(crate) struct AuditEntry {
     pub(crate) id: i32,
-    pub(crate) status: String,
-    pub(crate) rmbpost_id: Option<i32>,
-    pub(crate) error: Option<String>,
     pub(crate) created_at: chrono::DateTime<chrono::Utc>,
-    pub(crate) modified_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) user_id: Option<i32>,
+    pub(crate) username: Option<String>,
+    pub(crate) action: String,
+    pub(crate) target_type: String,
+    pub(crate) target_id: Option<String>,
+    pub(crate) summary: serde_json::Value,
+    pub(crate) outcome: String,
+}
+
+/// Body of every error response. `code` is stable, `message` is for humans and may change.
+#[derive(Serialize, Debug)]
+pub(crate) struct ErrorBody {
+    pub(crate) code: &'static str,
+    pub(crate) message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) details: Option<serde_json::Value>,
+}
+
+impl ErrorBody {
+    pub(crate) fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub(crate) fn details<T: Serialize>(mut self, details: &T) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+}
+
+/// Why a JSON request body was rejected. `code` and `message` go in the [`ErrorBody`], the rest
+/// in its details.
+#[derive(Serialize, Debug)]
+pub(crate) struct InvalidBody {
+    /// `malformed_json`, `missing_field`, `invalid_type`, `invalid_value` or `invalid_body`
+    #[serde(skip)]
+    pub(crate) code: &'static str,
+    #[serde(skip)]
+    pub(crate) message: String,
+    /// the offending field, e.g. `recipients[2]`, absent when the problem is the body as a whole
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) received: Option<String>,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+/// Details of the 409 returned when pending jobs on a dispatch rule out the new one.
+#[derive(Serialize, Debug)]
+pub(crate) struct PendingJobs {
+    pub(crate) dispatch_id: i32,
+    pub(crate) jobs: Vec<PendingJob>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct PendingJob {
+    pub(crate) id: i32,
+    pub(crate) action: String,
+    pub(crate) status: JobStatus,
+}
+
+/// Details of the 409 returned when an edit is based on an outdated revision, with what the
+/// client needs to merge.
+#[derive(Serialize, Debug)]
+pub(crate) struct EditConflict {
+    /// revision the edit was based on
+    base_revision: i32,
+    /// latest revision of the dispatch
+    revision: i32,
+    revised_by: String,
+    revised_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl EditConflict {
+    pub(crate) fn new(
+        base_revision: i32,
+        revision: i32,
+        revised_by: &str,
+        revised_at: chrono::DateTime<chrono::Utc>,
+    ) -> Self {
+        Self {
+            base_revision,
+            revision,
+            revised_by: revised_by.to_string(),
+            revised_at,
+        }
+    }
+}
+
+/// Details of the 410 returned for a deleted dispatch. Dispatches deleted before this was
+/// recorded have neither `deleted_at` nor `deleted_by`.
+#[derive(Serialize, Debug)]
+pub(crate) struct DeletedDispatch {
+    pub(crate) id: i32,
+    pub(crate) deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub(crate) deleted_by: Option<String>,
+}
+
+/// Details of the 423 returned for changes to a protected dispatch.
+#[derive(Serialize, Debug)]
+pub(crate) struct ProtectedDispatch {
+    pub(crate) id: i32,
+    pub(crate) protected_by: Option<String>,
+    pub(crate) protected_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct QuotaExceeded {
+    quota: String,
+    limit: i64,
+    oldest_job_created_at: Option<chrono::DateTime<chrono::Utc>>,
+    oldest_job_age_secs: Option<i64>,
+    available_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl QuotaExceeded {
+    pub(crate) fn new(
+        quota: &str,
+        limit: i64,
+        oldest_job_created_at: Option<chrono::DateTime<chrono::Utc>>,
+        available_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Self {
+        Self {
+            quota: quota.to_string(),
+            limit,
+            oldest_job_created_at,
+            oldest_job_age_secs: oldest_job_created_at
+                .map(|created_at| (chrono::Utc::now() - created_at).num_seconds()),
+            available_at,
+        }
+    }
+
+    pub(crate) fn message(&self) -> String {
+        format!("Exceeded the {} job quota of {}", self.quota, self.limit)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QueuedTelegram {
+    pub id: i32,
+    pub recipient: String,
+    pub telegram_id: String,
+    pub queue: String,
+}
+
+/// Response of `POST /telegrams`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QueuedTelegrams {
+    /// number of telegrams queued
+    pub accepted: usize,
+    /// repeats within the request and telegrams already waiting for the same recipient
+    pub duplicates_dropped: usize,
+    /// positions in the request of telegrams to recipients that aren't nation names
+    pub invalid: Vec<usize>,
+    /// recipients each filter left out, by filter; filters that left out none aren't listed
+    #[serde(default)]
+    pub filtered: BTreeMap<String, usize>,
+    /// in request order
+    pub queued: Vec<QueuedTelegram>,
+    /// in request order
+    pub dropped: Vec<DroppedTelegram>,
+}
+
+impl QueuedTelegrams {
+    pub(crate) fn new(queued: Vec<QueuedTelegram>, mut dropped: Vec<DroppedTelegram>) -> Self {
+        dropped.sort_by_key(|dropped| dropped.index);
+
+        Self {
+            accepted: queued.len(),
+            duplicates_dropped: dropped
+                .iter()
+                .filter(|dropped| matches!(dropped.reason.as_str(), "duplicate" | "already_queued"))
+                .count(),
+            invalid: dropped
+                .iter()
+                .filter(|dropped| dropped.reason == "invalid_recipient")
+                .map(|dropped| dropped.index)
+                .collect(),
+            filtered: dropped
+                .iter()
+                .filter(|dropped| RecipientFilter::NAMES.contains(&dropped.reason.as_str()))
+                .fold(BTreeMap::new(), |mut filtered, dropped| {
+                    *filtered.entry(dropped.reason.clone()).or_insert(0) += 1;
+                    filtered
+                }),
+            queued,
+            dropped,
+        }
+    }
+}
+
+/// A telegram of a batch that wasn't queued.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DroppedTelegram {
+    /// position in the request
+    pub index: usize,
+    /// as submitted
+    pub recipient: String,
+    pub telegram_id: String,
+    /// `invalid_recipient`, `duplicate`, `already_queued`, or `not_queued` when queueing failed
+    /// partway through the request
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TelegramStatus {
+    pub id: i32,
+    pub sender: String,
+    pub recipient: String,
+    pub telegram_id: String,
+    pub queue: String,
+    pub status: String,
+    pub error: Option<String>,
+    /// machine-readable reason NS refused the telegram, e.g. `recruitment_blocked`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub error_code: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub modified_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A campaign recipient, in send order.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CampaignRecipient {
+    pub position: i32,
+    pub recipient: String,
+    /// `pending` until the recipient is fed into the queue, then the status of its telegram
+    pub status: String,
+}
+
+/// A recruitment campaign and how far through its recipient list it is.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TelegramCampaign {
+    pub id: i32,
+    pub name: String,
+    pub sender: String,
+    pub telegram_id: String,
+    pub tg_type: String,
+    /// `active`, `paused` or `completed`
+    pub status: String,
+    pub recipients: i64,
+    pub sent: i64,
+    pub failed: i64,
+    pub skipped: i64,
+    /// recipients not yet sent to, including those waiting in the telegram queue
+    pub remaining: i64,
+    /// entries of the submitted list that aren't valid nation names, only reported on creation
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub rejected: Vec<String>,
+    /// the region the campaign was created with, if any
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub recipients_from: Option<RecipientSource>,
+    /// recipients `recipients_from` added on top of the submitted list
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub expanded_recipients: Option<i32>,
+    /// recipients each filter left out, by filter, only reported on creation
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub filtered: BTreeMap<String, usize>,
+    pub created_by: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub modified_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Serialize, Debug)]
 pub(crate) struct Telegram {
+    sender: String,
     recipient: String,
     id: String,
+    created_by: String,
 }
 
 impl Telegram {
-    pub(crate) fn new(recipient: &str, telegram_id: &str) -> Self {
+    pub(crate) fn new(sender: &str, recipient: &str, telegram_id: &str, created_by: &str) -> Self {
         Self {
+            sender: sender.to_string(),
             recipient: recipient.to_string(),
             id: telegram_id.to_string(),
+            created_by: created_by.to_string(),
         }
     }
 }
 
-#[derive(Serialize, Debug)]
-pub(crate) struct User {
-    id: i32,
-    username: String,
+/// Queued telegrams for one sender nation.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub(crate) struct TelegramSenderSummary {
+    pub(crate) recruitment_count: usize,
+    pub(crate) standard_count: usize,
+    pub(crate) oldest_queued_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// seconds until the sender can send its next recruitment telegram
+    pub(crate) recruitment_ready_in: u64,
+    /// seconds until the sender can send its next standard telegram
+    pub(crate) standard_ready_in: u64,
+}
+
+/// Recruitment telegram capacity of one sender nation, from `GET /telegrams/capacity`.
+#[derive(Serialize, Debug, PartialEq)]
+pub(crate) struct TelegramCapacity {
+    pub(crate) known: CapacityCounts,
+    pub(crate) estimated: CapacityEstimates,
+}
+
+/// Hard numbers, counted from sent telegrams, the queue and the ratelimiter.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub(crate) struct CapacityCounts {
+    /// recruitment telegrams sent in the last 24 hours
+    pub(crate) sent_last_24h: usize,
+    /// recruitment telegrams waiting in the queue
+    pub(crate) queued: usize,
+    /// recipients of active recruitment campaigns not fed into the queue yet
+    pub(crate) campaign_backlog: usize,
+    /// the current recruitment cooldown, in seconds
+    pub(crate) cooldown_secs: u64,
+    /// seconds until the sender can send its next recruitment telegram
+    pub(crate) ready_in: u64,
+}
+
+/// Projections assuming one recruitment telegram per cooldown and nothing else holding the
+/// nation back; standard telegrams from the same nation push them back.
+#[derive(Serialize, Debug, PartialEq)]
+pub(crate) struct CapacityEstimates {
+    /// recruitment telegrams the cooldown allows in 24 hours
+    pub(crate) max_per_day: u64,
+    /// recruitment telegrams that could still be sent before midnight UTC
+    pub(crate) max_remaining_today: u64,
+    /// when the queue and campaign backlog would be sent, `None` if there is nothing to send
+    pub(crate) projected_completion_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct User {
+    pub id: i32,
+    pub username: String,
 }
 
 impl User {
@@ -69,17 +1058,379 @@ impl User {
     }
 }
 
+/// An account as listed at `GET /admin/users`.
+#[derive(Serialize, Debug)]
+pub(crate) struct UserAccount {
+    pub(crate) id: i32,
+    pub(crate) username: String,
+    pub(crate) claims: Vec<String>,
+    pub(crate) created_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) last_login_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// set once an admin disabled the account, it can't authenticate anymore
+    pub(crate) disabled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub(crate) tenant_id: i32,
+}
+
+/// A tenant, see `GET /admin/tenants`.
+#[derive(Serialize, Debug)]
+pub(crate) struct Tenant {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    /// nations assigned to the tenant, the default tenant's are those assigned to none
+    pub(crate) nations: Vec<String>,
+    pub(crate) users: i64,
+    pub(crate) created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A claim users can be granted, as listed at `GET /admin/permissions`.
+#[derive(Serialize, Debug)]
+pub(crate) struct Permission {
+    pub(crate) claim: &'static str,
+    pub(crate) description: &'static str,
+}
+
 #[derive(Serialize, Debug)]
-pub(crate) struct Login {
+pub(crate) struct Profile {
+    id: i32,
     username: String,
-    token: String,
+    claims: Vec<String>,
+    dispatch_nations: Vec<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Profile {
+    pub(crate) fn new(user: &AuthorizedUser, dispatch_nations: Vec<String>) -> Self {
+        Self {
+            id: user.id,
+            username: user.username.clone(),
+            claims: user.claims.clone(),
+            dispatch_nations,
+            created_at: user.created_at,
+        }
+    }
+}
+
+/// Everything a UI needs before it can render its forms, from `GET /bootstrap`. A version only
+/// ever gains fields; anything else bumps it.
+#[derive(Serialize, Debug)]
+pub(crate) struct Bootstrap {
+    pub(crate) version: u32,
+    pub(crate) user: SessionUser,
+    pub(crate) dispatch_nations: Vec<String>,
+    pub(crate) rmbpost_nations: Vec<String>,
+    pub(crate) categories: Vec<DispatchCategory>,
+    pub(crate) limits: Limits,
+    /// what new dispatches of each nation with a preset are filled in with
+    pub(crate) presets: Vec<NationPreset>,
+}
+
+/// The user a request was made as.
+#[derive(Serialize, Debug)]
+pub(crate) struct SessionUser {
+    pub(crate) id: i32,
+    pub(crate) username: String,
+    pub(crate) claims: Vec<String>,
+}
+
+impl SessionUser {
+    pub(crate) fn new(user: &AuthorizedUser) -> Self {
+        Self {
+            id: user.id,
+            username: user.username.clone(),
+            claims: user.claims.clone(),
+        }
+    }
+}
+
+/// What the server accepts, for forms to check before submitting.
+#[derive(Serialize, Debug)]
+pub(crate) struct Limits {
+    pub(crate) dispatches: DispatchLimits,
+    pub(crate) rmbposts: RmbpostLimits,
+    /// jobs of each kind the user may queue
+    pub(crate) quota: QuotaLimits,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct DispatchLimits {
+    /// counted after non-ASCII characters have been encoded, see `POST /dispatches/preview`
+    pub(crate) max_text_length: usize,
+    /// nations one `POST /dispatches/multi` may post as
+    pub(crate) max_group_size: usize,
+    pub(crate) text_formats: &'static [TextFormat],
+    /// whether jobs can be queued to be posted later
+    pub(crate) scheduled_posts: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct RmbpostLimits {
+    /// regions one `POST /rmbposts/batch` may post to
+    pub(crate) max_batch_size: usize,
+}
+
+/// `None` where there's no limit, as for admins.
+#[derive(Serialize, Debug)]
+pub(crate) struct QuotaLimits {
+    pub(crate) max_pending: Option<i64>,
+    pub(crate) max_daily: Option<i64>,
+}
+
+/// An API key as listed to its owner.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    /// only returned once, when the key is created
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub key: Option<String>,
+    pub claims: Vec<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A content rule, see [`crate::controllers::content_policy`].
+#[derive(Serialize, Debug)]
+pub(crate) struct ContentRule {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    pub(crate) pattern: String,
+    pub(crate) action: RuleAction,
+    pub(crate) created_by: String,
+    pub(crate) created_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) modified_at: chrono::DateTime<chrono::Utc>,
+    /// why the pattern doesn't compile, the rule isn't applied until it does
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+/// How a nation's new dispatches are filled in, see `PUT /admin/nations/{name}/presets`.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct NationPreset {
+    pub(crate) nation: String,
+    pub(crate) default_category: Option<i16>,
+    pub(crate) default_subcategory: Option<i16>,
+    pub(crate) title_prefix: Option<String>,
+    pub(crate) signature_text: Option<String>,
+    pub(crate) updated_by: String,
+    pub(crate) updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A registered webhook. Carries how deliveries are signed, so receivers can be written
+/// against the response alone.
+#[derive(Serialize, Debug)]
+pub(crate) struct Webhook {
+    pub(crate) id: i32,
+    pub(crate) url: String,
+    /// only returned when the secret is generated, by a rotation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) secret: Option<String>,
+    pub(crate) created_by: String,
+    pub(crate) created_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) secret_rotated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub(crate) signature: SignatureScheme,
+}
+
+/// See [`crate::utils::signature`].
+#[derive(Serialize, Debug)]
+pub(crate) struct SignatureScheme {
+    pub(crate) header: &'static str,
+    pub(crate) algorithm: &'static str,
+    /// layout of the header value
+    pub(crate) format: &'static str,
+    /// what the HMAC is computed over, `body` being the raw request body
+    pub(crate) signed_payload: &'static str,
+    pub(crate) encoding: &'static str,
+    /// signatures further than this from the receiver's clock should be rejected
+    pub(crate) tolerance_secs: i64,
+}
+
+impl Default for SignatureScheme {
+    fn default() -> Self {
+        Self {
+            header: signature::HEADER,
+            algorithm: "HMAC-SHA256",
+            format: "t={timestamp},v1={signature}",
+            signed_payload: "{timestamp}.{body}",
+            encoding: "hex",
+            tolerance_secs: signature::TOLERANCE_SECS,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Login {
+    pub username: String,
+    pub token: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
 }
 
 impl Login {
-    pub(crate) fn new(username: &str, token: &str) -> Self {
+    pub(crate) fn new(username: &str, token: &IssuedToken) -> Self {
         Self {
             username: username.to_string(),
-            token: token.to_string(),
+            token: token.token.clone(),
+            expires_at: token.expires_at,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dispatch_status_urls() {
+        assert_eq!(DispatchStatus::self_url(12), "/queue/dispatches/12");
+
+        assert_eq!(
+            DispatchStatus::resource_url("add", JobStatus::Succeeded, Some(345)),
+            Some("/dispatches/345".to_string())
+        );
+        assert_eq!(
+            DispatchStatus::resource_url("edit", JobStatus::Succeeded, Some(345)),
+            Some("/dispatches/345".to_string())
+        );
+        assert_eq!(
+            DispatchStatus::resource_url("add", JobStatus::Queued, None),
+            None
+        );
+        assert_eq!(
+            DispatchStatus::resource_url("add", JobStatus::Failed, None),
+            None
+        );
+        assert_eq!(
+            DispatchStatus::resource_url("delete", JobStatus::Succeeded, Some(345)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dispatch_group_status() {
+        let job = |status: JobStatus| DispatchStatus {
+            id: 1,
+            action: "add".to_string(),
+            status,
+            dispatch_id: None,
+            error: None,
+            error_code: None,
+            self_url: String::new(),
+            resource: None,
+            warnings: Vec::new(),
+            created_at: chrono::Utc::now(),
+            claimed_at: None,
+            started_at: None,
+            prepared_at: None,
+            executing_at: None,
+            finished_at: None,
+            modified_at: chrono::Utc::now(),
+            durations: JobDurations::default(),
+            superseded_by: None,
+            revision_id: None,
+            estimated_completion_at: None,
+            payload: None,
+        };
+
+        assert_eq!(
+            DispatchGroup::status(&[job(JobStatus::Succeeded), job(JobStatus::Queued)]),
+            "queued"
+        );
+        assert_eq!(
+            DispatchGroup::status(&[job(JobStatus::Succeeded), job(JobStatus::Posting)]),
+            "queued"
+        );
+        assert_eq!(
+            DispatchGroup::status(&[job(JobStatus::Succeeded), job(JobStatus::Succeeded)]),
+            "succeeded"
+        );
+        assert_eq!(
+            DispatchGroup::status(&[job(JobStatus::Failed), job(JobStatus::Cancelled)]),
+            "failed"
+        );
+        assert_eq!(
+            DispatchGroup::status(&[job(JobStatus::Succeeded), job(JobStatus::Failed)]),
+            "partial"
+        );
+    }
+
+    #[test]
+    fn test_job_durations() {
+        let created_at = chrono::Utc::now();
+        let at = |ms| Some(created_at + chrono::Duration::milliseconds(ms));
+
+        assert_eq!(
+            JobDurations::new(
+                created_at,
+                at(100),
+                at(1_100),
+                at(1_300),
+                at(1_800),
+                at(2_000)
+            ),
+            JobDurations {
+                queued_ms: Some(100),
+                ratelimit_wait_ms: Some(1_500),
+                ns_prepare_ms: Some(200),
+                ns_execute_ms: Some(200),
+            }
+        );
+
+        // failed on the prepare request
+        assert_eq!(
+            JobDurations::new(created_at, at(100), at(1_100), None, None, at(1_300)),
+            JobDurations {
+                queued_ms: Some(100),
+                ratelimit_wait_ms: Some(1_000),
+                ns_prepare_ms: None,
+                ns_execute_ms: None,
+            }
+        );
+
+        assert_eq!(
+            JobDurations::new(created_at, None, None, None, None, None),
+            JobDurations::default()
+        );
+    }
+
+    #[test]
+    fn test_rmbpost_status_urls() {
+        assert_eq!(RmbPostStatus::self_url(7), "/queue/rmbposts/7");
+
+        assert_eq!(
+            RmbPostStatus::resource_url(JobStatus::Succeeded, "testregion", Some(42)),
+            Some(
+                "https://www.nationstates.net/region=testregion/page=display_region_rmb?postid=42#p42"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            RmbPostStatus::resource_url(JobStatus::Queued, "testregion", None),
+            None
+        );
+        assert_eq!(
+            RmbPostStatus::resource_url(JobStatus::Failed, "testregion", None),
+            None
+        );
+    }
+}
+
+/// Result of `GET /health`.
+#[derive(Serialize, Debug)]
+pub(crate) struct Health {
+    /// `ok`, or `unavailable` while the database doesn't answer
+    pub(crate) status: &'static str,
+    pub(crate) database: DatabaseHealth,
+}
+
+/// The connection pool, and whether the database answered a query through it.
+#[derive(Serialize, Debug)]
+pub(crate) struct DatabaseHealth {
+    pub(crate) reachable: bool,
+    /// how long the query took, `None` if it didn't get an answer
+    pub(crate) latency_ms: Option<u64>,
+    /// open connections, idle ones included
+    pub(crate) connections: u32,
+    pub(crate) idle_connections: usize,
+    pub(crate) max_connections: u32,
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serializer};
+use std::fmt;
+
+/// A credential eurocore hands on to NS but never shows: a nation password, a telegram's secret
+/// key or a client key. Its `Debug` prints `[redacted]`, and it has no `Serialize`, so it can't
+/// end up in a response by accident. The fields that have to send it opt in with [`expose`].
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// The secret in the clear, to send it where it's meant to go.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+/// Writes the secret in the clear, for `#[serde(serialize_with)]` on a field sent to NS, or to
+/// eurocore in a request body. Never for a response.
+pub(crate) fn expose<S>(secret: &Secret, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(secret.expose())
+}
@@ -2,12 +2,20 @@ use serde::{Deserialize, Serialize};
 
 pub(crate) type Username = String;
 
+/// The tenant everything from before tenants belongs to, whose admins manage the others.
+pub(crate) const DEFAULT_TENANT: i32 = 1;
+
 #[derive(Clone, Debug, sqlx::FromRow)]
 pub(crate) struct AuthorizedUser {
     pub(crate) id: i32,
     pub(crate) username: Username,
     pub(crate) password_hash: String,
     pub(crate) claims: Vec<String>,
+    pub(crate) created_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) tenant_id: i32,
+    /// set when the request was authenticated with an API key, `claims` are then the key's scope
+    #[sqlx(default)]
+    pub(crate) api_key_id: Option<i32>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -16,4 +24,35 @@ pub(crate) struct Claims {
     pub(crate) iat: usize,
     pub(crate) sub: String,
     pub(crate) iss: String,
+    /// tokens issued before tenants were introduced are the default tenant's
+    #[serde(default = "default_tenant")]
+    pub(crate) tenant: i32,
+}
+
+fn default_tenant() -> i32 {
+    DEFAULT_TENANT
+}
+
+impl AuthorizedUser {
+    /// Whether the user may manage tenants, which only the default tenant's admins may.
+    pub(crate) fn is_operator(&self) -> bool {
+        self.tenant_id == DEFAULT_TENANT
+    }
+
+    /// Tenant the user's admin routes are limited to, none for operators, who run every tenant.
+    pub(crate) fn admin_scope(&self) -> Option<i32> {
+        (!self.is_operator()).then_some(self.tenant_id)
+    }
+}
+
+/// Tenant whose rows a request is limited to: the user's, or the default tenant's for anonymous
+/// reads, so the other tenants' rows are only read by their own users.
+pub(crate) fn tenant_scope(user: Option<&AuthorizedUser>) -> i32 {
+    user.map_or(DEFAULT_TENANT, |user| user.tenant_id)
+}
+
+#[derive(Debug)]
+pub(crate) struct IssuedToken {
+    pub(crate) token: String,
+    pub(crate) expires_at: chrono::DateTime<chrono::Utc>,
 }
@@ -0,0 +1,529 @@
+//! Rendering of NationStates BBCode to sanitized HTML.
+//!
+//! Only the tags we actually use are supported. Anything else, including unbalanced tags and
+//! URLs that fail validation, is emitted as escaped text, so the output never contains markup
+//! that wasn't generated here.
+
+/// Hosts `[img]` tags may load images from.
+const IMAGE_HOSTS: &[&str] = &[
+    "www.nationstates.net",
+    "nationstates.net",
+    "i.imgur.com",
+    "i.ibb.co",
+];
+
+const NS_ORIGIN: &str = "https://www.nationstates.net";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tag {
+    Bold,
+    Italic,
+    Underline,
+    Url,
+    List,
+    Item,
+    Quote,
+    Box,
+    Spoiler,
+    Table,
+    Row,
+    Cell,
+    Header,
+    Image,
+}
+
+impl Tag {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "b" => Some(Tag::Bold),
+            "i" => Some(Tag::Italic),
+            "u" => Some(Tag::Underline),
+            "url" => Some(Tag::Url),
+            "list" => Some(Tag::List),
+            "*" => Some(Tag::Item),
+            "quote" => Some(Tag::Quote),
+            "box" => Some(Tag::Box),
+            "spoiler" => Some(Tag::Spoiler),
+            "table" => Some(Tag::Table),
+            "tr" => Some(Tag::Row),
+            "td" => Some(Tag::Cell),
+            "th" => Some(Tag::Header),
+            "img" => Some(Tag::Image),
+            _ => None,
+        }
+    }
+
+    fn takes_argument(&self) -> bool {
+        matches!(
+            self,
+            Tag::Url | Tag::List | Tag::Quote | Tag::Spoiler | Tag::Table
+        )
+    }
+
+    /// Tags rendered as block elements; a single newline next to them is not turned into `<br>`.
+    fn is_block(&self) -> bool {
+        !matches!(
+            self,
+            Tag::Bold | Tag::Italic | Tag::Underline | Tag::Url | Tag::Image
+        )
+    }
+
+    /// Tags whose direct children are other elements, where stray whitespace is dropped.
+    fn is_container(&self) -> bool {
+        matches!(self, Tag::List | Tag::Table | Tag::Row)
+    }
+}
+
+#[derive(Debug)]
+enum Token<'a> {
+    Text(&'a str),
+    Open {
+        tag: Tag,
+        argument: Option<&'a str>,
+        raw: &'a str,
+    },
+    Close {
+        tag: Tag,
+        raw: &'a str,
+    },
+}
+
+impl Token<'_> {
+    fn raw(&self) -> &str {
+        match self {
+            Token::Text(text) => text,
+            Token::Open { raw, .. } | Token::Close { raw, .. } => raw,
+        }
+    }
+}
+
+/// An element opened in the output that still needs closing.
+struct Element {
+    tag: Tag,
+    close: &'static str,
+    item_open: bool,
+}
+
+pub(crate) fn to_html(input: &str) -> String {
+    let tokens = tokenize(input);
+    let partners = match_tags(&tokens);
+
+    let mut output = String::with_capacity(input.len());
+    let mut open: Vec<Element> = Vec::new();
+    let mut index = 0;
+
+    while index < tokens.len() {
+        let token = &tokens[index];
+
+        match (token, partners[index]) {
+            (Token::Text(text), _) => {
+                let mut text = *text;
+
+                if index > 0 && is_block_token(&tokens[index - 1], partners[index - 1]) {
+                    text = text
+                        .strip_prefix("\r\n")
+                        .or_else(|| text.strip_prefix('\n'))
+                        .unwrap_or(text);
+                }
+
+                if index + 1 < tokens.len()
+                    && is_block_token(&tokens[index + 1], partners[index + 1])
+                {
+                    text = text
+                        .strip_suffix("\r\n")
+                        .or_else(|| text.strip_suffix('\n'))
+                        .unwrap_or(text);
+                }
+
+                let in_container = open
+                    .last()
+                    .is_some_and(|element| element.tag.is_container() && !element.item_open);
+
+                if !(in_container && text.trim().is_empty()) {
+                    push_text(&mut output, text);
+                }
+            }
+            (Token::Open { tag: Tag::Item, .. }, _) => match open.last_mut() {
+                Some(element) if element.tag == Tag::List => {
+                    if element.item_open {
+                        output.push_str("</li>");
+                    }
+
+                    output.push_str("<li>");
+                    element.item_open = true;
+                }
+                _ => push_text(&mut output, token.raw()),
+            },
+            (Token::Open { tag, argument, .. }, Some(close)) => {
+                match (tag, argument) {
+                    (Tag::Image, _) => {
+                        let src = content_url(&tokens, index).unwrap_or_default();
+
+                        output.push_str(&format!("<img src=\"{}\" alt=\"\">", escape(src)));
+                        index = close + 1;
+                        continue;
+                    }
+                    (Tag::Url, None) => {
+                        let url = content_url(&tokens, index).unwrap_or_default();
+
+                        output.push_str(&format!(
+                            "<a href=\"{}\" rel=\"nofollow noopener noreferrer\">{}</a>",
+                            escape(&normalize_url(url).unwrap_or_default()),
+                            escape(url)
+                        ));
+                        index = close + 1;
+                        continue;
+                    }
+                    _ => (),
+                }
+
+                let (html, close) = open_element(*tag, *argument);
+
+                output.push_str(&html);
+                open.push(Element {
+                    tag: *tag,
+                    close,
+                    item_open: false,
+                });
+            }
+            (Token::Close { .. }, Some(_)) => {
+                if let Some(element) = open.pop() {
+                    if element.item_open {
+                        output.push_str("</li>");
+                    }
+
+                    output.push_str(element.close);
+                }
+            }
+            (Token::Open { .. } | Token::Close { .. }, None) => {
+                push_text(&mut output, token.raw());
+            }
+        }
+
+        index += 1;
+    }
+
+    output
+}
+
+fn is_block_token(token: &Token, partner: Option<usize>) -> bool {
+    match token {
+        Token::Open { tag: Tag::Item, .. } => true,
+        Token::Open { tag, .. } | Token::Close { tag, .. } => tag.is_block() && partner.is_some(),
+        Token::Text(_) => false,
+    }
+}
+
+fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut position = 0;
+
+    while let Some(offset) = input[position..].find('[') {
+        let start = position + offset;
+
+        let tag = input[start..]
+            .find(']')
+            .map(|length| &input[start..=start + length])
+            .and_then(parse_tag);
+
+        match tag {
+            Some(token) => {
+                if text_start < start {
+                    tokens.push(Token::Text(&input[text_start..start]));
+                }
+
+                position = start + token.raw().len();
+                text_start = position;
+                tokens.push(token);
+            }
+            None => position = start + 1,
+        }
+    }
+
+    if text_start < input.len() {
+        tokens.push(Token::Text(&input[text_start..]));
+    }
+
+    tokens
+}
+
+/// Parses `raw`, which includes the surrounding brackets, into a tag token.
+fn parse_tag(raw: &str) -> Option<Token<'_>> {
+    let inner = &raw[1..raw.len() - 1];
+
+    if inner.contains('[') {
+        return None;
+    }
+
+    if let Some(name) = inner.strip_prefix('/') {
+        return match Tag::parse(name)? {
+            Tag::Item => None,
+            tag => Some(Token::Close { tag, raw }),
+        };
+    }
+
+    let (name, argument) = match inner.split_once('=') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (inner, None),
+    };
+
+    let tag = Tag::parse(name)?;
+
+    if argument.is_some() && !tag.takes_argument() {
+        return None;
+    }
+
+    Some(Token::Open { tag, argument, raw })
+}
+
+/// For every tag token, the index of the token closing or opening it. Tags left without a
+/// partner are rendered as text.
+fn match_tags(tokens: &[Token]) -> Vec<Option<usize>> {
+    let mut partners = vec![None; tokens.len()];
+    let mut stack: Vec<(Tag, usize)> = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Open { tag: Tag::Item, .. } | Token::Text(_) => (),
+            Token::Open { tag, .. } => stack.push((*tag, index)),
+            Token::Close { tag, .. } => {
+                // anything opened after the matching tag is unbalanced and left as text
+                if let Some(position) = stack.iter().rposition(|(open, _)| open == tag) {
+                    let (_, open) = stack[position];
+
+                    partners[open] = Some(index);
+                    partners[index] = Some(open);
+                    stack.truncate(position);
+                }
+            }
+        }
+    }
+
+    for (index, token) in tokens.iter().enumerate() {
+        let valid = match (token, partners[index]) {
+            (
+                Token::Open {
+                    tag: Tag::Image, ..
+                },
+                Some(_),
+            ) => content_url(tokens, index).is_some_and(is_allowed_image),
+            (
+                Token::Open {
+                    tag: Tag::Url,
+                    argument: None,
+                    ..
+                },
+                Some(_),
+            ) => content_url(tokens, index).and_then(normalize_url).is_some(),
+            (
+                Token::Open {
+                    tag: Tag::Url,
+                    argument: Some(url),
+                    ..
+                },
+                Some(_),
+            ) => normalize_url(url).is_some(),
+            _ => true,
+        };
+
+        if !valid {
+            if let Some(close) = partners[index] {
+                partners[close] = None;
+            }
+
+            partners[index] = None;
+        }
+    }
+
+    partners
+}
+
+/// The URL between an `[img]` or `[url]` tag and its closing tag, which must be plain text.
+fn content_url<'a>(tokens: &[Token<'a>], open: usize) -> Option<&'a str> {
+    match (tokens.get(open + 1), tokens.get(open + 2)) {
+        (Some(Token::Text(url)), Some(Token::Close { .. })) => Some(url.trim()),
+        _ => None,
+    }
+}
+
+fn open_element(tag: Tag, argument: Option<&str>) -> (String, &'static str) {
+    match tag {
+        Tag::Bold => ("<strong>".to_string(), "</strong>"),
+        Tag::Italic => ("<em>".to_string(), "</em>"),
+        Tag::Underline => ("<u>".to_string(), "</u>"),
+        Tag::Url => (
+            format!(
+                "<a href=\"{}\" rel=\"nofollow noopener noreferrer\">",
+                escape(&argument.and_then(normalize_url).unwrap_or_default())
+            ),
+            "</a>",
+        ),
+        Tag::List => match argument {
+            Some("1") => ("<ol>".to_string(), "</ol>"),
+            Some("a") => ("<ol type=\"a\">".to_string(), "</ol>"),
+            _ => ("<ul>".to_string(), "</ul>"),
+        },
+        Tag::Quote => match argument.and_then(|argument| argument.split(';').next()) {
+            Some(author) if !author.trim().is_empty() => (
+                format!("<blockquote><cite>{}</cite>", escape(author.trim())),
+                "</blockquote>",
+            ),
+            _ => ("<blockquote>".to_string(), "</blockquote>"),
+        },
+        Tag::Box => ("<div class=\"box\">".to_string(), "</div>"),
+        Tag::Spoiler => (
+            format!(
+                "<details class=\"spoiler\"><summary>{}</summary>",
+                escape(argument.unwrap_or("Spoiler"))
+            ),
+            "</details>",
+        ),
+        Tag::Table => ("<table>".to_string(), "</table>"),
+        Tag::Row => ("<tr>".to_string(), "</tr>"),
+        Tag::Cell => ("<td>".to_string(), "</td>"),
+        Tag::Header => ("<th>".to_string(), "</th>"),
+        // rendered without an element stack entry, see `to_html`
+        Tag::Item | Tag::Image => (String::new(), ""),
+    }
+}
+
+/// Absolute http(s) URLs are kept as-is, site-relative paths point at NationStates. Anything
+/// else (`javascript:`, protocol-relative URLs, ...) is rejected.
+fn normalize_url(url: &str) -> Option<String> {
+    let url = url.trim();
+
+    if url.is_empty() || url.chars().any(|char| char.is_whitespace() || char == '"') {
+        return None;
+    }
+
+    let lowercase = url.to_ascii_lowercase();
+
+    if lowercase.starts_with("https://") || lowercase.starts_with("http://") {
+        Some(url.to_string())
+    } else if url.starts_with('/') && !url.starts_with("//") {
+        Some(format!("{}{}", NS_ORIGIN, url))
+    } else {
+        None
+    }
+}
+
+fn is_allowed_image(url: &str) -> bool {
+    let rest = match url.get(..8) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("https://") => &url[8..],
+        _ => return false,
+    };
+
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+
+    if authority.contains('@') || url.chars().any(|char| char.is_whitespace() || char == '"') {
+        return false;
+    }
+
+    let host = authority.to_ascii_lowercase();
+
+    IMAGE_HOSTS.contains(&host.as_str())
+}
+
+fn push_text(output: &mut String, text: &str) {
+    output.push_str(&escape(text).replace("\r\n", "\n").replace('\n', "<br>\n"));
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            char => escaped.push(char),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            (
+                $name,
+                include_str!(concat!("fixtures/bbcode/", $name, ".bbcode")),
+                include_str!(concat!("fixtures/bbcode/", $name, ".html")),
+            )
+        };
+    }
+
+    #[test]
+    fn test_fixtures() {
+        for (name, input, expected) in [
+            fixture!("formatting"),
+            fixture!("links"),
+            fixture!("lists"),
+            fixture!("blocks"),
+            fixture!("tables"),
+            fixture!("images"),
+            fixture!("unbalanced"),
+            fixture!("injection"),
+        ] {
+            assert_eq!(
+                to_html(input.trim_end()),
+                expected.trim_end(),
+                "fixture {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_plain_text_is_escaped() {
+        assert_eq!(to_html(""), "");
+        assert_eq!(to_html("a < b & c"), "a &lt; b &amp; c");
+        assert_eq!(to_html("line\nbreak"), "line<br>\nbreak");
+    }
+
+    #[test]
+    fn test_unknown_and_malformed_tags() {
+        assert_eq!(to_html("[foo]x[/foo]"), "[foo]x[/foo]");
+        assert_eq!(to_html("[b=1]x[/b]"), "[b=1]x[/b]");
+        assert_eq!(to_html("[[b]x[/b]"), "[<strong>x</strong>");
+        assert_eq!(to_html("[b"), "[b");
+        assert_eq!(to_html("]"), "]");
+        assert_eq!(to_html("[/*]"), "[/*]");
+        assert_eq!(to_html("[*] outside"), "[*] outside");
+    }
+
+    #[test]
+    fn test_tags_are_case_insensitive() {
+        assert_eq!(to_html("[B]x[/b]"), "<strong>x</strong>");
+    }
+
+    #[test]
+    fn test_urls() {
+        assert_eq!(
+            normalize_url("https://example.com/a?b=c"),
+            Some("https://example.com/a?b=c".to_string())
+        );
+        assert_eq!(
+            normalize_url("/page=dispatch/id=1"),
+            Some("https://www.nationstates.net/page=dispatch/id=1".to_string())
+        );
+        assert_eq!(normalize_url("javascript:alert(1)"), None);
+        assert_eq!(normalize_url("//evil.example"), None);
+        assert_eq!(normalize_url("https://a b"), None);
+
+        assert!(is_allowed_image("https://i.imgur.com/a.png"));
+        assert!(is_allowed_image(
+            "https://WWW.NationStates.net/images/flag.png"
+        ));
+        assert!(!is_allowed_image("http://i.imgur.com/a.png"));
+        assert!(!is_allowed_image("https://i.imgur.com@evil.example/a.png"));
+        assert!(!is_allowed_image("https://evil.example/i.imgur.com/a.png"));
+    }
+}
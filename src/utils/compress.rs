@@ -0,0 +1,126 @@
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::io::{self, Read, Write};
+
+/// Texts shorter than this are stored as-is; compressing them saves little and costs every read.
+const MIN_SIZE: usize = 1024;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How a dispatch text is stored in `dispatch_content`, as the `text` and `text_compressed`
+/// columns.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Stored {
+    Plain(String),
+    Compressed(Vec<u8>),
+}
+
+impl Stored {
+    pub(crate) fn plain(&self) -> Option<&str> {
+        match self {
+            Stored::Plain(text) => Some(text),
+            Stored::Compressed(_) => None,
+        }
+    }
+
+    pub(crate) fn compressed(&self) -> Option<&[u8]> {
+        match self {
+            Stored::Plain(_) => None,
+            Stored::Compressed(bytes) => Some(bytes),
+        }
+    }
+}
+
+/// Compresses `text` if it's long enough to be worth it.
+pub(crate) fn encode(text: &str) -> Stored {
+    if text.len() < MIN_SIZE {
+        return Stored::Plain(text.to_string());
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+
+    match encoder
+        .write_all(text.as_bytes())
+        .and_then(|_| encoder.finish())
+    {
+        Ok(compressed) if compressed.len() < text.len() => Stored::Compressed(compressed),
+        Ok(_) => Stored::Plain(text.to_string()),
+        Err(e) => {
+            tracing::warn!("unable to compress dispatch text: {}", e);
+
+            Stored::Plain(text.to_string())
+        }
+    }
+}
+
+/// Reads a text back from its columns. Rows written before compression only have `text`.
+/// Compressed blobs are recognised by their magic bytes, so other codecs can be added later
+/// without touching existing rows.
+pub(crate) fn decode(text: Option<String>, compressed: Option<Vec<u8>>) -> io::Result<String> {
+    match (text, compressed) {
+        (Some(text), _) => Ok(text),
+        (None, Some(bytes)) if bytes.starts_with(&GZIP_MAGIC) => {
+            let mut text = String::new();
+            GzDecoder::new(bytes.as_slice()).read_to_string(&mut text)?;
+
+            Ok(text)
+        }
+        (None, Some(_)) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unknown dispatch text encoding",
+        )),
+        (None, None) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "dispatch text missing",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Roughly what one of the trading card guides looks like: lots of repeated table markup.
+    fn guide() -> String {
+        (0..600)
+            .map(|i| {
+                format!(
+                    "[tr][td][nation]card_{i}[/nation][/td][td][b]Legendary[/b][/td][td]{}[/td][td][url=https://www.nationstates.net/page=deck/card={i}/season=3]view[/url][/td][/tr]\n",
+                    i * 7 % 113
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = guide();
+
+        let stored = encode(&text);
+        let compressed = stored.compressed().unwrap();
+
+        assert!(compressed.len() * 5 < text.len());
+        assert_eq!(decode(None, Some(compressed.to_vec())).unwrap(), text);
+    }
+
+    #[test]
+    fn test_short_text_stays_plain() {
+        assert_eq!(encode("[b]hi[/b]"), Stored::Plain("[b]hi[/b]".to_string()));
+    }
+
+    #[test]
+    fn test_legacy_rows_read_as_is() {
+        assert_eq!(
+            decode(Some("[b]hi[/b]".to_string()), None).unwrap(),
+            "[b]hi[/b]"
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_encoding() {
+        assert!(decode(None, Some(vec![0x28, 0xb5, 0x2f, 0xfd])).is_err());
+        assert!(decode(None, Some(vec![0x1f, 0x8b, 0x00])).is_err());
+        assert!(decode(None, None).is_err());
+    }
+}
@@ -1,20 +1,90 @@
-use htmlentity::entity::{CharacterSet, EncodeType, ICodedDataTrait};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt::Write;
 
+/// Replaces every non-ASCII character with a decimal character reference, which is what NS
+/// expects. Works per `char`, i.e. per Unicode scalar value, so characters outside the BMP become
+/// a single reference rather than a surrogate pair. ASCII is left as it is, so entities already
+/// in the text aren't encoded a second time.
 pub(crate) fn encode(input: &str) -> String {
-    input
-        .chars()
-        .map(|char| {
-            if char.is_ascii() {
-                char.to_string()
-            } else {
-                htmlentity::entity::encode(
-                    char.encode_utf8(&mut [0; 4]).as_bytes(),
-                    &EncodeType::Decimal,
-                    &CharacterSet::All,
-                )
-                .to_string()
-                .unwrap()
+    let mut output = String::with_capacity(input.len());
+
+    for char in input.chars() {
+        if char.is_ascii() {
+            output.push(char);
+        } else {
+            let _ = write!(output, "&#{};", u32::from(char));
+        }
+    }
+
+    output
+}
+
+/// The characters [`encode`] replaces in `input` and how often each occurs, most frequent first.
+pub(crate) fn conversions(input: &str) -> Vec<(char, usize)> {
+    let mut counts: Vec<(char, usize)> = Vec::new();
+    let mut positions: HashMap<char, usize> = HashMap::new();
+
+    for char in input.chars().filter(|char| !char.is_ascii()) {
+        match positions.entry(char) {
+            Entry::Occupied(position) => counts[*position.get()].1 += 1,
+            Entry::Vacant(position) => {
+                position.insert(counts.len());
+                counts.push((char, 1));
             }
-        })
-        .collect()
+        }
+    }
+
+    // stable, so ties stay in order of first appearance
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_is_untouched() {
+        let text = "[b]Hello[/b], [url=https://example.com/?a=1&b=2]world[/url]!";
+
+        assert_eq!(encode(text), text);
+    }
+
+    #[test]
+    fn test_existing_entities_are_not_encoded_again() {
+        let text = "Fish &amp; chips &#8212; &mdash; &#x1F600;";
+
+        assert_eq!(encode(text), text);
+    }
+
+    #[test]
+    fn test_latin_punctuation() {
+        assert_eq!(
+            encode("Europeia \u{2014} “home”"),
+            "Europeia &#8212; &#8220;home&#8221;"
+        );
+    }
+
+    #[test]
+    fn test_outside_bmp() {
+        // one reference per character, not one per UTF-16 surrogate
+        assert_eq!(encode("😀"), "&#128512;");
+        assert_eq!(encode("a🏳️‍🌈b"), "a&#127987;&#65039;&#8205;&#127752;b");
+    }
+
+    #[test]
+    fn test_cjk() {
+        assert_eq!(encode("日本語"), "&#26085;&#26412;&#35486;");
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(
+            conversions("“a” — “b” — 😀"),
+            vec![('“', 2), ('”', 2), ('—', 2), ('😀', 1)]
+        );
+        assert!(conversions("plain ascii").is_empty());
+    }
 }
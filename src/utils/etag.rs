@@ -0,0 +1,102 @@
+use axum::http::{HeaderMap, header};
+
+use crate::core::error::Error;
+
+/// Formats `validator` as a strong entity tag.
+pub(crate) fn strong(validator: &str) -> String {
+    format!("\"{}\"", validator)
+}
+
+/// Whether the request's `If-None-Match` matches `etag`, i.e. the client's copy is current.
+/// Uses the weak comparison RFC 9110 prescribes for `If-None-Match`.
+pub(crate) fn matches(headers: &HeaderMap, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// The revision named by the request's `If-Match`, for conditional edits. `*` or no header means
+/// the edit isn't conditional. Only a single strong tag is accepted, as a revision is exact.
+pub(crate) fn if_match_revision(headers: &HeaderMap) -> Result<Option<i32>, Error> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+
+    let value = value.to_str().map_err(|_| Error::InvalidIfMatch)?.trim();
+
+    if value == "*" {
+        return Ok(None);
+    }
+
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .and_then(|revision| revision.parse().ok())
+        .map(Some)
+        .ok_or(Error::InvalidIfMatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(if_none_match: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_str(if_none_match).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_matches() {
+        let etag = strong("41-12");
+
+        assert!(matches(&headers("\"41-12\""), &etag));
+        assert!(matches(&headers("W/\"41-12\""), &etag));
+        assert!(matches(&headers("\"40-12\", \"41-12\""), &etag));
+        assert!(matches(&headers("*"), &etag));
+
+        assert!(!matches(&HeaderMap::new(), &etag));
+        assert!(!matches(&headers("\"40-12\""), &etag));
+        assert!(!matches(&headers("41-12"), &etag));
+    }
+
+    #[test]
+    fn test_mutation_changes_etag() {
+        let before = strong("41-12");
+
+        // an edit adds a dispatch_content row, a removal changes the active count
+        assert!(!matches(&headers(&before), &strong("42-12")));
+        assert!(!matches(&headers(&before), &strong("41-11")));
+    }
+
+    #[test]
+    fn test_if_match_revision() {
+        let if_match = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_MATCH, HeaderValue::from_str(value).unwrap());
+            if_match_revision(&headers)
+        };
+
+        assert_eq!(if_match_revision(&HeaderMap::new()).unwrap(), None);
+        assert_eq!(if_match("\"42\"").unwrap(), Some(42));
+        assert_eq!(if_match(" \"42\" ").unwrap(), Some(42));
+        assert_eq!(if_match("*").unwrap(), None);
+
+        for invalid in ["42", "W/\"42\"", "\"41\", \"42\"", "\"abc\"", "\"\""] {
+            assert!(
+                matches!(if_match(invalid), Err(Error::InvalidIfMatch)),
+                "{invalid} accepted"
+            );
+        }
+    }
+}
@@ -0,0 +1,726 @@
+//! Conversion of Markdown to NationStates BBCode.
+//!
+//! Covers the subset our drafters use: headings, emphasis, strikethrough, links, images, lists,
+//! blockquotes, thematic breaks and code. NS has no inline code, so code spans and fenced blocks
+//! both become `[pre]`. Constructs without a BBCode equivalent are converted to plain text and
+//! reported as warnings instead of failing. BBCode already present in the input is passed through.
+
+/// `[size]` for heading levels 1 to 6.
+const HEADING_SIZES: [u16; 6] = [200, 170, 150, 130, 115, 100];
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Converted {
+    pub(crate) bbcode: String,
+    /// constructs that couldn't be converted faithfully, one entry per kind
+    pub(crate) warnings: Vec<String>,
+}
+
+pub(crate) fn to_bbcode(markdown: &str) -> Converted {
+    let expanded = markdown.replace("\r\n", "\n").replace('\t', "    ");
+    let lines: Vec<&str> = expanded.lines().collect();
+
+    let mut emitter = Emitter {
+        warnings: Vec::new(),
+    };
+
+    let bbcode = emitter.blocks(&lines).join("\n\n");
+
+    Converted {
+        bbcode,
+        warnings: emitter.warnings,
+    }
+}
+
+struct Emitter {
+    warnings: Vec<String>,
+}
+
+impl Emitter {
+    fn warn(&mut self, warning: &str) {
+        if !self.warnings.iter().any(|existing| existing == warning) {
+            self.warnings.push(warning.to_string());
+        }
+    }
+
+    fn blocks(&mut self, lines: &[&str]) -> Vec<String> {
+        let mut blocks = Vec::new();
+        let mut index = 0;
+
+        while index < lines.len() {
+            let line = lines[index];
+
+            if line.trim().is_empty() {
+                index += 1;
+                continue;
+            }
+
+            if let Some(fence) = fence(line) {
+                let mut code = Vec::new();
+                index += 1;
+
+                while index < lines.len() && !closes_fence(lines[index], fence) {
+                    code.push(lines[index]);
+                    index += 1;
+                }
+
+                blocks.push(format!("[pre]{}[/pre]", code.join("\n")));
+                index += 1;
+                continue;
+            }
+
+            if let Some((level, text)) = heading(line) {
+                blocks.push(self.heading(level, text));
+                index += 1;
+                continue;
+            }
+
+            if is_thematic_break(line) {
+                blocks.push("[hr]".to_string());
+                index += 1;
+                continue;
+            }
+
+            if quote_content(line).is_some() {
+                let mut content = Vec::new();
+
+                while index < lines.len() {
+                    match quote_content(lines[index]) {
+                        Some(inner) => content.push(inner),
+                        // lazy continuation of a paragraph inside the quote
+                        None if !lines[index].trim().is_empty()
+                            && content.last().is_some_and(|last| !last.trim().is_empty())
+                            && !starts_block(lines[index]) =>
+                        {
+                            content.push(lines[index])
+                        }
+                        None => break,
+                    }
+                    index += 1;
+                }
+
+                blocks.push(format!(
+                    "[quote]{}[/quote]",
+                    self.blocks(&content).join("\n\n")
+                ));
+                continue;
+            }
+
+            if let Some(marker) = list_marker(line) {
+                let (list, end) = self.list(lines, index, marker);
+                blocks.push(list);
+                index = end;
+                continue;
+            }
+
+            if index + 1 < lines.len() && line.contains('|') && is_table_delimiter(lines[index + 1])
+            {
+                self.warn("tables are not supported and were converted to plain text");
+
+                let mut rows = vec![self.table_row(line)];
+                index += 2;
+
+                while index < lines.len() && lines[index].contains('|') {
+                    rows.push(self.table_row(lines[index]));
+                    index += 1;
+                }
+
+                blocks.push(rows.join("\n"));
+                continue;
+            }
+
+            let mut paragraph = vec![line];
+            index += 1;
+
+            while index < lines.len() {
+                let next = lines[index];
+
+                if let Some(level) = setext_level(next) {
+                    blocks.push(self.heading(level, &join_lines(&paragraph)));
+                    paragraph.clear();
+                    index += 1;
+                    break;
+                }
+
+                if next.trim().is_empty() || starts_block(next) {
+                    break;
+                }
+
+                paragraph.push(next);
+                index += 1;
+            }
+
+            if !paragraph.is_empty() {
+                blocks.push(self.inline(&join_lines(&paragraph)));
+            }
+        }
+
+        blocks
+    }
+
+    fn heading(&mut self, level: usize, text: &str) -> String {
+        format!(
+            "[size={}][b]{}[/b][/size]",
+            HEADING_SIZES[level - 1],
+            self.inline(text.trim())
+        )
+    }
+
+    /// Converts the list starting at `lines[start]`, returning it and the index of the first line
+    /// after it.
+    fn list(&mut self, lines: &[&str], start: usize, first: ListMarker) -> (String, usize) {
+        let mut items: Vec<Vec<&str>> = Vec::new();
+        let mut content_offset = first.content_offset;
+        let mut index = start;
+
+        while index < lines.len() {
+            let line = lines[index];
+
+            match list_marker(line) {
+                Some(marker) if marker.indent < content_offset => {
+                    if marker.ordered != first.ordered {
+                        break;
+                    }
+
+                    content_offset = marker.content_offset;
+                    items.push(vec![&line[marker.content_offset.min(line.len())..]]);
+                    index += 1;
+                    continue;
+                }
+                _ => (),
+            }
+
+            let item = items.last_mut().expect("list starts with an item");
+
+            if line.trim().is_empty() {
+                // a blank line only continues the item if indented content follows
+                let continues = lines[index + 1..]
+                    .iter()
+                    .find(|next| !next.trim().is_empty())
+                    .is_some_and(|next| {
+                        indentation(next) >= content_offset
+                            || list_marker(next).is_some_and(|marker| {
+                                marker.indent < content_offset && marker.ordered == first.ordered
+                            })
+                    });
+
+                if !continues {
+                    break;
+                }
+
+                item.push("");
+            } else if indentation(line) >= content_offset {
+                item.push(&line[content_offset..]);
+            } else if item.last().is_some_and(|last| !last.trim().is_empty()) && !starts_block(line)
+            {
+                item.push(line.trim_start());
+            } else {
+                break;
+            }
+
+            index += 1;
+        }
+
+        let open = if first.ordered { "[list=1]" } else { "[list]" };
+
+        let items = items
+            .iter()
+            .map(|item| format!("[*]{}", self.blocks(item).join("\n")))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        (format!("{}\n{}\n[/list]", open, items), index)
+    }
+
+    fn table_row(&mut self, line: &str) -> String {
+        let row = line.trim();
+        let row = row.strip_prefix('|').unwrap_or(row);
+        let row = row.strip_suffix('|').unwrap_or(row);
+
+        row.split('|')
+            .map(|cell| self.inline(cell.trim()))
+            .collect::<Vec<String>>()
+            .join(" | ")
+    }
+
+    fn inline(&mut self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut position = 0;
+
+        while position < text.len() {
+            let rest = &text[position..];
+
+            if let Some(escaped) = rest
+                .strip_prefix('\\')
+                .and_then(|rest| rest.chars().next())
+                .filter(|c| c.is_ascii_punctuation())
+            {
+                output.push(escaped);
+                position += 2;
+                continue;
+            }
+
+            if rest.starts_with('`') {
+                let ticks = rest.len() - rest.trim_start_matches('`').len();
+
+                match rest[ticks..].find(&rest[..ticks]) {
+                    Some(length) => {
+                        output.push_str("[pre]");
+                        output.push_str(rest[ticks..ticks + length].trim());
+                        output.push_str("[/pre]");
+                        position += 2 * ticks + length;
+                    }
+                    None => {
+                        output.push_str(&rest[..ticks]);
+                        position += ticks;
+                    }
+                }
+                continue;
+            }
+
+            if let Some((bbcode, length)) = self.link(rest) {
+                output.push_str(&bbcode);
+                position += length;
+                continue;
+            }
+
+            if let Some(url) = rest
+                .strip_prefix('<')
+                .and_then(|rest| rest.split_once('>'))
+                .map(|(url, _)| url)
+                .filter(|url| {
+                    (url.starts_with("http://") || url.starts_with("https://"))
+                        && !url.contains(char::is_whitespace)
+                })
+            {
+                output.push_str(&format!("[url]{}[/url]", url));
+                position += url.len() + 2;
+                continue;
+            }
+
+            if let Some((bbcode, length)) = self.emphasis(text, position) {
+                output.push_str(&bbcode);
+                position += length;
+                continue;
+            }
+
+            let c = rest.chars().next().expect("position is within text");
+            output.push(c);
+            position += c.len_utf8();
+        }
+
+        output
+    }
+
+    /// `[text](url)` or `![alt](src)` at the start of `text`, returning the BBCode and the
+    /// number of bytes consumed.
+    fn link(&mut self, text: &str) -> Option<(String, usize)> {
+        let (image, label_start) = match text.as_bytes() {
+            [b'!', b'[', ..] => (true, 2),
+            [b'[', ..] => (false, 1),
+            _ => return None,
+        };
+
+        let mut depth = 1;
+        let label_end = text[label_start..].find(|c| {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => (),
+            }
+            depth == 0
+        })? + label_start;
+
+        let destination = text[label_end + 1..].strip_prefix('(')?;
+        let destination_end = destination.find(')')?;
+        let (url, title) = match destination[..destination_end]
+            .trim()
+            .split_once(char::is_whitespace)
+        {
+            Some((url, title)) => (url, Some(title.trim())),
+            None => (destination[..destination_end].trim(), None),
+        };
+        let url = url.trim_start_matches('<').trim_end_matches('>');
+
+        if url.is_empty() {
+            return None;
+        }
+
+        let length = label_end + 2 + destination_end + 1;
+
+        if image {
+            if title.is_some() {
+                self.warn("image titles are not supported and were dropped");
+            }
+
+            return Some((format!("[img]{}[/img]", url), length));
+        }
+
+        let label = self.inline(&text[label_start..label_end]);
+
+        Some((format!("[url={}]{}[/url]", url, label), length))
+    }
+
+    /// Emphasis or strikethrough opening at `text[position..]`.
+    fn emphasis(&mut self, text: &str, position: usize) -> Option<(String, usize)> {
+        let rest = &text[position..];
+
+        let (delimiter, tag) = if rest.starts_with("**") || rest.starts_with("__") {
+            (&rest[..2], "b")
+        } else if rest.starts_with("~~") {
+            (&rest[..2], "s")
+        } else if rest.starts_with('*') || rest.starts_with('_') {
+            (&rest[..1], "i")
+        } else {
+            return None;
+        };
+
+        let underscore = delimiter.starts_with('_');
+        let before = text[..position].chars().next_back();
+
+        // `snake_case` identifiers don't open emphasis
+        if underscore && before.is_some_and(char::is_alphanumeric) {
+            return None;
+        }
+
+        let inner_start = delimiter.len();
+
+        if rest[inner_start..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+        {
+            return None;
+        }
+
+        let inner_end = find_closer(&rest[inner_start..], delimiter)? + inner_start;
+        let after = rest[inner_end + delimiter.len()..].chars().next();
+
+        if underscore && after.is_some_and(char::is_alphanumeric) {
+            return None;
+        }
+
+        let inner = self.inline(&rest[inner_start..inner_end]);
+
+        Some((
+            format!("[{tag}]{inner}[/{tag}]"),
+            inner_end + delimiter.len(),
+        ))
+    }
+}
+
+/// Position of the delimiter closing an emphasis span in `text`. A single `*` skips over `**`
+/// pairs, so `*a **b** c*` closes at the last asterisk.
+fn find_closer(text: &str, delimiter: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let single = delimiter.len() == 1;
+    let mut position = 0;
+
+    while position < text.len() {
+        if bytes[position] == b'\\' {
+            position += 1 + text[position + 1..]
+                .chars()
+                .next()
+                .map_or(0, char::len_utf8);
+            continue;
+        }
+
+        if text[position..].starts_with(delimiter) {
+            let doubled = single && bytes.get(position + 1) == Some(&bytes[position]);
+
+            if doubled {
+                position += 2;
+                continue;
+            }
+
+            let preceded_by_space = text[..position]
+                .chars()
+                .next_back()
+                .is_none_or(char::is_whitespace);
+
+            if position > 0 && !preceded_by_space {
+                return Some(position);
+            }
+        }
+
+        position += text[position..].chars().next().map_or(1, char::len_utf8);
+    }
+
+    None
+}
+
+/// Joins paragraph lines. Lines ending in two spaces or a backslash keep their line break.
+fn join_lines(lines: &[&str]) -> String {
+    let mut joined = String::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let last = index + 1 == lines.len();
+        let hard_break = line.ends_with("  ") || line.ends_with('\\');
+        let line = line.trim();
+
+        if last {
+            joined.push_str(line);
+        } else if hard_break {
+            joined.push_str(line.strip_suffix('\\').unwrap_or(line));
+            joined.push('\n');
+        } else {
+            joined.push_str(line);
+            joined.push(' ');
+        }
+    }
+
+    joined
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Lines that interrupt a paragraph.
+fn starts_block(line: &str) -> bool {
+    fence(line).is_some()
+        || heading(line).is_some()
+        || is_thematic_break(line)
+        || quote_content(line).is_some()
+        || list_marker(line).is_some()
+}
+
+fn fence(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+
+    ["```", "~~~"]
+        .into_iter()
+        .find(|fence| trimmed.starts_with(fence))
+}
+
+fn closes_fence(line: &str, fence: &str) -> bool {
+    let trimmed = line.trim();
+
+    trimmed.starts_with(fence) && trimmed.chars().all(|c| fence.starts_with(c))
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    if indentation(line) > 3 {
+        return None;
+    }
+
+    let trimmed = line.trim_start();
+    let level = trimmed.len() - trimmed.trim_start_matches('#').len();
+
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+
+    let text = &trimmed[level..];
+
+    if !text.is_empty() && !text.starts_with(' ') {
+        return None;
+    }
+
+    // optional closing sequence, as in `## Title ##`
+    let text = text.trim();
+    let text = match text.trim_end_matches('#') {
+        stripped if stripped.is_empty() || stripped.ends_with(' ') => stripped,
+        _ => text,
+    };
+
+    Some((level, text))
+}
+
+fn setext_level(line: &str) -> Option<usize> {
+    let trimmed = line.trim();
+
+    if indentation(line) > 3 || trimmed.is_empty() {
+        return None;
+    }
+
+    if trimmed.chars().all(|c| c == '=') {
+        Some(1)
+    } else if trimmed.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn is_thematic_break(line: &str) -> bool {
+    if indentation(line) > 3 {
+        return false;
+    }
+
+    let marks: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+
+    marks.len() >= 3
+        && ["-", "*", "_"]
+            .iter()
+            .any(|mark| marks.chars().all(|c| c.to_string() == *mark))
+}
+
+fn quote_content(line: &str) -> Option<&str> {
+    if indentation(line) > 3 {
+        return None;
+    }
+
+    let rest = line.trim_start().strip_prefix('>')?;
+
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+fn is_table_delimiter(line: &str) -> bool {
+    let trimmed = line.trim();
+    let trimmed = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let trimmed = trimmed.strip_suffix('|').unwrap_or(trimmed);
+
+    !trimmed.is_empty()
+        && trimmed.split('|').all(|cell| {
+            let cell = cell.trim();
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+
+            !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+        })
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ListMarker {
+    indent: usize,
+    ordered: bool,
+    /// column where the item's content starts
+    content_offset: usize,
+}
+
+fn list_marker(line: &str) -> Option<ListMarker> {
+    if is_thematic_break(line) {
+        return None;
+    }
+
+    let indent = indentation(line);
+    let rest = &line[indent..];
+
+    let marker_length = match rest.as_bytes().first()? {
+        b'-' | b'*' | b'+' => 1,
+        _ => {
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+            if !(1..=9).contains(&digits)
+                || !matches!(rest.as_bytes().get(digits), Some(b'.' | b')'))
+            {
+                return None;
+            }
+
+            digits + 1
+        }
+    };
+
+    let after = &rest[marker_length..];
+    let spaces = after.len() - after.trim_start_matches(' ').len();
+
+    if after.is_empty() {
+        return Some(ListMarker {
+            indent,
+            ordered: marker_length > 1,
+            content_offset: indent + marker_length + 1,
+        });
+    }
+
+    if spaces == 0 {
+        return None;
+    }
+
+    Some(ListMarker {
+        indent,
+        ordered: marker_length > 1,
+        content_offset: indent + marker_length + spaces.min(4),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bbcode(markdown: &str) -> String {
+        to_bbcode(markdown).bbcode
+    }
+
+    #[test]
+    fn test_inline() {
+        let cases = [
+            ("**bold** and *italic*", "[b]bold[/b] and [i]italic[/i]"),
+            (
+                "__bold__ _italic_ ~~gone~~",
+                "[b]bold[/b] [i]italic[/i] [s]gone[/s]",
+            ),
+            ("*a **b** c*", "[i]a [b]b[/b] c[/i]"),
+            ("snake_case_name", "snake_case_name"),
+            ("2 * 3 * 4", "2 * 3 * 4"),
+            (r"\*literal\*", "*literal*"),
+            ("use `a*b*c` here", "use [pre]a*b*c[/pre] here"),
+            (
+                "[the **site**](https://example.com)",
+                "[url=https://example.com]the [b]site[/b][/url]",
+            ),
+            ("<https://example.com>", "[url]https://example.com[/url]"),
+            (
+                "![flag](https://i.imgur.com/a.png)",
+                "[img]https://i.imgur.com/a.png[/img]",
+            ),
+            ("[not a link]", "[not a link]"),
+            ("äöü *ß*", "äöü [i]ß[/i]"),
+            // a backslash before a character that can't be escaped stays, whatever its width
+            (r"*a\é b*", r"[i]a\é b[/i]"),
+            (r"**x\日本**", r"[b]x\日本[/b]"),
+            (r"_a\ü b_", r"[i]a\ü b[/i]"),
+            (r"~~a\ß~~", r"[s]a\ß[/s]"),
+        ];
+
+        for (markdown, expected) in cases {
+            assert_eq!(bbcode(markdown), expected, "{}", markdown);
+        }
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(
+            bbcode("# Title\n\nSome\ntext.  \nNext line\n\n---\n\nSub\n==="),
+            "[size=200][b]Title[/b][/size]\n\nSome text.\nNext line\n\n[hr]\n\n[size=200][b]Sub[/b][/size]"
+        );
+
+        assert_eq!(
+            bbcode("## Law ##\n> quoted\ncontinued\n> > nested"),
+            "[size=170][b]Law[/b][/size]\n\n[quote]quoted continued\n\n[quote]nested[/quote][/quote]"
+        );
+
+        assert_eq!(bbcode("```\nlet *x* = 1;\n```"), "[pre]let *x* = 1;[/pre]");
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(
+            bbcode("- one\n- two\n  - nested\n- three\n\nafter"),
+            "[list]\n[*]one\n[*]two\n[list]\n[*]nested\n[/list]\n[*]three\n[/list]\n\nafter"
+        );
+
+        assert_eq!(
+            bbcode("1. first\n2. **second**\nwrapped"),
+            "[list=1]\n[*]first\n[*][b]second[/b] wrapped\n[/list]"
+        );
+
+        assert_eq!(
+            bbcode("- bullet\n1. number"),
+            "[list]\n[*]bullet\n[/list]\n\n[list=1]\n[*]number\n[/list]"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_constructs() {
+        let converted = to_bbcode(
+            "| Name | Votes |\n| --- | ---: |\n| *Aye* | 10 |\n\n![map](https://i.imgur.com/a.png \"Map\")",
+        );
+
+        assert_eq!(
+            converted.bbcode,
+            "Name | Votes\n[i]Aye[/i] | 10\n\n[img]https://i.imgur.com/a.png[/img]"
+        );
+        assert_eq!(converted.warnings.len(), 2);
+
+        assert!(to_bbcode("plain").warnings.is_empty());
+    }
+}
@@ -1 +1,8 @@
+pub(crate) mod bbcode;
+pub(crate) mod compress;
 pub(crate) mod encode;
+pub(crate) mod etag;
+pub(crate) mod markdown;
+pub(crate) mod name;
+pub(crate) mod signature;
+pub(crate) mod zip;
@@ -0,0 +1,94 @@
+/// Maximum length of a nation or region name on NationStates.
+const MAX_LENGTH: usize = 40;
+
+/// Converts a nation or region name to the form NS uses in URLs and API calls:
+/// "The North Pacific " and "the__north  pacific" both become "the_north_pacific".
+pub(crate) fn canonicalize(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Whether `name` is a canonical nation or region name.
+pub(crate) fn is_valid(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize() {
+        let cases = [
+            ("europeia", "europeia"),
+            ("europeia ", "europeia"),
+            ("  Europeia", "europeia"),
+            ("The North Pacific", "the_north_pacific"),
+            ("the_north_pacific", "the_north_pacific"),
+            ("10000 Islands", "10000_islands"),
+            ("Lazarus-Reborn", "lazarus-reborn"),
+            ("UPPER CANADAN  EMPIRE", "upper_canadan_empire"),
+            ("upper_canadan_empire", "upper_canadan_empire"),
+            ("_Upper \t_Canadan__Empire_", "upper_canadan_empire"),
+            ("", ""),
+            ("  ", ""),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(canonicalize(input), expected, "canonicalize({:?})", input);
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_variants_agree() {
+        let words = ["Upper", "Canadan", "Empire"];
+        let separators = [" ", "_", "  ", "__", " _ ", "\t"];
+
+        for _ in 0..1000 {
+            let mut variant = separators[rand::random_range(0..separators.len())].to_string();
+
+            for word in words {
+                variant.extend(word.chars().map(|c| {
+                    if rand::random() {
+                        c.to_ascii_uppercase()
+                    } else {
+                        c.to_ascii_lowercase()
+                    }
+                }));
+                variant.push_str(separators[rand::random_range(0..separators.len())]);
+            }
+
+            assert_eq!(
+                canonicalize(&variant),
+                "upper_canadan_empire",
+                "canonicalize({:?})",
+                variant
+            );
+            assert_eq!(
+                canonicalize(&canonicalize(&variant)),
+                canonicalize(&variant)
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("the_north_pacific"));
+        assert!(is_valid("lazarus-reborn"));
+        assert!(is_valid(&"a".repeat(MAX_LENGTH)));
+
+        assert!(!is_valid(""));
+        assert!(!is_valid("The_North_Pacific"));
+        assert!(!is_valid("the north pacific"));
+        assert!(!is_valid("europeia&q=happenings"));
+        assert!(!is_valid("europeïa"));
+        assert!(!is_valid(&"a".repeat(MAX_LENGTH + 1)));
+    }
+}
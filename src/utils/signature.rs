@@ -0,0 +1,180 @@
+//! Signatures of outgoing webhook deliveries. The header carries the time of signing and an
+//! HMAC-SHA256 over `{timestamp}.{body}` keyed with the webhook's secret, hex encoded:
+//!
+//! ```text
+//! X-Eurocore-Signature: t=1700000000,v1=b531ec3c…
+//! ```
+//!
+//! Receivers recompute the HMAC over the raw body they received and reject signatures older than
+//! [`TOLERANCE_SECS`], so a captured delivery can't be replayed later.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt::Write;
+
+pub const HEADER: &str = "X-Eurocore-Signature";
+
+/// How far the signing time may be from the receiver's clock, either way.
+pub const TOLERANCE_SECS: i64 = 300;
+
+/// Why a signature was rejected.
+#[cfg(any(test, feature = "client"))]
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    /// not `t={timestamp},v1={hex}`
+    Malformed,
+    /// signed more than [`TOLERANCE_SECS`] from `now`
+    Expired,
+    Mismatch,
+}
+
+/// The header value for `body` sent at `timestamp`, in seconds since the epoch.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let signature = mac(secret, timestamp, body)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .fold(String::new(), |mut output, byte| {
+            let _ = write!(output, "{:02x}", byte);
+            output
+        });
+
+    format!("t={},v1={}", timestamp, signature)
+}
+
+/// Checks `header` against `body` as received, with `now` in seconds since the epoch. Any one
+/// of several `v1` entries may match, so a delivery can be signed with more than one secret.
+/// eurocore only signs; this is for receivers, through the client.
+#[cfg(any(test, feature = "client"))]
+pub fn verify(secret: &str, header: &str, body: &[u8], now: i64) -> Result<(), SignatureError> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signatures.push(decode(value).ok_or(SignatureError::Malformed)?),
+            // other schemes may be added later
+            Some(_) => {}
+            None => return Err(SignatureError::Malformed),
+        }
+    }
+
+    let timestamp = timestamp.ok_or(SignatureError::Malformed)?;
+
+    if signatures.is_empty() {
+        return Err(SignatureError::Malformed);
+    }
+
+    if (now - timestamp).abs() > TOLERANCE_SECS {
+        return Err(SignatureError::Expired);
+    }
+
+    signatures
+        .iter()
+        .any(|signature| mac(secret, timestamp, body).verify_slice(signature).is_ok())
+        .then_some(())
+        .ok_or(SignatureError::Mismatch)
+}
+
+fn mac(secret: &str, timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    mac
+}
+
+#[cfg(any(test, feature = "client"))]
+fn decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed vectors receivers can check their implementation against.
+    const VECTORS: [(&str, i64, &str, &str); 3] = [
+        (
+            "whsec_test_secret_1",
+            1700000000,
+            r#"{"job":"dispatch","id":1,"status":"succeeded","self":"/queue/dispatches/1","resource":"/dispatches/1000"}"#,
+            "t=1700000000,v1=b531ec3cf2d2bc3d4e17b5163e695965c97f5a618651a346bd2e8713292eb7de",
+        ),
+        (
+            "whsec_test_secret_1",
+            1700000000,
+            "",
+            "t=1700000000,v1=1255ce5be0b18d90cb33658a5bd4895f474de17cd78fe3fd952061be13ae7830",
+        ),
+        (
+            "another secret",
+            1767225600,
+            r#"{"job":"rmbpost","id":7,"status":"failed","error_code":"rate_limited","self":"/queue/rmbposts/7","resource":null}"#,
+            "t=1767225600,v1=adb2b57182ac727fe03f0fff0906e143919c896257b9773d52f5403ac0622dea",
+        ),
+    ];
+
+    #[test]
+    fn test_known_signatures() {
+        for (secret, timestamp, body, header) in VECTORS {
+            assert_eq!(sign(secret, timestamp, body.as_bytes()), header);
+            assert_eq!(
+                verify(secret, header, body.as_bytes(), timestamp + 10),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejected_signatures() {
+        let (secret, timestamp, body, header) = VECTORS[0];
+
+        assert_eq!(
+            verify("wrong", header, body.as_bytes(), timestamp),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify(secret, header, b"{}", timestamp),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify(
+                secret,
+                header,
+                body.as_bytes(),
+                timestamp + TOLERANCE_SECS + 1
+            ),
+            Err(SignatureError::Expired)
+        );
+
+        for header in [
+            "",
+            "v1=abcd",
+            "t=1700000000",
+            "t=1700000000,v1=xyz",
+            "garbage",
+        ] {
+            assert_eq!(
+                verify(secret, header, body.as_bytes(), timestamp),
+                Err(SignatureError::Malformed),
+                "{header}"
+            );
+        }
+
+        // one matching signature among several is enough
+        let rotated = format!("{},v1={}", header, "00".repeat(32));
+        assert_eq!(verify(secret, &rotated, body.as_bytes(), timestamp), Ok(()));
+    }
+}
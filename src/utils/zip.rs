@@ -0,0 +1,252 @@
+//! A minimal ZIP writer that hands each entry back as soon as it's added, so archives can be
+//! streamed. Entries are deflated and carry their CRC in the local header, which needs the whole
+//! entry in memory but nothing else. Only the central directory is held until [`Writer::finish`].
+//!
+//! No ZIP64: archives are limited to 65535 entries and 4 GiB, past which [`Writer::file`] fails.
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use std::io::{self, Write};
+
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+
+/// 2.0, the first version with deflate
+const VERSION: u16 = 20;
+/// names are UTF-8
+const FLAGS: u16 = 1 << 11;
+const DEFLATE: u16 = 8;
+
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+    time: u16,
+    date: u16,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Writer {
+    offset: u64,
+    entries: Vec<Entry>,
+}
+
+impl Writer {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// The bytes of a file entry, to be sent right after those of the previous entry.
+    pub(crate) fn file(
+        &mut self,
+        name: &str,
+        contents: &[u8],
+        modified: DateTime<Utc>,
+    ) -> io::Result<Vec<u8>> {
+        if self.entries.len() == u16::MAX as usize {
+            return Err(too_large());
+        }
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(contents)?;
+        let data = encoder.finish()?;
+
+        let mut crc = flate2::Crc::new();
+        crc.update(contents);
+
+        let (time, date) = dos_time(modified);
+
+        let entry = Entry {
+            name: name.to_string(),
+            crc: crc.sum(),
+            compressed_size: u32::try_from(data.len()).map_err(|_| too_large())?,
+            size: u32::try_from(contents.len()).map_err(|_| too_large())?,
+            offset: u32::try_from(self.offset).map_err(|_| too_large())?,
+            time,
+            date,
+        };
+
+        let mut bytes = Vec::with_capacity(30 + name.len() + data.len());
+
+        put_u32(&mut bytes, LOCAL_HEADER);
+        put_u16(&mut bytes, VERSION);
+        put_u16(&mut bytes, FLAGS);
+        put_u16(&mut bytes, DEFLATE);
+        put_u16(&mut bytes, entry.time);
+        put_u16(&mut bytes, entry.date);
+        put_u32(&mut bytes, entry.crc);
+        put_u32(&mut bytes, entry.compressed_size);
+        put_u32(&mut bytes, entry.size);
+        put_u16(&mut bytes, name_length(name)?);
+        // no extra field
+        put_u16(&mut bytes, 0);
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&data);
+
+        self.offset += bytes.len() as u64;
+        self.entries.push(entry);
+
+        Ok(bytes)
+    }
+
+    /// The central directory, which ends the archive.
+    pub(crate) fn finish(self) -> io::Result<Vec<u8>> {
+        let start = u32::try_from(self.offset).map_err(|_| too_large())?;
+        let mut bytes = Vec::new();
+
+        for entry in &self.entries {
+            put_u32(&mut bytes, CENTRAL_HEADER);
+            // made by
+            put_u16(&mut bytes, VERSION);
+            put_u16(&mut bytes, VERSION);
+            put_u16(&mut bytes, FLAGS);
+            put_u16(&mut bytes, DEFLATE);
+            put_u16(&mut bytes, entry.time);
+            put_u16(&mut bytes, entry.date);
+            put_u32(&mut bytes, entry.crc);
+            put_u32(&mut bytes, entry.compressed_size);
+            put_u32(&mut bytes, entry.size);
+            put_u16(&mut bytes, name_length(&entry.name)?);
+            // extra field, comment, disk, internal and external attributes
+            put_u16(&mut bytes, 0);
+            put_u16(&mut bytes, 0);
+            put_u16(&mut bytes, 0);
+            put_u16(&mut bytes, 0);
+            put_u32(&mut bytes, 0);
+            put_u32(&mut bytes, entry.offset);
+            bytes.extend_from_slice(entry.name.as_bytes());
+        }
+
+        let size = u32::try_from(bytes.len()).map_err(|_| too_large())?;
+        let count = self.entries.len() as u16;
+
+        put_u32(&mut bytes, END_OF_CENTRAL_DIRECTORY);
+        // this disk, and the disk the central directory starts on
+        put_u16(&mut bytes, 0);
+        put_u16(&mut bytes, 0);
+        put_u16(&mut bytes, count);
+        put_u16(&mut bytes, count);
+        put_u32(&mut bytes, size);
+        put_u32(&mut bytes, start);
+        // no comment
+        put_u16(&mut bytes, 0);
+
+        Ok(bytes)
+    }
+}
+
+fn put_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn name_length(name: &str) -> io::Result<u16> {
+    u16::try_from(name.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "name too long"))
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "archive too large for ZIP without ZIP64",
+    )
+}
+
+/// MS-DOS time and date, which start in 1980 and count seconds in twos.
+fn dos_time(at: DateTime<Utc>) -> (u16, u16) {
+    let year = at.year().clamp(1980, 2107) as u16;
+
+    (
+        (at.hour() as u16) << 11 | (at.minute() as u16) << 5 | ((at.second() as u16) / 2),
+        (year - 1980) << 9 | (at.month() as u16) << 5 | at.day() as u16,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::DeflateDecoder;
+    use std::io::Read;
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_archive_layout() {
+        let modified = DateTime::parse_from_rfc3339("2026-10-15T12:34:56Z")
+            .unwrap()
+            .to_utc();
+        let files = [
+            ("testlandia/Factbook_1/1.bbcode", "[b]Hello[/b] ".repeat(50)),
+            ("testlandia/Factbook_1/2.bbcode", "Zürich".to_string()),
+        ];
+
+        let mut writer = Writer::new();
+        let mut archive = Vec::new();
+
+        for (name, contents) in &files {
+            archive.extend(writer.file(name, contents.as_bytes(), modified).unwrap());
+        }
+
+        let directory_at = archive.len();
+        archive.extend(writer.finish().unwrap());
+
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(&archive, end), END_OF_CENTRAL_DIRECTORY);
+        assert_eq!(u16_at(&archive, end + 10), 2);
+        assert_eq!(u32_at(&archive, end + 16) as usize, directory_at);
+
+        let mut central = directory_at;
+        for (name, contents) in &files {
+            assert_eq!(u32_at(&archive, central), CENTRAL_HEADER);
+            let name_length = u16_at(&archive, central + 28) as usize;
+            assert_eq!(
+                &archive[central + 46..central + 46 + name_length],
+                name.as_bytes()
+            );
+
+            let local = u32_at(&archive, central + 42) as usize;
+            assert_eq!(u32_at(&archive, local), LOCAL_HEADER);
+            assert_eq!(u16_at(&archive, local + 10), 12 << 11 | 34 << 5 | (56 / 2));
+            assert_eq!(u16_at(&archive, local + 12), 46 << 9 | 10 << 5 | 15);
+
+            let compressed_size = u32_at(&archive, local + 18) as usize;
+            let data = local + 30 + name_length;
+            let mut inflated = String::new();
+            DeflateDecoder::new(&archive[data..data + compressed_size])
+                .read_to_string(&mut inflated)
+                .unwrap();
+            assert_eq!(&inflated, contents);
+
+            let mut crc = flate2::Crc::new();
+            crc.update(contents.as_bytes());
+            assert_eq!(u32_at(&archive, local + 14), crc.sum());
+            assert_eq!(u32_at(&archive, central + 16), crc.sum());
+
+            central += 46 + name_length;
+        }
+
+        assert_eq!(central, end);
+    }
+
+    #[test]
+    fn test_empty_archive() {
+        let archive = Writer::new().finish().unwrap();
+
+        assert_eq!(archive.len(), 22);
+        assert_eq!(u32_at(&archive, 0), END_OF_CENTRAL_DIRECTORY);
+    }
+}
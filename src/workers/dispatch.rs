@@ -1,31 +1,343 @@
-use super::PERIOD;
+use super::{
+    Activity, DEFERRED_PERIOD, PERIOD, Phase, Pipeline, Recovery, Unfinished, is_transient, retry,
+};
 use crate::core::error::{ConfigError, Error};
-use crate::ns::dispatch::{self, Action, Command, Dispatch, IntermediateDispatch};
+use crate::ns;
+use crate::ns::dispatch::{
+    self, Action, Command, Dispatch, FactbookCategory, IntermediateDispatch, Operation,
+    QueueSummary, QueuedDispatchPayload, Source, encoded_length,
+};
+use crate::ns::error::{self, NsError};
+use crate::ns::reply::{self, Reply};
 use crate::ns::types::Mode;
 use crate::sync::{
-    nations,
-    ratelimiter::{self, Target},
+    events, nations,
+    ratelimiter::{self, Reservation, RestrictedAction, Target},
 };
+use crate::types::job::JobStatus;
+use crate::types::request::JobKind;
+use crate::types::response::{DispatchStatus, JobEvent};
+use crate::types::{DEFAULT_TENANT, Secret};
+use crate::utils::compress;
 use crate::utils::encode::encode;
-use quick_xml::de;
+use crate::utils::name;
 use regex::Regex;
-use serde::Deserialize;
-use sqlx::postgres::PgPool;
-use std::collections::VecDeque;
+use sqlx::Row;
+use sqlx::postgres::{PgPool, PgRow};
+use sqlx::types::Json;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio::task::{self, JoinError, JoinSet};
+use tracing::Instrument;
+
+/// How far back a dispatch found on NS after an ambiguous execute request may have been created
+/// and still be taken for the job's, allowing for NS's clock being off.
+const VERIFY_WINDOW: chrono::TimeDelta = chrono::TimeDelta::minutes(5);
+
+/// Jobs waiting to be posted, in one FIFO queue per nation. Nations take turns, so one with a
+/// long backlog can't keep the others waiting on its cooldowns. A dispatch belongs to one nation,
+/// so the jobs on it are posted one after another in job order.
+#[derive(Debug, Default)]
+struct NationQueues {
+    /// in turn order, the nation served last at the back; a nation leaves when its queue empties
+    nations: VecDeque<(String, VecDeque<IntermediateDispatch>)>,
+}
+
+impl NationQueues {
+    /// Queues `dispatch` unless its job is already waiting, as it can be when a job queued during
+    /// startup is also picked up by [`Client::recover`].
+    ///
+    /// Jobs can arrive out of job order when requests race. One that did goes ahead of the later
+    /// jobs on its dispatch, so an edit never lands after the delete queued behind it.
+    fn push(&mut self, dispatch: IntermediateDispatch) -> bool {
+        if self.iter().any(|queued| queued.job_id == dispatch.job_id) {
+            return false;
+        }
+
+        let nation = name::canonicalize(&dispatch.nation);
+
+        let overtaken = dispatch
+            .dispatch_id()
+            .and_then(|id| {
+                self.for_dispatch(id)
+                    .find(|queued| queued.job_id > dispatch.job_id)
+            })
+            .map(|queued| queued.job_id);
+
+        match self
+            .nations
+            .iter_mut()
+            .find(|(queued, _)| *queued == nation)
+        {
+            Some((_, queue)) => {
+                match overtaken
+                    .and_then(|job_id| queue.iter().position(|queued| queued.job_id == job_id))
+                {
+                    Some(position) => queue.insert(position, dispatch),
+                    None => queue.push_back(dispatch),
+                }
+            }
+            None => self.nations.push_back((nation, VecDeque::from([dispatch]))),
+        }
+
+        true
+    }
+
+    /// The waiting job `dispatch`, an edit, may be posted in place of: an edit by the same user,
+    /// queued before it, that is the last job waiting on the dispatch. Replacing one with a job
+    /// behind it would post the newer text before that job, or undo a delete.
+    fn coalescable(&self, dispatch: &IntermediateDispatch) -> Option<i32> {
+        let Action::Edit { id, .. } = dispatch.action else {
+            return None;
+        };
+
+        let last = self.for_dispatch(id).last()?;
+
+        (matches!(last.action, Action::Edit { .. })
+            && last.user == dispatch.user
+            && last.job_id < dispatch.job_id)
+            .then_some(last.job_id)
+    }
 
+    /// Puts `dispatch` where job `job_id` is waiting, or queues it if that job isn't waiting.
+    fn replace(&mut self, job_id: i32, dispatch: IntermediateDispatch) {
+        match self
+            .nations
+            .iter_mut()
+            .flat_map(|(_, queue)| queue.iter_mut())
+            .find(|queued| queued.job_id == job_id)
+        {
+            Some(queued) => *queued = dispatch,
+            None => {
+                self.push(dispatch);
+            }
+        }
+    }
+
+    /// Jobs waiting on NS dispatch `id`, in the order they'll be posted.
+    fn for_dispatch(&self, id: i32) -> impl Iterator<Item = &IntermediateDispatch> {
+        self.iter()
+            .filter(move |dispatch| dispatch.dispatch_id() == Some(id))
+    }
+
+    /// Job `job_id`, if it's waiting, and how many jobs of its nation are ahead of it.
+    fn position(&self, job_id: i32) -> Option<(usize, &IntermediateDispatch)> {
+        self.nations.iter().find_map(|(_, queue)| {
+            queue
+                .iter()
+                .enumerate()
+                .find(|(_, dispatch)| dispatch.job_id == job_id)
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.nations.iter().map(|(_, queue)| queue.len()).sum()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &IntermediateDispatch> {
+        self.nations.iter().flat_map(|(_, queue)| queue)
+    }
+
+    /// Takes the next job of the first nation, in turn order, whose limit `ready`, given the job's
+    /// id and target, says is satisfied, and sends that nation to the back of the line. Only the
+    /// head of each nation's queue is considered, so its jobs are posted in the order they were
+    /// queued, and nations in `busy` are skipped, so a nation never has two jobs posting at once.
+    ///
+    /// The heads are collected before `ready` is first awaited and the job is then taken by its
+    /// id, so nothing depends on positions in the queues staying put across the awaits.
+    async fn pop<F, R>(
+        &mut self,
+        busy: &HashSet<&str>,
+        mut ready: F,
+    ) -> Option<IntermediateDispatch>
+    where
+        F: FnMut(i32, Target) -> R,
+        R: Future<Output = bool>,
+    {
+        let heads = self
+            .nations
+            .iter()
+            .filter(|(nation, _)| !busy.contains(nation.as_str()))
+            .filter_map(|(nation, queue)| {
+                let dispatch = queue.front()?;
+                Some((nation.clone(), dispatch.job_id, target(dispatch)))
+            })
+            .collect::<Vec<_>>();
+
+        for (nation, job_id, target) in heads {
+            if ready(job_id, target).await {
+                return self.take(&nation, job_id);
+            }
+        }
+
+        None
+    }
+
+    /// Removes job `job_id` from `nation`'s queue and sends the nation to the back of the line.
+    fn take(&mut self, nation: &str, job_id: i32) -> Option<IntermediateDispatch> {
+        let index = self
+            .nations
+            .iter()
+            .position(|(queued, _)| queued == nation)?;
+        let (nation, mut queue) = self.nations.remove(index)?;
+
+        let dispatch = queue
+            .iter()
+            .position(|dispatch| dispatch.job_id == job_id)
+            .and_then(|position| queue.remove(position));
+
+        if !queue.is_empty() {
+            self.nations.push_back((nation, queue));
+        }
+
+        dispatch
+    }
+}
+
+/// Bucket gating the prepare step of `dispatch`. Only adding a dispatch is a restricted action.
+fn target(dispatch: &IntermediateDispatch) -> Target {
+    match dispatch.action {
+        Action::Add { .. } => Target::restricted(&dispatch.nation, RestrictedAction::Dispatch),
+        Action::Edit { .. } | Action::Remove { .. } => Target::Standard,
+    }
+}
+
+/// A row recording what a job did on NS.
 #[derive(Debug)]
-pub(crate) struct Client {
+enum Record {
+    Header {
+        id: i32,
+        nation: String,
+    },
+    Content {
+        id: i32,
+        category: FactbookCategory,
+        title: String,
+        text: String,
+        /// length of `text` once encoded for NS
+        size: usize,
+        source: Source,
+        created_by: String,
+    },
+    Inactive {
+        id: i32,
+        deleted_by: String,
+    },
+}
+
+/// What a finished job did, held until it's all written. NS can't be asked again, so a write
+/// lost to a database outage would leave the job queued although its dispatch was posted.
+#[derive(Debug)]
+struct Outcome {
+    job_id: i32,
+    status: JobStatus,
+    dispatch_id: Option<i32>,
+    error: Option<Error>,
+    /// written in order, before the job's status
+    records: VecDeque<Record>,
+}
+
+impl Outcome {
+    fn new(dispatch: IntermediateDispatch, result: Result<i32, Error>) -> Self {
+        let (status, dispatch_id, error) = match result {
+            Ok(id) => (JobStatus::Succeeded, Some(id), None),
+            Err(e) => (JobStatus::Failed, None, Some(e)),
+        };
+
+        let records = match (dispatch_id, dispatch.action) {
+            (None, _) => VecDeque::new(),
+            (
+                Some(id),
+                Action::Add {
+                    title,
+                    text,
+                    source,
+                    category,
+                },
+            ) => VecDeque::from([
+                Record::Header {
+                    id,
+                    nation: dispatch.nation,
+                },
+                Record::Content {
+                    id,
+                    category,
+                    title,
+                    size: encoded_length(&text),
+                    text,
+                    source,
+                    created_by: dispatch.user,
+                },
+            ]),
+            (
+                Some(_),
+                Action::Edit {
+                    id,
+                    title,
+                    text,
+                    source,
+                    category,
+                },
+            ) => VecDeque::from([Record::Content {
+                id,
+                category,
+                title,
+                size: encoded_length(&text),
+                text,
+                source,
+                created_by: dispatch.user,
+            }]),
+            (Some(_), Action::Remove { id }) => VecDeque::from([Record::Inactive {
+                id,
+                deleted_by: dispatch.user,
+            }]),
+        };
+
+        Self {
+            job_id: dispatch.job_id,
+            status,
+            dispatch_id,
+            error,
+            records,
+        }
+    }
+}
+
+/// What a posting task hands back: the job it posted and how that went.
+type Posted = (IntermediateDispatch, Result<i32, Error>);
+
+/// The NS and database side of posting a job, cloned onto the task posting it.
+#[derive(Clone, Debug)]
+struct Poster {
     url: String,
     client: reqwest::Client,
     pool: PgPool,
-    queue: VecDeque<IntermediateDispatch>,
     limiter: ratelimiter::Sender,
     nations: nations::Sender,
-    rx: mpsc::Receiver<Command>,
+    events: events::Sender,
     re: Regex,
 }
 
+#[derive(Debug)]
+pub(crate) struct Client {
+    poster: Poster,
+    queue: NationQueues,
+    rx: mpsc::Receiver<Command>,
+    pipeline: Pipeline,
+    recovery: Recovery,
+    /// outcomes the database couldn't take, oldest first
+    unwritten: VecDeque<Outcome>,
+    tasks: JoinSet<Posted>,
+    /// the nation and job of each running task, a nation has at most one
+    in_flight: HashMap<task::Id, (String, i32)>,
+    /// jobs posted at once, each for a different nation
+    concurrency: usize,
+    /// the ratelimiter reservation of each waiting job, by job id
+    reservations: HashMap<i32, Reservation>,
+}
+
 impl Client {
     fn new(
         user_agent: &str,
@@ -33,96 +345,957 @@ impl Client {
         pool: PgPool,
         limiter: ratelimiter::Sender,
         nations: nations::Sender,
+        events: events::Sender,
         rx: mpsc::Receiver<Command>,
     ) -> Result<Self, ConfigError> {
         let client = reqwest::Client::builder().user_agent(user_agent).build()?;
 
         Ok(Self {
-            url: url.to_string(),
-            client,
-            pool,
-            queue: VecDeque::new(),
-            limiter,
-            nations,
+            poster: Poster {
+                url: url.to_string(),
+                client,
+                pool,
+                limiter,
+                nations,
+                events,
+                re: Regex::new(r#"(\d+)"#)?,
+            },
+            queue: NationQueues::default(),
             rx,
-            re: Regex::new(r#"(\d+)"#)?,
+            pipeline: Pipeline::new("dispatch"),
+            recovery: Recovery::default(),
+            unwritten: VecDeque::new(),
+            tasks: JoinSet::new(),
+            in_flight: HashMap::new(),
+            concurrency: 1,
+            reservations: HashMap::new(),
         })
     }
 
+    /// Picks up the jobs an earlier worker accepted but didn't finish, so a restart doesn't
+    /// leave them pending forever. Those deferred while its channel was full are picked up with
+    /// them.
+    #[tracing::instrument(skip_all)]
+    async fn recover(&mut self) {
+        self.undefer().await;
+        self.restore(None).await;
+    }
+
+    /// Takes over the jobs deferred since the last look, which were stored but never sent to
+    /// the worker.
+    #[tracing::instrument(skip_all)]
+    async fn pick_up_deferred(&mut self) {
+        let job_ids = self.undefer().await;
+
+        if !job_ids.is_empty() {
+            tracing::info!(count = job_ids.len(), "picking up deferred jobs");
+            self.restore(Some(&job_ids)).await;
+        }
+    }
+
+    /// Moves every deferred job back to `queued`, returning their ids.
+    async fn undefer(&self) -> Vec<i32> {
+        match sqlx::query(
+            "UPDATE dispatch_queue SET status = 'queued', modified_at = $1
+            WHERE status = 'deferred'
+            RETURNING id;",
+        )
+        .bind(chrono::Utc::now())
+        .map(|row: PgRow| row.get("id"))
+        .fetch_all(&self.poster.pool)
+        .await
+        {
+            Ok(job_ids) => job_ids,
+            Err(e) => {
+                tracing::error!("{}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Queues the unfinished jobs, those in `job_ids` if given, or settles them if they can't
+    /// be posted any more.
+    async fn restore(&mut self, job_ids: Option<&[i32]>) {
+        let jobs = match sqlx::query(
+            "SELECT
+                dispatch_queue.id,
+                dispatch_queue.type AS action,
+                dispatch_queue.dispatch_id,
+                dispatch_queue.payload,
+                dispatch_queue.status,
+                dispatch_queue.created_by,
+                dispatch_queue.created_at,
+                dispatch_queue.executing_at,
+                dispatch_queue.request_id,
+                dispatches.nation
+            FROM dispatch_queue
+            LEFT JOIN dispatches ON dispatches.dispatch_id = COALESCE(
+                (dispatch_queue.payload->'edit'->>'id')::INTEGER,
+                (dispatch_queue.payload->'remove'->>'id')::INTEGER,
+                dispatch_queue.dispatch_id
+            )
+            WHERE dispatch_queue.status = ANY($2)
+            -- deferred jobs are taken over by the deferred pass instead
+            AND dispatch_queue.status <> 'deferred'
+            AND ($1::INTEGER[] IS NULL OR dispatch_queue.id = ANY($1))
+            ORDER BY dispatch_queue.id;",
+        )
+        .bind(job_ids)
+        .bind(JobStatus::PENDING)
+        .map(|row: PgRow| {
+            let Json(payload): Json<serde_json::Value> = row.get("payload");
+            let action: String = row.get("action");
+
+            let dispatch =
+                QueuedDispatchPayload::from_stored(&action, row.get("dispatch_id"), payload)
+                    .and_then(|payload| {
+                        IntermediateDispatch::restore(
+                            row.get("id"),
+                            row.get::<Option<String>, _>("created_by")
+                                .unwrap_or_default(),
+                            payload,
+                            row.get("nation"),
+                        )
+                    })
+                    .map(|mut dispatch| {
+                        dispatch.request_id = row.get("request_id");
+                        dispatch
+                    });
+
+            (
+                row.get::<i32, _>("id"),
+                row.get::<JobStatus, _>("status"),
+                row.get::<chrono::DateTime<chrono::Utc>, _>("created_at"),
+                row.get::<Option<chrono::DateTime<chrono::Utc>>, _>("executing_at"),
+                dispatch,
+            )
+        })
+        .fetch_all(&self.poster.pool)
+        .await
+        {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                tracing::error!("{}", e);
+                return;
+            }
+        };
+
+        let now = chrono::Utc::now();
+
+        for (job_id, status, created_at, executing_at, dispatch) in jobs {
+            match (self.recovery.decide(status, created_at, now), dispatch) {
+                (Unfinished::Requeue, Some(dispatch)) => {
+                    tracing::info!(job_id, "requeueing job");
+                    self.enqueue(dispatch).await;
+                }
+                (Unfinished::Requeue | Unfinished::Abandon, _) => {
+                    tracing::warn!(job_id, "abandoning job");
+                    self.poster
+                        .update_job(
+                            job_id,
+                            JobStatus::Cancelled,
+                            None,
+                            Some(&Error::JobAbandoned),
+                        )
+                        .await;
+                }
+                (Unfinished::Verify, Some(dispatch)) => {
+                    tracing::info!(job_id, "looking for the job's dispatch again");
+                    self.verify_again(dispatch, executing_at.unwrap_or(created_at));
+                }
+                (Unfinished::Interrupted | Unfinished::Verify, _) => {
+                    tracing::warn!(job_id, "job was interrupted");
+                    self.poster
+                        .update_job(
+                            job_id,
+                            JobStatus::Failed,
+                            None,
+                            Some(&Error::JobInterrupted),
+                        )
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// Looks for the dispatch of a job left unknown on a task of its own, as if posting it, the
+    /// execute request having been sent at `sent`.
+    fn verify_again(
+        &mut self,
+        dispatch: IntermediateDispatch,
+        sent: chrono::DateTime<chrono::Utc>,
+    ) {
+        let span = tracing::info_span!(
+            "job",
+            job_id = dispatch.job_id,
+            nation = dispatch.nation,
+            request_id = dispatch.request_id.as_deref()
+        );
+
+        let (nation, job_id) = (dispatch.nation.clone(), dispatch.job_id);
+        let poster = self.poster.clone();
+
+        let task = self.tasks.spawn(
+            async move {
+                let result = poster
+                    .look_for(
+                        job_id,
+                        &Dispatch::from(dispatch.clone()),
+                        sent,
+                        Error::JobNotPosted,
+                    )
+                    .await;
+
+                (dispatch, result)
+            }
+            .instrument(span),
+        );
+
+        self.pipeline.posting(job_id, &nation);
+        self.in_flight.insert(task.id(), (nation, job_id));
+    }
+
+    /// Looks again for the dispatches of the jobs left unknown because NS couldn't be asked,
+    /// other than those being looked for or waiting on the database already.
+    #[tracing::instrument(skip_all)]
+    async fn pick_up_unknown(&mut self) {
+        let busy: Vec<i32> = self
+            .in_flight
+            .values()
+            .map(|(_, job_id)| *job_id)
+            .chain(self.unwritten.iter().map(|outcome| outcome.job_id))
+            .collect();
+
+        let job_ids: Vec<i32> = match sqlx::query(
+            "SELECT id FROM dispatch_queue WHERE status = 'unknown' AND NOT (id = ANY($1));",
+        )
+        .bind(&busy)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_all(&self.poster.pool)
+        .await
+        {
+            Ok(job_ids) => job_ids,
+            Err(e) => {
+                tracing::error!("{}", e);
+                return;
+            }
+        };
+
+        if !job_ids.is_empty() {
+            tracing::info!(count = job_ids.len(), "verifying unknown jobs");
+            self.restore(Some(&job_ids)).await;
+        }
+    }
+
+    /// Queues `dispatch` and reserves its slot with the ratelimiter, so estimates for jobs
+    /// queued after it wait behind it.
+    async fn enqueue(&mut self, dispatch: IntermediateDispatch) {
+        let (job_id, target) = (dispatch.job_id, target(&dispatch));
+
+        if self.queue.push(dispatch) {
+            let reservation = self.poster.limiter.reserve(target).await;
+            self.reservations.insert(job_id, reservation);
+        }
+    }
+
+    /// Queues `dispatch`, an edit, in place of the edit it supersedes if there is one. It takes
+    /// over that edit's reservation, both being for the standard bucket.
+    #[tracing::instrument(skip_all)]
+    async fn coalesce(&mut self, dispatch: IntermediateDispatch) {
+        if let Some(job_id) = self.queue.coalescable(&dispatch)
+            && self.poster.supersede_job(job_id, dispatch.job_id).await
+        {
+            tracing::info!(job_id, by = dispatch.job_id, "job superseded");
+
+            if let Some(reservation) = self.reservations.remove(&job_id) {
+                self.reservations.insert(dispatch.job_id, reservation);
+            }
+
+            self.queue.replace(job_id, dispatch);
+
+            return;
+        }
+
+        self.enqueue(dispatch).await;
+    }
+
+    /// Writes `outcome`, or holds it, and everything finished after it, until the database is
+    /// back.
+    async fn record(&mut self, outcome: Outcome) {
+        if !self.unwritten.is_empty() {
+            return self.hold(outcome);
+        }
+
+        if let Err(outcome) = self.poster.write(outcome).await {
+            self.hold(outcome);
+        }
+    }
+
+    fn hold(&mut self, outcome: Outcome) {
+        tracing::warn!(
+            job_id = outcome.job_id,
+            "holding the job's outcome until the database is back"
+        );
+
+        self.unwritten.push_back(outcome);
+        self.pipeline.set_unwritten(self.unwritten.len());
+    }
+
+    /// Writes the held outcomes in the order their jobs finished, stopping at the first the
+    /// database still can't take.
+    #[tracing::instrument(skip_all)]
+    async fn flush(&mut self) {
+        while let Some(outcome) = self.unwritten.pop_front() {
+            let job_id = outcome.job_id;
+
+            if let Err(outcome) = self.poster.write(outcome).await {
+                self.unwritten.push_front(outcome);
+                break;
+            }
+
+            tracing::info!(job_id, "wrote a held outcome");
+        }
+
+        self.pipeline.set_unwritten(self.unwritten.len());
+    }
+
+    /// Pops the next eligible job, skipping the nations in `waiting` as well as those posting.
+    #[tracing::instrument(skip_all)]
+    async fn get_dispatch(&mut self, waiting: &HashSet<String>) -> Option<IntermediateDispatch> {
+        let (limiter, reservations) = (&self.poster.limiter, &self.reservations);
+        let busy = self
+            .in_flight
+            .values()
+            .map(|(nation, _)| nation.as_str())
+            .chain(waiting.iter().map(String::as_str))
+            .collect::<HashSet<_>>();
+
+        let dispatch = self
+            .queue
+            .pop(&busy, |job_id, target| async move {
+                let wait = match reservations.get(&job_id) {
+                    Some(reservation) => limiter.peek_reserved(reservation).await,
+                    None => limiter.peek(target).await,
+                };
+
+                wait <= PERIOD
+            })
+            .await;
+
+        if dispatch.is_some() {
+            tracing::info!("eligible dispatch found");
+        }
+
+        dispatch
+    }
+
+    /// Starts posting eligible jobs until `concurrency` are in flight. Each job runs on its own
+    /// task and goes through the shared ratelimiter, which paces them however many there are.
+    #[tracing::instrument(skip_all)]
+    async fn try_post(&mut self) {
+        // nothing new is posted while earlier outcomes wait on the database
+        if !self.unwritten.is_empty() {
+            self.flush().await;
+
+            if !self.unwritten.is_empty() {
+                return;
+            }
+        }
+
+        if self.pipeline.is_paused() {
+            return self.pipeline.waiting(self.queue.len());
+        }
+
+        // nations whose next job has to wait, until the next look
+        let mut waiting = HashSet::new();
+
+        while self.tasks.len() < self.concurrency {
+            let Some(dispatch) = self.get_dispatch(&waiting).await else {
+                break;
+            };
+
+            let nation = name::canonicalize(&dispatch.nation);
+
+            if !self.start(dispatch).await {
+                waiting.insert(nation);
+            }
+        }
+
+        self.pipeline.waiting(self.queue.len());
+    }
+
+    /// Claims the job of `dispatch` and posts it on a task of its own, returning whether it did.
+    /// A job on a dispatch that an earlier job hasn't reached the worker for yet goes back to
+    /// its queue, so jobs on a dispatch are posted in the order they were queued whatever order
+    /// they arrive in.
+    async fn start(&mut self, dispatch: IntermediateDispatch) -> bool {
+        let span = tracing::info_span!(
+            "job",
+            job_id = dispatch.job_id,
+            nation = dispatch.nation,
+            request_id = dispatch.request_id.as_deref()
+        );
+
+        let job_id = dispatch.job_id;
+
+        if dispatch.dispatch_id().is_some() {
+            match self.poster.held_back(job_id).instrument(span.clone()).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    tracing::debug!(parent: &span, "waiting on an earlier job on the dispatch");
+                    self.queue.push(dispatch);
+
+                    return false;
+                }
+                Err(e) => {
+                    tracing::error!(parent: &span, "{}", e);
+                    self.queue.push(dispatch);
+
+                    return false;
+                }
+            }
+        }
+
+        // a job that can't be claimed was finished or cancelled elsewhere, or has to wait for
+        // the database
+        match self
+            .poster
+            .write_job(job_id, JobStatus::Claimed, None, None)
+            .instrument(span.clone())
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                self.cancel_reservation(job_id).await;
+
+                return false;
+            }
+            Err(e) => {
+                tracing::error!(parent: &span, "{}", e);
+
+                if is_transient(&e) {
+                    self.queue.push(dispatch);
+                } else {
+                    self.cancel_reservation(job_id).await;
+                }
+
+                return false;
+            }
+        }
+
+        let nation = dispatch.nation.clone();
+        let poster = self.poster.clone();
+        let reservation = self.reservations.remove(&job_id);
+
+        let task = self.tasks.spawn(
+            async move {
+                let result = poster.post(dispatch.clone(), reservation).await;
+
+                (dispatch, result)
+            }
+            .instrument(span),
+        );
+
+        self.pipeline.posting(job_id, &nation);
+        self.in_flight.insert(task.id(), (nation, job_id));
+
+        true
+    }
+
+    /// Drops the reservation of job `job_id`, which won't be posted.
+    async fn cancel_reservation(&mut self, job_id: i32) {
+        if let Some(reservation) = self.reservations.remove(&job_id) {
+            self.poster.limiter.cancel(reservation).await;
+        }
+    }
+
+    /// Records what a posting task did, in the order the tasks finish.
+    #[tracing::instrument(skip_all)]
+    async fn finish(&mut self, joined: Result<(task::Id, Posted), JoinError>) {
+        match joined {
+            Ok((id, (dispatch, result))) => {
+                self.in_flight.remove(&id);
+                self.pipeline
+                    .finished(dispatch.job_id, result.as_ref().err());
+
+                if let Err(error) = &result {
+                    self.poster
+                        .report_credentials(&dispatch.nation, error)
+                        .await;
+                }
+
+                // left unknown for the next pass to look for again
+                if let Err(Error::JobUnverified) = result {
+                    return;
+                }
+
+                self.record(Outcome::new(dispatch, result)).await;
+            }
+            // left for recovery to decide, as if the worker had stopped mid-post
+            Err(e) => match self.in_flight.remove(&e.id()) {
+                Some((nation, job_id)) => {
+                    tracing::error!(job_id, nation, "job stopped: {}", e);
+                    self.pipeline.finished(job_id, Some(&Error::JobInterrupted));
+                }
+                None => tracing::error!("{}", e),
+            },
+        }
+    }
+
+    /// How long job `job_id` is likely to wait before it's posted: until its limit allows it, or
+    /// its reservation comes up, and a period for every job of its nation ahead of it. `None` if
+    /// the job isn't waiting, e.g. because it's already posting.
+    async fn estimate(&self, job_id: i32) -> Option<Duration> {
+        let (ahead, dispatch) = self.queue.position(job_id)?;
+
+        let wait = match self.reservations.get(&job_id) {
+            Some(reservation) => self.poster.limiter.peek_reserved(reservation).await,
+            None => self.poster.limiter.peek(target(dispatch)).await,
+        };
+
+        Some(wait + PERIOD * ahead as u32)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn process_command(&mut self, command: Command) {
+        tracing::info!("received command");
+
+        let response = match command.operation {
+            Operation::Queue(dispatch) => {
+                self.enqueue(dispatch).await;
+                dispatch::Response::Success
+            }
+            Operation::Coalesce(dispatch) => {
+                self.coalesce(dispatch).await;
+                dispatch::Response::Success
+            }
+            Operation::Inspect { nation } => {
+                dispatch::Response::Inspect(QueueSummary::new(self.queue.iter(), &nation))
+            }
+            Operation::Estimate { job_id } => {
+                dispatch::Response::Estimate(self.estimate(job_id).await)
+            }
+            Operation::Control(control) => {
+                dispatch::Response::Pipeline(self.pipeline.apply(control, self.queue.len()))
+            }
+            Operation::Revalidate { nation } => match self.poster.revalidate(&nation).await {
+                Ok(()) => dispatch::Response::Success,
+                Err(e) => dispatch::Response::Error(e),
+            },
+            #[cfg(test)]
+            Operation::Panic => panic!("test panic"),
+        };
+
+        if command.tx.send(response).is_err() {
+            tracing::error!("failed to send response");
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn run(&mut self) {
+        let mut interval = tokio::time::interval(PERIOD);
+        let mut deferred = tokio::time::interval(DEFERRED_PERIOD);
+
+        self.recover().await;
+
+        loop {
+            // commands first, so a pause always lands before the next tick
+            tokio::select! {
+                biased;
+
+                Some(command) = self.rx.recv() => {
+                    self.process_command(command).await;
+                }
+
+                Some(joined) = self.tasks.join_next_with_id(), if !self.tasks.is_empty() => {
+                    self.finish(joined).await;
+                }
+
+                _  = interval.tick() => {
+                    self.try_post().await;
+                }
+
+                _ = deferred.tick() => {
+                    self.pick_up_deferred().await;
+                    self.pick_up_unknown().await;
+                }
+            }
+        }
+    }
+}
+
+impl Poster {
+    /// Moves the job to `status` and stamps the time it got there. A job that isn't in a status
+    /// `status` can follow is left alone, and `false` returned.
+    #[tracing::instrument(skip_all)]
+    async fn update_job(
+        &self,
+        job_id: i32,
+        status: JobStatus,
+        dispatch_id: Option<i32>,
+        error: Option<&Error>,
+    ) -> bool {
+        match self.write_job(job_id, status, dispatch_id, error).await {
+            Ok(updated) => updated,
+            Err(e) => {
+                tracing::error!("{}", e);
+
+                false
+            }
+        }
+    }
+
+    /// [`Client::update_job`], handing database errors to the caller.
+    async fn write_job(
+        &self,
+        job_id: i32,
+        status: JobStatus,
+        dispatch_id: Option<i32>,
+        error: Option<&Error>,
+    ) -> Result<bool, sqlx::Error> {
+        let code = error.and_then(Error::job_code);
+
+        let row = sqlx::query(
+            "UPDATE dispatch_queue SET
+                status = $1,
+                dispatch_id = $2,
+                error = $3,
+                error_code = $4,
+                modified_at = $5,
+                claimed_at = CASE WHEN $1 = 'claimed' THEN $5 ELSE claimed_at END,
+                started_at = CASE WHEN $1 = 'posting' THEN $5 ELSE started_at END,
+                finished_at = CASE WHEN $1 IN ('succeeded', 'failed', 'cancelled') THEN $5 ELSE finished_at END
+            WHERE id = $6 AND status = ANY($7)
+            RETURNING type, created_by, tenant_id;",
+        )
+        .bind(status)
+        .bind(dispatch_id)
+        .bind(error.map(Error::to_string))
+        .bind(code)
+        .bind(chrono::Utc::now())
+        .bind(job_id)
+        .bind(status.follows())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => {
+                tracing::info!(job_id, %status, "job status changed");
+                self.publish(job_id, status, code, dispatch_id, &row);
+
+                Ok(true)
+            }
+            None => {
+                tracing::warn!(job_id, %status, "job can't change status");
+
+                Ok(false)
+            }
+        }
+    }
+
+    /// Whether a job queued before job `job_id` on the same dispatch is still to reach the
+    /// worker, e.g. it was deferred while the channel was full. One the worker already has is
+    /// ahead of `job_id` in its queue, and one left behind for good is freed by
+    /// `queue requeue-stuck`.
+    async fn held_back(&self, job_id: i32) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            "SELECT EXISTS (
+                SELECT 1 FROM dispatch_queue AS earlier
+                JOIN dispatch_queue AS job ON job.target_dispatch_id = earlier.target_dispatch_id
+                WHERE job.id = $1
+                AND earlier.id < job.id
+                AND earlier.status IN ('queued', 'deferred')
+            ) AS held;",
+        )
+        .bind(job_id)
+        .map(|row: PgRow| row.get("held"))
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Marks job `job_id`, if it's still queued, as superseded by job `by`.
+    #[tracing::instrument(skip_all)]
+    async fn supersede_job(&self, job_id: i32, by: i32) -> bool {
+        let status = JobStatus::Superseded;
+
+        match sqlx::query(
+            "UPDATE dispatch_queue SET
+                status = $1,
+                superseded_by = $2,
+                modified_at = $3,
+                finished_at = $3
+            WHERE id = $4 AND status = ANY($5)
+            RETURNING type, created_by, tenant_id;",
+        )
+        .bind(status)
+        .bind(by)
+        .bind(chrono::Utc::now())
+        .bind(job_id)
+        .bind(status.follows())
+        .fetch_optional(&self.pool)
+        .await
+        {
+            Ok(Some(row)) => {
+                self.publish(job_id, status, None, None, &row);
+
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                tracing::error!("{}", e);
+
+                false
+            }
+        }
+    }
+
+    /// Announces the job's new status, `row` holding the job's `type`, `created_by` and
+    /// `tenant_id`.
+    fn publish(
+        &self,
+        job_id: i32,
+        status: JobStatus,
+        error_code: Option<&str>,
+        dispatch_id: Option<i32>,
+        row: &PgRow,
+    ) {
+        self.events.publish(JobEvent {
+            job: JobKind::Dispatch,
+            id: job_id,
+            status,
+            error_code: error_code.map(str::to_string),
+            self_url: DispatchStatus::self_url(job_id),
+            resource: DispatchStatus::resource_url(row.get("type"), status, dispatch_id),
+            created_by: row.get("created_by"),
+            tenant_id: row.get("tenant_id"),
+        });
+    }
+
+    /// Stamps the time the job reached `phase`.
+    #[tracing::instrument(skip_all)]
+    async fn stamp_job(&self, job_id: i32, phase: Phase) {
+        if let Err(e) = sqlx::query(&format!(
+            "UPDATE dispatch_queue SET {} = $1 WHERE id = $2;",
+            phase.column()
+        ))
+        .bind(chrono::Utc::now())
+        .bind(job_id)
+        .execute(&self.pool)
+        .await
+        {
+            tracing::error!("{}", e);
+        }
+    }
+
+    /// Writes what's left of `outcome`, its records first, so a client polling the job never
+    /// reads the dispatch as it was before. Gives the outcome back when the database is
+    /// unreachable; other errors are logged and the write skipped, as retrying won't help them.
+    #[tracing::instrument(skip_all)]
+    async fn write(&self, mut outcome: Outcome) -> Result<(), Outcome> {
+        while let Some(record) = outcome.records.front() {
+            match retry(|| self.insert_record(record, outcome.job_id)).await {
+                Err(e) if is_transient(&e) => return Err(outcome),
+                Err(e) => tracing::error!("{}", e),
+                Ok(()) => {}
+            }
+
+            outcome.records.pop_front();
+        }
+
+        match retry(|| {
+            self.write_job(
+                outcome.job_id,
+                outcome.status,
+                outcome.dispatch_id,
+                outcome.error.as_ref(),
+            )
+        })
+        .await
+        {
+            Err(e) if is_transient(&e) => Err(outcome),
+            Err(e) => {
+                tracing::error!("{}", e);
+
+                Ok(())
+            }
+            Ok(_) => Ok(()),
+        }
+    }
+
+    async fn insert_record(&self, record: &Record, job_id: i32) -> Result<(), sqlx::Error> {
+        match record {
+            Record::Header { id, nation } => self.insert_dispatch_header(*id, nation, job_id).await,
+            Record::Content {
+                id,
+                category,
+                title,
+                text,
+                size,
+                source,
+                created_by,
+            } => {
+                self.insert_dispatch_content(
+                    *id,
+                    category.clone(),
+                    title,
+                    text,
+                    *size,
+                    source,
+                    created_by,
+                    job_id,
+                )
+                .await
+            }
+            Record::Inactive { id, deleted_by } => {
+                self.set_dispatch_inactive(*id, deleted_by).await
+            }
+        }
+    }
+
+    /// Links the dispatch to the group its job was queued in, if any, and gives it to the tenant
+    /// the job was queued by.
     #[tracing::instrument(skip_all)]
-    async fn update_job(
+    async fn insert_dispatch_header(
         &self,
+        id: i32,
+        nation: &str,
         job_id: i32,
-        status: &str,
-        dispatch_id: Option<i32>,
-        error: Option<String>,
-    ) {
-        if let Err(e) = sqlx::query(
-            "UPDATE dispatch_queue SET status = $1, dispatch_id = $2, error = $3, modified_at = $4 WHERE id = $5;",
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO dispatches (dispatch_id, nation, group_id, tenant_id)
+            VALUES (
+                $1,
+                $2,
+                (SELECT group_id FROM dispatch_queue WHERE id = $3),
+                COALESCE((SELECT tenant_id FROM dispatch_queue WHERE id = $3), $4)
+            );",
         )
-            .bind(status)
-            .bind(dispatch_id)
-            .bind(error)
-            .bind(chrono::Utc::now())
-            .bind(job_id)
-            .execute(&self.pool)
-            .await
-        {
-            tracing::error!("{}", e);
-        }
-    }
+        .bind(id)
+        .bind(nation)
+        .bind(job_id)
+        .bind(DEFAULT_TENANT)
+        .execute(&self.pool)
+        .await?;
 
-    #[tracing::instrument(skip_all)]
-    async fn insert_dispatch_header(&self, id: i32, nation: &str) {
-        if let Err(e) = sqlx::query("INSERT INTO dispatches (dispatch_id, nation) VALUES ($1, $2);")
-            .bind(id)
-            .bind(nation)
-            .execute(&self.pool)
-            .await
-        {
-            tracing::error!("{}", e);
-        }
+        Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[tracing::instrument(skip_all)]
     async fn insert_dispatch_content(
         &self,
         id: i32,
-        category: i16,
-        subcategory: i16,
+        category: FactbookCategory,
         title: &str,
         text: &str,
+        size: usize,
+        source: &Source,
         created_by: &str,
-    ) {
-        if let Err(e) = sqlx::query("INSERT INTO dispatch_content (dispatch_id, category, subcategory, title, text, created_by) VALUES ((SELECT id FROM dispatches WHERE dispatch_id = $1), $2, $3, $4, $5, $6);")
+        job_id: i32,
+    ) -> Result<(), sqlx::Error> {
+        let (category, subcategory) = category.to_tuple();
+        let stored = compress::encode(text);
+
+        sqlx::query("INSERT INTO dispatch_content (dispatch_id, category, subcategory, title, text, text_compressed, created_by, source_format, source, job_id, search, size) VALUES ((SELECT id FROM dispatches WHERE dispatch_id = $1), $2, $3, $4, $5, $6, $7, $8, $9, $10, dispatch_search_vector($4, $11), $12);")
             .bind(id)
             .bind(category)
             .bind(subcategory)
             .bind(title)
-            .bind(text)
+            .bind(stored.plain())
+            .bind(stored.compressed())
             .bind(created_by)
+            .bind(source.format.as_str())
+            .bind(&source.text)
+            .bind(job_id)
+            .bind(text)
+            .bind(size as i32)
             .execute(&self.pool)
-            .await
-        {
-            tracing::error!("{}", e);
-        }
+            .await?;
+
+        Ok(())
     }
 
     #[tracing::instrument(skip_all)]
-    async fn set_dispatch_inactive(&self, id: i32) {
-        if let Err(e) = sqlx::query("UPDATE dispatches SET is_active = false WHERE id = $1;")
-            .bind(id)
-            .execute(&self.pool)
-            .await
-        {
-            tracing::error!("{}", e);
+    async fn set_dispatch_inactive(&self, id: i32, deleted_by: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE dispatches SET is_active = false, deleted_at = NOW(), deleted_by = $2
+            WHERE dispatch_id = $1;",
+        )
+        .bind(id)
+        .bind(deleted_by)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Executes the prepare request and returns the token for the execute request. A stale pin
+    /// is forgotten and the request retried once with the password alone.
+    #[tracing::instrument(skip_all)]
+    async fn prepare(&self, password: &Secret, dispatch: &Dispatch) -> Result<String, Error> {
+        match self.try_prepare(password, dispatch).await {
+            Err(Error::NationStates(NsError::PinExpired)) => {
+                tracing::info!("pin expired, retrying with password");
+                self.nations.clear_pin(&dispatch.nation).await?;
+
+                self.try_prepare(password, dispatch).await
+            }
+            result => result,
         }
     }
 
     #[tracing::instrument(skip_all)]
-    async fn post(&mut self, mut dispatch: IntermediateDispatch) -> Result<i32, Error> {
+    async fn try_prepare(&self, password: &Secret, dispatch: &Dispatch) -> Result<String, Error> {
+        tracing::debug!("getting pin");
+        let pin = self
+            .nations
+            .get_pin(&dispatch.nation)
+            .await?
+            .unwrap_or_default();
+
+        tracing::debug!("executing prepare request");
+        let resp = self
+            .client
+            .post(&self.url)
+            .header("X-Password", password.expose())
+            .header("X-Pin", &pin)
+            .body(serde_urlencoded::to_string(dispatch)?)
+            .send()
+            .await?;
+
+        self.limiter.observe(resp.headers()).await;
+
+        let resp = error::check_status(resp, !pin.is_empty())?;
+
+        if let Some(val) = resp.headers().get("X-Pin") {
+            self.nations
+                .set_pin(&dispatch.nation, val.to_str()?)
+                .await?;
+        };
+
+        reply::parse(&resp.text().await?)
+            .and_then(Reply::into_result)
+            .map_err(Error::NationStates)
+    }
+
+    /// Posts `dispatch`, acquiring its slot through `reservation` if it holds one.
+    #[tracing::instrument(skip_all)]
+    async fn post(
+        &self,
+        mut dispatch: IntermediateDispatch,
+        reservation: Option<Reservation>,
+    ) -> Result<i32, Error> {
         tracing::debug!("getting nation password");
-        let password = self.nations.get_password(&dispatch.nation).await?;
+        let password = match self.nations.get_password(&dispatch.nation).await {
+            Ok(password) => password,
+            Err(e) => {
+                if let Some(reservation) = reservation {
+                    self.limiter.cancel(reservation).await;
+                }
+
+                return Err(e);
+            }
+        };
 
         let dispatch_id = match dispatch.action {
             Action::Add { .. } => None,
@@ -130,20 +1303,26 @@ impl Client {
             Action::Remove { id } => Some(id),
         };
 
-        let acquire = match &mut dispatch.action {
-            Action::Add { text, .. } => {
-                *text = encode(text);
+        // only adding a dispatch consumes a restricted action slot
+        let restricted = match dispatch.action {
+            Action::Add { .. } => Some(Target::restricted(
+                &dispatch.nation,
+                RestrictedAction::Dispatch,
+            )),
+            Action::Edit { .. } | Action::Remove { .. } => None,
+        };
+
+        if let Action::Add { text, .. } | Action::Edit { text, .. } = &mut dispatch.action {
+            *text = encode(text);
+        }
 
+        let (slot, acquire) = match reservation {
+            Some(reservation) => self.limiter.acquire_reserved(reservation).await,
+            None => {
                 self.limiter
-                    .acquire(Target::restricted(&dispatch.nation))
+                    .acquire_slot(restricted.clone().unwrap_or(Target::Standard))
                     .await
             }
-            Action::Edit { text, .. } => {
-                *text = encode(text);
-
-                self.limiter.acquire(Target::Standard).await
-            }
-            Action::Remove { .. } => self.limiter.acquire(Target::Standard).await,
         };
 
         if let Err(duration) = acquire {
@@ -151,213 +1330,564 @@ impl Client {
             tokio::time::sleep(duration).await;
         };
 
+        let job_id = dispatch.job_id;
         let mut dispatch = Dispatch::from(dispatch);
 
-        tracing::debug!("getting pin");
-        let pin = self
-            .nations
-            .get_pin(&dispatch.nation)
-            .await?
-            .unwrap_or_default();
+        self.update_job(job_id, JobStatus::Posting, None, None)
+            .await;
 
-        tracing::debug!("executing prepare request");
-        let resp = self
-            .client
-            .post(&self.url)
-            .header("X-Password", &password)
-            .header("X-Pin", pin)
-            .body(serde_urlencoded::to_string(&dispatch)?)
-            .send()
-            .await?
-            .error_for_status()?;
+        let token = match self.prepare(&password, &dispatch).await {
+            Ok(token) => token,
+            Err(e) => {
+                // NS never performed the action, so the cooldown slot can be handed back
+                if let Some(target) = restricted {
+                    self.limiter.release(target, slot).await;
+                }
 
-        if let Some(val) = resp.headers().get("X-Pin") {
-            self.nations
-                .set_pin(&dispatch.nation, val.to_str()?)
-                .await?;
+                return Err(e);
+            }
         };
 
-        let response = de::from_str::<Response>(&resp.text().await?)?;
-
-        if !response.is_ok() {
-            return Err(Error::NationStates(response.error.unwrap()));
-        }
+        self.stamp_job(job_id, Phase::Prepared).await;
 
         dispatch.set_mode(Mode::Execute);
-        dispatch.set_token(response.success.unwrap());
+        dispatch.set_token(token);
 
         if let Err(duration) = self.limiter.acquire(Target::Standard).await {
             tracing::info!("sleeping for {}ms", duration.as_millis());
             tokio::time::sleep(duration).await;
         };
 
+        let pin = self
+            .nations
+            .get_pin(&dispatch.nation)
+            .await?
+            .unwrap_or_default();
+
+        self.stamp_job(job_id, Phase::Executing).await;
+
+        let success = match self.execute(&password, &pin, &dispatch).await {
+            Ok(success) => success,
+            // NS may have posted the dispatch all the same, and posting it again would double it
+            Err(e) if dispatch_id.is_none() && is_ambiguous(&e) => {
+                return self.verify(job_id, &dispatch, e).await;
+            }
+            Err(e) => return Err(e),
+        };
+
+        // is this a stupid way to do this? idk, maybe
+        // but also, the only instance where dispatch_id will be None is for a new dispatch
+        // in which case, the response returned from NS 100% contains the id for the new dispatch
+        // it would be so much cooler if we could always reply on the response containing the id
+        // but alas
+        match dispatch_id {
+            Some(id) => Ok(id),
+            None => match self.re.find(&success) {
+                Some(id) => Ok(id.as_str().parse()?),
+                None => Err(Error::NationStates(NsError::Unknown(format!(
+                    "No dispatch id in NS reply: {}",
+                    success
+                )))),
+            },
+        }
+    }
+
+    /// Executes the execute request and returns NS's reply.
+    #[tracing::instrument(skip_all)]
+    async fn execute(
+        &self,
+        password: &Secret,
+        pin: &str,
+        dispatch: &Dispatch,
+    ) -> Result<String, Error> {
         tracing::debug!("executing execute request");
         let resp = self
             .client
             .post(&self.url)
-            .header("X-Password", &password)
-            .header(
-                "X-Pin",
-                self.nations
-                    .get_pin(&dispatch.nation)
-                    .await?
-                    .unwrap_or_default(),
-            )
-            .body(serde_urlencoded::to_string(&dispatch)?)
+            .header("X-Password", password.expose())
+            .header("X-Pin", pin)
+            .body(serde_urlencoded::to_string(dispatch)?)
             .send()
-            .await?
-            .error_for_status()?;
+            .await?;
 
-        let response = de::from_str::<Response>(&resp.text().await?)?;
+        self.limiter.observe(resp.headers()).await;
 
-        if response.is_ok() {
-            // is this a stupid way to do this? idk, maybe
-            // but also, the only instance where dispatch_id will be None is for a new dispatch
-            // in which case, the response returned from NS 100% contains the id for the new dispatch
-            // it would be so much cooler if we could always reply on the response containing the id
-            // but alas
-            match dispatch_id {
-                Some(id) => Ok(id),
-                None => Ok(self
-                    .re
-                    .find(&response.success.unwrap())
-                    .unwrap()
-                    .as_str()
-                    .parse()?),
-            }
-        } else {
-            Err(Error::NationStates(response.error.unwrap()))
-        }
+        let resp = error::check_status(resp, !pin.is_empty())?;
+
+        reply::parse(&resp.text().await?)
+            .and_then(Reply::into_result)
+            .map_err(Error::NationStates)
     }
 
+    /// Settles a new dispatch whose execute request failed with `error` without saying whether
+    /// NS posted it. The job is marked unknown while [`Poster::look_for`] searches the nation's
+    /// dispatch list.
     #[tracing::instrument(skip_all)]
-    async fn get_dispatch(&mut self) -> Option<IntermediateDispatch> {
-        for (index, dispatch) in self.queue.iter().enumerate() {
-            if self
-                .limiter
-                .peek(Target::restricted(&dispatch.nation))
-                .await
-                <= PERIOD
-            {
-                tracing::info!("eligible dispatch found");
-                return Some(self.queue.remove(index).unwrap());
-            }
-        }
+    async fn verify(&self, job_id: i32, dispatch: &Dispatch, error: Error) -> Result<i32, Error> {
+        tracing::warn!(job_id, "execute request failed ambiguously: {}", error);
+        self.update_job(job_id, JobStatus::Unknown, None, Some(&error))
+            .await;
 
-        None
+        self.look_for(job_id, dispatch, chrono::Utc::now(), error)
+            .await
     }
 
-    #[tracing::instrument(skip_all)]
-    async fn try_post(&mut self) {
-        if let Some(dispatch) = self.get_dispatch().await {
-            let job_id = dispatch.job_id;
-            tracing::debug!("job id: {}", job_id);
+    /// Looks for the dispatch of job `job_id`, whose execute request went to NS at `sent`. The
+    /// job succeeds with the newest dispatch of the same title from [`VERIFY_WINDOW`] before then
+    /// that isn't tracked yet, and fails with `error` if there's none. If the search itself
+    /// fails, it's [`Error::JobUnverified`] and the job stays unknown to be looked for again.
+    async fn look_for(
+        &self,
+        job_id: i32,
+        dispatch: &Dispatch,
+        sent: chrono::DateTime<chrono::Utc>,
+        error: Error,
+    ) -> Result<i32, Error> {
+        let found = match ns::dispatch::find_posted(
+            &self.client,
+            &self.url,
+            &self.limiter,
+            &dispatch.nation,
+            dispatch.title.as_deref().unwrap_or_default(),
+            sent - VERIFY_WINDOW,
+        )
+        .await
+        {
+            Ok(ids) => self.first_untracked(&ids).await,
+            Err(e) => Err(e),
+        };
 
-            let (status, dispatch_id, error) = match self.post(dispatch.clone()).await {
-                Ok(id) => ("success", Some(id), None),
-                Err(e) => ("failure", None, Some(e.to_string())),
-            };
+        match found {
+            Ok(Some(id)) => {
+                tracing::info!(job_id, id, "dispatch was posted");
 
-            self.update_job(job_id, status, dispatch_id, error).await;
-
-            if let Some(id) = dispatch_id {
-                match dispatch.action {
-                    Action::Add {
-                        title,
-                        text,
-                        category,
-                    } => {
-                        self.insert_dispatch_header(id, &dispatch.nation).await;
-
-                        let (category, subcategory) = category.to_tuple();
-
-                        self.insert_dispatch_content(
-                            id,
-                            category,
-                            subcategory,
-                            &title,
-                            &text,
-                            &dispatch.user,
-                        )
-                        .await;
-                    }
-                    Action::Edit {
-                        id,
-                        title,
-                        text,
-                        category,
-                    } => {
-                        let (category, subcategory) = category.to_tuple();
-
-                        self.insert_dispatch_content(
-                            id,
-                            category,
-                            subcategory,
-                            &title,
-                            &text,
-                            &dispatch.user,
-                        )
-                        .await;
-                    }
-                    Action::Remove { id } => {
-                        self.set_dispatch_inactive(id).await;
-                    }
-                }
+                Ok(id)
+            }
+            Ok(None) => {
+                tracing::info!(job_id, "dispatch wasn't posted");
+
+                Err(error)
+            }
+            Err(e) => {
+                tracing::error!(job_id, "couldn't look for the dispatch: {}", e);
+
+                Err(Error::JobUnverified)
             }
         }
     }
 
-    #[tracing::instrument(skip_all)]
-    async fn process_command(&mut self, command: Command) {
-        tracing::info!("received command");
-        self.queue.push_back(command.dispatch);
+    /// The first of `ids` no dispatch in the database has, one already tracked being some other
+    /// job's.
+    async fn first_untracked(&self, ids: &[i32]) -> Result<Option<i32>, Error> {
+        Ok(sqlx::query(
+            "SELECT found.id FROM UNNEST($1::INTEGER[]) WITH ORDINALITY AS found (id, position)
+            WHERE NOT EXISTS (SELECT 1 FROM dispatches WHERE dispatch_id = found.id)
+            ORDER BY found.position
+            LIMIT 1;",
+        )
+        .bind(ids)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_optional(&self.pool)
+        .await?)
+    }
 
-        if command.tx.send(dispatch::Response::Success).is_err() {
-            tracing::error!("failed to send response");
+    /// A rejected password marks the nation unhealthy, which holds its jobs back from NS until
+    /// an admin revalidates it.
+    #[tracing::instrument(skip_all)]
+    async fn report_credentials(&self, nation: &str, error: &Error) {
+        if let Error::NationStates(NsError::InvalidPassword) = error
+            && let Err(e) = self.nations.mark_unhealthy(nation).await
+        {
+            tracing::error!("{}", e);
         }
     }
 
-    #[tracing::instrument(skip_all)]
-    pub(crate) async fn run(&mut self) {
-        let mut interval = tokio::time::interval(PERIOD);
+    /// Checks the nation's password with a ping and marks it healthy again if NS accepts it.
+    #[tracing::instrument(skip(self))]
+    async fn revalidate(&self, nation: &str) -> Result<(), Error> {
+        let password = self.nations.get_password_unchecked(nation).await?;
 
-        loop {
-            tokio::select! {
-                Some(command) = self.rx.recv() => {
-                    self.process_command(command).await;
-                }
+        if let Err(duration) = self.limiter.acquire(Target::Standard).await {
+            tokio::time::sleep(duration).await;
+        }
 
-                _  = interval.tick() => {
-                    self.try_post().await;
-                }
-            }
+        if let Some(pin) = ns::ping(&self.client, &self.url, nation, &password).await? {
+            self.nations.set_pin(nation, &pin).await?;
         }
-    }
-}
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "UPPERCASE")]
-struct Response {
-    success: Option<String>,
-    error: Option<String>,
+        self.nations.mark_healthy(nation).await
+    }
 }
 
-impl Response {
-    fn is_ok(&self) -> bool {
-        self.success.is_some()
+/// Whether the execute request failed without NS saying whether it went through: it timed out,
+/// or NS answered with a server error. An error NS replied with means it refused the request.
+fn is_ambiguous(error: &Error) -> bool {
+    match error {
+        Error::NationStates(NsError::Unavailable) => true,
+        Error::HTTPClient(e) => {
+            e.is_timeout() || e.status().is_some_and(|status| status.is_server_error())
+        }
+        _ => false,
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn new(
     user: &str,
     url: &str,
     pool: PgPool,
     limiter: ratelimiter::Sender,
     nations: nations::Sender,
+    events: events::Sender,
+    recovery: Recovery,
+    concurrency: usize,
+    capacity: usize,
+    activity: Activity,
 ) -> Result<(mpsc::Sender<Command>, Client), ConfigError> {
-    let (tx, rx) = mpsc::channel(16);
+    let (tx, rx) = mpsc::channel(capacity.max(1));
 
-    let client = Client::new(user, url, pool, limiter, nations, rx)?;
+    let client = Client {
+        recovery,
+        concurrency: concurrency.max(1),
+        pipeline: Pipeline::reporting("dispatch", activity),
+        ..Client::new(user, url, pool, limiter, nations, events, rx)?
+    };
 
     Ok((tx, client))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ns::dispatch::{EditDispatch, NewDispatch};
+    use crate::workers::Control;
+    use sqlx::postgres::PgPoolOptions;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn add(job_id: i32, nation: &str) -> IntermediateDispatch {
+        let params = NewDispatch {
+            nation: nation.to_string(),
+            title: "Title".to_string(),
+            text: "text".to_string(),
+            category: Some(1),
+            subcategory: Some(100),
+            format: Default::default(),
+        };
+
+        IntermediateDispatch::add(job_id, "user".to_string(), params, "text".to_string()).unwrap()
+    }
+
+    fn delete(job_id: i32, nation: &str) -> IntermediateDispatch {
+        IntermediateDispatch::delete(job_id, "user".to_string(), 100, nation.to_string())
+    }
+
+    /// Hands out slots on a clock advanced by the test, one tick per worker period.
+    struct ScriptedLimiter {
+        now: u32,
+        restricted_cooldown: u32,
+        /// tick each bucket is free again
+        free_at: HashMap<String, u32>,
+    }
+
+    impl ScriptedLimiter {
+        fn new(restricted_cooldown: u32) -> Self {
+            Self {
+                now: 0,
+                restricted_cooldown,
+                free_at: HashMap::new(),
+            }
+        }
+
+        fn ready(&self, target: &Target) -> bool {
+            self.free_at
+                .get(&format!("{target:?}"))
+                .is_none_or(|free_at| *free_at <= self.now)
+        }
+
+        fn acquire(&mut self, target: &Target) {
+            let cooldown = match target {
+                Target::Restricted { .. } => self.restricted_cooldown,
+                _ => 1,
+            };
+
+            self.free_at
+                .insert(format!("{target:?}"), self.now + cooldown);
+        }
+    }
+
+    /// Runs the worker's selection for `ticks` periods, returning the job posted at each.
+    async fn schedule(
+        queue: &mut NationQueues,
+        limiter: &mut ScriptedLimiter,
+        ticks: u32,
+    ) -> Vec<Option<i32>> {
+        let mut posted = Vec::new();
+
+        for _ in 0..ticks {
+            let dispatch = queue
+                .pop(&HashSet::new(), |_, target| {
+                    std::future::ready(limiter.ready(&target))
+                })
+                .await;
+
+            if let Some(dispatch) = &dispatch {
+                limiter.acquire(&target(dispatch));
+                limiter.acquire(&Target::Standard);
+            }
+
+            posted.push(dispatch.map(|dispatch| dispatch.job_id));
+            limiter.now += 1;
+        }
+
+        posted
+    }
+
+    #[tokio::test]
+    async fn test_nations_take_turns() {
+        let mut queue = NationQueues::default();
+
+        for job_id in 1..=3 {
+            queue.push(add(job_id, "nation_a"));
+        }
+        queue.push(add(4, "Nation B"));
+        queue.push(add(5, "nation_b"));
+
+        assert_eq!(queue.len(), 5);
+
+        // restricted limits are always satisfied, draining a's backlog first would be allowed
+        let mut limiter = ScriptedLimiter::new(0);
+
+        assert_eq!(
+            schedule(&mut queue, &mut limiter, 5).await,
+            vec![Some(1), Some(4), Some(2), Some(5), Some(3)]
+        );
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_nation_on_cooldown_keeps_its_turn() {
+        let mut queue = NationQueues::default();
+
+        queue.push(add(1, "nation_a"));
+        queue.push(add(2, "nation_a"));
+        queue.push(delete(3, "nation_a"));
+        queue.push(add(4, "nation_b"));
+        queue.push(delete(5, "nation_b"));
+
+        let mut limiter = ScriptedLimiter::new(4);
+
+        assert_eq!(
+            schedule(&mut queue, &mut limiter, 8).await,
+            vec![
+                Some(1),
+                Some(4),
+                // a's add waits out its restricted cooldown, b's delete only needs the standard
+                // bucket and so goes ahead of it
+                Some(5),
+                None,
+                // a kept its place at the front while waiting, its delete stays behind its add
+                Some(2),
+                Some(3),
+                None,
+                None,
+            ]
+        );
+    }
+
+    /// A job that reached the worker after a later one on the same dispatch is still posted first,
+    /// while jobs on other dispatches keep their places.
+    #[tokio::test]
+    async fn test_jobs_on_a_dispatch_keep_job_order() {
+        let mut queue = NationQueues::default();
+        let mut limiter = ScriptedLimiter::new(0);
+
+        let remove = |job_id: i32, id: i32| {
+            let mut dispatch = delete(job_id, "nation_a");
+            dispatch.action = Action::Remove { id };
+            dispatch
+        };
+
+        queue.push(remove(1, 100));
+        queue.push(remove(3, 100));
+        queue.push(remove(4, 200));
+        queue.push(remove(2, 100));
+        queue.push(remove(5, 200));
+
+        assert_eq!(
+            queue
+                .for_dispatch(100)
+                .map(|d| d.job_id)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            schedule(&mut queue, &mut limiter, 5).await,
+            vec![Some(1), Some(2), Some(3), Some(4), Some(5)]
+        );
+    }
+
+    /// An edit only takes the place of its user's edit when that edit is the last job waiting on
+    /// the dispatch.
+    #[test]
+    fn test_coalescable_edits() {
+        let mut queue = NationQueues::default();
+
+        let edit = |job_id: i32, user: &str, id: i32| {
+            let params = EditDispatch {
+                title: "Title".to_string(),
+                text: "text".to_string(),
+                category: 1,
+                subcategory: 100,
+                format: Default::default(),
+                base_revision: None,
+            };
+
+            IntermediateDispatch::edit(
+                job_id,
+                user.to_string(),
+                id,
+                "nation_a".to_string(),
+                params,
+                "text".to_string(),
+            )
+            .unwrap()
+        };
+
+        queue.push(edit(1, "alice", 100));
+        queue.push(edit(2, "alice", 200));
+        queue.push(edit(3, "bob", 200));
+
+        assert_eq!(queue.coalescable(&edit(4, "alice", 100)), Some(1));
+        // bob's edit would land after the replacement
+        assert_eq!(queue.coalescable(&edit(4, "alice", 200)), None);
+        assert_eq!(queue.coalescable(&edit(4, "bob", 200)), Some(3));
+        assert_eq!(queue.coalescable(&edit(4, "alice", 300)), None);
+        assert_eq!(queue.coalescable(&delete(4, "nation_a")), None);
+
+        queue.replace(1, edit(4, "alice", 100));
+
+        assert_eq!(
+            queue.iter().map(|d| d.job_id).collect::<Vec<_>>(),
+            vec![4, 2, 3]
+        );
+    }
+
+    /// A job's position counts only the jobs of its own nation ahead of it.
+    #[test]
+    fn test_position_within_nation() {
+        let mut queue = NationQueues::default();
+
+        queue.push(delete(1, "nation_a"));
+        queue.push(delete(2, "nation_b"));
+        queue.push(delete(3, "nation_a"));
+
+        let position = |job_id: i32| {
+            queue
+                .position(job_id)
+                .map(|(ahead, dispatch)| (ahead, dispatch.job_id))
+        };
+
+        assert_eq!(position(1), Some((0, 1)));
+        assert_eq!(position(2), Some((0, 2)));
+        assert_eq!(position(3), Some((1, 3)));
+        assert_eq!(position(4), None);
+    }
+
+    /// New jobs keep arriving while the worker drains a queue of several hundred; each must be
+    /// posted exactly once, in the order it was queued for its nation.
+    #[tokio::test]
+    async fn test_interleaved_pushes_lose_nothing() {
+        let nations = ["nation_a", "nation_b", "nation_c", "nation_d", "nation_e"];
+        let mut queue = NationQueues::default();
+        let mut limiter = ScriptedLimiter::new(3);
+        let mut posted = Vec::new();
+        let mut next_id = 1;
+
+        while next_id <= 600 || queue.len() > 0 {
+            // a burst of commands between two ticks
+            for _ in 0..3 {
+                if next_id <= 600 {
+                    let nation = nations[next_id as usize % nations.len()];
+
+                    queue.push(match next_id % 4 {
+                        0 => delete(next_id, nation),
+                        _ => add(next_id, nation),
+                    });
+
+                    next_id += 1;
+                }
+            }
+
+            posted.extend(
+                schedule(&mut queue, &mut limiter, 1)
+                    .await
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+
+        let mut sorted = posted.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted, (1..=600).collect::<Vec<_>>());
+
+        for (index, nation) in nations.iter().enumerate() {
+            let jobs = posted
+                .iter()
+                .filter(|job_id| **job_id as usize % nations.len() == index)
+                .collect::<Vec<_>>();
+
+            assert!(jobs.is_sorted(), "{nation}'s jobs were reordered");
+        }
+    }
+
+    /// The outcome of a job NS already performed is kept while the database is down, and holds
+    /// back new jobs until it's written.
+    #[tokio::test]
+    async fn test_outcome_held_while_database_unavailable() {
+        // there's no database here, every write fails as if it were restarting
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy("postgresql://localhost/eurocore")
+            .unwrap();
+        let limiter = ratelimiter::new(
+            50,
+            Duration::from_secs(30),
+            Duration::from_secs(30),
+            Duration::from_secs(180),
+            Duration::from_secs(60),
+            Default::default(),
+            0.8,
+        );
+        let (_, rx) = mpsc::channel(1);
+
+        let mut client = Client::new(
+            "testlandia",
+            "http://localhost",
+            pool,
+            limiter,
+            nations::new("testlandia:password").unwrap(),
+            events::Sender::new(),
+            rx,
+        )
+        .unwrap();
+
+        client
+            .record(Outcome::new(delete(1, "testlandia"), Ok(100)))
+            .await;
+
+        assert_eq!(client.unwritten.len(), 1);
+        assert!(matches!(
+            client.unwritten[0].records.front(),
+            Some(Record::Inactive { id: 100, .. })
+        ));
+        assert_eq!(client.pipeline.apply(Control::Status, 0).unwritten, 1);
+
+        client.queue.push(delete(2, "testlandia"));
+        client.try_post().await;
+
+        assert_eq!(client.queue.len(), 1);
+        assert_eq!(client.unwritten.len(), 1);
+    }
+}
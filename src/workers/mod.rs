@@ -1,7 +1,751 @@
+use crate::core::error::{ConfigError, Error};
+use crate::types::job::JobStatus;
+use crate::types::response::{CurrentJob, PipelineStatus, WorkerError, WorkerState, WorkerStatus};
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::any::Any;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::Duration;
+use tokio::sync::mpsc::error::SendTimeoutError;
+use tokio::sync::{mpsc, watch};
 
 pub(crate) mod dispatch;
 pub(crate) mod rmbpost;
 pub(crate) mod telegram;
 
-const PERIOD: Duration = Duration::from_millis(250);
+pub(crate) const PERIOD: Duration = Duration::from_millis(250);
+
+/// Delay before a crashed worker is recreated, so a worker that panics on startup doesn't spin.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// How long a command waits for a replacement worker before giving up.
+const RESTART_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a worker looks for jobs that were deferred because its channel was full. Also the
+/// `Retry-After` of a request refused for the same reason.
+pub(crate) const DEFERRED_PERIOD: Duration = Duration::from_secs(2);
+
+/// The pipelines whose jobs [`requeue_stuck`] looks through, with their queue's table.
+pub(crate) const QUEUES: [(&str, &str); 2] =
+    [("dispatch", "dispatch_queue"), ("rmbpost", "rmbpost_queue")];
+
+/// Defers the jobs of `queue` left `queued` since before `cutoff`, so a running worker picks
+/// them up within [`DEFERRED_PERIOD`], otherwise the next one to start. One still waiting in a
+/// worker's channel is only claimed once, so handing it over again is harmless. Claimed and
+/// posting jobs are left alone, NS may already have seen them.
+pub(crate) async fn requeue_stuck(
+    pool: &PgPool,
+    queue: &'static str,
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<i32>, Error> {
+    Ok(sqlx::query(&format!(
+        "UPDATE {queue} SET status = 'deferred', modified_at = $2
+        WHERE status = 'queued' AND COALESCE(modified_at, created_at) < $1
+        RETURNING id;"
+    ))
+    .bind(cutoff)
+    .bind(Utc::now())
+    .map(|row: PgRow| row.get("id"))
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Tries at a write the database couldn't take before [`retry`] gives up on it.
+const WRITE_ATTEMPTS: u32 = 3;
+
+/// Wait after the first failed try at a write, doubled after each one after it.
+const WRITE_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Operator controls understood by every worker.
+#[derive(Debug)]
+pub(crate) enum Control {
+    Pause,
+    Resume,
+    Status,
+}
+
+/// What a worker is doing, shared with its [`Handle`] so it can be read while the worker is busy
+/// posting and can't answer commands. The worker updates it as it goes through its loop; it
+/// resets when the worker is restarted.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Activity(Arc<Mutex<ActivityState>>);
+
+#[derive(Debug, Default)]
+struct ActivityState {
+    paused: bool,
+    /// jobs being posted, the longest running first
+    posting: Vec<CurrentJob>,
+    /// jobs are waiting, but none may be posted yet
+    waiting: bool,
+    queue_depth: usize,
+    last_success_at: Option<DateTime<Utc>>,
+    last_error: Option<WorkerError>,
+}
+
+impl Activity {
+    fn update<T>(&self, f: impl FnOnce(&mut ActivityState) -> T) -> T {
+        f(&mut self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.update(|activity| activity.paused)
+    }
+
+    pub(crate) fn status(&self) -> WorkerStatus {
+        self.update(|activity| WorkerStatus {
+            state: if !activity.posting.is_empty() {
+                WorkerState::Posting
+            } else if activity.paused {
+                WorkerState::Paused
+            } else if activity.waiting {
+                WorkerState::SleepingRatelimit
+            } else {
+                WorkerState::Idle
+            },
+            current_job: activity.posting.first().cloned(),
+            queue_depth: activity.queue_depth,
+            last_success_at: activity.last_success_at,
+            last_error: activity.last_error.clone(),
+        })
+    }
+}
+
+/// Pause flag and last successful send of a worker. Lives in the worker, so it resets when the
+/// worker is restarted.
+#[derive(Debug)]
+pub(crate) struct Pipeline {
+    name: &'static str,
+    paused: bool,
+    /// finished jobs whose outcome is waiting for the database to come back
+    unwritten: usize,
+    activity: Activity,
+}
+
+impl Pipeline {
+    pub(crate) fn new(name: &'static str) -> Self {
+        Self::reporting(name, Activity::default())
+    }
+
+    /// A pipeline keeping `activity` up to date, starting over from what an earlier worker left.
+    pub(crate) fn reporting(name: &'static str, activity: Activity) -> Self {
+        activity.update(|activity| *activity = ActivityState::default());
+
+        Self {
+            name,
+            paused: false,
+            unwritten: 0,
+            activity,
+        }
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Job `job_id` of `nation` is being posted.
+    pub(crate) fn posting(&self, job_id: i32, nation: &str) {
+        self.activity.update(|activity| {
+            activity.posting.push(CurrentJob {
+                id: job_id,
+                nation: nation.to_string(),
+            })
+        });
+    }
+
+    /// Job `job_id` was posted, or failed with `error`.
+    pub(crate) fn finished(&self, job_id: i32, error: Option<&Error>) {
+        self.activity.update(|activity| {
+            activity.posting.retain(|job| job.id != job_id);
+
+            match error {
+                None => activity.last_success_at = Some(Utc::now()),
+                Some(e) => {
+                    activity.last_error = Some(WorkerError {
+                        at: Utc::now(),
+                        message: e.to_string(),
+                    })
+                }
+            }
+        });
+    }
+
+    /// `queue_depth` jobs are left waiting after the worker posted what it could.
+    pub(crate) fn waiting(&self, queue_depth: usize) {
+        self.activity.update(|activity| {
+            activity.queue_depth = queue_depth;
+            activity.waiting = queue_depth > 0;
+        });
+    }
+
+    pub(crate) fn set_unwritten(&mut self, unwritten: usize) {
+        self.unwritten = unwritten;
+    }
+
+    /// Applies `control` and reports the resulting state.
+    pub(crate) fn apply(&mut self, control: Control, queue_depth: usize) -> PipelineStatus {
+        match control {
+            Control::Pause => self.paused = true,
+            Control::Resume => self.paused = false,
+            Control::Status => {}
+        }
+
+        let paused = self.paused;
+
+        PipelineStatus {
+            name: self.name.to_string(),
+            paused,
+            queue_depth,
+            last_success_at: self.activity.update(|activity| {
+                activity.paused = paused;
+                activity.queue_depth = queue_depth;
+                activity.last_success_at
+            }),
+            unwritten: self.unwritten,
+        }
+    }
+}
+
+/// Steps of a job between `started_at` and `finished_at` that have no status of their own. The
+/// worker stamps them as it passes, for the timing breakdown in the job's status.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Phase {
+    /// NS answered the prepare request
+    Prepared,
+    /// the execute request went to NS
+    Executing,
+}
+
+impl Phase {
+    pub(crate) fn column(self) -> &'static str {
+        match self {
+            Phase::Prepared => "prepared_at",
+            Phase::Executing => "executing_at",
+        }
+    }
+}
+
+/// How a starting worker treats the jobs an earlier instance accepted but didn't finish, e.g.
+/// because the process restarted.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Recovery {
+    /// queued jobs older than this are cancelled rather than posted late, `None` posts them all
+    max_age: Option<chrono::Duration>,
+}
+
+/// What becomes of an unfinished job found on startup.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Unfinished {
+    /// never reached NS, queued again
+    Requeue,
+    /// never reached NS, but too old to still be wanted
+    Abandon,
+    /// may or may not have reached NS, so it's failed rather than risk posting it twice
+    Interrupted,
+    /// NS's reply was lost, so NS is asked again whether it posted the job
+    Verify,
+}
+
+impl Recovery {
+    /// `max_age_hours` of 0 picks up queued jobs however old they are.
+    pub(crate) fn new(max_age_hours: u32) -> Self {
+        Self {
+            max_age: (max_age_hours > 0).then(|| chrono::Duration::hours(max_age_hours.into())),
+        }
+    }
+
+    pub(crate) fn decide(
+        &self,
+        status: JobStatus,
+        created_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Unfinished {
+        match status {
+            JobStatus::Claimed | JobStatus::Posting => Unfinished::Interrupted,
+            JobStatus::Unknown => Unfinished::Verify,
+            _ if self
+                .max_age
+                .is_some_and(|max_age| now - created_at > max_age) =>
+            {
+                Unfinished::Abandon
+            }
+            _ => Unfinished::Requeue,
+        }
+    }
+}
+
+/// Whether `error` is the database being unreachable, e.g. restarting, rather than the query
+/// failing. The same write may succeed once it's back.
+pub(crate) fn is_transient(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut => true,
+        // connection exceptions, and the server shutting down or still starting up
+        sqlx::Error::Database(e) => e
+            .code()
+            .is_some_and(|code| code.starts_with("08") || code.starts_with("57P")),
+        _ => false,
+    }
+}
+
+/// Runs `write` until it succeeds, fails for a reason other than the database being unreachable,
+/// or has been tried [`WRITE_ATTEMPTS`] times, backing off between tries.
+pub(crate) async fn retry<T, F, Fut>(mut write: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut backoff = WRITE_BACKOFF;
+
+    for _ in 1..WRITE_ATTEMPTS {
+        match write().await {
+            Err(e) if is_transient(&e) => {
+                tracing::warn!(?backoff, "database unavailable, retrying: {}", e);
+
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+
+    write().await
+}
+
+/// A worker's command channel.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Channel {
+    /// commands buffered before senders wait for the worker
+    pub(crate) capacity: usize,
+    /// how long a sender waits for room, see [`Handle::send`]
+    pub(crate) send_timeout: Duration,
+}
+
+/// Sender half of a supervised worker. Always points at the currently running instance, so
+/// callers don't notice when the worker is replaced after a panic.
+#[derive(Debug)]
+pub(crate) struct Handle<C> {
+    name: &'static str,
+    /// how long a command waits for room in the channel before the worker counts as backed up
+    send_timeout: Duration,
+    rx: watch::Receiver<mpsc::Sender<C>>,
+    activity: Activity,
+}
+
+impl<C> Clone for Handle<C> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            send_timeout: self.send_timeout,
+            rx: self.rx.clone(),
+            activity: self.activity.clone(),
+        }
+    }
+}
+
+impl<C> Handle<C> {
+    /// Sends `command` to the worker. If the worker died, waits for the supervisor to start its
+    /// replacement and retries once. Fails with [`Error::PipelineBackedUp`], naming no jobs, when
+    /// the channel stays full for the send timeout, rather than holding up the request until the
+    /// worker gets to it.
+    pub(crate) async fn send(&self, command: C) -> Result<(), Error> {
+        let mut rx = self.rx.clone();
+
+        let tx = rx.borrow_and_update().clone();
+
+        let command = match tx.send_timeout(command, self.send_timeout).await {
+            Ok(()) => return Ok(()),
+            Err(SendTimeoutError::Timeout(_)) => return Err(self.backed_up()),
+            Err(SendTimeoutError::Closed(command)) => command,
+        };
+
+        let result = match tokio::time::timeout(RESTART_TIMEOUT, rx.changed()).await {
+            Ok(Ok(())) => {
+                let tx = rx.borrow().clone();
+                tx.send_timeout(command, self.send_timeout).await
+            }
+            _ => Err(SendTimeoutError::Closed(command)),
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(SendTimeoutError::Timeout(_)) => Err(self.backed_up()),
+            Err(SendTimeoutError::Closed(_)) => {
+                tracing::error!(worker = self.name, "unable to send command to worker");
+
+                Err(Error::Internal)
+            }
+        }
+    }
+
+    fn backed_up(&self) -> Error {
+        tracing::warn!(worker = self.name, "worker channel is full");
+
+        Error::PipelineBackedUp(self.name, Vec::new())
+    }
+
+    /// Commands waiting in the channel for the worker, and how many it holds.
+    pub(crate) fn occupancy(&self) -> (usize, usize) {
+        let tx = self.rx.borrow();
+
+        (tx.max_capacity() - tx.capacity(), tx.max_capacity())
+    }
+
+    /// What the running worker is doing, without waiting for it.
+    pub(crate) fn status(&self) -> WorkerStatus {
+        self.activity.status()
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.activity.is_paused()
+    }
+}
+
+/// Starts the worker built by `spawn` and restarts it, by calling `spawn` again, whenever its
+/// task panics. Commands wait up to `send_timeout` for room in its channel. Each worker reports
+/// what it's doing to the [`Activity`] `spawn` is given.
+pub(crate) fn supervise<C, F, W>(
+    name: &'static str,
+    send_timeout: Duration,
+    spawn: F,
+) -> Result<Handle<C>, ConfigError>
+where
+    C: Send + 'static,
+    F: Fn(Activity) -> Result<(mpsc::Sender<C>, W), ConfigError> + Send + 'static,
+    W: Future<Output = ()> + Send + 'static,
+{
+    let activity = Activity::default();
+
+    let (tx, worker) = spawn(activity.clone())?;
+
+    let (senders, rx) = watch::channel(tx);
+
+    tracing::info!(worker = name, "starting worker");
+
+    tokio::spawn({
+        let activity = activity.clone();
+
+        async move {
+            let mut handle = tokio::spawn(worker);
+
+            loop {
+                match (&mut handle).await {
+                    Ok(()) => {
+                        tracing::warn!(worker = name, "worker exited");
+                        return;
+                    }
+                    Err(e) if e.is_panic() => {
+                        tracing::error!(
+                            worker = name,
+                            "worker panicked, restarting: {}",
+                            panic_message(&*e.into_panic())
+                        );
+                    }
+                    Err(e) => {
+                        tracing::error!(worker = name, "worker was cancelled: {}", e);
+                        return;
+                    }
+                }
+
+                tokio::time::sleep(RESTART_DELAY).await;
+
+                match spawn(activity.clone()) {
+                    Ok((tx, worker)) => {
+                        senders.send_replace(tx);
+                        handle = tokio::spawn(worker);
+
+                        tracing::info!(worker = name, "restarted worker");
+                    }
+                    Err(e) => {
+                        tracing::error!(worker = name, "unable to restart worker: {}", e);
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(Handle {
+        name,
+        send_timeout,
+        rx,
+        activity,
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ns::dispatch::{Command, IntermediateDispatch, Operation, Response};
+    use crate::sync::{events, nations, ratelimiter};
+    use sqlx::postgres::PgPoolOptions;
+    use tokio::sync::oneshot;
+
+    async fn inspect(handle: &Handle<Command>) -> Result<Response, oneshot::error::RecvError> {
+        let (tx, rx) = oneshot::channel();
+
+        handle
+            .send(Command::new(
+                Operation::Inspect {
+                    nation: "testlandia".to_string(),
+                },
+                tx,
+            ))
+            .await
+            .unwrap();
+
+        rx.await
+    }
+
+    async fn control(handle: &Handle<Command>, control: Control) -> PipelineStatus {
+        let (tx, rx) = oneshot::channel();
+
+        handle
+            .send(Command::new(Operation::Control(control), tx))
+            .await
+            .unwrap();
+
+        match rx.await {
+            Ok(Response::Pipeline(status)) => status,
+            other => panic!("expected pipeline status, got {:?}", other),
+        }
+    }
+
+    fn dispatch_worker() -> Handle<Command> {
+        // there's no database, the worker's startup recovery should give up on it quickly
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy("postgresql://localhost/eurocore")
+            .unwrap();
+        let limiter = ratelimiter::new(
+            50,
+            Duration::from_secs(30),
+            Duration::from_secs(30),
+            Duration::from_secs(180),
+            Duration::from_secs(60),
+            Default::default(),
+            0.8,
+        );
+        let nations = nations::new("testlandia:password").unwrap();
+
+        supervise("dispatch", Duration::from_secs(5), move |activity| {
+            let (tx, mut client) = dispatch::new(
+                "testlandia",
+                "http://localhost",
+                pool.clone(),
+                limiter.clone(),
+                nations.clone(),
+                events::Sender::new(),
+                Recovery::default(),
+                1,
+                16,
+                activity,
+            )?;
+
+            Ok((tx, async move { client.run().await }))
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_recovery() {
+        let now = Utc::now();
+        let old = now - chrono::Duration::hours(25);
+
+        let recovery = Recovery::new(24);
+        assert_eq!(
+            recovery.decide(JobStatus::Queued, now, now),
+            Unfinished::Requeue
+        );
+        assert_eq!(
+            recovery.decide(JobStatus::Queued, old, now),
+            Unfinished::Abandon
+        );
+        assert_eq!(
+            recovery.decide(JobStatus::Claimed, now, now),
+            Unfinished::Interrupted
+        );
+        assert_eq!(
+            recovery.decide(JobStatus::Posting, old, now),
+            Unfinished::Interrupted
+        );
+        assert_eq!(
+            recovery.decide(JobStatus::Unknown, old, now),
+            Unfinished::Verify
+        );
+
+        assert_eq!(
+            Recovery::new(0).decide(JobStatus::Queued, old, now),
+            Unfinished::Requeue
+        );
+    }
+
+    #[test]
+    fn test_activity() {
+        let activity = Activity::default();
+        let mut pipeline = Pipeline::reporting("dispatch", activity.clone());
+
+        assert_eq!(activity.status().state, WorkerState::Idle);
+
+        pipeline.waiting(2);
+        assert_eq!(activity.status().state, WorkerState::SleepingRatelimit);
+        assert_eq!(activity.status().queue_depth, 2);
+
+        pipeline.posting(1, "testlandia");
+        pipeline.posting(2, "other");
+        let status = activity.status();
+        assert_eq!(status.state, WorkerState::Posting);
+        assert_eq!(
+            status.current_job,
+            Some(CurrentJob {
+                id: 1,
+                nation: "testlandia".to_string()
+            })
+        );
+
+        pipeline.finished(1, None);
+        pipeline.finished(2, Some(&Error::JobInterrupted));
+        pipeline.waiting(0);
+        let status = activity.status();
+        assert_eq!(status.state, WorkerState::Idle);
+        assert!(status.current_job.is_none());
+        assert!(status.last_success_at.is_some());
+        assert_eq!(
+            status.last_error.map(|error| error.message),
+            Some(Error::JobInterrupted.to_string())
+        );
+
+        pipeline.apply(Control::Pause, 3);
+        assert_eq!(activity.status().state, WorkerState::Paused);
+
+        // a restarted worker starts over
+        Pipeline::reporting("dispatch", activity.clone());
+        let status = activity.status();
+        assert_eq!(status.state, WorkerState::Idle);
+        assert!(status.last_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let tries = std::sync::atomic::AtomicU32::new(0);
+
+        let result = retry(|| async {
+            match tries.fetch_add(1, std::sync::atomic::Ordering::Relaxed) {
+                0 => Err(sqlx::Error::PoolTimedOut),
+                _ => Ok(1),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(tries.swap(0, std::sync::atomic::Ordering::Relaxed), 2);
+
+        let result: Result<(), _> = retry(|| async {
+            tries.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Err(sqlx::Error::PoolTimedOut)
+        })
+        .await;
+        assert!(matches!(result, Err(sqlx::Error::PoolTimedOut)));
+        assert_eq!(
+            tries.swap(0, std::sync::atomic::Ordering::Relaxed),
+            WRITE_ATTEMPTS
+        );
+
+        // a query that's wrong stays wrong
+        let result: Result<(), _> = retry(|| async {
+            tries.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Err(sqlx::Error::RowNotFound)
+        })
+        .await;
+        assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+        assert_eq!(tries.load(std::sync::atomic::Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_restart_after_panic() {
+        let handle = dispatch_worker();
+
+        assert!(matches!(inspect(&handle).await, Ok(Response::Inspect(_))));
+
+        let (tx, rx) = oneshot::channel();
+        handle
+            .send(Command::new(Operation::Panic, tx))
+            .await
+            .unwrap();
+        assert!(rx.await.is_err());
+
+        // wait for the replacement, a command sent while the old worker unwinds may be lost
+        handle.rx.clone().changed().await.unwrap();
+
+        assert!(matches!(inspect(&handle).await, Ok(Response::Inspect(_))));
+    }
+
+    #[tokio::test]
+    async fn test_full_channel() {
+        let handle = supervise("stalled", Duration::from_millis(50), |_| {
+            let (tx, rx) = mpsc::channel(1);
+
+            // holds on to its end of the channel without ever reading from it
+            Ok((tx, async move {
+                let _rx = rx;
+                std::future::pending::<()>().await
+            }))
+        })
+        .unwrap();
+
+        handle.send(1).await.unwrap();
+        assert_eq!(handle.occupancy(), (1, 1));
+
+        match handle.send(2).await {
+            Err(Error::PipelineBackedUp(pipeline, jobs)) => {
+                assert_eq!(pipeline, "stalled");
+                assert!(jobs.is_empty());
+            }
+            other => panic!("expected the worker to be backed up, got {other:?}"),
+        }
+
+        assert_eq!(handle.occupancy(), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_paused_worker_keeps_queue() {
+        let handle = dispatch_worker();
+
+        assert!(!control(&handle, Control::Status).await.paused);
+        assert!(control(&handle, Control::Pause).await.paused);
+
+        let (tx, rx) = oneshot::channel();
+        handle
+            .send(Command::new(
+                Operation::Queue(IntermediateDispatch::delete(
+                    1,
+                    "user".to_string(),
+                    1,
+                    "testlandia".to_string(),
+                )),
+                tx,
+            ))
+            .await
+            .unwrap();
+        rx.await.unwrap();
+
+        tokio::time::sleep(PERIOD * 3).await;
+
+        let status = control(&handle, Control::Status).await;
+        assert_eq!(status.name, "dispatch");
+        assert!(status.paused);
+        assert_eq!(status.queue_depth, 1);
+        assert!(status.last_success_at.is_none());
+
+        assert!(!control(&handle, Control::Resume).await.paused);
+    }
+}
@@ -1,18 +1,26 @@
-use super::PERIOD;
+use super::{Activity, DEFERRED_PERIOD, PERIOD, Phase, Pipeline, Recovery, Unfinished};
 use crate::core::error::{ConfigError, Error};
-use crate::ns::rmbpost::{self, Action, Command, IntermediateRmbPost};
+use crate::ns;
+use crate::ns::error::{self, NsError};
+use crate::ns::reply::{self, Reply};
+use crate::ns::rmbpost::{self, Action, Command, IntermediateRmbPost, RmbPost};
+use crate::ns::types::Unprepared;
+use crate::sync::events;
 use crate::sync::nations;
 use crate::sync::ratelimiter;
-use crate::types::response::RmbPostStatus;
+use crate::types::Secret;
+use crate::types::job::JobStatus;
+use crate::types::request::JobKind;
+use crate::types::response::{JobEvent, RmbPostStatus};
 use crate::utils::encode::encode;
-use quick_xml::de;
+use crate::utils::name;
 use regex::Regex;
-use serde::Deserialize;
-use sqlx::PgPool;
-use sqlx::Row;
+use reqwest::StatusCode;
 use sqlx::postgres::PgRow;
-use std::collections::VecDeque;
+use sqlx::{PgPool, Row};
+use std::collections::{HashSet, VecDeque};
 use tokio::sync::mpsc;
+use tracing::Instrument;
 
 #[derive(Debug)]
 pub(crate) struct Client {
@@ -22,8 +30,11 @@ pub(crate) struct Client {
     pool: PgPool,
     limiter: ratelimiter::Sender,
     nations: nations::Sender,
+    events: events::Sender,
     re: Regex,
     rx: mpsc::Receiver<Command>,
+    pipeline: Pipeline,
+    recovery: Recovery,
 }
 
 impl Client {
@@ -33,6 +44,7 @@ impl Client {
         pool: PgPool,
         limiter: ratelimiter::Sender,
         nations: nations::Sender,
+        events: events::Sender,
         rx: mpsc::Receiver<Command>,
     ) -> Result<Self, ConfigError> {
         let client = reqwest::Client::builder().user_agent(user_agent).build()?;
@@ -44,125 +56,327 @@ impl Client {
             pool,
             limiter,
             nations,
+            events,
             re: Regex::new(r#"=(\d+)#"#)?,
             rx,
+            pipeline: Pipeline::new("rmbpost"),
+            recovery: Recovery::default(),
         })
     }
 
+    /// Picks up the jobs an earlier worker accepted but didn't finish, so a restart doesn't
+    /// leave them pending forever. Those deferred while its channel was full are picked up with
+    /// them.
     #[tracing::instrument(skip_all)]
-    async fn try_post(&mut self) {
-        if let Some(post) = self.get_post().await {
+    async fn recover(&mut self) {
+        self.undefer().await;
+        self.restore(None).await;
+    }
+
+    /// Takes over the jobs deferred since the last look, which were stored but never sent to
+    /// the worker.
+    #[tracing::instrument(skip_all)]
+    async fn pick_up_deferred(&mut self) {
+        let job_ids = self.undefer().await;
+
+        if !job_ids.is_empty() {
+            tracing::info!(count = job_ids.len(), "picking up deferred jobs");
+            self.restore(Some(&job_ids)).await;
+        }
+    }
+
+    /// Moves every deferred job back to `queued`, returning their ids.
+    async fn undefer(&self) -> Vec<i32> {
+        match sqlx::query(
+            "UPDATE rmbpost_queue SET status = 'queued', modified_at = $1
+            WHERE status = 'deferred'
+            RETURNING id;",
+        )
+        .bind(chrono::Utc::now())
+        .map(|row: PgRow| row.get("id"))
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(job_ids) => job_ids,
+            Err(e) => {
+                tracing::error!("{}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Queues the unfinished jobs, those in `job_ids` if given, or settles them if they can't
+    /// be posted any more.
+    async fn restore(&mut self, job_ids: Option<&[i32]>) {
+        let jobs = match sqlx::query(
+            "SELECT id, nation, region, content, status, created_at, request_id
+            FROM rmbpost_queue
+            WHERE status = ANY($2)
+            -- deferred jobs are taken over by the deferred pass instead
+            AND status <> 'deferred'
+            AND ($1::INTEGER[] IS NULL OR id = ANY($1))
+            ORDER BY id;",
+        )
+        .bind(job_ids)
+        .bind(JobStatus::PENDING)
+        .map(|row: PgRow| {
+            let mut post = IntermediateRmbPost::new(
+                row.get("id"),
+                row.get("nation"),
+                row.get("region"),
+                row.get("content"),
+            );
+            post.request_id = row.get("request_id");
+
+            (
+                row.get::<JobStatus, _>("status"),
+                row.get::<chrono::DateTime<chrono::Utc>, _>("created_at"),
+                post,
+            )
+        })
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                tracing::error!("{}", e);
+                return;
+            }
+        };
+
+        let now = chrono::Utc::now();
+
+        for (status, created_at, post) in jobs {
             let job_id = post.job_id;
 
-            match self.post(post).await {
-                Ok(id) => self.update_job(job_id, "success", Some(id), None).await,
-                Err(e) => self.update_job(job_id, "error", None, Some(e)).await,
+            match self.recovery.decide(status, created_at, now) {
+                Unfinished::Requeue => {
+                    tracing::info!(job_id, "requeueing job");
+                    self.queue_post(post).await;
+                }
+                Unfinished::Abandon => {
+                    tracing::warn!(job_id, "abandoning job");
+                    self.update_job(
+                        job_id,
+                        JobStatus::Cancelled,
+                        None,
+                        Some(Error::JobAbandoned),
+                    )
+                    .await;
+                }
+                // RMB posts aren't looked for on NS, so none is left unknown to verify
+                Unfinished::Interrupted | Unfinished::Verify => {
+                    tracing::warn!(job_id, "job was interrupted");
+                    self.update_job(job_id, JobStatus::Failed, None, Some(Error::JobInterrupted))
+                        .await;
+                }
             }
         }
     }
 
     #[tracing::instrument(skip_all)]
-    async fn get_post(&mut self) -> Option<IntermediateRmbPost> {
-        for (index, post) in self.queue.iter().enumerate() {
-            if self
-                .limiter
-                .peek(ratelimiter::Target::Restricted {
-                    sender: post.nation.clone(),
-                })
-                .await
-                <= PERIOD
-            {
-                return Some(self.queue.remove(index).unwrap());
+    async fn try_post(&mut self) {
+        if self.pipeline.is_paused() {
+            return self.pipeline.waiting(self.queue.len());
+        }
+
+        if let Some(post) = self.get_post().await {
+            let span = tracing::info_span!(
+                "job",
+                job_id = post.job_id,
+                nation = post.nation,
+                request_id = post.request_id.as_deref()
+            );
+
+            async {
+                let job_id = post.job_id;
+                let nation = post.nation.clone();
+
+                // a job that can't be claimed was finished or cancelled elsewhere
+                if !self
+                    .update_job(job_id, JobStatus::Claimed, None, None)
+                    .await
+                {
+                    return;
+                }
+
+                self.pipeline.posting(job_id, &nation);
+
+                match self.post(post).await {
+                    Ok(id) => {
+                        self.pipeline.finished(job_id, None);
+                        self.update_job(job_id, JobStatus::Succeeded, Some(id), None)
+                            .await;
+                    }
+                    Err(e) => {
+                        self.pipeline.finished(job_id, Some(&e));
+                        self.report_credentials(&nation, &e).await;
+                        self.update_job(job_id, JobStatus::Failed, None, Some(e))
+                            .await;
+                    }
+                }
             }
+            .instrument(span)
+            .await;
         }
 
-        None
+        self.pipeline.waiting(self.queue.len());
     }
 
+    /// The first post whose nation is off cooldown. Only each nation's first post is peeked, and
+    /// the post is taken by its job id once the limiter has answered.
     #[tracing::instrument(skip_all)]
-    async fn post(&mut self, mut post: IntermediateRmbPost) -> Result<i32, Error> {
-        let nation = post.nation.clone();
-        let password = self.nations.get_password(&nation).await?;
+    async fn get_post(&mut self) -> Option<IntermediateRmbPost> {
+        let mut seen = HashSet::new();
+
+        let heads = self
+            .queue
+            .iter()
+            .filter(|post| seen.insert(name::canonicalize(&post.nation)))
+            .map(|post| {
+                (
+                    post.job_id,
+                    ratelimiter::Target::restricted(
+                        &post.nation,
+                        ratelimiter::RestrictedAction::RmbPost,
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
 
-        post.text = encode(&post.text);
+        for (job_id, target) in heads {
+            if self.limiter.peek(target).await <= PERIOD {
+                let index = self.queue.iter().position(|post| post.job_id == job_id)?;
 
-        if let Err(duration) = self
-            .limiter
-            .acquire(ratelimiter::Target::Restricted {
-                sender: post.nation.clone(),
-            })
-            .await
-        {
-            tokio::time::sleep(duration).await;
+                return self.queue.remove(index);
+            }
         }
 
-        let post = crate::ns::rmbpost::RmbPost::from(post);
+        None
+    }
+
+    /// Sends a private command for `nation`. The pin is read fresh for every request and the one
+    /// NS hands back is stored; when NS rejects the credentials the pin is forgotten, so the next
+    /// attempt logs in with the password instead of repeating a stale pin. A rejected pin comes
+    /// back as `NsError::PinExpired`, a rejected password as `NsError::InvalidPassword`.
+    #[tracing::instrument(skip_all)]
+    async fn send_command(
+        &self,
+        password: &Secret,
+        nation: &str,
+        body: String,
+    ) -> Result<String, Error> {
+        let pin = self.nations.get_pin(nation).await?.unwrap_or_default();
 
         let resp = self
             .client
             .post(&self.url)
-            .header("X-Password", &password)
-            .header(
-                "X-Pin",
-                &self.nations.get_pin(&nation).await?.unwrap_or_default(),
-            )
+            .header("X-Password", password.expose())
+            .header("X-Pin", &pin)
             .header(
                 "Content-Type",
                 "application/x-www-form-urlencoded; charset=UTF-8",
             )
-            .body(serde_urlencoded::to_string(&post)?)
+            .body(body)
             .send()
-            .await?
-            .error_for_status()?;
+            .await?;
+
+        self.limiter.observe(resp.headers()).await;
+
+        if resp.status() == StatusCode::FORBIDDEN {
+            self.nations.clear_pin(nation).await?;
+        }
+
+        let resp = error::check_status(resp, !pin.is_empty())?;
 
         if let Some(val) = resp.headers().get("X-Pin") {
             self.nations
-                .set_pin(post.nation(), val.to_str().map_err(Error::HeaderDecode)?)
+                .set_pin(nation, val.to_str().map_err(Error::HeaderDecode)?)
                 .await?
         }
 
-        let response = de::from_str::<Response>(&resp.text().await?)?;
+        Ok(resp.text().await?)
+    }
+
+    /// Executes the prepare request and returns the token for the execute request. When the pin
+    /// turns out to be stale the request is retried once with the password alone.
+    #[tracing::instrument(skip_all)]
+    async fn prepare(
+        &self,
+        password: &Secret,
+        nation: &str,
+        post: &RmbPost<Unprepared>,
+    ) -> Result<String, Error> {
+        let body = serde_urlencoded::to_string(post)?;
+
+        let body = match self.send_command(password, nation, body.clone()).await {
+            Err(Error::NationStates(NsError::PinExpired)) => {
+                tracing::info!("pin expired, retrying with password");
+                self.send_command(password, nation, body).await?
+            }
+            result => result?,
+        };
+
+        reply::parse(&body)
+            .and_then(Reply::into_result)
+            .map_err(Error::NationStates)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn post(&mut self, mut post: IntermediateRmbPost) -> Result<i32, Error> {
+        let nation = post.nation.clone();
+        let password = self.nations.get_password(&nation).await?;
+
+        post.text = encode(&post.text);
+
+        let target =
+            ratelimiter::Target::restricted(&post.nation, ratelimiter::RestrictedAction::RmbPost);
+
+        let (slot, acquire) = self.limiter.acquire_slot(target.clone()).await;
 
-        if !response.is_ok() {
-            return Err(Error::NationStates(response.error.unwrap()));
+        if let Err(duration) = acquire {
+            tokio::time::sleep(duration).await;
         }
 
-        let post = post.prepare(response.success.unwrap());
+        let job_id = post.job_id;
+        let post = RmbPost::from(post);
+
+        self.update_job(job_id, JobStatus::Posting, None, None)
+            .await;
+
+        let post = match self.prepare(&password, &nation, &post).await {
+            Ok(token) => post.prepare(token),
+            Err(e) => {
+                // NS never performed the action, so the cooldown slot can be handed back
+                self.limiter.release(target, slot).await;
+
+                return Err(e);
+            }
+        };
+
+        self.stamp_job(job_id, Phase::Prepared).await;
 
         if let Err(duration) = self.limiter.acquire(ratelimiter::Target::Standard).await {
             tokio::time::sleep(duration).await;
         }
 
-        let resp = self
-            .client
-            .post(&self.url)
-            .header("X-Password", &password)
-            .header(
-                "X-Pin",
-                &self.nations.get_pin(&nation).await?.unwrap_or_default(),
-            )
-            .header(
-                "Content-Type",
-                "application/x-www-form-urlencoded; charset=UTF-8",
-            )
-            .body(serde_urlencoded::to_string(&post)?)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        let response = de::from_str::<Response>(&resp.text().await?)?;
-
-        if response.is_ok() {
-            Ok(self
-                .re
-                .captures(&response.success.unwrap())
-                .unwrap()
-                .get(1)
-                .unwrap()
-                .as_str()
-                .parse::<i32>()?)
-        } else {
-            Err(Error::NationStates(response.error.unwrap()))
+        self.stamp_job(job_id, Phase::Executing).await;
+
+        // picks up the pin NS returned from the prepare request
+        let body = self
+            .send_command(&password, &nation, serde_urlencoded::to_string(&post)?)
+            .await?;
+
+        let success = reply::parse(&body)
+            .and_then(Reply::into_result)
+            .map_err(Error::NationStates)?;
+
+        match self.re.captures(&success).and_then(|c| c.get(1)) {
+            Some(id) => Ok(id.as_str().parse::<i32>()?),
+            None => Err(Error::NationStates(NsError::Unknown(format!(
+                "No post id in NS reply: {}",
+                success
+            )))),
         }
     }
 
@@ -173,6 +387,13 @@ impl Client {
                 self.queue_post(post).await;
                 rmbpost::Response::Success
             }
+            Action::Control(control) => {
+                rmbpost::Response::Pipeline(self.pipeline.apply(control, self.queue.len()))
+            }
+            Action::Revalidate { nation } => match self.revalidate(&nation).await {
+                Ok(()) => rmbpost::Response::Success,
+                Err(e) => rmbpost::Response::Error(e),
+            },
         };
 
         if command.tx.send(response).is_err() {
@@ -180,45 +401,136 @@ impl Client {
         }
     }
 
+    /// Queues `post` unless its job is already waiting, as it can be when a job queued during
+    /// startup is also picked up by [`Client::recover`].
     #[tracing::instrument(skip_all)]
     async fn queue_post(&mut self, post: IntermediateRmbPost) {
+        if self.queue.iter().any(|queued| queued.job_id == post.job_id) {
+            return;
+        }
+
         self.queue.push_back(post);
     }
 
+    /// A rejected password marks the nation unhealthy, which holds its jobs back from NS until
+    /// an admin revalidates it.
+    #[tracing::instrument(skip_all)]
+    async fn report_credentials(&self, nation: &str, error: &Error) {
+        if let Error::NationStates(NsError::InvalidPassword) = error
+            && let Err(e) = self.nations.mark_unhealthy(nation).await
+        {
+            tracing::error!("{}", e);
+        }
+    }
+
+    /// Checks the nation's password with a ping and marks it healthy again if NS accepts it.
+    #[tracing::instrument(skip(self))]
+    async fn revalidate(&self, nation: &str) -> Result<(), Error> {
+        let password = self.nations.get_password_unchecked(nation).await?;
+
+        if let Err(duration) = self.limiter.acquire(ratelimiter::Target::Standard).await {
+            tokio::time::sleep(duration).await;
+        }
+
+        if let Some(pin) = ns::ping(&self.client, &self.url, nation, &password).await? {
+            self.nations.set_pin(nation, &pin).await?;
+        }
+
+        self.nations.mark_healthy(nation).await
+    }
+
+    /// Stamps the time the job reached `phase`.
+    #[tracing::instrument(skip_all)]
+    async fn stamp_job(&self, job_id: i32, phase: Phase) {
+        if let Err(e) = sqlx::query(&format!(
+            "UPDATE rmbpost_queue SET {} = $1 WHERE id = $2;",
+            phase.column()
+        ))
+        .bind(chrono::Utc::now())
+        .bind(job_id)
+        .execute(&self.pool)
+        .await
+        {
+            tracing::error!("{}", e);
+        }
+    }
+
+    /// Moves the job to `status` and stamps the time it got there. A job that isn't in a status
+    /// `status` can follow is left alone, and `false` returned.
     #[tracing::instrument(skip_all)]
     async fn update_job(
         &self,
         job_id: i32,
-        status: &str,
-        dispatch_id: Option<i32>,
+        status: JobStatus,
+        rmbpost_id: Option<i32>,
         error: Option<Error>,
-    ) {
-        let error: &str = match error {
-            Some(err) => &err.to_string(),
-            None => "",
-        };
-
-        if let Err(e) = sqlx::query(
-            "UPDATE rmbpost_queue SET status = $1, rmbpost_id = $2, error = $3, modified_at = $4 WHERE id = $5;",
+    ) -> bool {
+        let code = error.as_ref().and_then(Error::job_code);
+        let error = error.map(|err| err.to_string());
+
+        match sqlx::query(
+            "UPDATE rmbpost_queue SET
+                status = $1,
+                rmbpost_id = $2,
+                error = $3,
+                error_code = $4,
+                modified_at = $5,
+                claimed_at = CASE WHEN $1 = 'claimed' THEN $5 ELSE claimed_at END,
+                started_at = CASE WHEN $1 = 'posting' THEN $5 ELSE started_at END,
+                finished_at = CASE WHEN $1 IN ('succeeded', 'failed', 'cancelled') THEN $5 ELSE finished_at END
+            WHERE id = $6 AND status = ANY($7)
+            RETURNING region, created_by, tenant_id;",
         )
             .bind(status)
-            .bind(dispatch_id)
+            .bind(rmbpost_id)
             .bind(error)
+            .bind(code)
             .bind(chrono::Utc::now())
             .bind(job_id)
-            .execute(&self.pool)
+            .bind(status.follows())
+            .fetch_optional(&self.pool)
             .await
         {
-            tracing::error!("{}", e);
+            Ok(Some(row)) => {
+                tracing::info!(job_id, %status, "job status changed");
+                self.events.publish(JobEvent {
+                    job: JobKind::Rmbpost,
+                    id: job_id,
+                    status,
+                    error_code: code.map(str::to_string),
+                    self_url: RmbPostStatus::self_url(job_id),
+                    resource: RmbPostStatus::resource_url(status, row.get("region"), rmbpost_id),
+                    created_by: row.get("created_by"),
+                    tenant_id: row.get("tenant_id"),
+                });
+
+                true
+            }
+            Ok(None) => {
+                tracing::warn!(job_id, %status, "job can't change status");
+
+                false
+            }
+            Err(e) => {
+                tracing::error!("{}", e);
+
+                false
+            }
         }
     }
 
     #[tracing::instrument(skip_all)]
     pub(crate) async fn run(&mut self) {
         let mut interval = tokio::time::interval(PERIOD);
+        let mut deferred = tokio::time::interval(DEFERRED_PERIOD);
+
+        self.recover().await;
 
         loop {
+            // commands first, so a pause always lands before the next tick
             tokio::select! {
+                biased;
+
                 Some(command) = self.rx.recv() => {
                     self.process_command(command).await;
                 }
@@ -226,45 +538,179 @@ impl Client {
                 _ = interval.tick() => {
                     self.try_post().await;
                 }
+
+                _ = deferred.tick() => {
+                    self.pick_up_deferred().await;
+                }
             }
         }
     }
 }
 
-fn post_status(row: PgRow) -> RmbPostStatus {
-    RmbPostStatus {
-        id: row.get("id"),
-        status: row.get("status"),
-        rmbpost_id: row.get("rmbpost_id"),
-        error: row.get("error"),
-        created_at: row.get("created_at"),
-        modified_at: row.get("modified_at"),
-    }
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "UPPERCASE")]
-struct Response {
-    success: Option<String>,
-    error: Option<String>,
-}
-
-impl Response {
-    fn is_ok(&self) -> bool {
-        self.success.is_some()
-    }
-}
-
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn new(
     user_agent: &str,
     url: &str,
     pool: PgPool,
     limiter: ratelimiter::Sender,
     nations: nations::Sender,
+    events: events::Sender,
+    recovery: Recovery,
+    capacity: usize,
+    activity: Activity,
 ) -> Result<(mpsc::Sender<Command>, Client), ConfigError> {
-    let (tx, rx) = mpsc::channel(16);
+    let (tx, rx) = mpsc::channel(capacity.max(1));
 
-    let client = Client::new(user_agent, url, pool, limiter, nations, rx)?;
+    let client = Client {
+        recovery,
+        pipeline: Pipeline::reporting("rmbpost", activity),
+        ..Client::new(user_agent, url, pool, limiter, nations, events, rx)?
+    };
 
     Ok((tx, client))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Form, State};
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use sqlx::postgres::PgPoolOptions;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Pins sent with each request to the stub, as `(mode, pin)`.
+    type Seen = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// Stands in for the NS API. Prepare hands out pin 1234; with `forbidden` every request is
+    /// rejected instead.
+    async fn serve(forbidden: bool) -> (String, Seen) {
+        let seen = Seen::default();
+
+        let app = axum::Router::new()
+            .route(
+                "/",
+                post(
+                    |State((seen, forbidden)): State<(Seen, bool)>,
+                     headers: HeaderMap,
+                     Form(form): Form<HashMap<String, String>>| async move {
+                        let pin = headers
+                            .get("X-Pin")
+                            .and_then(|pin| pin.to_str().ok())
+                            .unwrap_or_default()
+                            .to_string();
+
+                        seen.lock().unwrap().push((form["mode"].clone(), pin));
+
+                        if forbidden {
+                            return Err(StatusCode::FORBIDDEN);
+                        }
+
+                        Ok(match form["mode"].as_str() {
+                            "prepare" => (
+                                [("X-Pin", "1234")],
+                                "<NATION><SUCCESS>token</SUCCESS></NATION>",
+                            ),
+                            _ => (
+                                [("X-Pin", "1234")],
+                                "<NATION><SUCCESS>/region=testregion/page=display_region_rmb?postid=42#p42</SUCCESS></NATION>",
+                            ),
+                        })
+                    },
+                ),
+            )
+            .with_state((seen.clone(), forbidden));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{address}/"), seen)
+    }
+
+    fn client(url: &str, nations: nations::Sender) -> Client {
+        // there's no database here, so status updates should give up quickly
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy("postgresql://localhost/eurocore")
+            .unwrap();
+        let limiter = ratelimiter::new(
+            50,
+            Duration::from_secs(30),
+            Duration::from_secs(30),
+            Duration::from_secs(180),
+            Duration::from_secs(60),
+            Default::default(),
+            0.8,
+        );
+        let (_, rx) = mpsc::channel(1);
+
+        Client::new(
+            "testlandia",
+            url,
+            pool,
+            limiter,
+            nations,
+            events::Sender::new(),
+            rx,
+        )
+        .unwrap()
+    }
+
+    fn new_post() -> IntermediateRmbPost {
+        IntermediateRmbPost::new(
+            1,
+            "testlandia".to_string(),
+            "testregion".to_string(),
+            "text".to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_execute_uses_pin_from_prepare() {
+        let (url, seen) = serve(false).await;
+        let nations = nations::new("testlandia:password").unwrap();
+
+        let id = client(&url, nations.clone())
+            .post(new_post())
+            .await
+            .unwrap();
+
+        assert_eq!(id, 42);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                ("prepare".to_string(), String::new()),
+                ("execute".to_string(), "1234".to_string()),
+            ]
+        );
+        assert_eq!(
+            nations.get_pin("testlandia").await.unwrap().as_deref(),
+            Some("1234")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forbidden_clears_pin() {
+        let (url, seen) = serve(true).await;
+        let nations = nations::new("testlandia:password").unwrap();
+        nations.set_pin("testlandia", "stale").await.unwrap();
+
+        // the stale pin is dropped and prepare retried once with the password, which fails too
+        assert!(matches!(
+            client(&url, nations.clone()).post(new_post()).await,
+            Err(Error::NationStates(NsError::InvalidPassword))
+        ));
+        assert_eq!(nations.get_pin("testlandia").await.unwrap(), None);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                ("prepare".to_string(), "stale".to_string()),
+                ("prepare".to_string(), "".to_string()),
+            ]
+        );
+    }
+}
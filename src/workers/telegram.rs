@@ -1,29 +1,122 @@
-use super::PERIOD;
+use super::{Activity, PERIOD, Pipeline};
 use crate::core::error::{ConfigError, Error};
-use crate::ns::telegram::{Command, Header, Operation, Params, Response, Telegram, TgType};
+use crate::ns::error::NsError;
+use crate::ns::telegram::{self, Command, Job, Operation, Params, Response, Telegram, TgType};
 use crate::sync::ratelimiter;
 use crate::sync::ratelimiter::Target;
-use crate::types::response;
+use crate::sync::telegram_types::TelegramTypes;
+use crate::types::{Secret, response};
+use crate::utils::name;
+use chrono::{DateTime, Utc};
 use reqwest::{self, ClientBuilder};
-use std::collections::{HashMap, VecDeque};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tracing::Instrument;
+
+/// Most telegrams a campaign may have waiting in the in-memory queue at once.
+const CAMPAIGN_BATCH: usize = 50;
+/// How often campaigns are topped back up to `CAMPAIGN_BATCH`.
+const CAMPAIGN_PERIOD: Duration = Duration::from_secs(5);
+/// Window recruitment telegrams are counted over for capacity stats.
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// NS client keys are issued per nation, so each sender may use its own key.
+/// Senders without a dedicated key fall back to the default key, if one is configured.
+#[derive(Clone, Debug)]
+pub(crate) struct ClientKeys {
+    default: Option<Secret>,
+    keys: HashMap<String, Secret>,
+}
+
+impl ClientKeys {
+    /// `keys` is a comma-separated list of `nation:key` pairs. Errors point at the pair by
+    /// position, so keys don't end up in the logs.
+    pub(crate) fn new(default: Option<String>, keys: Option<&str>) -> Result<Self, ConfigError> {
+        let keys = match keys {
+            Some(keys) if !keys.trim().is_empty() => keys
+                .split(",")
+                .enumerate()
+                .map(|(index, value)| parse_client_key(index + 1, value))
+                .collect::<Result<HashMap<String, Secret>, ConfigError>>()?,
+            _ => HashMap::new(),
+        };
+
+        let default = default
+            .filter(|key| !key.trim().is_empty())
+            .map(Secret::from);
+
+        if default.is_none() && keys.is_empty() {
+            return Err(ConfigError::ClientKeys(
+                "no telegram client keys configured".to_string(),
+            ));
+        }
+
+        Ok(Self { default, keys })
+    }
+
+    pub(crate) fn get(&self, sender: &str) -> Option<&Secret> {
+        self.keys
+            .get(&name::canonicalize(sender))
+            .or(self.default.as_ref())
+    }
+
+    pub(crate) fn senders(&self) -> Vec<String> {
+        let mut senders = self.keys.keys().cloned().collect::<Vec<String>>();
+
+        senders.sort();
+
+        senders
+    }
+}
+
+fn parse_client_key(position: usize, value: &str) -> Result<(String, Secret), ConfigError> {
+    let (nation, key) = value.split_once(":").ok_or_else(|| {
+        ConfigError::ClientKeys(format!(
+            "entry {position} has no `:` between the nation and its key"
+        ))
+    })?;
+
+    let (nation, key) = (nation.trim(), key.trim());
+
+    if nation.is_empty() || key.is_empty() {
+        return Err(ConfigError::ClientKeys(format!(
+            "entry {position} has an empty nation or key"
+        )));
+    }
+
+    Ok((name::canonicalize(nation), Secret::from(key)))
+}
 
 #[derive(Debug)]
 pub(crate) struct Client {
     url: String,
     client: reqwest::Client,
-    key: String,
+    pool: PgPool,
+    keys: ClientKeys,
+    types: TelegramTypes,
     recruitment_queue: VecDeque<Telegram>,
     standard_queue: VecDeque<Telegram>,
     limiter: ratelimiter::Sender,
     rx: mpsc::Receiver<Command>,
+    pipeline: Pipeline,
+    /// active campaigns whose recipients are being fed into the queue
+    campaigns: BTreeSet<i32>,
+    /// queue the last telegram was taken from, see [`choose`]
+    last_queue: Option<Queue>,
+    /// sender and time of every recruitment telegram sent in the last [`DAY`], oldest first
+    recruitment_sent: VecDeque<(String, DateTime<Utc>)>,
 }
 
 impl Client {
     fn new(
         user_agent: &str,
         url: &str,
-        key: String,
+        pool: PgPool,
+        keys: ClientKeys,
+        types: TelegramTypes,
         limiter: ratelimiter::Sender,
         rx: mpsc::Receiver<Command>,
     ) -> Result<Self, ConfigError> {
@@ -32,26 +125,331 @@ impl Client {
         Ok(Self {
             url: url.to_owned(),
             client,
-            key,
+            pool,
+            keys,
+            types,
             recruitment_queue: VecDeque::new(),
             standard_queue: VecDeque::new(),
             limiter,
             rx,
+            pipeline: Pipeline::new("telegram"),
+            campaigns: BTreeSet::new(),
+            last_queue: None,
+            recruitment_sent: VecDeque::new(),
         })
     }
 
     #[tracing::instrument(skip_all)]
-    fn process_command(&mut self, command: Command) {
+    async fn update_job(&self, job_id: i32, status: &str, error: Option<&Error>) {
+        if let Err(e) = sqlx::query(
+            "UPDATE telegram_queue SET status = $1, error = $2, error_code = $3, modified_at = $4 WHERE id = $5;",
+        )
+        .bind(status)
+        .bind(error.map(Error::to_string))
+        .bind(error.and_then(Error::job_code))
+        .bind(chrono::Utc::now())
+        .bind(job_id)
+        .execute(&self.pool)
+        .await
+        {
+            tracing::error!("{}", e);
+        } else {
+            tracing::info!(job_id, status, "job status changed");
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn skip_jobs(&self, job_ids: &[i32]) {
+        if job_ids.is_empty() {
+            return;
+        }
+
+        if let Err(e) = sqlx::query(
+            "UPDATE telegram_queue SET status = 'skipped', modified_at = $1 WHERE id = ANY($2);",
+        )
+        .bind(chrono::Utc::now())
+        .bind(job_ids)
+        .execute(&self.pool)
+        .await
+        {
+            tracing::error!("{}", e);
+        }
+    }
+
+    /// Drops unsent campaign jobs so their recipients are picked up again on the next refill.
+    #[tracing::instrument(skip_all)]
+    async fn release_jobs(&self, job_ids: &[i32]) {
+        if job_ids.is_empty() {
+            return;
+        }
+
+        if let Err(e) =
+            sqlx::query("DELETE FROM telegram_queue WHERE id = ANY($1) AND status = 'queued';")
+                .bind(job_ids)
+                .execute(&self.pool)
+                .await
+        {
+            tracing::error!("{}", e);
+        }
+    }
+
+    /// Picks up active campaigns after a restart. Jobs that were waiting in the old process's
+    /// queue never reached NS, so they are released and fed in again.
+    #[tracing::instrument(skip_all)]
+    async fn load_campaigns(&mut self) {
+        if let Err(e) = sqlx::query(
+            "DELETE FROM telegram_queue
+            WHERE status = 'queued'
+            AND id IN (SELECT job_id FROM telegram_campaign_recipients);",
+        )
+        .execute(&self.pool)
+        .await
+        {
+            tracing::error!("{}", e);
+        }
+
+        match sqlx::query("SELECT id FROM telegram_campaigns WHERE status = 'active';")
+            .map(|row: PgRow| row.get::<i32, _>("id"))
+            .fetch_all(&self.pool)
+            .await
+        {
+            Ok(ids) => self.campaigns.extend(ids),
+            Err(e) => tracing::error!("{}", e),
+        }
+    }
+
+    /// Seeds the rolling count of recruitment telegrams with what was sent before a restart.
+    #[tracing::instrument(skip_all)]
+    async fn load_recruitment_sent(&mut self) {
+        match sqlx::query(
+            "SELECT sender, modified_at FROM telegram_queue
+            WHERE status = 'sent' AND tg_type = 'recruitment' AND modified_at > $1
+            ORDER BY modified_at;",
+        )
+        .bind(Utc::now() - DAY)
+        .map(|row: PgRow| {
+            (
+                name::canonicalize(row.get::<&str, _>("sender")),
+                row.get::<DateTime<Utc>, _>("modified_at"),
+            )
+        })
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(sent) => self.recruitment_sent.extend(sent),
+            Err(e) => tracing::error!("{}", e),
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn refill_campaigns(&mut self) {
+        for id in self.campaigns.clone() {
+            self.refill(id).await;
+        }
+    }
+
+    /// Tops the queue up with the next recipients of campaign `id`, and marks the campaign
+    /// completed once every recipient has been handed to NS.
+    #[tracing::instrument(skip(self))]
+    async fn refill(&mut self, id: i32) {
+        let pending = campaign_pending(&self.recruitment_queue, &self.standard_queue, id);
+
+        if pending >= CAMPAIGN_BATCH {
+            return;
+        }
+
+        let jobs = match self.next_jobs(id, CAMPAIGN_BATCH - pending).await {
+            Ok(Some(jobs)) => jobs,
+            Ok(None) => {
+                self.campaigns.remove(&id);
+                return;
+            }
+            Err(e) => {
+                tracing::error!("failed to refill campaign: {}", e);
+                return;
+            }
+        };
+
+        if jobs.is_empty() {
+            if pending == 0 {
+                self.complete_campaign(id).await;
+            }
+
+            return;
+        }
+
+        // a campaign may have been created before NS revealed its telegram's type
+        if let Err(e) = self
+            .types
+            .recorded(jobs.iter().map(|job| job.params.id.as_str()))
+            .await
+        {
+            tracing::error!("{}", e);
+        }
+
+        for job in jobs {
+            let job_id = job.id;
+
+            match Telegram::from_params(&self.keys, job) {
+                Some(mut telegram) => {
+                    telegram.campaign_id = Some(id);
+
+                    self.push(telegram);
+                }
+                None => {
+                    self.update_job(
+                        job_id,
+                        "failed",
+                        Some(&Error::UnknownTelegramSender(self.keys.senders())),
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+
+    /// Records the next `limit` unsent recipients of campaign `id` in `telegram_queue`.
+    /// Returns `None` if the campaign is no longer active.
+    #[tracing::instrument(skip(self))]
+    async fn next_jobs(&self, id: i32, limit: usize) -> Result<Option<Vec<Job>>, Error> {
+        let Some((sender, telegram_id, secret_key, tg_type, created_by)) = sqlx::query(
+            "SELECT sender, telegram_id, secret_key, tg_type, created_by
+            FROM telegram_campaigns
+            JOIN telegram_campaign_secrets ON telegram_campaign_secrets.campaign_id = id
+            WHERE id = $1 AND status = 'active';",
+        )
+        .bind(id)
+        .map(|row: PgRow| {
+            (
+                row.get::<String, _>("sender"),
+                row.get::<String, _>("telegram_id"),
+                Secret::new(row.get::<String, _>("secret_key")),
+                row.get::<String, _>("tg_type"),
+                row.get::<String, _>("created_by"),
+            )
+        })
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let mut jobs = sqlx::query(
+            "WITH next AS (
+                SELECT position, recipient
+                FROM telegram_campaign_recipients
+                WHERE campaign_id = $1 AND job_id IS NULL
+                ORDER BY position
+                LIMIT $2
+            ), queued AS (
+                INSERT INTO telegram_queue
+                    (sender, recipient, telegram_id, tg_type, status, created_by, tenant_id)
+                SELECT $3, recipient, $4, $5, 'queued', $6,
+                    (SELECT tenant_id FROM telegram_campaigns WHERE id = $1)
+                FROM next
+                ORDER BY position
+                RETURNING id, recipient
+            )
+            UPDATE telegram_campaign_recipients
+            SET job_id = queued.id
+            FROM queued
+            WHERE campaign_id = $1 AND telegram_campaign_recipients.recipient = queued.recipient
+            RETURNING queued.id, queued.recipient;",
+        )
+        .bind(id)
+        .bind(limit as i64)
+        .bind(&sender)
+        .bind(&telegram_id)
+        .bind(&tg_type)
+        .bind(&created_by)
+        .map(|row: PgRow| Job {
+            id: row.get("id"),
+            params: Params {
+                sender: sender.clone(),
+                id: telegram_id.clone(),
+                recipient: row.get("recipient"),
+                secret_key: secret_key.clone(),
+                tg_type: TgType::from_column(&tg_type),
+            },
+            created_by: created_by.clone(),
+        })
+        .fetch_all(&self.pool)
+        .await?;
+
+        // job ids follow recipient order, which UPDATE ... RETURNING doesn't preserve
+        jobs.sort_by_key(|job| job.id);
+
+        Ok(Some(jobs))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn complete_campaign(&mut self, id: i32) {
+        self.campaigns.remove(&id);
+
+        if let Err(e) = sqlx::query(
+            "UPDATE telegram_campaigns SET status = 'completed', modified_at = $1
+            WHERE id = $2 AND status = 'active';",
+        )
+        .bind(chrono::Utc::now())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        {
+            tracing::error!("{}", e);
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn process_command(&mut self, command: Command) {
         let response = match command.operation {
-            Operation::Queue(telegrams) => {
-                self.queue(telegrams);
+            Operation::Queue(jobs) => match self.queue(jobs) {
+                Ok(()) => Response::Ok,
+                Err(e) => Response::Error(e),
+            },
+            Operation::Delete(header, scope) => {
+                let removed =
+                    self.delete(|telegram| telegram.header() == header && scope.allows(telegram));
+                self.skip_jobs(&removed).await;
                 Response::Ok
             }
-            Operation::Delete(header) => {
-                self.delete(header);
+            Operation::DeleteJob(id, scope) => {
+                let owner = self
+                    .recruitment_queue
+                    .iter()
+                    .chain(self.standard_queue.iter())
+                    .find(|telegram| telegram.job_id == id)
+                    .map(|telegram| (scope.allows(telegram), telegram.created_by.clone()));
+
+                match owner {
+                    None => Response::Error(Error::JobNotFound),
+                    Some((false, created_by)) => {
+                        Response::Error(Error::NotTelegramOwner(created_by))
+                    }
+                    Some((true, _)) => {
+                        let removed = self.delete(|telegram| telegram.job_id == id);
+                        self.skip_jobs(&removed).await;
+                        Response::Ok
+                    }
+                }
+            }
+            Operation::List(created_by) => Response::List(self.list(created_by.as_deref())),
+            Operation::Summary => Response::Summary(self.summary().await),
+            Operation::Stats => Response::Stats(self.stats().await),
+            Operation::StartCampaign(id) => {
+                self.campaigns.insert(id);
+                self.refill(id).await;
                 Response::Ok
             }
-            Operation::List => Response::List(self.list()),
+            Operation::PauseCampaign(id) => {
+                self.campaigns.remove(&id);
+                let removed = self.delete(|telegram| telegram.campaign_id == Some(id));
+                self.release_jobs(&removed).await;
+                Response::Ok
+            }
+            Operation::Control(control) => Response::Pipeline(self.pipeline.apply(
+                control,
+                self.recruitment_queue.len() + self.standard_queue.len(),
+            )),
         };
 
         if command.tx.send(response).is_err() {
@@ -60,113 +458,347 @@ impl Client {
     }
 
     #[tracing::instrument(skip_all)]
-    fn queue(&mut self, params: Vec<Params>) {
-        for param in params {
-            let telegram = Telegram::from_params(&self.key, param);
+    fn queue(&mut self, jobs: Vec<Job>) -> Result<(), Error> {
+        let telegrams = jobs
+            .into_iter()
+            .map(|job| Telegram::from_params(&self.keys, job))
+            .collect::<Option<Vec<Telegram>>>()
+            .ok_or_else(|| Error::UnknownTelegramSender(self.keys.senders()))?;
 
-            match &telegram.tg_type {
-                TgType::Standard => self.standard_queue.push_back(telegram),
-                TgType::Recruitment => self.recruitment_queue.push_back(telegram),
-            }
+        for telegram in telegrams {
+            self.push(telegram);
+        }
+
+        Ok(())
+    }
+
+    /// Queues `telegram` by its type, corrected to the one NS was found to give it.
+    fn push(&mut self, mut telegram: Telegram) {
+        reclassify(&self.types, &mut telegram);
+
+        match &telegram.tg_type {
+            TgType::Standard => self.standard_queue.push_back(telegram),
+            TgType::Recruitment => self.recruitment_queue.push_back(telegram),
         }
     }
 
+    /// NS held `telegram`, sent as standard, to the recruitment cooldown, so its template is a
+    /// recruitment one. That's recorded, and it and the other queued telegrams of the template
+    /// move to the recruitment queue. It wasn't sent, so it goes first.
     #[tracing::instrument(skip_all)]
-    fn delete(&mut self, header: Header) {
-        self.standard_queue
-            .retain(|telegram| telegram.header() != header);
+    async fn correct(&mut self, mut telegram: Telegram) {
+        tracing::error!(
+            telegram_id = telegram.telegram_id,
+            "NS treats the telegram as recruitment, but it was queued as standard"
+        );
 
-        self.recruitment_queue
-            .retain(|telegram| telegram.header() != header);
+        if let Err(e) = self
+            .types
+            .record(&telegram.telegram_id, TgType::Recruitment)
+            .await
+        {
+            tracing::error!("{}", e);
+        }
+
+        // NS's recruitment cooldown is running, so the limiter's starts too
+        let _ = self
+            .limiter
+            .acquire(Target::recruitment(&telegram.sender))
+            .await;
+
+        let (mut moved, standard) = std::mem::take(&mut self.standard_queue)
+            .into_iter()
+            .partition(|queued| queued.telegram_id == telegram.telegram_id);
+        self.standard_queue = standard;
+
+        telegram.tg_type = TgType::Recruitment;
+        self.recruitment_queue.push_front(telegram);
+
+        for telegram in &mut moved {
+            telegram.tg_type = TgType::Recruitment;
+        }
+
+        self.recruitment_queue.extend(moved);
     }
 
+    /// Removes every queued telegram matching `predicate`, returning their job ids.
     #[tracing::instrument(skip_all)]
-    fn list(&self) -> HashMap<String, Vec<response::Telegram>> {
+    fn delete<F: Fn(&Telegram) -> bool>(&mut self, predicate: F) -> Vec<i32> {
+        let mut removed = Vec::new();
+
+        for queue in [&mut self.standard_queue, &mut self.recruitment_queue] {
+            queue.retain(|telegram| {
+                if predicate(telegram) {
+                    removed.push(telegram.job_id);
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        removed
+    }
+
+    /// Queued telegrams per queue, only those queued by `created_by` if set.
+    #[tracing::instrument(skip_all)]
+    fn list(&self, created_by: Option<&str>) -> HashMap<String, Vec<response::Telegram>> {
         let mut response = HashMap::new();
 
-        response.insert(
-            "recruitment".to_string(),
-            self.recruitment_queue
-                .iter()
-                .map(|tg| response::Telegram::new(&tg.recipient, &tg.telegram_id))
-                .collect(),
-        );
+        for (name, queue) in [
+            ("recruitment", &self.recruitment_queue),
+            ("standard", &self.standard_queue),
+        ] {
+            response.insert(
+                name.to_string(),
+                queue
+                    .iter()
+                    .filter(|tg| created_by.is_none_or(|created_by| tg.created_by == created_by))
+                    .map(|tg| {
+                        response::Telegram::new(
+                            &tg.sender,
+                            &tg.recipient,
+                            &tg.telegram_id,
+                            &tg.created_by,
+                        )
+                    })
+                    .collect(),
+            );
+        }
 
-        response.insert(
-            "standard".to_string(),
-            self.standard_queue
-                .iter()
-                .map(|tg| response::Telegram::new(&tg.recipient, &tg.telegram_id))
-                .collect(),
+        response
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn summary(&self) -> BTreeMap<String, response::TelegramSenderSummary> {
+        let mut summary = tally(
+            &self.recruitment_queue,
+            &self.standard_queue,
+            self.keys.senders(),
         );
 
-        response
+        for (sender, entry) in summary.iter_mut() {
+            entry.recruitment_ready_in = self
+                .limiter
+                .peek(Target::recruitment(sender))
+                .await
+                .as_secs_f64()
+                .ceil() as u64;
+
+            entry.standard_ready_in = self
+                .limiter
+                .peek(Target::telegram(sender))
+                .await
+                .as_secs_f64()
+                .ceil() as u64;
+        }
+
+        summary
+    }
+
+    /// Recruitment capacity of every configured sender and every sender with recruitment
+    /// telegrams queued or recently sent.
+    #[tracing::instrument(skip_all)]
+    async fn stats(&mut self) -> BTreeMap<String, response::TelegramCapacity> {
+        let now = Utc::now();
+
+        prune(&mut self.recruitment_sent, now);
+
+        let mut counts: BTreeMap<String, response::CapacityCounts> = self
+            .keys
+            .senders()
+            .into_iter()
+            .map(|sender| (sender, Default::default()))
+            .collect();
+
+        for (sender, _) in &self.recruitment_sent {
+            counts.entry(sender.clone()).or_default().sent_last_24h += 1;
+        }
+
+        for telegram in &self.recruitment_queue {
+            counts
+                .entry(name::canonicalize(&telegram.sender))
+                .or_default()
+                .queued += 1;
+        }
+
+        match sqlx::query(
+            "SELECT telegram_campaigns.sender, COUNT(*) AS backlog
+            FROM telegram_campaign_recipients
+            JOIN telegram_campaigns ON telegram_campaigns.id = telegram_campaign_recipients.campaign_id
+            WHERE telegram_campaigns.status = 'active'
+            AND telegram_campaigns.tg_type = 'recruitment'
+            AND telegram_campaign_recipients.job_id IS NULL
+            GROUP BY telegram_campaigns.sender;",
+        )
+        .map(|row: PgRow| {
+            (
+                name::canonicalize(row.get::<&str, _>("sender")),
+                row.get::<i64, _>("backlog") as usize,
+            )
+        })
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(backlogs) => {
+                for (sender, backlog) in backlogs {
+                    counts.entry(sender).or_default().campaign_backlog += backlog;
+                }
+            }
+            Err(e) => tracing::error!("{}", e),
+        }
+
+        let cooldown = self.limiter.recruitment_cooldown();
+        let mut stats = BTreeMap::new();
+
+        for (sender, mut known) in counts {
+            let ready_in = self.limiter.peek(Target::recruitment(&sender)).await;
+
+            known.cooldown_secs = cooldown.as_secs_f64().ceil() as u64;
+            known.ready_in = ready_in.as_secs_f64().ceil() as u64;
+
+            let estimated = estimate(
+                known.queued + known.campaign_backlog,
+                ready_in,
+                cooldown,
+                now,
+            );
+
+            stats.insert(sender, response::TelegramCapacity { known, estimated });
+        }
+
+        stats
     }
 
     #[tracing::instrument(skip_all)]
     async fn try_send(&mut self) {
+        if self.pipeline.is_paused() {
+            return self
+                .pipeline
+                .waiting(self.recruitment_queue.len() + self.standard_queue.len());
+        }
+
         if let Some(telegram) = self.get_telegram().await {
-            if let Err(e) = self.send(telegram).await {
-                tracing::error!("failed to send telegram: {}", e);
+            let job_id = telegram.job_id;
+            let recipient = telegram.recipient.clone();
+            let recruitment = match telegram.tg_type {
+                TgType::Recruitment => Some(name::canonicalize(&telegram.sender)),
+                TgType::Standard => None,
+            };
+
+            let span = tracing::info_span!(
+                "job",
+                job_id,
+                nation = name::canonicalize(&telegram.sender),
+                recipient,
+            );
+
+            async {
+                self.pipeline.posting(job_id, &telegram.sender);
+
+                let result = self.send(&telegram).await;
+
+                self.pipeline.finished(job_id, result.as_ref().err());
+
+                match result {
+                    Ok(()) => {
+                        if let Some(sender) = recruitment {
+                            let now = Utc::now();
+                            prune(&mut self.recruitment_sent, now);
+                            self.recruitment_sent.push_back((sender, now));
+                        }
+
+                        self.update_job(job_id, "sent", None).await;
+                    }
+                    Err(Error::NationStates(NsError::RecruitmentRateLimited))
+                        if telegram.tg_type == TgType::Standard =>
+                    {
+                        self.correct(telegram).await;
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            reason = e.job_code().unwrap_or("unknown"),
+                            "failed to send telegram: {}",
+                            e
+                        );
+                        self.update_job(job_id, "failed", Some(&e)).await;
+                    }
+                }
             }
+            .instrument(span)
+            .await;
         }
+
+        self.pipeline
+            .waiting(self.recruitment_queue.len() + self.standard_queue.len());
     }
 
     #[tracing::instrument(skip_all)]
     async fn get_telegram(&mut self) -> Option<Telegram> {
-        for (index, telegram) in self.recruitment_queue.iter().enumerate() {
-            if self
-                .limiter
-                .peek(ratelimiter::Target::RecruitmentTelegram {
-                    sender: telegram.sender.clone(),
-                })
-                .await
-                <= PERIOD
-            {
-                return Some(self.recruitment_queue.remove(index).unwrap());
-            }
-        }
+        let recruitment = candidate(
+            &self.limiter,
+            heads(&self.recruitment_queue),
+            Target::recruitment,
+        )
+        .await;
+        let standard =
+            candidate(&self.limiter, heads(&self.standard_queue), Target::telegram).await;
 
-        for (index, telegram) in self.standard_queue.iter().enumerate() {
-            if self
-                .limiter
-                .peek(ratelimiter::Target::Telegram {
-                    sender: telegram.sender.clone(),
-                })
-                .await
-                <= PERIOD
+        // a standard telegram from the same nation also holds its recruitment back by the
+        // restricted action cooldown
+        let standard_cost = match (&recruitment, &standard) {
+            (Some(r), Some(s))
+                if name::canonicalize(&r.sender) == name::canonicalize(&s.sender) =>
             {
-                return Some(self.standard_queue.remove(index).unwrap());
+                self.limiter
+                    .telegram_cooldown()
+                    .max(self.limiter.restricted_action_cooldown())
             }
-        }
+            _ => self.limiter.telegram_cooldown(),
+        };
+
+        let queue = choose(
+            recruitment.as_ref().map(|candidate| candidate.wait),
+            standard.as_ref().map(|candidate| candidate.wait),
+            standard_cost,
+            self.last_queue,
+        )?;
+
+        self.last_queue = Some(queue);
 
-        None
+        match queue {
+            Queue::Recruitment => take(&mut self.recruitment_queue, recruitment?.job_id),
+            Queue::Standard => take(&mut self.standard_queue, standard?.job_id),
+        }
     }
 
     #[tracing::instrument(skip_all)]
-    async fn send(&mut self, telegram: Telegram) -> Result<(), Error> {
-        let target = match &telegram.tg_type {
-            TgType::Recruitment => Target::RecruitmentTelegram {
-                sender: telegram.sender.clone(),
-            },
-            TgType::Standard => Target::Telegram {
-                sender: telegram.sender.clone(),
-            },
-        };
+    async fn send(&mut self, telegram: &Telegram) -> Result<(), Error> {
+        let target = target(telegram);
+
+        let (slot, acquire) = self.limiter.acquire_slot(target.clone()).await;
 
-        if let Err(duration) = self.limiter.acquire(target).await {
+        if let Err(duration) = acquire {
             tracing::info!("sleeping for {} ms", duration.as_millis());
             tokio::time::sleep(duration).await;
         }
 
         tracing::debug!("sending telegram");
 
-        self.client
-            .get(&self.url)
-            .query(&telegram)
-            .send()
-            .await?
-            .error_for_status()?;
+        let resp = self.client.get(&self.url).query(&telegram).send().await?;
+
+        self.limiter.observe(resp.headers()).await;
+
+        let body = resp.error_for_status()?.text().await?;
+
+        if let Err(e) = telegram::check_sent(&body) {
+            // NS refused without sending anything, so the cooldown slot can be handed back
+            if e != NsError::RateLimited {
+                self.limiter.release(target, slot).await;
+            }
+
+            return Err(Error::NationStates(e));
+        }
 
         Ok(())
     }
@@ -174,31 +806,658 @@ impl Client {
     #[tracing::instrument(skip_all)]
     pub(crate) async fn run(&mut self) {
         let mut interval = tokio::time::interval(PERIOD);
+        let mut campaigns = tokio::time::interval(CAMPAIGN_PERIOD);
+
+        self.load_campaigns().await;
+        self.load_recruitment_sent().await;
 
         loop {
+            // commands first, so a pause always lands before the next tick
             tokio::select! {
+                biased;
+
                 Some(command) = self.rx.recv() => {
-                    self.process_command(command);
+                    self.process_command(command).await;
                 }
 
                 _  = interval.tick() => {
                     self.try_send().await;
                 }
 
+                _ = campaigns.tick() => {
+                    self.refill_campaigns().await;
+                }
+
             }
         }
     }
 }
 
+/// Number of campaign `id`'s telegrams waiting in the queues.
+fn campaign_pending(
+    recruitment_queue: &VecDeque<Telegram>,
+    standard_queue: &VecDeque<Telegram>,
+    id: i32,
+) -> usize {
+    recruitment_queue
+        .iter()
+        .chain(standard_queue)
+        .filter(|telegram| telegram.campaign_id == Some(id))
+        .count()
+}
+
+/// The limiter target `telegram` is held to, which follows its type.
+fn target(telegram: &Telegram) -> Target {
+    match &telegram.tg_type {
+        TgType::Recruitment => Target::recruitment(&telegram.sender),
+        TgType::Standard => Target::telegram(&telegram.sender),
+    }
+}
+
+/// Corrects the type of `telegram` if NS was found to give its template another, logging loudly
+/// as the telegram was queued to be held to the wrong cooldown.
+fn reclassify(types: &TelegramTypes, telegram: &mut Telegram) {
+    if let Some(tg_type) = types.cached(&telegram.telegram_id)
+        && tg_type != telegram.tg_type
+    {
+        tracing::error!(
+            job_id = telegram.job_id,
+            telegram_id = telegram.telegram_id,
+            queued_as = %telegram.tg_type,
+            %tg_type,
+            "telegram was queued with the wrong type, correcting it"
+        );
+
+        telegram.tg_type = tg_type;
+    }
+}
+
+/// Drops sends older than a [`DAY`] from the rolling count.
+fn prune(sent: &mut VecDeque<(String, DateTime<Utc>)>, now: DateTime<Utc>) {
+    while sent
+        .front()
+        .is_some_and(|(_, at)| (now - *at).to_std().is_ok_and(|age| age > DAY))
+    {
+        sent.pop_front();
+    }
+}
+
+/// Projections for a sender that can send again in `ready_in`, with `pending` recruitment
+/// telegrams left to send, one per `cooldown`.
+fn estimate(
+    pending: usize,
+    ready_in: Duration,
+    cooldown: Duration,
+    now: DateTime<Utc>,
+) -> response::CapacityEstimates {
+    let midnight = (now.date_naive() + chrono::Days::new(1))
+        .and_time(chrono::NaiveTime::MIN)
+        .and_utc();
+    let until_midnight = (midnight - now).to_std().unwrap_or_default();
+
+    // sends at `ready_in`, then every `cooldown`, strictly before midnight
+    let max_remaining_today = match until_midnight.checked_sub(ready_in) {
+        Some(left) if !left.is_zero() => {
+            (left.as_secs_f64() / cooldown.as_secs_f64()).ceil() as u64
+        }
+        _ => 0,
+    };
+
+    let projected_completion_at = pending.checked_sub(1).and_then(|after_first| {
+        chrono::Duration::from_std(ready_in + cooldown * after_first as u32)
+            .ok()
+            .map(|duration| now + duration)
+    });
+
+    response::CapacityEstimates {
+        max_per_day: (DAY.as_secs_f64() / cooldown.as_secs_f64()).floor() as u64,
+        max_remaining_today,
+        projected_completion_at,
+    }
+}
+
+/// Counts queued telegrams per sender. Every configured sender is included, even with nothing queued.
+fn tally(
+    recruitment_queue: &VecDeque<Telegram>,
+    standard_queue: &VecDeque<Telegram>,
+    senders: Vec<String>,
+) -> BTreeMap<String, response::TelegramSenderSummary> {
+    let mut summary: BTreeMap<String, response::TelegramSenderSummary> = senders
+        .into_iter()
+        .map(|sender| (sender, Default::default()))
+        .collect();
+
+    for telegram in recruitment_queue.iter().chain(standard_queue) {
+        let entry = summary.entry(telegram.sender.clone()).or_default();
+
+        match telegram.tg_type {
+            TgType::Recruitment => entry.recruitment_count += 1,
+            TgType::Standard => entry.standard_count += 1,
+        }
+
+        entry.oldest_queued_at = Some(match entry.oldest_queued_at {
+            Some(oldest) => oldest.min(telegram.queued_at),
+            None => telegram.queued_at,
+        });
+    }
+
+    summary
+}
+
+/// The two telegram queues, see [`choose`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Queue {
+    Recruitment,
+    Standard,
+}
+
+/// Picks the queue to send from next, if any. `recruitment` and `standard` are how long the
+/// soonest sendable telegram of each queue has to wait, `None` if the queue is empty.
+/// `standard_cost` is how far sending a standard telegram now would push back that recruitment
+/// telegram.
+///
+/// A ready recruitment telegram goes first. A ready standard telegram only jumps a waiting
+/// recruitment telegram if its cooldown is over before the recruitment telegram could have
+/// been sent anyway, so standard traffic can't starve recruitment. When both are ready right
+/// after a recruitment send they take turns, so a short recruitment cooldown can't starve
+/// standard telegrams either.
+fn choose(
+    recruitment: Option<Duration>,
+    standard: Option<Duration>,
+    standard_cost: Duration,
+    last: Option<Queue>,
+) -> Option<Queue> {
+    let ready = |wait: Option<Duration>| wait.is_some_and(|wait| wait <= PERIOD);
+
+    match (ready(recruitment), ready(standard)) {
+        (true, true) if last == Some(Queue::Recruitment) => Some(Queue::Standard),
+        (true, _) => Some(Queue::Recruitment),
+        (false, true) => match recruitment {
+            Some(wait) if standard_cost > wait => None,
+            _ => Some(Queue::Standard),
+        },
+        (false, false) => None,
+    }
+}
+
+/// A telegram that could be sent next, with how long its sender still has to wait.
+struct Candidate {
+    job_id: i32,
+    sender: String,
+    wait: Duration,
+}
+
+/// The first telegram of each sender in `queue`, as its job id and sender. A sender's other
+/// telegrams wait on the same limits, so only these need to be peeked. Collected up front so
+/// nothing is read from the queue across the limiter round trips.
+fn heads(queue: &VecDeque<Telegram>) -> Vec<(i32, String)> {
+    let mut seen = BTreeSet::new();
+
+    queue
+        .iter()
+        .filter(|telegram| seen.insert(telegram.sender.as_str()))
+        .map(|telegram| (telegram.job_id, telegram.sender.clone()))
+        .collect()
+}
+
+/// Of `heads`, the telegram that can be sent soonest.
+async fn candidate(
+    limiter: &ratelimiter::Sender,
+    heads: Vec<(i32, String)>,
+    target: fn(&str) -> Target,
+) -> Option<Candidate> {
+    let mut best: Option<Candidate> = None;
+
+    for (job_id, sender) in heads {
+        let wait = limiter.peek(target(&sender)).await;
+
+        if best.as_ref().is_none_or(|best| wait < best.wait) {
+            best = Some(Candidate {
+                job_id,
+                sender,
+                wait,
+            });
+        }
+
+        if wait <= PERIOD {
+            break;
+        }
+    }
+
+    best
+}
+
+/// Removes the telegram of job `job_id` from `queue`, wherever it ended up.
+fn take(queue: &mut VecDeque<Telegram>, job_id: i32) -> Option<Telegram> {
+    let index = queue
+        .iter()
+        .position(|telegram| telegram.job_id == job_id)?;
+
+    queue.remove(index)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn new(
     user_agent: &str,
     url: &str,
-    key: String,
+    pool: PgPool,
+    keys: ClientKeys,
+    types: TelegramTypes,
     limiter: ratelimiter::Sender,
+    capacity: usize,
+    activity: Activity,
 ) -> Result<(mpsc::Sender<Command>, Client), ConfigError> {
-    let (tx, rx) = mpsc::channel(16);
+    let (tx, rx) = mpsc::channel(capacity.max(1));
 
-    let client = Client::new(user_agent, url, key, limiter, rx)?;
+    let client = Client {
+        pipeline: Pipeline::reporting("telegram", activity),
+        ..Client::new(user_agent, url, pool, keys, types, limiter, rx)?
+    };
 
     Ok((tx, client))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TELEGRAM_COOLDOWN: Duration = Duration::from_secs(30);
+
+    fn secs(secs: u64) -> Option<Duration> {
+        Some(Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn test_choose_sends_ready_recruitment_first() {
+        assert_eq!(
+            choose(secs(0), secs(0), TELEGRAM_COOLDOWN, None),
+            Some(Queue::Recruitment)
+        );
+        assert_eq!(
+            choose(secs(0), None, TELEGRAM_COOLDOWN, Some(Queue::Recruitment)),
+            Some(Queue::Recruitment)
+        );
+        assert_eq!(choose(None, None, TELEGRAM_COOLDOWN, None), None);
+        assert_eq!(choose(None, secs(5), TELEGRAM_COOLDOWN, None), None);
+    }
+
+    #[test]
+    fn test_choose_fills_recruitment_cooldown_with_standard() {
+        assert_eq!(
+            choose(secs(170), secs(0), TELEGRAM_COOLDOWN, None),
+            Some(Queue::Standard)
+        );
+        assert_eq!(
+            choose(None, secs(0), TELEGRAM_COOLDOWN, Some(Queue::Standard)),
+            Some(Queue::Standard)
+        );
+        // the standard cooldown runs out exactly when recruitment would have been ready
+        assert_eq!(
+            choose(secs(30), secs(0), TELEGRAM_COOLDOWN, None),
+            Some(Queue::Standard)
+        );
+    }
+
+    /// A recruitment telegram that's nearly ready mustn't be pushed back by a standard one.
+    #[test]
+    fn test_choose_does_not_starve_recruitment() {
+        assert_eq!(
+            choose(
+                Some(PERIOD + Duration::from_millis(1)),
+                secs(0),
+                TELEGRAM_COOLDOWN,
+                None
+            ),
+            None
+        );
+        assert_eq!(choose(secs(29), secs(0), TELEGRAM_COOLDOWN, None), None);
+        // same sender, so the restricted action cooldown applies as well
+        assert_eq!(
+            choose(secs(45), secs(0), Duration::from_secs(60), None),
+            None
+        );
+    }
+
+    /// With a recruitment cooldown no longer than the telegram cooldown, recruitment is ready
+    /// again on every tick; standard telegrams still get every other send.
+    #[test]
+    fn test_choose_does_not_starve_standard() {
+        assert_eq!(
+            choose(
+                secs(0),
+                secs(0),
+                TELEGRAM_COOLDOWN,
+                Some(Queue::Recruitment)
+            ),
+            Some(Queue::Standard)
+        );
+        assert_eq!(
+            choose(secs(0), secs(0), TELEGRAM_COOLDOWN, Some(Queue::Standard)),
+            Some(Queue::Recruitment)
+        );
+    }
+
+    #[test]
+    fn test_client_keys_per_sender() {
+        let keys = ClientKeys::new(None, Some("recruiter_one:key1, recruiter_two : key2")).unwrap();
+
+        assert_eq!(keys.get("recruiter_one"), Some(&Secret::from("key1")));
+        assert_eq!(keys.get("recruiter_two"), Some(&Secret::from("key2")));
+        assert_eq!(keys.get("someone_else"), None);
+        assert_eq!(keys.senders(), vec!["recruiter_one", "recruiter_two"]);
+    }
+
+    #[test]
+    fn test_client_keys_default_fallback() {
+        let keys = ClientKeys::new(Some("default".to_string()), Some("recruiter:key1")).unwrap();
+
+        assert_eq!(keys.get("recruiter"), Some(&Secret::from("key1")));
+        assert_eq!(keys.get("someone_else"), Some(&Secret::from("default")));
+
+        let keys = ClientKeys::new(Some("default".to_string()), None).unwrap();
+
+        assert_eq!(keys.get("anyone"), Some(&Secret::from("default")));
+    }
+
+    #[test]
+    fn test_client_keys_invalid() {
+        assert!(ClientKeys::new(None, None).is_err());
+        assert!(ClientKeys::new(None, Some("")).is_err());
+        assert!(ClientKeys::new(None, Some("recruiter")).is_err());
+        assert!(ClientKeys::new(None, Some("recruiter:")).is_err());
+    }
+
+    #[test]
+    fn test_client_keys_redacted() {
+        let keys =
+            ClientKeys::new(Some("default-key".to_string()), Some("recruiter:key1")).unwrap();
+        let debug = format!("{keys:?}");
+
+        assert!(!debug.contains("default-key"), "{debug}");
+        assert!(!debug.contains("key1"), "{debug}");
+
+        // a malformed pair is reported by position, not echoed
+        let error = ClientKeys::new(None, Some("recruiter:key1,:key2"))
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.contains("entry 2"), "{error}");
+        assert!(!error.contains("key2"), "{error}");
+    }
+
+    fn telegram(keys: &ClientKeys, id: i32, sender: &str, tg_type: TgType) -> Telegram {
+        Telegram::from_params(
+            keys,
+            Job {
+                id,
+                params: crate::ns::telegram::Params {
+                    sender: sender.to_string(),
+                    id: "1".to_string(),
+                    recipient: "recipient".to_string(),
+                    secret_key: crate::types::Secret::new("secret"),
+                    tg_type,
+                },
+                created_by: "recruiter".to_string(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_campaign_pending() {
+        let keys = ClientKeys::new(Some("default".to_string()), None).unwrap();
+
+        let mut campaign = telegram(&keys, 1, "recruiter", TgType::Recruitment);
+        campaign.campaign_id = Some(7);
+        let mut other = telegram(&keys, 2, "recruiter", TgType::Standard);
+        other.campaign_id = Some(8);
+
+        let recruitment = VecDeque::from([
+            campaign.clone(),
+            campaign,
+            telegram(&keys, 3, "recruiter", TgType::Recruitment),
+        ]);
+        let standard = VecDeque::from([other]);
+
+        assert_eq!(campaign_pending(&recruitment, &standard, 7), 2);
+        assert_eq!(campaign_pending(&recruitment, &standard, 8), 1);
+        assert_eq!(campaign_pending(&recruitment, &standard, 9), 0);
+    }
+
+    #[test]
+    fn test_tally() {
+        let keys = ClientKeys::new(Some("default".to_string()), Some("idle:key")).unwrap();
+
+        let first = telegram(&keys, 1, "recruiter", TgType::Recruitment);
+        let recruitment = VecDeque::from([
+            first.clone(),
+            telegram(&keys, 2, "recruiter", TgType::Recruitment),
+        ]);
+        let standard = VecDeque::from([
+            telegram(&keys, 3, "recruiter", TgType::Standard),
+            telegram(&keys, 4, "announcer", TgType::Standard),
+        ]);
+
+        let summary = tally(&recruitment, &standard, keys.senders());
+
+        assert_eq!(
+            summary.keys().collect::<Vec<_>>(),
+            vec!["announcer", "idle", "recruiter"]
+        );
+
+        assert_eq!(summary["recruiter"].recruitment_count, 2);
+        assert_eq!(summary["recruiter"].standard_count, 1);
+        assert_eq!(summary["recruiter"].oldest_queued_at, Some(first.queued_at));
+
+        assert_eq!(summary["announcer"].recruitment_count, 0);
+        assert_eq!(summary["announcer"].standard_count, 1);
+
+        assert_eq!(summary["idle"], response::TelegramSenderSummary::default());
+    }
+
+    #[test]
+    fn test_estimate() {
+        let cooldown = Duration::from_secs(180);
+        let at = |time: &str| {
+            DateTime::parse_from_rfc3339(&format!("2026-10-15T{time}Z"))
+                .unwrap()
+                .to_utc()
+        };
+
+        let idle = estimate(0, Duration::ZERO, cooldown, at("00:00:00"));
+        assert_eq!(idle.max_per_day, 480);
+        assert_eq!(idle.max_remaining_today, 480);
+        assert_eq!(idle.projected_completion_at, None);
+
+        // ready in a minute with ten minutes left: sends at +1, +4, +7 minutes
+        let busy = estimate(10, Duration::from_secs(60), cooldown, at("23:50:00"));
+        assert_eq!(busy.max_remaining_today, 3);
+        assert_eq!(
+            busy.projected_completion_at,
+            Some(at("23:51:00") + chrono::Duration::seconds(9 * 180))
+        );
+
+        let late = estimate(1, Duration::from_secs(120), cooldown, at("23:59:00"));
+        assert_eq!(late.max_remaining_today, 0);
+        assert_eq!(
+            late.projected_completion_at,
+            Some(at("23:59:00") + chrono::Duration::seconds(120))
+        );
+    }
+
+    #[test]
+    fn test_prune() {
+        let now = Utc::now();
+        let mut sent = VecDeque::from([
+            ("recruiter".to_string(), now - chrono::Duration::hours(25)),
+            ("recruiter".to_string(), now - chrono::Duration::hours(23)),
+            ("other".to_string(), now),
+        ]);
+
+        prune(&mut sent, now);
+
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].1, now - chrono::Duration::hours(23));
+    }
+
+    /// Stands in for the NS API, answering every `sendTG` with `body`.
+    async fn serve(body: &'static str) -> String {
+        let app = axum::Router::new().route("/", axum::routing::get(move || async move { body }));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        format!("http://{address}/")
+    }
+
+    async fn send_to(body: &'static str) -> (Result<(), Error>, Duration) {
+        let url = serve(body).await;
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgresql://localhost/eurocore")
+            .unwrap();
+        let limiter = ratelimiter::new(
+            50,
+            Duration::from_secs(30),
+            Duration::from_secs(30),
+            Duration::from_secs(180),
+            Duration::from_secs(60),
+            Default::default(),
+            0.8,
+        );
+        let keys = ClientKeys::new(Some("key".to_string()), None).unwrap();
+        let (_, rx) = mpsc::channel(1);
+
+        let types = TelegramTypes::new(pool.clone());
+
+        let mut client = Client::new(
+            "testlandia",
+            &url,
+            pool,
+            keys.clone(),
+            types,
+            limiter.clone(),
+            rx,
+        )
+        .unwrap();
+
+        let result = client
+            .send(&telegram(&keys, 1, "recruiter", TgType::Recruitment))
+            .await;
+
+        (result, limiter.peek(Target::recruitment("recruiter")).await)
+    }
+
+    #[tokio::test]
+    async fn test_send_keeps_cooldown_when_queued() {
+        let (result, wait) = send_to("queued\n").await;
+
+        assert!(result.is_ok());
+        assert!(wait > Duration::from_secs(170));
+    }
+
+    #[tokio::test]
+    async fn test_send_refused_releases_cooldown() {
+        let (result, wait) = send_to("Recipient is not accepting recruitment telegrams.").await;
+
+        assert!(matches!(
+            result,
+            Err(Error::NationStates(NsError::RecruitmentBlocked))
+        ));
+        assert_eq!(wait, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_heads_one_per_sender() {
+        let keys = ClientKeys::new(Some("default".to_string()), None).unwrap();
+
+        let queue = VecDeque::from([
+            telegram(&keys, 1, "recruiter", TgType::Standard),
+            telegram(&keys, 2, "recruiter", TgType::Standard),
+            telegram(&keys, 3, "announcer", TgType::Standard),
+            telegram(&keys, 4, "recruiter", TgType::Standard),
+        ]);
+
+        assert_eq!(
+            heads(&queue),
+            vec![(1, "recruiter".to_string()), (3, "announcer".to_string())]
+        );
+    }
+
+    /// Telegrams keep arriving while the worker drains a queue of several hundred; each must be
+    /// picked exactly once.
+    #[tokio::test]
+    async fn test_interleaved_pushes_lose_nothing() {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgresql://localhost/eurocore")
+            .unwrap();
+        let limiter = ratelimiter::new(
+            50,
+            Duration::from_secs(30),
+            Duration::from_secs(30),
+            Duration::from_secs(180),
+            Duration::from_secs(60),
+            Default::default(),
+            0.8,
+        );
+        let keys = ClientKeys::new(Some("key".to_string()), None).unwrap();
+        let (_, rx) = mpsc::channel(1);
+
+        let types = TelegramTypes::new(pool.clone());
+
+        let mut client = Client::new(
+            "testlandia",
+            "http://127.0.0.1:9/",
+            pool,
+            keys.clone(),
+            types,
+            limiter,
+            rx,
+        )
+        .unwrap();
+
+        let senders = ["recruiter", "announcer", "Recruiter", "herald"];
+        let mut picked = Vec::new();
+        let mut next_id = 1;
+
+        while next_id <= 400
+            || !client.recruitment_queue.is_empty()
+            || !client.standard_queue.is_empty()
+        {
+            // a burst of commands between two ticks
+            for _ in 0..3 {
+                if next_id <= 400 {
+                    let sender = senders[next_id as usize % senders.len()];
+
+                    if next_id % 3 == 0 {
+                        client.recruitment_queue.push_back(telegram(
+                            &keys,
+                            next_id,
+                            sender,
+                            TgType::Recruitment,
+                        ));
+                    } else {
+                        client.standard_queue.push_back(telegram(
+                            &keys,
+                            next_id,
+                            sender,
+                            TgType::Standard,
+                        ));
+                    }
+
+                    next_id += 1;
+                }
+            }
+
+            // nothing is sent, so every sender stays ready
+            if let Some(telegram) = client.get_telegram().await {
+                picked.push(telegram.job_id);
+            }
+        }
+
+        picked.sort();
+        picked.dedup();
+        assert_eq!(picked, (1..=400).collect::<Vec<_>>());
+    }
+}